@@ -1,14 +1,29 @@
 use eframe::egui::{self, Color32, RichText, Ui};
+use std::collections::BTreeMap;
 use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
 
 // 导入各个模块
-use crate::firewall::FirewallModule;
+use crate::firewall::{FirewallModule, ProtectionModule};
 use crate::tor::TorModule;
 use crate::dnscrypt::DnsCryptModule;
 use crate::i2p::I2PModule;
 use crate::proxy::ProxyModule;
 use crate::vpn::VpnModule;
-use crate::logger::Logger;
+use crate::logger::{Logger, LogLevel};
+use crate::scheduler::{Scheduler, ScheduledAction};
+use crate::diagnostics::DiagnosticsPanel;
+use crate::leaktest::LeakTestModule;
+use crate::monitor::{MonitorModule, RouteContext};
+use crate::utils::{self, PublicIpInfo};
+use crate::geoip;
+use crate::netif;
+use crate::traffic;
+use crate::services;
+use crate::supervisor::{self, SupervisorEventKind};
+use crate::components::{ComponentId, ComponentManager};
+use crate::macaddr::MacModule;
+use crate::system_dns::SystemDns;
 
 // 定义模块颜色
 pub const TOR_COLOR: Color32 = Color32::from_rgb(89, 49, 107); // 洋葱色
@@ -18,9 +33,37 @@ pub const FIREWALL_COLOR: Color32 = Color32::from_rgb(220, 53, 69); // 红色
 pub const SETTINGS_COLOR: Color32 = Color32::from_rgb(140, 192, 170); // 青色
 pub const LOG_COLOR: Color32 = Color32::from_rgb(108, 117, 125); // 灰色
 pub const VPN_COLOR: Color32 = Color32::from_rgb(0, 150, 136); // 青绿色
+pub const LEAKTEST_COLOR: Color32 = Color32::from_rgb(253, 126, 20); // 橙色
+pub const MONITOR_COLOR: Color32 = Color32::from_rgb(40, 167, 69); // 绿色
+
+// 需要管理员权限的功能在未提权时显示的提示，提供以管理员身份重新启动的入口
+pub fn render_admin_required(ui: &mut Ui, logger: &Arc<Mutex<Logger>>, feature: &str) {
+    ui.horizontal(|ui| {
+        ui.label(RichText::new(format!("⚠ {}需要管理员权限", feature)).color(Color32::YELLOW).strong());
+        if ui.button("以管理员身份重新启动").clicked() {
+            if let Err(e) = utils::relaunch_as_admin() {
+                if let Ok(mut logger) = logger.lock() {
+                    logger.warning("App", &format!("未能以管理员身份重新启动: {}", e));
+                }
+            }
+        }
+    });
+}
+
+// 公网IP自动刷新间隔
+const IP_CHECK_INTERVAL: Duration = Duration::from_secs(300);
+
+// 公网IP检测状态
+#[derive(Clone)]
+enum IpCheckState {
+    Idle,
+    Checking,
+    Done(PublicIpInfo),
+    Failed(String),
+}
 
 // 定义应用程序的标签页
-#[derive(PartialEq)]
+#[derive(PartialEq, Clone, Copy)]
 enum Tab {
     Tor,
     DnsCrypt,
@@ -28,10 +71,103 @@ enum Tab {
     Firewall,
     Proxy,
     VPN,
+    Monitor,
+    LeakTest,
     Logs,
     Settings,
 }
 
+// 标签页顺序（前9个对应Ctrl+1..9快捷键）
+const TABS: [(Tab, &str, Color32); 10] = [
+    (Tab::Tor, "Tor", TOR_COLOR),
+    (Tab::DnsCrypt, "DNSCrypt", DNS_COLOR),
+    (Tab::I2P, "I2P", I2P_COLOR),
+    (Tab::Firewall, "防火墙", FIREWALL_COLOR),
+    (Tab::Proxy, "代理", SETTINGS_COLOR),
+    (Tab::VPN, "VPN", VPN_COLOR),
+    (Tab::Monitor, "流量监控", MONITOR_COLOR),
+    (Tab::LeakTest, "泄露检测", LEAKTEST_COLOR),
+    (Tab::Logs, "日志", LOG_COLOR),
+    (Tab::Settings, "设置", SETTINGS_COLOR),
+];
+
+// 标签页快捷键
+const TAB_KEYS: [egui::Key; 9] = [
+    egui::Key::Num1,
+    egui::Key::Num2,
+    egui::Key::Num3,
+    egui::Key::Num4,
+    egui::Key::Num5,
+    egui::Key::Num6,
+    egui::Key::Num7,
+    egui::Key::Num8,
+    egui::Key::Num9,
+];
+
+// 命令面板中的命令
+#[derive(Clone, Copy)]
+enum PaletteCommand {
+    ToggleTor,
+    ToggleDnsCrypt,
+    ToggleI2P,
+    ToggleProxy,
+    ToggleFirewall,
+    NewFirewallRule,
+    TestVpnLatency,
+    UpdateSubscriptions,
+    RunDiagnostics,
+    OpenTab(Tab),
+}
+
+impl PaletteCommand {
+    // 所有命令
+    fn all() -> Vec<Self> {
+        let mut commands = vec![
+            PaletteCommand::ToggleTor,
+            PaletteCommand::ToggleDnsCrypt,
+            PaletteCommand::ToggleI2P,
+            PaletteCommand::ToggleProxy,
+            PaletteCommand::ToggleFirewall,
+            PaletteCommand::NewFirewallRule,
+            PaletteCommand::TestVpnLatency,
+            PaletteCommand::UpdateSubscriptions,
+            PaletteCommand::RunDiagnostics,
+        ];
+        commands.extend(TABS.iter().map(|(tab, _, _)| PaletteCommand::OpenTab(*tab)));
+        commands
+    }
+    
+    // 命令显示名称及英文关键字（便于搜索）
+    fn label(&self) -> (String, &'static str) {
+        match self {
+            PaletteCommand::ToggleTor => ("启动/停止Tor".to_string(), "start stop tor"),
+            PaletteCommand::ToggleDnsCrypt => ("启动/停止DNSCrypt".to_string(), "start stop dnscrypt dns"),
+            PaletteCommand::ToggleI2P => ("启动/停止I2P".to_string(), "start stop i2p"),
+            PaletteCommand::ToggleProxy => ("启动/停止代理".to_string(), "start stop proxy"),
+            PaletteCommand::ToggleFirewall => ("启用/禁用防火墙".to_string(), "enable disable firewall"),
+            PaletteCommand::NewFirewallRule => ("新建防火墙规则".to_string(), "new firewall rule"),
+            PaletteCommand::TestVpnLatency => ("测试VPN延迟".to_string(), "test vpn latency ping"),
+            PaletteCommand::UpdateSubscriptions => ("更新VPN订阅".to_string(), "update vpn subscriptions"),
+            PaletteCommand::RunDiagnostics => ("重新检测冲突软件".to_string(), "run diagnostics conflicts"),
+            PaletteCommand::OpenTab(tab) => {
+                let name = TABS.iter().find(|(t, _, _)| t == tab).map(|(_, name, _)| *name).unwrap_or("");
+                (format!("打开标签页: {}", name), "open tab go to")
+            }
+        }
+    }
+    
+    // 是否匹配搜索关键字
+    fn matches(&self, query: &str) -> bool {
+        let query = query.trim().to_lowercase();
+        if query.is_empty() {
+            return true;
+        }
+        let (label, keywords) = self.label();
+        let haystack = format!("{} {}", label, keywords).to_lowercase();
+        query.split_whitespace().all(|word| haystack.contains(word))
+    }
+}
+
 // 主应用程序结构
 pub struct InviZibleApp {
     current_tab: Tab,
@@ -41,7 +177,24 @@ pub struct InviZibleApp {
     firewall_module: FirewallModule,
     proxy_module: ProxyModule,
     vpn_module: VpnModule,
+    monitor_module: MonitorModule,
+    leaktest_module: LeakTestModule,
+    scheduler: Scheduler,
+    diagnostics: DiagnosticsPanel,
+    components: ComponentManager,
+    mac_module: MacModule,
+    system_dns: SystemDns,
     logger: Arc<Mutex<Logger>>,
+    palette_open: bool,
+    palette_query: String,
+    palette_selected: usize,
+    ip_check: Arc<Mutex<IpCheckState>>,
+    last_ip_check: Option<Instant>,
+    last_ip_route: Option<String>,
+    last_network_generation: u64,
+    service_alerts: BTreeMap<String, String>, // 进程名 -> 异常描述
+    restart_tor_after_update: bool,
+    restart_snowflake_after_update: bool,
 }
 
 impl InviZibleApp {
@@ -56,6 +209,15 @@ impl InviZibleApp {
         if let Ok(mut log) = logger.lock() {
             log.info("App", "InviZible Pro已启动");
         }
+        geoip::init(Arc::clone(&logger));
+        netif::start_watcher(Arc::clone(&logger));
+        traffic::start_flusher(Arc::clone(&logger));
+        
+        // 已安装Tor时在后台检查是否有新版本
+        let components = ComponentManager::new(Arc::clone(&logger));
+        if components.installed_version(ComponentId::Tor).is_some() {
+            components.check_update(ComponentId::Tor);
+        }
         
         // 创建应用程序实例
         Self {
@@ -66,7 +228,24 @@ impl InviZibleApp {
             firewall_module: FirewallModule::new(Arc::clone(&logger)),
             proxy_module: ProxyModule::new(Arc::clone(&logger)),
             vpn_module: VpnModule::new(Arc::clone(&logger)),
+            monitor_module: MonitorModule::new(Arc::clone(&logger)),
+            leaktest_module: LeakTestModule::new(Arc::clone(&logger)),
+            scheduler: Scheduler::new(Arc::clone(&logger)),
+            diagnostics: DiagnosticsPanel::new(Arc::clone(&logger)),
+            components,
+            mac_module: MacModule::new(Arc::clone(&logger)),
+            system_dns: SystemDns::new(Arc::clone(&logger)),
             logger,
+            palette_open: false,
+            palette_query: String::new(),
+            palette_selected: 0,
+            ip_check: Arc::new(Mutex::new(IpCheckState::Idle)),
+            last_ip_check: None,
+            last_ip_route: None,
+            last_network_generation: 0,
+            service_alerts: BTreeMap::new(),
+            restart_tor_after_update: false,
+            restart_snowflake_after_update: false,
         }
     }
     
@@ -76,31 +255,467 @@ impl InviZibleApp {
             ui.horizontal_wrapped(|ui| {
                 ui.spacing_mut().item_spacing.x = 10.0;
                 
-                self.tab_button(ui, Tab::Tor, "Tor", TOR_COLOR);
-                self.tab_button(ui, Tab::DnsCrypt, "DNSCrypt", DNS_COLOR);
-                self.tab_button(ui, Tab::I2P, "I2P", I2P_COLOR);
-                self.tab_button(ui, Tab::Firewall, "防火墙", FIREWALL_COLOR);
-                self.tab_button(ui, Tab::Proxy, "代理", SETTINGS_COLOR);
-                self.tab_button(ui, Tab::VPN, "VPN", VPN_COLOR);
-                self.tab_button(ui, Tab::Logs, "日志", LOG_COLOR);
-                self.tab_button(ui, Tab::Settings, "设置", SETTINGS_COLOR);
+                for (index, (tab, name, color)) in TABS.iter().enumerate() {
+                    self.tab_button(ui, *tab, name, *color, (index < TAB_KEYS.len()).then_some(index + 1));
+                }
+                
+                ui.with_layout(egui::Layout::right_to_left(egui::Align::Center), |ui| {
+                    if ui.button("命令 (Ctrl+K)").clicked() {
+                        self.open_palette();
+                    }
+                });
             });
         });
     }
     
     // 创建标签页按钮
-    fn tab_button(&mut self, ui: &mut Ui, tab: Tab, name: &str, color: Color32) {
+    fn tab_button(&mut self, ui: &mut Ui, tab: Tab, name: &str, color: Color32, shortcut: Option<usize>) {
         let selected = self.current_tab == tab;
         let text = RichText::new(name).color(if selected { color } else { Color32::GRAY });
         // 使用fill方法代替selected方法
         let button = egui::Button::new(text)
             .fill(if selected { ui.style().visuals.selection.bg_fill } else { ui.style().visuals.widgets.inactive.bg_fill });
         
-        if ui.add(button).clicked() {
+        let mut response = ui.add(button);
+        if let Some(shortcut) = shortcut {
+            response = response.on_hover_text(format!("Ctrl+{}", shortcut));
+        }
+        if response.clicked() {
             self.current_tab = tab;
         }
     }
     
+    // 处理全局快捷键
+    fn handle_shortcuts(&mut self, ctx: &egui::Context) {
+        let (ctrl, tab_index, palette) = ctx.input(|i| {
+            let tab_index = TAB_KEYS.iter().position(|key| i.key_pressed(*key));
+            (i.modifiers.command, tab_index, i.key_pressed(egui::Key::K))
+        });
+        if !ctrl {
+            return;
+        }
+        
+        if let Some(index) = tab_index {
+            self.current_tab = TABS[index].0;
+        }
+        if palette {
+            if self.palette_open {
+                self.palette_open = false;
+            } else {
+                self.open_palette();
+            }
+        }
+    }
+    
+    // 打开命令面板
+    fn open_palette(&mut self) {
+        self.palette_open = true;
+        self.palette_query.clear();
+        self.palette_selected = 0;
+    }
+    
+    // 执行命令面板中的命令
+    fn execute_command(&mut self, command: PaletteCommand) {
+        if let Ok(mut logger) = self.logger.lock() {
+            logger.debug("App", &format!("执行命令: {}", command.label().0));
+        }
+        
+        match command {
+            PaletteCommand::ToggleTor => {
+                let enabled = self.tor_module.is_enabled();
+                self.tor_module.set_enabled(!enabled);
+            },
+            PaletteCommand::ToggleDnsCrypt => {
+                let enabled = self.dnscrypt_module.is_enabled();
+                self.dnscrypt_module.set_enabled(!enabled);
+            },
+            PaletteCommand::ToggleI2P => {
+                let enabled = self.i2p_module.is_enabled();
+                self.i2p_module.set_enabled(!enabled);
+            },
+            PaletteCommand::ToggleProxy => {
+                let enabled = self.proxy_module.is_enabled();
+                self.proxy_module.set_enabled(!enabled);
+            },
+            PaletteCommand::ToggleFirewall => {
+                let enabled = self.firewall_module.enabled;
+                self.firewall_module.set_enabled(!enabled);
+            },
+            PaletteCommand::NewFirewallRule => {
+                self.current_tab = Tab::Firewall;
+                self.firewall_module.open_rule_form(None);
+            },
+            PaletteCommand::TestVpnLatency => {
+                self.current_tab = Tab::Logs;
+                self.vpn_module.test_latency();
+            },
+            PaletteCommand::UpdateSubscriptions => self.vpn_module.update_all_subscriptions(),
+            PaletteCommand::RunDiagnostics => {
+                self.current_tab = Tab::Settings;
+                self.diagnostics.run_checks();
+            },
+            PaletteCommand::OpenTab(tab) => self.current_tab = tab,
+        }
+    }
+    
+    // 渲染命令面板
+    fn render_palette(&mut self, ctx: &egui::Context) {
+        if !self.palette_open {
+            return;
+        }
+        
+        let commands: Vec<PaletteCommand> = PaletteCommand::all()
+            .into_iter()
+            .filter(|c| c.matches(&self.palette_query))
+            .collect();
+        
+        // 键盘选择
+        let (up, down, enter, escape) = ctx.input(|i| (
+            i.key_pressed(egui::Key::ArrowUp),
+            i.key_pressed(egui::Key::ArrowDown),
+            i.key_pressed(egui::Key::Enter),
+            i.key_pressed(egui::Key::Escape),
+        ));
+        if escape {
+            self.palette_open = false;
+            return;
+        }
+        if down && self.palette_selected + 1 < commands.len() {
+            self.palette_selected += 1;
+        }
+        if up && self.palette_selected > 0 {
+            self.palette_selected -= 1;
+        }
+        self.palette_selected = self.palette_selected.min(commands.len().saturating_sub(1));
+        
+        let mut chosen = None;
+        if enter {
+            chosen = commands.get(self.palette_selected).copied();
+        }
+        
+        egui::Window::new("命令面板")
+            .collapsible(false)
+            .resizable(false)
+            .title_bar(false)
+            .anchor(egui::Align2::CENTER_TOP, egui::vec2(0.0, 80.0))
+            .show(ctx, |ui| {
+                let response = ui.add(
+                    egui::TextEdit::singleline(&mut self.palette_query)
+                        .hint_text("输入命令，如 start tor、new firewall rule ...")
+                        .desired_width(400.0)
+                );
+                // 面板打开期间输入框始终保持焦点
+                response.request_focus();
+                if response.changed() {
+                    self.palette_selected = 0;
+                }
+                
+                ui.separator();
+                
+                if commands.is_empty() {
+                    ui.label(RichText::new("没有匹配的命令").color(Color32::GRAY));
+                }
+                for (index, command) in commands.iter().enumerate() {
+                    let (label, _) = command.label();
+                    if ui.selectable_label(index == self.palette_selected, label).clicked() {
+                        chosen = Some(*command);
+                    }
+                }
+                
+                ui.separator();
+                ui.label(RichText::new("↑↓ 选择  Enter 执行  Esc 关闭").small().color(Color32::GRAY));
+            });
+        
+        if let Some(command) = chosen {
+            self.palette_open = false;
+            self.execute_command(command);
+        }
+    }
+    
+    // 获取当前活动隧道的代理地址（优先Tor，其次统一代理；VPN接管系统流量时直接访问即可）
+    fn active_tunnel_proxy(&self) -> Option<String> {
+        self.tor_module.socks_proxy_url().or_else(|| self.proxy_module.proxy_url())
+    }
+    
+    // 在后台线程中通过活动隧道检测公网IP
+    fn start_ip_check(&mut self) {
+        let route = self.active_tunnel_proxy();
+        self.last_ip_check = Some(Instant::now());
+        self.last_ip_route = route.clone();
+        self.last_network_generation = netif::generation();
+        
+        if let Ok(mut state) = self.ip_check.lock() {
+            *state = IpCheckState::Checking;
+        }
+        
+        let ip_check = Arc::clone(&self.ip_check);
+        let logger = Arc::clone(&self.logger);
+        std::thread::spawn(move || {
+            let result = utils::fetch_public_ip(route.as_deref());
+            let new_state = match result {
+                Ok(info) => IpCheckState::Done(info),
+                Err(e) => {
+                    if let Ok(mut logger) = logger.lock() {
+                        logger.warning("App", &format!("公网IP检测失败: {}", e));
+                    }
+                    IpCheckState::Failed(e.to_string())
+                }
+            };
+            if let Ok(mut state) = ip_check.lock() {
+                *state = new_state;
+            }
+        });
+    }
+    
+    // 渲染底部状态栏
+    fn render_status_bar(&mut self, ctx: &egui::Context) {
+        // 隧道变化或超过刷新间隔时重新检测公网IP
+        let route = self.active_tunnel_proxy();
+        let checking = matches!(self.ip_check.lock().map(|s| s.clone()), Ok(IpCheckState::Checking));
+        let expired = self.last_ip_check.map_or(true, |t| t.elapsed() >= IP_CHECK_INTERVAL);
+        // 网络变化（切换网卡、重新连接等）后也需要重新检测
+        let network_changed = netif::generation() != self.last_network_generation;
+        if !checking && (expired || network_changed || route != self.last_ip_route) {
+            self.start_ip_check();
+        }
+        
+        egui::TopBottomPanel::bottom("status_bar").show(ctx, |ui| {
+            ui.horizontal(|ui| {
+                // 汇总保护状态
+                let layers = [
+                    ("Tor", self.tor_module.is_enabled(), TOR_COLOR),
+                    ("DNSCrypt", self.dnscrypt_module.is_enabled(), DNS_COLOR),
+                    ("I2P", self.i2p_module.is_enabled(), I2P_COLOR),
+                    ("VPN", self.vpn_module.is_enabled(), VPN_COLOR),
+                    ("防火墙", self.firewall_module.enabled, FIREWALL_COLOR),
+                ];
+                let active_count = layers.iter().filter(|(_, active, _)| *active).count();
+                let (summary, summary_color) = match active_count {
+                    0 => ("未受保护", Color32::RED),
+                    n if n == layers.len() => ("全面保护", Color32::GREEN),
+                    _ => ("部分保护", Color32::YELLOW),
+                };
+                ui.label(RichText::new(summary).color(summary_color).strong());
+                for (name, active, color) in layers {
+                    ui.label(RichText::new(name).color(if active { color } else { Color32::GRAY }));
+                }
+                
+                ui.separator();
+                
+                // 公网IP与国家
+                let ip_text = match self.ip_check.lock().map(|s| s.clone()) {
+                    Ok(IpCheckState::Done(info)) => format!("IP: {} ({})", info.ip, info.country),
+                    Ok(IpCheckState::Checking) => "IP: 检测中...".to_string(),
+                    Ok(IpCheckState::Failed(_)) => "IP: 检测失败".to_string(),
+                    _ => "IP: 未知".to_string(),
+                };
+                let via = if self.last_ip_route.is_some() { "经隧道" } else { "直连" };
+                if ui.link(ip_text).on_hover_text(format!("{}检测，点击刷新", via)).clicked() && !checking {
+                    self.start_ip_check();
+                }
+                
+                ui.separator();
+                
+                // 总吞吐量
+                let (down, up) = traffic::total_rate();
+                ui.label(format!(
+                    "↓ {}/s  ↑ {}/s",
+                    utils::format_bytes(down as u64),
+                    utils::format_bytes(up as u64)
+                ));
+                
+                ui.separator();
+                
+                // 受监控进程的异常状态
+                if !self.service_alerts.is_empty() {
+                    let details = self.service_alerts.iter()
+                        .map(|(process, alert)| format!("{}: {}", process, alert))
+                        .collect::<Vec<_>>()
+                        .join("\n");
+                    let text = RichText::new(format!("⚠ {}个服务异常", self.service_alerts.len()))
+                        .color(Color32::from_rgb(220, 53, 69));
+                    if ui.link(text).on_hover_text(details).clicked() {
+                        self.current_tab = Tab::Settings;
+                    }
+                    ui.separator();
+                }
+                
+                // 最近一条警告/错误
+                if let Ok(logger) = self.logger.lock() {
+                    if let Some(entry) = logger.last_problem() {
+                        let color = if entry.level == LogLevel::Error {
+                            Color32::from_rgb(220, 53, 69)
+                        } else {
+                            Color32::from_rgb(255, 193, 7)
+                        };
+                        let text = format!(
+                            "{} [{}] {}",
+                            entry.timestamp.format("%H:%M:%S"),
+                            entry.module,
+                            entry.message
+                        );
+                        if ui.link(RichText::new(text).color(color)).on_hover_text("点击查看日志").clicked() {
+                            self.current_tab = Tab::Logs;
+                        }
+                    }
+                }
+            });
+        });
+    }
+    
+    // 将加载配置时遇到的问题写入日志，显示在状态栏中
+    fn report_config_problems(&self) {
+        let problems = utils::take_config_problems();
+        if problems.is_empty() {
+            return;
+        }
+        if let Ok(mut logger) = self.logger.lock() {
+            for problem in &problems {
+                logger.warning("配置", problem);
+            }
+        }
+    }
+    
+    // 处理进程监控事件，更新状态栏中的服务异常提示
+    fn handle_supervisor_events(&mut self) {
+        for event in supervisor::take_events() {
+            let time = event.time.format("%H:%M:%S");
+            match event.kind {
+                SupervisorEventKind::Started { .. } | SupervisorEventKind::Recovered => {
+                    self.service_alerts.remove(&event.process);
+                }
+                SupervisorEventKind::Exited { code: Some(0) } => {
+                    self.service_alerts.remove(&event.process);
+                }
+                SupervisorEventKind::Exited { .. } => {
+                    self.service_alerts.insert(event.process, format!("{} 进程意外退出", time));
+                }
+                SupervisorEventKind::Unresponsive(reason) => {
+                    self.service_alerts.insert(event.process, format!("{} 进程无响应: {}", time, reason));
+                }
+                SupervisorEventKind::Restarting { attempt, .. } => {
+                    self.service_alerts.insert(event.process, format!("{} 正在第{}次重启", time, attempt));
+                }
+                SupervisorEventKind::GaveUp(reason) => {
+                    self.service_alerts.insert(event.process, format!("{} 已停止自动重启: {}", time, reason));
+                }
+            }
+        }
+    }
+    
+    // 决定由谁接管系统DNS：DNSCrypt运行时Tor不接管，两者都不需要时恢复原设置
+    fn sync_system_dns(&mut self) {
+        let owner = if self.dnscrypt_module.wants_system_dns() {
+            Some("DNSCrypt")
+        } else if self.tor_module.wants_system_dns() && !self.dnscrypt_module.is_enabled() {
+            Some("Tor")
+        } else {
+            None
+        };
+        self.system_dns.sync(owner);
+    }
+    
+    // 处理Tor与Snowflake代理的更新：运行中的先停止，替换完成后重新启动
+    fn handle_component_updates(&mut self) {
+        self.components.set_in_use(ComponentId::Tor, self.tor_module.is_enabled());
+        self.components.set_in_use(ComponentId::SnowflakeProxy, self.tor_module.is_snowflake_running());
+        self.tor_module.set_bundle_versions(
+            self.components.installed_version(ComponentId::Tor),
+            self.components.latest_version(ComponentId::Tor),
+        );
+        if self.tor_module.take_update_request() {
+            self.components.request_update(ComponentId::Tor);
+        }
+        
+        for (id, version) in self.components.take_update_requests() {
+            if id == ComponentId::Tor && self.tor_module.is_enabled() {
+                self.tor_module.set_enabled(false);
+                self.restart_tor_after_update = true;
+                self.components.set_in_use(id, false);
+            }
+            if id == ComponentId::SnowflakeProxy && self.tor_module.is_snowflake_running() {
+                self.tor_module.stop_snowflake_proxy();
+                self.restart_snowflake_after_update = true;
+                self.components.set_in_use(id, false);
+            }
+            self.components.install(id, version);
+        }
+        
+        for id in self.components.take_finished() {
+            if id == ComponentId::Tor {
+                self.tor_module.refresh_tor_version();
+                if std::mem::take(&mut self.restart_tor_after_update) {
+                    if let Ok(mut logger) = self.logger.lock() {
+                        logger.info("组件", "Tor更新结束，重新启动Tor");
+                    }
+                    self.tor_module.set_enabled(true);
+                }
+            }
+            if id == ComponentId::SnowflakeProxy && std::mem::take(&mut self.restart_snowflake_after_update) {
+                self.tor_module.resume_snowflake_proxy();
+            }
+        }
+    }
+    
+    // 执行定时任务调度器产生的动作
+    fn run_scheduled_actions(&mut self) {
+        for (rule_name, action) in self.scheduler.take_pending() {
+            if let Ok(mut logger) = self.logger.lock() {
+                logger.info("定时任务", &format!("执行定时任务 '{}': {}", rule_name, action.label()));
+            }
+            
+            match action {
+                ScheduledAction::StartTor => self.tor_module.set_enabled(true),
+                ScheduledAction::StopTor => self.tor_module.set_enabled(false),
+                ScheduledAction::StartDnsCrypt => self.dnscrypt_module.set_enabled(true),
+                ScheduledAction::StopDnsCrypt => self.dnscrypt_module.set_enabled(false),
+                ScheduledAction::StartI2P => self.i2p_module.set_enabled(true),
+                ScheduledAction::StopI2P => self.i2p_module.set_enabled(false),
+                ScheduledAction::EnableFirewall => self.firewall_module.set_enabled(true),
+                ScheduledAction::DisableFirewall => self.firewall_module.set_enabled(false),
+                ScheduledAction::SetFirewallProfile(profile) => self.firewall_module.set_profile(profile),
+                ScheduledAction::UpdateSubscriptions => self.vpn_module.update_all_subscriptions(),
+                ScheduledAction::RandomizeMac => self.mac_module.randomize_scheduled(),
+            }
+        }
+    }
+    
+    // 渲染网卡列表
+    fn render_adapters(ui: &mut Ui) {
+        let adapters = netif::adapters();
+        if adapters.is_empty() {
+            ui.label(RichText::new("正在枚举网卡...").color(Color32::GRAY));
+            return;
+        }
+        
+        egui::Grid::new("adapters_grid")
+            .num_columns(5)
+            .striped(true)
+            .spacing([10.0, 4.0])
+            .show(ui, |ui| {
+                ui.label(RichText::new("名称").strong());
+                ui.label(RichText::new("类型").strong());
+                ui.label(RichText::new("地址").strong());
+                ui.label(RichText::new("网关").strong());
+                ui.label(RichText::new("DNS").strong());
+                ui.end_row();
+                
+                let join = |addresses: &[std::net::IpAddr]| {
+                    addresses.iter().map(|a| a.to_string()).collect::<Vec<_>>().join("\n")
+                };
+                for adapter in &adapters {
+                    let color = if adapter.is_up { Color32::GREEN } else { Color32::GRAY };
+                    ui.label(RichText::new(&adapter.name).color(color))
+                        .on_hover_text(format!("{}\nMAC: {}", adapter.description, adapter.mac_address));
+                    ui.label(adapter.kind.label());
+                    ui.label(join(&adapter.addresses));
+                    ui.label(join(&adapter.gateways));
+                    ui.label(join(&adapter.dns_servers));
+                    ui.end_row();
+                }
+            });
+    }
+    
     // 渲染当前选中的标签页内容
     fn render_current_tab(&mut self, ui: &mut Ui) {
         match self.current_tab {
@@ -110,6 +725,23 @@ impl InviZibleApp {
             Tab::Firewall => self.firewall_module.ui(ui),
             Tab::Proxy => self.proxy_module.ui(ui),
             Tab::VPN => self.vpn_module.ui(ui),
+            Tab::Monitor => {
+                let route = RouteContext {
+                    tor_socks_port: self.tor_module.socks_port(),
+                    i2p_ports: self.i2p_module.tunnel_ports(),
+                    proxy_port: self.proxy_module.listen_port(),
+                    vpn_active: self.vpn_module.is_enabled(),
+                };
+                self.monitor_module.set_route_context(route);
+                self.monitor_module.ui(ui, &self.firewall_module);
+            },
+            Tab::LeakTest => {
+                // 检测通过当前活动的隧道进行
+                let proxy_url = self.active_tunnel_proxy();
+                let vpn_active = self.vpn_module.is_enabled();
+                self.leaktest_module.set_active_tunnel(proxy_url, vpn_active);
+                self.leaktest_module.ui(ui);
+            },
             Tab::Logs => {
                 if let Ok(mut logger) = self.logger.lock() {
                     logger.ui(ui);
@@ -118,8 +750,60 @@ impl InviZibleApp {
             Tab::Settings => {
                 ui.heading("设置");
                 ui.separator();
-                ui.label("全局设置选项将在这里显示");
-                // 这里可以添加全局设置选项
+                
+                ui.horizontal(|ui| {
+                    ui.label("数据目录:");
+                    ui.label(utils::get_app_data_dir().unwrap_or_else(|e| e.to_string()));
+                    if utils::is_portable() {
+                        ui.label(RichText::new("便携模式").color(Color32::GREEN));
+                    }
+                });
+                if utils::is_running_as_admin() {
+                    ui.label(RichText::new("正在以管理员身份运行").color(Color32::GREEN));
+                } else {
+                    render_admin_required(ui, &self.logger, "防火墙和系统DNS设置");
+                }
+                ui.horizontal(|ui| {
+                    ui.label("程序目录:");
+                    ui.label(utils::get_bin_dir().unwrap_or_else(|e| e.to_string()));
+                });
+                ui.label(RichText::new("在程序目录下放置 portable.flag 文件或使用 --portable 参数启动即可启用便携模式。").color(Color32::GRAY));
+                if services::is_simulated() {
+                    ui.label(RichText::new("模拟模式（--simulate）：不会启动任何外部进程").color(Color32::YELLOW));
+                }
+                ui.separator();
+                
+                ui.collapsing("定时任务", |ui| {
+                    self.scheduler.ui(ui);
+                });
+                
+                ui.collapsing("防火墙执行方式", |ui| {
+                    self.firewall_module.backend_ui(ui);
+                });
+                
+                ui.collapsing("冲突检测", |ui| {
+                    self.diagnostics.ui(ui);
+                });
+                
+                ui.collapsing("进程监控", |ui| {
+                    supervisor::ui(ui);
+                });
+                
+                ui.collapsing("网络接口", |ui| {
+                    Self::render_adapters(ui);
+                });
+                
+                ui.collapsing("MAC地址随机化", |ui| {
+                    self.mac_module.ui(ui);
+                });
+                
+                ui.collapsing("组件管理", |ui| {
+                    self.components.ui(ui);
+                });
+                
+                ui.collapsing("GeoIP数据库", |ui| {
+                    geoip::ui(ui, &self.logger);
+                });
             },
         }
     }
@@ -128,10 +812,57 @@ impl InviZibleApp {
 // 实现eframe应用程序特性
 impl eframe::App for InviZibleApp {
     fn update(&mut self, ctx: &egui::Context, _frame: &mut eframe::Frame) {
+        self.run_scheduled_actions();
+        self.report_config_problems();
+        self.handle_supervisor_events();
+        self.mac_module.poll();
+        self.proxy_module.set_tor_upstream(self.tor_module.upstream());
+        self.proxy_module.tick();
+        self.vpn_module.tick();
+        self.firewall_module.set_tor_ports(self.tor_module.listener_ports());
+        self.firewall_module.review_connections(&self.monitor_module.recent_flows());
+        // 防火墙的域名规则：按查询日志追踪解析到的地址，阻止的域名交给DNSCrypt拦截
+        self.firewall_module.set_dns_query_log(self.dnscrypt_module.query_log_enabled());
+        self.firewall_module.set_dns_resolver(self.dnscrypt_module.local_resolver());
+        let queries = self.dnscrypt_module.take_new_queries();
+        self.firewall_module.observe_dns_queries(&queries);
+        self.firewall_module.tick();
+        self.dnscrypt_module.set_firewall_blocked_names(self.firewall_module.blocked_domains());
+        let (active, connected) = match self.firewall_module.kill_switch_module() {
+            Some(ProtectionModule::Tor) => (self.tor_module.is_enabled(), self.tor_module.is_connected()),
+            Some(ProtectionModule::Vpn) => (self.vpn_module.is_enabled(), self.vpn_module.is_connected()),
+            Some(ProtectionModule::I2p) => (self.i2p_module.is_enabled(), self.i2p_module.is_connected()),
+            Some(ProtectionModule::DnsCrypt) => (self.dnscrypt_module.is_enabled(), self.dnscrypt_module.is_connected()),
+            None => (false, false),
+        };
+        self.firewall_module.update_kill_switch(active, connected);
+        self.sync_system_dns();
+        self.tor_module.sync_snowflake_counter();
+        self.dnscrypt_module.tick();
+        self.i2p_module.set_tor_socks_port(self.tor_module.socks_port());
+        self.i2p_module.tick();
+        self.handle_component_updates();
+        self.handle_shortcuts(ctx);
+        
+        // 状态栏需在中央面板之前添加，以便固定在窗口底部
+        self.render_status_bar(ctx);
+        // 定期重绘以刷新后台任务的结果
+        ctx.request_repaint_after(Duration::from_secs(1));
+        
         egui::CentralPanel::default().show(ctx, |ui| {
             self.render_top_panel(ui);
             ui.separator();
             self.render_current_tab(ui);
         });
+        
+        self.diagnostics.show_startup_window(ctx);
+        self.firewall_module.show_alert_window(ctx);
+        self.render_palette(ctx);
+    }
+    
+    fn on_close_event(&mut self) -> bool {
+        // 系统DNS指向Tor时，退出后将无法解析域名
+        self.system_dns.restore();
+        true
     }
 }
\ No newline at end of file
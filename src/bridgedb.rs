@@ -0,0 +1,278 @@
+use eframe::egui::{self, Color32, ColorImage, RichText, TextureHandle, TextureOptions};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use anyhow::{anyhow, Context, Result};
+use base64::{Engine as _, engine::general_purpose};
+use serde_json::{json, Value};
+
+// BridgeDB的moat接口地址
+const MOAT_URL: &str = "https://bridges.torproject.org/moat";
+
+// moat接口协议版本
+const MOAT_VERSION: &str = "0.1.0";
+
+// 请求的网桥类型
+const MOAT_TRANSPORT: &str = "obfs4";
+
+// 验证码
+pub struct Captcha {
+    pub challenge: String,
+    pub image: ColorImage,
+}
+
+// 创建HTTP客户端，proxy_url为当前使用的代理（如Tor的SOCKS端口）
+fn http_client(proxy_url: Option<&str>) -> Result<reqwest::blocking::Client> {
+    let mut builder = reqwest::blocking::Client::builder().timeout(Duration::from_secs(30));
+    if let Some(proxy_url) = proxy_url {
+        builder = builder.proxy(reqwest::Proxy::all(proxy_url).context("Invalid proxy url")?);
+    }
+    builder.build().context("Failed to build http client")
+}
+
+// 发送moat请求，返回data数组的第一项
+fn moat_request(proxy_url: Option<&str>, endpoint: &str, body: Value) -> Result<Value> {
+    let response: Value = http_client(proxy_url)?
+        .post(format!("{}/{}", MOAT_URL, endpoint))
+        .header("Content-Type", "application/vnd.api+json")
+        .body(body.to_string())
+        .send()
+        .context("Failed to reach BridgeDB")?
+        .json()
+        .context("Failed to parse BridgeDB response")?;
+
+    // 错误格式: {"errors":[{"code":419,"detail":"..."}]}
+    if let Some(error) = response["errors"].get(0) {
+        let detail = error["detail"].as_str().unwrap_or("未知错误");
+        return Err(anyhow!("{}", detail));
+    }
+    response["data"]
+        .get(0)
+        .cloned()
+        .ok_or_else(|| anyhow!("BridgeDB returned no data"))
+}
+
+// 获取验证码
+pub fn fetch_captcha(proxy_url: Option<&str>) -> Result<Captcha> {
+    let body = json!({
+        "data": [{
+            "version": MOAT_VERSION,
+            "type": "client-transports",
+            "supported": [MOAT_TRANSPORT],
+        }]
+    });
+    let data = moat_request(proxy_url, "fetch", body)?;
+    let challenge = data["challenge"].as_str().ok_or_else(|| anyhow!("BridgeDB returned no challenge"))?;
+    let encoded = data["image"].as_str().ok_or_else(|| anyhow!("BridgeDB returned no captcha image"))?;
+    let bytes = general_purpose::STANDARD.decode(encoded).context("Failed to decode captcha image")?;
+    let decoded = image::load_from_memory(&bytes).context("Failed to decode captcha image")?.to_rgba8();
+    let size = [decoded.width() as usize, decoded.height() as usize];
+    Ok(Captcha {
+        challenge: challenge.to_string(),
+        image: ColorImage::from_rgba_unmultiplied(size, decoded.as_raw()),
+    })
+}
+
+// 提交验证码答案，成功时返回网桥配置行
+pub fn check_solution(proxy_url: Option<&str>, challenge: &str, solution: &str) -> Result<Vec<String>> {
+    let body = json!({
+        "data": [{
+            "id": "2",
+            "type": "moat-solution",
+            "version": MOAT_VERSION,
+            "transport": MOAT_TRANSPORT,
+            "challenge": challenge,
+            "solution": solution,
+            "qrcode": "false",
+        }]
+    });
+    let data = moat_request(proxy_url, "check", body)?;
+    let bridges: Vec<String> = data["bridges"]
+        .as_array()
+        .map(|list| list.iter().filter_map(|b| b.as_str()).map(|b| b.trim().to_string()).collect())
+        .unwrap_or_default();
+    if bridges.is_empty() {
+        return Err(anyhow!("BridgeDB returned no bridges"));
+    }
+    Ok(bridges)
+}
+
+// 请求网桥流程的状态
+#[derive(Clone, Debug, PartialEq)]
+enum RequestState {
+    Loading,
+    Solving,
+    Checking,
+    Failed(String),
+}
+
+// "请求网桥"对话框
+pub struct BridgeRequest {
+    open: bool,
+    proxy_url: Option<String>,
+    state: Arc<Mutex<RequestState>>,
+    captcha: Arc<Mutex<Option<Captcha>>>,
+    received: Arc<Mutex<Vec<String>>>,
+    texture: Option<TextureHandle>,
+    challenge: String,
+    solution: String,
+}
+
+impl Default for BridgeRequest {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl BridgeRequest {
+    pub fn new() -> Self {
+        Self {
+            open: false,
+            proxy_url: None,
+            state: Arc::new(Mutex::new(RequestState::Loading)),
+            captcha: Arc::new(Mutex::new(None)),
+            received: Arc::new(Mutex::new(Vec::new())),
+            texture: None,
+            challenge: String::new(),
+            solution: String::new(),
+        }
+    }
+
+    // 打开对话框并获取验证码
+    pub fn open(&mut self, proxy_url: Option<String>) {
+        self.open = true;
+        self.proxy_url = proxy_url;
+        self.load_captcha();
+    }
+
+    fn set_state(&self, state: RequestState) {
+        if let Ok(mut current) = self.state.lock() {
+            *current = state;
+        }
+    }
+
+    fn load_captcha(&mut self) {
+        self.texture = None;
+        self.solution.clear();
+        self.set_state(RequestState::Loading);
+        let proxy_url = self.proxy_url.clone();
+        let state = Arc::clone(&self.state);
+        let captcha = Arc::clone(&self.captcha);
+        std::thread::spawn(move || {
+            let result = fetch_captcha(proxy_url.as_deref());
+            let next = match result {
+                Ok(fetched) => {
+                    if let Ok(mut captcha) = captcha.lock() {
+                        *captcha = Some(fetched);
+                    }
+                    RequestState::Solving
+                }
+                Err(e) => RequestState::Failed(format!("获取验证码失败: {:#}", e)),
+            };
+            if let Ok(mut state) = state.lock() {
+                *state = next;
+            }
+        });
+    }
+
+    fn submit(&mut self) {
+        self.set_state(RequestState::Checking);
+        let proxy_url = self.proxy_url.clone();
+        let challenge = self.challenge.clone();
+        let solution = self.solution.trim().to_string();
+        let state = Arc::clone(&self.state);
+        let received = Arc::clone(&self.received);
+        std::thread::spawn(move || {
+            match check_solution(proxy_url.as_deref(), &challenge, &solution) {
+                Ok(bridges) => {
+                    if let Ok(mut received) = received.lock() {
+                        received.extend(bridges);
+                    }
+                }
+                // 验证码只能使用一次，失败后需要换一张
+                Err(e) => {
+                    if let Ok(mut state) = state.lock() {
+                        *state = RequestState::Failed(format!("获取网桥失败: {:#}", e));
+                    }
+                }
+            }
+        });
+    }
+
+    // 渲染对话框，返回本次收到的网桥配置行（收到后对话框自动关闭）
+    pub fn ui(&mut self, ctx: &egui::Context) -> Vec<String> {
+        let received: Vec<String> = self.received.lock().map(|mut r| r.drain(..).collect()).unwrap_or_default();
+        if !received.is_empty() {
+            self.open = false;
+            return received;
+        }
+        if !self.open {
+            return Vec::new();
+        }
+
+        // 新的验证码到达后生成纹理
+        if let Some(captcha) = self.captcha.lock().ok().and_then(|mut c| c.take()) {
+            self.challenge = captcha.challenge;
+            self.texture = Some(ctx.load_texture("bridgedb_captcha", captcha.image, TextureOptions::default()));
+        }
+
+        let state = self.state.lock().map(|s| s.clone()).unwrap_or(RequestState::Loading);
+        if matches!(state, RequestState::Loading | RequestState::Checking) {
+            ctx.request_repaint_after(Duration::from_millis(200));
+        }
+        let mut open = self.open;
+        let mut reload = false;
+        let mut submit = false;
+        egui::Window::new("从BridgeDB请求网桥")
+            .open(&mut open)
+            .collapsible(false)
+            .resizable(false)
+            .show(ctx, |ui| {
+                let route = if self.proxy_url.is_some() { "通过Tor" } else { "直接连接" };
+                ui.label(format!("连接方式: {}", route));
+
+                match &state {
+                    RequestState::Loading => {
+                        ui.horizontal(|ui| {
+                            ui.spinner();
+                            ui.label("正在获取验证码...");
+                        });
+                    }
+                    RequestState::Checking => {
+                        ui.horizontal(|ui| {
+                            ui.spinner();
+                            ui.label("正在验证...");
+                        });
+                    }
+                    RequestState::Failed(error) => {
+                        ui.label(RichText::new(error).color(Color32::RED));
+                    }
+                    RequestState::Solving => {}
+                }
+
+                if let Some(texture) = &self.texture {
+                    ui.image(texture, texture.size_vec2());
+                }
+                if state == RequestState::Solving {
+                    ui.horizontal(|ui| {
+                        ui.label("验证码:");
+                        let response = ui.text_edit_singleline(&mut self.solution);
+                        let entered = response.lost_focus() && ui.input(|i| i.key_pressed(egui::Key::Enter));
+                        if (ui.button("提交").clicked() || entered) && !self.solution.trim().is_empty() {
+                            submit = true;
+                        }
+                    });
+                }
+                if ui.button("换一张").clicked() {
+                    reload = true;
+                }
+            });
+        self.open = open;
+
+        if submit {
+            self.submit();
+        } else if reload {
+            self.load_captcha();
+        }
+        Vec::new()
+    }
+}
@@ -0,0 +1,110 @@
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+use anyhow::{anyhow, Context, Result};
+
+use crate::utils;
+
+// 浏览器打开的第一个页面，用于确认已通过Tor访问
+const START_PAGE: &str = "https://check.torproject.org/";
+
+// 浏览器类型，决定代理的设置方式
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum BrowserKind {
+    Chromium, // Chrome、Edge、Brave等，通过命令行参数设置代理
+    Firefox,  // 通过独立配置文件中的user.js设置代理
+}
+
+impl BrowserKind {
+    pub fn detect(path: &Path) -> Self {
+        let name = path.file_stem().map(|n| n.to_string_lossy().to_lowercase()).unwrap_or_default();
+        if ["firefox", "librewolf", "waterfox", "floorp"].iter().any(|n| name.contains(n)) {
+            BrowserKind::Firefox
+        } else {
+            BrowserKind::Chromium
+        }
+    }
+}
+
+// 读取系统默认浏览器的可执行文件路径（https协议的关联程序）
+pub fn default_browser() -> Result<PathBuf> {
+    let script = "$id = (Get-ItemProperty 'HKCU:\\Software\\Microsoft\\Windows\\Shell\\Associations\\UrlAssociations\\https\\UserChoice').ProgId; \
+                  (Get-ItemProperty \"Registry::HKEY_CLASSES_ROOT\\$id\\shell\\open\\command\").'(default)'";
+    let mut command = Command::new("powershell");
+    command.args(["-NoProfile", "-Command", script]);
+    #[cfg(target_os = "windows")]
+    {
+        use std::os::windows::process::CommandExt;
+        const CREATE_NO_WINDOW: u32 = 0x08000000;
+        command.creation_flags(CREATE_NO_WINDOW);
+    }
+    let output = command.output().context("Failed to run powershell")?;
+    let command_line = String::from_utf8_lossy(&output.stdout).trim().to_string();
+    // 命令行形如 "C:\Program Files\...\chrome.exe" --single-argument %1
+    let program = match command_line.strip_prefix('"') {
+        Some(rest) => rest.split('"').next().unwrap_or(""),
+        None => command_line.split(" -").next().unwrap_or("").trim(),
+    };
+    if program.is_empty() {
+        return Err(anyhow!("无法读取默认浏览器"));
+    }
+    Ok(PathBuf::from(program))
+}
+
+// 浏览器专用的配置目录，避免影响日常使用的配置
+fn profile_dir(kind: BrowserKind) -> Result<PathBuf> {
+    let name = match kind {
+        BrowserKind::Chromium => "chromium",
+        BrowserKind::Firefox => "firefox",
+    };
+    let dir = Path::new(&utils::get_app_data_dir()?).join("browser").join(name);
+    fs::create_dir_all(&dir).context("Failed to create browser profile directory")?;
+    Ok(dir)
+}
+
+// Firefox的代理设置，域名也交给Tor解析
+fn firefox_prefs(socks_port: u16) -> String {
+    [
+        ("network.proxy.type", "1".to_string()),
+        ("network.proxy.socks", "\"127.0.0.1\"".to_string()),
+        ("network.proxy.socks_port", socks_port.to_string()),
+        ("network.proxy.socks_version", "5".to_string()),
+        ("network.proxy.socks_remote_dns", "true".to_string()),
+        ("network.proxy.no_proxies_on", "\"\"".to_string()),
+        ("network.trr.mode", "5".to_string()),
+        ("media.peerconnection.enabled", "false".to_string()),
+    ]
+    .iter()
+    .map(|(name, value)| format!("user_pref(\"{}\", {});\n", name, value))
+    .collect()
+}
+
+// 使用独立配置启动浏览器，代理指向Tor的SOCKS端口，返回启动的命令行
+pub fn launch(browser: &Path, socks_port: u16) -> Result<String> {
+    let kind = BrowserKind::detect(browser);
+    let profile = profile_dir(kind)?;
+    let args = match kind {
+        BrowserKind::Chromium => vec![
+            format!("--user-data-dir={}", profile.display()),
+            format!("--proxy-server=socks5://127.0.0.1:{}", socks_port),
+            // 禁止本地解析域名，防止DNS泄露
+            "--host-resolver-rules=MAP * ~NOTFOUND , EXCLUDE 127.0.0.1".to_string(),
+            "--no-first-run".to_string(),
+            START_PAGE.to_string(),
+        ],
+        BrowserKind::Firefox => {
+            fs::write(profile.join("user.js"), firefox_prefs(socks_port)).context("Failed to write user.js")?;
+            vec![
+                "-no-remote".to_string(),
+                "-profile".to_string(),
+                profile.display().to_string(),
+                START_PAGE.to_string(),
+            ]
+        }
+    };
+    Command::new(browser)
+        .args(&args)
+        .spawn()
+        .with_context(|| format!("Failed to launch {}", browser.display()))?;
+    Ok(format!("{} {}", browser.display(), args.join(" ")))
+}
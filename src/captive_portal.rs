@@ -0,0 +1,45 @@
+use std::time::Duration;
+use reqwest::blocking::Client;
+use reqwest::redirect::Policy;
+
+// Windows网络连接状态指示器（NCSI）使用的检测地址
+const PROBE_URL: &str = "http://www.msftconnecttest.com/connecttest.txt";
+const PROBE_BODY: &str = "Microsoft Connect Test";
+// 没有跳转地址时打开的页面，门户通常会拦截并显示登录页
+pub const LOGIN_URL: &str = "http://www.msftconnecttest.com/redirect";
+
+// 强制门户检测结果
+#[derive(Clone, Debug, PartialEq)]
+pub enum PortalStatus {
+    Online,                   // 可以正常访问互联网
+    Portal(Option<String>),   // 请求被拦截，可能附带登录页地址
+    NoConnectivity(String),   // 无法完成请求（常见于加密DNS在门户后无法连接上游）
+}
+
+// 直接请求检测地址（不使用代理、不跟随跳转），会阻塞数秒
+pub fn detect() -> PortalStatus {
+    let client = match Client::builder()
+        .no_proxy()
+        .redirect(Policy::none())
+        .timeout(Duration::from_secs(5))
+        .build()
+    {
+        Ok(client) => client,
+        Err(e) => return PortalStatus::NoConnectivity(e.to_string()),
+    };
+    let response = match client.get(PROBE_URL).send() {
+        Ok(response) => response,
+        Err(e) => return PortalStatus::NoConnectivity(e.to_string()),
+    };
+    if response.status().is_redirection() {
+        let location = response.headers()
+            .get(reqwest::header::LOCATION)
+            .and_then(|value| value.to_str().ok())
+            .map(str::to_string);
+        return PortalStatus::Portal(location);
+    }
+    match response.text() {
+        Ok(body) if body.trim() == PROBE_BODY => PortalStatus::Online,
+        _ => PortalStatus::Portal(None),
+    }
+}
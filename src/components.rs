@@ -0,0 +1,664 @@
+use eframe::egui::{Color32, RichText, Ui, Grid};
+use std::collections::{HashMap, HashSet};
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use serde::{Deserialize, Serialize};
+use anyhow::{anyhow, Context, Result};
+
+use crate::logger::Logger;
+use crate::utils;
+
+// 已安装组件记录文件名
+const COMPONENTS_FILE: &str = "components.json";
+
+// dnscrypt-proxy发布包的minisign公钥（见项目README）
+const DNSCRYPT_PROXY_MINISIGN_KEY: &str = "RWTk1xXqcTODeYttYMCMLo0YJHaFEHn7a3akqHlb/7QvIQXHVPxKbjB5";
+
+// Tor Browser Developers签名密钥的主指纹，用于校验Tor的校验值文件
+const TOR_SIGNING_KEY: &str = "EF6E286DDA85EA2A4BA7DE684E2C6E8793298290";
+const TOR_SIGNING_EMAIL: &str = "torbrowser@torproject.org";
+
+// 上游发布的签名
+enum ReleaseSignature {
+    Minisign(&'static str),                                  // 发布包旁的.minisig，值为公钥
+    Gpg { email: &'static str, fingerprint: &'static str }, // 校验值文件旁的.asc，按邮箱通过WKD获取公钥
+}
+
+// 外部组件
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum ComponentId {
+    Tor,
+    DnsCryptProxy,
+    I2pd,
+    Xray,
+    SnowflakeProxy,
+}
+
+impl ComponentId {
+    pub fn all() -> [ComponentId; 5] {
+        [ComponentId::Tor, ComponentId::DnsCryptProxy, ComponentId::I2pd, ComponentId::Xray, ComponentId::SnowflakeProxy]
+    }
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            ComponentId::Tor => "Tor（含obfs4/Snowflake传输插件）",
+            ComponentId::DnsCryptProxy => "dnscrypt-proxy",
+            ComponentId::I2pd => "i2pd",
+            ComponentId::Xray => "Xray（VPN核心）",
+            ComponentId::SnowflakeProxy => "Snowflake代理（志愿者模式）",
+        }
+    }
+
+    // 组件在程序目录下的子目录名
+    fn dir_name(&self) -> &'static str {
+        match self {
+            ComponentId::Tor => "tor",
+            ComponentId::DnsCryptProxy => "dnscrypt-proxy",
+            ComponentId::I2pd => "i2pd",
+            ComponentId::Xray => "xray",
+            ComponentId::SnowflakeProxy => "snowflake",
+        }
+    }
+
+    // 内置的默认版本
+    fn default_version(&self) -> &'static str {
+        match self {
+            ComponentId::Tor => "13.5.6",
+            ComponentId::DnsCryptProxy => "2.1.5",
+            ComponentId::I2pd => "2.54.0",
+            ComponentId::Xray => "1.8.24",
+            ComponentId::SnowflakeProxy => "2.10.1",
+        }
+    }
+
+    // 下载地址。Snowflake代理没有官方的Windows构建，需手动填写
+    fn download_url(&self, version: &str) -> Option<String> {
+        let url = match self {
+            ComponentId::Tor => format!(
+                "https://archive.torproject.org/tor-package-archive/torbrowser/{0}/tor-expert-bundle-windows-x86_64-{0}.tar.gz",
+                version
+            ),
+            ComponentId::DnsCryptProxy => format!(
+                "https://github.com/DNSCrypt/dnscrypt-proxy/releases/download/{0}/dnscrypt-proxy-win64-{0}.zip",
+                version
+            ),
+            ComponentId::I2pd => format!(
+                "https://github.com/PurpleI2P/i2pd/releases/download/{0}/i2pd_{0}_win64_mingw.zip",
+                version
+            ),
+            ComponentId::Xray => format!(
+                "https://github.com/XTLS/Xray-core/releases/download/v{}/Xray-windows-64.zip",
+                version
+            ),
+            ComponentId::SnowflakeProxy => return None,
+        };
+        Some(url)
+    }
+
+    // 发布包是单个可执行文件（而不是压缩包）时，安装后的文件名
+    fn single_executable(&self) -> Option<&'static str> {
+        match self {
+            ComponentId::SnowflakeProxy => Some("snowflake-proxy.exe"),
+            _ => None,
+        }
+    }
+
+    // 上游发布的校验值文件
+    fn checksum_url(&self, version: &str) -> Option<String> {
+        match self {
+            ComponentId::Tor => Some(format!(
+                "https://archive.torproject.org/tor-package-archive/torbrowser/{}/sha256sums-signed-build.txt",
+                version
+            )),
+            ComponentId::Xray => self.download_url(version).map(|url| format!("{}.dgst", url)),
+            ComponentId::DnsCryptProxy | ComponentId::I2pd | ComponentId::SnowflakeProxy => None,
+        }
+    }
+
+    // 上游签名。Xray的校验值文件没有签名，只能防止下载损坏
+    fn signature(&self) -> Option<ReleaseSignature> {
+        match self {
+            ComponentId::Tor => Some(ReleaseSignature::Gpg { email: TOR_SIGNING_EMAIL, fingerprint: TOR_SIGNING_KEY }),
+            ComponentId::DnsCryptProxy => Some(ReleaseSignature::Minisign(DNSCRYPT_PROXY_MINISIGN_KEY)),
+            ComponentId::I2pd | ComponentId::Xray | ComponentId::SnowflakeProxy => None,
+        }
+    }
+
+    // 是否有上游的校验来源，没有时必须为要安装的版本手动固定SHA-256
+    fn has_upstream_verification(&self) -> bool {
+        self.signature().is_some() || self.checksum_url(self.default_version()).is_some()
+    }
+
+    // 查询最新版本的地址
+    fn latest_version_url(&self) -> &'static str {
+        match self {
+            ComponentId::Tor => "https://aus1.torproject.org/torbrowser/update_3/release/downloads.json",
+            ComponentId::DnsCryptProxy => "https://api.github.com/repos/DNSCrypt/dnscrypt-proxy/releases/latest",
+            ComponentId::I2pd => "https://api.github.com/repos/PurpleI2P/i2pd/releases/latest",
+            ComponentId::Xray => "https://api.github.com/repos/XTLS/Xray-core/releases/latest",
+            ComponentId::SnowflakeProxy => "https://gitlab.torproject.org/api/v4/projects/tpo%2Fanti-censorship%2Fpluggable-transports%2Fsnowflake/releases/permalink/latest",
+        }
+    }
+}
+
+// 组件中的可执行文件
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Executable {
+    Tor,
+    Lyrebird, // obfs4/meek/webtunnel传输插件（原obfs4proxy）
+    Snowflake,
+    DnsCryptProxy,
+    I2pd,
+    Xray,
+    SnowflakeProxy, // Snowflake志愿者代理，与Tor专家包中的snowflake-client不同
+}
+
+impl Executable {
+    pub const ALL: [Executable; 7] = [
+        Executable::Tor,
+        Executable::Lyrebird,
+        Executable::Snowflake,
+        Executable::DnsCryptProxy,
+        Executable::I2pd,
+        Executable::Xray,
+        Executable::SnowflakeProxy,
+    ];
+
+    // 所属组件及相对路径
+    fn location(&self) -> (ComponentId, &'static str) {
+        match self {
+            Executable::Tor => (ComponentId::Tor, "tor/tor.exe"),
+            Executable::Lyrebird => (ComponentId::Tor, "tor/pluggable_transports/lyrebird.exe"),
+            Executable::Snowflake => (ComponentId::Tor, "tor/pluggable_transports/snowflake-client.exe"),
+            Executable::DnsCryptProxy => (ComponentId::DnsCryptProxy, "win64/dnscrypt-proxy.exe"),
+            Executable::I2pd => (ComponentId::I2pd, "i2pd.exe"),
+            Executable::Xray => (ComponentId::Xray, "xray.exe"),
+            Executable::SnowflakeProxy => (ComponentId::SnowflakeProxy, "snowflake-proxy.exe"),
+        }
+    }
+}
+
+// 获取已安装组件中可执行文件的路径
+pub fn executable_path(executable: Executable) -> Option<PathBuf> {
+    let (component, relative) = executable.location();
+    let path = Path::new(&utils::get_bin_dir().ok()?)
+        .join(component.dir_name())
+        .join(relative);
+    path.exists().then_some(path)
+}
+
+// 已安装组件的记录
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct InstalledComponent {
+    pub version: String,
+    pub sha256: String,
+    pub installed_at: String,
+}
+
+impl utils::VersionedConfig for HashMap<ComponentId, InstalledComponent> {
+    const VERSION: u32 = 1;
+}
+
+// 组件操作状态
+#[derive(Clone, Debug)]
+enum TaskState {
+    Idle,
+    Running(String),
+    Failed(String),
+}
+
+// 计算文件的SHA-256
+fn sha256_file(path: &Path) -> Result<String> {
+    let output = Command::new("powershell")
+        .args(["-NoProfile", "-Command", "(Get-FileHash -Algorithm SHA256 -LiteralPath $args[0]).Hash"])
+        .arg(path)
+        .output()
+        .context("Failed to run Get-FileHash")?;
+    let hash = String::from_utf8_lossy(&output.stdout).trim().to_lowercase();
+    if hash.len() != 64 {
+        return Err(anyhow!("Failed to hash {}", path.display()));
+    }
+    Ok(hash)
+}
+
+// 从上游校验文件中找出指定文件的SHA-256
+// 支持"<hash>  <文件名>"格式和Xray的"SHA2-256= <hash>"格式
+fn parse_checksum(contents: &str, file_name: &str) -> Option<String> {
+    contents.lines().find_map(|line| {
+        let line = line.trim();
+        if let Some(hash) = line.strip_prefix("SHA2-256=") {
+            return Some(hash.trim().to_lowercase());
+        }
+        if !line.ends_with(file_name) {
+            return None;
+        }
+        line.split_whitespace()
+            .next()
+            .filter(|hash| hash.len() == 64 && hash.chars().all(|c| c.is_ascii_hexdigit()))
+            .map(|hash| hash.to_lowercase())
+    })
+}
+
+// 用minisign公钥校验发布包
+fn minisign_verify(data: &[u8], signature: &str, public_key: &str) -> Result<()> {
+    let public_key = minisign_verify::PublicKey::from_base64(public_key).map_err(|e| anyhow!("Invalid minisign key: {}", e))?;
+    let signature = minisign_verify::Signature::decode(signature).map_err(|e| anyhow!("Invalid minisign signature: {}", e))?;
+    public_key.verify(data, &signature, true).map_err(|e| anyhow!("minisign签名校验失败: {}", e))
+}
+
+// 用gpg校验分离签名，签名必须来自指定主指纹的密钥。
+// 公钥通过WKD获取到临时密钥环中，不使用也不修改用户的密钥环
+fn gpg_verify(data: &Path, signature: &Path, email: &str, fingerprint: &str) -> Result<()> {
+    let home = signature.with_extension("gnupg");
+    if home.exists() {
+        fs::remove_dir_all(&home).context("Failed to clean gpg home")?;
+    }
+    fs::create_dir_all(&home)?;
+    let result = (|| {
+        let status = Command::new("gpg")
+            .arg("--homedir").arg(&home)
+            .args(["--batch", "--auto-key-locate", "nodefault,wkd", "--locate-keys", email])
+            .output()
+            .context("未找到gpg，请安装Gpg4win后重试，或填写该版本的SHA-256")?
+            .status;
+        if !status.success() {
+            return Err(anyhow!("Failed to fetch signing key of {}", email));
+        }
+        let output = Command::new("gpg")
+            .arg("--homedir").arg(&home)
+            .args(["--batch", "--status-fd", "1", "--verify"])
+            .arg(signature)
+            .arg(data)
+            .output()
+            .context("Failed to run gpg")?;
+        // VALIDSIG行的最后一项是签名密钥的主指纹
+        let valid = String::from_utf8_lossy(&output.stdout).lines()
+            .filter_map(|line| line.strip_prefix("[GNUPG:] VALIDSIG "))
+            .any(|fields| fields.split_whitespace().last().map_or(false, |primary| primary.eq_ignore_ascii_case(fingerprint)));
+        if valid {
+            Ok(())
+        } else {
+            Err(anyhow!("GPG签名校验失败: 签名不是来自 {}", fingerprint))
+        }
+    })();
+    let _ = fs::remove_dir_all(&home);
+    result
+}
+
+// 下载、校验并安装组件，返回安装记录
+// custom_url为手动填写的下载地址，优先于内置地址
+fn install_component(
+    id: ComponentId,
+    version: &str,
+    pinned_sha256: Option<String>,
+    custom_url: Option<String>,
+    progress: &dyn Fn(String),
+) -> Result<InstalledComponent> {
+    let client = reqwest::blocking::Client::builder()
+        .timeout(Duration::from_secs(600))
+        .user_agent("InviZible-Pro-Windows")
+        .build()
+        .context("Failed to build http client")?;
+
+    let url = custom_url.or_else(|| id.download_url(version))
+        .ok_or_else(|| anyhow!("该组件没有官方下载地址，请先填写 {} 版本的下载地址", version))?;
+    let file_name = url.rsplit('/').next().unwrap_or("download").to_string();
+    let bin_dir = PathBuf::from(utils::get_bin_dir()?);
+
+    // 确定期望的校验值：优先使用为该版本手动固定的值，其次使用上游校验文件（有签名时先校验签名）。
+    // 只有发布包签名的组件在下载后校验签名
+    let expected = match (pinned_sha256, id.checksum_url(version)) {
+        (Some(pinned), _) => Some(pinned.trim().to_lowercase()),
+        (None, Some(checksum_url)) => {
+            progress("正在获取校验值...".to_string());
+            let contents = client.get(&checksum_url).send()?.error_for_status()?.text()?;
+            if let Some(ReleaseSignature::Gpg { email, fingerprint }) = id.signature() {
+                progress("正在校验签名...".to_string());
+                let signature = client.get(format!("{}.asc", checksum_url)).send()?.error_for_status()?.bytes()?;
+                let checksum_path = bin_dir.join(format!("{}.sha256sums", id.dir_name()));
+                let signature_path = bin_dir.join(format!("{}.sha256sums.asc", id.dir_name()));
+                fs::write(&checksum_path, &contents).context("Failed to save checksums")?;
+                fs::write(&signature_path, &signature).context("Failed to save signature")?;
+                let result = gpg_verify(&checksum_path, &signature_path, email, fingerprint);
+                let _ = fs::remove_file(&checksum_path);
+                let _ = fs::remove_file(&signature_path);
+                result?;
+            }
+            Some(parse_checksum(&contents, &file_name)
+                .with_context(|| format!("No checksum for {} in {}", file_name, checksum_url))?)
+        }
+        (None, None) => None,
+    };
+    let minisign_key = match id.signature() {
+        Some(ReleaseSignature::Minisign(key)) => Some(key),
+        _ => None,
+    };
+    if expected.is_none() && minisign_key.is_none() {
+        return Err(anyhow!("该组件没有上游签名或校验文件，请先填写 {} 版本发布页提供的SHA-256", version));
+    }
+
+    let archive_path = bin_dir.join(&file_name);
+    progress(format!("正在下载 {}...", file_name));
+    let bytes = client.get(&url).send()?.error_for_status()?.bytes().context("Failed to download component")?;
+
+    progress("正在校验...".to_string());
+    if let (None, Some(key)) = (&expected, minisign_key) {
+        let signature = client.get(format!("{}.minisig", url)).send()?.error_for_status()?.text()?;
+        minisign_verify(&bytes, &signature, key)?;
+    }
+    fs::write(&archive_path, &bytes).context("Failed to save download")?;
+    let actual = sha256_file(&archive_path)?;
+    if let Some(expected) = expected {
+        if actual != expected {
+            let _ = fs::remove_file(&archive_path);
+            return Err(anyhow!("SHA-256校验失败: 期望 {}，实际 {}", expected, actual));
+        }
+    }
+
+    // 解压到临时目录，成功后替换旧版本
+    progress("正在解压...".to_string());
+    let target_dir = bin_dir.join(id.dir_name());
+    let staging_dir = bin_dir.join(format!("{}.new", id.dir_name()));
+    if staging_dir.exists() {
+        fs::remove_dir_all(&staging_dir).context("Failed to clean staging directory")?;
+    }
+    fs::create_dir_all(&staging_dir)?;
+    match id.single_executable().filter(|_| file_name.to_lowercase().ends_with(".exe")) {
+        Some(executable) => {
+            fs::rename(&archive_path, staging_dir.join(executable)).context("Failed to install executable")?;
+        }
+        None => {
+            let status = Command::new("tar")
+                .arg("-xf")
+                .arg(&archive_path)
+                .arg("-C")
+                .arg(&staging_dir)
+                .status()
+                .context("Failed to run tar")?;
+            let _ = fs::remove_file(&archive_path);
+            if !status.success() {
+                return Err(anyhow!("Failed to extract {}", file_name));
+            }
+        }
+    }
+    if target_dir.exists() {
+        fs::remove_dir_all(&target_dir).context("Failed to remove old version (is it still running?)")?;
+    }
+    fs::rename(&staging_dir, &target_dir).context("Failed to install component")?;
+
+    Ok(InstalledComponent {
+        version: version.to_string(),
+        sha256: actual,
+        installed_at: chrono::Local::now().format("%Y-%m-%d %H:%M:%S").to_string(),
+    })
+}
+
+// 查询组件的最新版本
+fn fetch_latest_version(id: ComponentId) -> Result<String> {
+    let client = reqwest::blocking::Client::builder()
+        .timeout(Duration::from_secs(30))
+        .user_agent("InviZible-Pro-Windows")
+        .build()
+        .context("Failed to build http client")?;
+    let json: serde_json::Value = client.get(id.latest_version_url()).send()?.error_for_status()?.json()?;
+    let version = match id {
+        ComponentId::Tor => json["version"].as_str(),
+        _ => json["tag_name"].as_str(),
+    };
+    version
+        .map(|v| v.trim_start_matches('v').to_string())
+        .context("Unexpected version response")
+}
+
+// 组件管理器
+pub struct ComponentManager {
+    installed: Arc<Mutex<HashMap<ComponentId, InstalledComponent>>>,
+    tasks: Arc<Mutex<HashMap<ComponentId, TaskState>>>,
+    latest: Arc<Mutex<HashMap<ComponentId, String>>>,
+    pinned_hashes: HashMap<(ComponentId, String), String>, // 按版本手动固定的SHA-256
+    custom_urls: HashMap<(ComponentId, String), String>,   // 按版本手动填写的下载地址
+    in_use: HashSet<ComponentId>,                // 正在运行、更新前需要先停止的组件
+    update_requests: Vec<(ComponentId, String)>, // 等待调用方停止组件后再安装的更新
+    finished: Arc<Mutex<Vec<ComponentId>>>,      // 已结束（成功或失败）的安装任务
+    logger: Arc<Mutex<Logger>>,
+}
+
+impl ComponentManager {
+    pub fn new(logger: Arc<Mutex<Logger>>) -> Self {
+        let installed = utils::load_saved_config(COMPONENTS_FILE).unwrap_or_default();
+
+        Self {
+            installed: Arc::new(Mutex::new(installed)),
+            tasks: Arc::new(Mutex::new(HashMap::new())),
+            latest: Arc::new(Mutex::new(HashMap::new())),
+            pinned_hashes: HashMap::new(),
+            custom_urls: HashMap::new(),
+            in_use: HashSet::new(),
+            update_requests: Vec::new(),
+            finished: Arc::new(Mutex::new(Vec::new())),
+            logger,
+        }
+    }
+
+    fn set_task(tasks: &Arc<Mutex<HashMap<ComponentId, TaskState>>>, id: ComponentId, state: TaskState) {
+        if let Ok(mut tasks) = tasks.lock() {
+            tasks.insert(id, state);
+        }
+    }
+
+    // 标记组件是否正在运行
+    pub fn set_in_use(&mut self, id: ComponentId, in_use: bool) {
+        if in_use {
+            self.in_use.insert(id);
+        } else {
+            self.in_use.remove(&id);
+        }
+    }
+
+    // 请求更新组件：正在运行的组件由调用方通过take_update_requests取出，停止后再安装
+    pub fn request_update(&mut self, id: ComponentId) {
+        if self.is_installing(id) {
+            return;
+        }
+        let version = self.latest_version(id).unwrap_or_else(|| id.default_version().to_string());
+        if self.in_use.contains(&id) {
+            if let Ok(mut logger) = self.logger.lock() {
+                logger.info("组件", &format!("{} 正在运行，将先停止再更新", id.name()));
+            }
+            self.update_requests.push((id, version));
+        } else {
+            self.install(id, version);
+        }
+    }
+
+    // 取出需要先停止组件的更新请求
+    pub fn take_update_requests(&mut self) -> Vec<(ComponentId, String)> {
+        std::mem::take(&mut self.update_requests)
+    }
+
+    // 取出已结束的安装任务
+    pub fn take_finished(&self) -> Vec<ComponentId> {
+        self.finished.lock().map(|mut f| f.drain(..).collect()).unwrap_or_default()
+    }
+
+    pub fn is_installing(&self, id: ComponentId) -> bool {
+        self.tasks.lock().map(|t| matches!(t.get(&id), Some(TaskState::Running(_)))).unwrap_or(false)
+    }
+
+    pub fn installed_version(&self, id: ComponentId) -> Option<String> {
+        self.installed.lock().ok()?.get(&id).map(|c| c.version.clone())
+    }
+
+    // 最近一次检查到的最新版本
+    pub fn latest_version(&self, id: ComponentId) -> Option<String> {
+        self.latest.lock().ok()?.get(&id).cloned()
+    }
+
+    // 在后台安装或更新组件
+    pub fn install(&mut self, id: ComponentId, version: String) {
+        let pinned = self.pinned_hashes.get(&(id, version.clone())).filter(|h| !h.trim().is_empty()).cloned();
+        let custom_url = self.custom_urls.get(&(id, version.clone())).map(|u| u.trim().to_string()).filter(|u| !u.is_empty());
+        let installed = Arc::clone(&self.installed);
+        let tasks = Arc::clone(&self.tasks);
+        let finished = Arc::clone(&self.finished);
+        let logger = Arc::clone(&self.logger);
+
+        Self::set_task(&tasks, id, TaskState::Running("准备中...".to_string()));
+        if let Ok(mut logger) = logger.lock() {
+            logger.info("组件", &format!("开始安装 {} {}", id.name(), version));
+        }
+
+        std::thread::spawn(move || {
+            let progress_tasks = Arc::clone(&tasks);
+            let progress = move |message: String| Self::set_task(&progress_tasks, id, TaskState::Running(message));
+            match install_component(id, &version, pinned, custom_url, &progress) {
+                Ok(component) => {
+                    if let Ok(mut logger) = logger.lock() {
+                        logger.info("组件", &format!("{} {} 安装完成，SHA-256: {}", id.name(), component.version, component.sha256));
+                    }
+                    if let Ok(mut installed) = installed.lock() {
+                        installed.insert(id, component);
+                        let result = utils::get_config_path(COMPONENTS_FILE)
+                            .and_then(|path| utils::save_versioned_config(&*installed, &path));
+                        if let Err(e) = result {
+                            if let Ok(mut logger) = logger.lock() {
+                                logger.error("组件", &format!("保存组件记录失败: {}", e));
+                            }
+                        }
+                    }
+                    Self::set_task(&tasks, id, TaskState::Idle);
+                }
+                Err(e) => {
+                    if let Ok(mut logger) = logger.lock() {
+                        logger.error("组件", &format!("{} 安装失败: {:#}", id.name(), e));
+                    }
+                    Self::set_task(&tasks, id, TaskState::Failed(format!("{:#}", e)));
+                }
+            }
+            if let Ok(mut finished) = finished.lock() {
+                finished.push(id);
+            }
+        });
+    }
+
+    // 在后台检查所有组件的最新版本
+    fn check_updates(&self) {
+        self.check_versions(ComponentId::all().to_vec());
+    }
+
+    // 在后台检查指定组件的最新版本
+    pub fn check_update(&self, id: ComponentId) {
+        self.check_versions(vec![id]);
+    }
+
+    fn check_versions(&self, ids: Vec<ComponentId>) {
+        let latest = Arc::clone(&self.latest);
+        let logger = Arc::clone(&self.logger);
+        std::thread::spawn(move || {
+            for id in ids {
+                match fetch_latest_version(id) {
+                    Ok(version) => {
+                        if let Ok(mut latest) = latest.lock() {
+                            latest.insert(id, version);
+                        }
+                    }
+                    Err(e) => {
+                        if let Ok(mut logger) = logger.lock() {
+                            logger.warning("组件", &format!("检查 {} 更新失败: {}", id.name(), e));
+                        }
+                    }
+                }
+            }
+        });
+    }
+
+    // 渲染UI
+    pub fn ui(&mut self, ui: &mut Ui) {
+        ui.horizontal(|ui| {
+            ui.label("组件下载后会校验上游签名或SHA-256，并安装到程序目录。");
+            if ui.button("检查更新").clicked() {
+                self.check_updates();
+            }
+        });
+
+        let installed = self.installed.lock().map(|i| i.clone()).unwrap_or_default();
+        let tasks = self.tasks.lock().map(|t| t.clone()).unwrap_or_default();
+        let latest = self.latest.lock().map(|l| l.clone()).unwrap_or_default();
+        let mut install_request = None;
+
+        Grid::new("components_grid")
+            .num_columns(5)
+            .striped(true)
+            .spacing([10.0, 4.0])
+            .show(ui, |ui| {
+                ui.label(RichText::new("组件").strong());
+                ui.label(RichText::new("已安装").strong());
+                ui.label(RichText::new("最新").strong());
+                ui.label(RichText::new("固定SHA-256").strong());
+                ui.label(RichText::new("操作").strong());
+                ui.end_row();
+
+                for id in ComponentId::all() {
+                    let current = installed.get(&id);
+                    let target = latest.get(&id).cloned().unwrap_or_else(|| id.default_version().to_string());
+
+                    ui.label(id.name());
+                    match current {
+                        Some(component) => ui.label(&component.version).on_hover_text(format!("SHA-256: {}\n安装于 {}", component.sha256, component.installed_at)),
+                        None => ui.label(RichText::new("未安装").color(Color32::GRAY)),
+                    };
+                    ui.label(&target);
+
+                    // 没有上游校验来源的组件需要为要安装的版本手动固定校验值
+                    if id.has_upstream_verification() {
+                        ui.label(RichText::new("使用上游签名或校验文件").color(Color32::GRAY));
+                    } else {
+                        ui.vertical(|ui| {
+                            if id.download_url(&target).is_none() {
+                                let url = self.custom_urls.entry((id, target.clone())).or_default();
+                                ui.add(eframe::egui::TextEdit::singleline(url).hint_text(format!("{} 版本的下载地址", target)).desired_width(200.0));
+                            }
+                            let hash = self.pinned_hashes.entry((id, target.clone())).or_default();
+                            ui.add(eframe::egui::TextEdit::singleline(hash).hint_text(format!("{} 版本的SHA-256", target)).desired_width(200.0));
+                        });
+                    }
+
+                    match tasks.get(&id).cloned().unwrap_or(TaskState::Idle) {
+                        TaskState::Running(message) => {
+                            ui.horizontal(|ui| {
+                                ui.spinner();
+                                ui.label(message);
+                            });
+                        }
+                        state => {
+                            ui.horizontal(|ui| {
+                                let up_to_date = current.map_or(false, |c| c.version == target);
+                                let label = if current.is_none() { "安装" } else { "更新" };
+                                if ui.add_enabled(!up_to_date, eframe::egui::Button::new(label)).clicked() {
+                                    install_request = Some((id, target.clone()));
+                                }
+                                if let TaskState::Failed(error) = state {
+                                    ui.label(RichText::new("失败").color(Color32::RED)).on_hover_text(error);
+                                }
+                            });
+                        }
+                    }
+                    ui.end_row();
+                }
+            });
+
+        if let Some((id, version)) = install_request {
+            if self.in_use.contains(&id) {
+                if let Ok(mut logger) = self.logger.lock() {
+                    logger.info("组件", &format!("{} 正在运行，将先停止再更新", id.name()));
+                }
+                self.update_requests.push((id, version));
+            } else {
+                self.install(id, version);
+            }
+        }
+    }
+}
@@ -0,0 +1,266 @@
+use eframe::egui::{self, Color32, RichText, Ui};
+use std::process::Command;
+use std::sync::{Arc, Mutex};
+
+use crate::logger::Logger;
+use crate::netif::{self, AdapterKind};
+use crate::ports::Protocol;
+use crate::services;
+
+// 诊断问题严重程度
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum IssueSeverity {
+    Warning,
+    Error,
+}
+
+// 诊断问题
+#[derive(Clone, Debug)]
+pub struct DiagnosticIssue {
+    pub severity: IssueSeverity,
+    pub title: String,
+    pub detail: String,
+    pub remediation: String,
+}
+
+impl DiagnosticIssue {
+    fn new(severity: IssueSeverity, title: &str, detail: &str, remediation: &str) -> Self {
+        Self {
+            severity,
+            title: title.to_string(),
+            detail: detail.to_string(),
+            remediation: remediation.to_string(),
+        }
+    }
+}
+
+// 本软件需要使用的端口：(端口, 协议, 用途, 解决建议)
+const REQUIRED_PORTS: [(u16, Protocol, &str, &str); 5] = [
+    (53, Protocol::Udp, "本地DNS解析（DNSCrypt）", "关闭占用53端口的DNS服务（如Acrylic、Unbound、Windows DNS服务器角色），或在DNSCrypt设置中更换监听地址。"),
+    (1080, Protocol::Tcp, "统一代理服务", "关闭占用1080端口的代理工具，或在代理设置中更换监听端口。"),
+    (9050, Protocol::Tcp, "Tor SOCKS端口", "关闭其他Tor实例（如Tor Browser之外单独运行的tor.exe），或更换Tor的SocksPort。"),
+    (9051, Protocol::Tcp, "Tor控制端口", "关闭其他Tor实例，或更换Tor的ControlPort。"),
+    (4444, Protocol::Tcp, "I2P HTTP代理", "关闭其他I2P路由器（如Java I2P、独立运行的i2pd），或更换隧道端口。"),
+];
+
+// 已知的冲突软件：(进程名, 显示名称, 类别)
+const KNOWN_CONFLICTS: [(&str, &str, &str); 24] = [
+    ("openvpn.exe", "OpenVPN", "VPN客户端"),
+    ("openvpn-gui.exe", "OpenVPN GUI", "VPN客户端"),
+    ("wireguard.exe", "WireGuard", "VPN客户端"),
+    ("nordvpn.exe", "NordVPN", "VPN客户端"),
+    ("expressvpn.exe", "ExpressVPN", "VPN客户端"),
+    ("protonvpn.exe", "Proton VPN", "VPN客户端"),
+    ("clash-verge.exe", "Clash Verge", "代理工具"),
+    ("clash.exe", "Clash", "代理工具"),
+    ("v2rayn.exe", "v2rayN", "代理工具"),
+    ("v2ray.exe", "V2Ray", "代理工具"),
+    ("xray.exe", "Xray", "代理工具"),
+    ("sing-box.exe", "sing-box", "代理工具"),
+    ("shadowsocks.exe", "Shadowsocks", "代理工具"),
+    ("privoxy.exe", "Privoxy", "代理工具"),
+    ("proxifier.exe", "Proxifier", "代理工具"),
+    ("dnscrypt-proxy.exe", "dnscrypt-proxy（外部实例）", "DNS服务"),
+    ("acrylicservice.exe", "Acrylic DNS Proxy", "DNS服务"),
+    ("unbound.exe", "Unbound", "DNS服务"),
+    ("stubby.exe", "Stubby", "DNS服务"),
+    ("simplewall.exe", "simplewall", "第三方防火墙"),
+    ("tinywall.exe", "TinyWall", "第三方防火墙"),
+    ("glasswire.exe", "GlassWire", "第三方防火墙"),
+    ("portmaster-core.exe", "Portmaster", "第三方防火墙"),
+    ("cmdagent.exe", "Comodo Firewall", "第三方防火墙"),
+];
+
+// 获取正在运行的进程名列表（小写）
+fn list_running_processes() -> Vec<String> {
+    let output = match Command::new("tasklist").args(["/FO", "CSV", "/NH"]).output() {
+        Ok(output) => output,
+        Err(_) => return Vec::new(),
+    };
+
+    String::from_utf8_lossy(&output.stdout)
+        .lines()
+        .filter_map(|line| line.split(',').next())
+        .map(|name| name.trim_matches('"').to_lowercase())
+        .filter(|name| !name.is_empty())
+        .collect()
+}
+
+// 获取系统中存在的TUN/TAP虚拟网卡描述
+fn list_tun_adapters() -> Vec<String> {
+    netif::enumerate_adapters()
+        .into_iter()
+        .filter(|adapter| adapter.kind == AdapterKind::Tunnel)
+        .map(|adapter| adapter.description)
+        .collect()
+}
+
+// 执行启动诊断，返回发现的问题
+pub fn run_startup_checks() -> Vec<DiagnosticIssue> {
+    let mut issues = Vec::new();
+    let network = services::network();
+
+    // 检查端口占用
+    for (port, protocol, usage, remediation) in REQUIRED_PORTS {
+        if network.is_port_in_use("127.0.0.1", port, protocol) {
+            let owner = match network.port_owner(port, protocol) {
+                Some(owner) => format!("占用进程: {}。", owner.describe()),
+                None => String::new(),
+            };
+            issues.push(DiagnosticIssue::new(
+                IssueSeverity::Error,
+                &format!("端口 {} 已被占用", port),
+                &format!("{}需要使用该端口，启动时将会失败。{}", usage, owner),
+                remediation,
+            ));
+        }
+    }
+
+    // 检查冲突进程
+    let processes = list_running_processes();
+    for (process, name, category) in KNOWN_CONFLICTS {
+        if processes.iter().any(|p| p == process) {
+            let remediation = match category {
+                "VPN客户端" => "同时运行多个VPN可能导致路由冲突和流量泄露，请在使用本软件的VPN或Tor前断开该客户端。",
+                "代理工具" => "其他代理工具可能修改系统代理或占用相同端口，请关闭该工具或确保其端口与本软件不同。",
+                "DNS服务" => "其他DNS服务会与DNSCrypt争用53端口并可能导致DNS泄露，请停止该服务。",
+                _ => "多个防火墙同时工作可能互相覆盖规则，请禁用该防火墙或仅保留一个。",
+            };
+            issues.push(DiagnosticIssue::new(
+                IssueSeverity::Warning,
+                &format!("检测到{}: {}", category, name),
+                &format!("进程 {} 正在运行。", process),
+                remediation,
+            ));
+        }
+    }
+
+    // 检查其他TUN/TAP网卡
+    for adapter in list_tun_adapters() {
+        issues.push(DiagnosticIssue::new(
+            IssueSeverity::Warning,
+            "检测到其他虚拟网卡",
+            &format!("网卡 '{}' 可能属于其他VPN软件。", adapter),
+            "如果该网卡处于连接状态，可能会接管默认路由，请断开对应的VPN或在网络设置中禁用该网卡。",
+        ));
+    }
+
+    issues
+}
+
+// 诊断面板
+pub struct DiagnosticsPanel {
+    issues: Arc<Mutex<Option<Vec<DiagnosticIssue>>>>,
+    logger: Arc<Mutex<Logger>>,
+    show_window: bool,
+    startup_shown: bool,
+}
+
+impl DiagnosticsPanel {
+    pub fn new(logger: Arc<Mutex<Logger>>) -> Self {
+        let panel = Self {
+            issues: Arc::new(Mutex::new(None)),
+            logger,
+            show_window: false,
+            startup_shown: false,
+        };
+        panel.run_checks();
+        panel
+    }
+
+    // 在后台线程中执行诊断
+    pub fn run_checks(&self) {
+        if let Ok(mut issues) = self.issues.lock() {
+            *issues = None;
+        }
+
+        let issues = Arc::clone(&self.issues);
+        let logger = Arc::clone(&self.logger);
+        std::thread::spawn(move || {
+            let result = run_startup_checks();
+            if let Ok(mut logger) = logger.lock() {
+                if result.is_empty() {
+                    logger.info("诊断", "未发现冲突软件或端口占用");
+                } else {
+                    for issue in &result {
+                        logger.warning("诊断", &format!("{}: {}", issue.title, issue.detail));
+                    }
+                }
+            }
+            if let Ok(mut issues) = issues.lock() {
+                *issues = Some(result);
+            }
+        });
+    }
+
+    // 渲染问题列表
+    fn issues_ui(ui: &mut Ui, issues: &[DiagnosticIssue]) {
+        for issue in issues {
+            ui.group(|ui| {
+                let color = match issue.severity {
+                    IssueSeverity::Error => Color32::from_rgb(220, 53, 69),
+                    IssueSeverity::Warning => Color32::from_rgb(255, 193, 7),
+                };
+                ui.label(RichText::new(&issue.title).color(color).strong());
+                ui.label(&issue.detail);
+                ui.label(format!("建议: {}", issue.remediation));
+            });
+        }
+    }
+
+    // 启动时发现问题则弹出诊断窗口
+    pub fn show_startup_window(&mut self, ctx: &egui::Context) {
+        let issues = self.issues.lock().ok().and_then(|i| i.clone());
+        let issues = match issues {
+            Some(issues) if !issues.is_empty() => issues,
+            _ => return,
+        };
+
+        if !self.startup_shown {
+            self.show_window = true;
+            self.startup_shown = true;
+        }
+        if !self.show_window {
+            return;
+        }
+
+        let mut open = true;
+        egui::Window::new("启动诊断")
+            .open(&mut open)
+            .collapsible(false)
+            .default_width(500.0)
+            .show(ctx, |ui| {
+                ui.label(format!("检测到 {} 个可能影响本软件运行的问题:", issues.len()));
+                egui::ScrollArea::vertical().max_height(400.0).show(ui, |ui| {
+                    Self::issues_ui(ui, &issues);
+                });
+            });
+        self.show_window = open;
+    }
+
+    // 渲染UI（设置页中）
+    pub fn ui(&mut self, ui: &mut Ui) {
+        let issues = self.issues.lock().ok().and_then(|i| i.clone());
+
+        ui.horizontal(|ui| {
+            if ui.button("重新检测").clicked() {
+                self.run_checks();
+            }
+            match &issues {
+                None => {
+                    ui.label("正在检测...");
+                }
+                Some(issues) if issues.is_empty() => {
+                    ui.label(RichText::new("未发现问题").color(Color32::GREEN));
+                }
+                Some(issues) => {
+                    ui.label(RichText::new(format!("发现 {} 个问题", issues.len())).color(Color32::YELLOW));
+                }
+            }
+        });
+
+        if let Some(issues) = issues {
+            Self::issues_ui(ui, &issues);
+        }
+    }
+}
@@ -0,0 +1,177 @@
+use std::collections::BTreeSet;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+use anyhow::{anyhow, Context, Result};
+use serde::{Deserialize, Serialize};
+
+use crate::dns_rules;
+
+// 合并后交给dnscrypt-proxy的屏蔽列表文件名
+pub const BLOCKED_NAMES_FILE: &str = "blocked-names.txt";
+
+// 一个屏蔽列表来源
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct BlocklistSource {
+    pub url: String,
+    pub enabled: bool,
+    pub last_updated: Option<String>,
+    pub entries: usize,
+    pub last_error: Option<String>,
+}
+
+impl BlocklistSource {
+    pub fn new(url: &str) -> Self {
+        Self {
+            url: url.to_string(),
+            enabled: true,
+            last_updated: None,
+            entries: 0,
+            last_error: None,
+        }
+    }
+}
+
+// 内置的屏蔽分类，每个分类对应几个常用的公开列表
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum BlocklistCategory {
+    Ads,
+    Trackers,
+    Malware,
+    Adult,
+}
+
+impl BlocklistCategory {
+    pub const ALL: [BlocklistCategory; 4] = [
+        BlocklistCategory::Ads,
+        BlocklistCategory::Trackers,
+        BlocklistCategory::Malware,
+        BlocklistCategory::Adult,
+    ];
+
+    pub fn label(&self) -> &'static str {
+        match self {
+            BlocklistCategory::Ads => "广告",
+            BlocklistCategory::Trackers => "跟踪器",
+            BlocklistCategory::Malware => "恶意软件",
+            BlocklistCategory::Adult => "成人内容",
+        }
+    }
+
+    pub fn description(&self) -> &'static str {
+        match self {
+            BlocklistCategory::Ads => "AdAway与Peter Lowe的广告服务器列表",
+            BlocklistCategory::Trackers => "EasyPrivacy与第一方跟踪器列表",
+            BlocklistCategory::Malware => "URLhaus恶意软件分发域名与RPiList恶意软件列表",
+            BlocklistCategory::Adult => "OISD成人内容列表",
+        }
+    }
+
+    pub fn urls(&self) -> &'static [&'static str] {
+        match self {
+            BlocklistCategory::Ads => &[
+                "https://adaway.org/hosts.txt",
+                "https://pgl.yoyo.org/adservers/serverlist.php?hostformat=hosts&showintro=0&mimetype=plaintext",
+            ],
+            BlocklistCategory::Trackers => &[
+                "https://v.firebog.net/hosts/Easyprivacy.txt",
+                "https://hostfiles.frogeye.fr/firstparty-trackers-hosts.txt",
+            ],
+            BlocklistCategory::Malware => &[
+                "https://urlhaus.abuse.ch/downloads/hostfile/",
+                "https://v.firebog.net/hosts/RPiList-Malware.txt",
+            ],
+            BlocklistCategory::Adult => &["https://nsfw.oisd.nl/domainswild"],
+        }
+    }
+
+    // 分类的所有列表都已添加并启用
+    pub fn is_enabled(&self, sources: &[BlocklistSource]) -> bool {
+        self.urls().iter().all(|url| sources.iter().any(|s| s.url == *url && s.enabled))
+    }
+
+    // 添加并启用分类的列表，返回是否有新添加的列表
+    pub fn enable(&self, sources: &mut Vec<BlocklistSource>) -> bool {
+        let mut added = false;
+        for url in self.urls() {
+            match sources.iter_mut().find(|s| s.url == *url) {
+                Some(source) => source.enabled = true,
+                None => {
+                    sources.push(BlocklistSource::new(url));
+                    added = true;
+                }
+            }
+        }
+        added
+    }
+
+    // 移除分类的列表
+    pub fn disable(&self, sources: &mut Vec<BlocklistSource>) {
+        sources.retain(|s| !self.urls().contains(&s.url.as_str()));
+    }
+}
+
+// 下载的列表缓存在工作目录下，按地址的FNV-1a哈希命名
+fn cache_file(home: &Path, url: &str) -> PathBuf {
+    let hash = url.bytes().fold(0xcbf29ce484222325u64, |hash, b| (hash ^ b as u64).wrapping_mul(0x100000001b3));
+    home.join("blocklists").join(format!("{:016x}.txt", hash))
+}
+
+// 从一行中取出域名，支持纯域名、hosts格式（0.0.0.0 example.com）与AdBlock格式（||example.com^）
+fn parse_line(line: &str) -> Option<String> {
+    let line = line.split('#').next()?.trim();
+    if line.is_empty() || line.starts_with('!') || line.starts_with('[') {
+        return None;
+    }
+    let mut parts = line.split_whitespace();
+    let first = parts.next()?;
+    let name = match first {
+        "0.0.0.0" | "127.0.0.1" | "::" | "::1" => parts.next()?,
+        _ => first,
+    };
+    let name = name.strip_prefix("||").map(|n| n.trim_end_matches('^')).unwrap_or(name).to_lowercase();
+    if name == "localhost" || dns_rules::validate_pattern(&name).is_err() {
+        return None;
+    }
+    Some(name)
+}
+
+// 下载一个列表并缓存，返回条目数
+pub fn update_source(home: &Path, url: &str) -> Result<usize> {
+    let client = reqwest::blocking::Client::builder()
+        .timeout(Duration::from_secs(60))
+        .build()
+        .context("Failed to create HTTP client")?;
+    let response = client.get(url).send().with_context(|| format!("Failed to download {}", url))?;
+    if !response.status().is_success() {
+        return Err(anyhow!("HTTP {}", response.status()));
+    }
+    let body = response.text().context("Failed to read blocklist")?;
+    let names: BTreeSet<String> = body.lines().filter_map(parse_line).collect();
+    if names.is_empty() {
+        return Err(anyhow!("No domains found in {}", url));
+    }
+    let path = cache_file(home, url);
+    if let Some(dir) = path.parent() {
+        fs::create_dir_all(dir).context("Failed to create blocklist directory")?;
+    }
+    let contents: String = names.iter().map(|name| format!("{}\n", name)).collect();
+    fs::write(&path, contents).context("Failed to save blocklist")?;
+    Ok(names.len())
+}
+
+// 合并已启用列表的缓存与额外的域名（防火墙的域名规则）写入blocked-names.txt，返回去重后的条目数
+// 没有条目时写入空文件，以便配置中始终可以引用该文件
+pub fn combine(home: &Path, sources: &[BlocklistSource], extra: &[String]) -> Result<usize> {
+    let mut names: BTreeSet<String> = extra.iter().cloned().collect();
+    for source in sources.iter().filter(|s| s.enabled) {
+        // 尚未下载成功的列表跳过
+        if let Ok(contents) = fs::read_to_string(cache_file(home, &source.url)) {
+            names.extend(contents.lines().map(str::to_string));
+        }
+    }
+    let path = home.join(BLOCKED_NAMES_FILE);
+    let contents: String = names.iter().map(|name| format!("{}\n", name)).collect();
+    fs::write(&path, contents).context("Failed to write blocked names")?;
+    Ok(names.len())
+}
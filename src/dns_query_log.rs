@@ -0,0 +1,398 @@
+use eframe::egui::{self, Color32, Grid, RichText, ScrollArea, Ui};
+use std::collections::{HashMap, HashSet, VecDeque};
+use std::fs::File;
+use std::io::{BufRead, BufReader, Seek, SeekFrom};
+use std::path::PathBuf;
+use std::time::{Duration, Instant};
+
+// 界面中保留的最多查询条数
+const MAX_ENTRIES: usize = 2000;
+// 读取日志文件的间隔
+const POLL_INTERVAL: Duration = Duration::from_secs(1);
+// 图表显示的分钟数
+const CHART_MINUTES: usize = 30;
+// 显示的拦截最多的域名数
+const TOP_BLOCKED: usize = 10;
+
+// dnscrypt-proxy查询日志（tsv格式）中的一条记录
+#[derive(Clone, Debug)]
+pub struct QueryEntry {
+    pub time: String,
+    pub client: String,
+    pub name: String,
+    pub qtype: String,
+    pub result: String, // PASS、REJECT、CLOAK、FORWARD等
+    pub latency_ms: Option<u32>,
+    pub server: String, // 使用的服务器，缓存命中或被拦截时为"-"
+}
+
+impl QueryEntry {
+    // 被屏蔽规则拦截的查询
+    pub fn blocked(&self) -> bool {
+        matches!(self.result.as_str(), "REJECT" | "DROP")
+    }
+
+    // 没有得到正常结果的查询
+    pub fn failed(&self) -> bool {
+        self.result.ends_with("ERROR") || matches!(self.result.as_str(), "SERVER_TIMEOUT" | "NOT_READY")
+    }
+}
+
+// 解析一行，如"[2024-01-01 12:00:00]\t127.0.0.1\texample.com\tA\tPASS\t25ms\tcloudflare"
+pub fn parse_line(line: &str) -> Option<QueryEntry> {
+    let fields: Vec<&str> = line.trim_end().split('\t').collect();
+    if fields.len() < 7 {
+        return None;
+    }
+    Some(QueryEntry {
+        time: fields[0].trim_matches(|c| c == '[' || c == ']').to_string(),
+        client: fields[1].to_string(),
+        name: fields[2].to_string(),
+        qtype: fields[3].to_string(),
+        result: fields[4].to_string(),
+        latency_ms: fields[5].trim_end_matches("ms").parse().ok(),
+        server: fields[6].to_string(),
+    })
+}
+
+// 根据查询日志统计的缓存情况（dnscrypt-proxy不提供缓存接口）
+#[derive(Clone, Debug, Default)]
+pub struct CacheStats {
+    pub hits: u64,
+    pub misses: u64,
+    cached: HashSet<(String, String)>, // 从上游得到结果的(域名, 类型)
+}
+
+impl CacheStats {
+    fn record(&mut self, entry: &QueryEntry) {
+        if entry.result != "PASS" {
+            return;
+        }
+        // 缓存命中的查询不经过服务器，日志中服务器为"-"
+        if entry.server == "-" {
+            self.hits += 1;
+        } else {
+            self.misses += 1;
+            self.cached.insert((entry.name.to_lowercase(), entry.qtype.clone()));
+        }
+    }
+
+    pub fn hit_ratio(&self) -> Option<f32> {
+        let total = self.hits + self.misses;
+        (total > 0).then(|| self.hits as f32 / total as f32)
+    }
+
+    // 估算的缓存条目数（未计入过期与淘汰）
+    pub fn entries(&self) -> usize {
+        self.cached.len()
+    }
+}
+
+// 每分钟的查询数与拦截数
+#[derive(Clone, Copy, Debug)]
+struct MinuteBucket {
+    minute: i64, // Unix时间戳 / 60
+    total: u64,
+    blocked: u64,
+}
+
+// 本次会话的查询与拦截统计
+#[derive(Clone, Debug, Default)]
+pub struct QueryStats {
+    pub total: u64,
+    pub blocked: u64,
+    blocked_domains: HashMap<String, u64>,
+    minutes: VecDeque<MinuteBucket>,
+}
+
+impl QueryStats {
+    fn record(&mut self, entry: &QueryEntry) {
+        let blocked = entry.blocked();
+        self.total += 1;
+        if blocked {
+            self.blocked += 1;
+            *self.blocked_domains.entry(entry.name.to_lowercase()).or_insert(0) += 1;
+        }
+
+        // 按读取日志的时间分桶，日志每秒读取一次，误差可以忽略
+        let minute = chrono::Local::now().timestamp() / 60;
+        if self.minutes.back().map_or(true, |b| b.minute != minute) {
+            self.minutes.push_back(MinuteBucket { minute, total: 0, blocked: 0 });
+        }
+        if let Some(bucket) = self.minutes.back_mut() {
+            bucket.total += 1;
+            bucket.blocked += blocked as u64;
+        }
+        while self.minutes.front().map_or(false, |b| b.minute + (CHART_MINUTES as i64) <= minute) {
+            self.minutes.pop_front();
+        }
+    }
+
+    pub fn blocked_ratio(&self) -> Option<f32> {
+        (self.total > 0).then(|| self.blocked as f32 / self.total as f32)
+    }
+
+    // 拦截次数最多的域名
+    pub fn top_blocked(&self, count: usize) -> Vec<(&str, u64)> {
+        let mut domains: Vec<(&str, u64)> = self.blocked_domains.iter().map(|(name, &hits)| (name.as_str(), hits)).collect();
+        domains.sort_by(|a, b| b.1.cmp(&a.1).then_with(|| a.0.cmp(b.0)));
+        domains.truncate(count);
+        domains
+    }
+
+    // 最近CHART_MINUTES分钟的柱状图，灰色为全部查询，红色为被拦截的部分
+    fn chart(&self, ui: &mut Ui) {
+        let size = egui::vec2(ui.available_width().min(480.0), 80.0);
+        let (rect, response) = ui.allocate_exact_size(size, egui::Sense::hover());
+        let painter = ui.painter_at(rect);
+        painter.rect_filled(rect, 2.0, ui.visuals().extreme_bg_color);
+
+        let now = chrono::Local::now().timestamp() / 60;
+        let max = self.minutes.iter().map(|b| b.total).max().unwrap_or(0).max(1) as f32;
+        let bar_width = rect.width() / CHART_MINUTES as f32;
+        let mut hovered = None;
+        for bucket in &self.minutes {
+            let age = (now - bucket.minute) as usize;
+            if age >= CHART_MINUTES {
+                continue;
+            }
+            let left = rect.right() - (age + 1) as f32 * bar_width;
+            let bar = |count: u64| {
+                let height = count as f32 / max * (rect.height() - 4.0);
+                egui::Rect::from_min_max(
+                    egui::pos2(left + 1.0, rect.bottom() - height),
+                    egui::pos2(left + bar_width - 1.0, rect.bottom()),
+                )
+            };
+            painter.rect_filled(bar(bucket.total), 0.0, Color32::GRAY);
+            painter.rect_filled(bar(bucket.blocked), 0.0, Color32::RED);
+            if response.hover_pos().map_or(false, |pos| pos.x >= left && pos.x < left + bar_width) {
+                hovered = Some((age, *bucket));
+            }
+        }
+        match hovered {
+            Some((age, bucket)) => {
+                response.on_hover_text(format!("{} 分钟前: 查询 {}，拦截 {}", age, bucket.total, bucket.blocked));
+            }
+            None => {
+                response.on_hover_text(format!("最近 {} 分钟每分钟的查询数（红色为被拦截）", CHART_MINUTES));
+            }
+        }
+    }
+
+    pub fn ui(&self, ui: &mut Ui) {
+        let ratio = self.blocked_ratio().map(|r| format!("{:.1}%", r * 100.0)).unwrap_or_else(|| "-".to_string());
+        ui.horizontal(|ui| {
+            ui.label(format!("查询总数: {}", self.total));
+            ui.label(RichText::new(format!("已拦截: {}（{}）", self.blocked, ratio)).color(Color32::RED));
+            ui.label(format!("被拦截的域名: {}", self.blocked_domains.len()));
+        });
+        self.chart(ui);
+
+        let top = self.top_blocked(TOP_BLOCKED);
+        if top.is_empty() {
+            return;
+        }
+        ui.label(RichText::new("拦截最多的域名").strong());
+        Grid::new("dns_top_blocked_grid")
+            .num_columns(2)
+            .striped(true)
+            .spacing([20.0, 2.0])
+            .show(ui, |ui| {
+                for (name, hits) in top {
+                    ui.label(RichText::new(name).monospace());
+                    ui.label(hits.to_string());
+                    ui.end_row();
+                }
+            });
+    }
+}
+
+// 实时读取查询日志并显示
+pub struct QueryLogViewer {
+    path: Option<PathBuf>,
+    offset: u64,
+    entries: VecDeque<QueryEntry>,
+    last_poll: Option<Instant>,
+    paused: bool,
+    search: String,
+    only_blocked: bool,
+    cache_stats: CacheStats,
+    query_stats: QueryStats,
+    new_entries: Vec<QueryEntry>, // 上次取走后新读到的记录，供防火墙的域名规则使用
+}
+
+impl QueryLogViewer {
+    pub fn new() -> Self {
+        Self {
+            path: None,
+            offset: 0,
+            entries: VecDeque::new(),
+            last_poll: None,
+            paused: false,
+            search: String::new(),
+            only_blocked: false,
+            cache_stats: CacheStats::default(),
+            query_stats: QueryStats::default(),
+            new_entries: Vec::new(),
+        }
+    }
+
+    // 切换到新的日志文件，从文件末尾开始读取
+    pub fn set_path(&mut self, path: Option<PathBuf>) {
+        if self.path == path {
+            return;
+        }
+        self.offset = path.as_ref()
+            .and_then(|p| std::fs::metadata(p).ok())
+            .map(|m| m.len())
+            .unwrap_or(0);
+        self.path = path;
+    }
+
+    // 读取上次位置之后新增的行；文件变小（被轮换）时从头读取
+    fn poll(&mut self) {
+        let path = match &self.path {
+            Some(path) => path,
+            None => return,
+        };
+        let mut file = match File::open(path) {
+            Ok(file) => file,
+            Err(_) => return,
+        };
+        let len = file.metadata().map(|m| m.len()).unwrap_or(0);
+        if len < self.offset {
+            self.offset = 0;
+        }
+        if len == self.offset || file.seek(SeekFrom::Start(self.offset)).is_err() {
+            return;
+        }
+        let mut reader = BufReader::new(file);
+        let mut line = String::new();
+        // 只处理完整的行，未写完的行下次再读
+        while let Ok(read) = reader.read_line(&mut line) {
+            if read == 0 || !line.ends_with('\n') {
+                break;
+            }
+            self.offset += read as u64;
+            if let Some(entry) = parse_line(&line) {
+                self.cache_stats.record(&entry);
+                self.query_stats.record(&entry);
+                self.new_entries.push(entry.clone());
+                self.entries.push_back(entry);
+            }
+            line.clear();
+        }
+        while self.entries.len() > MAX_ENTRIES {
+            self.entries.pop_front();
+        }
+        // 没有人取走时只保留最近的记录
+        if self.new_entries.len() > MAX_ENTRIES {
+            self.new_entries.drain(..self.new_entries.len() - MAX_ENTRIES);
+        }
+    }
+    
+    // 取走上次调用后新读到的记录
+    pub fn take_new_entries(&mut self) -> Vec<QueryEntry> {
+        std::mem::take(&mut self.new_entries)
+    }
+
+    pub fn cache_stats(&self) -> &CacheStats {
+        &self.cache_stats
+    }
+
+    pub fn reset_cache_stats(&mut self) {
+        self.cache_stats = CacheStats::default();
+    }
+
+    pub fn query_stats(&self) -> &QueryStats {
+        &self.query_stats
+    }
+
+    pub fn reset_query_stats(&mut self) {
+        self.query_stats = QueryStats::default();
+    }
+
+    // 到时间时读取新增的日志，不在DNSCrypt页面时也由模块的tick调用
+    pub fn tick(&mut self) {
+        if self.path.is_none() || self.paused {
+            return;
+        }
+        if self.last_poll.map_or(true, |t| t.elapsed() >= POLL_INTERVAL) {
+            self.poll();
+            self.last_poll = Some(Instant::now());
+        }
+    }
+    
+    // 定期读取新增的日志，面板折叠时统计也保持更新
+    pub fn update(&mut self, ctx: &egui::Context) {
+        if self.path.is_none() || self.paused {
+            return;
+        }
+        self.tick();
+        ctx.request_repaint_after(POLL_INTERVAL);
+    }
+
+    pub fn ui(&mut self, ui: &mut Ui) {
+        if self.path.is_none() {
+            ui.label("启用\"记录查询日志\"并启动DNSCrypt后在此显示解析的域名");
+            return;
+        }
+
+        ui.horizontal(|ui| {
+            ui.label("搜索:");
+            ui.add(egui::TextEdit::singleline(&mut self.search).hint_text("域名、类型或服务器").desired_width(200.0));
+            ui.checkbox(&mut self.only_blocked, "只看被拦截");
+            ui.checkbox(&mut self.paused, "暂停");
+            if ui.button("清空").clicked() {
+                self.entries.clear();
+            }
+        });
+
+        let search = self.search.trim().to_lowercase();
+        let visible: Vec<&QueryEntry> = self.entries.iter()
+            .filter(|e| !self.only_blocked || e.blocked())
+            .filter(|e| {
+                search.is_empty()
+                    || e.name.to_lowercase().contains(&search)
+                    || e.qtype.to_lowercase() == search
+                    || e.server.to_lowercase().contains(&search)
+            })
+            .collect();
+        ui.label(RichText::new(format!("显示 {} / {} 条", visible.len(), self.entries.len())).weak());
+
+        ScrollArea::vertical()
+            .id_source("dns_query_log")
+            .max_height(300.0)
+            .stick_to_bottom(true)
+            .show(ui, |ui| {
+                Grid::new("dns_query_log_grid")
+                    .num_columns(6)
+                    .striped(true)
+                    .spacing([10.0, 2.0])
+                    .show(ui, |ui| {
+                        for header in ["时间", "域名", "类型", "结果", "服务器", "耗时"] {
+                            ui.label(RichText::new(header).strong());
+                        }
+                        ui.end_row();
+
+                        for entry in visible {
+                            let color = if entry.blocked() {
+                                Color32::RED
+                            } else if entry.failed() {
+                                Color32::YELLOW
+                            } else {
+                                ui.visuals().text_color()
+                            };
+                            ui.label(RichText::new(&entry.time).small());
+                            ui.label(RichText::new(&entry.name).monospace()).on_hover_text(format!("客户端: {}", entry.client));
+                            ui.label(&entry.qtype);
+                            ui.label(RichText::new(&entry.result).color(color));
+                            ui.label(&entry.server);
+                            ui.label(entry.latency_ms.map(|ms| format!("{} ms", ms)).unwrap_or_else(|| "-".to_string()));
+                            ui.end_row();
+                        }
+                    });
+            });
+    }
+}
@@ -0,0 +1,174 @@
+use anyhow::Result;
+use serde::{Deserialize, Serialize};
+
+use crate::dns_blocklists::BlocklistSource;
+use crate::utils;
+
+// 规则文件名
+const RULES_FILE: &str = "dnscrypt_rules.json";
+// 默认每天自动更新一次公共服务器列表与屏蔽列表
+const DEFAULT_AUTO_UPDATE_HOURS: u32 = 24;
+
+fn default_auto_update_hours() -> u32 {
+    DEFAULT_AUTO_UPDATE_HOURS
+}
+
+// 域名规则格式：example.com（含子域名）、=example.com（仅此域名）、*.example.com、ads.*
+pub fn validate_pattern(pattern: &str) -> Result<(), String> {
+    let name = pattern.strip_prefix('=').unwrap_or(pattern);
+    if name.is_empty()
+        || name.starts_with('.')
+        || !name.chars().all(|c| c.is_ascii_alphanumeric() || matches!(c, '.' | '-' | '_' | '*'))
+    {
+        return Err(format!("域名规则格式无效 \"{}\"", pattern));
+    }
+    Ok(())
+}
+
+// 域名是否匹配规则，规则格式同validate_pattern
+pub fn pattern_matches(pattern: &str, name: &str) -> bool {
+    let name = name.trim_end_matches('.').to_lowercase();
+    let pattern = pattern.to_lowercase();
+    if let Some(exact) = pattern.strip_prefix('=') {
+        return name == exact;
+    }
+    if !pattern.contains('*') {
+        return name == pattern || name.ends_with(&format!(".{}", pattern));
+    }
+    // 通配符：依次匹配"*"分隔的各段
+    let parts: Vec<&str> = pattern.split('*').collect();
+    let mut rest = name.as_str();
+    for (index, part) in parts.iter().enumerate() {
+        if index == 0 {
+            match rest.strip_prefix(part) {
+                Some(stripped) => rest = stripped,
+                None => return false,
+            }
+        } else if index == parts.len() - 1 {
+            return rest.ends_with(part);
+        } else {
+            match rest.find(part) {
+                Some(pos) => rest = &rest[pos + part.len()..],
+                None => return false,
+            }
+        }
+    }
+    rest.is_empty()
+}
+
+// 伪装规则：把域名解析为指定IP，或作为另一个域名的别名（CNAME）
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+pub struct CloakingRule {
+    pub pattern: String,
+    pub target: String,
+}
+
+impl CloakingRule {
+    pub fn validate(&self) -> Result<(), String> {
+        validate_pattern(&self.pattern)?;
+        let target = self.target.as_str();
+        let is_ip = target.parse::<std::net::IpAddr>().is_ok();
+        let is_host = !target.is_empty()
+            && !target.starts_with('.')
+            && target.chars().all(|c| c.is_ascii_alphanumeric() || matches!(c, '.' | '-'));
+        if !is_ip && !is_host {
+            return Err(format!("目标应为IP地址或域名 \"{}\"", target));
+        }
+        Ok(())
+    }
+}
+
+// 转发规则：指定域名（含子域名）交给指定的普通DNS服务器解析，如内网域名
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+pub struct ForwardingRule {
+    pub domain: String,
+    pub servers: String, // 逗号分隔的IP[:端口]，按顺序尝试
+}
+
+impl ForwardingRule {
+    // 统一去掉"*."前缀，dnscrypt-proxy按后缀匹配
+    pub fn normalized(domain: &str, servers: &str) -> Self {
+        let domain = domain.trim().to_lowercase();
+        let domain = domain.trim_start_matches("*.").trim_matches('.').to_string();
+        let servers: Vec<&str> = servers.split(',').map(str::trim).filter(|s| !s.is_empty()).collect();
+        Self { domain, servers: servers.join(",") }
+    }
+
+    pub fn validate(&self) -> Result<(), String> {
+        if self.domain.is_empty()
+            || !self.domain.chars().all(|c| c.is_ascii_alphanumeric() || matches!(c, '.' | '-' | '_'))
+        {
+            return Err(format!("域名格式无效 \"{}\"", self.domain));
+        }
+        if self.servers.is_empty() {
+            return Err("请填写至少一个DNS服务器".to_string());
+        }
+        for server in self.servers.split(',') {
+            let valid = server.parse::<std::net::SocketAddr>().is_ok() || server.parse::<std::net::IpAddr>().is_ok();
+            if !valid {
+                return Err(format!("DNS服务器应为IP或IP:端口 \"{}\"", server));
+            }
+        }
+        Ok(())
+    }
+}
+
+// dnscrypt-proxy的伪装规则、白名单、转发规则与屏蔽列表
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct DnsRules {
+    pub cloaking: Vec<CloakingRule>,
+    pub allowlist: Vec<String>, // 始终放行、不受屏蔽列表影响的域名规则
+    #[serde(default)]
+    pub forwarding: Vec<ForwardingRule>,
+    #[serde(default)]
+    pub blocklists: Vec<BlocklistSource>,
+    #[serde(default = "default_auto_update_hours")]
+    pub auto_update_hours: u32,        // 0表示不自动更新
+    #[serde(default)]
+    pub lists_updated_at: Option<i64>, // 最近一次自动更新的时间（Unix时间戳）
+    #[serde(skip)]
+    pub firewall_blocked: Vec<String>, // 防火墙域名规则要阻止的域名，由防火墙模块设置，不保存
+}
+
+impl Default for DnsRules {
+    fn default() -> Self {
+        Self {
+            cloaking: Vec::new(),
+            allowlist: Vec::new(),
+            forwarding: Vec::new(),
+            blocklists: Vec::new(),
+            auto_update_hours: DEFAULT_AUTO_UPDATE_HOURS,
+            lists_updated_at: None,
+            firewall_blocked: Vec::new(),
+        }
+    }
+}
+
+impl utils::VersionedConfig for DnsRules {
+    const VERSION: u32 = 1;
+}
+
+impl DnsRules {
+    pub fn load() -> Self {
+        utils::load_saved_config(RULES_FILE).unwrap_or_default()
+    }
+
+    pub fn save(&self) -> Result<()> {
+        utils::save_versioned_config(self, &utils::get_config_path(RULES_FILE)?)
+    }
+
+    // cloaking-rules.txt内容，每行"规则 目标"
+    pub fn cloaking_file(&self) -> String {
+        self.cloaking.iter().map(|rule| format!("{} {}\n", rule.pattern, rule.target)).collect()
+    }
+
+    // forwarding-rules.txt内容，每行"域名 服务器1,服务器2"
+    pub fn forwarding_file(&self) -> String {
+        self.forwarding.iter().map(|rule| format!("{} {}\n", rule.domain, rule.servers)).collect()
+    }
+
+    // allowed-names.txt内容，每行一个规则
+    pub fn allowlist_file(&self) -> String {
+        self.allowlist.iter().map(|pattern| format!("{}\n", pattern)).collect()
+    }
+}
@@ -0,0 +1,189 @@
+use base64::{engine::general_purpose, Engine as _};
+
+// DNS Stamp中的协议类型
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum StampProtocol {
+    Plain,
+    DnsCrypt,
+    DoH,
+    DoT,
+    DoQ,
+    ODoHTarget,
+    DnsCryptRelay,
+    ODoHRelay,
+}
+
+impl StampProtocol {
+    fn from_byte(byte: u8) -> Option<Self> {
+        match byte {
+            0x00 => Some(StampProtocol::Plain),
+            0x01 => Some(StampProtocol::DnsCrypt),
+            0x02 => Some(StampProtocol::DoH),
+            0x03 => Some(StampProtocol::DoT),
+            0x04 => Some(StampProtocol::DoQ),
+            0x05 => Some(StampProtocol::ODoHTarget),
+            0x81 => Some(StampProtocol::DnsCryptRelay),
+            0x85 => Some(StampProtocol::ODoHRelay),
+            _ => None,
+        }
+    }
+
+    pub fn label(&self) -> &'static str {
+        match self {
+            StampProtocol::Plain => "DNS",
+            StampProtocol::DnsCrypt => "DNSCrypt",
+            StampProtocol::DoH => "DoH",
+            StampProtocol::DoT => "DoT",
+            StampProtocol::DoQ => "DoQ",
+            StampProtocol::ODoHTarget => "ODoH",
+            StampProtocol::DnsCryptRelay => "DNSCrypt中继",
+            StampProtocol::ODoHRelay => "ODoH中继",
+        }
+    }
+}
+
+// 解码后的DNS Stamp（只保留需要显示和使用的字段）
+#[derive(Clone, Debug)]
+pub struct DnsStamp {
+    pub protocol: StampProtocol,
+    pub dnssec: bool,
+    pub no_logs: bool,
+    pub no_filter: bool,
+    pub address: String,       // IP[:端口]，DoH等可为空（由主机名解析）
+    pub provider_name: String, // DNSCrypt的提供商名称，或DoH/DoT的主机名
+    pub path: String,          // DoH的路径，如"/dns-query"
+    pub public_key: Vec<u8>,   // DNSCrypt提供商公钥
+}
+
+// 按规范依次读取字段
+struct Reader<'a> {
+    bytes: &'a [u8],
+}
+
+impl<'a> Reader<'a> {
+    fn byte(&mut self) -> Result<u8, String> {
+        let (&first, rest) = self.bytes.split_first().ok_or("数据不完整")?;
+        self.bytes = rest;
+        Ok(first)
+    }
+
+    fn props(&mut self) -> Result<u64, String> {
+        if self.bytes.len() < 8 {
+            return Err("数据不完整".to_string());
+        }
+        let (props, rest) = self.bytes.split_at(8);
+        self.bytes = rest;
+        Ok(u64::from_le_bytes(props.try_into().unwrap_or_default()))
+    }
+
+    // 长度前缀的字段
+    fn lp(&mut self) -> Result<&'a [u8], String> {
+        let len = self.byte()? as usize;
+        if self.bytes.len() < len {
+            return Err("数据不完整".to_string());
+        }
+        let (value, rest) = self.bytes.split_at(len);
+        self.bytes = rest;
+        Ok(value)
+    }
+
+    fn lp_string(&mut self) -> Result<String, String> {
+        String::from_utf8(self.lp()?.to_vec()).map_err(|_| "包含无效的文本".to_string())
+    }
+
+    // 变长数组（长度字节最高位表示后面还有元素），如证书哈希列表
+    fn vlp(&mut self) -> Result<Vec<&'a [u8]>, String> {
+        let mut items = Vec::new();
+        loop {
+            let len = self.byte()?;
+            let size = (len & 0x7f) as usize;
+            if self.bytes.len() < size {
+                return Err("数据不完整".to_string());
+            }
+            let (value, rest) = self.bytes.split_at(size);
+            self.bytes = rest;
+            items.push(value);
+            if len & 0x80 == 0 {
+                return Ok(items);
+            }
+        }
+    }
+}
+
+// 解码"sdns://..."，错误信息用于直接显示
+pub fn parse(stamp: &str) -> Result<DnsStamp, String> {
+    let encoded = stamp.trim().strip_prefix("sdns://").ok_or("DNS Stamp应以sdns://开头")?;
+    let bytes = general_purpose::URL_SAFE_NO_PAD
+        .decode(encoded.trim_end_matches('='))
+        .map_err(|_| "DNS Stamp不是有效的Base64编码".to_string())?;
+    let mut reader = Reader { bytes: &bytes };
+    let protocol_byte = reader.byte()?;
+    let protocol = StampProtocol::from_byte(protocol_byte).ok_or(format!("不支持的协议类型 0x{:02x}", protocol_byte))?;
+
+    let props = match protocol {
+        StampProtocol::DnsCryptRelay => 0,
+        _ => reader.props()?,
+    };
+    let mut result = DnsStamp {
+        protocol,
+        dnssec: props & 1 != 0,
+        no_logs: props & 2 != 0,
+        no_filter: props & 4 != 0,
+        address: String::new(),
+        provider_name: String::new(),
+        path: String::new(),
+        public_key: Vec::new(),
+    };
+    match protocol {
+        StampProtocol::Plain | StampProtocol::DnsCryptRelay => {
+            result.address = reader.lp_string()?;
+        }
+        StampProtocol::DnsCrypt => {
+            result.address = reader.lp_string()?;
+            result.public_key = reader.lp()?.to_vec();
+            if result.public_key.len() != 32 {
+                return Err("DNSCrypt公钥长度应为32字节".to_string());
+            }
+            result.provider_name = reader.lp_string()?;
+        }
+        StampProtocol::DoH | StampProtocol::ODoHRelay => {
+            result.address = reader.lp_string()?;
+            reader.vlp()?;
+            result.provider_name = reader.lp_string()?;
+            result.path = reader.lp_string()?;
+        }
+        StampProtocol::DoT | StampProtocol::DoQ => {
+            result.address = reader.lp_string()?;
+            reader.vlp()?;
+            result.provider_name = reader.lp_string()?;
+        }
+        StampProtocol::ODoHTarget => {
+            result.provider_name = reader.lp_string()?;
+            result.path = reader.lp_string()?;
+        }
+    }
+    if result.address.is_empty() && result.provider_name.is_empty() {
+        return Err("DNS Stamp中没有服务器地址".to_string());
+    }
+    Ok(result)
+}
+
+// 长度前缀字段
+fn push_lp(bytes: &mut Vec<u8>, value: &str) -> Result<(), String> {
+    let len = u8::try_from(value.len()).map_err(|_| format!("字段过长: {}", value))?;
+    bytes.push(len);
+    bytes.extend_from_slice(value.as_bytes());
+    Ok(())
+}
+
+// 生成DoH服务器的DNS Stamp；address为空时由dnscrypt-proxy通过引导DNS解析主机名
+pub fn encode_doh(address: &str, host: &str, path: &str, dnssec: bool, no_logs: bool, no_filter: bool) -> Result<String, String> {
+    let props = dnssec as u64 | (no_logs as u64) << 1 | (no_filter as u64) << 2;
+    let mut bytes = vec![0x02];
+    bytes.extend_from_slice(&props.to_le_bytes());
+    push_lp(&mut bytes, address)?;
+    bytes.push(0); // 不固定证书哈希
+    push_lp(&mut bytes, host)?;
+    push_lp(&mut bytes, path)?;
+    Ok(format!("sdns://{}", general_purpose::URL_SAFE_NO_PAD.encode(bytes)))
+}
@@ -0,0 +1,422 @@
+use std::collections::HashMap;
+use std::io::{Read, Write};
+use std::net::{SocketAddr, TcpListener, TcpStream, UdpSocket};
+use std::sync::atomic::{AtomicBool, AtomicU64, AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::{Duration, Instant};
+use anyhow::{anyhow, Context, Result};
+
+use crate::logger::Logger;
+
+// 等待dnscrypt-proxy响应的超时
+const UPSTREAM_TIMEOUT: Duration = Duration::from_secs(5);
+
+// 同时等待响应的UDP查询数上限
+const MAX_PENDING_UDP: usize = 1024;
+
+// 同时处理的TCP连接数上限，超出的连接直接关闭
+const MAX_TCP_CLIENTS: usize = 64;
+
+// TCP客户端在此时间内没有发送查询即断开，避免空闲连接占满名额
+const CLIENT_TIMEOUT: Duration = Duration::from_secs(10);
+
+pub const RCODE_NOERROR: u8 = 0;
+pub const RCODE_REFUSED: u8 = 5;
+
+// 可以拒绝的查询类型
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum BlockedType {
+    Any,
+    Svcb,
+    Https,
+    Ptr,
+}
+
+impl BlockedType {
+    pub const ALL: [BlockedType; 4] = [BlockedType::Any, BlockedType::Svcb, BlockedType::Https, BlockedType::Ptr];
+
+    pub fn code(&self) -> u16 {
+        match self {
+            BlockedType::Any => 255,
+            BlockedType::Svcb => 64,
+            BlockedType::Https => 65,
+            BlockedType::Ptr => 12,
+        }
+    }
+
+    pub fn label(&self) -> &'static str {
+        match self {
+            BlockedType::Any => "ANY",
+            BlockedType::Svcb => "SVCB",
+            BlockedType::Https => "HTTPS",
+            BlockedType::Ptr => "PTR",
+        }
+    }
+
+    pub fn description(&self) -> &'static str {
+        match self {
+            BlockedType::Any => "一次请求域名的所有记录，正常应用很少使用，常被用于放大攻击",
+            BlockedType::Svcb => "服务绑定记录，可能暴露服务的附加信息",
+            BlockedType::Https => "浏览器查询的HTTPS服务记录（含ECH等参数），拒绝后浏览器回退到A/AAAA查询",
+            BlockedType::Ptr => "反向解析（IP→域名），拒绝可减少局域网设备名的查询与泄露",
+        }
+    }
+}
+
+// 读取查询中第一个问题的类型
+pub fn query_type(query: &[u8]) -> Option<u16> {
+    let mut pos = 12;
+    loop {
+        let len = *query.get(pos)? as usize;
+        if len == 0 {
+            break;
+        }
+        // 查询中不应出现压缩指针
+        if len & 0xc0 != 0 {
+            return None;
+        }
+        pos += len + 1;
+    }
+    let qtype = query.get(pos + 1..pos + 3)?;
+    Some(u16::from_be_bytes([qtype[0], qtype[1]]))
+}
+
+// 按查询构造没有记录的响应，只保留问题部分
+pub fn reply(query: &[u8], rcode: u8) -> Vec<u8> {
+    let mut response = query.to_vec();
+    response[2] = 0x80 | (query[2] & 0x01); // QR，保留RD
+    response[3] = 0x80 | (rcode & 0x0f);    // RA与响应码
+    // 回答、授权、附加记录数清零（同时去掉查询中的EDNS记录）
+    for byte in &mut response[6..12] {
+        *byte = 0;
+    }
+    let question_end = {
+        let mut pos = 12;
+        while pos < query.len() && query[pos] != 0 {
+            pos += query[pos] as usize + 1;
+        }
+        (pos + 5).min(query.len())
+    };
+    response.truncate(question_end);
+    response
+}
+
+// 为dnscrypt-proxy选择一个UDP与TCP都空闲的内部端口
+pub fn internal_port() -> Result<u16> {
+    for _ in 0..10 {
+        let port = TcpListener::bind("127.0.0.1:0").and_then(|l| l.local_addr()).context("Failed to find a free port")?.port();
+        if UdpSocket::bind(("127.0.0.1", port)).is_ok() {
+            return Ok(port);
+        }
+    }
+    Err(anyhow!("No free local port for dnscrypt-proxy"))
+}
+
+// 查询是否属于被拒绝的类型
+fn is_blocked(query: &[u8], blocked: &[u16]) -> bool {
+    query.len() >= 12 && query_type(query).map_or(false, |qtype| blocked.contains(&qtype))
+}
+
+// 在dnscrypt-proxy前拒绝指定类型的查询（dnscrypt-proxy没有按类型过滤的功能），
+// 其余查询原样转发给只监听内部端口的dnscrypt-proxy。
+// 转发后dnscrypt-proxy看到的客户端都是本机，查询日志中无法区分原来的客户端
+pub struct TypeFilter {
+    running: Arc<AtomicBool>,
+    refused: Arc<AtomicU64>,
+}
+
+impl TypeFilter {
+    pub fn start(
+        logger: Arc<Mutex<Logger>>,
+        listen: &[SocketAddr],
+        upstream: SocketAddr,
+        blocked: &[BlockedType],
+    ) -> Result<Self> {
+        let running = Arc::new(AtomicBool::new(true));
+        let refused = Arc::new(AtomicU64::new(0));
+        let codes: Arc<Vec<u16>> = Arc::new(blocked.iter().map(BlockedType::code).collect());
+        let tcp_clients = WorkerLimit::new(MAX_TCP_CLIENTS);
+
+        for &address in listen {
+            let udp = UdpSocket::bind(address).with_context(|| format!("Failed to bind UDP {}", address))?;
+            udp.set_read_timeout(Some(Duration::from_millis(500)))?;
+            let tcp = TcpListener::bind(address).with_context(|| format!("Failed to bind TCP {}", address))?;
+            tcp.set_nonblocking(true)?;
+            spawn_udp(udp, upstream, Arc::clone(&codes), Arc::clone(&running), Arc::clone(&refused))?;
+            spawn_tcp(tcp, upstream, Arc::clone(&codes), tcp_clients.clone(), Arc::clone(&running), Arc::clone(&refused));
+        }
+
+        if let Ok(mut logger) = logger.lock() {
+            let types: Vec<&str> = blocked.iter().map(BlockedType::label).collect();
+            let addresses: Vec<String> = listen.iter().map(SocketAddr::to_string).collect();
+            logger.info("DNSCrypt", &format!(
+                "查询类型过滤已在 {} 上启动，拒绝 {}，其余转发到 {}",
+                addresses.join(", "), types.join("/"), upstream
+            ));
+        }
+        Ok(Self { running, refused })
+    }
+
+    // 被拒绝的查询数
+    pub fn refused(&self) -> u64 {
+        self.refused.load(Ordering::Relaxed)
+    }
+
+    pub fn stop(&self) {
+        self.running.store(false, Ordering::SeqCst);
+    }
+}
+
+impl Drop for TypeFilter {
+    fn drop(&mut self) {
+        self.stop();
+    }
+}
+
+// 转发给dnscrypt-proxy、尚未收到响应的UDP查询
+struct PendingQuery {
+    id: u16, // 客户端使用的事务ID
+    client: SocketAddr,
+    sent: Instant,
+}
+
+// 把UDP查询经同一个上游套接字转发，用新的事务ID区分各个查询，响应按ID交回客户端。
+// 待响应的查询数有上限，超出时丢弃新查询，由客户端重试
+fn spawn_udp(socket: UdpSocket, upstream: SocketAddr, blocked: Arc<Vec<u16>>, running: Arc<AtomicBool>, refused: Arc<AtomicU64>) -> Result<()> {
+    let upstream_socket = UdpSocket::bind("127.0.0.1:0").context("Failed to bind upstream UDP socket")?;
+    upstream_socket.connect(upstream).context("Failed to connect upstream UDP socket")?;
+    upstream_socket.set_read_timeout(Some(Duration::from_millis(500)))?;
+    let pending: Arc<Mutex<HashMap<u16, PendingQuery>>> = Arc::new(Mutex::new(HashMap::new()));
+
+    let reply_socket = socket.try_clone().context("Failed to clone UDP socket")?;
+    let response_socket = upstream_socket.try_clone().context("Failed to clone UDP socket")?;
+    let response_pending = Arc::clone(&pending);
+    let response_running = Arc::clone(&running);
+    thread::spawn(move || {
+        let mut buffer = [0u8; 65535];
+        while response_running.load(Ordering::SeqCst) {
+            let len = match response_socket.recv(&mut buffer) {
+                Ok(len) if len >= 12 => len,
+                _ => continue, // 超时或无效响应
+            };
+            let id = u16::from_be_bytes([buffer[0], buffer[1]]);
+            let query = match response_pending.lock() {
+                Ok(mut pending) => pending.remove(&id),
+                Err(_) => None,
+            };
+            if let Some(query) = query {
+                buffer[..2].copy_from_slice(&query.id.to_be_bytes());
+                let _ = reply_socket.send_to(&buffer[..len], query.client);
+            }
+        }
+    });
+
+    thread::spawn(move || {
+        let mut buffer = [0u8; 4096];
+        let mut next_id: u16 = rand::random();
+        while running.load(Ordering::SeqCst) {
+            let (len, client) = match socket.recv_from(&mut buffer) {
+                Ok(received) => received,
+                Err(_) => continue, // 超时，检查是否已停止
+            };
+            let query = &mut buffer[..len];
+            if query.len() < 12 {
+                continue;
+            }
+            if is_blocked(query, &blocked) {
+                refused.fetch_add(1, Ordering::Relaxed);
+                let _ = socket.send_to(&reply(query, RCODE_REFUSED), client);
+                continue;
+            }
+
+            let id = match pending.lock() {
+                Ok(mut pending) => {
+                    // 超时未响应的查询不会再有响应
+                    pending.retain(|_, query| query.sent.elapsed() < UPSTREAM_TIMEOUT);
+                    if pending.len() >= MAX_PENDING_UDP {
+                        continue;
+                    }
+                    while pending.contains_key(&next_id) {
+                        next_id = next_id.wrapping_add(1);
+                    }
+                    let id = next_id;
+                    next_id = next_id.wrapping_add(1);
+                    pending.insert(id, PendingQuery {
+                        id: u16::from_be_bytes([query[0], query[1]]),
+                        client,
+                        sent: Instant::now(),
+                    });
+                    id
+                }
+                Err(_) => continue,
+            };
+            query[..2].copy_from_slice(&id.to_be_bytes());
+            let _ = upstream_socket.send(query);
+        }
+    });
+    Ok(())
+}
+
+fn spawn_tcp(
+    listener: TcpListener,
+    upstream: SocketAddr,
+    blocked: Arc<Vec<u16>>,
+    clients: WorkerLimit,
+    running: Arc<AtomicBool>,
+    refused: Arc<AtomicU64>,
+) {
+    thread::spawn(move || {
+        while running.load(Ordering::SeqCst) {
+            let client = match listener.accept() {
+                Ok((client, _)) => client,
+                Err(_) => {
+                    thread::sleep(Duration::from_millis(100));
+                    continue;
+                }
+            };
+            let slot = match clients.acquire() {
+                Some(slot) => slot,
+                None => continue, // 关闭连接
+            };
+            let blocked = Arc::clone(&blocked);
+            let refused = Arc::clone(&refused);
+            thread::spawn(move || {
+                let _slot = slot;
+                let _ = relay_tcp(client, upstream, &blocked, &refused);
+            });
+        }
+    });
+}
+
+// 读取一个DNS over TCP报文（2字节长度 + 报文）
+pub fn read_message<S: Read>(stream: &mut S) -> Result<Vec<u8>> {
+    let mut len = [0u8; 2];
+    stream.read_exact(&mut len)?;
+    let mut message = vec![0u8; u16::from_be_bytes(len) as usize];
+    stream.read_exact(&mut message)?;
+    Ok(message)
+}
+
+pub fn write_message<S: Write>(stream: &mut S, message: &[u8]) -> Result<()> {
+    let len = u16::try_from(message.len()).map_err(|_| anyhow!("DNS message too large"))?;
+    let mut framed = len.to_be_bytes().to_vec();
+    framed.extend_from_slice(message);
+    stream.write_all(&framed)?;
+    Ok(())
+}
+
+// 限制同时运行的处理线程数
+#[derive(Clone)]
+pub struct WorkerLimit {
+    active: Arc<AtomicUsize>,
+    max: usize,
+}
+
+// 占用一个名额，处理结束（drop）时归还
+pub struct WorkerSlot(Arc<AtomicUsize>);
+
+impl WorkerLimit {
+    pub fn new(max: usize) -> Self {
+        Self { active: Arc::new(AtomicUsize::new(0)), max }
+    }
+
+    // 已达上限时返回None
+    pub fn acquire(&self) -> Option<WorkerSlot> {
+        self.active
+            .fetch_update(Ordering::SeqCst, Ordering::SeqCst, |active| (active < self.max).then_some(active + 1))
+            .ok()
+            .map(|_| WorkerSlot(Arc::clone(&self.active)))
+    }
+}
+
+impl Drop for WorkerSlot {
+    fn drop(&mut self) {
+        self.0.fetch_sub(1, Ordering::SeqCst);
+    }
+}
+
+// 逐个处理一个TCP连接中的查询，上游连接在第一个需要转发的查询时建立
+fn relay_tcp(mut client: TcpStream, upstream: SocketAddr, blocked: &[u16], refused: &AtomicU64) -> Result<()> {
+    client.set_nonblocking(false)?;
+    client.set_read_timeout(Some(CLIENT_TIMEOUT))?;
+    client.set_write_timeout(Some(CLIENT_TIMEOUT))?;
+    let mut server: Option<TcpStream> = None;
+    loop {
+        let query = read_message(&mut client)?;
+        if is_blocked(&query, blocked) {
+            refused.fetch_add(1, Ordering::Relaxed);
+            write_message(&mut client, &reply(&query, RCODE_REFUSED))?;
+            continue;
+        }
+        if server.is_none() {
+            let stream = TcpStream::connect_timeout(&upstream, UPSTREAM_TIMEOUT)?;
+            stream.set_read_timeout(Some(UPSTREAM_TIMEOUT))?;
+            server = Some(stream);
+        }
+        if let Some(stream) = server.as_mut() {
+            write_message(stream, &query)?;
+            let response = read_message(stream)?;
+            write_message(&mut client, &response)?;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // 查询example.com，qtype为查询类型
+    fn query(id: u16, qtype: u16) -> Vec<u8> {
+        let mut query = id.to_be_bytes().to_vec();
+        query.extend_from_slice(&[0x01, 0x00, 0, 1, 0, 0, 0, 0, 0, 0]);
+        query.extend_from_slice(b"\x07example\x03com\x00");
+        query.extend_from_slice(&qtype.to_be_bytes());
+        query.extend_from_slice(&[0, 1]);
+        query
+    }
+
+    #[test]
+    fn relays_udp_queries_by_transaction_id() {
+        // 假的dnscrypt-proxy：把查询原样作为响应返回
+        let upstream = UdpSocket::bind("127.0.0.1:0").unwrap();
+        let upstream_address = upstream.local_addr().unwrap();
+        thread::spawn(move || {
+            let mut buffer = [0u8; 512];
+            while let Ok((len, from)) = upstream.recv_from(&mut buffer) {
+                let _ = upstream.send_to(&reply(&buffer[..len], RCODE_NOERROR), from);
+            }
+        });
+
+        let listen = UdpSocket::bind("127.0.0.1:0").unwrap().local_addr().unwrap();
+        let logger = Arc::new(Mutex::new(Logger::new()));
+        let filter = TypeFilter::start(logger, &[listen], upstream_address, &[BlockedType::Any]).unwrap();
+
+        let client = UdpSocket::bind("127.0.0.1:0").unwrap();
+        client.set_read_timeout(Some(Duration::from_secs(5))).unwrap();
+        let mut buffer = [0u8; 512];
+        for id in [0x1234, 0xbeef] {
+            client.send_to(&query(id, 1), listen).unwrap();
+            let len = client.recv(&mut buffer).unwrap();
+            assert_eq!(u16::from_be_bytes([buffer[0], buffer[1]]), id);
+            assert_eq!(buffer[3] & 0x0f, RCODE_NOERROR);
+            assert_eq!(query_type(&buffer[..len]), Some(1));
+        }
+
+        client.send_to(&query(7, BlockedType::Any.code()), listen).unwrap();
+        client.recv(&mut buffer).unwrap();
+        assert_eq!(u16::from_be_bytes([buffer[0], buffer[1]]), 7);
+        assert_eq!(buffer[3] & 0x0f, RCODE_REFUSED);
+        assert_eq!(filter.refused(), 1);
+    }
+
+    #[test]
+    fn worker_limit_caps_and_releases_slots() {
+        let limit = WorkerLimit::new(2);
+        let first = limit.acquire().unwrap();
+        let _second = limit.acquire().unwrap();
+        assert!(limit.acquire().is_none());
+        drop(first);
+        assert!(limit.acquire().is_some());
+    }
+}
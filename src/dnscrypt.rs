@@ -1,10 +1,53 @@
 use eframe::egui::{self, Color32, RichText, Ui, Grid, ScrollArea};
+use std::collections::{HashMap, HashSet};
+use std::net::{TcpStream, ToSocketAddrs};
 use std::sync::{Arc, Mutex};
+use chrono::{Local, TimeZone};
 use serde::{Deserialize, Serialize};
 
+use crate::captive_portal::{self, PortalStatus};
+use crate::dns_blocklists::{self, BlocklistCategory, BlocklistSource};
+use crate::dns_query_log::{QueryEntry, QueryLogViewer};
+use crate::dns_rules::{self, CloakingRule, DnsRules, ForwardingRule};
+use crate::dns_type_filter::{self, BlockedType, TypeFilter};
+use crate::dnssec_check::{self, DnssecResult};
+use crate::dnscrypt_process::{self, CacheSettings, DnsCryptProcess, DnsCryptSettings, DnsCryptState, LbStrategy};
+use crate::dot_forwarder::{DotForwarder, DotUpstream};
+use crate::hosts_file::HostsPanel;
 use crate::logger::Logger;
+use crate::geoip;
+use crate::ports::{self, PortStatus, Protocol};
+use crate::system_dns;
+use crate::resolver_list::{self, ListEntry};
+use crate::services::{self, ProcessLauncher};
+use crate::dns_stamp::{self, DnsStamp, StampProtocol};
+use crate::utils;
 use crate::app::DNS_COLOR;
 
+// 服务器列表文件名
+const SERVERS_FILE: &str = "dnscrypt_servers.json";
+// 每个服务器测量延迟的次数（取中位数）
+const LATENCY_PROBES: usize = 3;
+
+// 上游服务器协议
+#[derive(Clone, Copy, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub enum ServerProtocol {
+    #[default]
+    DnsCrypt,
+    DoH,
+    DoT,
+}
+
+impl ServerProtocol {
+    pub fn label(&self) -> &'static str {
+        match self {
+            ServerProtocol::DnsCrypt => "DNSCrypt",
+            ServerProtocol::DoH => "DoH",
+            ServerProtocol::DoT => "DoT",
+        }
+    }
+}
+
 // DNSCrypt服务器结构
 #[derive(Clone, Debug, Serialize, Deserialize)]
 pub struct DnsCryptServer {
@@ -16,6 +59,24 @@ pub struct DnsCryptServer {
     pub enabled: bool,
     pub dnssec: bool,
     pub no_logs: bool,
+    #[serde(default)]
+    pub no_filter: bool, // 不屏蔽任何域名
+    #[serde(default)]
+    pub resolver_name: String, // dnscrypt-proxy公共服务器列表中的名称
+    #[serde(default)]
+    pub stamp: String,         // DNS Stamp（sdns://...），填写时优先使用
+    #[serde(default)]
+    pub protocol: ServerProtocol,
+    #[serde(default)]
+    pub doh_url: String,       // DoH地址，如 https://dns.example/dns-query
+    #[serde(default)]
+    pub bootstrap_ip: String,  // DoH服务器的IP，为空时通过引导DNS解析主机名
+    #[serde(default)]
+    pub dot_hostname: String,  // DoT服务器证书中的主机名
+    #[serde(default)]
+    pub spki_pin: String,      // DoT服务器公钥的SHA-256（Base64），为空时只校验证书
+    #[serde(default)]
+    pub latency_ms: Option<u32>, // 最近一次测得的延迟中位数，None表示未测量或无法连接
 }
 
 impl DnsCryptServer {
@@ -29,10 +90,261 @@ impl DnsCryptServer {
             enabled: true,
             dnssec: false,
             no_logs: false,
+            no_filter: false,
+            resolver_name: String::new(),
+            stamp: String::new(),
+            protocol: ServerProtocol::DnsCrypt,
+            doh_url: String::new(),
+            bootstrap_ip: String::new(),
+            dot_hostname: String::new(),
+            spki_pin: String::new(),
+            latency_ms: None,
         }
     }
+    
+    // 用解码后的DNS Stamp填写地址与属性
+    pub fn apply_stamp(&mut self, stamp: &DnsStamp) {
+        self.address = if stamp.address.is_empty() { stamp.provider_name.clone() } else { stamp.address.clone() };
+        self.provider_name = stamp.provider_name.clone();
+        self.dnssec = stamp.dnssec;
+        self.no_logs = stamp.no_logs;
+        self.no_filter = stamp.no_filter;
+        if stamp.protocol == StampProtocol::DoH {
+            self.protocol = ServerProtocol::DoH;
+            self.doh_url = format!("https://{}{}", stamp.provider_name, stamp.path);
+            self.bootstrap_ip = stamp.address.clone();
+        } else if stamp.protocol == StampProtocol::DoT {
+            self.protocol = ServerProtocol::DoT;
+            self.dot_hostname = stamp.provider_name.clone();
+        }
+    }
+    
+    // 服务器IP（地址为主机名时为None）
+    fn ip(&self) -> Option<std::net::IpAddr> {
+        let address = match self.protocol {
+            ServerProtocol::DoH => self.bootstrap_ip.trim(),
+            _ => self.address.trim(),
+        };
+        address.parse::<std::net::SocketAddr>().map(|a| a.ip()).ok()
+            .or_else(|| address.trim_start_matches('[').trim_end_matches(']').parse().ok())
+    }
+    
+    // 按服务器IP查询的国家代码
+    pub fn country(&self) -> Option<String> {
+        self.ip().and_then(geoip::lookup)
+    }
+    
+    // 测量延迟时连接的地址（主机:端口）
+    fn probe_target(&self) -> Option<String> {
+        match self.protocol {
+            ServerProtocol::DoT => self.dot_upstream().ok().map(|u| u.address.to_string()),
+            ServerProtocol::DoH if self.bootstrap_ip.trim().is_empty() => {
+                let (host, _) = parse_doh_url(&self.doh_url).ok()?;
+                Some(if host.contains(':') { host } else { format!("{}:443", host) })
+            }
+            ServerProtocol::DoH => parse_bootstrap_ip(&self.bootstrap_ip).ok().map(|ip| format!("{}:443", ip)),
+            ServerProtocol::DnsCrypt => {
+                let address = self.address.trim();
+                if address.is_empty() {
+                    None
+                } else if address.parse::<std::net::SocketAddr>().is_ok() {
+                    Some(address.to_string())
+                } else {
+                    // 只有IP或主机名时使用默认端口443
+                    parse_bootstrap_ip(address).ok().map(|ip| format!("{}:443", ip))
+                }
+            }
+        }
+    }
+    
+    // DoT服务器由程序内的转发器使用
+    pub fn dot_upstream(&self) -> Result<DotUpstream, String> {
+        DotUpstream::new(&self.name, &self.address, &self.dot_hostname, &self.spki_pin)
+    }
+    
+    // 检查服务器能否被使用，导入服务器时逐个检查
+    pub fn validate(&self) -> Result<(), String> {
+        if self.name.trim().is_empty() {
+            return Err("服务器名称为空".to_string());
+        }
+        if !self.stamp.trim().is_empty() {
+            return dns_stamp::parse(self.stamp.trim()).map(|_| ()).map_err(|e| format!("DNS Stamp无效: {}", e));
+        }
+        match self.protocol {
+            ServerProtocol::DoT => self.dot_upstream().map(|_| ()),
+            ServerProtocol::DoH if self.resolver_name.trim().is_empty() => {
+                parse_doh_url(&self.doh_url).and_then(|_| parse_bootstrap_ip(&self.bootstrap_ip)).map(|_| ())
+            }
+            _ if self.resolver_name.trim().is_empty() => Err("缺少公共列表名称或DNS Stamp".to_string()),
+            _ => Ok(()),
+        }
+    }
+    
+    // 是否与另一个服务器指向同一上游（导入时据此合并）
+    fn same_upstream(&self, other: &DnsCryptServer) -> bool {
+        if !self.stamp.trim().is_empty() || !other.stamp.trim().is_empty() {
+            return self.stamp.trim() == other.stamp.trim();
+        }
+        if !self.resolver_name.is_empty() || !other.resolver_name.is_empty() {
+            return self.resolver_name == other.resolver_name;
+        }
+        self.protocol == other.protocol && self.name == other.name && self.address == other.address && self.doh_url == other.doh_url
+    }
+    
+    // 写入dnscrypt-proxy配置时使用的DNS Stamp：优先使用填写的Stamp，
+    // 公共列表中的服务器按名称引用，手动添加的DoH服务器按地址生成
+    pub fn config_stamp(&self) -> Result<Option<String>, String> {
+        if !self.stamp.trim().is_empty() {
+            return Ok(Some(self.stamp.trim().to_string()));
+        }
+        if !self.resolver_name.trim().is_empty() || self.protocol != ServerProtocol::DoH || self.doh_url.trim().is_empty() {
+            return Ok(None);
+        }
+        let (host, path) = parse_doh_url(&self.doh_url)?;
+        let address = parse_bootstrap_ip(&self.bootstrap_ip)?;
+        dns_stamp::encode_doh(&address, &host, &path, self.dnssec, self.no_logs, self.no_filter).map(Some)
+    }
+}
+
+// 拆分DoH地址为主机名（含非默认端口）与路径
+fn parse_doh_url(doh_url: &str) -> Result<(String, String), String> {
+    let url = url::Url::parse(doh_url.trim()).map_err(|e| format!("DoH地址无效: {}", e))?;
+    if url.scheme() != "https" {
+        return Err("DoH地址必须以https://开头".to_string());
+    }
+    let host = url.host_str().ok_or("DoH地址缺少主机名")?;
+    let host = match url.port() {
+        Some(port) => format!("{}:{}", host, port),
+        None => host.to_string(),
+    };
+    let path = match url.query() {
+        Some(query) => format!("{}?{}", url.path(), query),
+        None => url.path().to_string(),
+    };
+    Ok((host, path))
+}
+
+// 引导IP可为空；IPv6地址在Stamp中需要加方括号
+fn parse_bootstrap_ip(ip: &str) -> Result<String, String> {
+    let ip = ip.trim().trim_start_matches('[').trim_end_matches(']');
+    if ip.is_empty() {
+        return Ok(String::new());
+    }
+    match ip.parse::<std::net::IpAddr>() {
+        Ok(std::net::IpAddr::V4(v4)) => Ok(v4.to_string()),
+        Ok(std::net::IpAddr::V6(v6)) => Ok(format!("[{}]", v6)),
+        Err(_) => Err(format!("引导IP无效: {}", ip)),
+    }
+}
+
+// 多次建立TCP连接，返回耗时的中位数（毫秒）
+fn probe_latency(target: &str) -> Option<u32> {
+    let address = target.to_socket_addrs().ok()?.next()?;
+    let mut samples: Vec<u32> = (0..LATENCY_PROBES)
+        .filter_map(|_| {
+            let start = std::time::Instant::now();
+            TcpStream::connect_timeout(&address, std::time::Duration::from_secs(2))
+                .ok()
+                .map(|_| start.elapsed().as_millis() as u32)
+        })
+        .collect();
+    if samples.is_empty() {
+        return None;
+    }
+    samples.sort_unstable();
+    Some(samples[samples.len() / 2])
+}
+
+// 匿名DNS中继
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct DnsCryptRelay {
+    pub name: String,
+    pub address: String,
+    pub description: String,
+}
+
+// 保存的服务器与中继列表
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+struct ServerList {
+    servers: Vec<DnsCryptServer>,
+    relays: Vec<DnsCryptRelay>,
+    updated: Option<String>, // 最近一次从公共列表更新的时间
+}
+
+impl utils::VersionedConfig for ServerList {
+    const VERSION: u32 = 1;
+}
+
+// 导出的服务器列表，可在其他电脑上导入
+#[derive(Serialize, Deserialize)]
+struct ServerExport {
+    servers: Vec<DnsCryptServer>,
+}
+
+impl utils::VersionedConfig for ServerExport {
+    const VERSION: u32 = 1;
+}
+
+// 更新公共列表的状态
+enum RefreshState {
+    Idle,
+    Refreshing,
+    Done(Vec<ListEntry>, Vec<ListEntry>),
+    Failed(String),
+}
+
+// DNSSEC测试状态
+enum DnssecState {
+    Idle,
+    Checking,
+    Done(DnssecResult),
+}
+
+// 屏蔽列表更新状态，完成后为每个列表的(地址, 条目数或错误)
+enum BlocklistUpdate {
+    Idle,
+    Updating,
+    Done(Vec<(String, Result<usize, String>)>),
+}
+
+// 服务器列表的筛选条件
+#[derive(Default)]
+struct ServerFilter {
+    text: String,
+    dnssec: bool,
+    no_logs: bool,
+    no_filter: bool,
+    protocol: Option<ServerProtocol>,
+    country: Option<String>,
+}
+
+impl ServerFilter {
+    fn matches(&self, server: &DnsCryptServer) -> bool {
+        let text = self.text.trim().to_lowercase();
+        (text.is_empty()
+            || server.name.to_lowercase().contains(&text)
+            || server.address.to_lowercase().contains(&text)
+            || server.description.to_lowercase().contains(&text))
+            && (!self.dnssec || server.dnssec)
+            && (!self.no_logs || server.no_logs)
+            && (!self.no_filter || server.no_filter)
+            && self.protocol.map_or(true, |p| server.protocol == p)
+            && self.country.as_ref().map_or(true, |c| server.country().as_ref() == Some(c))
+    }
 }
 
+// 强制门户检测状态
+enum PortalCheck {
+    Idle,
+    Checking,
+    Done(PortalStatus),
+}
+
+// 连接上游超过此时间仍未就绪时自动检测强制门户
+const PORTAL_CHECK_DELAY: std::time::Duration = std::time::Duration::from_secs(20);
+// 运行时检查启用服务器是否可连接的间隔
+const HEALTH_CHECK_INTERVAL: std::time::Duration = std::time::Duration::from_secs(60);
+
 // DNSCrypt模块结构
 pub struct DnsCryptModule {
     enabled: bool,
@@ -47,10 +359,67 @@ pub struct DnsCryptModule {
     connection_status: String,
     dns_leak_protection: bool,
     ipv6_disabled: bool,
+    query_log: bool,
+    system_dns: bool, // 运行时将网卡DNS设为本机
+    query_log_viewer: QueryLogViewer,
+    hosts_panel: HostsPanel,
+    cache: CacheSettings,
+    listen_port: u16,
+    extra_listen: Vec<String>,
+    new_listen_address: String,
+    listen_error: Option<String>,
+    new_server_resolver: String,
+    new_server_stamp: String,
+    new_server_protocol: ServerProtocol,
+    new_server_doh_url: String,
+    new_server_bootstrap: String,
+    new_server_dot_hostname: String,
+    new_server_spki_pin: String,
+    stamp_check: Option<Result<DnsStamp, String>>, // 添加对话框中DNS Stamp的解码结果
+    form_error: Option<String>,
+    server_error: Option<String>, // 无法启用服务器的原因
+    launcher: Arc<dyn ProcessLauncher>,
+    process: Option<DnsCryptProcess>,
+    dot_forwarder: Option<DotForwarder>,
+    type_filter: Option<TypeFilter>, // 拒绝指定查询类型，位于dnscrypt-proxy之前
+    blocked_types: Vec<BlockedType>,
+    relays: Vec<DnsCryptRelay>,
+    list_updated: Option<String>,
+    refresh_state: Arc<Mutex<RefreshState>>,
+    import_result: Option<Result<String, String>>, // 最近一次导入/导出的结果
+    latency_results: Arc<Mutex<HashMap<usize, Option<u32>>>>, // 后台测量完成的结果
+    latency_pending: usize,
+    latency_manual: bool,                    // 本次测量由用户发起（完成时记录日志）
+    lb_strategy: LbStrategy,
+    unhealthy: HashSet<usize>,               // 最近一次检查无法连接的服务器
+    active_server: Option<usize>,            // 按顺序策略下正在使用的服务器
+    health_checked: Option<std::time::Instant>,
+    restart_pending: bool,                   // 服务器更改（不支持热重载时还有规则更改）需要重启dnscrypt-proxy才能生效
+    rules: DnsRules,
+    new_cloaking: CloakingRule,
+    new_allowed: String,
+    new_forwarding: ForwardingRule,
+    rules_error: Option<String>,
+    dnssec_state: Arc<Mutex<DnssecState>>,
+    bootstrap_resolvers: String, // 逗号分隔
+    portal_check: Arc<Mutex<PortalCheck>>,
+    plaintext_dns: bool,         // 为登录强制门户临时恢复网络分配的明文DNS
+    not_ready_since: Option<std::time::Instant>,
+    portal_auto_checked: bool,
+    blocklist_update: Arc<Mutex<BlocklistUpdate>>,
+    new_blocklist_url: String,
+    filter: ServerFilter,
 }
 
 impl DnsCryptModule {
     pub fn new(logger: Arc<Mutex<Logger>>) -> Self {
+        let launcher = services::launcher(Arc::clone(&logger));
+        Self::with_launcher(logger, launcher)
+    }
+    
+    // 使用指定的进程启动器创建模块
+    pub fn with_launcher(logger: Arc<Mutex<Logger>>, launcher: Arc<dyn ProcessLauncher>) -> Self {
+        let hosts_panel = HostsPanel::new(Arc::clone(&logger));
         let mut module = Self {
             enabled: false,
             servers: Vec::new(),
@@ -64,66 +433,1114 @@ impl DnsCryptModule {
             connection_status: "未连接".to_string(),
             dns_leak_protection: true,
             ipv6_disabled: false,
+            query_log: false,
+            system_dns: true,
+            query_log_viewer: QueryLogViewer::new(),
+            hosts_panel,
+            cache: CacheSettings::default(),
+            listen_port: 53,
+            extra_listen: Vec::new(),
+            new_listen_address: String::new(),
+            listen_error: None,
+            new_server_resolver: String::new(),
+            new_server_stamp: String::new(),
+            new_server_protocol: ServerProtocol::DnsCrypt,
+            new_server_doh_url: String::new(),
+            new_server_bootstrap: String::new(),
+            new_server_dot_hostname: String::new(),
+            new_server_spki_pin: String::new(),
+            stamp_check: None,
+            form_error: None,
+            server_error: None,
+            launcher,
+            process: None,
+            dot_forwarder: None,
+            type_filter: None,
+            blocked_types: Vec::new(),
+            relays: Vec::new(),
+            list_updated: None,
+            refresh_state: Arc::new(Mutex::new(RefreshState::Idle)),
+            import_result: None,
+            latency_results: Arc::new(Mutex::new(HashMap::new())),
+            latency_pending: 0,
+            latency_manual: false,
+            lb_strategy: LbStrategy::default(),
+            unhealthy: HashSet::new(),
+            active_server: None,
+            health_checked: None,
+            restart_pending: false,
+            rules: DnsRules::load(),
+            new_cloaking: CloakingRule::default(),
+            new_allowed: String::new(),
+            new_forwarding: ForwardingRule::default(),
+            rules_error: None,
+            dnssec_state: Arc::new(Mutex::new(DnssecState::Idle)),
+            bootstrap_resolvers: dnscrypt_process::DEFAULT_BOOTSTRAP_RESOLVERS.join(", "),
+            portal_check: Arc::new(Mutex::new(PortalCheck::Idle)),
+            plaintext_dns: false,
+            not_ready_since: None,
+            portal_auto_checked: false,
+            blocklist_update: Arc::new(Mutex::new(BlocklistUpdate::Idle)),
+            new_blocklist_url: String::new(),
+            filter: ServerFilter::default(),
+        };
+        
+        // 读取保存的服务器列表，没有时添加一些示例服务器
+        match module.load_servers() {
+            Some(list) if !list.servers.is_empty() => {
+                module.next_server_id = list.servers.iter().map(|s| s.id).max().unwrap_or(0) + 1;
+                module.servers = list.servers;
+                module.relays = list.relays;
+                module.list_updated = list.updated;
+            }
+            _ => module.add_example_servers(),
+        }
+        module.reserve_ports();
+        
+        // 记录模块初始化日志
+        if let Ok(mut logger) = module.logger.lock() {
+            logger.info("DNSCrypt", "DNSCrypt模块已初始化");
+        }
+        
+        module
+    }
+    
+    // 添加示例服务器
+    fn add_example_servers(&mut self) {
+        // 添加一些示例DNSCrypt服务器
+        let mut server1 = DnsCryptServer::new(
+            self.next_server_id,
+            "Cloudflare",
+            "1.1.1.1:443",
+            "2.dnscrypt-cert.cloudflare"
+        );
+        server1.description = "Cloudflare的DNS服务，注重隐私保护".to_string();
+        server1.dnssec = true;
+        server1.no_logs = true;
+        server1.resolver_name = "cloudflare".to_string();
+        self.servers.push(server1);
+        self.next_server_id += 1;
+        
+        let mut server2 = DnsCryptServer::new(
+            self.next_server_id,
+            "Google",
+            "8.8.8.8:443",
+            "2.dnscrypt-cert.google"
+        );
+        server2.description = "Google的公共DNS服务".to_string();
+        server2.dnssec = true;
+        server2.no_logs = false;
+        server2.resolver_name = "google".to_string();
+        self.servers.push(server2);
+        self.next_server_id += 1;
+        
+        let mut server3 = DnsCryptServer::new(
+            self.next_server_id,
+            "Quad9",
+            "9.9.9.9:443",
+            "2.dnscrypt-cert.quad9"
+        );
+        server3.description = "Quad9提供的安全DNS服务，可阻止恶意域名".to_string();
+        server3.dnssec = true;
+        server3.no_logs = true;
+        server3.resolver_name = "quad9-dnscrypt-ip4-filter-pri".to_string();
+        self.servers.push(server3);
+        self.next_server_id += 1;
+    }
+    
+    fn load_servers(&self) -> Option<ServerList> {
+        utils::load_saved_config(SERVERS_FILE)
+    }
+    
+    // 保存服务器与中继列表
+    fn save_servers(&self) {
+        let list = ServerList {
+            servers: self.servers.clone(),
+            relays: self.relays.clone(),
+            updated: self.list_updated.clone(),
+        };
+        if let Err(e) = utils::get_config_path(SERVERS_FILE).and_then(|path| utils::save_versioned_config(&list, &path)) {
+            if let Ok(mut logger) = self.logger.lock() {
+                logger.error("DNSCrypt", &format!("保存服务器列表失败: {}", e));
+            }
+        }
+    }
+    
+    // 将所有服务器（含自定义Stamp）导出到用户选择的文件
+    fn export_servers_dialog(&mut self) {
+        let path = match rfd::FileDialog::new()
+            .add_filter("JSON", &["json"])
+            .set_file_name("dnscrypt_servers_export.json")
+            .save_file()
+        {
+            Some(path) => path,
+            None => return,
+        };
+        // 延迟只对本机有意义，不导出
+        let export = ServerExport {
+            servers: self.servers.iter().cloned().map(|s| DnsCryptServer { latency_ms: None, ..s }).collect(),
+        };
+        let result = utils::save_versioned_config(&export, &path.to_string_lossy());
+        if let Ok(mut logger) = self.logger.lock() {
+            match &result {
+                Ok(()) => logger.info("DNSCrypt", &format!("已导出 {} 个服务器到 {}", export.servers.len(), path.display())),
+                Err(e) => logger.error("DNSCrypt", &format!("导出服务器失败: {:#}", e)),
+            }
+        }
+        self.import_result = Some(match result {
+            Ok(()) => Ok(format!("已导出 {} 个服务器", export.servers.len())),
+            Err(e) => Err(format!("导出失败: {:#}", e)),
+        });
+    }
+    
+    // 从文件导入服务器，与已有服务器指向同一上游的更新其设置，其余作为新服务器添加
+    fn import_servers_dialog(&mut self) {
+        let path = match rfd::FileDialog::new()
+            .add_filter("JSON", &["json"])
+            .pick_file()
+        {
+            Some(path) => path,
+            None => return,
+        };
+        let export: ServerExport = match utils::load_versioned_config(&path.to_string_lossy()) {
+            Ok(export) => export,
+            Err(e) => {
+                self.import_result = Some(Err(format!("无法读取 {}: {:#}", path.display(), e)));
+                return;
+            }
+        };
+        
+        let (mut added, mut updated, mut failures) = (0, 0, Vec::new());
+        for mut server in export.servers {
+            if let Err(reason) = server.validate() {
+                failures.push(format!("{}: {}", server.name, reason));
+                continue;
+            }
+            server.latency_ms = None;
+            match self.servers.iter_mut().find(|s| s.same_upstream(&server)) {
+                Some(existing) => {
+                    server.id = existing.id;
+                    *existing = server;
+                    updated += 1;
+                }
+                None => {
+                    server.id = self.next_server_id;
+                    self.servers.push(server);
+                    self.next_server_id += 1;
+                    added += 1;
+                }
+            }
+        }
+        self.save_servers();
+        self.apply_servers();
+        
+        let summary = format!("导入完成: 新增 {} 个，更新 {} 个，失败 {} 个", added, updated, failures.len());
+        if let Ok(mut logger) = self.logger.lock() {
+            logger.info("DNSCrypt", &format!("从 {} {}", path.display(), summary));
+            for failure in &failures {
+                logger.warning("DNSCrypt", &format!("跳过无效的服务器 {}", failure));
+            }
+        }
+        self.import_result = Some(if failures.is_empty() {
+            Ok(summary)
+        } else {
+            Err(format!("{}（{}）", summary, failures.join("；")))
+        });
+    }
+    
+    // 在后台下载公共服务器与中继列表
+    fn refresh_resolvers(&self) {
+        if let Ok(mut state) = self.refresh_state.lock() {
+            *state = RefreshState::Refreshing;
+        }
+        let state = Arc::clone(&self.refresh_state);
+        let logger = Arc::clone(&self.logger);
+        std::thread::spawn(move || {
+            let next = match resolver_list::fetch() {
+                Ok((resolvers, relays)) => RefreshState::Done(resolvers, relays),
+                Err(e) => {
+                    if let Ok(mut logger) = logger.lock() {
+                        logger.error("DNSCrypt", &format!("更新公共服务器列表失败: {:#}", e));
+                    }
+                    RefreshState::Failed(format!("{:#}", e))
+                }
+            };
+            if let Ok(mut state) = state.lock() {
+                *state = next;
+            }
+        });
+    }
+    
+    // 取回已下载的公共服务器列表
+    fn collect_refresh(&mut self) {
+        let done = self.refresh_state.lock().ok().and_then(|mut state| match &*state {
+            RefreshState::Done(..) => Some(std::mem::replace(&mut *state, RefreshState::Idle)),
+            _ => None,
+        });
+        if let Some(RefreshState::Done(resolvers, relays)) = done {
+            self.merge_resolvers(resolvers, relays);
+        }
+    }
+    
+    // 在后台下载所有启用的屏蔽列表
+    fn update_blocklists(&self) {
+        let urls: Vec<String> = self.rules.blocklists.iter().filter(|s| s.enabled).map(|s| s.url.clone()).collect();
+        if urls.is_empty() {
+            return;
+        }
+        if let Ok(mut update) = self.blocklist_update.lock() {
+            *update = BlocklistUpdate::Updating;
+        }
+        let update = Arc::clone(&self.blocklist_update);
+        std::thread::spawn(move || {
+            let results = match dnscrypt_process::dnscrypt_home() {
+                Ok(home) => urls.into_iter()
+                    .map(|url| {
+                        let result = dns_blocklists::update_source(&home, &url).map_err(|e| format!("{:#}", e));
+                        (url, result)
+                    })
+                    .collect(),
+                Err(e) => urls.into_iter().map(|url| (url, Err(format!("{:#}", e)))).collect(),
+            };
+            if let Ok(mut update) = update.lock() {
+                *update = BlocklistUpdate::Done(results);
+            }
+        });
+    }
+    
+    // 记录屏蔽列表的更新结果并重新合并
+    fn collect_blocklist_update(&mut self) {
+        let done = self.blocklist_update.lock().ok().and_then(|mut update| match &*update {
+            BlocklistUpdate::Done(..) => Some(std::mem::replace(&mut *update, BlocklistUpdate::Idle)),
+            _ => None,
+        });
+        let results = match done {
+            Some(BlocklistUpdate::Done(results)) => results,
+            _ => return,
+        };
+        let now = Local::now().format("%Y-%m-%d %H:%M").to_string();
+        for (url, result) in results {
+            let source = match self.rules.blocklists.iter_mut().find(|s| s.url == url) {
+                Some(source) => source,
+                None => continue,
+            };
+            if let Ok(mut logger) = self.logger.lock() {
+                match &result {
+                    Ok(count) => logger.info("DNSCrypt", &format!("屏蔽列表 {} 已更新，{} 条", url, count)),
+                    Err(e) => logger.error("DNSCrypt", &format!("更新屏蔽列表 {} 失败: {}", url, e)),
+                }
+            }
+            match result {
+                Ok(count) => {
+                    source.entries = count;
+                    source.last_updated = Some(now.clone());
+                    source.last_error = None;
+                }
+                Err(e) => source.last_error = Some(e),
+            }
+        }
+        self.save_rules();
+        self.combine_blocklists();
+    }
+    
+    // 按启用的列表重新生成blocked-names.txt，运行中的dnscrypt-proxy支持热重载时自动重新加载
+    fn combine_blocklists(&mut self) {
+        let result = dnscrypt_process::dnscrypt_home().and_then(|home| dns_blocklists::combine(&home, &self.rules.blocklists, &self.rules.firewall_blocked));
+        match result {
+            Ok(_) => self.note_rule_files_changed(),
+            Err(e) => {
+                if let Ok(mut logger) = self.logger.lock() {
+                    logger.error("DNSCrypt", &format!("合并屏蔽列表失败: {:#}", e));
+                }
+            }
+        }
+    }
+    
+    // 运行中的dnscrypt-proxy是否会自动重新加载规则文件
+    fn rules_hot_reload(&self) -> bool {
+        self.process.as_ref().map_or(false, |process| process.hot_reload())
+    }
+    
+    // 规则文件已更新：运行中的dnscrypt-proxy不支持热重载时需要重启才能生效
+    fn note_rule_files_changed(&mut self) {
+        if self.process.is_some() && !self.rules_hot_reload() {
+            self.restart_pending = true;
+        }
+    }
+    
+    // 每帧调用：取回后台任务结果，并按设定的间隔自动更新列表
+    pub fn tick(&mut self) {
+        self.query_log_viewer.tick();
+        self.monitor_health();
+        self.collect_refresh();
+        self.collect_blocklist_update();
+        if self.rules.auto_update_hours == 0 {
+            return;
+        }
+        let now = Local::now().timestamp();
+        let interval = self.rules.auto_update_hours as i64 * 3600;
+        if self.rules.lists_updated_at.map_or(false, |last| now - last < interval) {
+            return;
+        }
+        let busy = matches!(self.refresh_state.lock().as_deref(), Ok(RefreshState::Refreshing))
+            || matches!(self.blocklist_update.lock().as_deref(), Ok(BlocklistUpdate::Updating));
+        if busy {
+            return;
+        }
+        // 失败时同样等到下一个间隔再试，错误记录在日志中
+        self.rules.lists_updated_at = Some(now);
+        self.save_rules();
+        if let Ok(mut logger) = self.logger.lock() {
+            logger.info("DNSCrypt", "正在自动更新公共服务器列表与屏蔽列表");
+        }
+        self.refresh_resolvers();
+        self.update_blocklists();
+    }
+    
+    // 屏蔽列表与自动更新设置
+    fn render_blocklists(&mut self, ui: &mut Ui) {
+        ui.label("屏蔽列表中的域名返回空结果；支持纯域名、hosts（0.0.0.0 example.com）与AdBlock（||example.com^）格式");
+        
+        // 按分类一键添加常用列表
+        let mut download = false;
+        let mut category_toggled = false;
+        ui.horizontal(|ui| {
+            ui.label("快速选择:");
+            for category in BlocklistCategory::ALL {
+                let mut enabled = category.is_enabled(&self.rules.blocklists);
+                if ui.checkbox(&mut enabled, category.label()).on_hover_text(category.description()).changed() {
+                    if enabled {
+                        download |= category.enable(&mut self.rules.blocklists);
+                    } else {
+                        category.disable(&mut self.rules.blocklists);
+                    }
+                    category_toggled = true;
+                }
+            }
+        });
+        if category_toggled {
+            self.save_rules();
+            self.combine_blocklists();
+        }
+        if download {
+            self.update_blocklists();
+        }
+        
+        let mut removed = None;
+        let mut toggled = false;
+        for (index, source) in self.rules.blocklists.iter_mut().enumerate() {
+            ui.horizontal(|ui| {
+                toggled |= ui.checkbox(&mut source.enabled, "").changed();
+                ui.label(RichText::new(&source.url).monospace());
+                match (&source.last_error, &source.last_updated) {
+                    (Some(error), _) => {
+                        ui.label(RichText::new("更新失败").color(Color32::RED)).on_hover_text(error);
+                    }
+                    (None, Some(updated)) => {
+                        ui.label(RichText::new(format!("{} 条，更新于 {}", source.entries, updated)).weak());
+                    }
+                    (None, None) => {
+                        ui.label(RichText::new("未下载").weak());
+                    }
+                }
+                if ui.small_button("✖").on_hover_text("删除").clicked() {
+                    removed = Some(index);
+                }
+            });
+        }
+        if let Some(index) = removed {
+            self.rules.blocklists.remove(index);
+            toggled = true;
+        }
+        if toggled {
+            self.save_rules();
+            self.combine_blocklists();
+        }
+        
+        ui.horizontal(|ui| {
+            ui.add(egui::TextEdit::singleline(&mut self.new_blocklist_url).hint_text("https://.../hosts.txt").desired_width(300.0));
+            if ui.button("添加").clicked() {
+                let url = self.new_blocklist_url.trim().to_string();
+                if !(url.starts_with("https://") || url.starts_with("http://")) {
+                    self.rules_error = Some("屏蔽列表地址应以http://或https://开头".to_string());
+                } else if self.rules.blocklists.iter().any(|s| s.url == url) {
+                    self.rules_error = Some("该屏蔽列表已添加".to_string());
+                } else {
+                    self.rules.blocklists.push(BlocklistSource::new(&url));
+                    self.new_blocklist_url.clear();
+                    self.rules_error = None;
+                    self.save_rules();
+                    self.update_blocklists();
+                }
+            }
+        });
+        
+        ui.horizontal(|ui| {
+            let updating = matches!(self.blocklist_update.lock().as_deref(), Ok(BlocklistUpdate::Updating));
+            if ui.add_enabled(!updating && !self.rules.blocklists.is_empty(), egui::Button::new("立即更新")).clicked() {
+                self.update_blocklists();
+            }
+            if updating {
+                ui.spinner();
+                ui.ctx().request_repaint_after(std::time::Duration::from_millis(200));
+            }
+            ui.label("自动更新间隔:");
+            let response = ui.add(egui::DragValue::new(&mut self.rules.auto_update_hours).clamp_range(0..=720).suffix(" 小时"))
+                .on_hover_text("同时更新公共服务器列表与屏蔽列表，0表示不自动更新");
+            if response.changed() {
+                self.save_rules();
+            }
+        });
+        if let Some(updated) = self.rules.lists_updated_at.and_then(|t| Local.timestamp_opt(t, 0).single()) {
+            ui.label(RichText::new(format!("上次自动更新: {}", updated.format("%Y-%m-%d %H:%M"))).weak());
+        }
+    }
+    
+    // 服务器列表上方的搜索框与筛选条件
+    fn render_filter(&mut self, ui: &mut Ui) {
+        ui.horizontal_wrapped(|ui| {
+            ui.label("搜索:");
+            ui.add(egui::TextEdit::singleline(&mut self.filter.text).hint_text("名称、地址或描述").desired_width(160.0));
+            ui.toggle_value(&mut self.filter.dnssec, "DNSSEC");
+            ui.toggle_value(&mut self.filter.no_logs, "无日志");
+            ui.toggle_value(&mut self.filter.no_filter, "无过滤");
+            
+            egui::ComboBox::from_id_source("dnscrypt_filter_protocol")
+                .selected_text(self.filter.protocol.map_or("全部协议", |p| p.label()))
+                .show_ui(ui, |ui| {
+                    ui.selectable_value(&mut self.filter.protocol, None, "全部协议");
+                    for protocol in [ServerProtocol::DnsCrypt, ServerProtocol::DoH, ServerProtocol::DoT] {
+                        ui.selectable_value(&mut self.filter.protocol, Some(protocol), protocol.label());
+                    }
+                });
+            
+            let mut countries: Vec<String> = self.servers.iter().filter_map(|s| s.country()).collect();
+            countries.sort();
+            countries.dedup();
+            let selected = self.filter.country.as_deref().map_or("全部国家".to_string(), geoip::country_label);
+            egui::ComboBox::from_id_source("dnscrypt_filter_country")
+                .selected_text(selected)
+                .show_ui(ui, |ui| {
+                    ui.selectable_value(&mut self.filter.country, None, "全部国家");
+                    for code in countries {
+                        let label = geoip::country_label(&code);
+                        ui.selectable_value(&mut self.filter.country, Some(code), label);
+                    }
+                })
+                .response
+                .on_hover_text(if geoip::is_loaded() { "按服务器IP判断所在国家" } else { "GeoIP数据库未加载，无法按国家筛选" });
+            
+            let shown = self.servers.iter().filter(|s| self.filter.matches(s)).count();
+            if shown != self.servers.len() {
+                ui.label(RichText::new(format!("显示 {} / {}", shown, self.servers.len())).weak());
+            }
+        });
+    }
+    
+    // 在后台并行测量每个启用服务器的延迟
+    fn measure_latency(&mut self) {
+        for server in self.servers.iter().filter(|s| s.enabled) {
+            let id = server.id;
+            let target = server.probe_target();
+            let results = Arc::clone(&self.latency_results);
+            self.latency_pending += 1;
+            std::thread::spawn(move || {
+                let latency = target.and_then(|target| probe_latency(&target));
+                if let Ok(mut results) = results.lock() {
+                    results.insert(id, latency);
+                }
+            });
+        }
+    }
+    
+    // 取回已完成的测量结果，全部完成后保存
+    fn collect_latency(&mut self) {
+        let results: Vec<(usize, Option<u32>)> = match self.latency_results.lock() {
+            Ok(mut results) => results.drain().collect(),
+            Err(_) => return,
+        };
+        for (id, latency) in results {
+            if let Some(server) = self.servers.iter_mut().find(|s| s.id == id) {
+                server.latency_ms = latency;
+                // 只在状态变化时记录
+                let changed = if latency.is_none() { self.unhealthy.insert(id) } else { self.unhealthy.remove(&id) };
+                if changed {
+                    if let Ok(mut logger) = self.logger.lock() {
+                        match latency {
+                            None => logger.warning("DNSCrypt", &format!("服务器 {} 无法连接", server.name)),
+                            Some(_) => logger.info("DNSCrypt", &format!("服务器 {} 已恢复", server.name)),
+                        }
+                    }
+                }
+            }
+            self.latency_pending = self.latency_pending.saturating_sub(1);
+            if self.latency_pending == 0 {
+                if self.latency_manual {
+                    let measured = self.servers.iter().filter(|s| s.enabled && s.latency_ms.is_some()).count();
+                    if let Ok(mut logger) = self.logger.lock() {
+                        logger.info("DNSCrypt", &format!("延迟测量完成，{} 个服务器可连接", measured));
+                    }
+                }
+                self.latency_manual = false;
+                self.save_servers();
+                self.failover();
+            }
+        }
+    }
+    
+    // 运行时定期检查启用的服务器能否连接，结果同时更新延迟
+    fn monitor_health(&mut self) {
+        self.collect_latency();
+        if !self.enabled || self.latency_pending > 0 {
+            return;
+        }
+        if self.health_checked.map_or(false, |t| t.elapsed() < HEALTH_CHECK_INTERVAL) {
+            return;
+        }
+        self.health_checked = Some(std::time::Instant::now());
+        self.measure_latency();
+    }
+    
+    // 按顺序策略下使用的服务器：第一个可连接的DNSCrypt/DoH服务器，都不可连接时使用第一个
+    fn first_available(&self) -> Option<usize> {
+        let candidates: Vec<&DnsCryptServer> = self.servers.iter()
+            .filter(|s| s.enabled && s.protocol != ServerProtocol::DoT)
+            .collect();
+        candidates.iter()
+            .find(|s| !self.unhealthy.contains(&s.id))
+            .or_else(|| candidates.first())
+            .map(|s| s.id)
+    }
+    
+    // 按顺序策略下当前服务器无法连接或排在前面的服务器恢复时切换
+    fn failover(&mut self) {
+        if self.lb_strategy != LbStrategy::FirstAvailable || self.process.is_none() || self.active_server.is_none() {
+            return;
+        }
+        let next = self.first_available();
+        if next == self.active_server {
+            return;
+        }
+        let name = |id: Option<usize>| {
+            self.servers.iter().find(|s| Some(s.id) == id).map(|s| s.name.clone()).unwrap_or_default()
+        };
+        if let Ok(mut logger) = self.logger.lock() {
+            logger.warning("DNSCrypt", &format!("切换服务器: {} → {}", name(self.active_server), name(next)));
+        }
+        self.restart_upstreams();
+    }
+    
+    // 在后台检测是否处于需要登录的强制门户（酒店、机场等）之后
+    fn check_portal(&self) {
+        if let Ok(mut check) = self.portal_check.lock() {
+            *check = PortalCheck::Checking;
+        }
+        let check = Arc::clone(&self.portal_check);
+        let logger = Arc::clone(&self.logger);
+        std::thread::spawn(move || {
+            let status = captive_portal::detect();
+            if let Ok(mut logger) = logger.lock() {
+                match &status {
+                    PortalStatus::Online => logger.info("DNSCrypt", "网络连接正常，未检测到强制门户"),
+                    PortalStatus::Portal(_) => logger.warning("DNSCrypt", "检测到强制门户，需要登录后才能使用加密DNS"),
+                    PortalStatus::NoConnectivity(e) => logger.warning("DNSCrypt", &format!("无法访问网络连接检测地址: {}", e)),
+                }
+            }
+            if let Ok(mut check) = check.lock() {
+                *check = PortalCheck::Done(status);
+            }
+        });
+    }
+    
+    // 加密DNS长时间未就绪时自动检测一次强制门户
+    fn auto_check_portal(&mut self, ready: bool) {
+        if !self.enabled || ready {
+            self.not_ready_since = None;
+            self.portal_auto_checked = false;
+            return;
+        }
+        let since = *self.not_ready_since.get_or_insert_with(std::time::Instant::now);
+        if !self.portal_auto_checked && since.elapsed() >= PORTAL_CHECK_DELAY {
+            self.portal_auto_checked = true;
+            self.check_portal();
+        }
+    }
+    
+    // 强制门户检测结果与临时明文DNS
+    fn render_captive_portal(&mut self, ui: &mut Ui) {
+        let status = match self.portal_check.lock().as_deref() {
+            Ok(PortalCheck::Checking) => None,
+            Ok(PortalCheck::Done(status)) => Some(Some(status.clone())),
+            _ => Some(None),
         };
+        ui.horizontal(|ui| {
+            ui.label("强制门户:");
+            match &status {
+                None => {
+                    ui.spinner();
+                    ui.ctx().request_repaint_after(std::time::Duration::from_millis(200));
+                }
+                Some(None) => {
+                    ui.label(RichText::new("未检测").weak());
+                }
+                Some(Some(PortalStatus::Online)) => {
+                    ui.label(RichText::new("未检测到").color(Color32::GREEN));
+                }
+                Some(Some(PortalStatus::Portal(_))) => {
+                    ui.label(RichText::new("需要登录").color(Color32::YELLOW));
+                }
+                Some(Some(PortalStatus::NoConnectivity(e))) => {
+                    ui.label(RichText::new("无法访问网络").color(Color32::YELLOW)).on_hover_text(e);
+                }
+            }
+            if ui.add_enabled(status.is_some(), egui::Button::new("检测")).clicked() {
+                self.check_portal();
+            }
+        });
+        
+        if self.plaintext_dns {
+            ui.label(RichText::new("系统DNS已临时恢复为网络分配的明文DNS，完成门户登录后请恢复加密").color(Color32::YELLOW));
+            if ui.button("恢复加密DNS").clicked() {
+                self.plaintext_dns = false;
+                if let Ok(mut logger) = self.logger.lock() {
+                    logger.info("DNSCrypt", "已恢复加密DNS");
+                }
+                self.check_portal();
+            }
+            return;
+        }
+        let blocked = matches!(status, Some(Some(PortalStatus::Portal(_) | PortalStatus::NoConnectivity(_))));
+        if blocked && self.enabled && self.system_dns && ui.button("临时使用明文DNS登录").on_hover_text("恢复网卡原有的DNS并打开门户登录页").clicked() {
+            self.plaintext_dns = true;
+            if let Ok(mut logger) = self.logger.lock() {
+                logger.warning("DNSCrypt", "为登录强制门户临时使用明文DNS");
+            }
+            let url = match status {
+                Some(Some(PortalStatus::Portal(Some(location)))) => location,
+                _ => captive_portal::LOGIN_URL.to_string(),
+            };
+            if let Err(e) = webbrowser::open(&url) {
+                if let Ok(mut logger) = self.logger.lock() {
+                    logger.error("DNSCrypt", &format!("打开门户登录页失败: {}", e));
+                }
+            }
+        }
+    }
+    
+    // 在后台通过本地解析器测试DNSSEC验证
+    fn check_dnssec(&self) {
+        if let Ok(mut state) = self.dnssec_state.lock() {
+            *state = DnssecState::Checking;
+        }
+        let state = Arc::clone(&self.dnssec_state);
+        let logger = Arc::clone(&self.logger);
+        let port = self.listen_port;
+        std::thread::spawn(move || {
+            let result = dnssec_check::check(port);
+            if let Ok(mut logger) = logger.lock() {
+                match &result {
+                    DnssecResult::Validating => logger.info("DNSCrypt", "DNSSEC测试通过"),
+                    DnssecResult::NotValidating(reason) => logger.warning("DNSCrypt", &format!("DNSSEC测试未通过: {}", reason)),
+                    DnssecResult::Error(reason) => logger.error("DNSCrypt", &format!("DNSSEC测试失败: {}", reason)),
+                }
+            }
+            if let Ok(mut state) = state.lock() {
+                *state = DnssecState::Done(result);
+            }
+        });
+    }
+    
+    // 表头DNSSEC列旁的测试结果，点击重新测试
+    fn render_dnssec_indicator(&mut self, ui: &mut Ui) {
+        let (text, color, hover) = match self.dnssec_state.lock().as_deref() {
+            Ok(DnssecState::Checking) => {
+                ui.ctx().request_repaint_after(std::time::Duration::from_millis(200));
+                ("…", Color32::YELLOW, "正在测试...".to_string())
+            }
+            Ok(DnssecState::Done(DnssecResult::Validating)) => ("✔", Color32::GREEN, "当前解析器会验证DNSSEC签名".to_string()),
+            Ok(DnssecState::Done(DnssecResult::NotValidating(reason))) => ("✖", Color32::RED, format!("当前解析器不验证DNSSEC: {}", reason)),
+            Ok(DnssecState::Done(DnssecResult::Error(reason))) => ("?", Color32::YELLOW, format!("测试失败: {}", reason)),
+            _ => ("?", Color32::GRAY, "未测试".to_string()),
+        };
+        let checking = matches!(self.dnssec_state.lock().as_deref(), Ok(DnssecState::Checking));
+        let response = ui.add(egui::Label::new(RichText::new(text).color(color).strong()).sense(egui::Sense::click()))
+            .on_hover_text(if self.enabled { format!("{}\n点击通过本地解析器重新测试", hover) } else { format!("{}\n启动DNSCrypt后可点击测试", hover) });
+        if response.clicked() && self.enabled && !checking {
+            self.check_dnssec();
+        }
+    }
+    
+    // 按延迟排序服务器，未测量或无法连接的排在最后
+    fn sort_by_latency(&mut self) {
+        self.servers.sort_by_key(|s| (s.latency_ms.is_none(), s.latency_ms.unwrap_or(0)));
+        self.save_servers();
+    }
+    
+    fn save_rules(&self) {
+        if let Err(e) = self.rules.save() {
+            if let Ok(mut logger) = self.logger.lock() {
+                logger.error("DNSCrypt", &format!("保存DNS规则失败: {}", e));
+            }
+        }
+    }
+    
+    // 保存规则，运行中同时更新规则文件，由dnscrypt-proxy自动重新加载或在重启后生效
+    fn apply_rules(&mut self) {
+        self.save_rules();
+        if self.process.is_none() {
+            return;
+        }
+        let result = dnscrypt_process::reload_rules(&self.rules);
+        let hot_reload = self.rules_hot_reload();
+        if let Ok(mut logger) = self.logger.lock() {
+            match &result {
+                Ok(()) if hot_reload => logger.info("DNSCrypt", "DNS规则已更新，dnscrypt-proxy将自动重新加载"),
+                Ok(()) => logger.info("DNSCrypt", "DNS规则已更新，重启dnscrypt-proxy后生效"),
+                Err(e) => logger.error("DNSCrypt", &format!("更新规则文件失败: {:#}", e)),
+            }
+        }
+        if result.is_ok() {
+            self.note_rule_files_changed();
+        }
+    }
+    
+    // 伪装规则编辑器
+    fn render_cloaking(&mut self, ui: &mut Ui) {
+        ui.label("将域名解析为指定IP，或作为另一个域名的别名；支持 example.com、=example.com（不含子域名）与 *.example.com");
+        let mut removed = None;
+        for (index, rule) in self.rules.cloaking.iter().enumerate() {
+            ui.horizontal(|ui| {
+                ui.label(RichText::new(&rule.pattern).monospace());
+                ui.label("→");
+                ui.label(RichText::new(&rule.target).monospace());
+                if ui.small_button("✖").on_hover_text("删除").clicked() {
+                    removed = Some(index);
+                }
+            });
+        }
+        if let Some(index) = removed {
+            self.rules.cloaking.remove(index);
+            self.apply_rules();
+        }
         
-        // 添加一些示例服务器
-        module.add_example_servers();
+        ui.horizontal(|ui| {
+            ui.add(egui::TextEdit::singleline(&mut self.new_cloaking.pattern).hint_text("example.com").desired_width(200.0));
+            ui.label("→");
+            ui.add(egui::TextEdit::singleline(&mut self.new_cloaking.target).hint_text("192.168.1.10 或 other.example.com").desired_width(220.0));
+            if ui.button("添加").clicked() {
+                let rule = CloakingRule {
+                    pattern: self.new_cloaking.pattern.trim().to_lowercase(),
+                    target: self.new_cloaking.target.trim().to_lowercase(),
+                };
+                match rule.validate() {
+                    Ok(()) => {
+                        self.rules.cloaking.push(rule);
+                        self.new_cloaking = CloakingRule::default();
+                        self.rules_error = None;
+                        self.apply_rules();
+                    }
+                    Err(reason) => self.rules_error = Some(reason),
+                }
+            }
+        });
+    }
+    
+    // 白名单编辑器
+    fn render_allowlist(&mut self, ui: &mut Ui) {
+        ui.label("白名单中的域名始终放行，不受屏蔽列表影响");
+        let mut removed = None;
+        for (index, pattern) in self.rules.allowlist.iter().enumerate() {
+            ui.horizontal(|ui| {
+                ui.label(RichText::new(pattern).monospace());
+                if ui.small_button("✖").on_hover_text("删除").clicked() {
+                    removed = Some(index);
+                }
+            });
+        }
+        if let Some(index) = removed {
+            self.rules.allowlist.remove(index);
+            self.apply_rules();
+        }
+        
+        ui.horizontal(|ui| {
+            ui.add(egui::TextEdit::singleline(&mut self.new_allowed).hint_text("example.com").desired_width(200.0));
+            if ui.button("添加").clicked() {
+                let pattern = self.new_allowed.trim().to_lowercase();
+                match dns_rules::validate_pattern(&pattern) {
+                    Ok(()) if self.rules.allowlist.contains(&pattern) => {
+                        self.rules_error = Some(format!("{} 已在白名单中", pattern));
+                    }
+                    Ok(()) => {
+                        self.rules.allowlist.push(pattern);
+                        self.new_allowed.clear();
+                        self.rules_error = None;
+                        self.apply_rules();
+                    }
+                    Err(reason) => self.rules_error = Some(reason),
+                }
+            }
+        });
+    }
+    
+    // 根据查询日志显示缓存命中率
+    fn render_cache_stats(&mut self, ui: &mut Ui) {
+        if !self.cache.enabled {
+            return;
+        }
+        ui.horizontal(|ui| {
+            if !self.query_log {
+                ui.label(RichText::new("启用\"记录查询日志\"后可统计缓存命中率").weak());
+                return;
+            }
+            let stats = self.query_log_viewer.cache_stats();
+            let ratio = stats.hit_ratio().map(|r| format!("{:.1}%", r * 100.0)).unwrap_or_else(|| "-".to_string());
+            ui.label(format!("缓存命中率: {}（命中 {} / 未命中 {}）", ratio, stats.hits, stats.misses));
+            ui.label(format!("缓存条目（估算）: {} / {}", stats.entries().min(self.cache.size as usize), self.cache.size));
+            if ui.small_button("重置统计").clicked() {
+                self.query_log_viewer.reset_cache_stats();
+            }
+            if self.process.is_some() && ui.small_button("清空缓存").on_hover_text("重启dnscrypt-proxy以清空内存中的缓存").clicked() {
+                self.query_log_viewer.reset_cache_stats();
+                self.toggle_dnscrypt();
+                self.toggle_dnscrypt();
+            }
+        });
+    }
+    
+    // 本次会话的查询与拦截统计
+    fn render_query_stats(&mut self, ui: &mut Ui) {
+        if !self.query_log {
+            ui.label("启用\"记录查询日志\"并启动DNSCrypt后在此统计查询与拦截情况");
+            return;
+        }
+        self.query_log_viewer.query_stats().ui(ui);
+        if ui.small_button("重置统计").clicked() {
+            self.query_log_viewer.reset_query_stats();
+        }
+    }
+    
+    // 转发规则编辑器
+    fn render_forwarding(&mut self, ui: &mut Ui) {
+        ui.label("指定域名及其子域名交给指定的DNS服务器解析（不加密），其余查询仍通过加密服务器");
+        let mut removed = None;
+        for (index, rule) in self.rules.forwarding.iter().enumerate() {
+            ui.horizontal(|ui| {
+                ui.label(RichText::new(format!("*.{}", rule.domain)).monospace());
+                ui.label("→");
+                ui.label(RichText::new(&rule.servers).monospace());
+                if ui.small_button("✖").on_hover_text("删除").clicked() {
+                    removed = Some(index);
+                }
+            });
+        }
+        if let Some(index) = removed {
+            self.rules.forwarding.remove(index);
+            self.apply_rules();
+        }
+        
+        ui.horizontal(|ui| {
+            ui.add(egui::TextEdit::singleline(&mut self.new_forwarding.domain).hint_text("corp.local").desired_width(160.0));
+            ui.label("→");
+            ui.add(egui::TextEdit::singleline(&mut self.new_forwarding.servers).hint_text("10.0.0.53,10.0.0.54:53").desired_width(200.0));
+            if ui.button("添加").clicked() {
+                let rule = ForwardingRule::normalized(&self.new_forwarding.domain, &self.new_forwarding.servers);
+                match rule.validate() {
+                    Ok(()) if self.rules.forwarding.iter().any(|r| r.domain == rule.domain) => {
+                        self.rules_error = Some(format!("{} 已有转发规则", rule.domain));
+                    }
+                    Ok(()) => {
+                        self.rules.forwarding.push(rule);
+                        self.new_forwarding = ForwardingRule::default();
+                        self.rules_error = None;
+                        self.apply_rules();
+                    }
+                    Err(reason) => self.rules_error = Some(reason),
+                }
+            }
+            if ui.button(".onion → Tor").on_hover_text("通过Tor的DNSPort（默认127.0.0.1:5400，需在Tor设置中启用）解析.onion").clicked() {
+                self.new_forwarding = ForwardingRule { domain: "onion".to_string(), servers: "127.0.0.1:5400".to_string() };
+            }
+        });
+    }
+    
+    // 用下载的列表替换列表中的服务器，保留启用状态和手动添加的服务器
+    fn merge_resolvers(&mut self, resolvers: Vec<ListEntry>, relays: Vec<ListEntry>) {
+        let enabled: HashSet<String> = self.servers.iter()
+            .filter(|s| s.enabled && !s.resolver_name.is_empty())
+            .map(|s| s.resolver_name.clone())
+            .collect();
+        // 只替换按公共列表名称引用的服务器，带DNS Stamp的手动服务器保留
+        self.servers.retain(|s| s.resolver_name.is_empty() || !s.stamp.is_empty());
         
-        // 记录模块初始化日志
-        if let Ok(mut logger) = module.logger.lock() {
-            logger.info("DNSCrypt", "DNSCrypt模块已初始化");
+        // dnscrypt-proxy可直接使用DNSCrypt与DoH服务器
+        for entry in resolvers.into_iter().filter(|e| matches!(e.decoded.protocol, StampProtocol::DnsCrypt | StampProtocol::DoH)) {
+            let mut server = DnsCryptServer::new(self.next_server_id, &entry.name, "", "");
+            server.apply_stamp(&entry.decoded);
+            server.description = entry.description;
+            server.enabled = enabled.contains(&entry.name);
+            server.resolver_name = entry.name;
+            self.servers.push(server);
+            self.next_server_id += 1;
         }
+        self.relays = relays
+            .into_iter()
+            .filter(|e| e.decoded.protocol == StampProtocol::DnsCryptRelay)
+            .map(|e| DnsCryptRelay { name: e.name, address: e.decoded.address, description: e.description })
+            .collect();
+        self.list_updated = Some(Local::now().format("%Y-%m-%d %H:%M").to_string());
         
-        module
+        if let Ok(mut logger) = self.logger.lock() {
+            logger.info("DNSCrypt", &format!(
+                "已更新公共列表: {} 个服务器，{} 个中继，其中启用 {} 个",
+                self.servers.len(),
+                self.relays.len(),
+                self.servers.iter().filter(|s| s.enabled).count()
+            ));
+        }
+        self.save_servers();
     }
     
-    // 添加示例服务器
-    fn add_example_servers(&mut self) {
-        // 添加一些示例DNSCrypt服务器
-        let mut server1 = DnsCryptServer::new(
-            self.next_server_id,
-            "Cloudflare",
-            "1.1.1.1:443",
-            "2.dnscrypt-cert.cloudflare"
-        );
-        server1.description = "Cloudflare的DNS服务，注重隐私保护".to_string();
-        server1.dnssec = true;
-        server1.no_logs = true;
-        self.servers.push(server1);
-        self.next_server_id += 1;
-        
-        let mut server2 = DnsCryptServer::new(
-            self.next_server_id,
-            "Google",
-            "8.8.8.8:443",
-            "2.dnscrypt-cert.google"
-        );
-        server2.description = "Google的公共DNS服务".to_string();
-        server2.dnssec = true;
-        server2.no_logs = false;
-        self.servers.push(server2);
-        self.next_server_id += 1;
+    // DNS Stamp改变时重新解码，并用解码结果填写地址与提供商名称
+    fn on_stamp_changed(&mut self) {
+        let stamp = self.new_server_stamp.trim();
+        self.stamp_check = if stamp.is_empty() {
+            None
+        } else {
+            Some(dns_stamp::parse(stamp).and_then(|decoded| match decoded.protocol {
+                StampProtocol::DnsCrypt | StampProtocol::DoH | StampProtocol::DoT => Ok(decoded),
+                other => Err(format!("{}类型的Stamp不能作为上游服务器使用", other.label())),
+            }))
+        };
+        if let Some(Ok(decoded)) = &self.stamp_check {
+            let mut server = DnsCryptServer::new(0, "", "", "");
+            server.apply_stamp(decoded);
+            self.new_server_address = server.address;
+            self.new_server_provider = server.provider_name;
+            self.new_server_protocol = server.protocol;
+            self.new_server_doh_url = server.doh_url;
+            self.new_server_bootstrap = server.bootstrap_ip;
+            self.new_server_dot_hostname = server.dot_hostname;
+        }
+        self.form_error = None;
+    }
+    
+    // 在Stamp输入框下显示解码结果或错误
+    fn render_stamp_check(&self, ui: &mut Ui) {
+        match &self.stamp_check {
+            Some(Ok(decoded)) => {
+                let yes_no = |value: bool| if value { "是" } else { "否" };
+                ui.label(RichText::new(format!(
+                    "✓ {} | 地址: {} | 提供商: {}{} | DNSSEC: {} | 无日志: {} | 无过滤: {}",
+                    decoded.protocol.label(),
+                    if decoded.address.is_empty() { "-" } else { &decoded.address },
+                    decoded.provider_name,
+                    decoded.path,
+                    yes_no(decoded.dnssec),
+                    yes_no(decoded.no_logs),
+                    yes_no(decoded.no_filter),
+                )).color(Color32::GREEN).small());
+            }
+            Some(Err(error)) => {
+                ui.label(RichText::new(format!("✗ {}", error)).color(Color32::RED).small());
+            }
+            None => {}
+        }
+    }
+    
+    fn clear_form(&mut self) {
+        self.new_server_name.clear();
+        self.new_server_address.clear();
+        self.new_server_provider.clear();
+        self.new_server_resolver.clear();
+        self.new_server_stamp.clear();
+        self.new_server_protocol = ServerProtocol::DnsCrypt;
+        self.new_server_doh_url.clear();
+        self.new_server_bootstrap.clear();
+        self.new_server_dot_hostname.clear();
+        self.new_server_spki_pin.clear();
+        self.stamp_check = None;
+        self.form_error = None;
+    }
+    
+    // 校验并保存添加对话框中的服务器
+    fn save_form(&mut self) {
+        // 需要公共列表名称或DNS Stamp之一，dnscrypt-proxy才能使用该服务器
+        let name = self.new_server_name.trim().to_string();
+        let resolver = self.new_server_resolver.trim().to_string();
+        let error = if name.is_empty() {
+            Some("请填写服务器名称".to_string())
+        } else if let Some(Err(error)) = &self.stamp_check {
+            Some(format!("DNS Stamp无效: {}", error))
+        } else if self.new_server_protocol == ServerProtocol::DoT {
+            DotUpstream::new(&name, &self.new_server_address, &self.new_server_dot_hostname, &self.new_server_spki_pin).err()
+        } else if self.stamp_check.is_some() {
+            None
+        } else if self.new_server_protocol == ServerProtocol::DoH {
+            parse_doh_url(&self.new_server_doh_url)
+                .and_then(|_| parse_bootstrap_ip(&self.new_server_bootstrap))
+                .err()
+        } else if resolver.is_empty() {
+            Some("请填写公共列表名称或DNS Stamp".to_string())
+        } else {
+            None
+        };
+        if error.is_some() {
+            self.form_error = error;
+            return;
+        }
         
-        let mut server3 = DnsCryptServer::new(
+        let mut new_server = DnsCryptServer::new(
             self.next_server_id,
-            "Quad9",
-            "9.9.9.9:443",
-            "2.dnscrypt-cert.quad9"
+            &name,
+            &self.new_server_address,
+            &self.new_server_provider
         );
-        server3.description = "Quad9提供的安全DNS服务，可阻止恶意域名".to_string();
-        server3.dnssec = true;
-        server3.no_logs = true;
-        self.servers.push(server3);
-        self.next_server_id += 1;
+        if self.new_server_protocol == ServerProtocol::DoT {
+            new_server.protocol = ServerProtocol::DoT;
+            new_server.address = self.new_server_address.trim().to_string();
+            new_server.dot_hostname = self.new_server_dot_hostname.trim().to_string();
+            new_server.spki_pin = self.new_server_spki_pin.trim().to_string();
+            if let Some(Ok(decoded)) = &self.stamp_check {
+                new_server.dnssec = decoded.dnssec;
+                new_server.no_logs = decoded.no_logs;
+                new_server.no_filter = decoded.no_filter;
+            }
+        } else if let Some(Ok(decoded)) = &self.stamp_check {
+            new_server.apply_stamp(decoded);
+            new_server.stamp = self.new_server_stamp.trim().to_string();
+        } else if self.new_server_protocol == ServerProtocol::DoH {
+            new_server.protocol = ServerProtocol::DoH;
+            new_server.doh_url = self.new_server_doh_url.trim().to_string();
+            new_server.bootstrap_ip = self.new_server_bootstrap.trim().to_string();
+            if new_server.address.trim().is_empty() {
+                new_server.address = new_server.doh_url.clone();
+            }
+        }
+        new_server.resolver_name = resolver;
+        self.add_server(new_server);
+        self.clear_form();
+        self.edit_mode = false;
+    }
+    
+    // DoT服务器由程序内的转发器使用，不能与交给dnscrypt-proxy的DNSCrypt/DoH服务器同时启用
+    fn conflicts_with_enabled(&self, server: &DnsCryptServer) -> bool {
+        let dot = server.protocol == ServerProtocol::DoT;
+        self.servers.iter().any(|s| s.enabled && s.id != server.id && (s.protocol == ServerProtocol::DoT) != dot)
     }
     
-    // 添加新服务器
-    fn add_server(&mut self, server: DnsCryptServer) {
+    // 添加新服务器，与已启用的服务器冲突时添加为禁用
+    fn add_server(&mut self, mut server: DnsCryptServer) {
+        if server.enabled && self.conflicts_with_enabled(&server) {
+            server.enabled = false;
+            self.server_error = Some(format!("服务器 '{}' 已添加但未启用：DoT服务器不能与DNSCrypt/DoH服务器同时启用", server.name));
+        }
         if let Ok(mut logger) = self.logger.lock() {
             logger.info("DNSCrypt", &format!("添加新服务器: {}", server.name));
         }
         self.servers.push(server);
         self.next_server_id += 1;
+        self.save_servers();
+        self.apply_servers();
     }
     
     // 删除服务器
@@ -137,35 +1554,266 @@ impl DnsCryptModule {
             if self.selected_server == Some(id) {
                 self.selected_server = None;
             }
+            self.save_servers();
+            self.apply_servers();
         }
     }
     
     // 启用/禁用DNSCrypt
     fn toggle_dnscrypt(&mut self) {
-        // 先获取当前状态的副本
         let new_enabled = !self.enabled;
         let status_message = if new_enabled { "启用" } else { "禁用" };
-        
-        // 记录日志
-        {
-            if let Ok(mut logger) = self.logger.lock() {
-                logger.info("DNSCrypt", &format!("DNSCrypt已{}", status_message));
-            }
+        if let Ok(mut logger) = self.logger.lock() {
+            logger.info("DNSCrypt", &format!("DNSCrypt已{}", status_message));
         }
         
-        // 更新状态
         self.enabled = new_enabled;
+        self.plaintext_dns = false;
+        self.not_ready_since = None;
+        self.portal_auto_checked = false;
+        self.health_checked = None;
+        self.restart_pending = false;
         self.connection_status = if new_enabled { "正在连接..." } else { "未连接" }.to_string();
         
-        // 在实际应用中，这里会启动或停止DNSCrypt服务
+        // 启动或停止dnscrypt-proxy，进程由进程监控负责崩溃或端口无响应时自动重启
+        self.stop_upstreams();
         if new_enabled {
-            // 在实际应用中，这里会有异步连接逻辑
-            self.connection_status = "已连接".to_string();
+            if let Err(e) = self.start_upstreams() {
+                if let Ok(mut logger) = self.logger.lock() {
+                    logger.error("DNSCrypt", &format!("启动DNSCrypt失败: {:#}", e));
+                }
+                self.enabled = false;
+                self.connection_status = "启动失败".to_string();
+            }
+        }
+    }
+    
+    // 服务器增删或启用状态改变后生效：DoT转发直接替换上游，dnscrypt-proxy需要重启
+    fn apply_servers(&mut self) {
+        if !self.enabled {
+            return;
+        }
+        let enabled: Vec<&DnsCryptServer> = self.servers.iter().filter(|s| s.enabled).collect();
+        let dot_only = !enabled.is_empty() && enabled.iter().all(|s| s.protocol == ServerProtocol::DoT);
+        if let (Some(forwarder), true) = (&self.dot_forwarder, dot_only) {
+            let upstreams: Result<Vec<DotUpstream>, String> = enabled.iter().map(|s| s.dot_upstream()).collect();
+            let result = upstreams.map_err(anyhow::Error::msg).and_then(|upstreams| forwarder.set_upstreams(upstreams));
+            if let Err(e) = result {
+                if let Ok(mut logger) = self.logger.lock() {
+                    logger.error("DNSCrypt", &format!("更新DoT上游失败: {:#}", e));
+                }
+            }
+            return;
+        }
+        // dnscrypt-proxy只在启动时读取服务器列表，由用户选择何时重启
+        self.restart_pending = true;
+    }
+    
+    // 使用当前设置重新启动上游（切换服务器时）
+    fn restart_upstreams(&mut self) {
+        self.restart_pending = false;
+        self.stop_upstreams();
+        if let Err(e) = self.start_upstreams() {
+            if let Ok(mut logger) = self.logger.lock() {
+                logger.error("DNSCrypt", &format!("重新启动DNSCrypt失败: {:#}", e));
+            }
+            self.enabled = false;
+            self.connection_status = "启动失败".to_string();
+        }
+    }
+    
+    fn stop_upstreams(&mut self) {
+        if let Some(process) = self.process.take() {
+            process.stop();
+        }
+        if let Some(forwarder) = self.dot_forwarder.take() {
+            forwarder.stop();
+        }
+        if let Some(filter) = self.type_filter.take() {
+            filter.stop();
+        }
+    }
+    
+    // 只启用了DoT服务器时由程序内的转发器监听，否则启动dnscrypt-proxy
+    fn start_upstreams(&mut self) -> anyhow::Result<()> {
+        let (dot, others): (Vec<DnsCryptServer>, Vec<DnsCryptServer>) = self.servers.iter()
+            .filter(|s| s.enabled)
+            .cloned()
+            .partition(|s| s.protocol == ServerProtocol::DoT);
+        // 导入的服务器列表可能同时启用了两类服务器
+        if !dot.is_empty() && !others.is_empty() {
+            return Err(anyhow::anyhow!("DoT服务器不能与DNSCrypt/DoH服务器同时启用，请禁用其中一类"));
+        }
+        if !dot.is_empty() {
+            let upstreams = dot.iter()
+                .map(|s| s.dot_upstream().map_err(|e| anyhow::anyhow!("服务器 '{}': {}", s.name, e)))
+                .collect::<anyhow::Result<Vec<_>>>()?;
+            self.dot_forwarder = Some(DotForwarder::start(
+                Arc::clone(&self.logger),
+                upstreams,
+                self.listen_port,
+                self.ipv6_disabled,
+                self.lb_strategy,
+                &self.blocked_types,
+            )?);
+            self.active_server = None;
+            return Ok(());
+        }
+        // 按顺序策略只交给dnscrypt-proxy一个服务器，由健康检查负责切换
+        self.active_server = None;
+        let mut others = others;
+        if self.lb_strategy == LbStrategy::FirstAvailable {
+            self.active_server = self.first_available();
+            others.retain(|s| Some(s.id) == self.active_server);
+        }
+        let mut settings = DnsCryptSettings {
+            servers: others,
+            listen_port: self.listen_port,
+            extra_listen: self.extra_listen.clone(),
+            block_ipv6: self.ipv6_disabled,
+            rules: self.rules.clone(),
+            query_log: self.query_log,
+            cache: self.cache,
+            lb_strategy: self.lb_strategy,
+            blocked_types: self.blocked_types.clone(),
+            bootstrap_resolvers: self.bootstrap_resolvers
+                .split(',')
+                .map(str::trim)
+                .filter(|s| !s.is_empty())
+                .map(str::to_string)
+                .collect(),
+        };
+        // 需要拒绝查询类型时，过滤器监听原有地址，dnscrypt-proxy改为只监听内部端口
+        let mut filter_listen = Vec::new();
+        if !self.blocked_types.is_empty() {
+            filter_listen.push(std::net::SocketAddr::from(([127, 0, 0, 1], self.listen_port)));
+            filter_listen.extend(self.extra_listen.iter().filter_map(|a| a.parse::<std::net::SocketAddr>().ok()));
+            settings.listen_port = dns_type_filter::internal_port()?;
+            settings.extra_listen.clear();
+        }
+        self.process = Some(DnsCryptProcess::start(self.launcher.as_ref(), Arc::clone(&self.logger), &settings)?);
+        if !filter_listen.is_empty() {
+            let upstream = std::net::SocketAddr::from(([127, 0, 0, 1], settings.listen_port));
+            match TypeFilter::start(Arc::clone(&self.logger), &filter_listen, upstream, &self.blocked_types) {
+                Ok(filter) => self.type_filter = Some(filter),
+                Err(e) => {
+                    self.stop_upstreams();
+                    return Err(e);
+                }
+            }
+        }
+        if self.query_log {
+            self.query_log_viewer.set_path(dnscrypt_process::query_log_path().ok());
+        }
+        Ok(())
+    }
+    
+    // 登记本地DNS监听端口（DNS同时使用UDP和TCP）
+    fn reserve_ports(&self) {
+        let mut reserved = vec![(self.listen_port, Protocol::Udp), (self.listen_port, Protocol::Tcp)];
+        for address in self.extra_listen.iter().filter_map(|a| a.parse::<std::net::SocketAddr>().ok()) {
+            reserved.push((address.port(), Protocol::Udp));
+            reserved.push((address.port(), Protocol::Tcp));
+        }
+        ports::reserve("DNSCrypt", reserved);
+    }
+    
+    // 校验额外的监听地址
+    fn validate_listen_address(&self, address: &str) -> Result<std::net::SocketAddr, String> {
+        let parsed = address.parse::<std::net::SocketAddr>().map_err(|_| format!("监听地址应为IP:端口 \"{}\"", address))?;
+        if parsed.ip().is_loopback() && parsed.port() == self.listen_port {
+            return Err("与本地监听端口相同".to_string());
+        }
+        // 监听所有地址时会与127.0.0.1上的同一端口冲突
+        if parsed.ip().is_unspecified() && parsed.port() == self.listen_port {
+            return Err(format!("{}会与本地监听端口冲突，请填写具体的局域网IP", parsed.ip()));
+        }
+        if self.extra_listen.iter().any(|a| a == address) {
+            return Err("该地址已添加".to_string());
+        }
+        Ok(parsed)
+    }
+    
+    // 额外的监听地址，供局域网内其他设备使用
+    fn render_listen_addresses(&mut self, ui: &mut Ui) {
+        let mut removed = None;
+        for (index, address) in self.extra_listen.iter().enumerate() {
+            ui.horizontal(|ui| {
+                ui.label(RichText::new(address).monospace());
+                if let Ok(parsed) = address.parse::<std::net::SocketAddr>() {
+                    if self.enabled {
+                        ui.label(RichText::new("使用中").color(Color32::GREEN));
+                    } else {
+                        match ports::status("DNSCrypt", &parsed.ip().to_string(), parsed.port(), Protocol::Udp) {
+                            PortStatus::Checking => {
+                                ui.spinner();
+                            }
+                            PortStatus::Free => {
+                                ui.label(RichText::new("端口可用").color(Color32::GREEN));
+                            }
+                            PortStatus::InUse(owner) => {
+                                let text = match owner {
+                                    Some(owner) => format!("已被 {} 占用", owner.describe()),
+                                    None => "已被其他程序占用".to_string(),
+                                };
+                                ui.label(RichText::new(text).color(Color32::RED));
+                            }
+                            PortStatus::Reserved(other) => {
+                                ui.label(RichText::new(format!("与{}的端口冲突", other)).color(Color32::RED));
+                            }
+                        }
+                    }
+                }
+                if ui.small_button("✖").on_hover_text("删除").clicked() {
+                    removed = Some(index);
+                }
+            });
+        }
+        if let Some(index) = removed {
+            self.extra_listen.remove(index);
+            self.reserve_ports();
+        }
+        
+        ui.horizontal(|ui| {
+            ui.add(egui::TextEdit::singleline(&mut self.new_listen_address).hint_text("192.168.1.2:53").desired_width(160.0));
+            if ui.button("添加").clicked() {
+                let address = self.new_listen_address.trim().to_string();
+                match self.validate_listen_address(&address) {
+                    Ok(_) => {
+                        self.extra_listen.push(address);
+                        self.new_listen_address.clear();
+                        self.listen_error = None;
+                        self.reserve_ports();
+                    }
+                    Err(reason) => self.listen_error = Some(reason),
+                }
+            }
+        });
+        if let Some(error) = &self.listen_error {
+            ui.label(RichText::new(error).color(Color32::RED));
+        }
+        if !self.extra_listen.is_empty() {
+            ui.label(RichText::new("局域网设备需要防火墙放行对应端口（UDP与TCP）；只启用DoT服务器时不监听这些地址").weak());
+        }
+    }
+    
+    // 按指定状态启用/禁用DNSCrypt
+    pub fn set_enabled(&mut self, enabled: bool) {
+        if self.enabled != enabled {
+            self.toggle_dnscrypt();
         }
     }
     
     // 启用/禁用服务器
     fn toggle_server(&mut self, id: usize) {
+        if let Some(server) = self.servers.iter().find(|s| s.id == id) {
+            if !server.enabled && self.conflicts_with_enabled(server) {
+                self.server_error = Some(format!("无法启用 '{}'：DoT服务器不能与DNSCrypt/DoH服务器同时启用", server.name));
+                return;
+            }
+        }
+        self.server_error = None;
+        
         // 先查找服务器并获取必要信息，避免同时借用
         let server_info = self.servers.iter_mut()
             .find(|s| s.id == id)
@@ -181,11 +1829,72 @@ impl DnsCryptModule {
             if let Ok(mut logger) = self.logger.lock() {
                 logger.info("DNSCrypt", &format!("服务器 '{}' 已{}", name, if enabled { "启用" } else { "禁用" }));
             }
+            self.save_servers();
+            self.apply_servers();
+        }
+    }
+    
+    // 获取当前连接状态的副本
+    pub fn get_connection_status(&self) -> String {
+        self.connection_status.clone()
+    }
+    
+    // DNSCrypt是否已启用
+    pub fn is_enabled(&self) -> bool {
+        self.enabled
+    }
+
+    // dnscrypt-proxy（或DoT转发器）是否已就绪
+    pub fn is_connected(&self) -> bool {
+        let state = self.process.as_ref().map(|p| p.state())
+            .or_else(|| self.dot_forwarder.as_ref().map(|f| f.state()));
+        matches!(state, Some(DnsCryptState::Ready { .. }))
+    }
+    
+    // 已连接时本地DNS的监听地址
+    pub fn local_resolver(&self) -> Option<std::net::SocketAddr> {
+        self.is_connected().then(|| std::net::SocketAddr::from(([127, 0, 0, 1], self.listen_port)))
+    }
+    
+    // 是否开启了查询日志，防火墙的域名规则依赖它追踪解析到的地址
+    pub fn query_log_enabled(&self) -> bool {
+        self.query_log
+    }
+
+    // 取走查询日志中新增的记录
+    pub fn take_new_queries(&mut self) -> Vec<QueryEntry> {
+        self.query_log_viewer.take_new_entries()
+    }
+
+    // 设置防火墙域名规则要阻止的域名，变化时重新生成blocked-names.txt
+    pub fn set_firewall_blocked_names(&mut self, names: Vec<String>) {
+        if self.rules.firewall_blocked != names {
+            self.rules.firewall_blocked = names;
+            self.combine_blocklists();
         }
     }
+
+    // 是否需要将系统DNS指向本机（系统DNS只能使用53端口）
+    pub fn wants_system_dns(&self) -> bool {
+        self.enabled && self.system_dns && !self.plaintext_dns && self.listen_port == 53 && utils::is_running_as_admin()
+    }
     
     // 渲染UI
     pub fn ui(&mut self, ui: &mut Ui) {
+        // 根据进程状态更新连接状态
+        let state = self.process.as_ref().map(|p| p.state())
+            .or_else(|| self.dot_forwarder.as_ref().map(|f| f.state()));
+        self.auto_check_portal(matches!(state, Some(DnsCryptState::Ready { .. })));
+        if let Some(state) = &state {
+            self.connection_status = match state {
+                DnsCryptState::Starting => "正在连接...".to_string(),
+                DnsCryptState::Ready { .. } => "已连接".to_string(),
+                DnsCryptState::Restarting { .. } => "正在重启...".to_string(),
+                DnsCryptState::Stopped => "未连接".to_string(),
+                DnsCryptState::Failed(_) => "连接失败".to_string(),
+            };
+        }
+        
         ui.horizontal(|ui| {
             ui.heading(RichText::new("DNSCrypt").color(DNS_COLOR).strong());
             ui.add_space(10.0);
@@ -193,7 +1902,7 @@ impl DnsCryptModule {
             let status_text = &self.connection_status;
             let status_color = match status_text.as_str() {
                 "已连接" => Color32::GREEN,
-                "正在连接..." => Color32::YELLOW,
+                "正在连接..." | "正在重启..." => Color32::YELLOW,
                 _ => Color32::RED,
             };
             ui.label(RichText::new(status_text).color(status_color).strong());
@@ -207,6 +1916,30 @@ impl DnsCryptModule {
         
         ui.separator();
         
+        match &state {
+            Some(DnsCryptState::Starting) => {
+                ui.label("正在获取服务器列表并测试服务器...");
+                ui.ctx().request_repaint_after(std::time::Duration::from_millis(500));
+            }
+            Some(DnsCryptState::Ready { live_servers }) => {
+                ui.label(format!("DNS已在 127.0.0.1:{} 上加密解析，可用服务器 {} 个", self.listen_port, live_servers));
+                if let Some(forwarder) = &self.dot_forwarder {
+                    ui.label(format!("通过DoT转发，失败的查询 {} 次", forwarder.failures()));
+                }
+            }
+            Some(DnsCryptState::Restarting { attempt, delay }) => {
+                ui.label(RichText::new(format!("dnscrypt-proxy意外退出，{}秒后进行第{}次重启", delay.as_secs(), attempt)).color(Color32::YELLOW));
+                ui.ctx().request_repaint_after(std::time::Duration::from_secs(1));
+            }
+            Some(DnsCryptState::Failed(reason)) => {
+                ui.label(RichText::new(reason).color(Color32::RED));
+            }
+            Some(DnsCryptState::Stopped) | None => {}
+        }
+        if self.enabled {
+            ui.label(RichText::new("修改设置或服务器后需重启DNSCrypt生效").weak());
+        }
+        
         // DNSCrypt简介
         ui.collapsing("关于DNSCrypt", |ui| {
             ui.label("DNSCrypt是一种用于保护DNS查询的协议，可以防止DNS劫持和监听。");
@@ -219,39 +1952,200 @@ impl DnsCryptModule {
             ui.heading("DNSCrypt设置");
             
             ui.checkbox(&mut self.dns_leak_protection, "DNS泄露保护");
-            ui.checkbox(&mut self.ipv6_disabled, "禁用IPv6解析");
+            ui.checkbox(&mut self.ipv6_disabled, "禁用IPv6解析")
+                .on_hover_text("对AAAA（IPv6地址）查询直接返回空结果，应用只会连接IPv4地址；DNSCrypt与DoT转发均生效，需重启DNSCrypt");
+            ui.horizontal(|ui| {
+                ui.label("服务器选择策略:");
+                egui::ComboBox::from_id_source("dnscrypt_lb_strategy")
+                    .selected_text(self.lb_strategy.label())
+                    .show_ui(ui, |ui| {
+                        for strategy in LbStrategy::ALL {
+                            ui.selectable_value(&mut self.lb_strategy, strategy, strategy.label())
+                                .on_hover_text(strategy.description());
+                        }
+                    });
+                ui.label(RichText::new(format!("{}（更改后需重启DNSCrypt）", self.lb_strategy.description())).weak());
+            });
+            ui.horizontal(|ui| {
+                ui.label("拒绝的查询类型:");
+                for qtype in BlockedType::ALL {
+                    let mut blocked = self.blocked_types.contains(&qtype);
+                    if ui.checkbox(&mut blocked, qtype.label()).on_hover_text(qtype.description()).changed() {
+                        if blocked {
+                            self.blocked_types.push(qtype);
+                        } else {
+                            self.blocked_types.retain(|t| *t != qtype);
+                        }
+                    }
+                }
+                if let Some(filter) = &self.type_filter {
+                    ui.label(RichText::new(format!("已拒绝 {} 次", filter.refused())).weak());
+                }
+            })
+            .response
+            .on_hover_text("被拒绝的查询返回REFUSED；需重启DNSCrypt。启用后查询日志中的客户端均显示为127.0.0.1");
+            ui.add_enabled(utils::is_running_as_admin(), egui::Checkbox::new(&mut self.system_dns, "自动设置系统DNS"))
+                .on_hover_text("运行时将已联网网卡的DNS服务器设为127.0.0.1，停止或退出时恢复原设置")
+                .on_disabled_hover_text("修改系统DNS需要管理员权限");
+            if self.system_dns && self.listen_port != 53 {
+                ui.label(RichText::new("系统DNS只能使用53端口，请将本地监听端口设为53").color(Color32::YELLOW));
+            } else if self.wants_system_dns() && system_dns::is_redirected() {
+                ui.label(RichText::new("系统DNS当前指向本机").color(Color32::GREEN));
+            }
+            self.render_captive_portal(ui);
+            ui.horizontal(|ui| {
+                ui.label("引导DNS:");
+                ui.add(egui::TextEdit::singleline(&mut self.bootstrap_resolvers).desired_width(220.0))
+                    .on_hover_text("逗号分隔的IP:端口。用于下载服务器列表、解析服务器地址，以及检测网络是否可用（第一个）");
+            });
+            ui.checkbox(&mut self.query_log, "记录查询日志")
+                .on_hover_text("将解析的每个域名写入query.log并在下方\"查询日志\"中实时显示（DoT转发不记录）");
+            
+            ui.horizontal(|ui| {
+                ui.checkbox(&mut self.cache.enabled, "本地缓存");
+                ui.add_enabled_ui(self.cache.enabled, |ui| {
+                    ui.label("条目上限:");
+                    ui.add(egui::DragValue::new(&mut self.cache.size).clamp_range(64..=65536).speed(64));
+                    ui.label("最小TTL:");
+                    ui.add(egui::DragValue::new(&mut self.cache.min_ttl).clamp_range(0..=86400).suffix(" 秒"))
+                        .on_hover_text("短于此值的TTL会被延长，减少重复查询");
+                    ui.label("最大TTL:");
+                    ui.add(egui::DragValue::new(&mut self.cache.max_ttl).clamp_range(self.cache.min_ttl..=604800).suffix(" 秒"));
+                });
+            });
+            self.render_cache_stats(ui);
+            
+            ui.horizontal(|ui| {
+                ui.label("本地监听端口:");
+                let mut listen_port = self.listen_port;
+                if ports::port_field(ui, "DNSCrypt", "127.0.0.1", &mut listen_port, Protocol::Udp, self.enabled) {
+                    self.listen_port = listen_port;
+                    self.reserve_ports();
+                }
+            });
+            ui.collapsing("其他监听地址", |ui| self.render_listen_addresses(ui));
         });
         
         ui.separator();
         
         // 服务器管理区域
+        self.collect_refresh();
+        let refreshing = matches!(self.refresh_state.lock().as_deref(), Ok(RefreshState::Refreshing));
+        
         ui.horizontal(|ui| {
             ui.heading("DNSCrypt服务器");
             ui.with_layout(egui::Layout::right_to_left(egui::Align::Center), |ui| {
                 if ui.button("添加服务器").clicked() {
                     self.edit_mode = true;
                 }
+                let measuring = self.latency_pending > 0;
+                let button = ui.add_enabled(!measuring, egui::Button::new("测量延迟"))
+                    .on_hover_text("测量每个启用服务器的TCP连接延迟（取3次的中位数）");
+                if button.clicked() {
+                    self.latency_manual = true;
+                    self.measure_latency();
+                }
+                if measuring {
+                    ui.spinner();
+                    ui.ctx().request_repaint_after(std::time::Duration::from_millis(200));
+                }
+                if ui.button("导出...").on_hover_text("将所有服务器（含自定义DNS Stamp）导出为JSON文件").clicked() {
+                    self.export_servers_dialog();
+                }
+                if ui.button("导入...").on_hover_text("从导出的JSON文件导入服务器，相同的服务器会被更新").clicked() {
+                    self.import_servers_dialog();
+                }
+                let button = ui.add_enabled(!refreshing, egui::Button::new("更新公共列表"))
+                    .on_hover_text("下载public-resolvers.md与relays.md，按DNS Stamp读取服务器属性");
+                if button.clicked() {
+                    self.refresh_resolvers();
+                }
+                if refreshing {
+                    ui.spinner();
+                    ui.ctx().request_repaint_after(std::time::Duration::from_millis(200));
+                }
             });
         });
+        ui.horizontal(|ui| {
+            let enabled = self.servers.iter().filter(|s| s.enabled).count();
+            ui.label(format!("共 {} 个服务器，启用 {} 个，匿名中继 {} 个", self.servers.len(), enabled, self.relays.len()));
+            if let Some(updated) = &self.list_updated {
+                ui.label(RichText::new(format!("列表更新于 {}", updated)).weak());
+            }
+        });
+        if let Some(error) = &self.server_error {
+            ui.label(RichText::new(error).color(Color32::RED));
+        }
+        if self.restart_pending && self.process.is_some() {
+            let message = if self.rules_hot_reload() {
+                "服务器更改需要重启dnscrypt-proxy才能生效（规则与屏蔽列表的修改会自动生效）"
+            } else {
+                "更改需要重启dnscrypt-proxy才能生效（已安装的版本不支持规则热重载）"
+            };
+            ui.horizontal(|ui| {
+                ui.label(RichText::new(message).color(Color32::YELLOW));
+                if ui.small_button("立即重启").clicked() {
+                    self.restart_upstreams();
+                }
+            });
+        }
+        if let Some(result) = &self.import_result {
+            let (text, color) = match result {
+                Ok(message) => (message.clone(), Color32::GREEN),
+                Err(error) => (error.clone(), Color32::RED),
+            };
+            ui.horizontal(|ui| {
+                ui.label(RichText::new(text).color(color));
+                if ui.small_button("✖").clicked() {
+                    self.import_result = None;
+                }
+            });
+        }
+        if let Ok(mut state) = self.refresh_state.lock() {
+            if let RefreshState::Failed(error) = &*state {
+                let error = error.clone();
+                ui.horizontal(|ui| {
+                    ui.label(RichText::new(format!("更新公共列表失败: {}", error)).color(Color32::RED));
+                    if ui.small_button("✖").clicked() {
+                        *state = RefreshState::Idle;
+                    }
+                });
+            }
+        }
         
         // 服务器列表
-        ScrollArea::vertical().show(ui, |ui| {
+        self.render_filter(ui);
+        ScrollArea::vertical().id_source("dnscrypt_servers").max_height(360.0).show(ui, |ui| {
             Grid::new("dnscrypt_servers_grid")
-                .num_columns(6)
+                .num_columns(9)
                 .striped(true)
                 .spacing([10.0, 4.0])
                 .show(ui, |ui| {
                     // 表头
                     ui.label(RichText::new("启用").strong());
                     ui.label(RichText::new("名称").strong());
+                    ui.label(RichText::new("协议").strong());
                     ui.label(RichText::new("地址").strong());
-                    ui.label(RichText::new("DNSSEC").strong());
+                    ui.horizontal(|ui| {
+                        ui.label(RichText::new("DNSSEC").strong());
+                        self.render_dnssec_indicator(ui);
+                    });
                     ui.label(RichText::new("无日志").strong());
+                    ui.label(RichText::new("无过滤").strong());
+                    if ui.add(egui::Label::new(RichText::new("延迟 ⏶").strong()).sense(egui::Sense::click()))
+                        .on_hover_text("点击按延迟排序")
+                        .clicked()
+                    {
+                        self.sort_by_latency();
+                    }
                     ui.label(RichText::new("操作").strong());
                     ui.end_row();
                     
                     // 服务器列表
-                    let servers_copy = self.servers.clone();
+                    let servers_copy: Vec<DnsCryptServer> = self.servers.iter()
+                        .filter(|s| self.filter.matches(s))
+                        .cloned()
+                        .collect();
                     for (_index, server) in servers_copy.iter().enumerate() {
                         // 启用/禁用复选框
                         let mut enabled = server.enabled;
@@ -265,6 +2159,8 @@ impl DnsCryptModule {
                             self.selected_server = Some(server.id);
                         }
                         
+                        ui.label(server.protocol.label());
+                        
                         // 服务器地址
                         ui.label(&server.address);
                         
@@ -274,6 +2170,20 @@ impl DnsCryptModule {
                         // 无日志政策
                         ui.label(if server.no_logs { "✓" } else { "✗" });
                         
+                        // 是否不屏蔽任何域名
+                        ui.label(if server.no_filter { "✓" } else { "✗" });
+                        
+                        // 延迟
+                        let in_use = self.active_server == Some(server.id);
+                        match server.latency_ms {
+                            _ if self.unhealthy.contains(&server.id) => ui.label(RichText::new("不可用").color(Color32::RED))
+                                .on_hover_text("最近一次健康检查无法连接"),
+                            Some(ms) if in_use => ui.label(RichText::new(format!("{} ms ●", ms)).color(Color32::GREEN))
+                                .on_hover_text("按顺序策略下正在使用"),
+                            Some(ms) => ui.label(format!("{} ms", ms)),
+                            None => ui.label("-"),
+                        };
+                        
                         // 操作按钮（修复借用冲突）
                         let server_id = server.id;
                         ui.horizontal(|ui| {
@@ -313,6 +2223,38 @@ impl DnsCryptModule {
                         ui.label(&server.provider_name);
                         ui.end_row();
                         
+                        ui.label("协议:");
+                        ui.label(server.protocol.label());
+                        ui.end_row();
+                        
+                        if server.protocol == ServerProtocol::DoT {
+                            ui.label("TLS主机名:");
+                            ui.label(&server.dot_hostname);
+                            ui.end_row();
+                            
+                            ui.label("SPKI指纹:");
+                            ui.label(RichText::new(if server.spki_pin.is_empty() { "-" } else { &server.spki_pin }).monospace().small());
+                            ui.end_row();
+                        }
+                        
+                        if server.protocol == ServerProtocol::DoH {
+                            ui.label("DoH地址:");
+                            ui.label(&server.doh_url);
+                            ui.end_row();
+                            
+                            ui.label("引导IP:");
+                            ui.label(if server.bootstrap_ip.is_empty() { "-" } else { &server.bootstrap_ip });
+                            ui.end_row();
+                        }
+                        
+                        ui.label("公共列表名称:");
+                        ui.label(if server.resolver_name.is_empty() { "-" } else { &server.resolver_name });
+                        ui.end_row();
+                        
+                        ui.label("DNS Stamp:");
+                        ui.label(RichText::new(if server.stamp.is_empty() { "-" } else { &server.stamp }).monospace().small());
+                        ui.end_row();
+                        
                         ui.label("DNSSEC支持:");
                         ui.label(if server.dnssec { "是" } else { "否" });
                         ui.end_row();
@@ -321,6 +2263,10 @@ impl DnsCryptModule {
                         ui.label(if server.no_logs { "是" } else { "否" });
                         ui.end_row();
                         
+                        ui.label("无过滤:");
+                        ui.label(if server.no_filter { "是" } else { "否" });
+                        ui.end_row();
+                        
                         ui.label("描述:");
                         ui.label(&server.description);
                         ui.end_row();
@@ -328,6 +2274,59 @@ impl DnsCryptModule {
             }
         }
         
+        // 匿名DNS中继列表
+        if !self.relays.is_empty() {
+            ui.separator();
+            egui::CollapsingHeader::new(format!("匿名DNS中继 ({})", self.relays.len()))
+                .id_source("dnscrypt_relays")
+                .show(ui, |ui| {
+                    ScrollArea::vertical().id_source("dnscrypt_relays_scroll").max_height(200.0).show(ui, |ui| {
+                        Grid::new("dnscrypt_relays_grid")
+                            .num_columns(2)
+                            .striped(true)
+                            .spacing([10.0, 4.0])
+                            .show(ui, |ui| {
+                                for relay in &self.relays {
+                                    ui.label(&relay.name).on_hover_text(&relay.description);
+                                    ui.label(RichText::new(&relay.address).monospace());
+                                    ui.end_row();
+                                }
+                            });
+                    });
+                });
+        }
+        
+        // 实时查询日志与拦截统计
+        self.query_log_viewer.update(ui.ctx());
+        ui.separator();
+        egui::CollapsingHeader::new("拦截统计")
+            .id_source("dnscrypt_query_stats")
+            .show(ui, |ui| self.render_query_stats(ui));
+        egui::CollapsingHeader::new("查询日志")
+            .id_source("dnscrypt_query_log")
+            .show(ui, |ui| self.query_log_viewer.ui(ui));
+        
+        // 伪装规则、白名单、转发规则与屏蔽列表
+        ui.separator();
+        egui::CollapsingHeader::new(format!("伪装规则 ({})", self.rules.cloaking.len()))
+            .id_source("dnscrypt_cloaking")
+            .show(ui, |ui| self.render_cloaking(ui));
+        egui::CollapsingHeader::new(format!("白名单 ({})", self.rules.allowlist.len()))
+            .id_source("dnscrypt_allowlist")
+            .show(ui, |ui| self.render_allowlist(ui));
+        egui::CollapsingHeader::new(format!("转发规则 ({})", self.rules.forwarding.len()))
+            .id_source("dnscrypt_forwarding")
+            .show(ui, |ui| self.render_forwarding(ui));
+        egui::CollapsingHeader::new(format!("屏蔽列表 ({})", self.rules.blocklists.len()))
+            .id_source("dnscrypt_blocklists")
+            .show(ui, |ui| self.render_blocklists(ui));
+        egui::CollapsingHeader::new("hosts文件")
+            .id_source("dnscrypt_hosts")
+            .show(ui, |ui| self.hosts_panel.ui(ui, &self.rules.cloaking));
+        if let Some(error) = &self.rules_error {
+            ui.label(RichText::new(error).color(Color32::RED));
+        }
+        
         // 添加/编辑服务器对话框
         if self.edit_mode {
             // 在实际应用中，这里会使用一个模态对话框
@@ -343,45 +2342,87 @@ impl DnsCryptModule {
                 }
             });
             
-            let mut server_address = self.new_server_address.clone();
             ui.horizontal(|ui| {
-                ui.label("服务器地址:");
-                if ui.text_edit_singleline(&mut server_address).changed() {
-                    self.new_server_address = server_address;
+                ui.label("协议:");
+                for protocol in [ServerProtocol::DnsCrypt, ServerProtocol::DoH, ServerProtocol::DoT] {
+                    ui.radio_value(&mut self.new_server_protocol, protocol, protocol.label());
                 }
             });
             
-            let mut server_provider = self.new_server_provider.clone();
+            match self.new_server_protocol {
+                ServerProtocol::DnsCrypt => {
+                    let mut server_address = self.new_server_address.clone();
+                    ui.horizontal(|ui| {
+                        ui.label("服务器地址:");
+                        if ui.text_edit_singleline(&mut server_address).changed() {
+                            self.new_server_address = server_address;
+                        }
+                    });
+                    
+                    let mut server_provider = self.new_server_provider.clone();
+                    ui.horizontal(|ui| {
+                        ui.label("提供商名称:");
+                        if ui.text_edit_singleline(&mut server_provider).changed() {
+                            self.new_server_provider = server_provider;
+                        }
+                    });
+                    
+                    ui.horizontal(|ui| {
+                        ui.label("公共列表名称:");
+                        ui.add(egui::TextEdit::singleline(&mut self.new_server_resolver).hint_text("如 quad9-dnscrypt-ip4-filter-pri"));
+                    });
+                }
+                ServerProtocol::DoH => {
+                    ui.horizontal(|ui| {
+                        ui.label("DoH地址:");
+                        ui.add(egui::TextEdit::singleline(&mut self.new_server_doh_url).hint_text("https://dns.example/dns-query").desired_width(300.0));
+                    });
+                    ui.horizontal(|ui| {
+                        ui.label("引导IP:");
+                        ui.add(egui::TextEdit::singleline(&mut self.new_server_bootstrap).hint_text("可选，如 9.9.9.9"))
+                            .on_hover_text("DoH服务器的IP地址，留空时通过引导DNS解析主机名");
+                    });
+                }
+                ServerProtocol::DoT => {
+                    ui.horizontal(|ui| {
+                        ui.label("服务器地址:");
+                        ui.add(egui::TextEdit::singleline(&mut self.new_server_address).hint_text("IP或IP:端口，默认端口853"));
+                    });
+                    ui.horizontal(|ui| {
+                        ui.label("TLS主机名:");
+                        ui.add(egui::TextEdit::singleline(&mut self.new_server_dot_hostname).hint_text("如 dns.quad9.net"))
+                            .on_hover_text("用于SNI与证书校验");
+                    });
+                    ui.horizontal(|ui| {
+                        ui.label("SPKI指纹:");
+                        ui.add(egui::TextEdit::singleline(&mut self.new_server_spki_pin).hint_text("可选，Base64编码的SHA-256").desired_width(300.0))
+                            .on_hover_text("填写后只接受公钥与此指纹一致的证书");
+                    });
+                    ui.label(RichText::new("DoT服务器由InviZible Pro直接转发，不经过dnscrypt-proxy，不能与DNSCrypt/DoH服务器同时使用").weak().small());
+                }
+            }
+            
             ui.horizontal(|ui| {
-                ui.label("提供商名称:");
-                if ui.text_edit_singleline(&mut server_provider).changed() {
-                    self.new_server_provider = server_provider;
+                ui.label("DNS Stamp:");
+                let response = ui.add(egui::TextEdit::singleline(&mut self.new_server_stamp).hint_text("sdns://...（可选，填写时优先使用）").desired_width(360.0));
+                if response.changed() {
+                    self.on_stamp_changed();
                 }
             });
+            self.render_stamp_check(ui);
+            
+            if let Some(error) = &self.form_error {
+                ui.label(RichText::new(error).color(Color32::RED));
+            }
             
             ui.horizontal(|ui| {
                 if ui.button("取消").clicked() {
                     self.edit_mode = false;
-                    self.new_server_name.clear();
-                    self.new_server_address.clear();
-                    self.new_server_provider.clear();
+                    self.clear_form();
                 }
                 
                 if ui.button("保存").clicked() {
-                    // 保存服务器逻辑
-                    if !self.new_server_name.is_empty() && !self.new_server_address.is_empty() && !self.new_server_provider.is_empty() {
-                        let new_server = DnsCryptServer::new(
-                            self.next_server_id,
-                            &self.new_server_name,
-                            &self.new_server_address,
-                            &self.new_server_provider
-                        );
-                        self.add_server(new_server);
-                        self.new_server_name.clear();
-                        self.new_server_address.clear();
-                        self.new_server_provider.clear();
-                        self.edit_mode = false;
-                    }
+                    self.save_form();
                 }
             });
         }
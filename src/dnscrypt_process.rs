@@ -0,0 +1,440 @@
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use anyhow::{anyhow, Context, Result};
+
+use crate::components::{self, Executable};
+use crate::dns_blocklists::{self, BLOCKED_NAMES_FILE};
+use crate::dns_rules::DnsRules;
+use crate::dns_type_filter::BlockedType;
+use crate::dnscrypt::DnsCryptServer;
+use crate::logger::{LogLevel, Logger};
+use crate::resolver_list::{LIST_MINISIGN_KEY, PUBLIC_RESOLVERS_URLS};
+use crate::services::{ManagedProcess, ProcessLauncher};
+use crate::supervisor::{ProcessSpec, ProcessState};
+use crate::utils;
+
+// 下载服务器列表与检测网络时默认使用的普通DNS
+pub const DEFAULT_BOOTSTRAP_RESOLVERS: [&str; 2] = ["9.9.9.11:53", "1.1.1.1:53"];
+
+// 伪装规则与白名单文件名
+const CLOAKING_FILE: &str = "cloaking-rules.txt";
+const ALLOWED_NAMES_FILE: &str = "allowed-names.txt";
+const FORWARDING_FILE: &str = "forwarding-rules.txt";
+
+// 支持enable_hot_reload的最低版本，更早的版本遇到未知配置项会拒绝启动
+const HOT_RELOAD_SINCE: [u32; 3] = [2, 1, 8];
+
+// dnscrypt-proxy运行状态
+#[derive(Clone, Debug, PartialEq)]
+pub enum DnsCryptState {
+    Starting,
+    Ready { live_servers: usize },
+    Restarting { attempt: u32, delay: Duration },
+    Stopped, // 已按要求停止
+    Failed(String),
+}
+
+// 本地缓存设置
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct CacheSettings {
+    pub enabled: bool,
+    pub size: u32,    // 最多缓存的记录数
+    pub min_ttl: u32, // 秒，较短的TTL会被延长
+    pub max_ttl: u32, // 秒，较长的TTL会被缩短
+}
+
+impl Default for CacheSettings {
+    // 与dnscrypt-proxy示例配置相同
+    fn default() -> Self {
+        Self { enabled: true, size: 4096, min_ttl: 2400, max_ttl: 86400 }
+    }
+}
+
+// 多个服务器之间的选择策略
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub enum LbStrategy {
+    #[default]
+    Fastest,        // 总是使用延迟最低的服务器
+    Random,         // 每次查询随机选择
+    FirstAvailable, // 按列表顺序使用第一个可用的服务器
+}
+
+impl LbStrategy {
+    pub const ALL: [LbStrategy; 3] = [LbStrategy::Fastest, LbStrategy::Random, LbStrategy::FirstAvailable];
+
+    pub fn label(&self) -> &'static str {
+        match self {
+            LbStrategy::Fastest => "最快",
+            LbStrategy::Random => "随机",
+            LbStrategy::FirstAvailable => "按顺序",
+        }
+    }
+
+    pub fn description(&self) -> &'static str {
+        match self {
+            LbStrategy::Fastest => "总是使用延迟最低的服务器，无响应的服务器会被自动跳过",
+            LbStrategy::Random => "每次查询随机选择一个服务器，分散查询记录",
+            LbStrategy::FirstAvailable => "按列表顺序只使用第一个可用的服务器，健康检查失败时切换到下一个",
+        }
+    }
+
+    // dnscrypt-proxy的lb_strategy；按顺序时只写入选中的一个服务器
+    fn config_value(&self) -> &'static str {
+        match self {
+            LbStrategy::Random => "random",
+            LbStrategy::Fastest | LbStrategy::FirstAvailable => "first",
+        }
+    }
+}
+
+// 生成dnscrypt-proxy.toml所需的设置
+#[derive(Clone, Debug)]
+pub struct DnsCryptSettings {
+    pub servers: Vec<DnsCryptServer>, // 启用的服务器
+    pub listen_port: u16,
+    pub extra_listen: Vec<String>, // 额外监听的IP:端口，如局域网地址
+    pub block_ipv6: bool,
+    pub rules: DnsRules,
+    pub query_log: bool, // 记录每次查询到query.log
+    pub cache: CacheSettings,
+    pub lb_strategy: LbStrategy,
+    pub blocked_types: Vec<BlockedType>, // 由程序内的过滤器拒绝，此处只写入说明
+    pub bootstrap_resolvers: Vec<String>, // IP:端口，第一个同时用于检测网络是否可用
+}
+
+// dnscrypt-proxy工作目录（配置文件与服务器列表缓存所在位置）
+pub fn dnscrypt_home() -> Result<PathBuf> {
+    let home = Path::new(&utils::get_app_data_dir()?).join("dnscrypt");
+    fs::create_dir_all(&home).context("Failed to create dnscrypt directory")?;
+    Ok(home)
+}
+
+// 查询日志文件路径
+pub fn query_log_path() -> Result<PathBuf> {
+    Ok(dnscrypt_home()?.join("query.log"))
+}
+
+// TOML字面量字符串（单引号，不支持转义）
+fn toml_literal(value: &str) -> Result<String> {
+    if value.contains(['\'', '\n', '\r']) {
+        return Err(anyhow!("配置值不能包含单引号或换行: {}", value));
+    }
+    Ok(format!("'{}'", value))
+}
+
+fn toml_array(values: &[&str]) -> Result<String> {
+    let items: Result<Vec<String>> = values.iter().map(|v| toml_literal(v)).collect();
+    Ok(format!("[{}]", items?.join(", ")))
+}
+
+// 调用dnscrypt-proxy -version读取版本号，如[2, 1, 5]（会阻塞调用线程）
+fn proxy_version(exe: &Path) -> Option<Vec<u32>> {
+    let mut command = Command::new(exe);
+    command.arg("-version");
+    #[cfg(target_os = "windows")]
+    {
+        use std::os::windows::process::CommandExt;
+        const CREATE_NO_WINDOW: u32 = 0x08000000;
+        command.creation_flags(CREATE_NO_WINDOW);
+    }
+    let output = command.output().ok()?;
+    let version = String::from_utf8_lossy(&output.stdout);
+    version.trim().split('.').map(|part| part.parse().ok()).collect()
+}
+
+// 已安装的版本是否支持规则文件的热重载
+fn supports_hot_reload(exe: &Path) -> bool {
+    proxy_version(exe).map_or(false, |version| version.as_slice() >= HOT_RELOAD_SINCE.as_slice())
+}
+
+// 生成dnscrypt-proxy.toml：有DNS Stamp的服务器与DoH服务器写入[static]，其余按名称从公共列表中选取
+fn generate_toml(settings: &DnsCryptSettings, home: &Path, hot_reload: bool) -> Result<String> {
+    let mut server_names = Vec::new();
+    let mut statics = Vec::new();
+    for server in &settings.servers {
+        let stamp = server.config_stamp().map_err(|e| anyhow!("服务器 '{}': {}", server.name, e))?;
+        if let Some(stamp) = stamp {
+            let name = format!("static-{}", server.id);
+            statics.push(format!("[static.{}]\nstamp = {}\n", toml_literal(&name)?, toml_literal(&stamp)?));
+            server_names.push(name);
+        } else if !server.resolver_name.trim().is_empty() {
+            server_names.push(server.resolver_name.trim().to_string());
+        }
+    }
+    if server_names.is_empty() {
+        return Err(anyhow!("没有可用的服务器，请至少启用一个填写了公共列表名称或DNS Stamp的服务器"));
+    }
+    let names: Vec<&str> = server_names.iter().map(String::as_str).collect();
+    for resolver in &settings.bootstrap_resolvers {
+        resolver.parse::<std::net::SocketAddr>().map_err(|_| anyhow!("引导DNS应为IP:端口: {}", resolver))?;
+    }
+    let bootstrap: Vec<&str> = settings.bootstrap_resolvers.iter().map(String::as_str).collect();
+    let netprobe = bootstrap.first().ok_or_else(|| anyhow!("请至少填写一个引导DNS"))?;
+    let local = format!("127.0.0.1:{}", settings.listen_port);
+    let mut listen = vec![local.as_str()];
+    listen.extend(settings.extra_listen.iter().map(String::as_str));
+    let cache_file = home.join("public-resolvers.md").display().to_string();
+
+    let mut lines = vec![
+        "# 由InviZible Pro自动生成，修改将在下次启动时被覆盖".to_string(),
+        format!("listen_addresses = {}", toml_array(&listen)?),
+        format!("server_names = {}", toml_array(&names)?),
+        format!("lb_strategy = {}", toml_literal(settings.lb_strategy.config_value())?),
+        "lb_estimator = true".to_string(),
+        "ipv4_servers = true".to_string(),
+        "ipv6_servers = false".to_string(),
+        "dnscrypt_servers = true".to_string(),
+        "doh_servers = true".to_string(),
+        "# block_ipv6: 为true时AAAA（IPv6地址）查询直接返回空结果，不发往上游，".to_string(),
+        "# 应用只能得到IPv4地址，可避免没有IPv6网络时的连接延迟和IPv6泄露".to_string(),
+        format!("block_ipv6 = {}", settings.block_ipv6),
+        format!("cache = {}", settings.cache.enabled),
+        format!("cache_size = {}", settings.cache.size),
+        format!("cache_min_ttl = {}", settings.cache.min_ttl),
+        format!("cache_max_ttl = {}", settings.cache.max_ttl.max(settings.cache.min_ttl)),
+        format!("bootstrap_resolvers = {}", toml_array(&bootstrap)?),
+        format!("netprobe_target = {}", toml_literal(netprobe)?),
+    ];
+    if hot_reload {
+        lines.push("# 规则文件（伪装、转发、屏蔽、白名单）修改后自动重新加载，无需重启".to_string());
+        lines.push("enable_hot_reload = true".to_string());
+    }
+    if !settings.blocked_types.is_empty() {
+        // dnscrypt-proxy不能按类型过滤，由监听原端口的过滤器拒绝后再转发到此处的内部端口
+        let types: Vec<&str> = settings.blocked_types.iter().map(BlockedType::label).collect();
+        lines.push(format!("# 拒绝的查询类型: {}（由InviZible Pro的查询类型过滤器处理，只监听内部端口）", types.join(", ")));
+    }
+    // 规则文件由write_rule_files写入工作目录，为空时也引用，以便运行中添加规则
+    let cloaking = home.join(CLOAKING_FILE).display().to_string();
+    lines.push(format!("cloaking_rules = {}", toml_literal(&cloaking)?));
+    let forwarding = home.join(FORWARDING_FILE).display().to_string();
+    lines.push(format!("forwarding_rules = {}", toml_literal(&forwarding)?));
+    lines.push(String::new());
+    if settings.query_log {
+        let path = home.join("query.log").display().to_string();
+        lines.push("[query_log]".to_string());
+        lines.push(format!("file = {}", toml_literal(&path)?));
+        lines.push("format = 'tsv'".to_string());
+        lines.push(String::new());
+    }
+    let blocked_names = home.join(BLOCKED_NAMES_FILE).display().to_string();
+    lines.push("[blocked_names]".to_string());
+    lines.push(format!("blocked_names_file = {}", toml_literal(&blocked_names)?));
+    lines.push(String::new());
+    let allowed_names = home.join(ALLOWED_NAMES_FILE).display().to_string();
+    lines.push("[allowed_names]".to_string());
+    lines.push(format!("allowed_names_file = {}", toml_literal(&allowed_names)?));
+    lines.push(String::new());
+    lines.extend([
+        "[sources.public-resolvers]".to_string(),
+        format!("urls = {}", toml_array(&PUBLIC_RESOLVERS_URLS)?),
+        format!("cache_file = {}", toml_literal(&cache_file)?),
+        format!("minisign_key = {}", toml_literal(LIST_MINISIGN_KEY)?),
+        "refresh_delay = 72".to_string(),
+        String::new(),
+    ]);
+    lines.extend(statics);
+    Ok(lines.join("\n") + "\n")
+}
+
+// 写入配置中引用的规则文件（规则为空时写入空文件）
+fn write_rule_files(rules: &DnsRules, home: &Path) -> Result<()> {
+    fs::write(home.join(CLOAKING_FILE), rules.cloaking_file()).context("Failed to write cloaking rules")?;
+    dns_blocklists::combine(home, &rules.blocklists, &rules.firewall_blocked)?;
+    fs::write(home.join(FORWARDING_FILE), rules.forwarding_file()).context("Failed to write forwarding rules")?;
+    fs::write(home.join(ALLOWED_NAMES_FILE), rules.allowlist_file()).context("Failed to write allowed names")?;
+    Ok(())
+}
+
+// 运行中更新规则文件，支持热重载的dnscrypt-proxy检测到文件变化后自动重新加载，否则在重启后生效
+pub fn reload_rules(rules: &DnsRules) -> Result<()> {
+    write_rule_files(rules, &dnscrypt_home()?)
+}
+
+// 解析dnscrypt-proxy日志行，如"[2024-01-01 12:00:00] [NOTICE] dnscrypt-proxy is ready - live servers: 3"
+fn parse_log_line(line: &str) -> (LogLevel, String) {
+    let line = line.trim();
+    // 跳过时间戳
+    let rest = match line.strip_prefix('[').and_then(|l| l.split_once("] ")) {
+        Some((_, rest)) => rest,
+        None => line,
+    };
+    let severity = rest.strip_prefix('[').and_then(|l| l.split_once("] "));
+    match severity {
+        Some(("FATAL" | "CRITICAL" | "ERROR", message)) => (LogLevel::Error, message.to_string()),
+        Some(("WARNING", message)) => (LogLevel::Warning, message.to_string()),
+        Some(("NOTICE" | "INFO", message)) => (LogLevel::Info, message.to_string()),
+        Some(("DEBUG", message)) => (LogLevel::Debug, message.to_string()),
+        _ => (LogLevel::Info, rest.to_string()),
+    }
+}
+
+// 运行中的dnscrypt-proxy.exe
+pub struct DnsCryptProcess {
+    process: Box<dyn ManagedProcess>,
+    state: Arc<Mutex<DnsCryptState>>,
+    hot_reload: bool, // 规则文件的修改是否无需重启即可生效
+    stop_requested: AtomicBool,
+}
+
+impl DnsCryptProcess {
+    // 查找已安装的dnscrypt-proxy.exe，生成配置后启动
+    pub fn start(launcher: &dyn ProcessLauncher, logger: Arc<Mutex<Logger>>, settings: &DnsCryptSettings) -> Result<Self> {
+        let exe = components::executable_path(Executable::DnsCryptProxy)
+            .ok_or_else(|| anyhow!("未找到dnscrypt-proxy.exe，请在 设置 → 组件管理 中安装dnscrypt-proxy"))?;
+        let home = dnscrypt_home()?;
+        let config = home.join("dnscrypt-proxy.toml");
+        write_rule_files(&settings.rules, &home)?;
+        let hot_reload = supports_hot_reload(&exe);
+        fs::write(&config, generate_toml(settings, &home, hot_reload)?).context("Failed to write dnscrypt-proxy.toml")?;
+
+        let state = Arc::new(Mutex::new(DnsCryptState::Starting));
+        let handler_state = Arc::clone(&state);
+        let handler_logger = Arc::clone(&logger);
+        let on_output = move |line: &str| {
+            if line.trim().is_empty() {
+                return;
+            }
+            let (level, message) = parse_log_line(line);
+            if let Ok(mut logger) = handler_logger.lock() {
+                logger.log(level, "DNSCrypt", &message);
+            }
+            if let Ok(mut state) = handler_state.lock() {
+                if let Some(count) = message.strip_prefix("dnscrypt-proxy is ready - live servers: ") {
+                    *state = DnsCryptState::Ready { live_servers: count.trim().parse().unwrap_or(0) };
+                } else if level == LogLevel::Error && !matches!(*state, DnsCryptState::Ready { .. }) {
+                    // 就绪后的错误（如单个服务器不可用）不影响整体状态
+                    *state = DnsCryptState::Failed(message);
+                }
+            }
+        };
+
+        let mut spec = ProcessSpec::new("DNSCrypt", &exe);
+        spec.args = vec!["-config".to_string(), config.display().to_string()];
+        spec.working_dir = Some(home);
+        spec.liveness_port = Some(settings.listen_port);
+        spec.on_output = Some(Arc::new(on_output));
+
+        if let Ok(mut logger) = logger.lock() {
+            logger.info("DNSCrypt", &format!("正在启动 {}", exe.display()));
+        }
+        Ok(Self {
+            process: launcher.launch(spec),
+            state,
+            hot_reload,
+            stop_requested: AtomicBool::new(false),
+        })
+    }
+
+    pub fn hot_reload(&self) -> bool {
+        self.hot_reload
+    }
+
+    // 当前状态（包含进程退出与自动重启），未要求停止时进程退出视为失败
+    pub fn state(&self) -> DnsCryptState {
+        match self.process.status().state {
+            ProcessState::Failed(reason) => DnsCryptState::Failed(format!("dnscrypt-proxy已退出: {}", reason)),
+            ProcessState::Stopped if self.stop_requested.load(Ordering::SeqCst) => DnsCryptState::Stopped,
+            ProcessState::Stopped => DnsCryptState::Failed("dnscrypt-proxy已退出".to_string()),
+            ProcessState::Restarting { attempt, delay } => {
+                // 重启后需要重新等待就绪
+                if let Ok(mut state) = self.state.lock() {
+                    *state = DnsCryptState::Starting;
+                }
+                DnsCryptState::Restarting { attempt, delay }
+            }
+            _ => self.state.lock().map(|s| s.clone()).unwrap_or(DnsCryptState::Starting),
+        }
+    }
+
+    pub fn stop(&self) {
+        self.stop_requested.store(true, Ordering::SeqCst);
+        self.process.stop();
+    }
+}
+
+impl Drop for DnsCryptProcess {
+    fn drop(&mut self) {
+        self.stop();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::services::mock::MockLauncher;
+
+    fn settings() -> DnsCryptSettings {
+        let mut server = DnsCryptServer::new(1, "Cloudflare", "", "");
+        server.resolver_name = "cloudflare".to_string();
+        DnsCryptSettings {
+            servers: vec![server],
+            listen_port: 5354,
+            extra_listen: Vec::new(),
+            block_ipv6: false,
+            rules: DnsRules::default(),
+            query_log: false,
+            cache: CacheSettings::default(),
+            lb_strategy: LbStrategy::default(),
+            blocked_types: Vec::new(),
+            bootstrap_resolvers: vec!["9.9.9.9:53".to_string()],
+        }
+    }
+
+    fn process(launcher: &MockLauncher) -> DnsCryptProcess {
+        DnsCryptProcess {
+            process: launcher.launch(ProcessSpec::new("DNSCrypt", "dnscrypt-proxy.exe")),
+            state: Arc::new(Mutex::new(DnsCryptState::Starting)),
+            hot_reload: false,
+            stop_requested: AtomicBool::new(false),
+        }
+    }
+
+    #[test]
+    fn hot_reload_only_when_supported() {
+        let home = Path::new("dnscrypt");
+        let toml = generate_toml(&settings(), home, false).unwrap();
+        assert!(toml.contains("server_names = ['cloudflare']"));
+        assert!(toml.contains("listen_addresses = ['127.0.0.1:5354']"));
+        assert!(!toml.contains("enable_hot_reload"));
+
+        let toml = generate_toml(&settings(), home, true).unwrap();
+        assert!(toml.contains("enable_hot_reload = true"));
+    }
+
+    #[test]
+    fn rejects_missing_servers_and_bad_bootstrap() {
+        let home = Path::new("dnscrypt");
+        let mut no_servers = settings();
+        no_servers.servers.clear();
+        assert!(generate_toml(&no_servers, home, false).is_err());
+
+        let mut bad_bootstrap = settings();
+        bad_bootstrap.bootstrap_resolvers = vec!["9.9.9.9".to_string()];
+        assert!(generate_toml(&bad_bootstrap, home, false).is_err());
+    }
+
+    #[test]
+    fn state_follows_process() {
+        let launcher = MockLauncher::new(Arc::new(Mutex::new(Logger::new())));
+        let dnscrypt = process(&launcher);
+        assert_eq!(dnscrypt.state(), DnsCryptState::Starting);
+        dnscrypt.stop();
+        assert_eq!(dnscrypt.state(), DnsCryptState::Stopped);
+
+        let launcher = MockLauncher::failing(Arc::new(Mutex::new(Logger::new())), "exit code 1");
+        assert_eq!(process(&launcher).state(), DnsCryptState::Failed("dnscrypt-proxy已退出: exit code 1".to_string()));
+    }
+
+    #[test]
+    fn parses_log_levels() {
+        let (level, message) = parse_log_line("[2024-01-01 12:00:00] [NOTICE] dnscrypt-proxy is ready - live servers: 3");
+        assert_eq!(level, LogLevel::Info);
+        assert_eq!(message, "dnscrypt-proxy is ready - live servers: 3");
+        assert_eq!(parse_log_line("[2024-01-01 12:00:00] [FATAL] no servers").0, LogLevel::Error);
+    }
+}
@@ -0,0 +1,87 @@
+use std::net::{SocketAddr, UdpSocket};
+use std::time::Duration;
+use anyhow::{anyhow, Context, Result};
+
+// 签名正确的测试域名，验证DNSSEC的解析器应正常返回
+const SIGNED_DOMAINS: [&str; 2] = ["sigok.verteiltesysteme.net", "isc.org"];
+// 签名故意损坏的测试域名，验证DNSSEC的解析器应返回SERVFAIL
+const BROKEN_DOMAINS: [&str; 2] = ["sigfail.verteiltesysteme.net", "dnssec-failed.org"];
+
+const RCODE_NOERROR: u8 = 0;
+const RCODE_SERVFAIL: u8 = 2;
+
+// DNSSEC测试结果
+#[derive(Clone, Debug, PartialEq)]
+pub enum DnssecResult {
+    Validating,           // 正确签名可解析，损坏签名被拒绝
+    NotValidating(String), // 损坏签名的域名也能解析
+    Error(String),
+}
+
+// DNS响应中需要的部分
+struct Reply {
+    rcode: u8,
+    answers: u16,
+}
+
+// 构造A记录查询（设置RD位）
+fn build_query(id: u16, name: &str) -> Result<Vec<u8>> {
+    let mut packet = Vec::with_capacity(32 + name.len());
+    packet.extend_from_slice(&id.to_be_bytes());
+    packet.extend_from_slice(&[0x01, 0x00]); // RD
+    packet.extend_from_slice(&[0, 1, 0, 0, 0, 0, 0, 0]); // 1个问题
+    for label in name.trim_end_matches('.').split('.') {
+        let len = u8::try_from(label.len()).ok().filter(|&len| len > 0 && len < 64)
+            .ok_or_else(|| anyhow!("Invalid domain name: {}", name))?;
+        packet.push(len);
+        packet.extend_from_slice(label.as_bytes());
+    }
+    packet.push(0);
+    packet.extend_from_slice(&[0, 1, 0, 1]); // A, IN
+    Ok(packet)
+}
+
+fn query(server: SocketAddr, name: &str) -> Result<Reply> {
+    let socket = UdpSocket::bind("127.0.0.1:0").context("Failed to bind UDP socket")?;
+    socket.set_read_timeout(Some(Duration::from_secs(5)))?;
+    let id = rand::random::<u16>();
+    socket.send_to(&build_query(id, name)?, server).context("Failed to send DNS query")?;
+
+    let mut buffer = [0u8; 1500];
+    loop {
+        let (len, from) = socket.recv_from(&mut buffer).with_context(|| format!("No answer for {}", name))?;
+        // 忽略其他来源或ID不符的数据包
+        if from != server || len < 12 || u16::from_be_bytes([buffer[0], buffer[1]]) != id {
+            continue;
+        }
+        return Ok(Reply {
+            rcode: buffer[3] & 0x0f,
+            answers: u16::from_be_bytes([buffer[6], buffer[7]]),
+        });
+    }
+}
+
+// 通过本地解析器查询测试域名，判断是否验证DNSSEC（会阻塞数秒）
+pub fn check(port: u16) -> DnssecResult {
+    let server = SocketAddr::from(([127, 0, 0, 1], port));
+
+    // 先确认解析器可用，否则无法区分"拒绝"与"不可用"
+    let signed_ok = SIGNED_DOMAINS.iter().any(|name| {
+        matches!(query(server, name), Ok(reply) if reply.rcode == RCODE_NOERROR && reply.answers > 0)
+    });
+    if !signed_ok {
+        return DnssecResult::Error("无法解析签名正确的测试域名，请确认DNSCrypt已连接".to_string());
+    }
+
+    for name in BROKEN_DOMAINS {
+        match query(server, name) {
+            Ok(reply) if reply.rcode == RCODE_SERVFAIL => {}
+            Ok(reply) if reply.rcode == RCODE_NOERROR && reply.answers > 0 => {
+                return DnssecResult::NotValidating(format!("签名损坏的 {} 也被解析", name));
+            }
+            Ok(reply) => return DnssecResult::Error(format!("{} 返回了意外的响应码 {}", name, reply.rcode)),
+            Err(e) => return DnssecResult::Error(format!("{:#}", e)),
+        }
+    }
+    DnssecResult::Validating
+}
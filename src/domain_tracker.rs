@@ -0,0 +1,236 @@
+use std::collections::{BTreeSet, HashMap};
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr, UdpSocket};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::mpsc::{self, Receiver, SyncSender};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+use anyhow::{anyhow, Context, Result};
+
+use crate::dns_rules;
+
+// 同一域名重新解析的间隔，CDN等服务的地址会变化
+const REFRESH_INTERVAL: Duration = Duration::from_secs(600);
+// 每个域名最多记录的地址数，超过时丢弃最早记录的
+const MAX_ADDRESSES: usize = 64;
+// 等待解析的域名数上限，超过时丢弃，下次出现在查询日志中时再解析
+const MAX_QUEUED: usize = 256;
+const QUERY_TIMEOUT: Duration = Duration::from_secs(5);
+
+const TYPE_A: u16 = 1;
+const TYPE_AAAA: u16 = 28;
+
+// 构造查询（设置RD位）
+fn build_query(id: u16, name: &str, qtype: u16) -> Result<Vec<u8>> {
+    let mut packet = Vec::with_capacity(32 + name.len());
+    packet.extend_from_slice(&id.to_be_bytes());
+    packet.extend_from_slice(&[0x01, 0x00]); // RD
+    packet.extend_from_slice(&[0, 1, 0, 0, 0, 0, 0, 0]); // 1个问题
+    for label in name.split('.') {
+        let len = u8::try_from(label.len()).ok().filter(|&len| len > 0 && len < 64)
+            .ok_or_else(|| anyhow!("Invalid domain name: {}", name))?;
+        packet.push(len);
+        packet.extend_from_slice(label.as_bytes());
+    }
+    packet.push(0);
+    packet.extend_from_slice(&qtype.to_be_bytes());
+    packet.extend_from_slice(&[0, 1]); // IN
+    Ok(packet)
+}
+
+// 跳过消息中pos处的域名（可能以压缩指针结尾），返回其后的位置
+fn skip_name(message: &[u8], mut pos: usize) -> Option<usize> {
+    loop {
+        let len = *message.get(pos)?;
+        match len {
+            0 => return Some(pos + 1),
+            len if len & 0xc0 == 0xc0 => return Some(pos + 2),
+            len => pos += len as usize + 1,
+        }
+    }
+}
+
+// 读取响应中A/AAAA记录的地址
+fn parse_addresses(message: &[u8]) -> Option<Vec<IpAddr>> {
+    let questions = u16::from_be_bytes([*message.get(4)?, *message.get(5)?]);
+    let answers = u16::from_be_bytes([*message.get(6)?, *message.get(7)?]);
+    let mut pos = 12;
+    for _ in 0..questions {
+        pos = skip_name(message, pos)? + 4;
+    }
+    let mut addresses = Vec::new();
+    for _ in 0..answers {
+        pos = skip_name(message, pos)?;
+        let header = message.get(pos..pos + 10)?;
+        let rtype = u16::from_be_bytes([header[0], header[1]]);
+        let rdlength = u16::from_be_bytes([header[8], header[9]]) as usize;
+        let data = message.get(pos + 10..pos + 10 + rdlength)?;
+        match (rtype, data.len()) {
+            (TYPE_A, 4) => addresses.push(IpAddr::V4(Ipv4Addr::new(data[0], data[1], data[2], data[3]))),
+            (TYPE_AAAA, 16) => {
+                let mut octets = [0u8; 16];
+                octets.copy_from_slice(data);
+                addresses.push(IpAddr::V6(Ipv6Addr::from(octets)));
+            }
+            _ => {} // CNAME等
+        }
+        pos += 10 + rdlength;
+    }
+    Some(addresses)
+}
+
+// 通过本地解析器查询域名的A与AAAA记录
+fn resolve(server: SocketAddr, name: &str) -> Result<Vec<IpAddr>> {
+    let socket = UdpSocket::bind("127.0.0.1:0").context("Failed to bind UDP socket")?;
+    socket.set_read_timeout(Some(QUERY_TIMEOUT))?;
+    let mut addresses = Vec::new();
+    for qtype in [TYPE_A, TYPE_AAAA] {
+        let id = rand::random::<u16>();
+        socket.send_to(&build_query(id, name, qtype)?, server).context("Failed to send DNS query")?;
+        let mut buffer = [0u8; 4096];
+        loop {
+            let (len, from) = socket.recv_from(&mut buffer).with_context(|| format!("No answer for {}", name))?;
+            // 忽略其他来源或ID不符的数据包
+            if from != server || len < 12 || u16::from_be_bytes([buffer[0], buffer[1]]) != id {
+                continue;
+            }
+            addresses.extend(parse_addresses(&buffer[..len]).unwrap_or_default());
+            break;
+        }
+    }
+    Ok(addresses)
+}
+
+// 解析线程：依次解析队列中的域名，把新地址合并到记录中
+fn run(jobs: Receiver<(String, SocketAddr)>, addresses: Arc<Mutex<HashMap<String, Vec<IpAddr>>>>, changed: Arc<AtomicBool>) {
+    while let Ok((name, server)) = jobs.recv() {
+        // 0.0.0.0等地址不能用于过滤
+        let resolved: Vec<IpAddr> = match resolve(server, &name) {
+            Ok(addresses) => addresses.into_iter().filter(|ip| !ip.is_unspecified() && !ip.is_loopback()).collect(),
+            Err(_) => continue,
+        };
+        if let Ok(mut addresses) = addresses.lock() {
+            let known = addresses.entry(name).or_default();
+            let mut added = false;
+            for ip in resolved {
+                if !known.contains(&ip) {
+                    known.push(ip);
+                    added = true;
+                }
+            }
+            if known.len() > MAX_ADDRESSES {
+                let excess = known.len() - MAX_ADDRESSES;
+                known.drain(..excess);
+            }
+            if added {
+                changed.store(true, Ordering::SeqCst);
+            }
+        }
+    }
+}
+
+// 记录防火墙域名规则涉及的域名解析到的IP地址，供数据包层面的过滤使用。
+// 只通过本机DNSCrypt的监听地址解析，得到的就是应用程序实际收到的地址，不直接联系第三方服务器；
+// DNSCrypt未运行时不解析。被DNSCrypt拦截的域名只会得到0.0.0.0，由DNS层面阻止。
+// 地址只增不减（直到超过上限），避免连接仍在使用旧地址时规则失效
+pub struct DomainTracker {
+    addresses: Arc<Mutex<HashMap<String, Vec<IpAddr>>>>,
+    requested: HashMap<String, Instant>, // 各域名上次提交解析的时间
+    changed: Arc<AtomicBool>,
+    resolver: Option<SocketAddr>, // DNSCrypt的本地监听地址
+    jobs: SyncSender<(String, SocketAddr)>,
+}
+
+impl DomainTracker {
+    pub fn new() -> Self {
+        let addresses = Arc::new(Mutex::new(HashMap::new()));
+        let changed = Arc::new(AtomicBool::new(false));
+        let (jobs, receiver) = mpsc::sync_channel(MAX_QUEUED);
+        std::thread::spawn({
+            let addresses = Arc::clone(&addresses);
+            let changed = Arc::clone(&changed);
+            move || run(receiver, addresses, changed)
+        });
+        Self {
+            addresses,
+            requested: HashMap::new(),
+            changed,
+            resolver: None,
+            jobs,
+        }
+    }
+
+    // 设置用于解析的本地DNS监听地址，None表示DNSCrypt未运行
+    pub fn set_resolver(&mut self, resolver: Option<SocketAddr>) {
+        self.resolver = resolver;
+    }
+
+    pub fn has_resolver(&self) -> bool {
+        self.resolver.is_some()
+    }
+
+    // 把域名加入解析队列，距离上次解析不足REFRESH_INTERVAL时跳过
+    pub fn observe(&mut self, name: &str) {
+        let server = match self.resolver {
+            Some(server) => server,
+            None => return,
+        };
+        let name = name.trim_end_matches('.').to_lowercase();
+        if name.is_empty() || self.requested.get(&name).map_or(false, |t| t.elapsed() < REFRESH_INTERVAL) {
+            return;
+        }
+        // 队列已满时不记录，下次出现时再提交
+        if self.jobs.try_send((name.clone(), server)).is_ok() {
+            self.requested.insert(name, Instant::now());
+        }
+    }
+
+    // 上次调用后是否解析到了新地址
+    pub fn take_changed(&self) -> bool {
+        self.changed.swap(false, Ordering::SeqCst)
+    }
+
+    // 匹配规则的所有域名解析到的地址，已去重并排序
+    pub fn addresses_for(&self, pattern: &str) -> Vec<IpAddr> {
+        let addresses = match self.addresses.lock() {
+            Ok(addresses) => addresses,
+            Err(_) => return Vec::new(),
+        };
+        addresses.iter()
+            .filter(|(name, _)| dns_rules::pattern_matches(pattern, name))
+            .flat_map(|(_, ips)| ips.iter().copied())
+            .collect::<BTreeSet<_>>()
+            .into_iter()
+            .collect()
+    }
+}
+
+// 规则本身指定的域名，不含通配符时可在出现查询记录之前预先解析
+pub fn base_name(pattern: &str) -> Option<&str> {
+    let name = pattern.strip_prefix('=').unwrap_or(pattern);
+    if name.contains('*') {
+        None
+    } else {
+        Some(name)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_addresses_after_cname() {
+        let mut reply = build_query(1, "www.example.com", TYPE_A).unwrap();
+        reply[2] = 0x81;
+        reply[3] = 0x80;
+        reply[7] = 2; // 2条回答
+        // www.example.com CNAME example.com（名称使用指向问题的压缩指针）
+        reply.extend_from_slice(&[0xc0, 12, 0, 5, 0, 1, 0, 0, 0, 60, 0, 2, 0xc0, 16]);
+        // example.com A 93.184.216.34
+        reply.extend_from_slice(&[0xc0, 16, 0, 1, 0, 1, 0, 0, 0, 60, 0, 4, 93, 184, 216, 34]);
+        assert_eq!(parse_addresses(&reply), Some(vec!["93.184.216.34".parse::<IpAddr>().unwrap()]));
+
+        // 截断的响应
+        assert_eq!(parse_addresses(&reply[..reply.len() - 2]), None);
+    }
+}
@@ -0,0 +1,387 @@
+use std::net::{SocketAddr, TcpListener, TcpStream, UdpSocket};
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::{Duration, Instant};
+use anyhow::{anyhow, Context, Result};
+use base64::{engine::general_purpose, Engine as _};
+use native_tls::TlsConnector;
+use rand::seq::SliceRandom;
+use sha2::{Digest, Sha256};
+
+use crate::dns_type_filter::{self, BlockedType, WorkerLimit, RCODE_NOERROR, RCODE_REFUSED};
+use crate::dnscrypt_process::{DnsCryptState, LbStrategy};
+use crate::logger::Logger;
+
+// DoT默认端口
+const DOT_PORT: u16 = 853;
+// 连接与读写上游的超时
+const UPSTREAM_TIMEOUT: Duration = Duration::from_secs(5);
+
+// DoT上游服务器
+#[derive(Clone, Debug)]
+pub struct DotUpstream {
+    pub name: String,
+    pub address: SocketAddr,
+    pub hostname: String,         // 用于SNI与证书校验的主机名
+    pub spki_pin: Option<Vec<u8>>, // 证书公钥（SubjectPublicKeyInfo）的SHA-256
+}
+
+impl DotUpstream {
+    // 地址为"IP"或"IP:端口"，固定值为Base64编码的SHA-256（与stubby等工具的pin-sha256相同）
+    pub fn new(name: &str, address: &str, hostname: &str, spki_pin: &str) -> Result<Self, String> {
+        let address = address.trim();
+        let address = address.parse::<SocketAddr>()
+            .or_else(|_| address.trim_start_matches('[').trim_end_matches(']').parse::<std::net::IpAddr>().map(|ip| SocketAddr::new(ip, DOT_PORT)))
+            .map_err(|_| format!("DoT服务器地址无效: {}", address))?;
+        let hostname = hostname.trim();
+        if hostname.is_empty() {
+            return Err("请填写DoT服务器的TLS主机名".to_string());
+        }
+        let spki_pin = match spki_pin.trim() {
+            "" => None,
+            pin => {
+                let bytes = general_purpose::STANDARD
+                    .decode(pin.strip_prefix("pin-sha256=").unwrap_or(pin).trim_matches('"'))
+                    .map_err(|_| "SPKI指纹应为Base64编码".to_string())?;
+                if bytes.len() != 32 {
+                    return Err("SPKI指纹应为32字节的SHA-256".to_string());
+                }
+                Some(bytes)
+            }
+        };
+        Ok(Self { name: name.to_string(), address, hostname: hostname.to_string(), spki_pin })
+    }
+}
+
+// 读取一个DER元素，返回(整个元素, 内容, 剩余数据)
+fn der_element(data: &[u8]) -> Option<(&[u8], &[u8], &[u8])> {
+    let first_len = *data.get(1)?;
+    let (len, header) = if first_len & 0x80 == 0 {
+        (first_len as usize, 2)
+    } else {
+        let count = (first_len & 0x7f) as usize;
+        if count == 0 || count > 4 {
+            return None;
+        }
+        let len = data.get(2..2 + count)?.iter().fold(0usize, |acc, &b| acc << 8 | b as usize);
+        (len, 2 + count)
+    };
+    let element = data.get(..header + len)?;
+    Some((element, &element[header..], &data[header + len..]))
+}
+
+// 从DER证书中取出SubjectPublicKeyInfo
+fn certificate_spki(cert: &[u8]) -> Option<&[u8]> {
+    let (_, certificate, _) = der_element(cert)?;
+    let (_, tbs, _) = der_element(certificate)?;
+    let mut rest = tbs;
+    // 可选的版本字段[0]
+    if rest.first() == Some(&0xa0) {
+        rest = der_element(rest)?.2;
+    }
+    // 依次跳过序列号、签名算法、颁发者、有效期、使用者
+    for _ in 0..5 {
+        rest = der_element(rest)?.2;
+    }
+    der_element(rest).map(|(spki, _, _)| spki)
+}
+
+// DNS查询类型AAAA
+const QTYPE_AAAA: u16 = 28;
+
+// 查询失败的上游记为此延迟，排到其他上游之后
+const FAILED_RTT_MS: u64 = 60_000;
+
+// 同时处理的UDP查询与TCP客户端连接数上限，超过时丢弃新的查询或连接（客户端会重试）
+const MAX_WORKERS: usize = 64;
+// 每个上游保留的空闲TLS连接数
+const MAX_IDLE_PER_UPSTREAM: usize = 4;
+// 本地TCP客户端两次查询之间的最长等待
+const CLIENT_TIMEOUT: Duration = Duration::from_secs(10);
+
+type TlsStream = native_tls::TlsStream<TcpStream>;
+
+// 按策略决定本次查询尝试上游的顺序
+fn upstream_order(strategy: LbStrategy, rtts: &[AtomicU64]) -> Vec<usize> {
+    let mut order: Vec<usize> = (0..rtts.len()).collect();
+    match strategy {
+        // 未测得延迟（0）的上游会先被尝试一次
+        LbStrategy::Fastest => order.sort_by_key(|&i| rtts[i].load(Ordering::Relaxed)),
+        LbStrategy::Random => order.shuffle(&mut rand::thread_rng()),
+        LbStrategy::FirstAvailable => {}
+    }
+    order
+}
+
+// 建立到上游的TLS连接并校验证书公钥
+fn connect_upstream(upstream: &DotUpstream) -> Result<TlsStream> {
+    let tcp = TcpStream::connect_timeout(&upstream.address, UPSTREAM_TIMEOUT)
+        .with_context(|| format!("Failed to connect to {}", upstream.address))?;
+    tcp.set_read_timeout(Some(UPSTREAM_TIMEOUT))?;
+    tcp.set_write_timeout(Some(UPSTREAM_TIMEOUT))?;
+    let connector = TlsConnector::new().context("Failed to create TLS connector")?;
+    let stream = connector.connect(&upstream.hostname, tcp)
+        .map_err(|e| anyhow!("TLS handshake with {} failed: {}", upstream.hostname, e))?;
+
+    if let Some(pin) = &upstream.spki_pin {
+        let cert = stream.peer_certificate()?.ok_or_else(|| anyhow!("Server sent no certificate"))?;
+        let der = cert.to_der()?;
+        let spki = certificate_spki(&der).ok_or_else(|| anyhow!("Failed to parse server certificate"))?;
+        if Sha256::digest(spki).as_slice() != pin.as_slice() {
+            return Err(anyhow!("SPKI pin mismatch for {}", upstream.hostname));
+        }
+    }
+    Ok(stream)
+}
+
+// 在一个TLS连接上发送一次查询（DNS over TCP格式）
+fn exchange(stream: &mut TlsStream, query: &[u8]) -> Result<Vec<u8>> {
+    dns_type_filter::write_message(stream, query)?;
+    dns_type_filter::read_message(stream)
+}
+
+// 上游列表、各上游最近一次查询的耗时（毫秒）与空闲的TLS连接
+struct UpstreamSet {
+    upstreams: Vec<DotUpstream>,
+    rtts: Vec<AtomicU64>,
+    idle: Vec<Mutex<Vec<TlsStream>>>,
+}
+
+impl UpstreamSet {
+    fn new(upstreams: Vec<DotUpstream>) -> Arc<Self> {
+        let rtts = upstreams.iter().map(|_| AtomicU64::new(0)).collect();
+        let idle = upstreams.iter().map(|_| Mutex::new(Vec::new())).collect();
+        Arc::new(Self { upstreams, rtts, idle })
+    }
+
+    // 向一个上游发送查询，优先复用空闲连接。空闲连接可能已被服务器关闭，失败时改用新连接
+    fn query(&self, index: usize, query: &[u8]) -> Result<Vec<u8>> {
+        let pooled = self.idle[index].lock().ok().and_then(|mut idle| idle.pop());
+        let reused = pooled.and_then(|mut stream| exchange(&mut stream, query).ok().map(|response| (stream, response)));
+        let (stream, response) = match reused {
+            Some(reused) => reused,
+            None => {
+                let mut stream = connect_upstream(&self.upstreams[index])?;
+                let response = exchange(&mut stream, query)?;
+                (stream, response)
+            }
+        };
+        if let Ok(mut idle) = self.idle[index].lock() {
+            if idle.len() < MAX_IDLE_PER_UPSTREAM {
+                idle.push(stream);
+            }
+        }
+        Ok(response)
+    }
+}
+
+// UDP与TCP监听共用的查询处理
+#[derive(Clone)]
+struct Resolver {
+    upstreams: Arc<Mutex<Arc<UpstreamSet>>>, // 可在运行中替换，已开始的查询继续使用旧列表
+    strategy: LbStrategy,
+    block_ipv6: bool,
+    blocked_codes: Arc<Vec<u16>>,
+    failures: Arc<AtomicU64>,
+    logger: Arc<Mutex<Logger>>,
+}
+
+impl Resolver {
+    // 不需要转发的查询：block_ipv6时AAAA返回空结果，被拒绝的查询类型返回REFUSED
+    fn local_reply(&self, query: &[u8]) -> Option<Vec<u8>> {
+        let qtype = if query.len() >= 12 { dns_type_filter::query_type(query) } else { None };
+        if self.block_ipv6 && qtype == Some(QTYPE_AAAA) {
+            return Some(dns_type_filter::reply(query, RCODE_NOERROR));
+        }
+        if qtype.map_or(false, |qtype| self.blocked_codes.contains(&qtype)) {
+            return Some(dns_type_filter::reply(query, RCODE_REFUSED));
+        }
+        None
+    }
+
+    // 按策略的顺序尝试上游直到成功，全部失败时返回None
+    fn resolve(&self, query: &[u8]) -> Option<Vec<u8>> {
+        let set = match self.upstreams.lock() {
+            Ok(set) => Arc::clone(&set),
+            Err(_) => return None,
+        };
+        let mut last_error = None;
+        for index in upstream_order(self.strategy, &set.rtts) {
+            let start = Instant::now();
+            match set.query(index, query) {
+                Ok(response) => {
+                    set.rtts[index].store(start.elapsed().as_millis().max(1) as u64, Ordering::Relaxed);
+                    return Some(response);
+                }
+                Err(e) => {
+                    set.rtts[index].store(FAILED_RTT_MS, Ordering::Relaxed);
+                    last_error = Some(format!("{}: {:#}", set.upstreams[index].name, e));
+                }
+            }
+        }
+        self.failures.fetch_add(1, Ordering::Relaxed);
+        if let (Some(error), Ok(mut logger)) = (last_error, self.logger.lock()) {
+            logger.warning("DNSCrypt", &format!("DoT查询失败: {}", error));
+        }
+        None
+    }
+
+    // 逐个处理一个TCP客户端连接中的查询
+    fn serve_tcp(&self, mut client: TcpStream) -> Result<()> {
+        client.set_nonblocking(false)?;
+        client.set_read_timeout(Some(CLIENT_TIMEOUT))?;
+        client.set_write_timeout(Some(UPSTREAM_TIMEOUT))?;
+        loop {
+            let query = dns_type_filter::read_message(&mut client)?;
+            let response = match self.local_reply(&query).or_else(|| self.resolve(&query)) {
+                Some(response) => response,
+                None => return Ok(()), // 关闭连接，客户端会重试
+            };
+            dns_type_filter::write_message(&mut client, &response)?;
+        }
+    }
+}
+
+// 本地UDP与TCP监听，把查询通过DoT转发给上游（dnscrypt-proxy不支持DoT上游）
+pub struct DotForwarder {
+    running: Arc<AtomicBool>,
+    upstreams: Arc<Mutex<Arc<UpstreamSet>>>,
+    failures: Arc<AtomicU64>,
+    logger: Arc<Mutex<Logger>>,
+}
+
+impl DotForwarder {
+    // block_ipv6为true时AAAA查询直接返回空结果
+    pub fn start(
+        logger: Arc<Mutex<Logger>>,
+        upstreams: Vec<DotUpstream>,
+        listen_port: u16,
+        block_ipv6: bool,
+        strategy: LbStrategy,
+        blocked_types: &[BlockedType],
+    ) -> Result<Self> {
+        if upstreams.is_empty() {
+            return Err(anyhow!("没有启用的DoT服务器"));
+        }
+        let socket = UdpSocket::bind(("127.0.0.1", listen_port))
+            .with_context(|| format!("Failed to bind 127.0.0.1:{}", listen_port))?;
+        socket.set_read_timeout(Some(Duration::from_millis(500)))?;
+        let listener = TcpListener::bind(("127.0.0.1", listen_port))
+            .with_context(|| format!("Failed to bind TCP 127.0.0.1:{}", listen_port))?;
+        listener.set_nonblocking(true)?;
+
+        if let Ok(mut logger) = logger.lock() {
+            let names: Vec<&str> = upstreams.iter().map(|u| u.name.as_str()).collect();
+            logger.info("DNSCrypt", &format!("DoT转发已在 127.0.0.1:{} 上启动，上游: {}", listen_port, names.join(", ")));
+        }
+
+        let running = Arc::new(AtomicBool::new(true));
+        let resolver = Resolver {
+            upstreams: Arc::new(Mutex::new(UpstreamSet::new(upstreams))),
+            strategy,
+            block_ipv6,
+            blocked_codes: Arc::new(blocked_types.iter().map(BlockedType::code).collect()),
+            failures: Arc::new(AtomicU64::new(0)),
+            logger: Arc::clone(&logger),
+        };
+        let workers = WorkerLimit::new(MAX_WORKERS);
+        spawn_udp(socket, resolver.clone(), workers.clone(), Arc::clone(&running));
+        spawn_tcp(listener, resolver.clone(), workers, Arc::clone(&running));
+
+        Ok(Self { running, upstreams: resolver.upstreams, failures: resolver.failures, logger })
+    }
+
+    // 运行中替换上游，不需要重新监听，之后的查询使用新列表
+    pub fn set_upstreams(&self, upstreams: Vec<DotUpstream>) -> Result<()> {
+        if upstreams.is_empty() {
+            return Err(anyhow!("没有启用的DoT服务器"));
+        }
+        let names: Vec<String> = upstreams.iter().map(|u| u.name.clone()).collect();
+        if let Ok(mut set) = self.upstreams.lock() {
+            *set = UpstreamSet::new(upstreams);
+        }
+        if let Ok(mut logger) = self.logger.lock() {
+            logger.info("DNSCrypt", &format!("DoT转发的上游已更新: {}", names.join(", ")));
+        }
+        Ok(())
+    }
+
+    // 监听成功后即可使用，上游是否可用在每次查询时判断
+    pub fn state(&self) -> DnsCryptState {
+        let live_servers = self.upstreams.lock().map(|set| set.upstreams.len()).unwrap_or(0);
+        DnsCryptState::Ready { live_servers }
+    }
+
+    // 所有上游都失败的查询数
+    pub fn failures(&self) -> u64 {
+        self.failures.load(Ordering::Relaxed)
+    }
+
+    pub fn stop(&self) {
+        self.running.store(false, Ordering::SeqCst);
+    }
+}
+
+impl Drop for DotForwarder {
+    fn drop(&mut self) {
+        self.stop();
+    }
+}
+
+// 每个UDP查询占用一个处理线程，本地即可回答的查询直接回复
+fn spawn_udp(socket: UdpSocket, resolver: Resolver, workers: WorkerLimit, running: Arc<AtomicBool>) {
+    thread::spawn(move || {
+        let mut buffer = [0u8; 4096];
+        while running.load(Ordering::SeqCst) {
+            let (len, client) = match socket.recv_from(&mut buffer) {
+                Ok(received) => received,
+                Err(_) => continue, // 超时，检查是否已停止
+            };
+            let query = buffer[..len].to_vec();
+            if let Some(response) = resolver.local_reply(&query) {
+                let _ = socket.send_to(&response, client);
+                continue;
+            }
+            let slot = match workers.acquire() {
+                Some(slot) => slot,
+                None => continue,
+            };
+            let reply_socket = match socket.try_clone() {
+                Ok(socket) => socket,
+                Err(_) => continue,
+            };
+            let resolver = resolver.clone();
+            thread::spawn(move || {
+                let _slot = slot;
+                if let Some(response) = resolver.resolve(&query) {
+                    let _ = reply_socket.send_to(&response, client);
+                }
+            });
+        }
+    });
+}
+
+// 每个TCP客户端连接占用一个处理线程
+fn spawn_tcp(listener: TcpListener, resolver: Resolver, workers: WorkerLimit, running: Arc<AtomicBool>) {
+    thread::spawn(move || {
+        while running.load(Ordering::SeqCst) {
+            let client = match listener.accept() {
+                Ok((client, _)) => client,
+                Err(_) => {
+                    thread::sleep(Duration::from_millis(100));
+                    continue;
+                }
+            };
+            let slot = match workers.acquire() {
+                Some(slot) => slot,
+                None => continue, // 关闭连接
+            };
+            let resolver = resolver.clone();
+            thread::spawn(move || {
+                let _slot = slot;
+                let _ = resolver.serve_tcp(client);
+            });
+        }
+    });
+}
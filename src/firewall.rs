@@ -1,10 +1,24 @@
 use eframe::egui::{self, Color32, RichText, Ui, Grid, ScrollArea};
 use std::sync::{Arc, Mutex};
-use std::collections::HashMap;
+use std::collections::{BTreeMap, BTreeSet, HashMap, HashSet, VecDeque};
+use std::net::{IpAddr, Ipv4Addr};
+use std::path::PathBuf;
+use std::time::{Duration, Instant};
+use anyhow::Result;
 use serde::{Deserialize, Serialize};
 
 use crate::logger::Logger;
-use crate::app::FIREWALL_COLOR;
+use crate::app::{render_admin_required, FIREWALL_COLOR};
+use crate::components::{self, Executable};
+use crate::dns_query_log::QueryEntry;
+use crate::dns_rules;
+use crate::domain_tracker::{self, DomainTracker};
+use crate::geoip;
+use crate::monitor::FlowStats;
+use crate::netfw;
+use crate::services;
+use crate::utils;
+use crate::wfp;
 
 // 防火墙规则类型
 #[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
@@ -12,6 +26,8 @@ pub enum RuleType {
     Application,
     Port,
     Address,
+    Domain,
+    Country,
 }
 
 // 防火墙规则动作
@@ -21,6 +37,52 @@ pub enum RuleAction {
     Block,
 }
 
+// 规则作用的连接方向
+#[derive(Clone, Copy, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub enum RuleDirection {
+    #[default]
+    Both,
+    Outbound,
+    Inbound,
+}
+
+impl RuleDirection {
+    const ALL: [RuleDirection; 3] = [Self::Both, Self::Outbound, Self::Inbound];
+
+    fn label(&self) -> &'static str {
+        match self {
+            Self::Both => "出站和入站",
+            Self::Outbound => "仅出站",
+            Self::Inbound => "仅入站",
+        }
+    }
+
+    // 是否作用于入站（true）或出站（false）连接
+    pub fn includes(&self, inbound: bool) -> bool {
+        match self {
+            Self::Both => true,
+            Self::Outbound => !inbound,
+            Self::Inbound => inbound,
+        }
+    }
+}
+
+// 防火墙配置方案
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub enum FirewallProfile {
+    Standard, // 标准：未匹配规则的连接默认允许
+    Strict,   // 严格：未被规则明确允许的连接一律阻止
+}
+
+impl FirewallProfile {
+    pub fn label(&self) -> &'static str {
+        match self {
+            FirewallProfile::Standard => "标准",
+            FirewallProfile::Strict => "严格",
+        }
+    }
+}
+
 // 防火墙规则结构
 #[derive(Clone, Debug, Serialize, Deserialize)]
 pub struct FirewallRule {
@@ -33,7 +95,17 @@ pub struct FirewallRule {
     pub port: Option<u16>,                 // 用于端口规则
     pub protocol: Option<String>,          // TCP/UDP
     pub address: Option<String>,           // 用于地址规则
+    #[serde(default)]
+    pub domain: Option<String>,            // 用于域名规则，格式同DNS规则
+    #[serde(default)]
+    pub countries: Vec<String>,            // 用于国家规则，国家代码（如"RU"）
+    #[serde(default)]
+    pub direction: RuleDirection,          // 规则作用的连接方向
     pub description: String,
+    #[serde(skip)]
+    pub temporary: bool,                   // 仅本次运行有效，不保存
+    #[serde(skip)]
+    pub resolved_addresses: Vec<IpAddr>,   // 域名规则匹配的域名已解析到的地址
 }
 
 impl FirewallRule {
@@ -48,11 +120,353 @@ impl FirewallRule {
             port: None,
             protocol: Some("TCP".to_string()),
             address: None,
+            domain: None,
+            countries: Vec::new(),
+            direction: RuleDirection::default(),
             description: String::new(),
+            temporary: false,
+            resolved_addresses: Vec::new(),
+        }
+    }
+    
+    // 规则是否匹配指定连接
+    pub fn matches(&self, connection: &Connection) -> bool {
+        self.matcher().map_or(false, |matcher| matcher.matches(connection))
+    }
+    
+    // 解析规则的匹配条件，规则不完整时返回原因
+    pub fn matcher(&self) -> Result<RuleMatcher<'_>, String> {
+        let mut matcher = RuleMatcher {
+            app: None,
+            protocol: None,
+            port: None,
+            remote: Remote::Any,
+            direction: self.direction,
+        };
+        match self.rule_type {
+            RuleType::Application => {
+                let path = self.application_path.as_deref().map(str::trim).filter(|p| !p.is_empty())
+                    .ok_or("未指定应用程序路径")?;
+                matcher.app = Some(path);
+            }
+            RuleType::Port => {
+                matcher.port = Some(self.port.filter(|p| *p != 0).ok_or("未指定端口")?);
+                matcher.protocol = match self.protocol.as_deref() {
+                    Some(p) if p.eq_ignore_ascii_case("TCP") => Some(Protocol::Tcp),
+                    Some(p) if p.eq_ignore_ascii_case("UDP") => Some(Protocol::Udp),
+                    _ => None,
+                };
+            }
+            RuleType::Address => {
+                let value = self.address.as_deref().unwrap_or("");
+                let network = wfp::parse_address(value).ok_or_else(|| format!("地址格式无效: {}", value))?;
+                matcher.remote = Remote::Networks(vec![network]);
+            }
+            // 尚未解析到地址时不匹配任何连接（阻止规则仍在DNS解析时生效）
+            RuleType::Domain => {
+                matcher.remote = Remote::Networks(self.resolved_addresses.iter()
+                    .map(|&address| (address, if address.is_ipv4() { 32 } else { 128 }))
+                    .collect());
+            }
+            RuleType::Country => {
+                if self.countries.is_empty() {
+                    return Err("未选择国家".to_string());
+                }
+                matcher.remote = Remote::Countries(&self.countries);
+            }
+        }
+        Ok(matcher)
+    }
+}
+
+// 传输层协议
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Protocol {
+    Tcp,
+    Udp,
+}
+
+// 规则匹配的远程地址
+#[derive(Clone, Debug, PartialEq)]
+pub enum Remote<'a> {
+    Any,
+    Networks(Vec<(IpAddr, u8)>), // 地址与前缀长度
+    Countries(&'a [String]),
+}
+
+// 规则的匹配条件，各项需全部满足。程序内评估（evaluate）与WFP过滤器（wfp::plan）都由此生成
+#[derive(Clone, Debug, PartialEq)]
+pub struct RuleMatcher<'a> {
+    pub app: Option<&'a str>,        // 应用程序完整路径
+    pub protocol: Option<Protocol>,  // None表示任意协议
+    pub port: Option<u16>,           // 出站连接的远程端口，入站连接的本地端口
+    pub remote: Remote<'a>,
+    pub direction: RuleDirection,
+}
+
+impl RuleMatcher<'_> {
+    pub fn matches(&self, connection: &Connection) -> bool {
+        if !self.direction.includes(connection.inbound) {
+            return false;
+        }
+        // 待评估的连接只有进程名，按文件名比较
+        if let Some(path) = self.app {
+            let file_name = path.rsplit(['\\', '/']).next().unwrap_or(path);
+            if !file_name.eq_ignore_ascii_case(connection.process_name) {
+                return false;
+            }
         }
+        if self.protocol.map_or(false, |p| p != connection.protocol) || self.port.map_or(false, |p| p != connection.port) {
+            return false;
+        }
+        match &self.remote {
+            Remote::Any => true,
+            Remote::Networks(networks) => connection.address.parse::<IpAddr>()
+                .map_or(false, |ip| networks.iter().any(|&(network, prefix)| in_network(ip, network, prefix))),
+            Remote::Countries(countries) => connection.address.parse::<IpAddr>().ok()
+                .and_then(geoip::lookup)
+                .map_or(false, |code| countries.contains(&code)),
+        }
+    }
+}
+
+// 地址是否属于指定网段
+fn in_network(ip: IpAddr, network: IpAddr, prefix: u8) -> bool {
+    match (ip, network) {
+        (IpAddr::V4(ip), IpAddr::V4(network)) => {
+            let mask = u32::MAX.checked_shl(32 - prefix as u32).unwrap_or(0);
+            u32::from(ip) & mask == u32::from(network) & mask
+        }
+        (IpAddr::V6(ip), IpAddr::V6(network)) => {
+            let mask = u128::MAX.checked_shl(128 - prefix as u32).unwrap_or(0);
+            u128::from(ip) & mask == u128::from(network) & mask
+        }
+        _ => false,
+    }
+}
+
+// 待评估的连接
+pub struct Connection<'a> {
+    pub process_name: &'a str,
+    pub protocol: Protocol,
+    pub port: u16,
+    pub address: &'a str,
+    pub inbound: bool,
+}
+
+// 规则评估结果
+pub struct Verdict<'a> {
+    pub action: RuleAction,
+    pub rule: Option<&'a FirewallRule>, // 为None时表示使用配置方案的默认动作
+    pub tor_only: bool,                 // 由"仅限Tor的应用"决定
+}
+
+// 按规则顺序评估连接，取第一条匹配的已启用规则，未匹配时使用配置方案的默认动作
+pub fn evaluate<'a>(rules: &'a [FirewallRule], profile: &FirewallProfile, connection: &Connection) -> Verdict<'a> {
+    match rules.iter().filter(|r| r.enabled).find(|r| r.matches(connection)) {
+        Some(rule) => Verdict {
+            action: rule.action.clone(),
+            rule: Some(rule),
+            tor_only: false,
+        },
+        None => Verdict {
+            action: match profile {
+                FirewallProfile::Standard => RuleAction::Allow,
+                FirewallProfile::Strict => RuleAction::Block,
+            },
+            rule: None,
+            tor_only: false,
+        },
+    }
+}
+
+// 规则的执行方式
+#[derive(Clone, Copy, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub enum Backend {
+    #[default]
+    Wfp,             // 直接在Windows筛选平台中添加过滤器
+    WindowsFirewall, // 在Windows Defender防火墙中创建规则
+}
+
+impl Backend {
+    const ALL: [Backend; 2] = [Self::Wfp, Self::WindowsFirewall];
+
+    fn label(&self) -> &'static str {
+        match self {
+            Self::Wfp => "Windows筛选平台（推荐）",
+            Self::WindowsFirewall => "Windows Defender防火墙规则",
+        }
+    }
+
+    fn description(&self) -> &'static str {
+        match self {
+            Self::Wfp => "规则按列表顺序生效，支持严格模式；过滤器只在程序运行期间存在，退出后自动移除",
+            Self::WindowsFirewall => "规则可在系统防火墙设置中查看；阻止规则总是优先于允许规则，不支持严格模式的默认阻止",
+        }
+    }
+}
+
+// 断线保护所跟随的模块
+#[derive(Clone, Copy, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub enum ProtectionModule {
+    #[default]
+    Tor,
+    Vpn,
+    I2p,
+    DnsCrypt,
+}
+
+impl ProtectionModule {
+    const ALL: [ProtectionModule; 4] = [Self::Tor, Self::Vpn, Self::I2p, Self::DnsCrypt];
+
+    fn label(&self) -> &'static str {
+        match self {
+            Self::Tor => "Tor",
+            Self::Vpn => "VPN",
+            Self::I2p => "I2P",
+            Self::DnsCrypt => "DNSCrypt",
+        }
+    }
+}
+
+// 断线保护期间仍允许联网的程序：本次运行中各模块实际启动过的程序，以及已安装组件中的
+// 全部程序（阻止期间才启动的传输插件等也能联网），包括tor.exe及其传输插件、dnscrypt-proxy、
+// i2pd、Xray与Snowflake代理
+fn kill_switch_apps() -> Vec<String> {
+    let mut apps: BTreeSet<PathBuf> = services::launched_programs().into_iter().collect();
+    apps.extend(Executable::ALL.iter().filter_map(|executable| components::executable_path(*executable)));
+    apps.iter().map(|path| path.to_string_lossy().to_string()).collect()
+}
+
+// 域名解析到新地址后，等待此时间再重新应用规则
+const DOMAIN_APPLY_DELAY: Duration = Duration::from_secs(5);
+
+// 读取国家规则命中次数的间隔
+const HIT_POLL_INTERVAL: Duration = Duration::from_secs(5);
+
+// 防火墙设置文件名
+const SETTINGS_FILE: &str = "firewall.json";
+
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+struct FirewallSettings {
+    backend: Backend,
+    #[serde(default)]
+    interactive: bool,     // 未知应用联网时询问
+    #[serde(default)]
+    kill_switch: bool,     // 所选模块断开时阻止其他程序联网
+    #[serde(default)]
+    kill_switch_module: ProtectionModule,
+    #[serde(default)]
+    count_hits: bool,      // 统计国家规则命中次数，需要开启系统的数据包丢弃审核
+}
+
+impl utils::VersionedConfig for FirewallSettings {
+    const VERSION: u32 = 1;
+}
+
+fn load_settings() -> FirewallSettings {
+    utils::load_saved_config(SETTINGS_FILE).unwrap_or_default()
+}
+
+// 规则列表文件名
+const RULES_FILE: &str = "firewall_rules.json";
+
+#[derive(Serialize, Deserialize)]
+struct SavedRules {
+    rules: Vec<FirewallRule>,
+    next_rule_id: usize,
+}
+
+impl utils::VersionedConfig for SavedRules {
+    const VERSION: u32 = 1;
+}
+
+fn load_rules() -> Option<SavedRules> {
+    utils::load_saved_config(RULES_FILE)
+}
+
+// 交互模式下等待用户决定的联网请求
+#[derive(Clone)]
+struct ConnectionAlert {
+    process: String,
+    path: Option<String>, // 可执行文件完整路径，无权读取时为None
+    address: String,
+    port: u16,
+    flow: FlowKey,
+}
+
+// 一个连接：进程ID、本地端口、远程地址与端口
+type FlowKey = (u32, u16, Ipv4Addr, u16);
+
+fn flow_key(flow: &FlowStats) -> FlowKey {
+    (flow.pid, flow.local_port, flow.remote_addr, flow.remote_port)
+}
+
+// 连接表中已建立的TCP连接，按出站连接评估
+pub fn flow_connection<'a>(flow: &'a FlowStats, address: &'a str) -> Connection<'a> {
+    Connection {
+        process_name: &flow.process,
+        protocol: Protocol::Tcp,
+        port: flow.remote_port,
+        address,
+        inbound: false,
     }
 }
 
+// 防火墙启用时规则的生效方式
+enum Enforcement {
+    Wfp(wfp::WfpEngine),
+    WindowsFirewall,
+}
+
+// 仅限Tor的应用列表文件名
+const TOR_ONLY_FILE: &str = "tor_only_apps.json";
+
+// 写入Windows防火墙的规则名前缀
+const TOR_ONLY_RULE_PREFIX: &str = "InviZible Tor-only";
+
+// 除回环地址外的所有远程地址，用于阻止直连
+const NON_LOOPBACK_ADDRESSES: &str = "0.0.0.0-126.255.255.255,128.0.0.0-255.255.255.255,::-::0,::2-ffff:ffff:ffff:ffff:ffff:ffff:ffff:ffff";
+
+// 只允许通过Tor联网的应用
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+struct TorOnlyApps {
+    apps: Vec<String>, // 可执行文件完整路径
+}
+
+impl utils::VersionedConfig for TorOnlyApps {
+    const VERSION: u32 = 1;
+}
+
+fn load_tor_only_apps() -> Vec<String> {
+    utils::load_saved_config::<TorOnlyApps>(TOR_ONLY_FILE)
+        .map(|config| config.apps)
+        .unwrap_or_default()
+}
+
+fn tor_only_rule_name(path: &str) -> String {
+    format!("{} {}", TOR_ONLY_RULE_PREFIX, path)
+}
+
+// 在Windows防火墙中阻止应用连接回环地址以外的目标，只能经本机Tor端口联网
+fn install_tor_only_rule(path: &str) -> Result<()> {
+    netfw::run_netsh(&[
+        "advfirewall", "firewall", "add", "rule",
+        &format!("name={}", tor_only_rule_name(path)),
+        "dir=out",
+        "action=block",
+        &format!("program={}", path),
+        &format!("remoteip={}", NON_LOOPBACK_ADDRESSES),
+    ])
+}
+
+fn remove_tor_only_rule(path: &str) -> Result<()> {
+    netfw::run_netsh(&[
+        "advfirewall", "firewall", "delete", "rule",
+        &format!("name={}", tor_only_rule_name(path)),
+    ])
+}
+
 // 防火墙模块结构
 pub struct FirewallModule {
     pub enabled: bool,
@@ -68,7 +482,37 @@ pub struct FirewallModule {
     pub new_rule_address: String,
     pub new_rule_action: RuleAction,
     pub new_rule_description: String,
-    pub running_applications: HashMap<String, bool>
+    new_rule_path: String,
+    editing_rule: Option<usize>, // 正在编辑的规则ID，添加新规则时为None
+    form_error: Option<String>,
+    dragged_rule: Option<usize>, // 正在拖动的规则在列表中的位置
+    pub running_applications: HashMap<String, bool>,
+    pub profile: FirewallProfile,
+    pub tor_only_apps: Vec<String>,
+    pub new_tor_only_app: String,
+    pub tor_ports: Vec<u16>, // Tor当前的本地监听端口，Tor未运行时为空
+    settings: FirewallSettings,
+    enforcement: Option<Enforcement>, // 防火墙启用时规则的生效方式
+    active_rules: usize,              // 当前生效的过滤器或系统防火墙规则数
+    skipped_rules: Vec<String>,       // 上次应用时无法生效的规则
+    alerts: VecDeque<ConnectionAlert>,
+    alerted: HashSet<String>,         // 本次运行中已询问过的进程名（小写）
+    once_rules: HashMap<usize, FlowKey>, // "一次"的规则ID → 规则针对的连接
+    new_rule_domain: String,
+    domains: DomainTracker,
+    domains_changed_at: Option<Instant>, // 域名解析到新地址的时间，尚未重新应用规则
+    dns_query_log: bool,              // DNSCrypt是否记录查询日志，域名规则依赖它发现子域名
+    new_rule_countries: Vec<String>,
+    new_rule_country_code: String,
+    new_rule_direction: RuleDirection,
+    country_hits: HashMap<usize, BTreeMap<String, u64>>, // 各国家规则本次运行中按国家统计的阻止次数
+    last_hit_poll: Option<Instant>,
+    hit_error: Option<String>,        // 读取命中次数失败的原因
+    geoip_generation: u64,            // 生成过滤器时使用的GeoIP数据库版本
+    kill_switch: Option<wfp::WfpEngine>, // 断线保护生效时的WFP会话，关闭即恢复联网
+    kill_switch_state: Option<(bool, bool)>, // 上次处理时所跟随模块的状态（已启用、已连接）
+    kill_switch_failed: bool,         // 本次断开时启用断线保护失败，状态改变前不再重试
+    netfw: netfw::Worker,             // 在后台修改Windows防火墙规则
 }
 
 impl FirewallModule {
@@ -79,6 +523,10 @@ impl FirewallModule {
             new_rule_description: String::new(),
             new_rule_protocol: String::from("TCP"),
             new_rule_port: 0,
+            new_rule_path: String::new(),
+            editing_rule: None,
+            form_error: None,
+            dragged_rule: None,
             enabled: false,
             rules: Vec::new(),
             next_rule_id: 1,
@@ -88,10 +536,57 @@ impl FirewallModule {
             new_rule_type: RuleType::Application,
             edit_mode: false,
             running_applications: HashMap::new(),
+            profile: FirewallProfile::Standard,
+            tor_only_apps: load_tor_only_apps(),
+            new_tor_only_app: String::new(),
+            tor_ports: Vec::new(),
+            settings: load_settings(),
+            enforcement: None,
+            active_rules: 0,
+            skipped_rules: Vec::new(),
+            alerts: VecDeque::new(),
+            alerted: HashSet::new(),
+            once_rules: HashMap::new(),
+            new_rule_domain: String::new(),
+            domains: DomainTracker::new(),
+            domains_changed_at: None,
+            dns_query_log: false,
+            new_rule_countries: Vec::new(),
+            new_rule_country_code: String::new(),
+            new_rule_direction: RuleDirection::default(),
+            country_hits: HashMap::new(),
+            last_hit_poll: None,
+            hit_error: None,
+            geoip_generation: geoip::generation(),
+            kill_switch: None,
+            kill_switch_state: None,
+            kill_switch_failed: false,
+            netfw: netfw::Worker::new(),
         };
         
-        // 添加一些示例规则
-        module.add_example_rules();
+        // 加载保存的规则，首次运行时添加一些示例规则
+        match load_rules() {
+            Some(saved) => {
+                module.rules = saved.rules;
+                module.next_rule_id = saved.next_rule_id;
+            }
+            None => module.add_example_rules(),
+        }
+        
+        // 上次统计命中次数后未能恢复的审核设置（如程序异常退出）
+        match wfp::restore_leftover_event_settings() {
+            Ok(true) => {
+                if let Ok(mut logger) = module.logger.lock() {
+                    logger.info("防火墙", "已恢复上次运行时修改的数据包丢弃审核设置");
+                }
+            }
+            Ok(false) => {}
+            Err(e) => {
+                if let Ok(mut logger) = module.logger.lock() {
+                    logger.warning("防火墙", &format!("恢复数据包丢弃审核设置失败: {:#}", e));
+                }
+            }
+        }
         
         // 记录模块初始化日志
         if let Ok(mut logger) = module.logger.lock() {
@@ -129,42 +624,731 @@ impl FirewallModule {
         self.next_rule_id += 1;
     }
     
-    // 添加新规则
-    fn add_rule(&mut self, rule: FirewallRule) {
-        if let Ok(mut logger) = self.logger.lock() {
-            logger.info("防火墙", &format!("添加新规则: {}", rule.name));
+    // 添加新规则
+    fn add_rule(&mut self, rule: FirewallRule) {
+        if let Ok(mut logger) = self.logger.lock() {
+            logger.info("防火墙", &format!("添加新规则: {}", rule.name));
+        }
+        self.rules.push(rule);
+        self.next_rule_id += 1;
+        self.save_rules();
+        self.apply_rules();
+    }
+    
+    // 删除规则
+    fn remove_rule(&mut self, id: usize) {
+        if let Some(index) = self.rules.iter().position(|r| r.id == id) {
+            let rule = &self.rules[index];
+            if let Ok(mut logger) = self.logger.lock() {
+                logger.info("防火墙", &format!("删除规则: {}", rule.name));
+            }
+            self.rules.remove(index);
+            self.country_hits.remove(&id);
+            if self.selected_rule == Some(id) {
+                self.selected_rule = None;
+            }
+            self.save_rules();
+            self.apply_rules();
+        }
+    }
+    
+    // 启用/禁用防火墙
+    fn toggle_firewall(&mut self) {
+        // 修改系统防火墙需要管理员权限，未提权时不假装启用成功
+        if !self.enabled && !utils::is_running_as_admin() {
+            if let Ok(mut logger) = self.logger.lock() {
+                logger.warning("防火墙", "启用防火墙需要管理员权限，请以管理员身份重新启动");
+            }
+            return;
+        }
+        if self.enabled {
+            self.remove_enforcement();
+        } else {
+            let enforcement = match self.settings.backend {
+                Backend::Wfp => match wfp::WfpEngine::open(wfp::Sublayer::Rules) {
+                    Ok(engine) => Enforcement::Wfp(engine),
+                    Err(e) => {
+                        if let Ok(mut logger) = self.logger.lock() {
+                            logger.error("防火墙", &format!("无法打开Windows筛选平台: {:#}", e));
+                        }
+                        return;
+                    }
+                },
+                Backend::WindowsFirewall => Enforcement::WindowsFirewall,
+            };
+            self.enforcement = Some(enforcement);
+            if !self.apply_rules() {
+                self.remove_enforcement();
+                return;
+            }
+        }
+        self.enabled = !self.enabled;
+        let is_enabled = self.enabled; // 先保存状态，避免后续借用冲突
+        
+        {
+            // 使用单独的作用域限制logger的借用范围
+            if let Ok(mut logger) = self.logger.lock() {
+                logger.info("防火墙", &format!("防火墙已{}", if is_enabled { "启用" } else { "禁用" }));
+            }
+        }
+    }
+    
+    // 按指定状态启用/禁用防火墙
+    pub fn set_enabled(&mut self, enabled: bool) {
+        if self.enabled != enabled {
+            self.toggle_firewall();
+        }
+    }
+    
+    // 切换防火墙配置方案
+    pub fn set_profile(&mut self, profile: FirewallProfile) {
+        if self.profile == profile {
+            return;
+        }
+        self.profile = profile;
+        let label = self.profile.label();
+        if let Ok(mut logger) = self.logger.lock() {
+            logger.info("防火墙", &format!("防火墙配置已切换为{}", label));
+        }
+        self.apply_rules();
+    }
+    
+    // 把当前规则和配置方案写入WFP或Windows防火墙，防火墙未启用时不做任何事。
+    // WFP失败时保留之前的过滤器并返回false；Windows防火墙在后台修改，失败时恢复之前的规则
+    fn apply_rules(&mut self) -> bool {
+        self.refresh_domain_addresses();
+        self.geoip_generation = geoip::generation();
+        let result = match self.enforcement.as_mut() {
+            None => return true,
+            Some(Enforcement::Wfp(engine)) => {
+                let (filters, mut skipped) = wfp::plan(&self.rules, &self.profile);
+                engine.apply(&filters).map(|unresolved| {
+                    skipped.extend(unresolved);
+                    (format!("已应用 {} 个WFP过滤器", engine.filter_count()), engine.filter_count(), skipped)
+                })
+            }
+            // 由后台线程修改，结果在tick中报告
+            Some(Enforcement::WindowsFirewall) => {
+                let (planned, skipped) = netfw::plan(&self.rules, &self.profile);
+                let summary = format!("正在更新Windows防火墙规则（共 {} 条）", planned.len());
+                self.netfw.apply(planned);
+                Ok((summary, self.active_rules, skipped))
+            }
+        };
+        match result {
+            Ok((summary, count, skipped)) => {
+                self.active_rules = count;
+                if let Ok(mut logger) = self.logger.lock() {
+                    logger.info("防火墙", &summary);
+                    for message in &skipped {
+                        logger.warning("防火墙", &format!("规则未生效: {}", message));
+                    }
+                }
+                self.skipped_rules = skipped;
+                true
+            }
+            Err(e) => {
+                if let Ok(mut logger) = self.logger.lock() {
+                    logger.error("防火墙", &format!("应用防火墙规则失败: {:#}", e));
+                }
+                false
+            }
+        }
+    }
+    
+    // 撤销已生效的规则：关闭WFP会话即删除其中的过滤器，Windows防火墙规则由后台线程逐条删除
+    fn remove_enforcement(&mut self) {
+        if let Some(Enforcement::WindowsFirewall) = self.enforcement.take() {
+            self.netfw.remove_all();
+        }
+        self.active_rules = 0;
+        self.skipped_rules.clear();
+    }
+    
+    // 报告后台修改Windows防火墙规则的结果
+    fn report_netfw_outcomes(&mut self) {
+        for outcome in self.netfw.take_outcomes() {
+            match outcome {
+                netfw::Outcome::Applied(count) => {
+                    if let Some(Enforcement::WindowsFirewall) = self.enforcement {
+                        self.active_rules = count;
+                    }
+                    if let Ok(mut logger) = self.logger.lock() {
+                        logger.info("防火墙", &format!("Windows防火墙中本程序的规则已更新，共 {} 条", count));
+                    }
+                }
+                netfw::Outcome::Failed(e) => {
+                    if let Ok(mut logger) = self.logger.lock() {
+                        logger.error("防火墙", &format!("更新Windows防火墙规则失败，已恢复之前的规则: {}", e));
+                    }
+                }
+            }
+        }
+    }
+    
+    // 保存规则列表，仅本次运行有效的规则除外
+    fn save_rules(&self) {
+        let saved = SavedRules {
+            rules: self.rules.iter().filter(|r| !r.temporary).cloned().collect(),
+            next_rule_id: self.next_rule_id,
+        };
+        let result = utils::get_config_path(RULES_FILE)
+            .and_then(|path| utils::save_versioned_config(&saved, &path));
+        if let Err(e) = result {
+            if let Ok(mut logger) = self.logger.lock() {
+                logger.error("防火墙", &format!("保存防火墙规则失败: {}", e));
+            }
+        }
+    }
+    
+    // 交互模式：检查最近的连接，没有匹配规则的应用加入询问队列。
+    // 只能发现已建立的连接，严格模式下未知应用的连接已被阻止，不会触发询问
+    pub fn review_connections(&mut self, flows: &[FlowStats]) {
+        self.expire_once_rules(flows);
+        if !self.enabled || !self.settings.interactive {
+            return;
+        }
+        let own_pid = std::process::id();
+        let bin_dir = utils::get_bin_dir().ok().map(|dir| dir.to_lowercase());
+        for flow in flows {
+            if flow.pid == own_pid || flow.remote_addr.is_loopback() || flow.remote_addr.is_unspecified()
+                || flow.process.starts_with("PID ") {
+                continue;
+            }
+            let key = flow.process.to_lowercase();
+            if self.alerted.contains(&key) {
+                continue;
+            }
+            let address = flow.remote_addr.to_string();
+            let verdict = self.evaluate(&flow_connection(flow, &address));
+            if verdict.rule.is_some() || verdict.tor_only {
+                continue;
+            }
+            self.alerted.insert(key);
+            let path = utils::process_path(flow.pid);
+            // 本程序自带的组件（Tor、DNSCrypt、i2pd等）不询问
+            let bundled = match (&path, &bin_dir) {
+                (Some(path), Some(dir)) => path.to_lowercase().starts_with(dir.as_str()),
+                _ => false,
+            };
+            if bundled {
+                continue;
+            }
+            if let Ok(mut logger) = self.logger.lock() {
+                logger.info("防火墙", &format!("{} 正在连接 {}:{}，等待确认", flow.process, address, flow.remote_port));
+            }
+            utils::show_toast("InviZible Pro 防火墙", &format!("{} 正在连接 {}:{}", flow.process, address, flow.remote_port));
+            self.alerts.push_back(ConnectionAlert {
+                process: flow.process.clone(),
+                path,
+                address,
+                port: flow.remote_port,
+                flow: flow_key(flow),
+            });
+        }
+    }
+    
+    // "一次"的规则只对创建时的连接有效：该连接结束，或规则匹配到其他连接时删除，
+    // 之后该程序的新连接会再次询问
+    fn expire_once_rules(&mut self, flows: &[FlowStats]) {
+        let expired: Vec<usize> = self.once_rules.iter()
+            .filter(|(id, key)| {
+                let rule = match self.rules.iter().find(|r| r.id == **id) {
+                    Some(rule) => rule,
+                    None => return true,
+                };
+                let mut alive = false;
+                for flow in flows {
+                    if flow_key(flow) == **key {
+                        alive = true;
+                        continue;
+                    }
+                    let address = flow.remote_addr.to_string();
+                    if rule.matches(&flow_connection(flow, &address)) {
+                        return true;
+                    }
+                }
+                !alive
+            })
+            .map(|(id, _)| *id)
+            .collect();
+        for id in expired {
+            self.once_rules.remove(&id);
+            if let Some(rule) = self.rules.iter().find(|r| r.id == id) {
+                self.alerted.remove(&rule.name.to_lowercase());
+            }
+            self.remove_rule(id);
+        }
+    }
+    
+    // 按用户的选择为应用创建规则，"一次"的规则只对本次连接有效。
+    // 没有完整路径时不创建规则，只按文件名的规则会匹配同名的其他程序
+    fn resolve_alert(&mut self, alert: &ConnectionAlert, action: RuleAction, permanent: bool) {
+        let path = match &alert.path {
+            Some(path) => path.clone(),
+            None => {
+                if let Ok(mut logger) = self.logger.lock() {
+                    logger.warning("防火墙", &format!("无法读取 {} 的完整路径，未创建规则", alert.process));
+                }
+                return;
+            }
+        };
+        let id = self.next_rule_id;
+        let mut rule = FirewallRule::new(id, &alert.process, RuleType::Application);
+        rule.application_path = Some(path);
+        rule.action = action;
+        rule.temporary = !permanent;
+        rule.description = if permanent {
+            "由交互模式创建".to_string()
+        } else {
+            "由交互模式创建，仅对本次连接有效".to_string()
+        };
+        self.add_rule(rule);
+        if !permanent {
+            self.once_rules.insert(id, alert.flow);
+        }
+    }
+    
+    // 交互模式的询问窗口，一次显示一个请求
+    pub fn show_alert_window(&mut self, ctx: &egui::Context) {
+        let alert = match self.alerts.front() {
+            Some(alert) => alert.clone(),
+            None => return,
+        };
+        let pending = self.alerts.len() - 1;
+        let mut decision = None;
+        let mut dismissed = false;
+        egui::Window::new("应用程序联网请求")
+            .collapsible(false)
+            .resizable(false)
+            .anchor(egui::Align2::RIGHT_BOTTOM, [-10.0, -40.0])
+            .show(ctx, |ui| {
+                ui.label(RichText::new(&alert.process).color(FIREWALL_COLOR).strong());
+                match &alert.path {
+                    Some(path) => ui.label(RichText::new(path).weak()),
+                    None => ui.label(RichText::new("无法读取程序的完整路径（可能需要管理员权限），不能为其创建规则").color(Color32::YELLOW)),
+                };
+                ui.label(format!("正在连接 {}:{}，当前没有匹配的规则", alert.address, alert.port));
+                ui.horizontal(|ui| {
+                    ui.add_enabled_ui(alert.path.is_some(), |ui| {
+                        if ui.button("允许一次").on_hover_text("只对这个连接有效").clicked() {
+                            decision = Some((RuleAction::Allow, false));
+                        }
+                        if ui.button("阻止一次").on_hover_text("只对这个连接有效").clicked() {
+                            decision = Some((RuleAction::Block, false));
+                        }
+                        if ui.button(RichText::new("始终允许").color(Color32::GREEN)).clicked() {
+                            decision = Some((RuleAction::Allow, true));
+                        }
+                        if ui.button(RichText::new("始终阻止").color(Color32::RED)).clicked() {
+                            decision = Some((RuleAction::Block, true));
+                        }
+                    });
+                    if alert.path.is_none() && ui.button("忽略").clicked() {
+                        dismissed = true;
+                    }
+                });
+                if pending > 0 {
+                    ui.label(RichText::new(format!("还有 {} 个请求等待处理", pending)).weak());
+                }
+            });
+        if let Some((action, permanent)) = decision {
+            self.alerts.pop_front();
+            self.resolve_alert(&alert, action, permanent);
+        } else if dismissed {
+            self.alerts.pop_front();
+        }
+    }
+    
+    fn save_settings(&self) {
+        let result = utils::get_config_path(SETTINGS_FILE)
+            .and_then(|path| utils::save_versioned_config(&self.settings, &path));
+        if let Err(e) = result {
+            if let Ok(mut logger) = self.logger.lock() {
+                logger.error("防火墙", &format!("保存防火墙设置失败: {}", e));
+            }
+        }
+    }
+    
+    // 断线保护所跟随的模块，未开启断线保护时为None
+    pub fn kill_switch_module(&self) -> Option<ProtectionModule> {
+        self.settings.kill_switch.then_some(self.settings.kill_switch_module)
+    }
+    
+    // 所跟随模块的状态（或断线保护设置）改变时返回是否应当阻止，未改变时返回None
+    fn kill_switch_change(&mut self, active: bool, connected: bool) -> Option<bool> {
+        if self.kill_switch_state == Some((active, connected)) {
+            return None;
+        }
+        self.kill_switch_state = Some((active, connected));
+        Some(self.settings.kill_switch && active && !connected)
+    }
+    
+    // 每帧调用，只在状态改变时修改WFP：所跟随的模块已启用但未连接时阻止其他程序联网；
+    // 连接恢复、模块被停用或关闭断线保护时关闭会话，过滤器随之删除
+    pub fn update_kill_switch(&mut self, active: bool, connected: bool) {
+        let should_block = match self.kill_switch_change(active, connected) {
+            Some(should_block) => should_block,
+            None => return,
+        };
+        self.kill_switch_failed = false;
+        if !should_block {
+            if self.kill_switch.take().is_some() {
+                if let Ok(mut logger) = self.logger.lock() {
+                    logger.info("防火墙", "断线保护已解除，恢复联网");
+                }
+            }
+            return;
+        }
+        // 只是修改了设置（如切换跟随的模块）时保留已生效的阻止
+        if self.kill_switch.is_some() {
+            return;
+        }
+        
+        let module = self.settings.kill_switch_module.label();
+        let apps = kill_switch_apps();
+        let result = wfp::WfpEngine::open(wfp::Sublayer::KillSwitch).and_then(|mut engine| {
+            engine.apply(&wfp::kill_switch_plan(&apps)).map(|skipped| (engine, skipped))
+        });
+        match result {
+            Ok((engine, skipped)) => {
+                if let Ok(mut logger) = self.logger.lock() {
+                    logger.warning("防火墙", &format!("{}已断开，断线保护已阻止其他程序的出站连接", module));
+                    for message in &skipped {
+                        logger.warning("防火墙", &format!("断线保护无法放行: {}", message));
+                    }
+                }
+                utils::show_toast("InviZible Pro 断线保护", &format!("{}已断开，已阻止其他程序联网，重新连接后自动恢复", module));
+                self.kill_switch = Some(engine);
+            }
+            Err(e) => {
+                if let Ok(mut logger) = self.logger.lock() {
+                    logger.error("防火墙", &format!("启用断线保护失败: {:#}", e));
+                }
+                self.kill_switch_failed = true;
+            }
+        }
+    }
+    
+    // 防火墙页中的断线保护设置与状态
+    fn render_kill_switch(&mut self, ui: &mut Ui) {
+        let mut enabled = self.settings.kill_switch;
+        let mut module = self.settings.kill_switch_module;
+        let can_enable = enabled || utils::is_running_as_admin();
+        ui.add_enabled(can_enable, egui::Checkbox::new(&mut enabled, "所选模块断开时阻止其他程序联网"))
+            .on_hover_text("只放行本程序启动的和已安装组件中的Tor（含传输插件）、DNSCrypt、I2P、Xray与Snowflake进程以及本机回环连接和DHCP；手动停用该模块时不阻止")
+            .on_disabled_hover_text("需要管理员权限");
+        // 过滤器属于WFP动态会话，会话随进程结束而关闭
+        ui.label(RichText::new("⚠ 阻止规则只在本程序运行期间存在：程序退出或崩溃时系统会自动移除这些规则，其他程序随即恢复联网").color(Color32::from_rgb(255, 165, 0)));
+        ui.horizontal(|ui| {
+            ui.label("跟随模块:");
+            egui::ComboBox::from_id_source("firewall_kill_switch_module")
+                .selected_text(module.label())
+                .show_ui(ui, |ui| {
+                    for option in ProtectionModule::ALL {
+                        ui.selectable_value(&mut module, option, option.label());
+                    }
+                });
+        });
+        if enabled != self.settings.kill_switch || module != self.settings.kill_switch_module {
+            self.settings.kill_switch = enabled;
+            self.settings.kill_switch_module = module;
+            self.kill_switch_state = None;
+            self.save_settings();
+        }
+        
+        if self.kill_switch.is_some() {
+            ui.label(RichText::new(format!("⛔ {}已断开，其他程序的出站连接已被阻止", module.label())).color(Color32::RED));
+        } else if self.kill_switch_failed {
+            ui.label(RichText::new("启用断线保护失败，详见日志").color(Color32::RED));
+        } else if enabled {
+            ui.label(RichText::new(format!("待命中：{}断开时自动阻止", module.label())).weak());
+        }
+    }
+    
+    // 设置页中的执行方式选择，防火墙启用时不能切换
+    pub fn backend_ui(&mut self, ui: &mut Ui) {
+        let mut backend = self.settings.backend;
+        ui.add_enabled_ui(!self.enabled, |ui| {
+            for option in Backend::ALL {
+                ui.radio_value(&mut backend, option, option.label()).on_hover_text(option.description());
+            }
+        });
+        ui.label(RichText::new(backend.description()).weak());
+        if self.enabled {
+            ui.label(RichText::new("切换前请先禁用防火墙").color(Color32::YELLOW));
+        }
+        if backend != self.settings.backend {
+            self.settings.backend = backend;
+            self.save_settings();
+            if let Ok(mut logger) = self.logger.lock() {
+                logger.info("防火墙", &format!("防火墙执行方式已切换为{}", backend.label()));
+            }
+        }
+        
+        let can_clean = !self.enabled && utils::is_running_as_admin();
+        let button = egui::Button::new(format!("清理Windows防火墙中的\"{}\"规则", netfw::RULE_PREFIX));
+        if ui.add_enabled(can_clean, button).on_disabled_hover_text("需要管理员权限且防火墙未启用").clicked() {
+            self.netfw.remove_all();
+            if let Ok(mut logger) = self.logger.lock() {
+                logger.info("防火墙", "正在清理Windows防火墙中本程序的规则");
+            }
+        }
+        if self.netfw.is_busy() {
+            ui.horizontal(|ui| {
+                ui.spinner();
+                ui.label("正在更新Windows防火墙规则...");
+            });
+        }
+    }
+    
+    // 按当前规则和配置方案评估连接，仅限Tor的应用优先于其他规则
+    pub fn evaluate(&self, connection: &Connection) -> Verdict {
+        if self.is_tor_only(connection.process_name) {
+            let loopback = connection.address.parse::<std::net::IpAddr>().map(|a| a.is_loopback()).unwrap_or(false);
+            let via_tor = loopback && self.tor_ports.contains(&connection.port);
+            return Verdict {
+                action: if via_tor { RuleAction::Allow } else { RuleAction::Block },
+                rule: None,
+                tor_only: true,
+            };
+        }
+        evaluate(&self.rules, &self.profile, connection)
+    }
+    
+    // 更新Tor的本地监听端口
+    pub fn set_tor_ports(&mut self, ports: Vec<u16>) {
+        self.tor_ports = ports;
+    }
+
+    // 更新DNSCrypt是否记录查询日志
+    pub fn set_dns_query_log(&mut self, enabled: bool) {
+        self.dns_query_log = enabled;
+    }
+
+    // 防火墙启用时，阻止类域名规则的域名，交给DNSCrypt在解析时拦截
+    pub fn blocked_domains(&self) -> Vec<String> {
+        if !self.enabled {
+            return Vec::new();
+        }
+        self.rules.iter()
+            .filter(|r| r.enabled && r.rule_type == RuleType::Domain && r.action == RuleAction::Block)
+            .filter_map(|r| r.domain.clone())
+            .collect()
+    }
+
+    fn domain_patterns(&self) -> Vec<String> {
+        self.rules.iter()
+            .filter(|r| r.enabled && r.rule_type == RuleType::Domain)
+            .filter_map(|r| r.domain.clone())
+            .collect()
+    }
+
+    // 设置解析规则域名所用的本地DNS（DNSCrypt的监听地址），DNSCrypt未运行时为None
+    pub fn set_dns_resolver(&mut self, resolver: Option<std::net::SocketAddr>) {
+        self.domains.set_resolver(resolver);
+    }
+
+    // 根据DNSCrypt查询日志中的新记录，解析与域名规则匹配的域名
+    pub fn observe_dns_queries(&mut self, queries: &[QueryEntry]) {
+        if queries.is_empty() {
+            return;
+        }
+        let patterns = self.domain_patterns();
+        for query in queries.iter().filter(|q| !q.blocked() && !q.failed()) {
+            if patterns.iter().any(|pattern| dns_rules::pattern_matches(pattern, &query.name)) {
+                self.domains.observe(&query.name);
+            }
+        }
+    }
+
+    // 每帧调用：解析规则中的域名，解析到新地址时更新规则并重新应用
+    pub fn tick(&mut self) {
+        self.report_netfw_outcomes();
+        for pattern in self.domain_patterns() {
+            if let Some(name) = domain_tracker::base_name(&pattern) {
+                self.domains.observe(name);
+            }
+        }
+        // 解析结果陆续到达，合并一段时间内的变化后再重新应用规则
+        if self.domains.take_changed() && self.domains_changed_at.is_none() {
+            self.domains_changed_at = Some(Instant::now());
+        }
+        if self.domains_changed_at.map_or(false, |t| t.elapsed() >= DOMAIN_APPLY_DELAY) {
+            self.domains_changed_at = None;
+            if self.refresh_domain_addresses() {
+                self.apply_rules();
+            }
+        }
+        // GeoIP数据库加载或更新后，按新的地址段重新生成国家规则
+        let has_country_rules = self.rules.iter().any(|r| r.enabled && r.rule_type == RuleType::Country);
+        if geoip::generation() != self.geoip_generation {
+            self.geoip_generation = geoip::generation();
+            if has_country_rules {
+                self.apply_rules();
+            }
+        }
+        if has_country_rules && self.settings.count_hits && self.last_hit_poll.map_or(true, |t| t.elapsed() >= HIT_POLL_INTERVAL) {
+            self.last_hit_poll = Some(Instant::now());
+            self.poll_country_hits();
+        }
+    }
+
+    // 停止统计命中次数，恢复系统的审核设置
+    fn stop_hit_collection(&mut self) {
+        self.last_hit_poll = None;
+        self.hit_error = None;
+        if let Some(Enforcement::Wfp(engine)) = self.enforcement.as_mut() {
+            if let Err(e) = engine.stop_hit_collection() {
+                if let Ok(mut logger) = self.logger.lock() {
+                    logger.warning("防火墙", &format!("恢复数据包丢弃审核设置失败: {:#}", e));
+                }
+            }
+        }
+    }
+    
+    // 从WFP的阻止事件中统计国家规则的命中次数，Windows防火墙方式下不统计
+    fn poll_country_hits(&mut self) {
+        let engine = match self.enforcement.as_mut() {
+            Some(Enforcement::Wfp(engine)) => engine,
+            _ => return,
+        };
+        match engine.take_hits() {
+            Ok(hits) => {
+                for (rule_id, country) in hits {
+                    *self.country_hits.entry(rule_id).or_default().entry(country).or_insert(0) += 1;
+                }
+                self.hit_error = None;
+            }
+            Err(e) => {
+                let message = format!("{:#}", e);
+                if self.hit_error.as_ref() != Some(&message) {
+                    if let Ok(mut logger) = self.logger.lock() {
+                        logger.warning("防火墙", &format!("读取国家规则命中次数失败: {}", message));
+                    }
+                    self.hit_error = Some(message);
+                }
+            }
+        }
+    }
+
+    // 用已解析的地址更新域名规则，返回是否有变化
+    fn refresh_domain_addresses(&mut self) -> bool {
+        let mut changed = false;
+        for rule in self.rules.iter_mut().filter(|r| r.rule_type == RuleType::Domain) {
+            let addresses = rule.domain.as_deref().map(|d| self.domains.addresses_for(d)).unwrap_or_default();
+            if addresses != rule.resolved_addresses {
+                rule.resolved_addresses = addresses;
+                changed = true;
+            }
+        }
+        changed
+    }
+
+    // 进程是否在仅限Tor的应用列表中
+    fn is_tor_only(&self, process_name: &str) -> bool {
+        self.tor_only_apps.iter().any(|path| {
+            let file_name = path.rsplit(['\\', '/']).next().unwrap_or(path);
+            file_name.eq_ignore_ascii_case(process_name)
+        })
+    }
+    
+    fn save_tor_only_apps(&self) {
+        let result = utils::get_config_path(TOR_ONLY_FILE)
+            .and_then(|path| utils::save_versioned_config(&TorOnlyApps { apps: self.tor_only_apps.clone() }, &path));
+        if let Err(e) = result {
+            if let Ok(mut logger) = self.logger.lock() {
+                logger.error("防火墙", &format!("保存仅限Tor的应用失败: {}", e));
+            }
         }
-        self.rules.push(rule);
-        self.next_rule_id += 1;
     }
     
-    // 删除规则
-    fn remove_rule(&mut self, id: usize) {
-        if let Some(index) = self.rules.iter().position(|r| r.id == id) {
-            let rule = &self.rules[index];
+    // 添加仅限Tor的应用，同时在Windows防火墙中阻止其直连
+    fn add_tor_only_app(&mut self, path: &str) {
+        let path = path.trim().trim_matches('"').to_string();
+        if path.is_empty() || self.tor_only_apps.iter().any(|p| p.eq_ignore_ascii_case(&path)) {
+            return;
+        }
+        if let Err(e) = install_tor_only_rule(&path) {
             if let Ok(mut logger) = self.logger.lock() {
-                logger.info("防火墙", &format!("删除规则: {}", rule.name));
-            }
-            self.rules.remove(index);
-            if self.selected_rule == Some(id) {
-                self.selected_rule = None;
+                logger.error("防火墙", &format!("无法为 {} 添加仅限Tor规则: {:#}", path, e));
             }
+            return;
         }
+        if let Ok(mut logger) = self.logger.lock() {
+            logger.info("防火墙", &format!("{} 现在只能通过Tor联网", path));
+        }
+        self.tor_only_apps.push(path);
+        self.save_tor_only_apps();
     }
     
-    // 启用/禁用防火墙
-    fn toggle_firewall(&mut self) {
-        self.enabled = !self.enabled;
-        let is_enabled = self.enabled; // 先保存状态，避免后续借用冲突
-        
-        {
-            // 使用单独的作用域限制logger的借用范围
+    fn remove_tor_only_app(&mut self, path: &str) {
+        if let Err(e) = remove_tor_only_rule(path) {
             if let Ok(mut logger) = self.logger.lock() {
-                logger.info("防火墙", &format!("防火墙已{}", if is_enabled { "启用" } else { "禁用" }));
+                logger.warning("防火墙", &format!("删除 {} 的仅限Tor规则失败: {:#}", path, e));
             }
         }
+        if let Ok(mut logger) = self.logger.lock() {
+            logger.info("防火墙", &format!("{} 已恢复直接联网", path));
+        }
+        self.tor_only_apps.retain(|p| p != path);
+        self.save_tor_only_apps();
     }
     
+    // 仅限Tor的应用列表
+    fn render_tor_only_apps(&mut self, ui: &mut Ui) {
+        ui.label("列表中的应用只能连接本机的Tor端口，直接访问外网的连接会被Windows防火墙阻止。");
+        match self.tor_ports.first() {
+            Some(port) => {
+                ui.label(format!("请在这些应用中将代理设置为 SOCKS5 127.0.0.1:{}", port));
+            }
+            None => {
+                ui.label(RichText::new("Tor未运行，这些应用当前无法联网").color(Color32::YELLOW));
+            }
+        }
+        
+        let mut remove = None;
+        Grid::new("tor_only_apps_grid").num_columns(2).striped(true).spacing([10.0, 4.0]).show(ui, |ui| {
+            for path in &self.tor_only_apps {
+                ui.label(path);
+                if ui.button("移除").clicked() {
+                    remove = Some(path.clone());
+                }
+                ui.end_row();
+            }
+        });
+        if let Some(path) = remove {
+            self.remove_tor_only_app(&path);
+        }
+        
+        let is_admin = utils::is_running_as_admin();
+        ui.horizontal(|ui| {
+            ui.add(egui::TextEdit::singleline(&mut self.new_tor_only_app).hint_text("应用程序路径，如 C:\\Program Files\\App\\app.exe").desired_width(320.0));
+            let can_add = is_admin && !self.new_tor_only_app.trim().is_empty();
+            if ui.add_enabled(can_add, egui::Button::new("添加")).on_disabled_hover_text("需要管理员权限").clicked() {
+                let path = std::mem::take(&mut self.new_tor_only_app);
+                self.add_tor_only_app(&path);
+            }
+        });
+        
+        // 从运行中的应用程序中选择
+        let candidates: Vec<String> = self.running_applications.keys()
+            .filter(|path| !self.tor_only_apps.contains(path))
+            .cloned()
+            .collect();
+        if !candidates.is_empty() {
+            ui.horizontal_wrapped(|ui| {
+                ui.label("运行中的应用:");
+                for path in candidates {
+                    let name = path.rsplit('\\').next().unwrap_or(&path).to_string();
+                    if ui.add_enabled(is_admin, egui::Button::new(name).small()).on_hover_text(&path).clicked() {
+                        self.add_tor_only_app(&path);
+                    }
+                }
+            });
+        }
+    }
+
     // 启用/禁用规则
     fn toggle_rule(&mut self, id: usize) {
         // 先查找规则并获取必要信息，避免同时借用
@@ -182,7 +1366,27 @@ impl FirewallModule {
             if let Ok(mut logger) = self.logger.lock() {
                 logger.info("防火墙", &format!("规则 '{}' 已{}", name, if enabled { "启用" } else { "禁用" }));
             }
+            self.save_rules();
+            self.apply_rules();
+        }
+    }
+    
+    // 把第from条规则移动到第to条之前（to为列表长度时移到末尾）
+    fn move_rule(&mut self, from: usize, to: usize) {
+        if from >= self.rules.len() {
+            return;
+        }
+        let to = if to > from { to - 1 } else { to }.min(self.rules.len() - 1);
+        if to == from {
+            return;
+        }
+        let rule = self.rules.remove(from);
+        if let Ok(mut logger) = self.logger.lock() {
+            logger.info("防火墙", &format!("规则 '{}' 的优先级已调整为第 {} 位", rule.name, to + 1));
         }
+        self.rules.insert(to, rule);
+        self.save_rules();
+        self.apply_rules();
     }
     
     // 更改规则动作
@@ -205,6 +1409,293 @@ impl FirewallModule {
             if let Ok(mut logger) = self.logger.lock() {
                 logger.info("防火墙", &format!("规则 '{}' 动作已更改为 {:?}", name, action));
             }
+            self.save_rules();
+            self.apply_rules();
+        }
+    }
+    
+    // 打开规则对话框，编辑时用规则的全部字段填充表单
+    pub fn open_rule_form(&mut self, rule_id: Option<usize>) {
+        let rule = rule_id
+            .and_then(|id| self.rules.iter().find(|r| r.id == id).cloned())
+            .unwrap_or_else(|| FirewallRule::new(0, "", RuleType::Application));
+        self.editing_rule = rule_id.filter(|_| rule.id != 0);
+        self.new_rule_name = rule.name;
+        self.new_rule_type = rule.rule_type;
+        self.new_rule_path = rule.application_path.unwrap_or_default();
+        self.new_rule_port = rule.port.unwrap_or(0);
+        self.new_rule_protocol = rule.protocol.unwrap_or_else(|| "TCP".to_string());
+        self.new_rule_address = rule.address.unwrap_or_default();
+        self.new_rule_domain = rule.domain.unwrap_or_default();
+        self.new_rule_countries = rule.countries;
+        self.new_rule_country_code.clear();
+        self.new_rule_direction = rule.direction;
+        self.new_rule_action = rule.action;
+        self.new_rule_description = rule.description;
+        self.form_error = None;
+        self.edit_mode = true;
+    }
+    
+    // 由表单生成规则，检查当前类型所需的字段
+    fn rule_from_form(&self, id: usize) -> Result<FirewallRule, String> {
+        let mut rule = FirewallRule::new(id, self.new_rule_name.trim(), self.new_rule_type.clone());
+        rule.action = self.new_rule_action.clone();
+        rule.direction = self.new_rule_direction;
+        rule.description = self.new_rule_description.trim().to_string();
+        match self.new_rule_type {
+            RuleType::Application => {
+                let path = self.new_rule_path.trim().trim_matches('"');
+                if path.is_empty() {
+                    return Err("请输入应用程序路径".to_string());
+                }
+                rule.application_path = Some(path.to_string());
+            }
+            RuleType::Port => {
+                if self.new_rule_port == 0 {
+                    return Err("请输入端口号".to_string());
+                }
+                rule.port = Some(self.new_rule_port);
+                rule.protocol = Some(self.new_rule_protocol.clone());
+            }
+            RuleType::Address => {
+                let address = self.new_rule_address.trim();
+                if wfp::parse_address(address).is_none() {
+                    return Err(format!("IP地址格式无效: {}", address));
+                }
+                rule.address = Some(address.to_string());
+            }
+            RuleType::Domain => {
+                let domain = self.new_rule_domain.trim().to_lowercase();
+                dns_rules::validate_pattern(&domain)?;
+                rule.domain = Some(domain);
+            }
+            RuleType::Country => {
+                if self.new_rule_countries.is_empty() {
+                    return Err("请至少选择一个国家".to_string());
+                }
+                rule.countries = self.new_rule_countries.clone();
+            }
+        }
+        if rule.name.is_empty() {
+            return Err("请输入规则名称".to_string());
+        }
+        Ok(rule)
+    }
+    
+    // 用编辑后的内容替换同一ID的规则，保留启用状态
+    fn update_rule(&mut self, mut rule: FirewallRule) {
+        let existing = match self.rules.iter_mut().find(|r| r.id == rule.id) {
+            Some(existing) => existing,
+            None => return,
+        };
+        rule.enabled = existing.enabled;
+        rule.temporary = existing.temporary;
+        *existing = rule;
+        let name = existing.name.clone();
+        if let Ok(mut logger) = self.logger.lock() {
+            logger.info("防火墙", &format!("规则 '{}' 已更新", name));
+        }
+        self.save_rules();
+        self.apply_rules();
+    }
+    
+    fn save_rule_form(&mut self) {
+        let id = self.editing_rule.unwrap_or(self.next_rule_id);
+        match self.rule_from_form(id) {
+            Ok(rule) => {
+                if self.editing_rule.is_some() {
+                    self.update_rule(rule);
+                } else {
+                    self.add_rule(rule);
+                }
+                self.edit_mode = false;
+                self.editing_rule = None;
+            }
+            Err(e) => self.form_error = Some(e),
+        }
+    }
+    
+    // 添加/编辑规则对话框
+    fn render_rule_form(&mut self, ctx: &egui::Context) {
+        let mut open = true;
+        let mut save = false;
+        let mut cancel = false;
+        egui::Window::new(if self.editing_rule.is_some() { "编辑规则" } else { "添加规则" })
+            .open(&mut open)
+            .collapsible(false)
+            .resizable(false)
+            .show(ctx, |ui| {
+                Grid::new("firewall_rule_form_grid").num_columns(2).spacing([10.0, 6.0]).show(ui, |ui| {
+                    ui.label("规则名称:");
+                    ui.text_edit_singleline(&mut self.new_rule_name);
+                    ui.end_row();
+                    
+                    ui.label("规则类型:");
+                    egui::ComboBox::from_id_source("firewall_rule_type").selected_text(match self.new_rule_type {
+                        RuleType::Application => "应用程序",
+                        RuleType::Port => "端口",
+                        RuleType::Address => "地址",
+                        RuleType::Domain => "域名",
+                        RuleType::Country => "国家",
+                    }).show_ui(ui, |ui| {
+                        ui.selectable_value(&mut self.new_rule_type, RuleType::Application, "应用程序");
+                        ui.selectable_value(&mut self.new_rule_type, RuleType::Port, "端口");
+                        ui.selectable_value(&mut self.new_rule_type, RuleType::Address, "地址");
+                        ui.selectable_value(&mut self.new_rule_type, RuleType::Domain, "域名");
+                        ui.selectable_value(&mut self.new_rule_type, RuleType::Country, "国家");
+                    });
+                    ui.end_row();
+                    
+                    match self.new_rule_type {
+                        RuleType::Application => {
+                            ui.label("应用程序路径:");
+                            ui.horizontal(|ui| {
+                                let response = ui.add(egui::TextEdit::singleline(&mut self.new_rule_path)
+                                    .hint_text("C:\\Program Files\\App\\app.exe")
+                                    .desired_width(280.0));
+                                // 名称为空时用文件名填充
+                                if response.changed() && self.new_rule_name.is_empty() {
+                                    if let Some(file_name) = self.new_rule_path.rsplit(['\\', '/']).next() {
+                                        self.new_rule_name = file_name.trim_matches('"').to_string();
+                                    }
+                                }
+                                if ui.button("浏览...").clicked() {
+                                    if let Some(path) = rfd::FileDialog::new().add_filter("应用程序", &["exe"]).pick_file() {
+                                        self.new_rule_path = path.to_string_lossy().to_string();
+                                        if self.new_rule_name.is_empty() {
+                                            if let Some(file_name) = path.file_name() {
+                                                self.new_rule_name = file_name.to_string_lossy().to_string();
+                                            }
+                                        }
+                                    }
+                                }
+                            });
+                            ui.end_row();
+                        }
+                        RuleType::Port => {
+                            ui.label("端口号:");
+                            ui.add(egui::DragValue::new(&mut self.new_rule_port).speed(1));
+                            ui.end_row();
+                            
+                            ui.label("协议:");
+                            egui::ComboBox::from_id_source("firewall_rule_protocol").selected_text(self.new_rule_protocol.as_str()).show_ui(ui, |ui| {
+                                ui.selectable_value(&mut self.new_rule_protocol, "TCP".to_string(), "TCP");
+                                ui.selectable_value(&mut self.new_rule_protocol, "UDP".to_string(), "UDP");
+                            });
+                            ui.end_row();
+                        }
+                        RuleType::Address => {
+                            ui.label("IP地址:");
+                            ui.add(egui::TextEdit::singleline(&mut self.new_rule_address).hint_text("192.168.1.100"));
+                            ui.end_row();
+                        }
+                        RuleType::Domain => {
+                            ui.label("域名:").on_hover_text("example.com（含子域名）、=example.com（仅此域名）、*.example.com、ads.*");
+                            ui.add(egui::TextEdit::singleline(&mut self.new_rule_domain).hint_text("*.telemetry.example.com"));
+                            ui.end_row();
+                            
+                            ui.label("");
+                            ui.label(RichText::new("阻止规则同时加入DNSCrypt的屏蔽列表；\n解析到的IP地址也会按规则过滤").weak());
+                            ui.end_row();
+                        }
+                        RuleType::Country => {
+                            ui.label("国家:");
+                            ui.vertical(|ui| {
+                                self.render_country_picker(ui);
+                            });
+                            ui.end_row();
+                            
+                            if !geoip::is_loaded() {
+                                ui.label("");
+                                ui.label(RichText::new("GeoIP数据库未加载，规则暂时无法生效，可在设置中更新").color(Color32::YELLOW));
+                                ui.end_row();
+                            }
+                        }
+                    }
+                    
+                    ui.label("方向:");
+                    egui::ComboBox::from_id_source("firewall_rule_direction").selected_text(self.new_rule_direction.label()).show_ui(ui, |ui| {
+                        for direction in RuleDirection::ALL {
+                            ui.selectable_value(&mut self.new_rule_direction, direction, direction.label());
+                        }
+                    });
+                    ui.end_row();
+                    
+                    ui.label("动作:");
+                    egui::ComboBox::from_id_source("firewall_rule_action").selected_text(match self.new_rule_action {
+                        RuleAction::Allow => "允许",
+                        RuleAction::Block => "阻止",
+                    }).show_ui(ui, |ui| {
+                        ui.selectable_value(&mut self.new_rule_action, RuleAction::Allow, "允许");
+                        ui.selectable_value(&mut self.new_rule_action, RuleAction::Block, "阻止");
+                    });
+                    ui.end_row();
+                    
+                    ui.label("描述:");
+                    ui.text_edit_multiline(&mut self.new_rule_description);
+                    ui.end_row();
+                });
+                
+                if let Some(error) = &self.form_error {
+                    ui.label(RichText::new(error).color(Color32::RED));
+                }
+                ui.horizontal(|ui| {
+                    if ui.button("保存").clicked() {
+                        save = true;
+                    }
+                    if ui.button("取消").clicked() {
+                        cancel = true;
+                    }
+                });
+            });
+        if save {
+            self.save_rule_form();
+        }
+        if cancel || !open {
+            self.edit_mode = false;
+            self.editing_rule = None;
+        }
+    }
+    
+    // 规则对话框中已选的国家与添加国家的控件
+    fn render_country_picker(&mut self, ui: &mut Ui) {
+        ui.horizontal_wrapped(|ui| {
+            let mut removed = None;
+            for (index, code) in self.new_rule_countries.iter().enumerate() {
+                if ui.small_button(format!("{} ✖", geoip::country_label(code))).on_hover_text("移除").clicked() {
+                    removed = Some(index);
+                }
+            }
+            if let Some(index) = removed {
+                self.new_rule_countries.remove(index);
+            }
+        });
+        
+        let mut added = None;
+        ui.horizontal(|ui| {
+            egui::ComboBox::from_id_source("firewall_rule_country").selected_text("添加国家").show_ui(ui, |ui| {
+                for code in geoip::country_codes() {
+                    if !self.new_rule_countries.iter().any(|c| c == code) && ui.selectable_label(false, geoip::country_label(code)).clicked() {
+                        added = Some(code.to_string());
+                    }
+                }
+            });
+            // 列表中没有的国家按代码添加
+            ui.add(egui::TextEdit::singleline(&mut self.new_rule_country_code).hint_text("代码").desired_width(40.0));
+            if ui.button("添加").clicked() {
+                let code = self.new_rule_country_code.trim().to_ascii_uppercase();
+                if code.len() == 2 && code.chars().all(|c| c.is_ascii_alphabetic()) {
+                    added = Some(code);
+                    self.new_rule_country_code.clear();
+                } else {
+                    self.form_error = Some(format!("国家代码应为两个字母（如DE）: {}", code));
+                }
+            }
+        });
+        if let Some(code) = added {
+            if !self.new_rule_countries.contains(&code) {
+                self.new_rule_countries.push(code);
+            }
         }
     }
     
@@ -237,18 +1728,84 @@ impl FirewallModule {
             ui.label(RichText::new(status_text).color(status_color).strong());
             
             ui.with_layout(egui::Layout::right_to_left(egui::Align::Center), |ui| {
-                if ui.button(if self.enabled { "禁用防火墙" } else { "启用防火墙" }).clicked() {
+                let can_toggle = self.enabled || utils::is_running_as_admin();
+                let button = egui::Button::new(if self.enabled { "禁用防火墙" } else { "启用防火墙" });
+                if ui.add_enabled(can_toggle, button).on_disabled_hover_text("需要管理员权限").clicked() {
                     self.toggle_firewall();
                 }
+                
+                let mut profile = self.profile.clone();
+                egui::ComboBox::from_id_source("firewall_profile_combo")
+                    .selected_text(profile.label())
+                    .show_ui(ui, |ui| {
+                        ui.selectable_value(&mut profile, FirewallProfile::Standard, FirewallProfile::Standard.label());
+                        ui.selectable_value(&mut profile, FirewallProfile::Strict, FirewallProfile::Strict.label());
+                    });
+                ui.label("配置方案:");
+                if profile != self.profile {
+                    self.set_profile(profile);
+                }
             });
         });
         
+        match &self.enforcement {
+            Some(Enforcement::Wfp(_)) => {
+                ui.label(RichText::new(format!("Windows筛选平台中已生效 {} 个过滤器，程序退出后自动移除", self.active_rules)).weak());
+            }
+            Some(Enforcement::WindowsFirewall) => {
+                ui.label(RichText::new(format!("Windows防火墙中已添加 {} 条规则（名称以\"{}\"开头），禁用或退出时删除", self.active_rules, netfw::RULE_PREFIX)).weak());
+            }
+            None => {}
+        }
+        if !self.skipped_rules.is_empty() {
+            ui.collapsing(RichText::new(format!("⚠ {} 条规则未生效", self.skipped_rules.len())).color(Color32::YELLOW), |ui| {
+                for message in &self.skipped_rules {
+                    ui.label(message);
+                }
+            });
+        }
+        
         ui.separator();
         
+        if !utils::is_running_as_admin() {
+            render_admin_required(ui, &self.logger, "启用防火墙");
+            ui.separator();
+        }
+        
+        let mut interactive = self.settings.interactive;
+        ui.checkbox(&mut interactive, "交互模式：没有匹配规则的应用联网时询问")
+            .on_hover_text("根据流量监控发现的连接弹出询问，可选择允许或阻止一次或始终生效；严格模式下未知应用的连接会直接被阻止，不会询问");
+        if interactive != self.settings.interactive {
+            self.settings.interactive = interactive;
+            self.save_settings();
+            if !interactive {
+                self.alerts.clear();
+            }
+        }
+        
+        let mut count_hits = self.settings.count_hits;
+        ui.checkbox(&mut count_hits, "统计国家规则的命中次数")
+            .on_hover_text("仅在使用Windows筛选平台时有效。统计期间会开启系统的\"筛选平台数据包丢弃\"审核，所有被阻止的连接都会写入安全日志；关闭此项或退出程序后恢复原设置");
+        if count_hits != self.settings.count_hits {
+            self.settings.count_hits = count_hits;
+            self.save_settings();
+            if !count_hits {
+                self.stop_hit_collection();
+            }
+        }
+        
+        ui.collapsing("断线保护", |ui| {
+            self.render_kill_switch(ui);
+        });
+        
         // 防火墙简介
         ui.collapsing("关于防火墙", |ui| {
             ui.label("防火墙可以控制应用程序的网络访问权限，阻止未授权的连接，保护您的计算机免受网络威胁。");
-            ui.label("您可以创建基于应用程序、端口或IP地址的规则来精确控制网络流量。");
+            ui.label("您可以创建基于应用程序、端口、IP地址、域名或国家的规则来精确控制网络流量。");
+        });
+        
+        ui.collapsing(format!("仅限Tor的应用（{}）", self.tor_only_apps.len()), |ui| {
+            self.render_tor_only_apps(ui);
         });
         
         ui.separator();
@@ -261,19 +1818,32 @@ impl FirewallModule {
                     self.scan_running_applications();
                 }
                 if ui.button("添加规则").clicked() {
-                    self.edit_mode = true;
+                    self.open_rule_form(None);
                 }
             });
         });
         
+        let has_domain_rules = self.rules.iter().any(|r| r.enabled && r.rule_type == RuleType::Domain);
+        if has_domain_rules && !self.domains.has_resolver() {
+            ui.label(RichText::new("⚠ 域名规则通过本地DNSCrypt解析，DNSCrypt未连接时不会更新解析到的地址").color(Color32::YELLOW));
+        } else if has_domain_rules && !self.dns_query_log {
+            ui.label(RichText::new("⚠ 域名规则需要在DNSCrypt中开启\"记录查询日志\"才能追踪子域名解析到的地址，目前只解析规则中的域名本身").color(Color32::YELLOW));
+        }
+        if self.settings.backend == Backend::WindowsFirewall {
+            ui.label(RichText::new("当前使用Windows防火墙规则执行：阻止规则总是优先于允许规则，顺序仅对程序内的评估生效").color(Color32::YELLOW));
+        }
+        
         // 规则列表
         ScrollArea::vertical().show(ui, |ui| {
+            let mut handle_rects = Vec::new();
+            let mut drag_released = false;
             Grid::new("firewall_rules_grid")
-                .num_columns(5)
+                .num_columns(6)
                 .striped(true)
                 .spacing([10.0, 4.0])
                 .show(ui, |ui| {
                     // 表头
+                    ui.label(RichText::new("优先级").strong());
                     ui.label(RichText::new("启用").strong());
                     ui.label(RichText::new("名称").strong());
                     ui.label(RichText::new("类型").strong());
@@ -283,7 +1853,18 @@ impl FirewallModule {
                     
                     // 规则列表
                     let rules_clone = self.rules.clone(); // 克隆规则列表以避免借用冲突
-                    for rule in &rules_clone {
+                    for (index, rule) in rules_clone.iter().enumerate() {
+                        // 拖动手柄，靠前的规则先匹配
+                        let handle = ui.add(egui::Label::new(RichText::new(format!("☰ {}", index + 1)).monospace()).sense(egui::Sense::drag()))
+                            .on_hover_text("拖动以调整优先级：按从上到下的顺序匹配，第一条匹配的规则生效");
+                        if handle.drag_started() {
+                            self.dragged_rule = Some(index);
+                        }
+                        if handle.drag_released() {
+                            drag_released = true;
+                        }
+                        handle_rects.push(handle.rect);
+                        
                         // 启用/禁用复选框
                         let mut enabled = rule.enabled;
                         let rule_id = rule.id; // 先获取ID避免借用冲突
@@ -292,7 +1873,11 @@ impl FirewallModule {
                         }
                         
                         // 规则名称
-                        let rule_text = RichText::new(&rule.name);
+                        let rule_text = if rule.temporary {
+                            RichText::new(format!("{}（仅本次连接）", rule.name))
+                        } else {
+                            RichText::new(&rule.name)
+                        };
                         if ui.selectable_label(self.selected_rule == Some(rule.id), rule_text).clicked() {
                             self.selected_rule = Some(rule.id);
                         }
@@ -302,6 +1887,8 @@ impl FirewallModule {
                             RuleType::Application => "应用程序",
                             RuleType::Port => "端口",
                             RuleType::Address => "地址",
+                            RuleType::Domain => "域名",
+                            RuleType::Country => "国家",
                         };
                         ui.label(type_text);
                         
@@ -318,9 +1905,8 @@ impl FirewallModule {
                         let rule_id = rule.id; // 再次获取ID避免闭包中的借用冲突
                         ui.horizontal(|ui| {
                             if ui.button("编辑").clicked() {
-                                // 编辑规则逻辑
                                 self.selected_rule = Some(rule_id);
-                                self.edit_mode = true;
+                                self.open_rule_form(Some(rule_id));
                             }
                             if ui.button("删除").clicked() {
                                 self.remove_rule(rule_id);
@@ -330,6 +1916,25 @@ impl FirewallModule {
                         ui.end_row();
                     }
                 });
+            
+            // 拖动中显示插入位置，松开时移动规则
+            if let Some(from) = self.dragged_rule {
+                let pointer = ui.ctx().pointer_interact_pos();
+                let target = pointer.map(|pos| handle_rects.iter().filter(|rect| rect.center().y < pos.y).count());
+                if let (Some(target), Some(first), Some(last)) = (target, handle_rects.first(), handle_rects.last()) {
+                    let y = match handle_rects.get(target) {
+                        Some(rect) => rect.top() - 2.0,
+                        None => last.bottom() + 2.0,
+                    };
+                    ui.painter().hline(first.left()..=ui.max_rect().right(), y, egui::Stroke::new(2.0, FIREWALL_COLOR));
+                }
+                if drag_released || !ui.input(|i| i.pointer.any_down()) {
+                    self.dragged_rule = None;
+                    if let Some(target) = target {
+                        self.move_rule(from, target);
+                    }
+                }
+            }
         });
         
         // 规则详情区域
@@ -351,6 +1956,8 @@ impl FirewallModule {
                             RuleType::Application => "应用程序",
                             RuleType::Port => "端口",
                             RuleType::Address => "地址",
+                            RuleType::Domain => "域名",
+                            RuleType::Country => "国家",
                         });
                         ui.end_row();
                         
@@ -361,6 +1968,10 @@ impl FirewallModule {
                         });
                         ui.end_row();
                         
+                        ui.label("方向:");
+                        ui.label(rule.direction.label());
+                        ui.end_row();
+                        
                         match rule.rule_type {
                             RuleType::Application => {
                                 ui.label("应用程序路径:");
@@ -389,6 +2000,41 @@ impl FirewallModule {
                                 }
                                 ui.end_row();
                             },
+                            RuleType::Domain => {
+                                ui.label("域名:");
+                                if let Some(domain) = &rule.domain {
+                                    ui.label(domain);
+                                }
+                                ui.end_row();
+                                
+                                ui.label("已解析地址:");
+                                if rule.resolved_addresses.is_empty() {
+                                    ui.label(RichText::new("尚未解析到地址").weak());
+                                } else {
+                                    let addresses: Vec<String> = rule.resolved_addresses.iter().map(|ip| ip.to_string()).collect();
+                                    ui.label(addresses.join(", "));
+                                }
+                                ui.end_row();
+                            },
+                            RuleType::Country => {
+                                // 每个国家一行，开启统计时附带本次运行中被阻止的次数
+                                ui.label("国家:").on_hover_text("命中次数为本次运行中被该规则阻止的连接数，需在上方开启\"统计国家规则的命中次数\"");
+                                let hits = self.country_hits.get(&rule.id);
+                                ui.vertical(|ui| {
+                                    for code in &rule.countries {
+                                        if self.settings.count_hits {
+                                            let count = hits.and_then(|h| h.get(code)).copied().unwrap_or(0);
+                                            ui.label(format!("{}  命中 {} 次", geoip::country_label(code), count));
+                                        } else {
+                                            ui.label(geoip::country_label(code));
+                                        }
+                                    }
+                                    if let Some(error) = &self.hit_error {
+                                        ui.label(RichText::new(format!("无法统计命中次数: {}", error)).weak());
+                                    }
+                                });
+                                ui.end_row();
+                            },
                         }
                         
                         ui.label("描述:");
@@ -400,107 +2046,7 @@ impl FirewallModule {
         
         // 添加/编辑规则对话框
         if self.edit_mode {
-            let mut dialog_open = true;
-            egui::Window::new(if self.selected_rule.is_some() { "编辑规则" } else { "添加规则" })
-                .open(&mut dialog_open)
-                .show(ui.ctx(), |ui| {
-                    // 对话框内容
-                    if !dialog_open {
-                        self.edit_mode = false;
-                        self.new_rule_name.clear();
-                    }
-                });
-            ui.separator();
-            ui.heading(if self.selected_rule.is_some() { "编辑规则" } else { "添加规则" });
-            
-            let mut rule_name = self.new_rule_name.clone();
-            ui.horizontal(|ui| {
-                ui.label("规则名称:");
-                if ui.text_edit_singleline(&mut rule_name).changed() {
-                    self.new_rule_name = rule_name;
-                }
-            });
-            
-            ui.horizontal(|ui| {
-                ui.label("规则类型:");
-                egui::ComboBox::from_label("").selected_text(match self.new_rule_type {
-                    RuleType::Application => "应用程序",
-                    RuleType::Port => "端口",
-                    RuleType::Address => "地址",
-                }).show_ui(ui, |ui| {
-                    ui.selectable_value(&mut self.new_rule_type, RuleType::Application, "应用程序");
-                    ui.selectable_value(&mut self.new_rule_type, RuleType::Port, "端口");
-                    ui.selectable_value(&mut self.new_rule_type, RuleType::Address, "地址");
-                });
-            });
-
-            match self.new_rule_type {
-                RuleType::Application => {
-                    ui.horizontal(|ui| {
-                        ui.label("应用程序路径:");
-                        if ui.text_edit_singleline(&mut self.new_rule_name).changed() {
-                            // 自动填充规则名称
-                            self.new_rule_name = self.new_rule_name.split("\\").last().unwrap_or("未知应用").to_string();
-                        }
-                    });
-                },
-                RuleType::Port => {
-                    ui.horizontal(|ui| {
-                        ui.label("端口号:");
-                        ui.add(egui::DragValue::new(&mut self.new_rule_port).speed(1));
-                    });
-                    ui.horizontal(|ui| {
-                        ui.label("协议:");
-                        egui::ComboBox::from_label("").selected_text("TCP").show_ui(ui, |ui| {
-                            ui.selectable_value(&mut self.new_rule_protocol, "TCP".to_string(), "TCP");
-                            ui.selectable_value(&mut self.new_rule_protocol, "UDP".to_string(), "UDP");
-                        });
-                    });
-                },
-                RuleType::Address => {
-                    ui.horizontal(|ui| {
-                        ui.label("IP地址:");
-                        ui.text_edit_singleline(&mut self.new_rule_address);
-                    });
-                },
-            }
-
-            ui.horizontal(|ui| {
-                ui.label("动作:");
-                egui::ComboBox::from_label("").selected_text(match self.new_rule_action {
-                    RuleAction::Allow => "允许",
-                    RuleAction::Block => "阻止",
-                }).show_ui(ui, |ui| {
-                    ui.selectable_value(&mut self.new_rule_action, RuleAction::Allow, "允许");
-                    ui.selectable_value(&mut self.new_rule_action, RuleAction::Block, "阻止");
-                });
-            });
-
-            ui.horizontal(|ui| {
-                ui.label("描述:");
-                ui.text_edit_multiline(&mut self.new_rule_description);
-            });
-            
-            ui.horizontal(|ui| {
-                if ui.button("取消").clicked() {
-                    self.edit_mode = false;
-                    self.new_rule_name.clear();
-                }
-                
-                if ui.button("保存").clicked() {
-                    // 保存规则逻辑
-                    if !self.new_rule_name.is_empty() {
-                        let new_rule = FirewallRule::new(
-                            self.next_rule_id,
-                            &self.new_rule_name,
-                            self.new_rule_type.clone()
-                        );
-                        self.add_rule(new_rule);
-                        self.new_rule_name.clear();
-                        self.edit_mode = false;
-                    }
-                }
-            });
+            self.render_rule_form(ui.ctx());
         }
         
         // 运行中的应用程序列表
@@ -564,4 +2110,90 @@ impl FirewallModule {
             });
         }
     }
-}
\ No newline at end of file
+}
+
+// 退出时删除Windows防火墙中的规则（WFP过滤器随会话关闭自动删除）
+impl Drop for FirewallModule {
+    fn drop(&mut self) {
+        self.remove_enforcement();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn connection<'a>(protocol: Protocol, port: u16, address: &'a str) -> Connection<'a> {
+        Connection { process_name: "curl.exe", protocol, port, address, inbound: false }
+    }
+
+    #[test]
+    fn address_rule_matches_cidr() {
+        let mut rule = FirewallRule::new(1, "阻止内网", RuleType::Address);
+        rule.address = Some("10.0.0.0/8".to_string());
+        let rules = [rule];
+
+        let verdict = evaluate(&rules, &FirewallProfile::Standard, &connection(Protocol::Tcp, 443, "10.20.30.40"));
+        assert_eq!(verdict.action, RuleAction::Block);
+        assert_eq!(verdict.rule.map(|r| r.id), Some(1));
+        assert!(!rules[0].matches(&connection(Protocol::Tcp, 443, "11.0.0.1")));
+        assert!(!rules[0].matches(&connection(Protocol::Tcp, 443, "::ffff:10.0.0.1")));
+    }
+
+    #[test]
+    fn port_rule_matches_protocol_and_direction() {
+        let mut rule = FirewallRule::new(1, "阻止DNS", RuleType::Port);
+        rule.port = Some(53);
+        rule.protocol = Some("UDP".to_string());
+        rule.direction = RuleDirection::Outbound;
+
+        assert!(rule.matches(&connection(Protocol::Udp, 53, "8.8.8.8")));
+        assert!(!rule.matches(&connection(Protocol::Tcp, 53, "8.8.8.8")));
+        assert!(!rule.matches(&Connection { inbound: true, ..connection(Protocol::Udp, 53, "8.8.8.8") }));
+    }
+
+    #[test]
+    fn rule_covering_connection_suppresses_alert() {
+        let mut module = FirewallModule::new(Arc::new(Mutex::new(Logger::new())));
+        let mut rule = FirewallRule::new(1, "允许文档网段", RuleType::Address);
+        rule.address = Some("192.0.2.0/24".to_string());
+        rule.action = RuleAction::Allow;
+        module.rules = vec![rule];
+        module.enabled = true;
+        module.settings.interactive = true;
+
+        let flow = |process: &str, remote_addr: Ipv4Addr| FlowStats {
+            pid: 4242,
+            process: process.to_string(),
+            local_port: 50000,
+            remote_addr,
+            remote_port: 443,
+            rx_rate: 0.0,
+            tx_rate: 0.0,
+        };
+        module.review_connections(&[
+            flow("covered.exe", Ipv4Addr::new(192, 0, 2, 10)),
+            flow("unknown.exe", Ipv4Addr::new(198, 51, 100, 1)),
+        ]);
+        let alerted: Vec<&str> = module.alerts.iter().map(|a| a.process.as_str()).collect();
+        assert_eq!(alerted, ["unknown.exe"]);
+    }
+
+    #[test]
+    fn kill_switch_changes_only_with_module_state() {
+        let mut module = FirewallModule::new(Arc::new(Mutex::new(Logger::new())));
+        module.settings.kill_switch = true;
+
+        assert_eq!(module.kill_switch_change(true, false), Some(true));
+        assert_eq!(module.kill_switch_change(true, false), None);
+        assert_eq!(module.kill_switch_change(true, true), Some(false));
+        assert_eq!(module.kill_switch_change(true, true), None);
+        assert_eq!(module.kill_switch_change(true, false), Some(true));
+
+        // 关闭断线保护后重新判断
+        module.settings.kill_switch = false;
+        module.kill_switch_state = None;
+        assert_eq!(module.kill_switch_change(true, false), Some(false));
+        assert_eq!(module.kill_switch_change(true, false), None);
+    }
+}
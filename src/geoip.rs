@@ -0,0 +1,352 @@
+use eframe::egui::{Color32, RichText, Ui};
+use std::fs;
+use std::io::Read;
+use std::net::IpAddr;
+use std::path::Path;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, SystemTime};
+use anyhow::{anyhow, Context, Result};
+use chrono::{Datelike, Local, Months};
+use flate2::read::GzDecoder;
+use once_cell::sync::Lazy;
+
+use crate::logger::Logger;
+use crate::utils;
+
+// 本地数据库文件名（解压后的CSV）
+const GEOIP_FILE: &str = "geoip.csv";
+
+// DB-IP免费国家数据库下载地址（按月发布）
+const DBIP_URL: &str = "https://download.db-ip.com/free/dbip-country-lite-{month}.csv.gz";
+
+// 数据库超过该时间后自动更新
+const MAX_AGE: Duration = Duration::from_secs(30 * 24 * 3600);
+
+// GeoIP数据库：按起始地址排序的地址段
+pub struct GeoIpDatabase {
+    v4: Vec<(u32, u32, [u8; 2])>,
+    v6: Vec<(u128, u128, [u8; 2])>,
+}
+
+impl GeoIpDatabase {
+    // 解析DB-IP CSV（每行: 起始地址,结束地址,国家代码）
+    fn parse(contents: &str) -> Self {
+        let mut v4 = Vec::new();
+        let mut v6 = Vec::new();
+        for line in contents.lines() {
+            let mut fields = line.split(',');
+            let (Some(start), Some(end), Some(country)) = (fields.next(), fields.next(), fields.next()) else {
+                continue;
+            };
+            let country = country.trim().trim_matches('"').as_bytes();
+            if country.len() != 2 {
+                continue;
+            }
+            let code = [country[0].to_ascii_uppercase(), country[1].to_ascii_uppercase()];
+            match (start.trim().parse::<IpAddr>(), end.trim().parse::<IpAddr>()) {
+                (Ok(IpAddr::V4(start)), Ok(IpAddr::V4(end))) => v4.push((u32::from(start), u32::from(end), code)),
+                (Ok(IpAddr::V6(start)), Ok(IpAddr::V6(end))) => v6.push((u128::from(start), u128::from(end), code)),
+                _ => {}
+            }
+        }
+        v4.sort_by_key(|r| r.0);
+        v6.sort_by_key(|r| r.0);
+        Self { v4, v6 }
+    }
+
+    // 在排好序的地址段中二分查找
+    fn find<T: Ord + Copy>(ranges: &[(T, T, [u8; 2])], ip: T) -> Option<String> {
+        let index = ranges.partition_point(|r| r.0 <= ip).checked_sub(1)?;
+        let (_, end, code) = ranges[index];
+        if ip <= end {
+            Some(String::from_utf8_lossy(&code).to_string())
+        } else {
+            None
+        }
+    }
+
+    // 查询IP所属国家代码（如"DE"）
+    pub fn lookup(&self, ip: IpAddr) -> Option<String> {
+        match ip {
+            IpAddr::V4(ip) => Self::find(&self.v4, u32::from(ip)),
+            IpAddr::V6(ip) => match ip.to_ipv4_mapped() {
+                Some(ip) => Self::find(&self.v4, u32::from(ip)),
+                None => Self::find(&self.v6, u128::from(ip)),
+            },
+        }
+    }
+
+    // 在排好序的地址段中取出某个国家的地址段，相邻的地址段合并为一段
+    fn collect_ranges<T: Ord + Copy>(ranges: &[(T, T, [u8; 2])], code: [u8; 2], next: fn(T) -> Option<T>) -> Vec<(T, T)> {
+        let mut merged: Vec<(T, T)> = Vec::new();
+        for &(start, end, _) in ranges.iter().filter(|r| r.2 == code) {
+            match merged.last_mut() {
+                Some(last) if next(last.1) == Some(start) => last.1 = end,
+                _ => merged.push((start, end)),
+            }
+        }
+        merged
+    }
+
+    // 国家代码对应的全部地址段（起始地址, 结束地址）
+    pub fn ranges(&self, code: &str) -> Vec<(IpAddr, IpAddr)> {
+        let code = code.as_bytes();
+        if code.len() != 2 {
+            return Vec::new();
+        }
+        let code = [code[0].to_ascii_uppercase(), code[1].to_ascii_uppercase()];
+        let v4 = Self::collect_ranges(&self.v4, code, |ip| ip.checked_add(1))
+            .into_iter()
+            .map(|(start, end)| (IpAddr::V4(start.into()), IpAddr::V4(end.into())));
+        let v6 = Self::collect_ranges(&self.v6, code, |ip| ip.checked_add(1))
+            .into_iter()
+            .map(|(start, end)| (IpAddr::V6(start.into()), IpAddr::V6(end.into())));
+        v4.chain(v6).collect()
+    }
+
+    // 地址段数量
+    pub fn len(&self) -> usize {
+        self.v4.len() + self.v6.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+}
+
+// 常见国家/地区代码的中文名称（按代码排序），未收录的直接显示代码
+const COUNTRY_NAMES: &[(&str, &str)] = &[
+    ("AD", "安道尔"), ("AE", "阿联酋"), ("AF", "阿富汗"), ("AL", "阿尔巴尼亚"), ("AM", "亚美尼亚"), ("AO", "安哥拉"),
+    ("AR", "阿根廷"), ("AT", "奥地利"), ("AU", "澳大利亚"), ("AZ", "阿塞拜疆"), ("BA", "波黑"), ("BD", "孟加拉国"),
+    ("BE", "比利时"), ("BG", "保加利亚"), ("BH", "巴林"), ("BR", "巴西"), ("BY", "白俄罗斯"), ("CA", "加拿大"),
+    ("CH", "瑞士"), ("CL", "智利"), ("CN", "中国"), ("CO", "哥伦比亚"), ("CR", "哥斯达黎加"), ("CU", "古巴"),
+    ("CY", "塞浦路斯"), ("CZ", "捷克"), ("DE", "德国"), ("DK", "丹麦"), ("DZ", "阿尔及利亚"), ("EC", "厄瓜多尔"),
+    ("EE", "爱沙尼亚"), ("EG", "埃及"), ("ES", "西班牙"), ("ET", "埃塞俄比亚"), ("FI", "芬兰"), ("FR", "法国"),
+    ("GB", "英国"), ("GE", "格鲁吉亚"), ("GR", "希腊"), ("HK", "中国香港"), ("HR", "克罗地亚"), ("HU", "匈牙利"),
+    ("ID", "印度尼西亚"), ("IE", "爱尔兰"), ("IL", "以色列"), ("IN", "印度"), ("IQ", "伊拉克"), ("IR", "伊朗"),
+    ("IS", "冰岛"), ("IT", "意大利"), ("JO", "约旦"), ("JP", "日本"), ("KE", "肯尼亚"), ("KG", "吉尔吉斯斯坦"),
+    ("KH", "柬埔寨"), ("KP", "朝鲜"), ("KR", "韩国"), ("KW", "科威特"), ("KZ", "哈萨克斯坦"), ("LB", "黎巴嫩"),
+    ("LI", "列支敦士登"), ("LK", "斯里兰卡"), ("LT", "立陶宛"), ("LU", "卢森堡"), ("LV", "拉脱维亚"), ("MA", "摩洛哥"),
+    ("MC", "摩纳哥"), ("MD", "摩尔多瓦"), ("ME", "黑山"), ("MK", "北马其顿"), ("MM", "缅甸"), ("MN", "蒙古"),
+    ("MO", "中国澳门"), ("MT", "马耳他"), ("MX", "墨西哥"), ("MY", "马来西亚"), ("NG", "尼日利亚"), ("NL", "荷兰"),
+    ("NO", "挪威"), ("NP", "尼泊尔"), ("NZ", "新西兰"), ("OM", "阿曼"), ("PA", "巴拿马"), ("PE", "秘鲁"),
+    ("PH", "菲律宾"), ("PK", "巴基斯坦"), ("PL", "波兰"), ("PT", "葡萄牙"), ("QA", "卡塔尔"), ("RO", "罗马尼亚"),
+    ("RS", "塞尔维亚"), ("RU", "俄罗斯"), ("SA", "沙特阿拉伯"), ("SC", "塞舌尔"), ("SE", "瑞典"), ("SG", "新加坡"),
+    ("SI", "斯洛文尼亚"), ("SK", "斯洛伐克"), ("SY", "叙利亚"), ("TH", "泰国"), ("TM", "土库曼斯坦"), ("TN", "突尼斯"),
+    ("TR", "土耳其"), ("TW", "中国台湾"), ("UA", "乌克兰"), ("US", "美国"), ("UY", "乌拉圭"), ("UZ", "乌兹别克斯坦"),
+    ("VE", "委内瑞拉"), ("VN", "越南"), ("ZA", "南非"),
+];
+
+// 全局共享的数据库，所有模块通过lookup查询
+static DATABASE: Lazy<Mutex<Option<Arc<GeoIpDatabase>>>> = Lazy::new(|| Mutex::new(None));
+
+// 是否有更新任务正在进行
+static UPDATING: Lazy<Mutex<bool>> = Lazy::new(|| Mutex::new(false));
+
+// 数据库每次加载或更新后加一，使用地址段的模块据此重新生成规则
+static GENERATION: AtomicU64 = AtomicU64::new(0);
+
+fn install(database: GeoIpDatabase) {
+    if let Ok(mut db) = DATABASE.lock() {
+        *db = Some(Arc::new(database));
+        GENERATION.fetch_add(1, Ordering::SeqCst);
+    }
+}
+
+// 当前数据库的版本号，未加载时为0
+pub fn generation() -> u64 {
+    GENERATION.load(Ordering::SeqCst)
+}
+
+// 查询IP所属国家代码，数据库未加载时返回None
+pub fn lookup(ip: IpAddr) -> Option<String> {
+    let database = DATABASE.lock().ok()?.clone()?;
+    database.lookup(ip)
+}
+
+// 国家代码对应的地址段，数据库未加载时返回None
+pub fn country_ranges(code: &str) -> Option<Vec<(IpAddr, IpAddr)>> {
+    let database = DATABASE.lock().ok()?.clone()?;
+    Some(database.ranges(code))
+}
+
+// 有中文名称的国家代码，用于界面中的国家选择
+pub fn country_codes() -> impl Iterator<Item = &'static str> {
+    COUNTRY_NAMES.iter().map(|(code, _)| *code)
+}
+
+// 国家代码对应的中文名称
+pub fn country_name(code: &str) -> Option<&'static str> {
+    let code = code.to_ascii_uppercase();
+    COUNTRY_NAMES
+        .binary_search_by(|(c, _)| c.cmp(&code.as_str()))
+        .ok()
+        .map(|index| COUNTRY_NAMES[index].1)
+}
+
+// 用于界面显示的国家，如"德国（DE）"；未收录名称时只显示代码
+pub fn country_label(code: &str) -> String {
+    match country_name(code) {
+        Some(name) => format!("{}（{}）", name, code.to_ascii_uppercase()),
+        None => code.to_ascii_uppercase(),
+    }
+}
+
+// 查询IP所属国家并格式化为显示文本
+pub fn describe(ip: IpAddr) -> Option<String> {
+    lookup(ip).map(|code| country_label(&code))
+}
+
+// 数据库是否已加载
+pub fn is_loaded() -> bool {
+    DATABASE.lock().map(|db| db.is_some()).unwrap_or(false)
+}
+
+// 本地数据库文件路径
+fn database_path() -> Result<String> {
+    utils::get_config_path(GEOIP_FILE)
+}
+
+// 本地数据库最后更新时间
+fn database_modified() -> Option<SystemTime> {
+    let path = database_path().ok()?;
+    fs::metadata(path).ok()?.modified().ok()
+}
+
+// 从本地文件加载数据库
+fn load_from_disk() -> Result<usize> {
+    let contents = fs::read_to_string(database_path()?).context("Failed to read geoip database")?;
+    let database = GeoIpDatabase::parse(&contents);
+    let entries = database.len();
+    if database.is_empty() {
+        return Err(anyhow!("GeoIP database is empty"));
+    }
+    install(database);
+    Ok(entries)
+}
+
+// 下载最新的数据库（本月文件尚未发布时使用上月文件）
+fn download_database() -> Result<usize> {
+    let client = reqwest::blocking::Client::builder()
+        .timeout(Duration::from_secs(300))
+        .build()
+        .context("Failed to build http client")?;
+
+    let today = Local::now().date_naive().with_day(1).unwrap_or_else(|| Local::now().date_naive());
+    let months = [Some(today), today.checked_sub_months(Months::new(1))];
+    let mut last_error = anyhow!("No download attempted");
+    for month in months.into_iter().flatten() {
+        let url = DBIP_URL.replace("{month}", &month.format("%Y-%m").to_string());
+        let response = match client.get(&url).send().and_then(|r| r.error_for_status()) {
+            Ok(response) => response,
+            Err(e) => {
+                last_error = anyhow!(e).context(format!("Failed to download {}", url));
+                continue;
+            }
+        };
+
+        let compressed = response.bytes().context("Failed to read geoip download")?;
+        let mut contents = String::new();
+        GzDecoder::new(&compressed[..])
+            .read_to_string(&mut contents)
+            .context("Failed to decompress geoip database")?;
+
+        // 先验证内容再替换本地文件
+        let database = GeoIpDatabase::parse(&contents);
+        let entries = database.len();
+        if database.is_empty() {
+            return Err(anyhow!("Downloaded GeoIP database is empty"));
+        }
+        let path = database_path()?;
+        let temp_path = format!("{}.tmp", path);
+        fs::write(&temp_path, contents).context("Failed to write geoip database")?;
+        fs::rename(&temp_path, &path).context("Failed to replace geoip database")?;
+
+        install(database);
+        return Ok(entries);
+    }
+    Err(last_error)
+}
+
+// 在后台更新数据库
+pub fn start_update(logger: Arc<Mutex<Logger>>) {
+    if let Ok(mut updating) = UPDATING.lock() {
+        if *updating {
+            return;
+        }
+        *updating = true;
+    }
+
+    std::thread::spawn(move || {
+        if let Ok(mut logger) = logger.lock() {
+            logger.info("GeoIP", "正在下载GeoIP数据库...");
+        }
+        let result = download_database();
+        if let Ok(mut logger) = logger.lock() {
+            match result {
+                Ok(entries) => logger.info("GeoIP", &format!("GeoIP数据库已更新，共 {} 个地址段", entries)),
+                Err(e) => logger.error("GeoIP", &format!("GeoIP数据库更新失败: {:#}", e)),
+            }
+        }
+        if let Ok(mut updating) = UPDATING.lock() {
+            *updating = false;
+        }
+    });
+}
+
+// 启动时加载本地数据库，不存在或已过期时自动更新
+pub fn init(logger: Arc<Mutex<Logger>>) {
+    std::thread::spawn(move || {
+        let path_exists = database_path().map(|p| Path::new(&p).exists()).unwrap_or(false);
+        if path_exists {
+            match load_from_disk() {
+                Ok(entries) => {
+                    if let Ok(mut logger) = logger.lock() {
+                        logger.info("GeoIP", &format!("已加载GeoIP数据库，共 {} 个地址段", entries));
+                    }
+                }
+                Err(e) => {
+                    if let Ok(mut logger) = logger.lock() {
+                        logger.warning("GeoIP", &format!("加载GeoIP数据库失败: {:#}", e));
+                    }
+                }
+            }
+        }
+
+        let expired = database_modified()
+            .and_then(|modified| modified.elapsed().ok())
+            .map_or(true, |age| age > MAX_AGE);
+        if !is_loaded() || expired {
+            start_update(logger);
+        }
+    });
+}
+
+// 设置页中的GeoIP状态与更新按钮
+pub fn ui(ui: &mut Ui, logger: &Arc<Mutex<Logger>>) {
+    let updating = UPDATING.lock().map(|u| *u).unwrap_or(false);
+    let entries = DATABASE.lock().ok().and_then(|db| db.as_ref().map(|db| db.len()));
+
+    ui.horizontal(|ui| {
+        match entries {
+            Some(entries) => {
+                ui.label(RichText::new(format!("已加载 {} 个地址段", entries)).color(Color32::GREEN));
+            }
+            None => {
+                ui.label(RichText::new("未加载").color(Color32::YELLOW));
+            }
+        }
+        if let Some(modified) = database_modified() {
+            let modified: chrono::DateTime<Local> = modified.into();
+            ui.label(format!("更新于 {}", modified.format("%Y-%m-%d")));
+        }
+
+        if updating {
+            ui.spinner();
+        } else if ui.button("立即更新").clicked() {
+            start_update(Arc::clone(logger));
+        }
+    });
+    ui.label(RichText::new("国家数据库由 DB-IP (db-ip.com) 提供，按CC BY 4.0许可使用，每30天自动更新。").color(Color32::GRAY));
+}
@@ -0,0 +1,252 @@
+use eframe::egui::{self, Color32, Grid, RichText, ScrollArea, Ui};
+use std::fs;
+use std::net::IpAddr;
+use std::path::PathBuf;
+use std::sync::{Arc, Mutex};
+use anyhow::{anyhow, Context, Result};
+
+use crate::dns_rules::{self, CloakingRule};
+use crate::logger::Logger;
+use crate::utils;
+
+// 系统hosts文件路径
+pub fn path() -> PathBuf {
+    #[cfg(target_os = "windows")]
+    {
+        let root = std::env::var("SystemRoot").unwrap_or_else(|_| "C:\\Windows".to_string());
+        PathBuf::from(root).join("System32").join("drivers").join("etc").join("hosts")
+    }
+    #[cfg(not(target_os = "windows"))]
+    {
+        PathBuf::from("/etc/hosts")
+    }
+}
+
+// hosts文件中的一条记录
+#[derive(Clone, Debug)]
+pub struct HostsEntry {
+    pub line: usize, // 所在行号（从0开始），删除时使用
+    pub ip: String,
+    pub names: Vec<String>,
+    pub comment: String,
+}
+
+// 解析hosts文件，跳过注释与无效行
+pub fn parse(contents: &str) -> Vec<HostsEntry> {
+    contents.lines()
+        .enumerate()
+        .filter_map(|(line, text)| {
+            let (data, comment) = match text.split_once('#') {
+                Some((data, comment)) => (data, comment.trim()),
+                None => (text, ""),
+            };
+            let mut fields = data.split_whitespace();
+            let ip = fields.next()?;
+            ip.parse::<IpAddr>().ok()?;
+            let names: Vec<String> = fields.map(str::to_lowercase).collect();
+            if names.is_empty() {
+                return None;
+            }
+            Some(HostsEntry { line, ip: ip.to_string(), names, comment: comment.to_string() })
+        })
+        .collect()
+}
+
+pub fn read() -> Result<Vec<HostsEntry>> {
+    let contents = fs::read_to_string(path()).context("Failed to read hosts file")?;
+    Ok(parse(&contents))
+}
+
+// 在文件末尾添加一行
+pub fn add_entry(ip: &str, name: &str) -> Result<()> {
+    let path = path();
+    let mut contents = fs::read_to_string(&path).context("Failed to read hosts file")?;
+    if !contents.is_empty() && !contents.ends_with('\n') {
+        contents.push_str("\r\n");
+    }
+    contents.push_str(&format!("{} {}\r\n", ip, name));
+    fs::write(&path, contents).context("Failed to write hosts file")
+}
+
+// 删除指定行，行内容与读取时不同（文件已被其他程序修改）时拒绝
+pub fn remove_entry(entry: &HostsEntry) -> Result<()> {
+    let path = path();
+    let contents = fs::read_to_string(&path).context("Failed to read hosts file")?;
+    let current = parse(&contents);
+    let unchanged = current.iter().any(|e| e.line == entry.line && e.ip == entry.ip && e.names == entry.names);
+    if !unchanged {
+        return Err(anyhow!("hosts file changed since it was read, please reload"));
+    }
+    let kept: Vec<&str> = contents.lines()
+        .enumerate()
+        .filter(|(line, _)| *line != entry.line)
+        .map(|(_, text)| text)
+        .collect();
+    fs::write(&path, kept.join("\r\n") + "\r\n").context("Failed to write hosts file")
+}
+
+// hosts文件优先于DNS，与伪装规则匹配同一域名时伪装规则不会生效
+pub fn conflicts<'a>(entries: &'a [HostsEntry], cloaking: &'a [CloakingRule]) -> Vec<(&'a HostsEntry, &'a str, &'a CloakingRule)> {
+    let mut found = Vec::new();
+    for entry in entries {
+        for name in &entry.names {
+            for rule in cloaking.iter().filter(|rule| dns_rules::pattern_matches(&rule.pattern, name)) {
+                found.push((entry, name.as_str(), rule));
+            }
+        }
+    }
+    found
+}
+
+// DNSCrypt页面中的hosts文件面板
+pub struct HostsPanel {
+    logger: Arc<Mutex<Logger>>,
+    entries: Option<Result<Vec<HostsEntry>, String>>, // 展开面板时读取
+    new_ip: String,
+    new_name: String,
+    error: Option<String>,
+}
+
+impl HostsPanel {
+    pub fn new(logger: Arc<Mutex<Logger>>) -> Self {
+        Self {
+            logger,
+            entries: None,
+            new_ip: "0.0.0.0".to_string(),
+            new_name: String::new(),
+            error: None,
+        }
+    }
+
+    fn reload(&mut self) {
+        self.entries = Some(read().map_err(|e| format!("{:#}", e)));
+    }
+
+    fn add(&mut self) {
+        let ip = self.new_ip.trim().to_string();
+        let name = self.new_name.trim().to_lowercase();
+        if ip.parse::<IpAddr>().is_err() {
+            self.error = Some(format!("IP地址无效 \"{}\"", ip));
+            return;
+        }
+        if name.is_empty() || name.contains('*') || dns_rules::validate_pattern(&name).is_err() {
+            self.error = Some(format!("域名无效 \"{}\"（hosts文件不支持通配符）", name));
+            return;
+        }
+        match add_entry(&ip, &name) {
+            Ok(()) => {
+                if let Ok(mut logger) = self.logger.lock() {
+                    logger.info("Hosts", &format!("已添加hosts记录: {} {}", ip, name));
+                }
+                self.new_name.clear();
+                self.error = None;
+            }
+            Err(e) => {
+                if let Ok(mut logger) = self.logger.lock() {
+                    logger.error("Hosts", &format!("添加hosts记录失败: {:#}", e));
+                }
+                self.error = Some(format!("{:#}", e));
+            }
+        }
+        self.reload();
+    }
+
+    fn remove(&mut self, entry: &HostsEntry) {
+        match remove_entry(entry) {
+            Ok(()) => {
+                if let Ok(mut logger) = self.logger.lock() {
+                    logger.info("Hosts", &format!("已删除hosts记录: {} {}", entry.ip, entry.names.join(" ")));
+                }
+                self.error = None;
+            }
+            Err(e) => {
+                if let Ok(mut logger) = self.logger.lock() {
+                    logger.error("Hosts", &format!("删除hosts记录失败: {:#}", e));
+                }
+                self.error = Some(format!("{:#}", e));
+            }
+        }
+        self.reload();
+    }
+
+    pub fn ui(&mut self, ui: &mut Ui, cloaking: &[CloakingRule]) {
+        if self.entries.is_none() {
+            self.reload();
+        }
+        let admin = utils::is_running_as_admin();
+        ui.horizontal(|ui| {
+            ui.label(RichText::new(path().display().to_string()).monospace());
+            if ui.small_button("重新读取").clicked() {
+                self.reload();
+            }
+        });
+        ui.label(RichText::new("hosts文件中的记录优先于DNS查询，包括加密DNS").weak());
+
+        let entries = match &self.entries {
+            Some(Ok(entries)) => entries.clone(),
+            Some(Err(error)) => {
+                ui.label(RichText::new(format!("无法读取hosts文件: {}", error)).color(Color32::RED));
+                return;
+            }
+            None => return,
+        };
+
+        for (entry, name, rule) in conflicts(&entries, cloaking) {
+            ui.label(RichText::new(format!(
+                "⚠ {} 在hosts中指向 {}，伪装规则 \"{} → {}\" 不会生效",
+                name, entry.ip, rule.pattern, rule.target
+            )).color(Color32::YELLOW));
+        }
+
+        let mut removed = None;
+        ScrollArea::vertical()
+            .id_source("hosts_entries")
+            .max_height(200.0)
+            .show(ui, |ui| {
+                Grid::new("hosts_entries_grid")
+                    .num_columns(4)
+                    .striped(true)
+                    .spacing([10.0, 2.0])
+                    .show(ui, |ui| {
+                        for header in ["IP", "域名", "注释", ""] {
+                            ui.label(RichText::new(header).strong());
+                        }
+                        ui.end_row();
+                        for entry in &entries {
+                            ui.label(RichText::new(&entry.ip).monospace());
+                            ui.label(RichText::new(entry.names.join(" ")).monospace());
+                            ui.label(RichText::new(&entry.comment).weak());
+                            if ui.add_enabled(admin, egui::Button::new("✖").small())
+                                .on_hover_text("删除")
+                                .on_disabled_hover_text("修改hosts文件需要管理员权限")
+                                .clicked()
+                            {
+                                removed = Some(entry.clone());
+                            }
+                            ui.end_row();
+                        }
+                    });
+            });
+        if entries.is_empty() {
+            ui.label(RichText::new("hosts文件中没有记录").weak());
+        }
+        if let Some(entry) = removed {
+            self.remove(&entry);
+        }
+
+        if !admin {
+            ui.label(RichText::new("修改hosts文件需要管理员权限").color(Color32::YELLOW));
+            return;
+        }
+        ui.horizontal(|ui| {
+            ui.add(egui::TextEdit::singleline(&mut self.new_ip).hint_text("IP").desired_width(120.0));
+            ui.add(egui::TextEdit::singleline(&mut self.new_name).hint_text("example.com").desired_width(200.0));
+            if ui.button("添加").clicked() {
+                self.add();
+            }
+        });
+        if let Some(error) = &self.error {
+            ui.label(RichText::new(error).color(Color32::RED));
+        }
+    }
+}
@@ -1,15 +1,198 @@
 use eframe::egui::{self, Color32, RichText, Ui, Grid, ScrollArea};
+use eframe::egui::plot::{Legend, Line, Plot, PlotPoints};
+use std::collections::HashMap;
+use std::path::PathBuf;
 use std::sync::{Arc, Mutex};
 use serde::{Deserialize, Serialize};
 
+use crate::i2p_addressbook::AddressbookPanel;
+use crate::i2p_control;
+use crate::i2p_destination::DestinationTool;
+use crate::i2p_key_backup::{self, KeyBackup};
+use crate::i2p_router_settings::RouterSettingsPanel;
+use crate::i2p_sam::{self, SamTunnel, SamTunnelState};
+use crate::i2pd_process::{self, I2pdProcess, I2pdSettings, I2pdState};
 use crate::logger::Logger;
+use crate::ports::{self, PortStatus, Protocol};
+use crate::services::{self, ProcessLauncher};
 use crate::app::I2P_COLOR;
+use crate::utils;
+
+// 隧道配置文件名
+const TUNNELS_CONFIG_FILE: &str = "i2p_tunnels.json";
+
+// 隧道模板：名称、类型、默认端口、目标地址与描述
+const TUNNEL_TEMPLATES: [(&str, TunnelType, u16, &str, &str); 4] = [
+    ("HTTP代理", TunnelType::Client, 4444, "http://i2p-projekt.i2p", "浏览.i2p网站的HTTP代理"),
+    ("IRC", TunnelType::Client, 6668, "irc://irc.postman.i2p", "连接Irc2P网络的IRC隧道"),
+    ("SMTP邮件", TunnelType::Client, 7659, "smtp.postman.i2p:25", "通过I2P发送邮件（mail.i2p）"),
+    ("网站(eepsite)", TunnelType::Server, 8080, "http://127.0.0.1:8080", "把本机的网站发布为.i2p站点"),
+];
+
+// 新建隧道时检查端口使用的模块名（与已有隧道登记的"I2P"区分，以便发现重复端口）
+const NEW_TUNNEL_MODULE: &str = "I2P新隧道";
 
 // I2P隧道类型
 #[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
 pub enum TunnelType {
     Client,
     Server,
+    Socks, // 通过SAM提供的SOCKS5代理，可转发UDP
+}
+
+impl TunnelType {
+    pub fn label(&self) -> &'static str {
+        match self {
+            TunnelType::Client => "客户端",
+            TunnelType::Server => "服务端",
+            TunnelType::Socks => "SOCKS代理",
+        }
+    }
+    
+    // 是否在本地端口监听（服务端隧道的端口属于被转发的本地服务）
+    pub fn listens_locally(&self) -> bool {
+        *self != TunnelType::Server
+    }
+}
+
+// 隧道的跳数与数量（I2CP选项），跳数越多匿名性越好，延迟也越大
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct TunnelOptions {
+    pub inbound_length: u8,
+    pub outbound_length: u8,
+    pub inbound_length_variance: i8, // 正值为在length上随机增减的范围，负值为只随机增加
+    pub outbound_length_variance: i8,
+    pub inbound_quantity: u8,        // 同时保持的隧道数
+    pub outbound_quantity: u8,
+    #[serde(default)]
+    pub custom: Vec<(String, String)>, // 其他I2CP选项，如i2cp.reduceOnIdle
+}
+
+// 由跳数设置或隧道配置本身决定的选项，不能在其他选项中覆盖
+const RESERVED_OPTIONS: [&str; 14] = [
+    "inbound.length", "outbound.length", "inbound.lengthVariance", "outbound.lengthVariance",
+    "inbound.quantity", "outbound.quantity", "inbound.nickname",
+    "type", "address", "port", "host", "keys", "destination", "destinationport",
+];
+
+// 检查一个自定义选项，选项会原样写入tunnels.conf与SAM命令
+fn check_custom_option(key: &str, value: &str) -> Result<(), String> {
+    if key.is_empty() || !key.chars().all(|c| c.is_ascii_alphanumeric() || matches!(c, '.' | '_' | '-')) {
+        return Err(format!("选项名无效: '{}'，只能包含字母、数字与 . _ -", key));
+    }
+    if RESERVED_OPTIONS.iter().any(|reserved| reserved.eq_ignore_ascii_case(key)) {
+        return Err(format!("{} 由隧道设置决定，不能在此修改", key));
+    }
+    if value.is_empty() || value.chars().any(|c| c.is_whitespace() || matches!(c, '=' | '#' | ';' | '"')) {
+        return Err(format!("{} 的值无效，不能为空或包含空白、= # ; \"", key));
+    }
+    Ok(())
+}
+
+impl Default for TunnelOptions {
+    // 与i2pd的默认值相同
+    fn default() -> Self {
+        Self {
+            inbound_length: 3,
+            outbound_length: 3,
+            inbound_length_variance: 0,
+            outbound_length_variance: 0,
+            inbound_quantity: 5,
+            outbound_quantity: 5,
+            custom: Vec::new(),
+        }
+    }
+}
+
+impl TunnelOptions {
+    // I2CP选项名与值，用于tunnels.conf与SAM会话
+    pub fn i2cp_options(&self) -> Vec<(String, String)> {
+        let mut options = vec![
+            ("inbound.length".to_string(), self.inbound_length.to_string()),
+            ("outbound.length".to_string(), self.outbound_length.to_string()),
+            ("inbound.lengthVariance".to_string(), self.inbound_length_variance.to_string()),
+            ("outbound.lengthVariance".to_string(), self.outbound_length_variance.to_string()),
+            ("inbound.quantity".to_string(), self.inbound_quantity.to_string()),
+            ("outbound.quantity".to_string(), self.outbound_quantity.to_string()),
+        ];
+        // 配置文件被手动修改时跳过无效的选项
+        options.extend(self.custom.iter()
+            .map(|(key, value)| (key.trim().to_string(), value.trim().to_string()))
+            .filter(|(key, value)| check_custom_option(key, value).is_ok()));
+        options
+    }
+    
+    // 去掉自定义选项两端的空白
+    fn normalized(mut self) -> Self {
+        for (key, value) in &mut self.custom {
+            *key = key.trim().to_string();
+            *value = value.trim().to_string();
+        }
+        self
+    }
+    
+    // 检查所有自定义选项，包括重复的选项名
+    pub fn validate(&self) -> Result<(), String> {
+        for (index, (key, value)) in self.custom.iter().enumerate() {
+            let key = key.trim();
+            check_custom_option(key, value.trim())?;
+            if self.custom[..index].iter().any(|(other, _)| other.trim() == key) {
+                return Err(format!("选项 {} 重复", key));
+            }
+        }
+        Ok(())
+    }
+    
+    // 编辑跳数与数量
+    fn ui(&mut self, ui: &mut Ui) {
+        Grid::new("tunnel_options_grid").num_columns(3).spacing([10.0, 4.0]).show(ui, |ui| {
+            ui.label("");
+            ui.label("入站");
+            ui.label("出站");
+            ui.end_row();
+            
+            ui.label("跳数:").on_hover_text("0跳没有匿名性，只适合测试；跳数越多越匿名，延迟也越大");
+            ui.add(egui::Slider::new(&mut self.inbound_length, 0..=7));
+            ui.add(egui::Slider::new(&mut self.outbound_length, 0..=7));
+            ui.end_row();
+            
+            ui.label("跳数浮动:").on_hover_text("正值表示每条隧道的跳数在设定值上随机增减，负值表示只随机增加，使隧道长度更难被推测");
+            ui.add(egui::Slider::new(&mut self.inbound_length_variance, -2..=2));
+            ui.add(egui::Slider::new(&mut self.outbound_length_variance, -2..=2));
+            ui.end_row();
+            
+            ui.label("隧道数:").on_hover_text("同时保持的隧道数，更多的隧道可提高带宽与可靠性");
+            ui.add(egui::Slider::new(&mut self.inbound_quantity, 1..=16));
+            ui.add(egui::Slider::new(&mut self.outbound_quantity, 1..=16));
+            ui.end_row();
+        });
+        if self.inbound_length == 0 || self.outbound_length == 0 {
+            ui.label(RichText::new("⚠ 0跳隧道不提供匿名性").color(Color32::YELLOW));
+        }
+        
+        ui.separator();
+        ui.label("其他I2CP选项:").on_hover_text("例如 i2cp.reduceOnIdle = true、i2cp.leaseSetEncType = 4，参见i2pd文档");
+        let mut removed = None;
+        for (index, (key, value)) in self.custom.iter_mut().enumerate() {
+            ui.horizontal(|ui| {
+                ui.add(egui::TextEdit::singleline(key).hint_text("选项名").desired_width(180.0));
+                ui.label("=");
+                ui.add(egui::TextEdit::singleline(value).hint_text("值").desired_width(120.0));
+                if ui.small_button("✖").on_hover_text("删除").clicked() {
+                    removed = Some(index);
+                }
+            });
+        }
+        if let Some(index) = removed {
+            self.custom.remove(index);
+        }
+        if ui.small_button("添加选项").clicked() {
+            self.custom.push((String::new(), String::new()));
+        }
+        if let Err(error) = self.validate() {
+            ui.label(RichText::new(error).color(Color32::RED));
+        }
+    }
 }
 
 // I2P隧道结构
@@ -22,6 +205,10 @@ pub struct I2PTunnel {
     pub destination: String,
     pub enabled: bool,
     pub description: String,
+    #[serde(default)]
+    pub options: TunnelOptions,
+    #[serde(default)]
+    pub udp: bool, // SOCKS代理隧道是否支持UDP ASSOCIATE
 }
 
 impl I2PTunnel {
@@ -34,8 +221,35 @@ impl I2PTunnel {
             destination: destination.to_string(),
             enabled: true,
             description: String::new(),
+            options: TunnelOptions::default(),
+            udp: false,
         }
     }
+    
+    // HTTP/SOCKS代理隧道只能写入i2pd的tunnels.conf，其余隧道运行时通过SAM注册
+    pub fn is_proxy(&self) -> bool {
+        let scheme = self.destination.trim().split_once("://").map(|(scheme, _)| scheme.to_lowercase());
+        self.tunnel_type == TunnelType::Client && matches!(scheme.as_deref(), Some("http" | "socks"))
+    }
+}
+
+// v1: 引入版本号，格式与之前相同
+impl utils::VersionedConfig for Vec<I2PTunnel> {
+    const VERSION: u32 = 1;
+}
+
+// 服务端隧道私钥的导出/导入对话框
+enum KeyDialogMode {
+    Export,
+    Import(PathBuf, KeyBackup), // 已选择的备份文件
+}
+
+struct KeyDialog {
+    tunnel_id: usize,
+    mode: KeyDialogMode,
+    password: String,
+    confirm: String,
+    error: Option<String>,
 }
 
 // I2P模块结构
@@ -49,14 +263,39 @@ pub struct I2PModule {
     new_tunnel_type: TunnelType,
     new_tunnel_port: u16,
     new_tunnel_destination: String,
+    new_tunnel_description: String,
+    new_tunnel_udp: bool,
+    new_tunnel_options: TunnelOptions,
+    editing_tunnel: Option<usize>, // 正在编辑的隧道，None表示添加新隧道
     edit_mode: bool,
+    form_error: Option<String>,
+    port_conflict: Option<(usize, String, Option<u16>)>, // 启用失败的隧道、原因与建议的空闲端口
     connection_status: String,
     bandwidth_in: u32,  // KB/s
     bandwidth_out: u32, // KB/s
+    history_minutes: u64, // 带宽曲线显示的时长
+    launcher: Arc<dyn ProcessLauncher>,
+    process: Option<I2pdProcess>,
+    sam_tunnels: HashMap<usize, SamTunnel>, // 已通过SAM注册的隧道
+    conf_tunnels: Vec<usize>,               // 启动i2pd时写入tunnels.conf的代理隧道
+    addressbook: AddressbookPanel,
+    router_settings: RouterSettingsPanel,
+    tor_socks_port: Option<u16>, // Tor运行时的SOCKS端口，用于通过Tor连接I2P
+    destination_tool: DestinationTool,
+    key_dialog: Option<KeyDialog>,
+    key_message: Option<Result<String, String>>, // 最近一次导出/导入的结果
 }
 
 impl I2PModule {
     pub fn new(logger: Arc<Mutex<Logger>>) -> Self {
+        let launcher = services::launcher(Arc::clone(&logger));
+        Self::with_launcher(logger, launcher)
+    }
+    
+    // 使用指定的启动器创建模块（测试模式下不会真正启动i2pd）
+    pub fn with_launcher(logger: Arc<Mutex<Logger>>, launcher: Arc<dyn ProcessLauncher>) -> Self {
+        let addressbook = AddressbookPanel::new(Arc::clone(&logger));
+        let router_settings = RouterSettingsPanel::new(Arc::clone(&logger));
         let mut module = Self {
             enabled: false,
             tunnels: Vec::new(),
@@ -67,14 +306,41 @@ impl I2PModule {
             new_tunnel_type: TunnelType::Client,
             new_tunnel_port: 0,
             new_tunnel_destination: String::new(),
+            new_tunnel_description: String::new(),
+            new_tunnel_udp: false,
+            new_tunnel_options: TunnelOptions::default(),
+            editing_tunnel: None,
             edit_mode: false,
+            form_error: None,
+            port_conflict: None,
             connection_status: "未连接".to_string(),
             bandwidth_in: 0,
             bandwidth_out: 0,
+            history_minutes: 10,
+            launcher,
+            process: None,
+            sam_tunnels: HashMap::new(),
+            conf_tunnels: Vec::new(),
+            addressbook,
+            router_settings,
+            tor_socks_port: None,
+            destination_tool: DestinationTool::new(),
+            key_dialog: None,
+            key_message: None,
         };
         
-        // 添加一些示例隧道
-        module.add_example_tunnels();
+        // 首次运行（尚无配置文件）时添加示例隧道
+        match module.load_tunnels() {
+            Some(tunnels) => {
+                module.next_tunnel_id = tunnels.iter().map(|t| t.id).max().unwrap_or(0) + 1;
+                module.tunnels = tunnels;
+            }
+            None => {
+                module.add_example_tunnels();
+                module.save_tunnels();
+            }
+        }
+        module.reserve_ports();
         
         // 记录模块初始化日志
         if let Ok(mut logger) = module.logger.lock() {
@@ -84,6 +350,22 @@ impl I2PModule {
         module
     }
     
+    // 读取保存的隧道，没有配置文件或读取失败时返回None
+    fn load_tunnels(&self) -> Option<Vec<I2PTunnel>> {
+        utils::load_saved_config(TUNNELS_CONFIG_FILE)
+    }
+    
+    // 保存隧道到配置文件
+    fn save_tunnels(&self) {
+        let result = utils::get_config_path(TUNNELS_CONFIG_FILE)
+            .and_then(|path| utils::save_versioned_config(&self.tunnels, &path));
+        if let Err(e) = result {
+            if let Ok(mut logger) = self.logger.lock() {
+                logger.error("I2P", &format!("保存I2P隧道失败: {}", e));
+            }
+        }
+    }
+    
     // 添加示例隧道
     fn add_example_tunnels(&mut self) {
         // 添加一些示例I2P隧道
@@ -128,6 +410,266 @@ impl I2PModule {
         }
         self.tunnels.push(tunnel);
         self.next_tunnel_id += 1;
+        self.save_tunnels();
+        self.reserve_ports();
+    }
+    
+    // 保存对已有隧道的修改，运行中的SAM隧道会按新设置重新注册
+    fn update_tunnel(&mut self, tunnel: I2PTunnel) {
+        let id = tunnel.id;
+        let proxy = tunnel.is_proxy();
+        match self.tunnels.iter_mut().find(|t| t.id == id) {
+            Some(existing) => *existing = tunnel,
+            None => return,
+        }
+        if let Ok(mut logger) = self.logger.lock() {
+            if proxy && self.process.is_some() {
+                logger.info("I2P", &format!("隧道 #{} 已修改，重启I2P后生效", id));
+            } else {
+                logger.info("I2P", &format!("隧道 #{} 已修改", id));
+            }
+        }
+        self.sam_tunnels.remove(&id);
+        self.save_tunnels();
+        self.reserve_ports();
+    }
+    
+    // 选择备份文件后打开导入对话框
+    fn pick_key_backup(&mut self, tunnel_id: usize) {
+        let path = match rfd::FileDialog::new().add_filter("JSON", &["json"]).pick_file() {
+            Some(path) => path,
+            None => return,
+        };
+        match i2p_key_backup::read(&path) {
+            Ok(backup) => {
+                self.key_dialog = Some(KeyDialog {
+                    tunnel_id,
+                    mode: KeyDialogMode::Import(path, backup),
+                    password: String::new(),
+                    confirm: String::new(),
+                    error: None,
+                });
+            }
+            Err(e) => self.key_message = Some(Err(format!("无法读取密钥备份: {:#}", e))),
+        }
+    }
+    
+    // 执行导出或导入，成功时返回提示信息
+    fn run_key_dialog(&mut self, dialog: &KeyDialog) -> Result<String, String> {
+        let tunnel = self.tunnels.iter().find(|t| t.id == dialog.tunnel_id).cloned().ok_or("隧道已被删除")?;
+        match &dialog.mode {
+            KeyDialogMode::Export => {
+                if dialog.password.chars().count() < i2p_key_backup::MIN_PASSWORD_LEN {
+                    return Err(format!("密码至少需要{}个字符", i2p_key_backup::MIN_PASSWORD_LEN));
+                }
+                if dialog.password != dialog.confirm {
+                    return Err("两次输入的密码不一致".to_string());
+                }
+                let path = rfd::FileDialog::new()
+                    .add_filter("JSON", &["json"])
+                    .set_file_name(&format!("i2p_keys_{}.json", tunnel.name))
+                    .save_file()
+                    .ok_or("已取消")?;
+                let address = i2p_key_backup::export(tunnel.id, &tunnel.name, &path, &dialog.password)
+                    .map_err(|e| format!("导出失败: {:#}", e))?;
+                if let Ok(mut logger) = self.logger.lock() {
+                    logger.info("I2P", &format!("已导出隧道 '{}' 的密钥 ({}) 到 {}", tunnel.name, address, path.display()));
+                }
+                Ok(format!("已导出 {} 的密钥，请妥善保管备份文件与密码", address))
+            }
+            KeyDialogMode::Import(_, backup) => {
+                let address = i2p_key_backup::import(tunnel.id, backup, &dialog.password)
+                    .map_err(|e| format!("导入失败: {:#}", e))?;
+                if let Ok(mut logger) = self.logger.lock() {
+                    logger.info("I2P", &format!("隧道 '{}' 已导入密钥，地址为 {}", tunnel.name, address));
+                }
+                // 重新注册隧道以使用导入的密钥
+                self.sam_tunnels.remove(&tunnel.id);
+                Ok(format!("已导入密钥，隧道地址为 {}", address))
+            }
+        }
+    }
+    
+    fn render_key_dialog(&mut self, ctx: &egui::Context) {
+        let mut dialog = match self.key_dialog.take() {
+            Some(dialog) => dialog,
+            None => return,
+        };
+        let title = match dialog.mode {
+            KeyDialogMode::Export => "导出隧道密钥",
+            KeyDialogMode::Import(..) => "导入隧道密钥",
+        };
+        let current = i2p_sam::read_keys(dialog.tunnel_id).and_then(|key| i2p_sam::b32_address(&key));
+        let mut open = true;
+        let mut submit = false;
+        let mut cancel = false;
+        egui::Window::new(title)
+            .open(&mut open)
+            .collapsible(false)
+            .resizable(false)
+            .show(ctx, |ui| {
+                match &dialog.mode {
+                    KeyDialogMode::Export => {
+                        ui.label("私钥决定了服务端隧道的I2P地址，备份文件将用密码加密。");
+                        ui.label(RichText::new("忘记密码将无法恢复备份").color(Color32::YELLOW));
+                    }
+                    KeyDialogMode::Import(path, backup) => {
+                        ui.label(format!("文件: {}", path.display()));
+                        ui.label(format!("备份的隧道: {}", backup.name));
+                        ui.label(RichText::new(format!("地址: {}", backup.address)).monospace());
+                        if let Some(current) = current.as_ref().filter(|current| **current != backup.address) {
+                            ui.label(RichText::new(format!("⚠ 当前地址 {} 将被替换，如需保留请先导出", current)).color(Color32::YELLOW));
+                        }
+                    }
+                }
+                Grid::new("i2p_key_dialog_grid").num_columns(2).spacing([10.0, 4.0]).show(ui, |ui| {
+                    ui.label("密码:");
+                    ui.add(egui::TextEdit::singleline(&mut dialog.password).password(true));
+                    ui.end_row();
+                    if let KeyDialogMode::Export = dialog.mode {
+                        ui.label("确认密码:");
+                        ui.add(egui::TextEdit::singleline(&mut dialog.confirm).password(true));
+                        ui.end_row();
+                    }
+                });
+                if let Some(error) = &dialog.error {
+                    ui.label(RichText::new(error).color(Color32::RED));
+                }
+                ui.horizontal(|ui| {
+                    if ui.button("取消").clicked() {
+                        cancel = true;
+                    }
+                    let label = match dialog.mode {
+                        KeyDialogMode::Export => "导出...",
+                        KeyDialogMode::Import(..) => "导入",
+                    };
+                    if ui.button(label).clicked() {
+                        submit = true;
+                    }
+                });
+            });
+        if !open || cancel {
+            return;
+        }
+        if submit {
+            match self.run_key_dialog(&dialog) {
+                Ok(message) => {
+                    self.key_message = Some(Ok(message));
+                    return;
+                }
+                Err(error) => dialog.error = Some(error),
+            }
+        }
+        self.key_dialog = Some(dialog);
+    }
+    
+    // 清空添加/编辑对话框
+    fn reset_form(&mut self) {
+        self.new_tunnel_name.clear();
+        self.new_tunnel_type = TunnelType::Client;
+        self.new_tunnel_destination.clear();
+        self.new_tunnel_description.clear();
+        self.new_tunnel_udp = false;
+        self.new_tunnel_options = TunnelOptions::default();
+        self.new_tunnel_port = 0;
+        self.editing_tunnel = None;
+        self.form_error = None;
+    }
+    
+    // 用已有隧道或模板填充添加/编辑对话框
+    fn fill_form(&mut self, tunnel: &I2PTunnel) {
+        self.new_tunnel_name = tunnel.name.clone();
+        self.new_tunnel_type = tunnel.tunnel_type.clone();
+        self.new_tunnel_port = tunnel.local_port;
+        self.new_tunnel_destination = tunnel.destination.clone();
+        self.new_tunnel_description = tunnel.description.clone();
+        self.new_tunnel_udp = tunnel.udp;
+        self.new_tunnel_options = tunnel.options.clone();
+        self.form_error = None;
+    }
+    
+    // 以已有隧道或模板为基础打开添加对话框，监听本地端口的隧道换用空闲端口
+    fn open_new_form(&mut self, base: &I2PTunnel) {
+        self.reset_form();
+        self.fill_form(base);
+        if base.tunnel_type.listens_locally() {
+            self.new_tunnel_port = ports::suggest_free_port(NEW_TUNNEL_MODULE, "127.0.0.1", base.local_port, Protocol::Tcp)
+                .unwrap_or(base.local_port);
+        }
+        self.edit_mode = true;
+    }
+    
+    // 复制隧道（服务端隧道的副本会生成新的I2P地址）
+    fn clone_tunnel(&mut self, tunnel_id: usize) {
+        let mut copy = match self.tunnels.iter().find(|t| t.id == tunnel_id) {
+            Some(tunnel) => tunnel.clone(),
+            None => return,
+        };
+        copy.name = format!("{} 副本", copy.name);
+        self.open_new_form(&copy);
+    }
+    
+    // 本隧道当前是否已在监听本地端口（通过SAM注册或写入了tunnels.conf）
+    fn is_listening(&self, tunnel_id: usize) -> bool {
+        self.sam_tunnels.contains_key(&tunnel_id) || self.conf_tunnels.contains(&tunnel_id)
+    }
+    
+    // 检查客户端隧道的本地端口，冲突时返回原因与建议的空闲端口
+    fn check_tunnel_port(&self, tunnel_id: Option<usize>, port: u16) -> Option<(String, Option<u16>)> {
+        let existing = tunnel_id.and_then(|id| self.tunnels.iter().find(|t| t.id == id));
+        let reason = if let Some(other) = self.tunnels.iter().find(|t| {
+            Some(t.id) != tunnel_id && t.enabled && t.tunnel_type.listens_locally() && t.local_port == port
+        }) {
+            format!("端口 {} 已被隧道 '{}' 使用", port, other.name)
+        } else if existing.map_or(false, |t| t.local_port == port && self.is_listening(t.id)) {
+            return None;
+        } else {
+            // 其他隧道已在上面检查，这里忽略I2P登记的端口
+            match ports::check_now("I2P", "127.0.0.1", port, Protocol::Tcp) {
+                PortStatus::Free | PortStatus::Checking => return None,
+                PortStatus::InUse(Some(owner)) => format!("端口 {} 已被 {} 占用", port, owner.describe()),
+                PortStatus::InUse(None) => format!("端口 {} 已被其他程序占用", port),
+                PortStatus::Reserved(other) => format!("端口 {} 与{}的端口冲突", port, other),
+            }
+        };
+        // 以新隧道的身份查找，跳过所有隧道已使用的端口
+        let suggestion = ports::suggest_free_port(NEW_TUNNEL_MODULE, "127.0.0.1", port, Protocol::Tcp);
+        Some((reason, suggestion))
+    }
+    
+    // 启用隧道，客户端隧道的端口被占用时不启用并提示建议端口
+    fn enable_tunnel(&mut self, tunnel_id: usize) {
+        let port = match self.tunnels.iter().find(|t| t.id == tunnel_id) {
+            Some(tunnel) if tunnel.tunnel_type.listens_locally() => Some(tunnel.local_port),
+            Some(_) => None,
+            None => return,
+        };
+        if let Some((reason, suggestion)) = port.and_then(|port| self.check_tunnel_port(Some(tunnel_id), port)) {
+            if let Ok(mut logger) = self.logger.lock() {
+                logger.warning("I2P", &format!("无法启用隧道 #{}: {}", tunnel_id, reason));
+            }
+            self.port_conflict = Some((tunnel_id, reason, suggestion));
+            return;
+        }
+        if let Some(tunnel) = self.tunnels.iter_mut().find(|t| t.id == tunnel_id) {
+            tunnel.enabled = true;
+        }
+        self.port_conflict = None;
+        self.save_tunnels();
+    }
+    
+    // 登记客户端隧道的本地端口与i2pd的控制台、SAM（含UDP）、I2PControl端口，避免与其他模块冲突
+    // （服务端隧道的端口属于被转发的本地服务，不由I2P监听）
+    fn reserve_ports(&self) {
+        let mut reserved: Vec<(u16, Protocol)> = self.tunnels.iter()
+            .filter(|t| t.tunnel_type.listens_locally())
+            .map(|t| (t.local_port, Protocol::Tcp))
+            .collect();
+        reserved.push((i2pd_process::CONSOLE_PORT, Protocol::Tcp));
+        reserved.push((i2pd_process::SAM_PORT, Protocol::Tcp));
+        reserved.push((i2pd_process::SAM_UDP_PORT, Protocol::Udp));
+        reserved.push((i2p_control::I2PCONTROL_PORT, Protocol::Tcp));
+        ports::reserve("I2P", reserved);
     }
     
     // 删除隧道
@@ -139,10 +681,17 @@ impl I2PModule {
             if let Ok(mut logger) = self.logger.lock() {
                 logger.info("I2P", &format!("删除隧道: {}", tunnel_name));
             }
-            self.tunnels.remove(index);
+            let tunnel = self.tunnels.remove(index);
+            // 注销隧道，服务端隧道的私钥随之删除
+            self.sam_tunnels.remove(&id);
+            if tunnel.tunnel_type == TunnelType::Server {
+                i2p_sam::remove_keys(id);
+            }
             if self.selected_tunnel == Some(id) {
                 self.selected_tunnel = None;
             }
+            self.save_tunnels();
+            self.reserve_ports();
         }
     }
     
@@ -163,37 +712,319 @@ impl I2PModule {
         // 更新状态
         self.enabled = new_enabled;
         self.connection_status = if new_enabled { "正在连接..." } else { "未连接" }.to_string();
+        self.bandwidth_in = 0;
+        self.bandwidth_out = 0;
         
-        // 在实际应用中，这里会启动或停止I2P服务
+        // 启动或停止i2pd，进程由进程监控负责崩溃时自动重启
+        self.sam_tunnels.clear();
+        self.conf_tunnels.clear();
+        if let Some(process) = self.process.take() {
+            process.stop();
+        }
+        let tor_proxy = self.router_settings.settings().tor_proxy;
+        if new_enabled && tor_proxy && self.tor_socks_port.is_none() {
+            if let Ok(mut logger) = self.logger.lock() {
+                logger.error("I2P", "已设置通过Tor连接I2P网络，但Tor未运行，请先启动Tor");
+            }
+            self.enabled = false;
+            self.connection_status = "需要先启动Tor".to_string();
+            return;
+        }
         if new_enabled {
-            // 在实际应用中，这里会有异步连接逻辑
-            // 模拟连接成功
-            self.connection_status = "已连接".to_string();
-            // 模拟带宽数据
-            self.bandwidth_in = 128;
-            self.bandwidth_out = 64;
-        } else {
-            // 重置带宽数据
-            self.bandwidth_in = 0;
-            self.bandwidth_out = 0;
+            let settings = I2pdSettings {
+                tunnels: self.tunnels.iter().filter(|t| t.enabled && t.is_proxy()).cloned().collect(),
+                console_port: i2pd_process::CONSOLE_PORT,
+                sam_port: i2pd_process::SAM_PORT,
+                control_port: i2p_control::I2PCONTROL_PORT,
+                control_password: format!("{:032x}", rand::random::<u128>()),
+                subscriptions: self.addressbook.subscription_urls(),
+                router: self.router_settings.settings().clone(),
+                tor_socks_port: if tor_proxy { self.tor_socks_port } else { None },
+            };
+            match I2pdProcess::start(self.launcher.as_ref(), Arc::clone(&self.logger), &settings) {
+                Ok(process) => {
+                    self.conf_tunnels = settings.tunnels.iter().map(|t| t.id).collect();
+                    self.process = Some(process);
+                }
+                Err(e) => {
+                    if let Ok(mut logger) = self.logger.lock() {
+                        logger.error("I2P", &format!("启动I2P失败: {:#}", e));
+                    }
+                    self.enabled = false;
+                    self.connection_status = "启动失败".to_string();
+                }
+            }
         }
     }
     
-    // 打开I2P控制台
-    fn open_i2p_console(&mut self) {
-        if let Ok(mut logger) = self.logger.lock() {
-            logger.info("I2P", "正在打开I2P控制台");
+    // 每帧调用：根据i2pd的状态更新连接状态与带宽（切换到其他页面时也保持更新）
+    pub fn tick(&mut self) {
+        let process = match &self.process {
+            Some(process) => process,
+            None => return,
+        };
+        let state = process.state();
+        self.connection_status = match &state {
+            I2pdState::Starting => "正在连接...".to_string(),
+            I2pdState::Integrating { .. } => "正在加入网络...".to_string(),
+            I2pdState::Ready => "已连接".to_string(),
+            I2pdState::Restarting { .. } => "正在重启...".to_string(),
+            I2pdState::Failed(_) => "连接失败".to_string(),
+        };
+        let status = process.router_status().unwrap_or_default();
+        self.bandwidth_in = status.in_kbps.round() as u32;
+        self.bandwidth_out = status.out_kbps.round() as u32;
+        if state == I2pdState::Ready {
+            self.sync_sam_tunnels();
         }
-        
-        // 在实际应用中，这里会打开I2P控制台网页
-        // 例如使用webbrowser库打开http://127.0.0.1:7657/
-        if let Err(e) = std::process::Command::new("cmd")
-            .args(["/c", "start", "http://127.0.0.1:7657/"])
-            .spawn() {
+    }
+    
+    // 让SAM中注册的隧道与列表一致：注册新启用的隧道，注销已禁用或删除的隧道
+    fn sync_sam_tunnels(&mut self) {
+        let wanted: Vec<&I2PTunnel> = self.tunnels.iter().filter(|t| t.enabled && !t.is_proxy()).collect();
+        self.sam_tunnels.retain(|id, _| wanted.iter().any(|t| t.id == *id));
+        for tunnel in wanted {
+            if !self.sam_tunnels.contains_key(&tunnel.id) {
+                let registered = SamTunnel::start(Arc::clone(&self.logger), i2pd_process::SAM_PORT, tunnel);
+                self.sam_tunnels.insert(tunnel.id, registered);
+            }
+        }
+    }
+    
+    // 隧道在路由器中的状态文字与颜色
+    fn tunnel_status(&self, tunnel: &I2PTunnel) -> (String, Color32) {
+        if self.process.is_none() || !tunnel.enabled {
+            return ("-".to_string(), Color32::GRAY);
+        }
+        if tunnel.is_proxy() {
+            return if self.conf_tunnels.contains(&tunnel.id) {
+                ("运行中".to_string(), Color32::GREEN)
+            } else {
+                ("重启I2P后生效".to_string(), Color32::YELLOW)
+            };
+        }
+        match self.sam_tunnels.get(&tunnel.id).map(SamTunnel::state) {
+            Some(SamTunnelState::Active) => ("运行中".to_string(), Color32::GREEN),
+            Some(SamTunnelState::Connecting) => ("正在注册...".to_string(), Color32::YELLOW),
+            Some(SamTunnelState::Failed(_)) => ("注册失败，等待重试".to_string(), Color32::RED),
+            None => ("等待I2P就绪".to_string(), Color32::YELLOW),
+        }
+    }
+    
+    // 由主程序每帧同步Tor的SOCKS端口
+    pub fn set_tor_socks_port(&mut self, port: Option<u16>) {
+        if self.tor_socks_port == port {
+            return;
+        }
+        self.tor_socks_port = port;
+        if self.process.is_some() && self.router_settings.settings().tor_proxy {
             if let Ok(mut logger) = self.logger.lock() {
-                logger.error("I2P", &format!("无法打开I2P控制台: {}", e));
+                match port {
+                    Some(_) => logger.info("I2P", "Tor端口已变化，重启I2P后生效"),
+                    None => logger.warning("I2P", "Tor已停止，I2P暂时无法建立新的连接"),
+                }
+            }
+        }
+    }
+    
+    // 按指定状态启用/禁用I2P
+    pub fn set_enabled(&mut self, enabled: bool) {
+        if self.enabled != enabled {
+            self.toggle_i2p();
+        }
+    }
+    
+    // 最近一段时间的入站/出站速率曲线
+    fn render_bandwidth(&mut self, ui: &mut Ui) {
+        ui.horizontal(|ui| {
+            ui.label(format!("入站: {} KB/s", self.bandwidth_in));
+            ui.label(format!("出站: {} KB/s", self.bandwidth_out));
+            ui.with_layout(egui::Layout::right_to_left(egui::Align::Center), |ui| {
+                egui::ComboBox::from_id_source("i2p_history_minutes")
+                    .selected_text(format!("最近 {} 分钟", self.history_minutes))
+                    .show_ui(ui, |ui| {
+                        for minutes in [5, 10, i2pd_process::HISTORY_MINUTES] {
+                            ui.selectable_value(&mut self.history_minutes, minutes, format!("最近 {} 分钟", minutes));
+                        }
+                    });
+            });
+        });
+        
+        let history = self.process.as_ref().map(|p| p.bandwidth_history()).unwrap_or_default();
+        let interval = i2pd_process::STATUS_INTERVAL.as_secs_f64() / 60.0;
+        let shown = (self.history_minutes as f64 / interval) as usize;
+        let start = history.len().saturating_sub(shown);
+        // 横轴为距现在的分钟数
+        let points = |select: fn(&(f32, f32)) -> f32| -> Vec<[f64; 2]> {
+            let count = history.len() - start;
+            history[start..].iter()
+                .enumerate()
+                .map(|(i, sample)| [-((count - 1 - i) as f64) * interval, select(sample) as f64])
+                .collect()
+        };
+        let rate_in = points(|s| s.0);
+        let rate_out = points(|s| s.1);
+        Plot::new("i2p_bandwidth_plot")
+            .height(140.0)
+            .legend(Legend::default())
+            .allow_drag(false)
+            .allow_zoom(false)
+            .allow_scroll(false)
+            .include_y(0.0)
+            .include_x(-(self.history_minutes as f64))
+            .include_x(0.0)
+            .x_axis_formatter(|value, _| format!("{:.0} 分钟", value.abs()))
+            .y_axis_formatter(|value, _| format!("{:.0} KB/s", value))
+            .show(ui, |plot_ui| {
+                plot_ui.line(Line::new(PlotPoints::from(rate_in)).name("入站").color(Color32::GREEN));
+                plot_ui.line(Line::new(PlotPoints::from(rate_out)).name("出站").color(Color32::LIGHT_BLUE));
+            });
+        ui.ctx().request_repaint_after(i2pd_process::STATUS_INTERVAL);
+    }
+    
+    // 路由器状态面板，数据来自I2PControl与网页控制台
+    fn render_router_status(&self, ui: &mut Ui) {
+        let process = match &self.process {
+            Some(process) => process,
+            None => return,
+        };
+        let state = process.state();
+        let status = process.router_status().unwrap_or_default();
+        let (state_text, state_color) = match &state {
+            I2pdState::Starting => ("正在启动".to_string(), Color32::YELLOW),
+            I2pdState::Integrating { .. } => ("正在加入网络".to_string(), Color32::YELLOW),
+            I2pdState::Ready => ("运行中".to_string(), Color32::GREEN),
+            I2pdState::Restarting { attempt, delay } => (format!("第{}次重启，{}秒后", attempt, delay.as_secs()), Color32::YELLOW),
+            I2pdState::Failed(reason) => (reason.clone(), Color32::RED),
+        };
+        let network = match status.network.as_str() {
+            "OK" => "正常",
+            "Testing" => "测试中",
+            "Firewalled" => "被防火墙阻挡（只能主动连接）",
+            "Proxy" => "通过代理连接",
+            "Mesh" => "Mesh网络",
+            "" => "-",
+            _ => "未知",
+        };
+        
+        Grid::new("i2p_router_status_grid")
+            .num_columns(4)
+            .spacing([20.0, 4.0])
+            .show(ui, |ui| {
+                ui.label("路由器:");
+                ui.label(RichText::new(state_text).color(state_color));
+                ui.label("网络状态:");
+                ui.label(network);
+                ui.end_row();
+                
+                ui.label("版本:");
+                ui.label(if status.version.is_empty() { "-" } else { status.version.as_str() });
+                ui.label("运行时间:");
+                ui.label(status.uptime.map_or("-".to_string(), |t| {
+                    let secs = t.as_secs();
+                    format!("{:02}:{:02}:{:02}", secs / 3600, secs / 60 % 60, secs % 60)
+                }));
+                ui.end_row();
+                
+                ui.label("已知节点:");
+                ui.label(status.routers.to_string());
+                ui.label("活跃节点:");
+                ui.label(status.active_peers.to_string());
+                ui.end_row();
+                
+                ui.label("客户端隧道:");
+                ui.label(status.client_tunnels.to_string());
+                ui.label("中转隧道:");
+                ui.label(status.transit_tunnels.to_string());
+                ui.end_row();
+            });
+    }
+    
+    // NetDB与隧道建立统计，用于判断启动后的网络状况
+    fn render_netdb_stats(&self, ui: &mut Ui) {
+        let status = match self.process.as_ref().and_then(|p| p.router_status()) {
+            Some(status) => status,
+            None => {
+                ui.label(RichText::new("等待路由器数据...").weak());
+                return;
             }
+        };
+        
+        Grid::new("i2p_netdb_grid")
+            .num_columns(2)
+            .spacing([20.0, 4.0])
+            .show(ui, |ui| {
+                ui.label("已知路由器:");
+                ui.label(status.routers.to_string());
+                ui.end_row();
+                
+                ui.label("Floodfill路由器:").on_hover_text("保存并提供NetDB数据的路由器");
+                ui.label(status.floodfills.to_string());
+                ui.end_row();
+                
+                ui.label("LeaseSet:").on_hover_text("已知的I2P服务目标地址");
+                ui.label(status.leasesets.to_string());
+                ui.end_row();
+                
+                ui.label("隧道建立成功率:");
+                match status.tunnel_success_rate {
+                    Some(rate) => {
+                        let color = match rate {
+                            0..=9 => Color32::RED,
+                            10..=24 => Color32::YELLOW,
+                            _ => Color32::GREEN,
+                        };
+                        ui.label(RichText::new(format!("{}%", rate)).color(color));
+                    }
+                    None => {
+                        ui.label("-");
+                    }
+                }
+                ui.end_row();
+            });
+        
+        // 按经验给出网络状况提示
+        let hint = if status.routers < 100 {
+            Some("已知路由器较少，刚启动时属于正常情况，通常几分钟后会增加")
+        } else if status.floodfills == 0 && status.uptime.is_some() {
+            Some("尚未发现floodfill路由器，可能无法解析地址")
+        } else if status.tunnel_success_rate.map_or(false, |rate| rate < 10) {
+            Some("隧道建立成功率较低，网络可能受到干扰或拥塞，访问会较慢")
+        } else {
+            None
+        };
+        match hint {
+            Some(hint) => ui.label(RichText::new(format!("⚠ {}", hint)).color(Color32::YELLOW)),
+            None => ui.label(RichText::new("网络状况良好").color(Color32::GREEN)),
+        };
+    }
+    
+    // 获取当前连接状态的副本
+    pub fn get_connection_status(&self) -> String {
+        self.connection_status.clone()
+    }
+    
+    // I2P是否已启用
+    pub fn is_enabled(&self) -> bool {
+        self.enabled
+    }
+    
+    // i2pd是否已加入网络
+    pub fn is_connected(&self) -> bool {
+        self.process.as_ref().map_or(false, |p| p.state() == I2pdState::Ready)
+    }
+    
+    // 获取当前带宽（入站, 出站），单位KB/s
+    pub fn bandwidth(&self) -> (u32, u32) {
+        (self.bandwidth_in, self.bandwidth_out)
+    }
+    
+    // 获取已启用隧道的本地端口（仅在I2P启用时可用）
+    pub fn tunnel_ports(&self) -> Vec<u16> {
+        if !self.enabled {
+            return Vec::new();
         }
+        self.tunnels.iter().filter(|t| t.enabled).map(|t| t.local_port).collect()
     }
     
     // 将for循环移到UI方法内的正确位置
@@ -205,7 +1036,7 @@ impl I2PModule {
             let status_text = &self.connection_status;
             let status_color = match status_text.as_str() {
                 "已连接" => Color32::GREEN,
-                "正在连接..." => Color32::YELLOW,
+                "正在连接..." | "正在加入网络..." | "正在重启..." => Color32::YELLOW,
                 _ => Color32::RED,
             };
             ui.label(RichText::new(status_text).color(status_color).strong());
@@ -224,45 +1055,88 @@ impl I2PModule {
             ui.label("I2P（Invisible Internet Project）是一个匿名网络层，允许进行抗审查和私密的通信。");
             ui.label("与Tor不同，I2P主要设计用于网络内部的通信，而不是访问外部互联网。");
             ui.label("官方网站: https://geti2p.net/");
-            
-            if ui.button("打开I2P控制台").clicked() {
-                self.open_i2p_console();
-            }
         });
         
         // 如果I2P已启用，显示带宽信息
         if self.enabled {
             ui.group(|ui| {
                 ui.heading("带宽使用情况");
-                
-                ui.horizontal(|ui| {
-                    ui.label("入站:");
-                    ui.label(format!("{} KB/s", self.bandwidth_in));
-                });
-                
-                ui.horizontal(|ui| {
-                    ui.label("出站:");
-                    ui.label(format!("{} KB/s", self.bandwidth_out));
-                });
+                self.render_bandwidth(ui);
+            });
+            
+            ui.group(|ui| {
+                ui.heading("路由器状态");
+                self.render_router_status(ui);
+            });
+            
+            ui.collapsing("NetDB统计", |ui| {
+                self.render_netdb_stats(ui);
             });
         }
         
+        ui.collapsing("路由器设置", |ui| {
+            self.router_settings.ui(ui, self.process.is_some(), self.tor_socks_port.is_some());
+        });
+        
+        let ready = self.process.as_ref().map_or(false, |p| p.state() == I2pdState::Ready);
+        ui.collapsing("地址簿", |ui| {
+            self.addressbook.ui(ui, ready);
+        });
+        
+        ui.collapsing("地址工具", |ui| {
+            ui.label(RichText::new("检查b32/Base64地址格式，或通过地址簿与跳转服务解析.i2p域名").weak());
+            self.destination_tool.ui(ui, ready, self.addressbook.subscriptions());
+        });
+        
         ui.separator();
         
         // 隧道管理区域
         ui.horizontal(|ui| {
             ui.heading("I2P隧道");
             ui.with_layout(egui::Layout::right_to_left(egui::Align::Center), |ui| {
+                ui.menu_button("从模板添加", |ui| {
+                    for (name, tunnel_type, port, destination, description) in TUNNEL_TEMPLATES {
+                        if ui.button(name).on_hover_text(description).clicked() {
+                            let mut template = I2PTunnel::new(0, name, tunnel_type, port, destination);
+                            template.description = description.to_string();
+                            self.open_new_form(&template);
+                            ui.close_menu();
+                        }
+                    }
+                });
                 if ui.button("添加隧道").clicked() {
+                    self.reset_form();
+                    // 预填一个空闲端口
+                    self.new_tunnel_port = ports::suggest_free_port(NEW_TUNNEL_MODULE, "127.0.0.1", 7000, Protocol::Tcp).unwrap_or(0);
                     self.edit_mode = true;
                 }
             });
         });
         
+        // 启用隧道时发现的端口冲突
+        if let Some((tunnel_id, reason, suggestion)) = self.port_conflict.clone() {
+            ui.horizontal(|ui| {
+                ui.label(RichText::new(format!("⚠ {}，隧道未启用", reason)).color(Color32::YELLOW));
+                if let Some(port) = suggestion {
+                    if ui.button(format!("改用端口 {} 并启用", port)).clicked() {
+                        if let Some(tunnel) = self.tunnels.iter_mut().find(|t| t.id == tunnel_id) {
+                            tunnel.local_port = port;
+                        }
+                        self.port_conflict = None;
+                        self.reserve_ports();
+                        self.enable_tunnel(tunnel_id);
+                    }
+                }
+                if ui.small_button("✖").on_hover_text("关闭").clicked() {
+                    self.port_conflict = None;
+                }
+            });
+        }
+        
         // 隧道列表
         ScrollArea::vertical().show(ui, |ui| {
             Grid::new("i2p_tunnels_grid")
-                .num_columns(5)
+                .num_columns(7)
                 .striped(true)
                 .spacing([10.0, 4.0])
                 .show(ui, |ui| {
@@ -271,6 +1145,8 @@ impl I2PModule {
                     ui.label(RichText::new("名称").strong());
                     ui.label(RichText::new("类型").strong());
                     ui.label(RichText::new("本地端口").strong());
+                    ui.label(RichText::new("状态").strong());
+                    ui.label(RichText::new("流量").strong());
                     ui.label(RichText::new("操作").strong());
                     ui.end_row();
                     
@@ -283,18 +1159,25 @@ impl I2PModule {
                             tunnel.name.clone(),
                             tunnel.tunnel_type.clone(),
                             tunnel.local_port,
-                            self.selected_tunnel == Some(tunnel.id)
+                            self.selected_tunnel == Some(tunnel.id),
+                            self.tunnel_status(tunnel),
+                            self.sam_tunnels.get(&tunnel.id).map(SamTunnel::traffic)
                         )
                     }).collect();
                     
-                    for (tunnel_id, mut enabled, tunnel_name, tunnel_type, local_port, is_selected) in tunnels_info {
+                    for (tunnel_id, mut enabled, tunnel_name, tunnel_type, local_port, is_selected, (status, status_color), traffic) in tunnels_info {
                         // 启用/禁用复选框
                         if ui.checkbox(&mut enabled, "")
                             .on_hover_text("启用/禁用该隧道")
                             .changed() {
-                            // 在实际应用中，这里应该更新隧道的启用状态
-                            if let Some(tunnel) = self.tunnels.iter_mut().find(|t| t.id == tunnel_id) {
-                                tunnel.enabled = enabled;
+                            // I2P运行时由tick()注册或注销对应的SAM隧道
+                            if enabled {
+                                self.enable_tunnel(tunnel_id);
+                            } else {
+                                if let Some(tunnel) = self.tunnels.iter_mut().find(|t| t.id == tunnel_id) {
+                                    tunnel.enabled = false;
+                                }
+                                self.save_tunnels();
                             }
                         }
                         
@@ -304,21 +1187,40 @@ impl I2PModule {
                         }
                         
                         // 隧道类型
-                        let type_text = match tunnel_type {
-                            TunnelType::Client => "客户端",
-                            TunnelType::Server => "服务端",
-                        };
-                        ui.label(type_text);
+                        ui.label(tunnel_type.label());
                         
                         // 本地端口
                         ui.label(local_port.to_string());
                         
+                        ui.label(RichText::new(status).color(status_color));
+                        
+                        // 流量（代理隧道由i2pd转发，无法按隧道统计）
+                        match traffic {
+                            Some(traffic) => {
+                                ui.label(format!(
+                                    "↓{} ↑{} · {}连接",
+                                    utils::format_bytes(traffic.received), utils::format_bytes(traffic.sent), traffic.active
+                                ))
+                                .on_hover_text(format!("隧道注册以来: 当前连接 {}，累计连接 {}", traffic.active, traffic.connections));
+                            }
+                            None => {
+                                ui.label("-");
+                            }
+                        }
+                        
                         // 操作按钮
                         let tunnel_id_copy = tunnel_id; // 创建一个副本用于闭包
                         ui.horizontal(|ui| {
                             if ui.button("编辑").clicked() {
                                 self.selected_tunnel = Some(tunnel_id_copy);
-                                self.edit_mode = true;
+                                if let Some(tunnel) = self.tunnels.iter().find(|t| t.id == tunnel_id_copy).cloned() {
+                                    self.fill_form(&tunnel);
+                                    self.editing_tunnel = Some(tunnel_id_copy);
+                                    self.edit_mode = true;
+                                }
+                            }
+                            if ui.button("复制").on_hover_text("以该隧道为模板新建").clicked() {
+                                self.clone_tunnel(tunnel_id_copy);
                             }
                             if ui.button("删除").clicked() {
                                 self.remove_tunnel(tunnel_id_copy);
@@ -345,40 +1247,128 @@ impl I2PModule {
                         ui.end_row();
                         
                         ui.label("类型:");
-                        ui.label(match tunnel.tunnel_type {
-                            TunnelType::Client => "客户端",
-                            TunnelType::Server => "服务端",
-                        });
+                        ui.label(tunnel.tunnel_type.label());
                         ui.end_row();
                         
                         ui.label("本地端口:");
                         ui.label(tunnel.local_port.to_string());
                         ui.end_row();
                         
-                        ui.label("目标地址:");
-                        ui.label(&tunnel.destination);
+                        if tunnel.tunnel_type == TunnelType::Socks {
+                            ui.label("UDP转发:");
+                            ui.label(if tunnel.udp { "已启用" } else { "未启用" });
+                        } else {
+                            ui.label("目标地址:");
+                            ui.label(&tunnel.destination);
+                        }
                         ui.end_row();
                         
                         ui.label("描述:");
                         ui.label(&tunnel.description);
                         ui.end_row();
+                        
+                        let options = &tunnel.options;
+                        ui.label("隧道参数:");
+                        ui.label(format!(
+                            "入站 {}跳（浮动{:+}）×{}，出站 {}跳（浮动{:+}）×{}",
+                            options.inbound_length, options.inbound_length_variance, options.inbound_quantity,
+                            options.outbound_length, options.outbound_length_variance, options.outbound_quantity
+                        ));
+                        ui.end_row();
+                        
+                        if !options.custom.is_empty() {
+                            ui.label("其他选项:");
+                            ui.vertical(|ui| {
+                                for (key, value) in &options.custom {
+                                    ui.label(RichText::new(format!("{} = {}", key, value)).monospace());
+                                }
+                            });
+                            ui.end_row();
+                        }
+                        
+                        let registered = self.sam_tunnels.get(&tunnel.id);
+                        if let Some(SamTunnelState::Failed(error)) = registered.map(SamTunnel::state) {
+                            ui.label("错误:");
+                            ui.label(RichText::new(error).color(Color32::RED));
+                            ui.end_row();
+                        }
+                        
+                        // 服务端隧道的I2P地址，供他人访问
+                        let address = registered.and_then(SamTunnel::address)
+                            .or_else(|| i2p_sam::read_keys(tunnel.id).and_then(|key| i2p_sam::b32_address(&key)));
+                        if let Some(address) = address {
+                            ui.label("I2P地址:");
+                            ui.horizontal(|ui| {
+                                ui.label(RichText::new(&address).monospace());
+                                if ui.small_button("复制").clicked() {
+                                    ui.output_mut(|o| o.copied_text = address.clone());
+                                }
+                            });
+                            ui.end_row();
+                        }
+                    });
+                
+                // 服务端隧道的私钥备份，换电脑或重装后可保留同一地址
+                if tunnel.tunnel_type == TunnelType::Server {
+                    let has_keys = i2p_sam::read_keys(tunnel_id).is_some();
+                    let mut export_clicked = false;
+                    let mut import_clicked = false;
+                    ui.horizontal(|ui| {
+                        export_clicked = ui.add_enabled(has_keys, egui::Button::new("导出密钥..."))
+                            .on_disabled_hover_text("启用隧道并连接I2P后才会生成密钥")
+                            .clicked();
+                        import_clicked = ui.button("导入密钥...")
+                            .on_hover_text("从备份恢复隧道地址")
+                            .clicked();
                     });
+                    if export_clicked {
+                        self.key_message = None;
+                        self.key_dialog = Some(KeyDialog {
+                            tunnel_id,
+                            mode: KeyDialogMode::Export,
+                            password: String::new(),
+                            confirm: String::new(),
+                            error: None,
+                        });
+                    }
+                    if import_clicked {
+                        self.key_message = None;
+                        self.pick_key_backup(tunnel_id);
+                    }
+                    match &self.key_message {
+                        Some(Ok(message)) => { ui.label(RichText::new(message).color(Color32::GREEN)); }
+                        Some(Err(error)) => { ui.label(RichText::new(error).color(Color32::RED)); }
+                        None => {}
+                    }
+                }
             }
         }
         
+        self.render_key_dialog(ui.ctx());
+        
         // 添加/编辑隧道对话框
         if self.edit_mode {
             // 提前获取所需数据，避免在闭包中直接借用self
             let is_edit_mode = self.edit_mode;
-            let has_selected_tunnel = self.selected_tunnel.is_some();
-            let window_title = if has_selected_tunnel { "编辑隧道" } else { "添加隧道" };
+            let editing = self.editing_tunnel.and_then(|id| self.tunnels.iter().find(|t| t.id == id)).cloned();
+            let window_title = if editing.is_some() { "编辑隧道" } else { "添加隧道" };
             
             // 创建可变引用的副本，以便在闭包中使用
             let mut new_tunnel_name = self.new_tunnel_name.clone();
             let mut new_tunnel_type = self.new_tunnel_type.clone();
             let mut new_tunnel_port = self.new_tunnel_port;
             let mut new_tunnel_destination = self.new_tunnel_destination.clone();
+            let mut new_tunnel_description = self.new_tunnel_description.clone();
+            let mut new_tunnel_udp = self.new_tunnel_udp;
+            let mut new_tunnel_options = self.new_tunnel_options.clone();
             let next_tunnel_id = self.next_tunnel_id;
+            // 编辑时端口未修改则不与隧道自身登记的端口比较
+            let port_module = match &editing {
+                Some(tunnel) if tunnel.local_port == new_tunnel_port => "I2P",
+                _ => NEW_TUNNEL_MODULE,
+            };
+            let listening = editing.as_ref().map_or(false, |t| t.local_port == new_tunnel_port && self.is_listening(t.id));
+            let form_error = self.form_error.clone();
             
             // 使用模态对话框进行隧道编辑
             let mut still_open = is_edit_mode;
@@ -393,31 +1383,50 @@ impl I2PModule {
                     ui.horizontal(|ui| {
                         ui.label("隧道类型:");
                         egui::ComboBox::from_id_source("tunnel_type_combo")
-                            .selected_text(match new_tunnel_type {
-                                TunnelType::Client => "客户端",
-                                TunnelType::Server => "服务端",
-                            })
+                            .selected_text(new_tunnel_type.label())
                             .show_ui(ui, |ui| {
-                                ui.selectable_value(&mut new_tunnel_type, TunnelType::Client, "客户端");
-                                ui.selectable_value(&mut new_tunnel_type, TunnelType::Server, "服务端");
+                                for tunnel_type in [TunnelType::Client, TunnelType::Server, TunnelType::Socks] {
+                                    let label = tunnel_type.label();
+                                    ui.selectable_value(&mut new_tunnel_type, tunnel_type, label);
+                                }
                             });
                     });
 
                     ui.horizontal(|ui| {
                         ui.label("本地端口:");
-                        let mut tunnel_port = new_tunnel_port.to_string();
-                        if ui.text_edit_singleline(&mut tunnel_port).changed() {
-                            if let Ok(port) = tunnel_port.parse::<u16>() {
-                                new_tunnel_port = port;
-                            }
+                        // 服务端隧道的端口是被转发的本地服务，应当已被占用
+                        if new_tunnel_type.listens_locally() {
+                            ports::port_field(ui, port_module, "127.0.0.1", &mut new_tunnel_port, Protocol::Tcp, listening);
+                        } else {
+                            ui.add(egui::DragValue::new(&mut new_tunnel_port).clamp_range(1..=65535));
+                            ui.label(RichText::new("本地服务的端口").weak());
                         }
                     });
 
+                    // SOCKS代理的目标由应用在每次连接时指定
+                    if new_tunnel_type == TunnelType::Socks {
+                        ui.checkbox(&mut new_tunnel_udp, "转发UDP（SOCKS5 UDP ASSOCIATE）")
+                            .on_hover_text("供BT下载等使用数据报的应用，每个UDP关联使用单独的临时I2P地址");
+                    } else {
+                        ui.horizontal(|ui| {
+                            ui.label("目标地址:");
+                            ui.text_edit_singleline(&mut new_tunnel_destination);
+                        });
+                    }
+
                     ui.horizontal(|ui| {
-                        ui.label("目标地址:");
-                        ui.text_edit_singleline(&mut new_tunnel_destination);
+                        ui.label("描述:");
+                        ui.text_edit_singleline(&mut new_tunnel_description);
                     });
 
+                    egui::CollapsingHeader::new("隧道参数")
+                        .id_source("tunnel_options_header")
+                        .show(ui, |ui| new_tunnel_options.ui(ui));
+
+                    if let Some(error) = &form_error {
+                        ui.label(RichText::new(error).color(Color32::RED));
+                    }
+                    
                     // 保存用户操作的结果
                     let mut save_clicked = false;
                     let mut cancel_clicked = false;
@@ -428,48 +1437,86 @@ impl I2PModule {
                         }
 
                         if ui.button("保存").clicked() {
-                            if !new_tunnel_name.is_empty() && !new_tunnel_destination.is_empty() && new_tunnel_port > 0 {
+                            let has_destination = new_tunnel_type == TunnelType::Socks || !new_tunnel_destination.is_empty();
+                            if !new_tunnel_name.is_empty() && has_destination && new_tunnel_port > 0
+                                && new_tunnel_options.validate().is_ok()
+                            {
                                 save_clicked = true;
                             }
                         }
                     });
                     
                     // 返回用户操作结果和表单数据
-                    (save_clicked, cancel_clicked, new_tunnel_name, new_tunnel_type, new_tunnel_port, new_tunnel_destination)
+                    (save_clicked, cancel_clicked, new_tunnel_name, new_tunnel_type, new_tunnel_port, new_tunnel_destination, new_tunnel_description, new_tunnel_options, new_tunnel_udp)
                 })
                 .and_then(|inner_result| inner_result.inner)
-                .map(|(save_clicked, cancel_clicked, name, tunnel_type, port, destination)| {
-                    // 根据用户操作更新状态
+                .map(|(save_clicked, cancel_clicked, name, tunnel_type, port, destination, description, options, udp)| {
+                    // 启用的客户端隧道不能使用被占用的端口
+                    let check_port = tunnel_type.listens_locally() && editing.as_ref().map_or(true, |t| t.enabled);
+                    let conflict = if save_clicked && check_port {
+                        self.check_tunnel_port(editing.as_ref().map(|t| t.id), port)
+                    } else {
+                        None
+                    };
                     if save_clicked {
-                        let new_tunnel = I2PTunnel::new(
-                            next_tunnel_id,
-                            &name,
-                            tunnel_type,
-                            port,
-                            &destination
-                        );
-                        self.add_tunnel(new_tunnel);
-                        self.new_tunnel_name.clear();
-                        self.new_tunnel_destination.clear();
-                        self.new_tunnel_port = 0;
+                        self.form_error = conflict.map(|(reason, suggestion)| match suggestion {
+                            Some(free) => format!("{}，建议使用空闲端口 {}", reason, free),
+                            None => reason,
+                        });
+                    }
+                    
+                    // 根据用户操作更新状态
+                    if save_clicked && self.form_error.is_none() {
+                        match editing {
+                            Some(existing) => {
+                                let mut tunnel = existing;
+                                tunnel.name = name;
+                                tunnel.tunnel_type = tunnel_type;
+                                tunnel.local_port = port;
+                                tunnel.destination = destination;
+                                tunnel.description = description;
+                                tunnel.options = options.normalized();
+                                tunnel.udp = udp;
+                                self.update_tunnel(tunnel);
+                            }
+                            None => {
+                                let mut new_tunnel = I2PTunnel::new(
+                                    next_tunnel_id,
+                                    &name,
+                                    tunnel_type,
+                                    port,
+                                    &destination
+                                );
+                                new_tunnel.description = description;
+                                new_tunnel.options = options.normalized();
+                                new_tunnel.udp = udp;
+                                self.add_tunnel(new_tunnel);
+                            }
+                        }
+                        self.reset_form();
                         self.edit_mode = false;
                     } else if cancel_clicked {
                         self.edit_mode = false;
-                        self.new_tunnel_name.clear();
-                        self.new_tunnel_destination.clear();
-                        self.new_tunnel_port = 0;
+                        self.reset_form();
                     } else {
                         // 更新表单数据，但不关闭窗口
+                        if port != self.new_tunnel_port || tunnel_type != self.new_tunnel_type {
+                            self.form_error = None;
+                        }
                         self.new_tunnel_name = name;
                         self.new_tunnel_type = tunnel_type;
                         self.new_tunnel_port = port;
                         self.new_tunnel_destination = destination;
+                        self.new_tunnel_description = description;
+                        self.new_tunnel_options = options;
+                        self.new_tunnel_udp = udp;
                     }
                 });
                 
             // 如果窗口被关闭，更新edit_mode
             if !still_open {
                 self.edit_mode = false;
+                self.reset_form();
             }
         }
     }
@@ -0,0 +1,278 @@
+use eframe::egui::{self, Color32, RichText, Ui};
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+use anyhow::{anyhow, Context, Result};
+use chrono::Local;
+use serde::{Deserialize, Serialize};
+
+use crate::i2p_sam;
+use crate::i2pd_process;
+use crate::logger::Logger;
+use crate::utils;
+
+// 订阅配置文件名
+const ADDRESSBOOK_CONFIG_FILE: &str = "i2p_addressbook.json";
+
+// 默认订阅：reg.i2p（i2pd的默认订阅）与stats.i2p的新域名列表
+const DEFAULT_SUBSCRIPTIONS: [&str; 2] = [
+    "http://shx5vqsw7usdaunyzr2qmes2fq37oumybpudrd4jjj4e4vk4uusa.b32.i2p/hosts.txt",
+    "http://stats.i2p/cgi-bin/newhosts.txt",
+];
+
+// 一个地址簿订阅
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct Subscription {
+    pub url: String,
+    pub enabled: bool,
+    pub last_updated: Option<String>,
+    pub entries: usize,
+    pub last_error: Option<String>,
+}
+
+impl Subscription {
+    pub fn new(url: &str) -> Self {
+        Self {
+            url: url.to_string(),
+            enabled: true,
+            last_updated: None,
+            entries: 0,
+            last_error: None,
+        }
+    }
+}
+
+// v1: 引入版本号，格式与之前相同
+impl utils::VersionedConfig for Vec<Subscription> {
+    const VERSION: u32 = 1;
+}
+
+// 下载的订阅缓存在i2pd工作目录下，按地址的FNV-1a哈希命名
+fn cache_file(home: &Path, url: &str) -> PathBuf {
+    let hash = url.bytes().fold(0xcbf29ce484222325u64, |hash, b| (hash ^ b as u64).wrapping_mul(0x100000001b3));
+    home.join("addressbook-cache").join(format!("{:016x}.txt", hash))
+}
+
+// 解析hosts.txt格式（"域名=目标地址"，目标地址后可能带"#!"开头的签名信息）
+fn parse_hosts(contents: &str) -> BTreeMap<String, String> {
+    contents.lines()
+        .filter_map(|line| {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                return None;
+            }
+            let (name, destination) = line.split_once('=')?;
+            let name = name.trim().to_lowercase();
+            let destination = destination.split("#!").next()?.trim();
+            if !name.ends_with(".i2p") || i2p_sam::b32_address(destination).is_none() {
+                return None;
+            }
+            Some((name, destination.to_string()))
+        })
+        .collect()
+}
+
+// 通过I2P下载一个订阅并缓存，返回条目数
+fn update_subscription(home: &Path, sam_port: u16, url: &str) -> Result<usize> {
+    let body = i2p_sam::http_get(sam_port, url)?;
+    let hosts = parse_hosts(&body);
+    if hosts.is_empty() {
+        return Err(anyhow!("No addresses found in {}", url));
+    }
+    let path = cache_file(home, url);
+    if let Some(dir) = path.parent() {
+        fs::create_dir_all(dir).context("Failed to create addressbook directory")?;
+    }
+    let contents: String = hosts.iter().map(|(name, destination)| format!("{}={}\n", name, destination)).collect();
+    fs::write(&path, contents).context("Failed to save addressbook")?;
+    Ok(hosts.len())
+}
+
+// 在已下载的订阅中查找域名
+pub fn lookup_cached(home: &Path, subscriptions: &[Subscription], name: &str) -> Option<String> {
+    subscriptions.iter()
+        .filter(|s| s.enabled)
+        .filter_map(|s| fs::read_to_string(cache_file(home, &s.url)).ok())
+        .find_map(|contents| parse_hosts(&contents).remove(name))
+}
+
+enum SubscriptionUpdate {
+    Idle,
+    Updating,
+    Done(Vec<(String, Result<usize, String>)>),
+}
+
+// I2P页面中的地址簿面板
+pub struct AddressbookPanel {
+    logger: Arc<Mutex<Logger>>,
+    subscriptions: Vec<Subscription>,
+    new_url: String,
+    error: Option<String>,
+    update: Arc<Mutex<SubscriptionUpdate>>,
+}
+
+impl AddressbookPanel {
+    pub fn new(logger: Arc<Mutex<Logger>>) -> Self {
+        let subscriptions: Vec<Subscription> = utils::load_saved_config(ADDRESSBOOK_CONFIG_FILE)
+            .unwrap_or_else(|| DEFAULT_SUBSCRIPTIONS.iter().map(|url| Subscription::new(url)).collect());
+        Self {
+            logger,
+            subscriptions,
+            new_url: String::new(),
+            error: None,
+            update: Arc::new(Mutex::new(SubscriptionUpdate::Idle)),
+        }
+    }
+
+    pub fn subscriptions(&self) -> &[Subscription] {
+        &self.subscriptions
+    }
+
+    // 启用的订阅地址，写入i2pd.conf
+    pub fn subscription_urls(&self) -> Vec<String> {
+        self.subscriptions.iter().filter(|s| s.enabled).map(|s| s.url.clone()).collect()
+    }
+
+    fn save(&self) {
+        let result = utils::get_config_path(ADDRESSBOOK_CONFIG_FILE)
+            .and_then(|path| utils::save_versioned_config(&self.subscriptions, &path));
+        if let Err(e) = result {
+            if let Ok(mut logger) = self.logger.lock() {
+                logger.error("I2P", &format!("保存地址簿订阅失败: {}", e));
+            }
+        }
+    }
+
+    // 在后台通过I2P下载所有启用的订阅
+    fn update_subscriptions(&self) {
+        let urls = self.subscription_urls();
+        if urls.is_empty() {
+            return;
+        }
+        if let Ok(mut update) = self.update.lock() {
+            *update = SubscriptionUpdate::Updating;
+        }
+        let update = Arc::clone(&self.update);
+        std::thread::spawn(move || {
+            let results = match i2pd_process::i2pd_home() {
+                Ok(home) => urls.into_iter()
+                    .map(|url| {
+                        let result = update_subscription(&home, i2pd_process::SAM_PORT, &url).map_err(|e| format!("{:#}", e));
+                        (url, result)
+                    })
+                    .collect(),
+                Err(e) => urls.into_iter().map(|url| (url, Err(format!("{:#}", e)))).collect(),
+            };
+            if let Ok(mut update) = update.lock() {
+                *update = SubscriptionUpdate::Done(results);
+            }
+        });
+    }
+
+    // 记录订阅的更新结果
+    fn collect_update(&mut self) {
+        let done = self.update.lock().ok().and_then(|mut update| match &*update {
+            SubscriptionUpdate::Done(..) => Some(std::mem::replace(&mut *update, SubscriptionUpdate::Idle)),
+            _ => None,
+        });
+        let results = match done {
+            Some(SubscriptionUpdate::Done(results)) => results,
+            _ => return,
+        };
+        let now = Local::now().format("%Y-%m-%d %H:%M").to_string();
+        for (url, result) in results {
+            let subscription = match self.subscriptions.iter_mut().find(|s| s.url == url) {
+                Some(subscription) => subscription,
+                None => continue,
+            };
+            if let Ok(mut logger) = self.logger.lock() {
+                match &result {
+                    Ok(count) => logger.info("I2P", &format!("地址簿订阅 {} 已更新，{} 条", url, count)),
+                    Err(e) => logger.error("I2P", &format!("更新地址簿订阅 {} 失败: {}", url, e)),
+                }
+            }
+            match result {
+                Ok(count) => {
+                    subscription.entries = count;
+                    subscription.last_updated = Some(now.clone());
+                    subscription.last_error = None;
+                }
+                Err(e) => subscription.last_error = Some(e),
+            }
+        }
+        self.save();
+    }
+
+    pub fn ui(&mut self, ui: &mut Ui, router_ready: bool) {
+        self.collect_update();
+
+        ui.label(RichText::new("订阅提供.i2p域名到目标地址的对应关系，修改订阅后重启I2P生效").weak());
+        let mut removed = None;
+        let mut toggled = false;
+        for (index, subscription) in self.subscriptions.iter_mut().enumerate() {
+            ui.horizontal(|ui| {
+                toggled |= ui.checkbox(&mut subscription.enabled, "").changed();
+                ui.label(RichText::new(&subscription.url).monospace());
+                match (&subscription.last_error, &subscription.last_updated) {
+                    (Some(error), _) => {
+                        ui.label(RichText::new("更新失败").color(Color32::RED)).on_hover_text(error);
+                    }
+                    (None, Some(updated)) => {
+                        ui.label(RichText::new(format!("{} 条，更新于 {}", subscription.entries, updated)).weak());
+                    }
+                    (None, None) => {
+                        ui.label(RichText::new("未下载").weak());
+                    }
+                }
+                if ui.small_button("✖").on_hover_text("删除").clicked() {
+                    removed = Some(index);
+                }
+            });
+        }
+        if let Some(index) = removed {
+            self.subscriptions.remove(index);
+            toggled = true;
+        }
+        if toggled {
+            self.save();
+        }
+
+        ui.horizontal(|ui| {
+            ui.add(egui::TextEdit::singleline(&mut self.new_url).hint_text("http://example.i2p/hosts.txt").desired_width(300.0));
+            if ui.button("添加").clicked() {
+                let url = self.new_url.trim().to_string();
+                if !url.starts_with("http://") {
+                    self.error = Some("订阅地址应以http://开头".to_string());
+                } else if self.subscriptions.iter().any(|s| s.url == url) {
+                    self.error = Some("该订阅已添加".to_string());
+                } else {
+                    self.subscriptions.push(Subscription::new(&url));
+                    self.new_url.clear();
+                    self.error = None;
+                    self.save();
+                }
+            }
+        });
+        if let Some(error) = &self.error {
+            ui.label(RichText::new(error).color(Color32::RED));
+        }
+
+        ui.horizontal(|ui| {
+            let updating = matches!(self.update.lock().as_deref(), Ok(SubscriptionUpdate::Updating));
+            let can_update = router_ready && !updating && !self.subscriptions.is_empty();
+            if ui.add_enabled(can_update, egui::Button::new("立即更新"))
+                .on_hover_text("通过I2P下载订阅，供地址工具查询域名")
+                .on_disabled_hover_text("需要I2P已连接")
+                .clicked()
+            {
+                self.update_subscriptions();
+            }
+            if updating {
+                ui.spinner();
+                ui.label(RichText::new("正在通过I2P下载，可能需要几分钟").weak());
+                ui.ctx().request_repaint_after(std::time::Duration::from_millis(200));
+            }
+        });
+    }
+}
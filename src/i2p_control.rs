@@ -0,0 +1,138 @@
+use std::time::Duration;
+use anyhow::{anyhow, Context, Result};
+use serde_json::{json, Value};
+
+// I2PControl接口端口
+pub const I2PCONTROL_PORT: u16 = 7650;
+
+// 请求超时
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(3);
+
+// 通过I2PControl读取的路由器统计
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct ControlStats {
+    pub version: String,
+    pub network: String,
+    pub uptime: Duration,
+    pub known_peers: u32,
+    pub active_peers: u32,
+    pub floodfills: u32,
+    pub leasesets: u32,
+    pub participating_tunnels: u32,
+    pub tunnel_success_rate: Option<u32>, // 隧道建立成功率（%），旧版本不提供
+    pub in_bps: f64,  // 最近1秒的入站速率（字节/秒）
+    pub out_bps: f64,
+}
+
+// i2pd的网络状态码
+fn network_status(code: i64) -> &'static str {
+    match code {
+        0 => "OK",
+        1 => "Testing",
+        2 => "Firewalled",
+        4 => "Proxy",
+        5 => "Mesh",
+        _ => "Unknown",
+    }
+}
+
+// I2PControl JSON-RPC 2.0客户端，使用HTTPS（i2pd生成自签名证书，只连接本机）
+pub struct I2pControlClient {
+    client: reqwest::blocking::Client,
+    url: String,
+    password: String,
+    token: Option<String>,
+    next_id: u64,
+}
+
+impl I2pControlClient {
+    pub fn new(port: u16, password: &str) -> Result<Self> {
+        let client = reqwest::blocking::Client::builder()
+            .no_proxy()
+            .timeout(REQUEST_TIMEOUT)
+            .danger_accept_invalid_certs(true)
+            .build()
+            .context("Failed to create HTTP client")?;
+        Ok(Self {
+            client,
+            url: format!("https://127.0.0.1:{}/", port),
+            password: password.to_string(),
+            token: None,
+            next_id: 1,
+        })
+    }
+
+    fn call(&mut self, method: &str, params: Value) -> Result<Value> {
+        let request = json!({ "id": self.next_id, "method": method, "params": params, "jsonrpc": "2.0" });
+        self.next_id += 1;
+        let response: Value = self.client.post(&self.url)
+            .json(&request)
+            .send()
+            .context("Failed to connect to I2PControl")?
+            .json()
+            .context("Invalid I2PControl response")?;
+        if let Some(error) = response.get("error") {
+            return Err(anyhow!(
+                "I2PControl error {}: {}",
+                error["code"],
+                error["message"].as_str().unwrap_or("")
+            ));
+        }
+        response.get("result").cloned().ok_or_else(|| anyhow!("I2PControl response has no result"))
+    }
+
+    fn authenticate(&mut self) -> Result<String> {
+        let result = self.call("Authenticate", json!({ "API": 1, "Password": self.password }))?;
+        let token = result["Token"].as_str().ok_or_else(|| anyhow!("I2PControl did not return a token"))?.to_string();
+        self.token = Some(token.clone());
+        Ok(token)
+    }
+
+    // 读取路由器统计，令牌失效时重新认证一次
+    pub fn router_info(&mut self) -> Result<ControlStats> {
+        let token = match self.token.clone() {
+            Some(token) => token,
+            None => self.authenticate()?,
+        };
+        let params = |token: &str| json!({
+            "Token": token,
+            "i2p.router.version": null,
+            "i2p.router.uptime": null,
+            "i2p.router.net.status": null,
+            "i2p.router.netdb.knownpeers": null,
+            "i2p.router.netdb.activepeers": null,
+            "i2p.router.netdb.floodfills": null,
+            "i2p.router.netdb.leasesets": null,
+            "i2p.router.net.tunnels.participating": null,
+            "i2p.router.net.tunnels.successrate": null,
+            "i2p.router.net.bw.inbound.1s": null,
+            "i2p.router.net.bw.outbound.1s": null,
+        });
+        let result = match self.call("RouterInfo", params(&token)) {
+            Ok(result) => result,
+            Err(_) => {
+                let token = self.authenticate()?;
+                self.call("RouterInfo", params(&token))?
+            }
+        };
+        // 部分版本以字符串返回数值
+        let optional = |key: &str| {
+            let value = &result[key];
+            value.as_f64().or_else(|| value.as_str().and_then(|v| v.parse().ok()))
+        };
+        let number = |key: &str| optional(key).unwrap_or(0.0);
+        Ok(ControlStats {
+            version: result["i2p.router.version"].as_str().unwrap_or("").to_string(),
+            network: network_status(number("i2p.router.net.status") as i64).to_string(),
+            uptime: Duration::from_millis(number("i2p.router.uptime") as u64),
+            known_peers: number("i2p.router.netdb.knownpeers") as u32,
+            active_peers: number("i2p.router.netdb.activepeers") as u32,
+            floodfills: number("i2p.router.netdb.floodfills") as u32,
+            leasesets: number("i2p.router.netdb.leasesets") as u32,
+            participating_tunnels: number("i2p.router.net.tunnels.participating") as u32,
+            tunnel_success_rate: optional("i2p.router.net.tunnels.successrate").map(|rate| rate as u32),
+            in_bps: number("i2p.router.net.bw.inbound.1s"),
+            out_bps: number("i2p.router.net.bw.outbound.1s"),
+        })
+    }
+}
@@ -0,0 +1,238 @@
+use eframe::egui::{self, Color32, RichText, Ui};
+use std::sync::{Arc, Mutex};
+use anyhow::{anyhow, Result};
+
+use crate::i2p_addressbook::{self, Subscription};
+use crate::i2p_sam;
+use crate::i2pd_process;
+
+// 跳转服务，找不到域名时依次尝试，响应中的i2paddresshelper参数即目标地址
+const JUMP_SERVICES: [(&str, &str); 2] = [
+    ("stats.i2p", "http://stats.i2p/cgi-bin/jump.cgi?a="),
+    ("reg.i2p", "http://reg.i2p/jump/"),
+];
+
+// 用户输入的地址类型
+#[derive(Debug, PartialEq)]
+pub enum AddressInput {
+    B32 { address: String, encrypted: bool }, // .b32.i2p地址，encrypted为加密LeaseSet的b33地址
+    Destination(String),                      // Base64完整目标地址
+    Hostname(String),                         // 需要通过地址簿解析的.i2p域名
+}
+
+fn is_base32(value: &str) -> bool {
+    value.chars().all(|c| matches!(c, 'a'..='z' | '2'..='7'))
+}
+
+// 判断输入的类型并检查格式
+pub fn classify(input: &str) -> Result<AddressInput, String> {
+    let input = input.trim();
+    if input.is_empty() {
+        return Err("请输入地址".to_string());
+    }
+    let lower = input.to_lowercase();
+    if let Some(label) = lower.strip_suffix(".b32.i2p") {
+        return match label.len() {
+            _ if !is_base32(label) => Err(format!("b32地址只能包含a-z与2-7: {}", input)),
+            52 => Ok(AddressInput::B32 { address: lower.clone(), encrypted: false }),
+            len if len >= 56 => Ok(AddressInput::B32 { address: lower.clone(), encrypted: true }),
+            len => Err(format!("b32地址应为52个字符（加密地址至少56个），当前为{}个", len)),
+        };
+    }
+    if lower.ends_with(".i2p") {
+        let valid = lower.split('.').all(|part| {
+            !part.is_empty() && part.chars().all(|c| c.is_ascii_alphanumeric() || c == '-')
+        });
+        return if valid { Ok(AddressInput::Hostname(lower)) } else { Err(format!("域名格式无效: {}", input)) };
+    }
+    // Base64目标地址至少516个字符（387字节）
+    if input.len() >= 516 {
+        return match i2p_sam::b32_address(input) {
+            Some(_) => Ok(AddressInput::Destination(input.to_string())),
+            None => Err("Base64目标地址无法解码或长度不正确".to_string()),
+        };
+    }
+    Err("应为.i2p域名、.b32.i2p地址或Base64目标地址".to_string())
+}
+
+// 从跳转服务的重定向或页面中取出i2paddresshelper参数
+fn address_helper(response: &i2p_sam::HttpResponse) -> Option<String> {
+    [response.head.as_str(), response.body.as_str()].iter().find_map(|text| {
+        let start = text.find("i2paddresshelper=")?;
+        let raw: String = text[start..]
+            .chars()
+            .take_while(|c| c.is_ascii_alphanumeric() || matches!(c, '=' | '-' | '~' | '%'))
+            .collect();
+        url::form_urlencoded::parse(raw.as_bytes())
+            .find(|(key, _)| key == "i2paddresshelper")
+            .map(|(_, value)| value.into_owned())
+            .filter(|destination| i2p_sam::b32_address(destination).is_some())
+    })
+}
+
+// 依次通过跳转服务查询域名
+fn jump_lookup(name: &str) -> Result<(String, &'static str)> {
+    let mut errors = Vec::new();
+    for (service, prefix) in JUMP_SERVICES {
+        match i2p_sam::http_request(i2pd_process::SAM_PORT, &format!("{}{}", prefix, name)) {
+            Ok(response) => match address_helper(&response) {
+                Some(destination) => return Ok((destination, service)),
+                None => errors.push(format!("{}: 未找到", service)),
+            },
+            Err(e) => errors.push(format!("{}: {:#}", service, e)),
+        }
+    }
+    Err(anyhow!("{}", errors.join("; ")))
+}
+
+// 查询结果
+#[derive(Clone, Debug)]
+struct Resolved {
+    b32: String,
+    destination: Option<String>, // b32地址无法还原完整目标地址
+    source: String,
+    note: Option<String>,
+}
+
+impl Resolved {
+    fn from_destination(destination: String, source: &str) -> Result<Self, String> {
+        match i2p_sam::b32_address(&destination) {
+            Some(b32) => Ok(Self { b32, destination: Some(destination), source: source.to_string(), note: None }),
+            None => Err("目标地址格式无效".to_string()),
+        }
+    }
+}
+
+enum Check {
+    Idle,
+    Running,
+    Done(Result<Resolved, String>),
+}
+
+// I2P页面中的地址工具：检查b32与Base64地址，解析.i2p域名
+pub struct DestinationTool {
+    input: String,
+    check: Arc<Mutex<Check>>,
+}
+
+impl Default for DestinationTool {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl DestinationTool {
+    pub fn new() -> Self {
+        Self {
+            input: String::new(),
+            check: Arc::new(Mutex::new(Check::Idle)),
+        }
+    }
+
+    fn set_check(&self, check: Check) {
+        if let Ok(mut current) = self.check.lock() {
+            *current = check;
+        }
+    }
+
+    fn start(&self, router_ready: bool, subscriptions: &[Subscription]) {
+        let name = match classify(&self.input) {
+            Ok(AddressInput::B32 { address, encrypted }) => {
+                let note = if encrypted { "加密LeaseSet地址，需要对方提供的密钥才能访问" } else { "格式正确" };
+                self.set_check(Check::Done(Ok(Resolved {
+                    b32: address,
+                    destination: None,
+                    source: "输入".to_string(),
+                    note: Some(note.to_string()),
+                })));
+                return;
+            }
+            Ok(AddressInput::Destination(destination)) => {
+                self.set_check(Check::Done(Resolved::from_destination(destination, "输入")));
+                return;
+            }
+            Ok(AddressInput::Hostname(name)) => name,
+            Err(e) => {
+                self.set_check(Check::Done(Err(e)));
+                return;
+            }
+        };
+
+        // 域名：依次查询已下载的订阅、路由器地址簿与跳转服务
+        self.set_check(Check::Running);
+        let subscriptions = subscriptions.to_vec();
+        let check = Arc::clone(&self.check);
+        std::thread::spawn(move || {
+            let cached = i2pd_process::i2pd_home()
+                .ok()
+                .and_then(|home| i2p_addressbook::lookup_cached(&home, &subscriptions, &name));
+            let result = match cached {
+                Some(destination) => Resolved::from_destination(destination, "已下载的订阅"),
+                None if !router_ready => Err(format!("订阅中没有 {}，I2P就绪后可查询路由器地址簿与跳转服务", name)),
+                None => match i2p_sam::lookup(i2pd_process::SAM_PORT, &name) {
+                    Ok(destination) => Resolved::from_destination(destination, "路由器地址簿"),
+                    Err(_) => match jump_lookup(&name) {
+                        Ok((destination, service)) => {
+                            Resolved::from_destination(destination, &format!("跳转服务 {}", service))
+                        }
+                        Err(e) => Err(format!("未找到 {}（{}）", name, e)),
+                    },
+                },
+            };
+            if let Ok(mut check) = check.lock() {
+                *check = Check::Done(result);
+            }
+        });
+    }
+
+    pub fn ui(&mut self, ui: &mut Ui, router_ready: bool, subscriptions: &[Subscription]) {
+        let running = matches!(self.check.lock().as_deref(), Ok(Check::Running));
+        ui.horizontal(|ui| {
+            let response = ui.add(
+                egui::TextEdit::singleline(&mut self.input)
+                    .hint_text("example.i2p / xxx.b32.i2p / Base64目标地址")
+                    .desired_width(320.0),
+            );
+            let submitted = response.lost_focus() && ui.input(|i| i.key_pressed(egui::Key::Enter));
+            let clicked = ui.add_enabled(!running, egui::Button::new("检查")).clicked();
+            if (clicked || submitted) && !running {
+                self.start(router_ready, subscriptions);
+            }
+            if running {
+                ui.spinner();
+                ui.label(RichText::new("正在查询...").weak());
+                ui.ctx().request_repaint_after(std::time::Duration::from_millis(200));
+            }
+        });
+
+        let check = self.check.lock();
+        let result = match check.as_deref() {
+            Ok(Check::Done(result)) => result,
+            _ => return,
+        };
+        match result {
+            Ok(resolved) => {
+                ui.horizontal(|ui| {
+                    ui.label(RichText::new("✔").color(Color32::GREEN));
+                    ui.label(RichText::new(&resolved.b32).monospace().strong());
+                    if ui.small_button("复制").clicked() {
+                        ui.output_mut(|o| o.copied_text = resolved.b32.clone());
+                    }
+                    if let Some(destination) = &resolved.destination {
+                        if ui.small_button("复制完整地址").on_hover_text(destination).clicked() {
+                            ui.output_mut(|o| o.copied_text = destination.clone());
+                        }
+                    }
+                });
+                let mut details = format!("来源: {}", resolved.source);
+                if let Some(note) = &resolved.note {
+                    details.push_str(&format!("，{}", note));
+                }
+                ui.label(RichText::new(details).weak());
+            }
+            Err(error) => {
+                ui.label(RichText::new(format!("✖ {}", error)).color(Color32::RED));
+            }
+        }
+    }
+}
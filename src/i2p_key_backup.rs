@@ -0,0 +1,152 @@
+use std::path::Path;
+use aes::cipher::{KeyIvInit, StreamCipher};
+use anyhow::{anyhow, Result};
+use base64::{engine::general_purpose, Engine as _};
+use hmac::{Hmac, Mac};
+use serde::{Deserialize, Serialize};
+use sha2::Sha256;
+
+use crate::i2p_sam;
+use crate::utils;
+
+type Aes256Ctr = ctr::Ctr128BE<aes::Aes256>;
+type HmacSha256 = Hmac<Sha256>;
+
+// PBKDF2迭代次数
+const KDF_ITERATIONS: u32 = 310_000;
+// 导入时接受的最大迭代次数，防止被篡改的文件让解密长时间占用CPU
+const MAX_KDF_ITERATIONS: u32 = 10 * KDF_ITERATIONS;
+// 备份密码的最短长度
+pub const MIN_PASSWORD_LEN: usize = 8;
+
+// 加密的服务端隧道私钥备份：PBKDF2-HMAC-SHA256派生密钥，AES-256-CTR加密，HMAC-SHA256校验
+#[derive(Serialize, Deserialize)]
+pub struct KeyBackup {
+    pub name: String, // 导出时的隧道名称，仅供参考
+    pub address: String, // .b32.i2p地址，导入前可核对
+    iterations: u32,
+    salt: String,
+    iv: String,
+    ciphertext: String,
+    mac: String,
+}
+
+impl utils::VersionedConfig for KeyBackup {
+    const VERSION: u32 = 1;
+}
+
+// 由密码派生加密密钥与校验密钥
+fn derive_keys(password: &str, salt: &[u8], iterations: u32) -> ([u8; 32], [u8; 32]) {
+    let mut material = [0u8; 64];
+    pbkdf2::pbkdf2_hmac::<Sha256>(password.as_bytes(), salt, iterations, &mut material);
+    let mut encryption = [0u8; 32];
+    let mut authentication = [0u8; 32];
+    encryption.copy_from_slice(&material[..32]);
+    authentication.copy_from_slice(&material[32..]);
+    (encryption, authentication)
+}
+
+fn compute_mac(key: &[u8; 32], iv: &[u8], ciphertext: &[u8]) -> Result<HmacSha256> {
+    let mut mac = <HmacSha256 as Mac>::new_from_slice(key).map_err(|_| anyhow!("Invalid HMAC key"))?;
+    mac.update(iv);
+    mac.update(ciphertext);
+    Ok(mac)
+}
+
+fn decode(value: &str) -> Result<Vec<u8>> {
+    general_purpose::STANDARD.decode(value).map_err(|_| anyhow!("Key backup file is corrupted"))
+}
+
+// 用密码加密私钥
+pub fn encrypt(name: &str, private_key: &str, password: &str) -> Result<KeyBackup> {
+    let address = i2p_sam::b32_address(private_key).ok_or_else(|| anyhow!("Invalid I2P private key"))?;
+    let salt: [u8; 16] = rand::random();
+    let iv: [u8; 16] = rand::random();
+    let (encryption, authentication) = derive_keys(password, &salt, KDF_ITERATIONS);
+    let mut ciphertext = private_key.as_bytes().to_vec();
+    Aes256Ctr::new(&encryption.into(), &iv.into()).apply_keystream(&mut ciphertext);
+    let mac = compute_mac(&authentication, &iv, &ciphertext)?.finalize().into_bytes();
+    Ok(KeyBackup {
+        name: name.to_string(),
+        address,
+        iterations: KDF_ITERATIONS,
+        salt: general_purpose::STANDARD.encode(salt),
+        iv: general_purpose::STANDARD.encode(iv),
+        ciphertext: general_purpose::STANDARD.encode(ciphertext),
+        mac: general_purpose::STANDARD.encode(mac),
+    })
+}
+
+// 用密码解密，密码错误或文件被修改时返回错误
+pub fn decrypt(backup: &KeyBackup, password: &str) -> Result<String> {
+    let salt = decode(&backup.salt)?;
+    let iv = decode(&backup.iv)?;
+    let mut data = decode(&backup.ciphertext)?;
+    let expected = decode(&backup.mac)?;
+    if iv.len() != 16 {
+        return Err(anyhow!("Key backup file is corrupted"));
+    }
+    if !(KDF_ITERATIONS..=MAX_KDF_ITERATIONS).contains(&backup.iterations) {
+        return Err(anyhow!("Unsupported key backup iteration count: {}", backup.iterations));
+    }
+    let (encryption, authentication) = derive_keys(password, &salt, backup.iterations);
+    compute_mac(&authentication, &iv, &data)?
+        .verify_slice(&expected)
+        .map_err(|_| anyhow!("Wrong password or corrupted key backup"))?;
+    let mut iv_block = [0u8; 16];
+    iv_block.copy_from_slice(&iv);
+    Aes256Ctr::new(&encryption.into(), &iv_block.into()).apply_keystream(&mut data);
+    let private_key = String::from_utf8(data).map_err(|_| anyhow!("Key backup file is corrupted"))?;
+    match i2p_sam::b32_address(&private_key) {
+        Some(address) if address == backup.address => Ok(private_key),
+        _ => Err(anyhow!("Key backup does not match its address")),
+    }
+}
+
+// 把隧道的私钥加密导出到文件，返回地址
+pub fn export(tunnel_id: usize, name: &str, path: &Path, password: &str) -> Result<String> {
+    let private_key = i2p_sam::read_keys(tunnel_id)
+        .ok_or_else(|| anyhow!("Tunnel has no keys yet, start I2P with the tunnel enabled first"))?;
+    let backup = encrypt(name, &private_key, password)?;
+    utils::save_versioned_config(&backup, &path.to_string_lossy())?;
+    Ok(backup.address)
+}
+
+// 读取备份文件（解密前可显示其中的地址）
+pub fn read(path: &Path) -> Result<KeyBackup> {
+    utils::load_versioned_config(&path.to_string_lossy())
+}
+
+// 解密备份并替换隧道的私钥，返回新地址
+pub fn import(tunnel_id: usize, backup: &KeyBackup, password: &str) -> Result<String> {
+    let private_key = decrypt(backup, password)?;
+    i2p_sam::write_keys(tunnel_id, &private_key)?;
+    Ok(backup.address.clone())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn backup(iterations: u32) -> KeyBackup {
+        KeyBackup {
+            name: "test".to_string(),
+            address: "test.b32.i2p".to_string(),
+            iterations,
+            salt: general_purpose::STANDARD.encode([0u8; 16]),
+            iv: general_purpose::STANDARD.encode([0u8; 16]),
+            ciphertext: general_purpose::STANDARD.encode(b"key"),
+            mac: general_purpose::STANDARD.encode([0u8; 32]),
+        }
+    }
+
+    #[test]
+    fn rejects_iterations_out_of_range() {
+        for iterations in [0, 1, KDF_ITERATIONS - 1, MAX_KDF_ITERATIONS + 1, u32::MAX] {
+            let error = decrypt(&backup(iterations), "password").unwrap_err();
+            assert!(error.to_string().contains("iteration count"), "{}", iterations);
+        }
+        let error = decrypt(&backup(KDF_ITERATIONS), "password").unwrap_err();
+        assert!(error.to_string().contains("Wrong password"));
+    }
+}
@@ -0,0 +1,194 @@
+use eframe::egui::{self, Color32, Grid, RichText, Ui};
+use std::sync::{Arc, Mutex};
+use serde::{Deserialize, Serialize};
+
+use crate::logger::Logger;
+use crate::utils;
+
+// 路由器设置文件名
+const ROUTER_CONFIG_FILE: &str = "i2p_router.json";
+
+// i2pd的带宽等级（KB/s），对外公布为路由器能力
+const BANDWIDTH_PRESETS: [(&str, u32); 3] = [("L", 32), ("O", 256), ("P", 2048)];
+
+// 转发到程序日志的i2pd日志级别
+#[derive(Clone, Copy, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub enum RouterLogLevel {
+    Error,
+    #[default]
+    Warn,
+    Info,
+    Debug,
+}
+
+impl RouterLogLevel {
+    const ALL: [RouterLogLevel; 4] = [Self::Error, Self::Warn, Self::Info, Self::Debug];
+
+    pub fn conf_value(&self) -> &'static str {
+        match self {
+            Self::Error => "error",
+            Self::Warn => "warn",
+            Self::Info => "info",
+            Self::Debug => "debug",
+        }
+    }
+
+    fn label(&self) -> &'static str {
+        match self {
+            Self::Error => "仅错误",
+            Self::Warn => "警告",
+            Self::Info => "信息（较多）",
+            Self::Debug => "调试（非常多）",
+        }
+    }
+}
+
+// 路由器级别的带宽与转发设置，写入i2pd.conf
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct RouterSettings {
+    pub bandwidth: Option<u32>, // 带宽上限（KB/s），None为不限制
+    pub share: u8,              // 带宽中用于转发他人流量的百分比
+    pub transit: bool,          // 是否参与转发隧道
+    pub transit_tunnels: u32,   // 最多同时转发的隧道数
+    #[serde(default)]
+    pub tor_proxy: bool,        // 通过Tor的SOCKS端口建立NTCP2连接
+    #[serde(default)]
+    pub log_level: RouterLogLevel,
+}
+
+impl Default for RouterSettings {
+    // 与i2pd的默认值相同
+    fn default() -> Self {
+        Self {
+            bandwidth: Some(32),
+            share: 100,
+            transit: true,
+            transit_tunnels: 5000,
+            tor_proxy: false,
+            log_level: RouterLogLevel::default(),
+        }
+    }
+}
+
+impl utils::VersionedConfig for RouterSettings {
+    const VERSION: u32 = 1;
+}
+
+impl RouterSettings {
+    // i2pd.conf中的bandwidth值
+    pub fn bandwidth_value(&self) -> String {
+        match self.bandwidth {
+            Some(limit) => limit.to_string(),
+            None => "X".to_string(),
+        }
+    }
+}
+
+// I2P页面中的路由器设置，修改后重启I2P生效
+pub struct RouterSettingsPanel {
+    logger: Arc<Mutex<Logger>>,
+    saved: RouterSettings,
+    draft: RouterSettings,
+}
+
+impl RouterSettingsPanel {
+    pub fn new(logger: Arc<Mutex<Logger>>) -> Self {
+        let saved: RouterSettings = utils::load_saved_config(ROUTER_CONFIG_FILE).unwrap_or_default();
+        Self {
+            logger,
+            draft: saved.clone(),
+            saved,
+        }
+    }
+
+    pub fn settings(&self) -> &RouterSettings {
+        &self.saved
+    }
+
+    fn save(&mut self) {
+        let result = utils::get_config_path(ROUTER_CONFIG_FILE)
+            .and_then(|path| utils::save_versioned_config(&self.draft, &path));
+        if let Ok(mut logger) = self.logger.lock() {
+            match result {
+                Ok(()) => logger.info("I2P", "路由器设置已保存，重启I2P后生效"),
+                Err(e) => logger.error("I2P", &format!("保存I2P路由器设置失败: {}", e)),
+            }
+        }
+        self.saved = self.draft.clone();
+    }
+
+    pub fn ui(&mut self, ui: &mut Ui, running: bool, tor_running: bool) {
+        let draft = &mut self.draft;
+        Grid::new("i2p_router_settings_grid").num_columns(2).spacing([10.0, 4.0]).show(ui, |ui| {
+            ui.label("带宽上限:").on_hover_text("路由器收发的总速率上限，也决定对外公布的带宽等级");
+            ui.horizontal(|ui| {
+                let mut unlimited = draft.bandwidth.is_none();
+                if ui.checkbox(&mut unlimited, "不限制").changed() {
+                    draft.bandwidth = if unlimited { None } else { RouterSettings::default().bandwidth };
+                }
+                if let Some(limit) = &mut draft.bandwidth {
+                    ui.add(egui::DragValue::new(limit).clamp_range(16..=1_000_000).suffix(" KB/s"));
+                    for (class, value) in BANDWIDTH_PRESETS {
+                        if ui.selectable_label(*limit == value, class).on_hover_text(format!("{} KB/s", value)).clicked() {
+                            *limit = value;
+                        }
+                    }
+                }
+            });
+            ui.end_row();
+
+            ui.label("参与转发:").on_hover_text("为其他I2P用户转发隧道流量，有助于网络，也让自己的流量更难被区分");
+            ui.checkbox(&mut draft.transit, "");
+            ui.end_row();
+
+            ui.add_enabled(draft.transit, egui::Label::new("转发份额:"))
+                .on_hover_text("带宽上限中可用于转发的百分比");
+            ui.add_enabled(draft.transit, egui::Slider::new(&mut draft.share, 0..=100).suffix("%"));
+            ui.end_row();
+
+            ui.add_enabled(draft.transit, egui::Label::new("最多转发隧道:"));
+            ui.add_enabled(draft.transit, egui::DragValue::new(&mut draft.transit_tunnels).clamp_range(100..=65535));
+            ui.end_row();
+
+            ui.label("日志级别:").on_hover_text("i2pd输出的日志会记录到程序日志的I2P模块中");
+            egui::ComboBox::from_id_source("i2p_router_log_level")
+                .selected_text(draft.log_level.label())
+                .show_ui(ui, |ui| {
+                    for level in RouterLogLevel::ALL {
+                        ui.selectable_value(&mut draft.log_level, level, level.label());
+                    }
+                });
+            ui.end_row();
+        });
+        if !draft.transit {
+            ui.label(RichText::new("⚠ 关闭转发后，他人无法借助你的路由器建立隧道，自己的流量也更容易被识别").color(Color32::YELLOW));
+        }
+
+        ui.checkbox(&mut draft.tor_proxy, "通过Tor连接I2P网络")
+            .on_hover_text("所有到其他路由器的连接经由Tor的SOCKS端口建立，适用于I2P被防火墙封锁的网络");
+        if draft.tor_proxy {
+            ui.label(RichText::new("⚠ 速度会明显变慢，隧道建立成功率降低；UDP传输(SSU2)将被禁用，其他路由器也无法主动连接到你").color(Color32::YELLOW));
+            if !tor_running {
+                ui.label(RichText::new("需要先启动Tor").color(Color32::RED));
+            }
+        }
+
+        let changed = self.draft != self.saved;
+        ui.horizontal(|ui| {
+            if ui.add_enabled(changed, egui::Button::new("保存")).clicked() {
+                self.save();
+            }
+            if ui.add_enabled(changed, egui::Button::new("撤销")).clicked() {
+                self.draft = self.saved.clone();
+            }
+            if ui.button("恢复默认").clicked() {
+                self.draft = RouterSettings::default();
+            }
+            if changed {
+                ui.label(RichText::new("有未保存的修改").weak());
+            } else if running {
+                ui.label(RichText::new("修改在重启I2P后生效").weak());
+            }
+        });
+    }
+}
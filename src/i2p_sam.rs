@@ -0,0 +1,800 @@
+use std::collections::HashMap;
+use std::fs;
+use std::io::{self, BufRead, BufReader, Read, Write};
+use std::net::{Ipv4Addr, SocketAddr, TcpListener, TcpStream, UdpSocket};
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicBool, AtomicU64, AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::{Duration, Instant};
+use anyhow::{anyhow, Context, Result};
+use base64::{engine::general_purpose, Engine as _};
+use sha2::{Digest, Sha256};
+
+use crate::i2p::{I2PTunnel, TunnelType};
+use crate::i2p_socks::{self, SocksRequest};
+use crate::i2pd_process;
+use crate::logger::Logger;
+use crate::tor_control::parse_keywords;
+
+// 连接SAM端口的超时时间
+const CONNECT_TIMEOUT: Duration = Duration::from_secs(5);
+// 命令响应的超时时间（创建会话需要建立隧道，可能较慢）
+const REPLY_TIMEOUT: Duration = Duration::from_secs(120);
+// 检查会话是否仍然有效的间隔
+const KEEPALIVE_INTERVAL: Duration = Duration::from_secs(30);
+// 会话断开后重新创建前的等待时间
+const RETRY_DELAY: Duration = Duration::from_secs(10);
+// Ed25519签名，i2pd推荐的密钥类型
+const SIGNATURE_TYPE: u32 = 7;
+// SOCKS握手的超时时间
+const HANDSHAKE_TIMEOUT: Duration = Duration::from_secs(30);
+// UDP转发线程检查是否需要退出的间隔
+const UDP_POLL_INTERVAL: Duration = Duration::from_secs(1);
+
+// 一个SAM v3连接，HELLO握手后可发送命令
+struct SamConnection {
+    reader: BufReader<TcpStream>,
+    stream: TcpStream,
+}
+
+impl SamConnection {
+    fn connect(port: u16) -> Result<Self> {
+        let address = SocketAddr::from(([127, 0, 0, 1], port));
+        let stream = TcpStream::connect_timeout(&address, CONNECT_TIMEOUT)
+            .with_context(|| format!("Failed to connect to SAM port {}", port))?;
+        stream.set_read_timeout(Some(REPLY_TIMEOUT))?;
+        let reader = BufReader::new(stream.try_clone().context("Failed to clone SAM connection")?);
+        let mut connection = Self { reader, stream };
+        connection.command("HELLO VERSION MIN=3.1 MAX=3.3", "HELLO REPLY")?;
+        Ok(connection)
+    }
+
+    fn read_line(&mut self) -> Result<String> {
+        let mut line = String::new();
+        let read = self.reader.read_line(&mut line).context("Failed to read from SAM port")?;
+        if read == 0 {
+            return Err(anyhow!("SAM connection closed"));
+        }
+        Ok(line.trim_end_matches(['\r', '\n']).to_string())
+    }
+
+    // 发送命令并读取一行响应，响应不以expect开头或RESULT不为OK时视为错误
+    fn command(&mut self, command: &str, expect: &str) -> Result<HashMap<String, String>> {
+        self.stream
+            .write_all(format!("{}\n", command).as_bytes())
+            .context("Failed to write to SAM port")?;
+        let line = self.read_line()?;
+        let arguments = match line.strip_prefix(expect) {
+            Some(rest) => parse_keywords(rest),
+            None => return Err(anyhow!("Unexpected SAM reply: {}", line)),
+        };
+        match arguments.get("RESULT").map(String::as_str) {
+            None | Some("OK") => Ok(arguments),
+            Some(result) => Err(anyhow!(
+                "{} {}",
+                result,
+                arguments.get("MESSAGE").map(String::as_str).unwrap_or("")
+            )),
+        }
+    }
+
+    // 会话保活：SAM 3.2起支持PING
+    fn ping(&mut self) -> Result<()> {
+        self.stream.write_all(b"PING\n").context("Failed to write to SAM port")?;
+        let line = self.read_line()?;
+        if !line.starts_with("PONG") {
+            return Err(anyhow!("Unexpected SAM reply: {}", line));
+        }
+        Ok(())
+    }
+
+    // 流建立后连接变为原始数据通道，返回套接字与已读入缓冲区的数据
+    fn into_stream(self) -> (TcpStream, Vec<u8>) {
+        let buffered = self.reader.buffer().to_vec();
+        (self.stream, buffered)
+    }
+}
+
+// 生成新的目标地址，返回(公钥, 私钥)，均为I2P格式的Base64
+fn generate_destination(port: u16) -> Result<(String, String)> {
+    let mut connection = SamConnection::connect(port)?;
+    let reply = connection.command(&format!("DEST GENERATE SIGNATURE_TYPE={}", SIGNATURE_TYPE), "DEST REPLY")?;
+    match (reply.get("PUB"), reply.get("PRIV")) {
+        (Some(public), Some(private)) => Ok((public.clone(), private.clone())),
+        _ => Err(anyhow!("SAM did not return a destination")),
+    }
+}
+
+// I2P的Base64使用"-"与"~"代替"+"与"/"
+fn decode_i2p_base64(value: &str) -> Option<Vec<u8>> {
+    general_purpose::STANDARD.decode(value.replace('-', "+").replace('~', "/")).ok()
+}
+
+// 小写、无填充的Base32（RFC 4648字母表）
+fn base32(bytes: &[u8]) -> String {
+    const ALPHABET: &[u8] = b"abcdefghijklmnopqrstuvwxyz234567";
+    let mut result = String::new();
+    let mut buffer: u32 = 0;
+    let mut bits = 0;
+    for &byte in bytes {
+        buffer = (buffer << 8) | byte as u32;
+        bits += 8;
+        while bits >= 5 {
+            bits -= 5;
+            result.push(ALPHABET[((buffer >> bits) & 0x1f) as usize] as char);
+        }
+    }
+    if bits > 0 {
+        result.push(ALPHABET[((buffer << (5 - bits)) & 0x1f) as usize] as char);
+    }
+    result
+}
+
+// 由目标地址或私钥计算.b32.i2p地址：私钥以目标地址开头（256字节加密公钥、128字节签名公钥与证书），
+// 地址为目标地址SHA-256的Base32
+pub fn b32_address(key: &str) -> Option<String> {
+    let bytes = decode_i2p_base64(key)?;
+    let certificate_len = u16::from_be_bytes([*bytes.get(385)?, *bytes.get(386)?]) as usize;
+    let destination = bytes.get(..387 + certificate_len)?;
+    Some(format!("{}.b32.i2p", base32(&Sha256::digest(destination))))
+}
+
+// 服务端隧道的私钥文件，保存在i2pd工作目录中以保持地址不变
+fn keys_path(tunnel_id: usize) -> Result<PathBuf> {
+    let dir = i2pd_process::i2pd_home()?.join("sam-keys");
+    fs::create_dir_all(&dir).context("Failed to create SAM keys directory")?;
+    Ok(dir.join(format!("tunnel-{}.keys", tunnel_id)))
+}
+
+// 读取隧道的私钥，没有时通过SAM生成并保存
+fn load_or_generate_keys(tunnel_id: usize, port: u16) -> Result<String> {
+    if let Some(key) = read_keys(tunnel_id) {
+        return Ok(key);
+    }
+    let (_, private) = generate_destination(port)?;
+    write_keys(tunnel_id, &private)?;
+    Ok(private)
+}
+
+// 读取隧道已保存的私钥
+pub fn read_keys(tunnel_id: usize) -> Option<String> {
+    let contents = fs::read_to_string(keys_path(tunnel_id).ok()?).ok()?;
+    let key = contents.trim().to_string();
+    b32_address(&key).map(|_| key)
+}
+
+// 替换隧道的私钥（导入备份），隧道重新注册后使用新地址
+pub fn write_keys(tunnel_id: usize, private_key: &str) -> Result<()> {
+    if b32_address(private_key).is_none() {
+        return Err(anyhow!("Invalid I2P private key"));
+    }
+    fs::write(keys_path(tunnel_id)?, private_key).context("Failed to save tunnel keys")
+}
+
+// 删除隧道时一并删除其私钥，避免新隧道沿用相同编号时继承旧地址
+pub fn remove_keys(tunnel_id: usize) {
+    if let Ok(path) = keys_path(tunnel_id) {
+        let _ = fs::remove_file(path);
+    }
+}
+
+// 客户端隧道的目标：去掉协议前缀，拆分"主机[:端口]"
+fn parse_destination(destination: &str) -> Result<(String, Option<u16>)> {
+    let destination = destination.trim();
+    let target = match destination.split_once("://") {
+        Some((_, target)) => target,
+        None => destination,
+    }
+    .trim_end_matches('/');
+    let (host, port) = match target.rsplit_once(':') {
+        Some((host, port)) => match port.parse::<u16>() {
+            Ok(port) => (host, Some(port)),
+            Err(_) => (target, None),
+        },
+        None => (target, None),
+    };
+    if host.is_empty() || host.contains(char::is_whitespace) {
+        return Err(anyhow!("Invalid I2P destination: {}", destination));
+    }
+    Ok((host.to_string(), port))
+}
+
+// 隧道在SAM中的注册状态
+#[derive(Clone, Debug, PartialEq)]
+pub enum SamTunnelState {
+    Connecting,
+    Active,
+    Failed(String), // 正在等待重试
+}
+
+// 隧道转发的流量，由转发线程累加
+#[derive(Default)]
+struct TrafficCounters {
+    received: AtomicU64, // 从I2P收到的字节数
+    sent: AtomicU64,     // 发往I2P的字节数
+    active: AtomicUsize,
+    connections: AtomicU64,
+}
+
+// 隧道注册以来的流量统计
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct TunnelTraffic {
+    pub received: u64,
+    pub sent: u64,
+    pub active: usize,     // 当前连接数
+    pub connections: u64,  // 累计连接数
+}
+
+// 写入时累加字节数
+struct CountingWriter<'a> {
+    inner: TcpStream,
+    counter: &'a AtomicU64,
+}
+
+impl Write for CountingWriter<'_> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let written = self.inner.write(buf)?;
+        self.counter.fetch_add(written as u64, Ordering::Relaxed);
+        Ok(written)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.inner.flush()
+    }
+}
+
+// 一个通过SAM注册到路由器的隧道：后台线程持有会话连接，连接关闭时路由器即移除该隧道
+pub struct SamTunnel {
+    state: Arc<Mutex<SamTunnelState>>,
+    address: Arc<Mutex<Option<String>>>, // 服务端隧道的.b32.i2p地址
+    traffic: Arc<TrafficCounters>,
+    stop: Arc<AtomicBool>,
+}
+
+impl SamTunnel {
+    pub fn start(logger: Arc<Mutex<Logger>>, sam_port: u16, tunnel: &I2PTunnel) -> Self {
+        let state = Arc::new(Mutex::new(SamTunnelState::Connecting));
+        let address = Arc::new(Mutex::new(None));
+        let traffic = Arc::new(TrafficCounters::default());
+        let stop = Arc::new(AtomicBool::new(false));
+
+        let session = Session {
+            logger,
+            sam_port,
+            tunnel: tunnel.clone(),
+            id: format!("invizible-{}", tunnel.id),
+            state: Arc::clone(&state),
+            address: Arc::clone(&address),
+            traffic: Arc::clone(&traffic),
+            stop: Arc::clone(&stop),
+        };
+        thread::spawn(move || session.run());
+
+        Self { state, address, traffic, stop }
+    }
+
+    pub fn state(&self) -> SamTunnelState {
+        self.state.lock().map(|s| s.clone()).unwrap_or(SamTunnelState::Connecting)
+    }
+
+    pub fn address(&self) -> Option<String> {
+        self.address.lock().ok().and_then(|a| a.clone())
+    }
+
+    pub fn traffic(&self) -> TunnelTraffic {
+        TunnelTraffic {
+            received: self.traffic.received.load(Ordering::Relaxed),
+            sent: self.traffic.sent.load(Ordering::Relaxed),
+            active: self.traffic.active.load(Ordering::Relaxed),
+            connections: self.traffic.connections.load(Ordering::Relaxed),
+        }
+    }
+
+    pub fn stop(&self) {
+        self.stop.store(true, Ordering::SeqCst);
+    }
+}
+
+impl Drop for SamTunnel {
+    fn drop(&mut self) {
+        self.stop();
+    }
+}
+
+// 后台线程中的会话
+struct Session {
+    logger: Arc<Mutex<Logger>>,
+    sam_port: u16,
+    tunnel: I2PTunnel,
+    id: String,
+    state: Arc<Mutex<SamTunnelState>>,
+    address: Arc<Mutex<Option<String>>>,
+    traffic: Arc<TrafficCounters>,
+    stop: Arc<AtomicBool>,
+}
+
+impl Session {
+    fn set_state(&self, state: SamTunnelState) {
+        if let Ok(mut current) = self.state.lock() {
+            *current = state;
+        }
+    }
+
+    fn stopped(&self) -> bool {
+        self.stop.load(Ordering::SeqCst)
+    }
+
+    // 会话断开（如i2pd重启）后自动重新创建，直到被停止
+    fn run(self) {
+        while !self.stopped() {
+            self.set_state(SamTunnelState::Connecting);
+            let result = match self.tunnel.tunnel_type {
+                TunnelType::Client => self.run_client(),
+                TunnelType::Server => self.run_server(),
+                TunnelType::Socks => self.run_socks(),
+            };
+            if let Err(e) = result {
+                if self.stopped() {
+                    break;
+                }
+                if let Ok(mut logger) = self.logger.lock() {
+                    logger.warning("I2P", &format!("隧道 '{}' 的SAM会话失败: {:#}", self.tunnel.name, e));
+                }
+                self.set_state(SamTunnelState::Failed(format!("{:#}", e)));
+                let retry_at = Instant::now() + RETRY_DELAY;
+                while !self.stopped() && Instant::now() < retry_at {
+                    thread::sleep(Duration::from_millis(200));
+                }
+            }
+        }
+        if let Ok(mut logger) = self.logger.lock() {
+            logger.info("I2P", &format!("已注销隧道 '{}'", self.tunnel.name));
+        }
+    }
+
+    fn create_session(&self, destination: &str) -> Result<SamConnection> {
+        create_session(self.sam_port, &self.tunnel, &self.id, "STREAM", destination)
+    }
+
+    // 会话建立后保持连接，定期PING，直到被停止或连接断开
+    fn keep_alive(&self, control: &mut SamConnection, mut poll: impl FnMut() -> Result<()>) -> Result<()> {
+        let mut last_ping = Instant::now();
+        while !self.stopped() {
+            poll()?;
+            if last_ping.elapsed() >= KEEPALIVE_INTERVAL {
+                control.ping().context("SAM session closed")?;
+                last_ping = Instant::now();
+            }
+            thread::sleep(Duration::from_millis(100));
+        }
+        Ok(())
+    }
+
+    // 客户端隧道：在本地端口监听，每个连接通过STREAM CONNECT转发到目标地址
+    fn run_client(&self) -> Result<()> {
+        let (host, port) = parse_destination(&self.tunnel.destination)?;
+        let mut control = self.create_session("TRANSIENT")?;
+        let listener = TcpListener::bind(("127.0.0.1", self.tunnel.local_port))
+            .with_context(|| format!("Failed to listen on 127.0.0.1:{}", self.tunnel.local_port))?;
+        listener.set_nonblocking(true)?;
+        if let Ok(mut logger) = self.logger.lock() {
+            logger.info("I2P", &format!(
+                "客户端隧道 '{}' 已注册: 127.0.0.1:{} → {}",
+                self.tunnel.name, self.tunnel.local_port, host
+            ));
+        }
+        self.set_state(SamTunnelState::Active);
+
+        self.keep_alive(&mut control, || {
+            match listener.accept() {
+                Ok((client, _)) => {
+                    let sam_port = self.sam_port;
+                    let id = self.id.clone();
+                    let host = host.clone();
+                    let logger = Arc::clone(&self.logger);
+                    let name = self.tunnel.name.clone();
+                    let traffic = Arc::clone(&self.traffic);
+                    thread::spawn(move || {
+                        if let Err(e) = connect_stream(client, sam_port, &id, &host, port, &traffic) {
+                            if let Ok(mut logger) = logger.lock() {
+                                logger.debug("I2P", &format!("隧道 '{}' 连接 {} 失败: {:#}", name, host, e));
+                            }
+                        }
+                    });
+                    Ok(())
+                }
+                Err(e) if e.kind() == io::ErrorKind::WouldBlock => Ok(()),
+                Err(e) => Err(e).context("Failed to accept connection"),
+            }
+        })
+    }
+
+    // 服务端隧道：使用保存的私钥创建会话，通过STREAM FORWARD把访问转发到本机的中转端口，
+    // 再由中转端口连接本地服务（以便统计流量）
+    fn run_server(&self) -> Result<()> {
+        let private_key = load_or_generate_keys(self.tunnel.id, self.sam_port)?;
+        let address = b32_address(&private_key);
+        if let Ok(mut current) = self.address.lock() {
+            *current = address.clone();
+        }
+        let mut control = self.create_session(&private_key)?;
+        let relay = TcpListener::bind(("127.0.0.1", 0)).context("Failed to listen for forwarded streams")?;
+        relay.set_nonblocking(true)?;
+        let relay_port = relay.local_addr()?.port();
+        // FORWARD在该连接保持打开期间有效
+        let mut forward = SamConnection::connect(self.sam_port)?;
+        forward.command(
+            &format!("STREAM FORWARD ID={} PORT={} HOST=127.0.0.1 SILENT=true", self.id, relay_port),
+            "STREAM STATUS",
+        ).context("Failed to forward SAM stream")?;
+        if let Ok(mut logger) = self.logger.lock() {
+            logger.info("I2P", &format!(
+                "服务端隧道 '{}' 已注册: {} → 127.0.0.1:{}",
+                self.tunnel.name, address.unwrap_or_default(), self.tunnel.local_port
+            ));
+        }
+        self.set_state(SamTunnelState::Active);
+
+        self.keep_alive(&mut control, || {
+            match relay.accept() {
+                Ok((stream, _)) => {
+                    let local_port = self.tunnel.local_port;
+                    let logger = Arc::clone(&self.logger);
+                    let name = self.tunnel.name.clone();
+                    let traffic = Arc::clone(&self.traffic);
+                    thread::spawn(move || {
+                        let result = stream.set_nonblocking(false)
+                            .map_err(anyhow::Error::from)
+                            .and_then(|_| {
+                                let service = TcpStream::connect_timeout(&SocketAddr::from(([127, 0, 0, 1], local_port)), CONNECT_TIMEOUT)
+                                    .with_context(|| format!("Failed to connect to 127.0.0.1:{}", local_port))?;
+                                relay_streams(service, stream, &[], &traffic)
+                            });
+                        if let Err(e) = result {
+                            if let Ok(mut logger) = logger.lock() {
+                                logger.debug("I2P", &format!("隧道 '{}' 转发到本地服务失败: {:#}", name, e));
+                            }
+                        }
+                    });
+                    Ok(())
+                }
+                Err(e) if e.kind() == io::ErrorKind::WouldBlock => Ok(()),
+                Err(e) => Err(e).context("Failed to accept forwarded stream"),
+            }
+        })?;
+        drop(forward);
+        Ok(())
+    }
+
+    // SOCKS代理隧道：在本地端口提供SOCKS5，CONNECT通过STREAM CONNECT转发，
+    // UDP ASSOCIATE为每个关联创建单独的DATAGRAM会话
+    fn run_socks(&self) -> Result<()> {
+        let mut control = self.create_session("TRANSIENT")?;
+        let listener = TcpListener::bind(("127.0.0.1", self.tunnel.local_port))
+            .with_context(|| format!("Failed to listen on 127.0.0.1:{}", self.tunnel.local_port))?;
+        listener.set_nonblocking(true)?;
+        if let Ok(mut logger) = self.logger.lock() {
+            logger.info("I2P", &format!(
+                "SOCKS代理隧道 '{}' 已注册: 127.0.0.1:{}{}",
+                self.tunnel.name, self.tunnel.local_port, if self.tunnel.udp { "（支持UDP）" } else { "" }
+            ));
+        }
+        self.set_state(SamTunnelState::Active);
+
+        let mut associations = 0usize;
+        self.keep_alive(&mut control, || {
+            match listener.accept() {
+                Ok((client, _)) => {
+                    associations += 1;
+                    let socks = SocksClient {
+                        sam_port: self.sam_port,
+                        tunnel: self.tunnel.clone(),
+                        id: self.id.clone(),
+                        udp_id: format!("{}-udp{}", self.id, associations),
+                        traffic: Arc::clone(&self.traffic),
+                        stop: Arc::clone(&self.stop),
+                    };
+                    let logger = Arc::clone(&self.logger);
+                    thread::spawn(move || {
+                        if let Err(e) = socks.serve(client) {
+                            if let Ok(mut logger) = logger.lock() {
+                                logger.debug("I2P", &format!("隧道 '{}' 的SOCKS连接失败: {:#}", socks.tunnel.name, e));
+                            }
+                        }
+                    });
+                    Ok(())
+                }
+                Err(e) if e.kind() == io::ErrorKind::WouldBlock => Ok(()),
+                Err(e) => Err(e).context("Failed to accept connection"),
+            }
+        })
+    }
+}
+
+// SOCKS代理隧道的一个客户端连接
+struct SocksClient {
+    sam_port: u16,
+    tunnel: I2PTunnel,
+    id: String,     // 隧道的STREAM会话
+    udp_id: String, // UDP ASSOCIATE时创建的DATAGRAM会话
+    traffic: Arc<TrafficCounters>,
+    stop: Arc<AtomicBool>,
+}
+
+impl SocksClient {
+    fn serve(&self, mut client: TcpStream) -> Result<()> {
+        client.set_nonblocking(false)?;
+        client.set_read_timeout(Some(HANDSHAKE_TIMEOUT))?;
+        let unspecified = SocketAddr::from((Ipv4Addr::UNSPECIFIED, 0));
+        match i2p_socks::handshake(&mut client)? {
+            SocksRequest::Connect { host, port } => {
+                let (stream, buffered) = match open_stream(self.sam_port, &self.id, &host, Some(port).filter(|&p| p != 0)) {
+                    Ok(opened) => opened,
+                    Err(e) => {
+                        let _ = i2p_socks::reply(&mut client, i2p_socks::HOST_UNREACHABLE, unspecified);
+                        return Err(e);
+                    }
+                };
+                i2p_socks::reply(&mut client, i2p_socks::SUCCEEDED, unspecified)?;
+                client.set_read_timeout(None)?;
+                stream.set_read_timeout(None)?;
+                relay_streams(client, stream, &buffered, &self.traffic)
+            }
+            SocksRequest::UdpAssociate if self.tunnel.udp => self.udp_associate(client),
+            SocksRequest::UdpAssociate => {
+                i2p_socks::reply(&mut client, i2p_socks::COMMAND_NOT_SUPPORTED, unspecified)?;
+                Err(anyhow!("UDP is disabled for this tunnel"))
+            }
+            SocksRequest::Unsupported(code) => {
+                i2p_socks::reply(&mut client, code, unspecified)?;
+                Err(anyhow!("Unsupported SOCKS request"))
+            }
+        }
+    }
+
+    // UDP关联：客户端的数据包经SAM的UDP端口发出，会话收到的数据报由路由器转发到inbound后交给客户端，
+    // 关联在控制连接关闭时结束
+    fn udp_associate(&self, mut control: TcpStream) -> Result<()> {
+        let relay = UdpSocket::bind(("127.0.0.1", 0)).context("Failed to bind UDP relay")?;
+        let inbound = UdpSocket::bind(("127.0.0.1", 0)).context("Failed to bind UDP relay")?;
+        let style = format!("DATAGRAM PORT={} HOST=127.0.0.1", inbound.local_addr()?.port());
+        let session = match create_session(self.sam_port, &self.tunnel, &self.udp_id, &style, "TRANSIENT") {
+            Ok(session) => session,
+            Err(e) => {
+                let _ = i2p_socks::reply(&mut control, i2p_socks::GENERAL_FAILURE, SocketAddr::from((Ipv4Addr::UNSPECIFIED, 0)));
+                return Err(e);
+            }
+        };
+        i2p_socks::reply(&mut control, i2p_socks::SUCCEEDED, relay.local_addr()?)?;
+        relay.set_read_timeout(Some(UDP_POLL_INTERVAL))?;
+        inbound.set_read_timeout(Some(UDP_POLL_INTERVAL))?;
+        control.set_read_timeout(Some(UDP_POLL_INTERVAL))?;
+        self.traffic.active.fetch_add(1, Ordering::Relaxed);
+        self.traffic.connections.fetch_add(1, Ordering::Relaxed);
+
+        let closed = Arc::new(AtomicBool::new(false));
+        let client_addr = Arc::new(Mutex::new(None));
+        let outbound = {
+            let relay = relay.try_clone()?;
+            let closed = Arc::clone(&closed);
+            let client_addr = Arc::clone(&client_addr);
+            let stop = Arc::clone(&self.stop);
+            let traffic = Arc::clone(&self.traffic);
+            let sam_port = self.sam_port;
+            let udp_id = self.udp_id.clone();
+            thread::spawn(move || {
+                let sam_udp = SocketAddr::from(([127, 0, 0, 1], i2pd_process::SAM_UDP_PORT));
+                let mut destinations: HashMap<String, String> = HashMap::new();
+                let mut buffer = vec![0u8; 65536];
+                while !closed.load(Ordering::SeqCst) && !stop.load(Ordering::SeqCst) {
+                    let (len, from) = match relay.recv_from(&mut buffer) {
+                        Ok(received) => received,
+                        Err(_) => continue,
+                    };
+                    if let Ok(mut client_addr) = client_addr.lock() {
+                        *client_addr = Some(from);
+                    }
+                    let (host, _, data) = match i2p_socks::parse_udp_packet(&buffer[..len]) {
+                        Some(parsed) => parsed,
+                        None => continue,
+                    };
+                    // 域名只解析一次
+                    if !destinations.contains_key(&host) {
+                        match lookup(sam_port, &host) {
+                            Ok(destination) => {
+                                destinations.insert(host.clone(), destination);
+                            }
+                            Err(_) => continue,
+                        }
+                    }
+                    let mut datagram = format!("3.0 {} {}\n", udp_id, destinations[&host]).into_bytes();
+                    datagram.extend_from_slice(data);
+                    if relay.send_to(&datagram, sam_udp).is_ok() {
+                        traffic.sent.fetch_add(data.len() as u64, Ordering::Relaxed);
+                    }
+                }
+            })
+        };
+        let inbound_thread = {
+            let relay = relay.try_clone()?;
+            let closed = Arc::clone(&closed);
+            let client_addr = Arc::clone(&client_addr);
+            let stop = Arc::clone(&self.stop);
+            let traffic = Arc::clone(&self.traffic);
+            thread::spawn(move || {
+                let mut buffer = vec![0u8; 65536];
+                while !closed.load(Ordering::SeqCst) && !stop.load(Ordering::SeqCst) {
+                    let len = match inbound.recv(&mut buffer) {
+                        Ok(len) => len,
+                        Err(_) => continue,
+                    };
+                    // 首行为发送方的目标地址（可能带FROM_PORT等参数），之后是数据
+                    let packet = &buffer[..len];
+                    let newline = match packet.iter().position(|&b| b == b'\n') {
+                        Some(newline) => newline,
+                        None => continue,
+                    };
+                    let header = String::from_utf8_lossy(&packet[..newline]);
+                    let source = header.split_whitespace().next().and_then(b32_address);
+                    let client = client_addr.lock().ok().and_then(|addr| *addr);
+                    if let (Some(source), Some(client)) = (source, client) {
+                        let data = &packet[newline + 1..];
+                        if relay.send_to(&i2p_socks::udp_packet(&source, 0, data), client).is_ok() {
+                            traffic.received.fetch_add(data.len() as u64, Ordering::Relaxed);
+                        }
+                    }
+                }
+            })
+        };
+
+        // 控制连接关闭或隧道停止时结束关联
+        let mut byte = [0u8; 1];
+        while !self.stop.load(Ordering::SeqCst) {
+            match control.read(&mut byte) {
+                Ok(0) => break,
+                Ok(_) => {}
+                Err(e) if matches!(e.kind(), io::ErrorKind::WouldBlock | io::ErrorKind::TimedOut) => {}
+                Err(_) => break,
+            }
+        }
+        closed.store(true, Ordering::SeqCst);
+        let _ = outbound.join();
+        let _ = inbound_thread.join();
+        drop(session);
+        self.traffic.active.fetch_sub(1, Ordering::Relaxed);
+        Ok(())
+    }
+}
+
+// 创建SAM会话，style可带参数（如"DATAGRAM PORT=..."），连接关闭时会话即被移除
+fn create_session(sam_port: u16, tunnel: &I2PTunnel, id: &str, style: &str, destination: &str) -> Result<SamConnection> {
+    let mut control = SamConnection::connect(sam_port)?;
+    // 隧道的跳数与数量作为I2CP选项附加在命令末尾
+    let options: Vec<String> = tunnel.options.i2cp_options()
+        .into_iter()
+        .map(|(key, value)| format!("{}={}", key, value))
+        .collect();
+    control.command(
+        &format!(
+            "SESSION CREATE STYLE={} ID={} DESTINATION={} SIGNATURE_TYPE={} inbound.nickname={} {}",
+            style, id, destination, SIGNATURE_TYPE, id, options.join(" ")
+        ),
+        "SESSION STATUS",
+    ).context("Failed to create SAM session")?;
+    Ok(control)
+}
+
+// 通过路由器的地址簿把.i2p域名或.b32.i2p地址解析为完整的目标地址
+pub fn lookup(sam_port: u16, name: &str) -> Result<String> {
+    let mut connection = SamConnection::connect(sam_port)?;
+    lookup_with(&mut connection, name)
+}
+
+fn lookup_with(connection: &mut SamConnection, name: &str) -> Result<String> {
+    let reply = connection.command(&format!("NAMING LOOKUP NAME={}", name), "NAMING REPLY")
+        .with_context(|| format!("Failed to resolve {}", name))?;
+    reply.get("VALUE").cloned().ok_or_else(|| anyhow!("Failed to resolve {}", name))
+}
+
+// 在会话中建立到目标地址的流，返回数据通道与已读入的数据
+fn open_stream(sam_port: u16, id: &str, host: &str, port: Option<u16>) -> Result<(TcpStream, Vec<u8>)> {
+    let mut connection = SamConnection::connect(sam_port)?;
+    // 域名与.b32.i2p地址先解析为完整的目标地址
+    let destination = if host.ends_with(".i2p") {
+        lookup_with(&mut connection, host)?
+    } else {
+        host.to_string()
+    };
+    let mut command = format!("STREAM CONNECT ID={} DESTINATION={} SILENT=false", id, destination);
+    if let Some(port) = port {
+        command.push_str(&format!(" TO_PORT={}", port));
+    }
+    connection.command(&command, "STREAM STATUS")?;
+    Ok(connection.into_stream())
+}
+
+// I2P站点的HTTP响应
+pub struct HttpResponse {
+    pub status: u16,
+    pub head: String, // 状态行与响应头
+    pub body: String,
+}
+
+// 通过临时会话发送HTTP GET请求，不跟随重定向
+pub fn http_request(sam_port: u16, url: &str) -> Result<HttpResponse> {
+    let parsed = url::Url::parse(url).with_context(|| format!("Invalid URL: {}", url))?;
+    if parsed.scheme() != "http" {
+        return Err(anyhow!("Only http:// URLs are supported inside I2P: {}", url));
+    }
+    let host = parsed.host_str().ok_or_else(|| anyhow!("URL has no host: {}", url))?;
+    let path = match parsed.query() {
+        Some(query) => format!("{}?{}", parsed.path(), query),
+        None => parsed.path().to_string(),
+    };
+
+    let id = format!("invizible-fetch-{:08x}", rand::random::<u32>());
+    let mut control = SamConnection::connect(sam_port)?;
+    control.command(
+        &format!("SESSION CREATE STYLE=STREAM ID={} DESTINATION=TRANSIENT SIGNATURE_TYPE={}", id, SIGNATURE_TYPE),
+        "SESSION STATUS",
+    ).context("Failed to create SAM session")?;
+
+    let (mut stream, mut response) = open_stream(sam_port, &id, host, parsed.port())?;
+    stream.set_read_timeout(Some(REPLY_TIMEOUT))?;
+    stream.write_all(format!(
+        "GET {} HTTP/1.0\r\nHost: {}\r\nUser-Agent: MYOB/6.66 (AN/ON)\r\nConnection: close\r\n\r\n",
+        path, host
+    ).as_bytes())?;
+    io::Read::read_to_end(&mut stream, &mut response).context("Failed to read HTTP response")?;
+    drop(control);
+
+    let response = String::from_utf8_lossy(&response);
+    let (head, body) = response.split_once("\r\n\r\n").ok_or_else(|| anyhow!("Malformed HTTP response"))?;
+    let status = head.lines().next()
+        .and_then(|line| line.split_whitespace().nth(1))
+        .and_then(|code| code.parse().ok())
+        .ok_or_else(|| anyhow!("Malformed HTTP response"))?;
+    Ok(HttpResponse { status, head: head.to_string(), body: body.to_string() })
+}
+
+// 下载I2P站点上的文件（如地址簿订阅），返回响应正文
+pub fn http_get(sam_port: u16, url: &str) -> Result<String> {
+    let response = http_request(sam_port, url)?;
+    if response.status != 200 {
+        return Err(anyhow!("HTTP {}", response.head.lines().next().unwrap_or("")));
+    }
+    Ok(response.body)
+}
+
+// 为一个本地连接建立到目标地址的流，然后双向转发数据
+fn connect_stream(client: TcpStream, sam_port: u16, id: &str, host: &str, port: Option<u16>, traffic: &Arc<TrafficCounters>) -> Result<()> {
+    client.set_nonblocking(false)?;
+    let (stream, buffered) = open_stream(sam_port, id, host, port)?;
+    stream.set_read_timeout(None)?;
+    relay_streams(client, stream, &buffered, traffic)
+}
+
+// 在本地连接与I2P流之间双向转发并统计流量，buffered为已从I2P流读出的数据
+fn relay_streams(local: TcpStream, stream: TcpStream, buffered: &[u8], traffic: &Arc<TrafficCounters>) -> Result<()> {
+    traffic.active.fetch_add(1, Ordering::Relaxed);
+    traffic.connections.fetch_add(1, Ordering::Relaxed);
+    let result = (|| -> Result<()> {
+        let mut local_writer = CountingWriter { inner: local.try_clone()?, counter: &traffic.received };
+        local_writer.write_all(buffered)?;
+        let mut stream_reader = stream.try_clone()?;
+        let mut local_reader = local;
+        let upload_traffic = Arc::clone(traffic);
+        let upload = thread::spawn(move || {
+            let mut stream_writer = CountingWriter { inner: stream, counter: &upload_traffic.sent };
+            let _ = io::copy(&mut local_reader, &mut stream_writer);
+            let _ = stream_writer.inner.shutdown(std::net::Shutdown::Both);
+        });
+        let _ = io::copy(&mut stream_reader, &mut local_writer);
+        let _ = local_writer.inner.shutdown(std::net::Shutdown::Both);
+        let _ = upload.join();
+        Ok(())
+    })();
+    traffic.active.fetch_sub(1, Ordering::Relaxed);
+    result
+}
@@ -0,0 +1,115 @@
+use std::io::{Read, Write};
+use std::net::{IpAddr, SocketAddr, TcpStream};
+use anyhow::{anyhow, Context, Result};
+
+// SOCKS5应答码
+pub const SUCCEEDED: u8 = 0x00;
+pub const GENERAL_FAILURE: u8 = 0x01;
+pub const HOST_UNREACHABLE: u8 = 0x04;
+pub const COMMAND_NOT_SUPPORTED: u8 = 0x07;
+pub const ADDRESS_NOT_SUPPORTED: u8 = 0x08;
+
+// 地址类型
+const ATYP_IPV4: u8 = 0x01;
+const ATYP_DOMAIN: u8 = 0x03;
+const ATYP_IPV6: u8 = 0x04;
+
+// 客户端的SOCKS5请求
+#[derive(Debug, PartialEq)]
+pub enum SocksRequest {
+    Connect { host: String, port: u16 },
+    UdpAssociate,
+    Unsupported(u8), // 应答码
+}
+
+fn read_u8(stream: &mut TcpStream) -> Result<u8> {
+    let mut byte = [0u8; 1];
+    stream.read_exact(&mut byte)?;
+    Ok(byte[0])
+}
+
+// 完成无认证的SOCKS5握手并读取请求。I2P中只能使用域名（.i2p或.b32.i2p），IP地址不受支持
+pub fn handshake(stream: &mut TcpStream) -> Result<SocksRequest> {
+    if read_u8(stream)? != 5 {
+        return Err(anyhow!("Only SOCKS5 is supported"));
+    }
+    let mut methods = vec![0u8; read_u8(stream)? as usize];
+    stream.read_exact(&mut methods)?;
+    if !methods.contains(&0x00) {
+        stream.write_all(&[5, 0xff])?;
+        return Err(anyhow!("Client does not allow unauthenticated SOCKS"));
+    }
+    stream.write_all(&[5, 0x00])?;
+
+    let mut header = [0u8; 4];
+    stream.read_exact(&mut header).context("Failed to read SOCKS request")?;
+    let [version, command, _, atyp] = header;
+    if version != 5 {
+        return Err(anyhow!("Invalid SOCKS request"));
+    }
+    let host = match atyp {
+        ATYP_DOMAIN => {
+            let mut name = vec![0u8; read_u8(stream)? as usize];
+            stream.read_exact(&mut name)?;
+            Some(String::from_utf8(name).map_err(|_| anyhow!("Invalid SOCKS host name"))?)
+        }
+        ATYP_IPV4 => {
+            stream.read_exact(&mut [0u8; 4])?;
+            None
+        }
+        ATYP_IPV6 => {
+            stream.read_exact(&mut [0u8; 16])?;
+            None
+        }
+        _ => return Err(anyhow!("Invalid SOCKS address type")),
+    };
+    let mut port = [0u8; 2];
+    stream.read_exact(&mut port)?;
+    let port = u16::from_be_bytes(port);
+
+    Ok(match (command, host) {
+        // UDP ASSOCIATE中的地址是客户端自己的地址，通常为0.0.0.0
+        (0x03, _) => SocksRequest::UdpAssociate,
+        (0x01, Some(host)) => SocksRequest::Connect { host: host.to_lowercase(), port },
+        (0x01, None) => SocksRequest::Unsupported(ADDRESS_NOT_SUPPORTED),
+        _ => SocksRequest::Unsupported(COMMAND_NOT_SUPPORTED),
+    })
+}
+
+// 发送应答，bound为CONNECT时本端的地址或UDP ASSOCIATE时的中继地址
+pub fn reply(stream: &mut TcpStream, code: u8, bound: SocketAddr) -> Result<()> {
+    let mut response = vec![5, code, 0];
+    match bound.ip() {
+        IpAddr::V4(ip) => {
+            response.push(ATYP_IPV4);
+            response.extend_from_slice(&ip.octets());
+        }
+        IpAddr::V6(ip) => {
+            response.push(ATYP_IPV6);
+            response.extend_from_slice(&ip.octets());
+        }
+    }
+    response.extend_from_slice(&bound.port().to_be_bytes());
+    stream.write_all(&response).context("Failed to send SOCKS reply")
+}
+
+// 解析客户端发来的UDP数据包（RSV RSV FRAG ATYP DST.ADDR DST.PORT DATA），不支持分片与IP地址
+pub fn parse_udp_packet(packet: &[u8]) -> Option<(String, u16, &[u8])> {
+    if packet.len() < 5 || packet[2] != 0 || packet[3] != ATYP_DOMAIN {
+        return None;
+    }
+    let len = packet[4] as usize;
+    let name = packet.get(5..5 + len)?;
+    let port = packet.get(5 + len..7 + len)?;
+    let host = std::str::from_utf8(name).ok()?.to_lowercase();
+    Some((host, u16::from_be_bytes([port[0], port[1]]), &packet[7 + len..]))
+}
+
+// 把收到的数据报包装为发给客户端的UDP数据包，来源为.b32.i2p地址
+pub fn udp_packet(host: &str, port: u16, data: &[u8]) -> Vec<u8> {
+    let mut packet = vec![0, 0, 0, ATYP_DOMAIN, host.len().min(255) as u8];
+    packet.extend_from_slice(&host.as_bytes()[..host.len().min(255)]);
+    packet.extend_from_slice(&port.to_be_bytes());
+    packet.extend_from_slice(data);
+    packet
+}
@@ -0,0 +1,485 @@
+use std::collections::VecDeque;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+use anyhow::{anyhow, Context, Result};
+
+use crate::components::{self, Executable};
+use crate::i2p_control::I2pControlClient;
+use crate::i2p::{I2PTunnel, TunnelType};
+use crate::i2p_router_settings::RouterSettings;
+use crate::logger::{LogLevel, Logger};
+use crate::services::{ManagedProcess, ProcessLauncher};
+use crate::supervisor::{ProcessSpec, ProcessState};
+use crate::utils;
+
+// i2pd网页控制台端口
+pub const CONSOLE_PORT: u16 = 7070;
+// SAM v3接口端口
+pub const SAM_PORT: u16 = 7656;
+// SAM接收待发送数据报的UDP端口
+pub const SAM_UDP_PORT: u16 = 7655;
+// 读取控制台状态的间隔
+pub const STATUS_INTERVAL: Duration = Duration::from_secs(2);
+// 保留的带宽历史时长
+pub const HISTORY_MINUTES: u64 = 30;
+// 超过此时间仍未建立客户端隧道时报告失败（i2pd仍会继续尝试）
+const READY_TIMEOUT: Duration = Duration::from_secs(300);
+
+// i2pd运行状态
+#[derive(Clone, Debug, PartialEq)]
+pub enum I2pdState {
+    Starting,
+    Integrating { routers: u32 }, // 正在发现节点并建立隧道
+    Ready,
+    Restarting { attempt: u32, delay: Duration },
+    Failed(String),
+}
+
+// 路由器状态：客户端隧道数来自网页控制台，其余优先使用I2PControl的实时数据
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct RouterStatus {
+    pub network: String,     // 网络状态，如OK、Testing、Firewalled
+    pub routers: u32,        // 已知路由器数
+    pub active_peers: u32,   // 活跃节点数（仅I2PControl提供）
+    pub floodfills: u32,     // NetDB中的floodfill路由器数（仅I2PControl提供）
+    pub leasesets: u32,      // NetDB中的LeaseSet数（仅I2PControl提供）
+    pub client_tunnels: u32,
+    pub transit_tunnels: u32,
+    pub tunnel_success_rate: Option<u32>, // 隧道建立成功率（%）
+    pub in_kbps: f32,        // 入站速率（KiB/s）
+    pub out_kbps: f32,
+    pub uptime: Option<Duration>, // 仅I2PControl提供
+    pub version: String,          // 仅I2PControl提供
+}
+
+// 生成i2pd配置所需的设置
+#[derive(Clone, Debug)]
+pub struct I2pdSettings {
+    pub tunnels: Vec<I2PTunnel>, // 写入tunnels.conf的隧道（I2P页面只写入代理隧道，其余通过SAM注册）
+    pub console_port: u16,
+    pub sam_port: u16,
+    pub control_port: u16,
+    pub control_password: String, // I2PControl密码，每次启动随机生成
+    pub subscriptions: Vec<String>, // 地址簿订阅地址，由i2pd定期更新
+    pub router: RouterSettings,
+    pub tor_socks_port: Option<u16>, // 设置后NTCP2与补种均通过Tor连接
+}
+
+// i2pd工作目录（配置文件、路由器数据与隧道密钥所在位置）
+pub fn i2pd_home() -> Result<PathBuf> {
+    let home = Path::new(&utils::get_app_data_dir()?).join("i2pd");
+    fs::create_dir_all(&home).context("Failed to create i2pd directory")?;
+    Ok(home)
+}
+
+// 配置文件中的值不能换行，也不能包含注释符
+fn conf_value(value: &str) -> Result<&str> {
+    if value.contains(['\n', '\r', '#', ';']) {
+        return Err(anyhow!("配置值不能包含换行、#或; : {}", value));
+    }
+    Ok(value)
+}
+
+fn generate_conf(settings: &I2pdSettings, home: &Path) -> Result<String> {
+    let tunconf = home.join("tunnels.conf").display().to_string();
+    let subscriptions = settings.subscriptions.iter()
+        .map(|url| conf_value(url))
+        .collect::<Result<Vec<_>>>()?
+        .join(",");
+    let lines = [
+        "# 由InviZible Pro自动生成，修改将在下次启动时被覆盖".to_string(),
+        format!("tunconf = {}", conf_value(&tunconf)?),
+        "log = stdout".to_string(),
+        format!("loglevel = {}", settings.router.log_level.conf_value()),
+        "daemon = false".to_string(),
+        "ipv4 = true".to_string(),
+        "ipv6 = false".to_string(),
+        format!("bandwidth = {}", settings.router.bandwidth_value()),
+        format!("share = {}", settings.router.share.min(100)),
+        format!("notransit = {}", !settings.router.transit),
+        String::new(),
+        "[limits]".to_string(),
+        format!("transittunnels = {}", settings.router.transit_tunnels),
+        String::new(),
+        "[http]".to_string(),
+        "enabled = true".to_string(),
+        "address = 127.0.0.1".to_string(),
+        format!("port = {}", settings.console_port),
+        String::new(),
+        // HTTP与SOCKS代理由tunnels.conf中的隧道提供，不使用内置的默认端口
+        "[httpproxy]".to_string(),
+        "enabled = false".to_string(),
+        String::new(),
+        "[socksproxy]".to_string(),
+        "enabled = false".to_string(),
+        String::new(),
+        "[sam]".to_string(),
+        "enabled = true".to_string(),
+        "address = 127.0.0.1".to_string(),
+        format!("port = {}", settings.sam_port),
+        format!("portudp = {}", SAM_UDP_PORT),
+        String::new(),
+        "[i2pcontrol]".to_string(),
+        "enabled = true".to_string(),
+        "address = 127.0.0.1".to_string(),
+        format!("port = {}", settings.control_port),
+        format!("password = {}", conf_value(&settings.control_password)?),
+        String::new(),
+        "[addressbook]".to_string(),
+        format!("subscriptions = {}", subscriptions),
+        String::new(),
+    ];
+    let mut contents = lines.join("\n");
+    // 经由Tor时只能使用TCP，并且不对外公布地址（Tor无法接收入站连接）
+    if let Some(port) = settings.tor_socks_port {
+        let proxy = [
+            "[ntcp2]".to_string(),
+            "enabled = true".to_string(),
+            "published = false".to_string(),
+            format!("proxy = socks://127.0.0.1:{}", port),
+            String::new(),
+            "[ssu2]".to_string(),
+            "enabled = false".to_string(),
+            String::new(),
+            "[reseed]".to_string(),
+            format!("proxy = socks://127.0.0.1:{}", port),
+            String::new(),
+        ];
+        contents.push('\n');
+        contents.push_str(&proxy.join("\n"));
+    }
+    Ok(contents)
+}
+
+// 目标地址的协议前缀决定隧道类型：http:// 为HTTP代理，socks:// 为SOCKS代理，
+// irc://主机[:端口] 为IRC隧道，其余按"主机.i2p[:端口]"作为普通客户端隧道
+fn tunnel_section(tunnel: &I2PTunnel) -> Result<String> {
+    let name = conf_value(&tunnel.name)?;
+    let mut lines = vec![format!("# {}", name), format!("[tunnel-{}]", tunnel.id)];
+    let destination = tunnel.destination.trim();
+    let (scheme, target) = match destination.split_once("://") {
+        Some((scheme, target)) => (scheme.to_lowercase(), target.trim_end_matches('/')),
+        None => (String::new(), destination),
+    };
+    let (host, port) = match target.rsplit_once(':') {
+        Some((host, port)) if port.parse::<u16>().is_ok() => (host, Some(port)),
+        _ => (target, None),
+    };
+    match tunnel.tunnel_type {
+        TunnelType::Client | TunnelType::Socks => {
+            let kind = match scheme.as_str() {
+                // SOCKS隧道通常通过SAM注册，写入tunnels.conf时使用i2pd的SOCKS代理（不支持UDP）
+                _ if tunnel.tunnel_type == TunnelType::Socks => "socks",
+                "http" => "httpproxy",
+                "socks" => "socks",
+                "irc" => "irc",
+                _ => "client",
+            };
+            lines.push(format!("type = {}", kind));
+            lines.push("address = 127.0.0.1".to_string());
+            lines.push(format!("port = {}", tunnel.local_port));
+            if kind == "irc" || kind == "client" {
+                if host.is_empty() {
+                    return Err(anyhow!("隧道 '{}' 缺少目标地址", tunnel.name));
+                }
+                lines.push(format!("destination = {}", conf_value(host)?));
+                if let Some(port) = port {
+                    lines.push(format!("destinationport = {}", port));
+                }
+            }
+            lines.push("keys = transient".to_string());
+        }
+        TunnelType::Server => {
+            // 服务端隧道把I2P上的访问转发到本机端口，密钥保存在工作目录中以保持地址不变
+            let kind = if scheme == "http" { "http" } else { "server" };
+            lines.push(format!("type = {}", kind));
+            lines.push("host = 127.0.0.1".to_string());
+            lines.push(format!("port = {}", tunnel.local_port));
+            lines.push(format!("keys = tunnel-{}.dat", tunnel.id));
+        }
+    }
+    for (key, value) in tunnel.options.i2cp_options() {
+        lines.push(format!("{} = {}", key, value));
+    }
+    lines.push(String::new());
+    Ok(lines.join("\n"))
+}
+
+fn generate_tunnels(tunnels: &[I2PTunnel]) -> Result<String> {
+    let mut contents = "# 由InviZible Pro自动生成，修改将在下次启动时被覆盖\n\n".to_string();
+    for tunnel in tunnels {
+        contents.push_str(&tunnel_section(tunnel)?);
+        contents.push('\n');
+    }
+    Ok(contents)
+}
+
+// 取出控制台中"<b>标签</b> 值"的值部分
+fn console_value<'a>(html: &'a str, label: &str) -> Option<&'a str> {
+    let start = html.find(&format!("<b>{}</b>", label))? + label.len() + 7;
+    let rest = &html[start..];
+    Some(rest[..rest.find('<').unwrap_or(rest.len())].trim())
+}
+
+// 速率如"12.34 MiB (5.67 KiB/s)"，统一换算为KiB/s
+fn parse_rate(value: &str) -> f32 {
+    let rate = value.rsplit_once('(').map(|(_, r)| r.trim_end_matches(')')).unwrap_or("");
+    let mut parts = rate.split_whitespace();
+    let number: f32 = parts.next().and_then(|n| n.parse().ok()).unwrap_or(0.0);
+    match parts.next() {
+        Some("B/s") => number / 1024.0,
+        Some("MiB/s") => number * 1024.0,
+        Some("GiB/s") => number * 1024.0 * 1024.0,
+        _ => number,
+    }
+}
+
+fn parse_console(html: &str) -> RouterStatus {
+    let number = |label: &str| console_value(html, label).and_then(|v| v.parse().ok()).unwrap_or(0);
+    RouterStatus {
+        network: console_value(html, "Network status:").unwrap_or("").to_string(),
+        routers: number("Routers:"),
+        client_tunnels: number("Client Tunnels:"),
+        transit_tunnels: number("Transit Tunnels:"),
+        // 如"35%"
+        tunnel_success_rate: console_value(html, "Tunnel creation success rate:")
+            .and_then(|v| v.trim_end_matches('%').trim().parse().ok()),
+        in_kbps: console_value(html, "Received:").map(parse_rate).unwrap_or(0.0),
+        out_kbps: console_value(html, "Sent:").map(parse_rate).unwrap_or(0.0),
+        ..Default::default()
+    }
+}
+
+// 读取网页控制台首页
+fn query_console(client: &reqwest::blocking::Client, port: u16) -> Result<RouterStatus> {
+    let html = client.get(format!("http://127.0.0.1:{}/", port))
+        .send()
+        .context("Failed to connect to i2pd console")?
+        .text()
+        .context("Failed to read i2pd console")?;
+    Ok(parse_console(&html))
+}
+
+// 去掉终端颜色控制符（如"\x1b[1;31m"）
+fn strip_ansi(line: &str) -> String {
+    let mut result = String::with_capacity(line.len());
+    let mut chars = line.chars();
+    while let Some(c) = chars.next() {
+        if c == '\x1b' {
+            // 跳过到控制序列的结束字母
+            for c in chars.by_ref() {
+                if c.is_ascii_alphabetic() {
+                    break;
+                }
+            }
+        } else {
+            result.push(c);
+        }
+    }
+    result
+}
+
+// 解析i2pd日志行，如"12:00:00@123/warn - NetDb: ..."，返回日志级别与消息
+fn parse_log_line(line: &str) -> (LogLevel, String) {
+    let line = strip_ansi(line);
+    let line = line.trim();
+    // 前缀为"时间@线程/级别"，消息本身也可能包含" - "
+    let severity = line.split_once(" - ").and_then(|(prefix, message)| {
+        let (_, level) = prefix.split_once('@')?.1.rsplit_once('/')?;
+        Some((level, message.trim()))
+    });
+    match severity {
+        Some(("critical" | "error", message)) => (LogLevel::Error, message.to_string()),
+        Some(("warn", message)) => (LogLevel::Warning, message.to_string()),
+        Some(("info", message)) => (LogLevel::Info, message.to_string()),
+        Some(("debug", message)) => (LogLevel::Debug, message.to_string()),
+        // 没有级别标记的输出（如启动参数错误）按普通信息记录
+        _ => (LogLevel::Info, line.to_string()),
+    }
+}
+
+// 连续重复的日志行只记录一次，之后汇总重复次数
+#[derive(Default)]
+struct RepeatFilter {
+    last: Option<(LogLevel, String)>,
+    repeated: u32,
+}
+
+impl RepeatFilter {
+    // 返回需要记录的日志（可能包括上一条消息的重复次数）
+    fn push(&mut self, level: LogLevel, message: String) -> Vec<(LogLevel, String)> {
+        if self.last.as_ref().map_or(false, |(_, last)| *last == message) {
+            self.repeated += 1;
+            return Vec::new();
+        }
+        let mut entries = Vec::new();
+        if let Some((last_level, _)) = &self.last {
+            if self.repeated > 0 {
+                entries.push((*last_level, format!("上一条消息又重复了{}次", self.repeated)));
+            }
+        }
+        entries.push((level, message.clone()));
+        self.last = Some((level, message));
+        self.repeated = 0;
+        entries
+    }
+}
+
+// 运行中的i2pd.exe
+pub struct I2pdProcess {
+    process: Box<dyn ManagedProcess>,
+    status: Arc<Mutex<Option<RouterStatus>>>, // 最近一次读取的控制台状态
+    history: Arc<Mutex<VecDeque<(f32, f32)>>>, // 每次采样的(入站, 出站)速率，KiB/s
+    error: Arc<Mutex<Option<String>>>,        // 就绪前的严重错误
+    started: Instant,
+    stop: Arc<AtomicBool>,
+}
+
+impl I2pdProcess {
+    // 查找已安装的i2pd.exe，生成i2pd.conf与tunnels.conf后启动
+    pub fn start(launcher: &dyn ProcessLauncher, logger: Arc<Mutex<Logger>>, settings: &I2pdSettings) -> Result<Self> {
+        let exe = components::executable_path(Executable::I2pd)
+            .ok_or_else(|| anyhow!("未找到i2pd.exe，请在 设置 → 组件管理 中安装i2pd"))?;
+        let home = i2pd_home()?;
+        let conf = home.join("i2pd.conf");
+        fs::write(home.join("tunnels.conf"), generate_tunnels(&settings.tunnels)?).context("Failed to write tunnels.conf")?;
+        fs::write(&conf, generate_conf(settings, &home)?).context("Failed to write i2pd.conf")?;
+
+        let error = Arc::new(Mutex::new(None));
+        let handler_error = Arc::clone(&error);
+        let handler_logger = Arc::clone(&logger);
+        let repeats = Mutex::new(RepeatFilter::default());
+        let on_output = move |line: &str| {
+            if line.trim().is_empty() {
+                return;
+            }
+            let (level, message) = parse_log_line(line);
+            let entries = match repeats.lock() {
+                Ok(mut repeats) => repeats.push(level, message.clone()),
+                Err(_) => vec![(level, message.clone())],
+            };
+            if let Ok(mut logger) = handler_logger.lock() {
+                for (level, message) in entries {
+                    logger.log(level, "I2P", &message);
+                }
+            }
+            if level == LogLevel::Error {
+                if let Ok(mut error) = handler_error.lock() {
+                    *error = Some(message);
+                }
+            }
+        };
+
+        let mut spec = ProcessSpec::new("I2P", &exe);
+        spec.args = vec![
+            format!("--conf={}", conf.display()),
+            format!("--datadir={}", home.display()),
+        ];
+        spec.working_dir = Some(home);
+        spec.liveness_port = Some(settings.console_port);
+        spec.on_output = Some(Arc::new(on_output));
+
+        if let Ok(mut logger) = logger.lock() {
+            logger.info("I2P", &format!("正在启动 {}", exe.display()));
+        }
+        let process = launcher.launch(spec);
+
+        // 定期读取控制台，得到隧道数量与流量
+        let status = Arc::new(Mutex::new(None));
+        let history = Arc::new(Mutex::new(VecDeque::new()));
+        let stop = Arc::new(AtomicBool::new(false));
+        let thread_status = Arc::clone(&status);
+        let thread_history = Arc::clone(&history);
+        let thread_stop = Arc::clone(&stop);
+        let console_port = settings.console_port;
+        let mut control = I2pControlClient::new(settings.control_port, &settings.control_password).ok();
+        std::thread::spawn(move || {
+            let client = match reqwest::blocking::Client::builder().no_proxy().timeout(Duration::from_secs(3)).build() {
+                Ok(client) => client,
+                Err(_) => return,
+            };
+            while !thread_stop.load(Ordering::SeqCst) {
+                // 启动初期控制台尚未监听，读取失败属于正常情况
+                let mut current = query_console(&client, console_port).ok();
+                if let (Some(status), Some(control)) = (current.as_mut(), control.as_mut()) {
+                    if let Ok(stats) = control.router_info() {
+                        status.network = stats.network;
+                        status.routers = stats.known_peers;
+                        status.active_peers = stats.active_peers;
+                        status.floodfills = stats.floodfills;
+                        status.leasesets = stats.leasesets;
+                        status.version = stats.version;
+                        status.transit_tunnels = stats.participating_tunnels;
+                        status.tunnel_success_rate = stats.tunnel_success_rate.or(status.tunnel_success_rate);
+                        status.in_kbps = (stats.in_bps / 1024.0) as f32;
+                        status.out_kbps = (stats.out_bps / 1024.0) as f32;
+                        status.uptime = Some(stats.uptime);
+                    }
+                }
+                if let Ok(mut history) = thread_history.lock() {
+                    let (rate_in, rate_out) = current.as_ref().map_or((0.0, 0.0), |s| (s.in_kbps, s.out_kbps));
+                    history.push_back((rate_in, rate_out));
+                    let capacity = (HISTORY_MINUTES * 60 / STATUS_INTERVAL.as_secs()) as usize;
+                    while history.len() > capacity {
+                        history.pop_front();
+                    }
+                }
+                if let Ok(mut status) = thread_status.lock() {
+                    *status = current;
+                }
+                std::thread::sleep(STATUS_INTERVAL);
+            }
+        });
+
+        Ok(Self { process, status, history, error, started: Instant::now(), stop })
+    }
+
+    // 当前状态：控制台显示已有客户端隧道时视为就绪
+    pub fn state(&self) -> I2pdState {
+        match self.process.status().state {
+            ProcessState::Failed(reason) => return I2pdState::Failed(format!("i2pd已退出: {}", reason)),
+            ProcessState::Stopped => return I2pdState::Failed("i2pd已退出".to_string()),
+            ProcessState::Restarting { attempt, delay } => return I2pdState::Restarting { attempt, delay },
+            _ => {}
+        }
+        let status = self.router_status();
+        match status {
+            Some(status) if status.client_tunnels > 0 => I2pdState::Ready,
+            _ => {
+                if let Some(error) = self.error.lock().ok().and_then(|e| e.clone()) {
+                    return I2pdState::Failed(error);
+                }
+                if self.started.elapsed() > READY_TIMEOUT {
+                    return I2pdState::Failed("长时间未能建立隧道，请检查网络连接".to_string());
+                }
+                match status {
+                    Some(status) => I2pdState::Integrating { routers: status.routers },
+                    None => I2pdState::Starting,
+                }
+            }
+        }
+    }
+
+    pub fn router_status(&self) -> Option<RouterStatus> {
+        self.status.lock().ok().and_then(|s| s.clone())
+    }
+
+    // 带宽历史，按时间先后排列，每STATUS_INTERVAL一个采样
+    pub fn bandwidth_history(&self) -> Vec<(f32, f32)> {
+        self.history.lock().map(|h| h.iter().copied().collect()).unwrap_or_default()
+    }
+
+    pub fn stop(&self) {
+        self.stop.store(true, Ordering::SeqCst);
+        self.process.stop();
+    }
+}
+
+impl Drop for I2pdProcess {
+    fn drop(&mut self) {
+        self.stop();
+    }
+}
@@ -0,0 +1,374 @@
+use eframe::egui::{Color32, RichText, Ui, Grid};
+use std::net::{IpAddr, Ipv4Addr, ToSocketAddrs, UdpSocket};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+use serde::Deserialize;
+use anyhow::{anyhow, Context, Result};
+
+use crate::logger::Logger;
+use crate::app::LEAKTEST_COLOR;
+use crate::utils;
+
+// STUN服务器（用于模拟WebRTC获取公网地址）
+const STUN_SERVER: &str = "stun.l.google.com:19302";
+
+// STUN消息魔术字
+const STUN_MAGIC_COOKIE: u32 = 0x2112_A442;
+
+// 检测结果状态
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum CheckStatus {
+    Pass,
+    Fail,
+    Warning,
+    Skipped,
+}
+
+impl CheckStatus {
+    fn text(&self) -> RichText {
+        match self {
+            CheckStatus::Pass => RichText::new("通过").color(Color32::GREEN),
+            CheckStatus::Fail => RichText::new("失败").color(Color32::RED),
+            CheckStatus::Warning => RichText::new("警告").color(Color32::YELLOW),
+            CheckStatus::Skipped => RichText::new("跳过").color(Color32::GRAY),
+        }
+    }
+}
+
+// 单项检测结果
+#[derive(Clone, Debug)]
+pub struct LeakCheckResult {
+    pub name: String,
+    pub status: CheckStatus,
+    pub detail: String,
+    pub hint: String,
+}
+
+impl LeakCheckResult {
+    fn new(name: &str, status: CheckStatus, detail: String, hint: &str) -> Self {
+        Self {
+            name: name.to_string(),
+            status,
+            detail,
+            hint: hint.to_string(),
+        }
+    }
+}
+
+// bash.ws DNS泄露检测返回的条目
+#[derive(Clone, Debug, Deserialize)]
+struct DnsLeakEntry {
+    ip: String,
+    #[serde(default)]
+    country_name: String,
+    #[serde(default)]
+    asn: String,
+    #[serde(rename = "type")]
+    entry_type: String,
+}
+
+// 检测所需的隧道信息
+#[derive(Clone, Debug, Default)]
+struct TunnelInfo {
+    proxy_url: Option<String>,
+    vpn_active: bool,
+}
+
+impl TunnelInfo {
+    fn is_active(&self) -> bool {
+        self.proxy_url.is_some() || self.vpn_active
+    }
+}
+
+// 创建HTTP客户端（可选代理）
+fn http_client(proxy_url: Option<&str>) -> Result<reqwest::blocking::Client> {
+    let mut builder = reqwest::blocking::Client::builder().timeout(Duration::from_secs(15));
+    if let Some(proxy_url) = proxy_url {
+        builder = builder.proxy(reqwest::Proxy::all(proxy_url).context("Invalid proxy url")?);
+    }
+    builder.build().context("Failed to build http client")
+}
+
+// 通过STUN获取UDP流量的公网地址
+fn stun_public_ip(server: &str) -> Result<IpAddr> {
+    let socket = UdpSocket::bind("0.0.0.0:0").context("Failed to bind udp socket")?;
+    socket.set_read_timeout(Some(Duration::from_secs(3)))?;
+
+    // Binding Request: 类型(2) + 长度(2) + 魔术字(4) + 事务ID(12)
+    let mut request = [0u8; 20];
+    request[0..2].copy_from_slice(&0x0001u16.to_be_bytes());
+    request[4..8].copy_from_slice(&STUN_MAGIC_COOKIE.to_be_bytes());
+    let nanos = SystemTime::now().duration_since(UNIX_EPOCH).unwrap_or_default().as_nanos();
+    request[8..20].copy_from_slice(&nanos.to_be_bytes()[4..16]);
+
+    socket.send_to(&request, server).context("Failed to send stun request")?;
+    let mut buf = [0u8; 512];
+    let (len, _) = socket.recv_from(&mut buf).context("No stun response")?;
+
+    // 解析属性，优先使用XOR-MAPPED-ADDRESS
+    let mut offset = 20;
+    let mut mapped = None;
+    while offset + 4 <= len {
+        let attr_type = u16::from_be_bytes([buf[offset], buf[offset + 1]]);
+        let attr_len = u16::from_be_bytes([buf[offset + 2], buf[offset + 3]]) as usize;
+        let value = &buf[offset + 4..(offset + 4 + attr_len).min(len)];
+        if value.len() >= 8 && value[1] == 0x01 {
+            let raw = [value[4], value[5], value[6], value[7]];
+            match attr_type {
+                0x0020 => {
+                    let xored = u32::from_be_bytes(raw) ^ STUN_MAGIC_COOKIE;
+                    return Ok(IpAddr::V4(Ipv4Addr::from(xored)));
+                }
+                0x0001 => mapped = Some(IpAddr::V4(Ipv4Addr::from(raw))),
+                _ => {}
+            }
+        }
+        offset += 4 + ((attr_len + 3) & !3);
+    }
+    mapped.ok_or_else(|| anyhow!("Stun response has no mapped address"))
+}
+
+// 通过bash.ws执行DNS泄露检测，返回观察到的条目
+fn dns_leak_entries(proxy_url: Option<&str>) -> Result<Vec<DnsLeakEntry>> {
+    let direct = http_client(None)?;
+    let id = direct.get("https://bash.ws/id").send()?.text()?.trim().to_string();
+    if id.is_empty() {
+        return Err(anyhow!("Empty test id"));
+    }
+
+    // 通过系统解析器和隧道分别解析唯一子域名，让检测服务记录下实际使用的DNS服务器
+    let tunnel = proxy_url.map(|url| http_client(Some(url))).transpose()?;
+    for i in 1..=5 {
+        let host = format!("{}.{}.bash.ws", i, id);
+        let _ = (host.as_str(), 80).to_socket_addrs();
+        if let Some(tunnel) = &tunnel {
+            let _ = tunnel.get(format!("http://{}", host)).send();
+        }
+    }
+
+    let entries: Vec<DnsLeakEntry> = direct
+        .get(format!("https://bash.ws/dnsleak/test/{}?json", id))
+        .send()?
+        .json()
+        .context("Failed to parse dns leak result")?;
+    Ok(entries)
+}
+
+// 执行全部检测
+fn run_all_checks(tunnel: &TunnelInfo) -> Vec<LeakCheckResult> {
+    let mut results = Vec::new();
+
+    // 预期出口IP：经隧道检测到的公网IP
+    let direct_ip = utils::fetch_public_ip(None).ok();
+    let exit_ip = match &tunnel.proxy_url {
+        Some(proxy) => utils::fetch_public_ip(Some(proxy)).ok(),
+        None => direct_ip.clone(),
+    };
+
+    // 1. IP泄露
+    results.push(match (&direct_ip, &exit_ip) {
+        _ if !tunnel.is_active() => LeakCheckResult::new(
+            "IP泄露", CheckStatus::Warning, "当前没有活动的隧道，流量直接连接".to_string(),
+            "启动Tor、VPN或统一代理后再进行检测。"),
+        (_, None) => LeakCheckResult::new(
+            "IP泄露", CheckStatus::Fail, "无法通过隧道访问外网".to_string(),
+            "检查隧道是否已完成连接，或查看日志中的错误信息。"),
+        (Some(direct), Some(exit)) if tunnel.proxy_url.is_some() && direct.ip == exit.ip => LeakCheckResult::new(
+            "IP泄露", CheckStatus::Fail, format!("隧道出口与直连IP相同: {}", exit.ip),
+            "代理未将流量转发到隧道，请检查代理的上游设置。"),
+        (_, Some(exit)) => LeakCheckResult::new(
+            "IP泄露", CheckStatus::Pass, format!("出口IP: {} ({})", exit.ip, exit.country),
+            ""),
+    });
+
+    // 2. DNS泄露
+    results.push(match dns_leak_entries(tunnel.proxy_url.as_deref()) {
+        Ok(entries) => {
+            let own_asn: Vec<&str> = entries.iter()
+                .filter(|e| e.entry_type == "ip")
+                .map(|e| e.asn.as_str())
+                .collect();
+            let servers: Vec<&DnsLeakEntry> = entries.iter().filter(|e| e.entry_type == "dns").collect();
+            let leaking: Vec<&&DnsLeakEntry> = servers.iter()
+                .filter(|s| !s.asn.is_empty() && own_asn.contains(&s.asn.as_str()))
+                .collect();
+            let list = servers.iter()
+                .map(|s| format!("{} ({})", s.ip, s.country_name))
+                .collect::<Vec<_>>()
+                .join(", ");
+            if servers.is_empty() {
+                LeakCheckResult::new("DNS泄露", CheckStatus::Warning, "未观察到DNS服务器".to_string(),
+                    "检测服务未收到解析请求，可能是网络被限制，请稍后重试。")
+            } else if !leaking.is_empty() && tunnel.is_active() {
+                LeakCheckResult::new("DNS泄露", CheckStatus::Fail, format!("DNS请求经由本地运营商: {}", list),
+                    "启用DNSCrypt或Tor的DNS端口，并将系统DNS设置为127.0.0.1。")
+            } else {
+                LeakCheckResult::new("DNS泄露", CheckStatus::Pass, format!("使用的DNS服务器: {}", list), "")
+            }
+        }
+        Err(e) => LeakCheckResult::new("DNS泄露", CheckStatus::Skipped, format!("检测失败: {}", e),
+            "无法连接DNS泄露检测服务。"),
+    });
+
+    // 3. IPv6泄露
+    let ipv6 = http_client(None)
+        .and_then(|client| Ok(client.get("https://api6.ipify.org").send()?.text()?));
+    results.push(match ipv6 {
+        Err(_) => LeakCheckResult::new("IPv6泄露", CheckStatus::Pass, "没有直接的IPv6连接".to_string(), ""),
+        Ok(address) => {
+            let address = address.trim().to_string();
+            let via_tunnel = exit_ip.as_ref().map_or(false, |exit| exit.ip == address);
+            if tunnel.is_active() && !via_tunnel {
+                LeakCheckResult::new("IPv6泄露", CheckStatus::Fail, format!("IPv6流量绕过隧道: {}", address),
+                    "在网卡属性中禁用IPv6，或启用DNSCrypt的\"禁用IPv6解析\"选项。")
+            } else {
+                LeakCheckResult::new("IPv6泄露", CheckStatus::Pass, format!("IPv6地址: {}", address), "")
+            }
+        }
+    });
+
+    // 4. WebRTC/STUN泄露
+    results.push(match stun_public_ip(STUN_SERVER) {
+        Err(e) => LeakCheckResult::new("WebRTC (STUN)", CheckStatus::Pass, format!("UDP STUN请求未成功: {}", e), ""),
+        Ok(stun_ip) => {
+            let stun_ip = stun_ip.to_string();
+            let expected = exit_ip.as_ref().map(|exit| exit.ip.clone()).unwrap_or_default();
+            if tunnel.is_active() && stun_ip != expected {
+                LeakCheckResult::new("WebRTC (STUN)", CheckStatus::Fail, format!("STUN暴露的地址: {}（预期出口: {}）", stun_ip, expected),
+                    "在浏览器中禁用WebRTC或限制其仅使用代理，并在防火墙中阻止非隧道UDP流量。")
+            } else {
+                LeakCheckResult::new("WebRTC (STUN)", CheckStatus::Pass, format!("STUN地址: {}", stun_ip), "")
+            }
+        }
+    });
+
+    results
+}
+
+// 泄露检测模块结构
+pub struct LeakTestModule {
+    logger: Arc<Mutex<Logger>>,
+    tunnel: TunnelInfo,
+    results: Arc<Mutex<Vec<LeakCheckResult>>>,
+    running: Arc<Mutex<bool>>,
+}
+
+impl LeakTestModule {
+    pub fn new(logger: Arc<Mutex<Logger>>) -> Self {
+        Self {
+            logger,
+            tunnel: TunnelInfo::default(),
+            results: Arc::new(Mutex::new(Vec::new())),
+            running: Arc::new(Mutex::new(false)),
+        }
+    }
+
+    // 更新当前活动的隧道信息
+    pub fn set_active_tunnel(&mut self, proxy_url: Option<String>, vpn_active: bool) {
+        self.tunnel = TunnelInfo { proxy_url, vpn_active };
+    }
+
+    // 在后台线程中执行检测
+    fn start_tests(&mut self) {
+        if let Ok(mut running) = self.running.lock() {
+            if *running {
+                return;
+            }
+            *running = true;
+        }
+        if let Ok(mut logger) = self.logger.lock() {
+            logger.info("泄露检测", "开始泄露检测");
+        }
+
+        let tunnel = self.tunnel.clone();
+        let results = Arc::clone(&self.results);
+        let running = Arc::clone(&self.running);
+        let logger = Arc::clone(&self.logger);
+        std::thread::spawn(move || {
+            let new_results = run_all_checks(&tunnel);
+            let failed = new_results.iter().filter(|r| r.status == CheckStatus::Fail).count();
+            if let Ok(mut logger) = logger.lock() {
+                if failed > 0 {
+                    logger.warning("泄露检测", &format!("泄露检测完成，{} 项未通过", failed));
+                } else {
+                    logger.info("泄露检测", "泄露检测完成，未发现泄露");
+                }
+            }
+            if let Ok(mut results) = results.lock() {
+                *results = new_results;
+            }
+            if let Ok(mut running) = running.lock() {
+                *running = false;
+            }
+        });
+    }
+
+    // 渲染UI
+    pub fn ui(&mut self, ui: &mut Ui) {
+        let running = self.running.lock().map(|r| *r).unwrap_or(false);
+
+        ui.horizontal(|ui| {
+            ui.heading(RichText::new("泄露检测").color(LEAKTEST_COLOR).strong());
+            ui.add_space(10.0);
+            let route = match (&self.tunnel.proxy_url, self.tunnel.vpn_active) {
+                (Some(proxy), _) => format!("检测隧道: {}", proxy),
+                (None, true) => "检测隧道: VPN".to_string(),
+                (None, false) => "当前没有活动的隧道".to_string(),
+            };
+            ui.label(route);
+
+            ui.with_layout(eframe::egui::Layout::right_to_left(eframe::egui::Align::Center), |ui| {
+                if running {
+                    ui.spinner();
+                } else if ui.button("开始检测").clicked() {
+                    self.start_tests();
+                }
+            });
+        });
+
+        ui.separator();
+
+        ui.collapsing("关于泄露检测", |ui| {
+            ui.label("检测当前活动的隧道是否存在IP、DNS、IPv6或WebRTC泄露。");
+            ui.label("DNS检测使用 bash.ws 服务，WebRTC检测向STUN服务器发送UDP请求。");
+        });
+
+        let results = self.results.lock().map(|r| r.clone()).unwrap_or_default();
+        if results.is_empty() {
+            ui.label(RichText::new(if running { "正在检测..." } else { "尚未进行检测" }).color(Color32::GRAY));
+            return;
+        }
+
+        // 汇总
+        let failed = results.iter().filter(|r| r.status == CheckStatus::Fail).count();
+        let summary = if failed == 0 {
+            RichText::new(format!("全部 {} 项检测通过", results.len())).color(Color32::GREEN).strong()
+        } else {
+            RichText::new(format!("{} / {} 项检测未通过", failed, results.len())).color(Color32::RED).strong()
+        };
+        ui.label(summary);
+
+        Grid::new("leak_test_grid")
+            .num_columns(3)
+            .striped(true)
+            .spacing([10.0, 4.0])
+            .show(ui, |ui| {
+                ui.label(RichText::new("检测项").strong());
+                ui.label(RichText::new("结果").strong());
+                ui.label(RichText::new("详情").strong());
+                ui.end_row();
+
+                for result in &results {
+                    ui.label(&result.name);
+                    ui.label(result.status.text());
+                    ui.vertical(|ui| {
+                        ui.label(&result.detail);
+                        if !result.hint.is_empty() && result.status != CheckStatus::Pass {
+                            ui.label(RichText::new(format!("建议: {}", result.hint)).color(Color32::GRAY));
+                        }
+                    });
+                    ui.end_row();
+                }
+            });
+    }
+}
@@ -42,7 +42,7 @@ impl LogEntry {
     }
     
     // 获取日志级别的字符串表示
-    fn level_str(&self) -> &'static str {
+    pub fn level_str(&self) -> &'static str {
         match self.level {
             LogLevel::Info => "INFO",
             LogLevel::Warning => "WARN",
@@ -106,6 +106,11 @@ impl Logger {
         self.logs.clear();
     }
     
+    // 获取最近一条警告或错误日志
+    pub fn last_problem(&self) -> Option<&LogEntry> {
+        self.logs.iter().rev().find(|log| matches!(log.level, LogLevel::Warning | LogLevel::Error))
+    }
+    
     // 渲染日志UI
     pub fn ui(&mut self, ui: &mut Ui) {
         ui.heading("系统日志");
@@ -0,0 +1,332 @@
+use eframe::egui::{self, Color32, Grid, RichText, Ui};
+use std::collections::{HashMap, HashSet};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+use anyhow::{Context, Result};
+use rand::Rng;
+use serde::{Deserialize, Serialize};
+
+use crate::app::render_admin_required;
+use crate::logger::Logger;
+use crate::netif::{self, AdapterKind, NetworkAdapter};
+use crate::utils;
+
+// MAC设置配置文件名
+const MAC_CONFIG_FILE: &str = "mac.json";
+
+// 修改MAC后网卡会重新连接，冷却期内的重连不再触发随机化
+const RECONNECT_COOLDOWN: Duration = Duration::from_secs(90);
+
+// 网卡的MAC随机化策略
+#[derive(Clone, Copy, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub enum MacPolicy {
+    #[default]
+    Manual,    // 仅手动随机化
+    OnConnect, // 每次连接网络时随机化
+    Scheduled, // 由定时任务随机化
+}
+
+impl MacPolicy {
+    fn label(&self) -> &'static str {
+        match self {
+            MacPolicy::Manual => "手动",
+            MacPolicy::OnConnect => "每次连接",
+            MacPolicy::Scheduled => "定时任务",
+        }
+    }
+}
+
+// 按网卡名称保存的策略
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+struct MacSettings {
+    policies: HashMap<String, MacPolicy>,
+}
+
+impl utils::VersionedConfig for MacSettings {
+    const VERSION: u32 = 1;
+}
+
+// 生成随机的本地管理单播地址（第一字节低两位为10，Windows无线网卡也要求如此）
+pub fn random_mac() -> [u8; 6] {
+    let mut mac: [u8; 6] = rand::thread_rng().gen();
+    mac[0] = (mac[0] & 0xFC) | 0x02;
+    mac
+}
+
+// 格式化为AA-BB-CC-DD-EE-FF
+fn format_mac(mac: &[u8; 6]) -> String {
+    mac.iter().map(|b| format!("{:02X}", b)).collect::<Vec<_>>().join("-")
+}
+
+// 读取所有网卡的硬件（出厂）MAC地址
+fn permanent_addresses() -> HashMap<String, String> {
+    #[derive(Deserialize)]
+    #[serde(rename_all = "PascalCase")]
+    struct Entry {
+        name: String,
+        #[serde(default)]
+        permanent_address: String,
+    }
+
+    let script = "ConvertTo-Json -Compress -InputObject @(Get-NetAdapter | Select-Object Name,PermanentAddress)";
+    let entries: Vec<Entry> = utils::run_powershell(script)
+        .ok()
+        .and_then(|output| serde_json::from_str(&output).ok())
+        .unwrap_or_default();
+    entries
+        .into_iter()
+        .filter(|e| e.permanent_address.len() == 12)
+        .map(|e| {
+            let formatted = e.permanent_address.as_bytes()
+                .chunks(2)
+                .map(|c| String::from_utf8_lossy(c).to_string())
+                .collect::<Vec<_>>()
+                .join("-");
+            (e.name, formatted)
+        })
+        .collect()
+}
+
+// 通过网卡驱动的NetworkAddress注册表项设置MAC，None表示恢复硬件地址，然后重启网卡使其生效
+fn apply_mac(adapter: &str, mac: Option<[u8; 6]>) -> Result<()> {
+    let name = utils::ps_quote(adapter);
+    let set = match mac {
+        Some(mac) => {
+            let value: String = mac.iter().map(|b| format!("{:02X}", b)).collect();
+            format!(
+                "Set-NetAdapterAdvancedProperty -Name {} -RegistryKeyword NetworkAddress -RegistryValue '{}' -ErrorAction Stop",
+                name, value
+            )
+        }
+        None => format!(
+            "Reset-NetAdapterAdvancedProperty -Name {} -RegistryKeyword NetworkAddress -ErrorAction Stop",
+            name
+        ),
+    };
+    let script = format!("{}; Restart-NetAdapter -Name {} -ErrorAction Stop", set, name);
+    utils::run_powershell(&script)
+        .map(|_| ())
+        .with_context(|| format!("Failed to change MAC address of {}", adapter))
+}
+
+// MAC地址随机化模块
+pub struct MacModule {
+    logger: Arc<Mutex<Logger>>,
+    settings: MacSettings,
+    permanent: Arc<Mutex<HashMap<String, String>>>,
+    busy: Arc<Mutex<HashSet<String>>>,
+    cooldown: HashMap<String, Instant>,
+    last_state: HashMap<String, bool>,
+    last_generation: u64,
+}
+
+impl MacModule {
+    pub fn new(logger: Arc<Mutex<Logger>>) -> Self {
+        let settings = utils::load_saved_config(MAC_CONFIG_FILE).unwrap_or_default();
+
+        let module = Self {
+            logger,
+            settings,
+            permanent: Arc::new(Mutex::new(HashMap::new())),
+            busy: Arc::new(Mutex::new(HashSet::new())),
+            cooldown: HashMap::new(),
+            last_state: HashMap::new(),
+            last_generation: 0,
+        };
+        module.refresh_permanent_addresses();
+        module
+    }
+
+    // 在后台读取硬件MAC地址
+    fn refresh_permanent_addresses(&self) {
+        let permanent = Arc::clone(&self.permanent);
+        std::thread::spawn(move || {
+            let addresses = permanent_addresses();
+            if let Ok(mut permanent) = permanent.lock() {
+                *permanent = addresses;
+            }
+        });
+    }
+
+    fn save_settings(&self) {
+        let result = utils::get_config_path(MAC_CONFIG_FILE)
+            .and_then(|path| utils::save_versioned_config(&self.settings, &path));
+        if let Err(e) = result {
+            if let Ok(mut logger) = self.logger.lock() {
+                logger.error("MAC", &format!("保存MAC设置失败: {}", e));
+            }
+        }
+    }
+
+    fn policy(&self, adapter: &str) -> MacPolicy {
+        self.settings.policies.get(adapter).copied().unwrap_or_default()
+    }
+
+    // 在后台修改网卡MAC（None表示恢复硬件地址）
+    fn change_mac(&mut self, adapter: &str, mac: Option<[u8; 6]>) {
+        if !utils::is_running_as_admin() {
+            if let Ok(mut logger) = self.logger.lock() {
+                logger.warning("MAC", "修改MAC地址需要管理员权限");
+            }
+            return;
+        }
+        if let Ok(mut busy) = self.busy.lock() {
+            if !busy.insert(adapter.to_string()) {
+                return;
+            }
+        }
+        self.cooldown.insert(adapter.to_string(), Instant::now());
+
+        let adapter = adapter.to_string();
+        let logger = Arc::clone(&self.logger);
+        let busy = Arc::clone(&self.busy);
+        std::thread::spawn(move || {
+            let result = apply_mac(&adapter, mac);
+            if let Ok(mut logger) = logger.lock() {
+                match (result, mac) {
+                    (Ok(()), Some(mac)) => logger.info("MAC", &format!("网卡 {} 的MAC地址已更改为 {}", adapter, format_mac(&mac))),
+                    (Ok(()), None) => logger.info("MAC", &format!("网卡 {} 已恢复硬件MAC地址", adapter)),
+                    (Err(e), _) => logger.error("MAC", &format!("{:#}", e)),
+                }
+            }
+            if let Ok(mut busy) = busy.lock() {
+                busy.remove(&adapter);
+            }
+        });
+    }
+
+    // 为网卡设置新的随机MAC
+    pub fn randomize(&mut self, adapter: &str) {
+        self.change_mac(adapter, Some(random_mac()));
+    }
+
+    // 恢复网卡的硬件MAC
+    pub fn restore(&mut self, adapter: &str) {
+        self.change_mac(adapter, None);
+    }
+
+    // 定时任务：随机化所有设为定时策略的网卡
+    pub fn randomize_scheduled(&mut self) {
+        let adapters: Vec<String> = self.settings.policies.iter()
+            .filter(|(_, policy)| **policy == MacPolicy::Scheduled)
+            .map(|(name, _)| name.clone())
+            .collect();
+        for adapter in adapters {
+            self.randomize(&adapter);
+        }
+    }
+
+    // 检查网卡连接变化，为设为每次连接策略的网卡随机化MAC（每帧调用）
+    pub fn poll(&mut self) {
+        let generation = netif::generation();
+        if generation == self.last_generation {
+            return;
+        }
+        self.last_generation = generation;
+
+        let adapters = netif::adapters();
+        let mut connected = Vec::new();
+        for adapter in &adapters {
+            let was_up = self.last_state.insert(adapter.name.clone(), adapter.is_up);
+            // 首次看到的网卡不触发
+            if was_up == Some(false) && adapter.is_up && self.policy(&adapter.name) == MacPolicy::OnConnect {
+                let cooling = self.cooldown.get(&adapter.name).map_or(false, |t| t.elapsed() < RECONNECT_COOLDOWN);
+                if !cooling {
+                    connected.push(adapter.name.clone());
+                }
+            }
+        }
+        for adapter in connected {
+            self.randomize(&adapter);
+        }
+        self.refresh_permanent_addresses();
+    }
+
+    // 可修改MAC的物理网卡
+    fn physical_adapters() -> Vec<NetworkAdapter> {
+        netif::adapters()
+            .into_iter()
+            .filter(|a| matches!(a.kind, AdapterKind::Ethernet | AdapterKind::Wireless))
+            .collect()
+    }
+
+    pub fn ui(&mut self, ui: &mut Ui) {
+        if !utils::is_running_as_admin() {
+            render_admin_required(ui, &self.logger, "修改MAC地址");
+        }
+        ui.label(RichText::new("修改后网卡会短暂断开重连。部分无线网卡驱动不支持自定义MAC地址。").color(Color32::GRAY));
+
+        let adapters = Self::physical_adapters();
+        if adapters.is_empty() {
+            ui.label(RichText::new("未找到物理网卡").color(Color32::GRAY));
+            return;
+        }
+
+        let permanent = self.permanent.lock().map(|p| p.clone()).unwrap_or_default();
+        let busy = self.busy.lock().map(|b| b.clone()).unwrap_or_default();
+        let admin = utils::is_running_as_admin();
+        let mut action: Option<(String, bool)> = None; // (网卡, 是否随机化)
+        let mut policy_changed = false;
+
+        Grid::new("mac_adapters_grid")
+            .num_columns(6)
+            .striped(true)
+            .spacing([10.0, 4.0])
+            .show(ui, |ui| {
+                ui.label(RichText::new("网卡").strong());
+                ui.label(RichText::new("当前MAC").strong());
+                ui.label(RichText::new("硬件MAC").strong());
+                ui.label(RichText::new("随机化").strong());
+                ui.label("");
+                ui.label("");
+                ui.end_row();
+
+                for adapter in adapters {
+                    ui.label(format!("{} ({})", adapter.name, adapter.kind.label()));
+                    let hardware = permanent.get(&adapter.name);
+                    let spoofed = hardware.map_or(false, |h| !h.eq_ignore_ascii_case(&adapter.mac_address));
+                    if spoofed {
+                        ui.label(RichText::new(&adapter.mac_address).color(Color32::GREEN));
+                    } else {
+                        ui.label(&adapter.mac_address);
+                    }
+                    ui.label(hardware.map_or("-", |h| h.as_str()));
+
+                    let mut policy = self.policy(&adapter.name);
+                    egui::ComboBox::from_id_source(format!("mac_policy_{}", adapter.index))
+                        .selected_text(policy.label())
+                        .show_ui(ui, |ui| {
+                            for option in [MacPolicy::Manual, MacPolicy::OnConnect, MacPolicy::Scheduled] {
+                                ui.selectable_value(&mut policy, option, option.label());
+                            }
+                        });
+                    if policy != self.policy(&adapter.name) {
+                        self.settings.policies.insert(adapter.name.clone(), policy);
+                        policy_changed = true;
+                    }
+
+                    if busy.contains(&adapter.name) {
+                        ui.spinner();
+                        ui.label("");
+                    } else {
+                        if ui.add_enabled(admin, egui::Button::new("随机化")).clicked() {
+                            action = Some((adapter.name.clone(), true));
+                        }
+                        if ui.add_enabled(admin && spoofed, egui::Button::new("恢复")).clicked() {
+                            action = Some((adapter.name.clone(), false));
+                        }
+                    }
+                    ui.end_row();
+                }
+            });
+
+        if policy_changed {
+            self.save_settings();
+        }
+        match action {
+            Some((adapter, true)) => self.randomize(&adapter),
+            Some((adapter, false)) => self.restore(&adapter),
+            None => {}
+        }
+    }
+}
@@ -8,8 +8,57 @@ mod dnscrypt;
 mod i2p;
 mod proxy;
 mod vpn;
+mod vpn_url;
 mod logger;
 mod utils;
+mod scheduler;
+mod diagnostics;
+mod leaktest;
+mod monitor;
+mod geoip;
+mod components;
+mod netif;
+mod system_dns;
+mod traffic;
+mod supervisor;
+mod services;
+mod ports;
+mod macaddr;
+mod tor_process;
+mod tor_control;
+mod tor_circuits;
+mod tor_config;
+mod bridgedb;
+mod transports;
+mod tor_bandwidth;
+mod tor_check;
+mod onionoo;
+mod tor_guards;
+mod snowflake_proxy;
+mod browser;
+mod tor_accounting;
+mod dnscrypt_process;
+mod dns_stamp;
+mod resolver_list;
+mod dot_forwarder;
+mod dns_rules;
+mod dns_query_log;
+mod dnssec_check;
+mod captive_portal;
+mod dns_blocklists;
+mod dns_type_filter;
+mod hosts_file;
+mod i2pd_process;
+mod i2p_sam;
+mod i2p_control;
+mod i2p_addressbook;
+mod i2p_destination;
+mod i2p_key_backup;
+mod i2p_router_settings;
+mod i2p_socks;
+mod wfp;
+mod netfw;
+mod domain_tracker;
 
 use app::InviZibleApp;
 
@@ -0,0 +1,667 @@
+use eframe::egui::{Color32, RichText, Ui, Grid, ScrollArea};
+use eframe::egui::plot::{Legend, Line, Plot, PlotPoints};
+use std::collections::{HashMap, VecDeque};
+use std::net::{IpAddr, Ipv4Addr};
+use std::process::Command;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+use serde::Deserialize;
+
+use crate::firewall::{flow_connection, FirewallModule, RuleAction};
+use crate::geoip;
+use crate::logger::Logger;
+use crate::traffic::{self, TrafficSource};
+use crate::app::{render_admin_required, MONITOR_COLOR};
+use crate::utils;
+
+// 采样间隔
+const SAMPLE_INTERVAL: Duration = Duration::from_secs(2);
+
+// 标签页不可见超过该时间后暂停采样
+const IDLE_TIMEOUT: Duration = Duration::from_secs(5);
+
+// 图表保留的采样点数量
+const HISTORY_LEN: usize = 60;
+
+// 排行榜显示的进程数量
+const TOP_TALKERS: usize = 10;
+
+// 标签页不可见时刷新进程名的间隔
+const PROCESS_NAMES_INTERVAL: Duration = Duration::from_secs(30);
+
+// 连接的传输路径
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum FlowPath {
+    Direct,
+    Tor,
+    Vpn,
+    I2P,
+    Proxy,
+    Local,
+}
+
+impl FlowPath {
+    // 对应的流量统计来源，本地连接不计入统计（其流量会在隧道进程的外部连接上统计）
+    fn traffic_source(&self) -> Option<TrafficSource> {
+        match self {
+            FlowPath::Direct => Some(TrafficSource::Direct),
+            FlowPath::I2P => Some(TrafficSource::I2P),
+            // Tor、VPN与代理的流量由各模块自行上报，这里不再重复统计
+            FlowPath::Tor | FlowPath::Vpn | FlowPath::Proxy | FlowPath::Local => None,
+        }
+    }
+
+    fn label(&self) -> RichText {
+        match self {
+            FlowPath::Direct => RichText::new("直连").color(Color32::YELLOW),
+            FlowPath::Tor => RichText::new("Tor").color(Color32::from_rgb(155, 89, 182)),
+            FlowPath::Vpn => RichText::new("VPN").color(Color32::from_rgb(0, 150, 136)),
+            FlowPath::I2P => RichText::new("I2P").color(Color32::from_rgb(102, 51, 153)),
+            FlowPath::Proxy => RichText::new("代理").color(Color32::from_rgb(140, 192, 170)),
+            FlowPath::Local => RichText::new("本地").color(Color32::GRAY),
+        }
+    }
+}
+
+// 判断连接路径所需的隧道信息
+#[derive(Clone, Debug, Default)]
+pub struct RouteContext {
+    pub tor_socks_port: Option<u16>,
+    pub i2p_ports: Vec<u16>,
+    pub proxy_port: Option<u16>,
+    pub vpn_active: bool,
+}
+
+impl RouteContext {
+    // 根据目标地址和所属进程判断连接经过的路径
+    fn classify(&self, flow: &FlowStats) -> FlowPath {
+        if flow.remote_addr.is_loopback() {
+            if Some(flow.remote_port) == self.tor_socks_port {
+                return FlowPath::Tor;
+            }
+            if self.i2p_ports.contains(&flow.remote_port) {
+                return FlowPath::I2P;
+            }
+            if Some(flow.remote_port) == self.proxy_port {
+                return FlowPath::Proxy;
+            }
+            return FlowPath::Local;
+        }
+
+        match flow.process.to_lowercase().as_str() {
+            "tor.exe" => FlowPath::Tor,
+            "i2pd.exe" | "i2p.exe" => FlowPath::I2P,
+            _ if self.vpn_active => FlowPath::Vpn,
+            _ => FlowPath::Direct,
+        }
+    }
+}
+
+// 网卡统计（PowerShell Get-NetAdapterStatistics输出）
+#[derive(Deserialize)]
+struct AdapterCounters {
+    #[serde(rename = "Name")]
+    name: String,
+    #[serde(rename = "ReceivedBytes")]
+    received_bytes: u64,
+    #[serde(rename = "SentBytes")]
+    sent_bytes: u64,
+}
+
+// 单个TCP连接的累计字节数
+struct ConnectionSample {
+    pid: u32,
+    local_port: u16,
+    remote_addr: Ipv4Addr,
+    remote_port: u16,
+    bytes_in: u64,
+    bytes_out: u64,
+}
+
+// 网卡吞吐量
+#[derive(Clone)]
+pub struct InterfaceStats {
+    pub name: String,
+    pub rx_rate: f64, // 字节/秒
+    pub tx_rate: f64, // 字节/秒
+    history: VecDeque<(f64, f64)>,
+}
+
+// 进程吞吐量
+#[derive(Clone)]
+pub struct ProcessStats {
+    pub pid: u32,
+    pub name: String,
+    pub rx_rate: f64,
+    pub tx_rate: f64,
+    pub connections: usize,
+    history: VecDeque<(f64, f64)>,
+}
+
+// 连接吞吐量
+#[derive(Clone)]
+pub struct FlowStats {
+    pub pid: u32,
+    pub process: String,
+    pub local_port: u16,
+    pub remote_addr: Ipv4Addr,
+    pub remote_port: u16,
+    pub rx_rate: f64,
+    pub tx_rate: f64,
+}
+
+// 监控数据快照
+#[derive(Clone, Default)]
+struct MonitorSnapshot {
+    interfaces: Vec<InterfaceStats>,
+    processes: Vec<ProcessStats>,
+    flows: Vec<FlowStats>,
+    per_flow_counters: bool, // 是否获取到逐连接字节计数（需要管理员权限）
+}
+
+// 追加采样点并限制历史长度
+fn push_history(history: &mut VecDeque<(f64, f64)>, rx: f64, tx: f64) {
+    history.push_back((rx, tx));
+    while history.len() > HISTORY_LEN {
+        history.pop_front();
+    }
+}
+
+// 读取所有网卡的累计收发字节
+fn sample_interfaces() -> Vec<AdapterCounters> {
+    let script = "ConvertTo-Json -Compress -InputObject @(Get-NetAdapterStatistics | Select-Object Name,ReceivedBytes,SentBytes)";
+    let output = match Command::new("powershell").args(["-NoProfile", "-Command", script]).output() {
+        Ok(output) => output,
+        Err(_) => return Vec::new(),
+    };
+    serde_json::from_slice(&output.stdout).unwrap_or_default()
+}
+
+// 读取系统总的收发字节数（netstat -e的第一行数据为字节数）
+fn sample_system_totals() -> Option<(u64, u64)> {
+    let output = Command::new("netstat").arg("-e").output().ok()?;
+    String::from_utf8_lossy(&output.stdout).lines().find_map(|line| {
+        let numbers: Vec<u64> = line.split_whitespace().filter_map(|f| f.parse().ok()).collect();
+        match numbers[..] {
+            [received, sent] => Some((received, sent)),
+            _ => None,
+        }
+    })
+}
+
+// 获取进程ID到进程名的映射
+fn list_process_names() -> HashMap<u32, String> {
+    let output = match Command::new("tasklist").args(["/FO", "CSV", "/NH"]).output() {
+        Ok(output) => output,
+        Err(_) => return HashMap::new(),
+    };
+
+    String::from_utf8_lossy(&output.stdout)
+        .lines()
+        .filter_map(|line| {
+            let mut fields = line.split("\",\"").map(|f| f.trim_matches('"'));
+            let name = fields.next()?.to_string();
+            let pid = fields.next()?.parse().ok()?;
+            Some((pid, name))
+        })
+        .collect()
+}
+
+// 通过TCP扩展统计(ESTATS)读取每个已建立连接的累计字节数
+#[cfg(target_os = "windows")]
+fn sample_tcp_connections() -> Vec<ConnectionSample> {
+    use std::mem::{size_of, zeroed};
+    use std::ptr::null_mut;
+    use winapi::shared::iprtrmib::TCP_TABLE_OWNER_PID_CONNECTIONS;
+    use winapi::shared::tcpestats::{TcpConnectionEstatsData, TCP_ESTATS_DATA_ROD_v0, TCP_ESTATS_DATA_RW_v0};
+    use winapi::shared::tcpmib::{MIB_TCPROW, MIB_TCPTABLE_OWNER_PID, MIB_TCP_STATE_ESTAB};
+    use winapi::shared::winerror::{ERROR_INSUFFICIENT_BUFFER, NO_ERROR};
+    use winapi::shared::ws2def::AF_INET;
+    use winapi::um::iphlpapi::{GetExtendedTcpTable, GetPerTcpConnectionEStats, SetPerTcpConnectionEStats};
+
+    let mut samples = Vec::new();
+
+    unsafe {
+        // 先查询所需缓冲区大小
+        let mut size: u32 = 0;
+        let result = GetExtendedTcpTable(null_mut(), &mut size, 0, AF_INET as u32, TCP_TABLE_OWNER_PID_CONNECTIONS, 0);
+        if result != ERROR_INSUFFICIENT_BUFFER {
+            return samples;
+        }
+
+        // 使用u32缓冲区以满足表结构的对齐要求
+        let mut buffer = vec![0u32; size as usize / 4 + 1];
+        let result = GetExtendedTcpTable(buffer.as_mut_ptr() as *mut _, &mut size, 0, AF_INET as u32, TCP_TABLE_OWNER_PID_CONNECTIONS, 0);
+        if result != NO_ERROR {
+            return samples;
+        }
+
+        let table = &*(buffer.as_ptr() as *const MIB_TCPTABLE_OWNER_PID);
+        let rows = std::slice::from_raw_parts(table.table.as_ptr(), table.dwNumEntries as usize);
+
+        for entry in rows.iter().filter(|r| r.dwState == MIB_TCP_STATE_ESTAB) {
+            let mut row = MIB_TCPROW {
+                State: entry.dwState,
+                dwLocalAddr: entry.dwLocalAddr,
+                dwLocalPort: entry.dwLocalPort,
+                dwRemoteAddr: entry.dwRemoteAddr,
+                dwRemotePort: entry.dwRemotePort,
+            };
+
+            // 开启该连接的数据统计（已开启时无副作用）
+            let mut rw = TCP_ESTATS_DATA_RW_v0 { EnableCollection: 1 };
+            SetPerTcpConnectionEStats(
+                &mut row,
+                TcpConnectionEstatsData,
+                &mut rw as *mut _ as *mut u8,
+                0,
+                size_of::<TCP_ESTATS_DATA_RW_v0>() as u32,
+                0,
+            );
+
+            let mut rod: TCP_ESTATS_DATA_ROD_v0 = zeroed();
+            let result = GetPerTcpConnectionEStats(
+                &mut row,
+                TcpConnectionEstatsData,
+                null_mut(), 0, 0,
+                null_mut(), 0, 0,
+                &mut rod as *mut _ as *mut u8,
+                0,
+                size_of::<TCP_ESTATS_DATA_ROD_v0>() as u32,
+            );
+            if result != NO_ERROR {
+                continue;
+            }
+
+            // 地址和端口均为网络字节序
+            samples.push(ConnectionSample {
+                pid: entry.dwOwningPid,
+                local_port: u16::from_be(entry.dwLocalPort as u16),
+                remote_addr: Ipv4Addr::from(entry.dwRemoteAddr.to_ne_bytes()),
+                remote_port: u16::from_be(entry.dwRemotePort as u16),
+                bytes_in: rod.DataBytesIn,
+                bytes_out: rod.DataBytesOut,
+            });
+        }
+    }
+
+    samples
+}
+
+#[cfg(not(target_os = "windows"))]
+fn sample_tcp_connections() -> Vec<ConnectionSample> {
+    Vec::new()
+}
+
+// 后台采样器：保存上一次的累计值以计算速率
+struct Sampler {
+    last_sample: Option<Instant>,
+    interface_totals: HashMap<String, (u64, u64)>,
+    flow_totals: HashMap<(u32, u16, Ipv4Addr, u16), (u64, u64)>,
+    system_totals: Option<(u64, u64)>,
+    process_names: HashMap<u32, String>,
+    names_updated: Option<Instant>,
+    interface_history: HashMap<String, VecDeque<(f64, f64)>>,
+    process_history: HashMap<u32, VecDeque<(f64, f64)>>,
+    latest_flows: Vec<FlowStats>, // 最近一次采样的全部连接，不论标签页是否可见
+}
+
+impl Sampler {
+    fn new() -> Self {
+        Self {
+            last_sample: None,
+            interface_totals: HashMap::new(),
+            flow_totals: HashMap::new(),
+            system_totals: None,
+            process_names: HashMap::new(),
+            names_updated: None,
+            interface_history: HashMap::new(),
+            process_history: HashMap::new(),
+            latest_flows: Vec::new(),
+        }
+    }
+
+    // 采样一次，将新增流量上报到流量统计；标签页可见时生成界面快照
+    fn sample(&mut self, visible: bool, route: &RouteContext) -> Option<MonitorSnapshot> {
+        let now = Instant::now();
+        let elapsed = self.last_sample.map(|t| now.duration_since(t).as_secs_f64()).unwrap_or(0.0);
+        self.last_sample = Some(now);
+        // 计算两次累计值之间的速率（首次采样或计数器重置时为0）
+        let rate = |current: u64, previous: Option<u64>| match previous {
+            Some(previous) if elapsed > 0.0 && current >= previous => (current - previous) as f64 / elapsed,
+            _ => 0.0,
+        };
+
+        // 累计值的增量（首次采样或计数器重置时为0）
+        let delta = |current: u64, previous: Option<u64>| match previous {
+            Some(previous) if current >= previous => current - previous,
+            _ => 0,
+        };
+
+        // 网卡（仅在可见时采样，PowerShell开销较大）
+        let mut interfaces = Vec::new();
+        let mut interface_totals = HashMap::new();
+        let adapters = if visible { sample_interfaces() } else { Vec::new() };
+        for adapter in adapters {
+            let previous = self.interface_totals.get(&adapter.name).copied();
+            let rx_rate = rate(adapter.received_bytes, previous.map(|p| p.0));
+            let tx_rate = rate(adapter.sent_bytes, previous.map(|p| p.1));
+            let history = self.interface_history.entry(adapter.name.clone()).or_default();
+            push_history(history, rx_rate, tx_rate);
+            interface_totals.insert(adapter.name.clone(), (adapter.received_bytes, adapter.sent_bytes));
+            interfaces.push(InterfaceStats {
+                name: adapter.name,
+                rx_rate,
+                tx_rate,
+                history: history.clone(),
+            });
+        }
+        self.interface_totals = interface_totals;
+        self.interface_history.retain(|name, _| self.interface_totals.contains_key(name));
+
+        // 连接
+        let connections = sample_tcp_connections();
+        let per_flow_counters = !connections.is_empty();
+        let names_expired = self.names_updated.map_or(true, |t| t.elapsed() >= PROCESS_NAMES_INTERVAL);
+        if visible || names_expired {
+            self.process_names = list_process_names();
+            self.names_updated = Some(Instant::now());
+        }
+        let names = &self.process_names;
+        let mut flows = Vec::new();
+        let mut flow_totals = HashMap::new();
+        for conn in connections {
+            let key = (conn.pid, conn.local_port, conn.remote_addr, conn.remote_port);
+            let previous = self.flow_totals.get(&key).copied();
+            let flow = FlowStats {
+                pid: conn.pid,
+                process: names.get(&conn.pid).cloned().unwrap_or_else(|| format!("PID {}", conn.pid)),
+                local_port: conn.local_port,
+                remote_addr: conn.remote_addr,
+                remote_port: conn.remote_port,
+                rx_rate: rate(conn.bytes_in, previous.map(|p| p.0)),
+                tx_rate: rate(conn.bytes_out, previous.map(|p| p.1)),
+            };
+            if let Some(source) = route.classify(&flow).traffic_source() {
+                traffic::report(
+                    source,
+                    delta(conn.bytes_in, previous.map(|p| p.0)),
+                    delta(conn.bytes_out, previous.map(|p| p.1)),
+                );
+            }
+            flows.push(flow);
+            flow_totals.insert(key, (conn.bytes_in, conn.bytes_out));
+        }
+        self.flow_totals = flow_totals;
+        self.latest_flows = flows.clone();
+
+        // 无法读取逐连接统计时，以系统总流量作为未区分流量上报
+        if !per_flow_counters {
+            let totals = sample_system_totals();
+            if let (Some(current), Some(previous)) = (totals, self.system_totals) {
+                traffic::report(TrafficSource::Unclassified, delta(current.0, Some(previous.0)), delta(current.1, Some(previous.1)));
+            }
+            self.system_totals = totals;
+        } else {
+            self.system_totals = None;
+        }
+
+        if !visible {
+            self.interface_history.clear();
+            self.process_history.clear();
+            return None;
+        }
+
+        // 按进程汇总
+        let mut by_process: HashMap<u32, ProcessStats> = HashMap::new();
+        for flow in &flows {
+            let stats = by_process.entry(flow.pid).or_insert_with(|| ProcessStats {
+                pid: flow.pid,
+                name: flow.process.clone(),
+                rx_rate: 0.0,
+                tx_rate: 0.0,
+                connections: 0,
+                history: VecDeque::new(),
+            });
+            stats.rx_rate += flow.rx_rate;
+            stats.tx_rate += flow.tx_rate;
+            stats.connections += 1;
+        }
+        let mut processes: Vec<ProcessStats> = by_process.into_values().collect();
+        for stats in processes.iter_mut() {
+            let history = self.process_history.entry(stats.pid).or_default();
+            push_history(history, stats.rx_rate, stats.tx_rate);
+            stats.history = history.clone();
+        }
+        self.process_history.retain(|pid, _| processes.iter().any(|p| p.pid == *pid));
+        processes.sort_by(|a, b| (b.rx_rate + b.tx_rate).total_cmp(&(a.rx_rate + a.tx_rate)));
+        flows.sort_by(|a, b| (b.rx_rate + b.tx_rate).total_cmp(&(a.rx_rate + a.tx_rate)));
+
+        Some(MonitorSnapshot {
+            interfaces,
+            processes,
+            flows,
+            per_flow_counters,
+        })
+    }
+}
+
+// 绘制收发速率曲线
+fn rate_plot(ui: &mut Ui, id: &str, history: &VecDeque<(f64, f64)>) {
+    let rx: Vec<[f64; 2]> = history.iter().enumerate().map(|(i, (rx, _))| [i as f64, *rx / 1024.0]).collect();
+    let tx: Vec<[f64; 2]> = history.iter().enumerate().map(|(i, (_, tx))| [i as f64, *tx / 1024.0]).collect();
+    Plot::new(id)
+        .height(120.0)
+        .legend(Legend::default())
+        .allow_drag(false)
+        .allow_zoom(false)
+        .allow_scroll(false)
+        .include_y(0.0)
+        .y_axis_formatter(|value, _| format!("{:.0} KB/s", value))
+        .show(ui, |plot_ui| {
+            plot_ui.line(Line::new(PlotPoints::from(rx)).name("下载").color(Color32::GREEN));
+            plot_ui.line(Line::new(PlotPoints::from(tx)).name("上传").color(Color32::LIGHT_BLUE));
+        });
+}
+
+// 速率显示
+fn format_rate(rate: f64) -> String {
+    format!("{}/s", utils::format_bytes(rate as u64))
+}
+
+// 流量监控模块结构
+pub struct MonitorModule {
+    logger: Arc<Mutex<Logger>>,
+    snapshot: Arc<Mutex<MonitorSnapshot>>,
+    last_viewed: Arc<Mutex<Option<Instant>>>,
+    route: Arc<Mutex<RouteContext>>,
+    recent_flows: Arc<Mutex<Vec<FlowStats>>>,
+    selected_process: Option<u32>,
+}
+
+impl MonitorModule {
+    pub fn new(logger: Arc<Mutex<Logger>>) -> Self {
+        let module = Self {
+            logger,
+            snapshot: Arc::new(Mutex::new(MonitorSnapshot::default())),
+            last_viewed: Arc::new(Mutex::new(None)),
+            route: Arc::new(Mutex::new(RouteContext::default())),
+            recent_flows: Arc::new(Mutex::new(Vec::new())),
+            selected_process: None,
+        };
+        module.spawn_worker();
+
+        if let Ok(mut logger) = module.logger.lock() {
+            logger.info("流量监控", "流量监控模块已初始化");
+        }
+
+        module
+    }
+
+    // 启动后台采样线程（持续为流量统计采样，标签页可见时额外采集界面数据）
+    fn spawn_worker(&self) {
+        let snapshot = Arc::clone(&self.snapshot);
+        let last_viewed = Arc::clone(&self.last_viewed);
+        let route = Arc::clone(&self.route);
+        let recent_flows = Arc::clone(&self.recent_flows);
+        std::thread::spawn(move || {
+            let mut sampler = Sampler::new();
+            loop {
+                let visible = last_viewed.lock()
+                    .map(|t| t.map_or(false, |t| t.elapsed() < IDLE_TIMEOUT))
+                    .unwrap_or(false);
+                let route = route.lock().map(|r| r.clone()).unwrap_or_default();
+                if let Some(new_snapshot) = sampler.sample(visible, &route) {
+                    if let Ok(mut snapshot) = snapshot.lock() {
+                        *snapshot = new_snapshot;
+                    }
+                }
+                if let Ok(mut flows) = recent_flows.lock() {
+                    *flows = sampler.latest_flows.clone();
+                }
+                std::thread::sleep(SAMPLE_INTERVAL);
+            }
+        });
+    }
+
+    // 更新判断连接路径所需的隧道信息
+    pub fn set_route_context(&mut self, route: RouteContext) {
+        if let Ok(mut current) = self.route.lock() {
+            *current = route;
+        }
+    }
+
+    // 最近一次采样到的连接（防火墙交互模式据此发现未知应用）
+    pub fn recent_flows(&self) -> Vec<FlowStats> {
+        self.recent_flows.lock().map(|flows| flows.clone()).unwrap_or_default()
+    }
+
+    // 渲染UI
+    pub fn ui(&mut self, ui: &mut Ui, firewall: &FirewallModule) {
+        if let Ok(mut last_viewed) = self.last_viewed.lock() {
+            *last_viewed = Some(Instant::now());
+        }
+        let snapshot = self.snapshot.lock().map(|s| s.clone()).unwrap_or_default();
+
+        ui.horizontal(|ui| {
+            ui.heading(RichText::new("流量监控").color(MONITOR_COLOR).strong());
+            ui.add_space(10.0);
+            let total_rx: f64 = snapshot.interfaces.iter().map(|i| i.rx_rate).sum();
+            let total_tx: f64 = snapshot.interfaces.iter().map(|i| i.tx_rate).sum();
+            ui.label(format!("↓ {}  ↑ {}", format_rate(total_rx), format_rate(total_tx)));
+        });
+
+        ui.separator();
+
+        if !utils::is_running_as_admin() {
+            render_admin_required(ui, &self.logger, "进程和连接流量统计");
+        } else if !snapshot.per_flow_counters {
+            ui.label(RichText::new("未能读取逐连接流量统计。").color(Color32::YELLOW));
+        }
+
+        let route = self.route.lock().map(|r| r.clone()).unwrap_or_default();
+
+        ScrollArea::vertical().show(ui, |ui| {
+            ui.collapsing("流量统计（按天）", |ui| {
+                traffic::ui(ui);
+            });
+
+            // 网卡
+            ui.collapsing("网卡吞吐量", |ui| {
+                if snapshot.interfaces.is_empty() {
+                    ui.label(RichText::new("正在采样...").color(Color32::GRAY));
+                }
+                for interface in &snapshot.interfaces {
+                    ui.label(format!(
+                        "{}  ↓ {}  ↑ {}",
+                        interface.name,
+                        format_rate(interface.rx_rate),
+                        format_rate(interface.tx_rate)
+                    ));
+                    rate_plot(ui, &format!("monitor_if_{}", interface.name), &interface.history);
+                }
+            });
+
+            // 进程排行
+            ui.collapsing("流量排行", |ui| {
+                Grid::new("monitor_top_talkers")
+                    .num_columns(5)
+                    .striped(true)
+                    .spacing([10.0, 4.0])
+                    .show(ui, |ui| {
+                        ui.label(RichText::new("进程").strong());
+                        ui.label(RichText::new("PID").strong());
+                        ui.label(RichText::new("下载").strong());
+                        ui.label(RichText::new("上传").strong());
+                        ui.label(RichText::new("连接数").strong());
+                        ui.end_row();
+
+                        for process in snapshot.processes.iter().take(TOP_TALKERS) {
+                            let selected = self.selected_process == Some(process.pid);
+                            if ui.selectable_label(selected, &process.name).clicked() {
+                                self.selected_process = if selected { None } else { Some(process.pid) };
+                            }
+                            ui.label(process.pid.to_string());
+                            ui.label(format_rate(process.rx_rate));
+                            ui.label(format_rate(process.tx_rate));
+                            ui.label(process.connections.to_string());
+                            ui.end_row();
+                        }
+                    });
+
+                if let Some(process) = snapshot.processes.iter().find(|p| Some(p.pid) == self.selected_process) {
+                    ui.label(format!("{} (PID {})", process.name, process.pid));
+                    rate_plot(ui, "monitor_process_plot", &process.history);
+                }
+            });
+
+            // 连接及其路径
+            ui.collapsing("活动连接", |ui| {
+                Grid::new("monitor_flows")
+                    .num_columns(7)
+                    .striped(true)
+                    .spacing([10.0, 4.0])
+                    .show(ui, |ui| {
+                        ui.label(RichText::new("进程").strong());
+                        ui.label(RichText::new("本地端口 → 远程地址").strong());
+                        ui.label(RichText::new("国家").strong());
+                        ui.label(RichText::new("路径").strong());
+                        ui.label(RichText::new("下载").strong());
+                        ui.label(RichText::new("上传").strong());
+                        ui.label(RichText::new("防火墙规则").strong());
+                        ui.end_row();
+
+                        for flow in &snapshot.flows {
+                            ui.label(&flow.process);
+                            ui.label(format!("{} → {}:{}", flow.local_port, flow.remote_addr, flow.remote_port));
+                            ui.label(geoip::lookup(IpAddr::V4(flow.remote_addr)).unwrap_or_else(|| "-".to_string()));
+                            ui.label(route.classify(flow).label());
+                            ui.label(format_rate(flow.rx_rate));
+                            ui.label(format_rate(flow.tx_rate));
+                            let address = flow.remote_addr.to_string();
+                            let verdict = firewall.evaluate(&flow_connection(flow, &address));
+                            match (verdict.rule, verdict.action) {
+                                (None, action) if verdict.tor_only => {
+                                    let color = if action == RuleAction::Allow { Color32::GREEN } else { Color32::RED };
+                                    ui.label(RichText::new("仅限Tor").color(color));
+                                }
+                                (Some(rule), RuleAction::Allow) => {
+                                    ui.label(RichText::new(&rule.name).color(Color32::GREEN));
+                                }
+                                (Some(rule), RuleAction::Block) => {
+                                    ui.label(RichText::new(&rule.name).color(Color32::RED));
+                                }
+                                (None, RuleAction::Allow) => {
+                                    ui.label(RichText::new("默认允许").color(Color32::GRAY));
+                                }
+                                (None, RuleAction::Block) => {
+                                    ui.label(RichText::new("默认阻止").color(Color32::GRAY));
+                                }
+                            }
+                            ui.end_row();
+                        }
+                    });
+            });
+        });
+    }
+}
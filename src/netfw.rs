@@ -0,0 +1,366 @@
+use std::collections::BTreeMap;
+#[cfg(target_os = "windows")]
+use std::process::Command;
+use std::sync::mpsc::{self, Receiver, Sender};
+use std::sync::{Arc, Mutex};
+use std::thread::JoinHandle;
+use anyhow::{anyhow, Context, Result};
+use serde::{Deserialize, Serialize};
+
+use crate::firewall::{FirewallProfile, FirewallRule, RuleAction, RuleType};
+use crate::geoip;
+use crate::utils;
+use crate::wfp;
+
+// Windows Defender防火墙中本程序规则名的前缀，用于在系统防火墙设置中识别
+pub const RULE_PREFIX: &str = "InviZible Pro";
+
+// 国家规则中每条系统规则包含的地址段数，受netsh命令行长度（约32K字符）限制
+const RANGES_PER_RULE: usize = 200;
+
+// 已添加到系统防火墙的规则记录文件，异常退出后下次启动据此删除
+const INSTALLED_FILE: &str = "netfw_rules.json";
+
+// 系统防火墙规则：规则名 → netsh add rule的参数（不含名称）
+pub type SystemRules = BTreeMap<String, Vec<String>>;
+
+#[derive(Default, Serialize, Deserialize)]
+struct InstalledRules {
+    rules: SystemRules,
+}
+
+impl utils::VersionedConfig for InstalledRules {
+    const VERSION: u32 = 1;
+}
+
+// netsh的参数中不能出现双引号
+fn netsh_value(value: &str) -> String {
+    value.replace('"', "'")
+}
+
+// 运行netsh命令，失败时返回其输出的错误信息
+#[cfg(target_os = "windows")]
+pub fn run_netsh(args: &[&str]) -> Result<()> {
+    use std::os::windows::process::CommandExt;
+    const CREATE_NO_WINDOW: u32 = 0x08000000;
+    let output = Command::new("netsh")
+        .args(args)
+        .creation_flags(CREATE_NO_WINDOW)
+        .output()
+        .context("Failed to run netsh")?;
+    if output.status.success() {
+        Ok(())
+    } else {
+        // netsh把错误信息输出到标准输出
+        Err(anyhow!("netsh: {}", String::from_utf8_lossy(&output.stdout).trim()))
+    }
+}
+
+#[cfg(not(target_os = "windows"))]
+pub fn run_netsh(_args: &[&str]) -> Result<()> {
+    Err(anyhow!("Windows Firewall is only available on Windows"))
+}
+
+fn add_rule(name: &str, args: &[String]) -> Result<()> {
+    let name = format!("name={}", name);
+    let mut command = vec!["advfirewall", "firewall", "add", "rule", name.as_str()];
+    command.extend(args.iter().map(String::as_str));
+    run_netsh(&command).with_context(|| format!("Failed to add firewall rule '{}'", name))
+}
+
+fn delete_rule(name: &str) -> Result<()> {
+    run_netsh(&["advfirewall", "firewall", "delete", "rule", &format!("name={}", name)])
+        .with_context(|| format!("Failed to delete firewall rule '{}'", name))
+}
+
+// 把一条规则翻译为netsh参数，出站和入站各一条
+fn rule_args(rule: &FirewallRule) -> Result<Vec<Vec<String>>, String> {
+    let mut common = vec![
+        format!("action={}", match rule.action {
+            RuleAction::Allow => "allow",
+            RuleAction::Block => "block",
+        }),
+        "enable=yes".to_string(),
+        "profile=any".to_string(),
+    ];
+    let description = if rule.description.is_empty() {
+        rule.name.clone()
+    } else {
+        format!("{}: {}", rule.name, rule.description)
+    };
+    common.push(format!("description={}", netsh_value(&description)));
+
+    let (outbound, inbound) = match rule.rule_type {
+        RuleType::Application => {
+            let path = rule.application_path.as_deref().map(str::trim).filter(|p| !p.is_empty())
+                .ok_or("未指定应用程序路径")?;
+            let program = vec![format!("program={}", netsh_value(path))];
+            (program.clone(), program)
+        }
+        // 出站连接匹配远程端口，入站连接匹配本地端口
+        RuleType::Port => {
+            let port = rule.port.filter(|p| *p != 0).ok_or("未指定端口")?;
+            let protocol = match rule.protocol.as_deref() {
+                Some(p) if p.eq_ignore_ascii_case("UDP") => "UDP",
+                _ => "TCP",
+            };
+            (
+                vec![format!("protocol={}", protocol), format!("remoteport={}", port)],
+                vec![format!("protocol={}", protocol), format!("localport={}", port)],
+            )
+        }
+        RuleType::Address => {
+            let value = rule.address.as_deref().unwrap_or("");
+            let (address, prefix) = wfp::parse_address(value).ok_or_else(|| format!("地址格式无效: {}", value))?;
+            let remote = vec![format!("remoteip={}/{}", address, prefix)];
+            (remote.clone(), remote)
+        }
+        // 按域名已解析到的地址过滤，尚未解析时不添加规则
+        RuleType::Domain => {
+            if rule.resolved_addresses.is_empty() {
+                return Ok(Vec::new());
+            }
+            let addresses: Vec<String> = rule.resolved_addresses.iter().map(|ip| ip.to_string()).collect();
+            let remote = vec![format!("remoteip={}", addresses.join(","))];
+            (remote.clone(), remote)
+        }
+        RuleType::Country => return country_args(rule, &common),
+    };
+    Ok([("out", outbound, false), ("in", inbound, true)]
+        .into_iter()
+        .filter(|(_, _, inbound)| rule.direction.includes(*inbound))
+        .map(|(direction, filter, _)| {
+            let mut args = vec![format!("dir={}", direction)];
+            args.extend(common.iter().cloned());
+            args.extend(filter);
+            args
+        })
+        .collect())
+}
+
+// 国家规则：每个国家的地址段拆分为多条系统规则，按规则的方向创建
+fn country_args(rule: &FirewallRule, common: &[String]) -> Result<Vec<Vec<String>>, String> {
+    if rule.countries.is_empty() {
+        return Err("未选择国家".to_string());
+    }
+    let mut rules = Vec::new();
+    for code in &rule.countries {
+        let ranges: Vec<String> = geoip::country_ranges(code).ok_or("GeoIP数据库未加载")?
+            .into_iter()
+            .map(|(start, end)| format!("{}-{}", start, end))
+            .collect();
+        for chunk in ranges.chunks(RANGES_PER_RULE) {
+            for (direction, inbound) in [("out", false), ("in", true)] {
+                if rule.direction.includes(inbound) {
+                    let mut args = vec![format!("dir={}", direction)];
+                    args.extend(common.iter().cloned());
+                    args.push(format!("remoteip={}", chunk.join(",")));
+                    rules.push(args);
+                }
+            }
+        }
+    }
+    Ok(rules)
+}
+
+// 把当前规则翻译为系统防火墙规则，返回规则集与无法生效的规则说明。
+// 规则名由规则ID与序号组成，规则内容不变时名称与参数都不变，便于增量更新
+pub fn plan(rules: &[FirewallRule], profile: &FirewallProfile) -> (SystemRules, Vec<String>) {
+    let mut planned = SystemRules::new();
+    let mut skipped = Vec::new();
+    for rule in rules.iter().filter(|r| r.enabled) {
+        match rule_args(rule) {
+            Ok(rule_args) => {
+                for (index, args) in rule_args.into_iter().enumerate() {
+                    planned.insert(format!("{} {}.{}", RULE_PREFIX, rule.id, index), args);
+                }
+            }
+            Err(e) => skipped.push(format!("{}: {}", rule.name, e)),
+        }
+    }
+    // Windows防火墙的默认动作是全局设置，不在本程序的规则中修改
+    if *profile == FirewallProfile::Strict {
+        skipped.push("严格模式: Windows防火墙规则无法实现默认阻止，请改用Windows筛选平台".to_string());
+    }
+    (planned, skipped)
+}
+
+// 依次删除旧规则、添加新规则，把成功完成的操作记录到done中以便回滚
+fn apply_changes(
+    removed: &[(String, Vec<String>)],
+    added: &[(String, Vec<String>)],
+    done_removed: &mut Vec<(String, Vec<String>)>,
+    done_added: &mut Vec<String>,
+) -> Result<()> {
+    for (name, args) in removed {
+        delete_rule(name)?;
+        done_removed.push((name.clone(), args.clone()));
+    }
+    for (name, args) in added {
+        add_rule(name, args)?;
+        done_added.push(name.clone());
+    }
+    Ok(())
+}
+
+// 只修改有变化的规则。中途失败时撤销已完成的修改，恢复之前的规则
+fn sync(installed: &mut SystemRules, planned: &SystemRules) -> Result<()> {
+    let removed: Vec<(String, Vec<String>)> = installed.iter()
+        .filter(|(name, args)| planned.get(*name) != Some(*args))
+        .map(|(name, args)| (name.clone(), args.clone()))
+        .collect();
+    let added: Vec<(String, Vec<String>)> = planned.iter()
+        .filter(|(name, args)| installed.get(*name) != Some(*args))
+        .map(|(name, args)| (name.clone(), args.clone()))
+        .collect();
+
+    let mut done_removed = Vec::new();
+    let mut done_added = Vec::new();
+    if let Err(e) = apply_changes(&removed, &added, &mut done_removed, &mut done_added) {
+        for name in &done_added {
+            let _ = delete_rule(name);
+        }
+        for (name, args) in &done_removed {
+            let _ = add_rule(name, args);
+        }
+        return Err(e);
+    }
+    *installed = planned.clone();
+    Ok(())
+}
+
+fn save_installed(installed: &SystemRules) -> Result<()> {
+    let record = InstalledRules { rules: installed.clone() };
+    utils::save_versioned_config(&record, &utils::get_config_path(INSTALLED_FILE)?)
+}
+
+// 后台操作的结果
+pub enum Outcome {
+    Applied(usize), // 系统防火墙中本程序当前的规则数
+    Failed(String), // 已恢复为之前的规则
+}
+
+// 在后台线程中修改系统防火墙（每条规则需运行一次netsh，不能阻塞界面）。
+// 线程持有已添加规则的记录，异常退出后留下的规则在下次启动时删除
+pub struct Worker {
+    jobs: Option<Sender<Option<SystemRules>>>, // None表示删除全部规则
+    outcomes: Arc<Mutex<Vec<Outcome>>>,
+    pending: Arc<Mutex<usize>>,
+    thread: Option<JoinHandle<()>>,
+}
+
+impl Worker {
+    pub fn new() -> Self {
+        let installed = utils::load_saved_config::<InstalledRules>(INSTALLED_FILE).unwrap_or_default().rules;
+        let leftover = !installed.is_empty();
+        let (jobs, receiver) = mpsc::channel();
+        let outcomes = Arc::new(Mutex::new(Vec::new()));
+        let pending = Arc::new(Mutex::new(0));
+        let thread = std::thread::spawn({
+            let outcomes = Arc::clone(&outcomes);
+            let pending = Arc::clone(&pending);
+            move || run(installed, receiver, outcomes, pending)
+        });
+        let worker = Self {
+            jobs: Some(jobs),
+            outcomes,
+            pending,
+            thread: Some(thread),
+        };
+        if leftover {
+            worker.remove_all();
+        }
+        worker
+    }
+
+    fn send(&self, job: Option<SystemRules>) {
+        if let Some(jobs) = &self.jobs {
+            if let Ok(mut pending) = self.pending.lock() {
+                *pending += 1;
+            }
+            let _ = jobs.send(job);
+        }
+    }
+
+    // 把系统防火墙中的规则更新为planned
+    pub fn apply(&self, planned: SystemRules) {
+        self.send(Some(planned));
+    }
+
+    // 删除本程序添加的全部规则
+    pub fn remove_all(&self) {
+        self.send(None);
+    }
+
+    // 是否有尚未完成的操作
+    pub fn is_busy(&self) -> bool {
+        self.pending.lock().map(|pending| *pending > 0).unwrap_or(false)
+    }
+
+    // 取出已完成操作的结果
+    pub fn take_outcomes(&self) -> Vec<Outcome> {
+        self.outcomes.lock().map(|mut outcomes| std::mem::take(&mut *outcomes)).unwrap_or_default()
+    }
+}
+
+impl Drop for Worker {
+    // 等待排队的操作（包括退出前的删除）完成
+    fn drop(&mut self) {
+        self.jobs.take();
+        if let Some(thread) = self.thread.take() {
+            let _ = thread.join();
+        }
+    }
+}
+
+fn run(
+    mut installed: SystemRules,
+    jobs: Receiver<Option<SystemRules>>,
+    outcomes: Arc<Mutex<Vec<Outcome>>>,
+    pending: Arc<Mutex<usize>>,
+) {
+    while let Ok(mut job) = jobs.recv() {
+        // 只执行最新的请求，跳过期间排队的旧请求
+        let mut count = 1;
+        while let Ok(newer) = jobs.try_recv() {
+            job = newer;
+            count += 1;
+        }
+        let planned = job.unwrap_or_default();
+        let outcome = match sync(&mut installed, &planned) {
+            Ok(()) => Outcome::Applied(installed.len()),
+            Err(e) => Outcome::Failed(format!("{:#}", e)),
+        };
+        // 回滚也可能部分失败，记录总是按最后一次成功的状态保存
+        let _ = save_installed(&installed);
+        if let Ok(mut outcomes) = outcomes.lock() {
+            outcomes.push(outcome);
+        }
+        if let Ok(mut pending) = pending.lock() {
+            *pending = pending.saturating_sub(count);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn plans_one_rule_per_direction() {
+        let mut port = FirewallRule::new(3, "阻止远程桌面", RuleType::Port);
+        port.port = Some(3389);
+        port.protocol = Some("TCP".to_string());
+        let mut disabled = FirewallRule::new(4, "已禁用", RuleType::Port);
+        disabled.port = Some(80);
+        disabled.enabled = false;
+        let missing_path = FirewallRule::new(5, "未填写路径", RuleType::Application);
+
+        let (planned, skipped) = plan(&[port, disabled, missing_path], &FirewallProfile::Standard);
+        let names: Vec<&str> = planned.keys().map(String::as_str).collect();
+        assert_eq!(names, ["InviZible Pro 3.0", "InviZible Pro 3.1"]);
+        assert_eq!(planned["InviZible Pro 3.0"], ["dir=out", "action=block", "enable=yes", "profile=any", "description=阻止远程桌面", "protocol=TCP", "remoteport=3389"]);
+        assert!(planned["InviZible Pro 3.1"].contains(&"localport=3389".to_string()));
+        assert_eq!(skipped, ["未填写路径: 未指定应用程序路径"]);
+    }
+}
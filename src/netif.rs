@@ -0,0 +1,223 @@
+use std::net::IpAddr;
+use std::process::Command;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use serde::Deserialize;
+use once_cell::sync::Lazy;
+
+use crate::logger::Logger;
+
+// 网络变化检测间隔
+const WATCH_INTERVAL: Duration = Duration::from_secs(5);
+
+// 枚举网卡的PowerShell脚本，每个网卡输出一个对象
+const ENUM_SCRIPT: &str = r#"
+ConvertTo-Json -Compress -Depth 3 -InputObject @(Get-NetAdapter -IncludeHidden:$false | ForEach-Object {
+    $ip = Get-NetIPConfiguration -InterfaceIndex $_.ifIndex -ErrorAction SilentlyContinue
+    [pscustomobject]@{
+        Name = $_.Name
+        Description = $_.InterfaceDescription
+        Index = $_.ifIndex
+        MediaType = [string]$_.MediaType
+        Status = [string]$_.Status
+        MacAddress = $_.MacAddress
+        Hardware = [bool]$_.HardwareInterface
+        IPv4 = @($ip.IPv4Address | ForEach-Object { $_.IPAddress })
+        IPv6 = @($ip.IPv6Address | ForEach-Object { $_.IPAddress })
+        Gateways = @($ip.IPv4DefaultGateway | ForEach-Object { $_.NextHop })
+        DnsServers = @($ip.DNSServer | ForEach-Object { $_.ServerAddresses })
+    }
+})
+"#;
+
+// 常见TUN/TAP虚拟网卡的描述关键字
+const TUNNEL_KEYWORDS: [&str; 5] = ["tap-windows", "wintun", "wireguard", "openvpn", "tun"];
+
+// 网卡类型
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum AdapterKind {
+    Ethernet,
+    Wireless,
+    Tunnel,  // TUN/TAP等VPN虚拟网卡
+    Virtual, // 其他虚拟网卡（Hyper-V、VirtualBox等）
+}
+
+impl AdapterKind {
+    pub fn label(&self) -> &'static str {
+        match self {
+            AdapterKind::Ethernet => "以太网",
+            AdapterKind::Wireless => "无线",
+            AdapterKind::Tunnel => "隧道",
+            AdapterKind::Virtual => "虚拟",
+        }
+    }
+}
+
+// PowerShell输出的原始网卡信息
+#[derive(Deserialize)]
+#[serde(rename_all = "PascalCase")]
+struct RawAdapter {
+    name: String,
+    #[serde(default)]
+    description: String,
+    index: u32,
+    #[serde(default)]
+    media_type: String,
+    #[serde(default)]
+    status: String,
+    #[serde(default)]
+    mac_address: String,
+    #[serde(default)]
+    hardware: bool,
+    #[serde(rename = "IPv4", default)]
+    ipv4: Vec<String>,
+    #[serde(rename = "IPv6", default)]
+    ipv6: Vec<String>,
+    #[serde(default)]
+    gateways: Vec<String>,
+    #[serde(default)]
+    dns_servers: Vec<String>,
+}
+
+// 网卡信息
+#[derive(Clone, Debug, PartialEq)]
+pub struct NetworkAdapter {
+    pub name: String,
+    pub description: String,
+    pub index: u32,
+    pub kind: AdapterKind,
+    pub is_up: bool,
+    pub mac_address: String,
+    pub addresses: Vec<IpAddr>,
+    pub gateways: Vec<IpAddr>,
+    pub dns_servers: Vec<IpAddr>,
+}
+
+impl NetworkAdapter {
+    fn from_raw(raw: RawAdapter) -> Self {
+        let description = raw.description.to_lowercase();
+        let is_tunnel = description
+            .split(|c: char| !c.is_alphanumeric() && c != '-')
+            .any(|word| TUNNEL_KEYWORDS.contains(&word));
+        let kind = if is_tunnel {
+            AdapterKind::Tunnel
+        } else if raw.media_type.contains("802.11") {
+            AdapterKind::Wireless
+        } else if raw.hardware {
+            AdapterKind::Ethernet
+        } else {
+            AdapterKind::Virtual
+        };
+        let parse_all = |values: Vec<String>| -> Vec<IpAddr> {
+            values.iter().filter_map(|v| v.split('%').next()?.parse().ok()).collect()
+        };
+
+        Self {
+            name: raw.name,
+            description: raw.description,
+            index: raw.index,
+            kind,
+            is_up: raw.status.eq_ignore_ascii_case("Up"),
+            mac_address: raw.mac_address,
+            addresses: parse_all(raw.ipv4.into_iter().chain(raw.ipv6).collect()),
+            gateways: parse_all(raw.gateways),
+            dns_servers: parse_all(raw.dns_servers),
+        }
+    }
+
+    // 网卡的IPv4地址
+    pub fn ipv4_addresses(&self) -> impl Iterator<Item = &IpAddr> {
+        self.addresses.iter().filter(|a| a.is_ipv4())
+    }
+}
+
+// 网卡列表快照及其版本号（每次变化递增）
+struct AdapterCache {
+    adapters: Vec<NetworkAdapter>,
+    generation: u64,
+}
+
+static CACHE: Lazy<Mutex<AdapterCache>> = Lazy::new(|| {
+    Mutex::new(AdapterCache {
+        adapters: Vec::new(),
+        generation: 0,
+    })
+});
+
+// 立即枚举系统中的网卡
+pub fn enumerate_adapters() -> Vec<NetworkAdapter> {
+    let output = match Command::new("powershell").args(["-NoProfile", "-Command", ENUM_SCRIPT]).output() {
+        Ok(output) => output,
+        Err(_) => return Vec::new(),
+    };
+    let raw: Vec<RawAdapter> = serde_json::from_slice(&output.stdout).unwrap_or_default();
+    raw.into_iter().map(NetworkAdapter::from_raw).collect()
+}
+
+// 获取缓存的网卡列表（由后台线程保持更新）
+pub fn adapters() -> Vec<NetworkAdapter> {
+    CACHE.lock().map(|c| c.adapters.clone()).unwrap_or_default()
+}
+
+// 网卡列表的版本号，调用方可比较该值判断网络是否发生变化
+pub fn generation() -> u64 {
+    CACHE.lock().map(|c| c.generation).unwrap_or(0)
+}
+
+// 描述两次快照之间的变化
+fn describe_changes(old: &[NetworkAdapter], new: &[NetworkAdapter]) -> Vec<String> {
+    let mut changes = Vec::new();
+    for adapter in new {
+        match old.iter().find(|a| a.index == adapter.index) {
+            None => changes.push(format!("新增网卡: {}", adapter.name)),
+            Some(previous) if previous.is_up != adapter.is_up => changes.push(format!(
+                "网卡 {} 已{}",
+                adapter.name,
+                if adapter.is_up { "连接" } else { "断开" }
+            )),
+            Some(previous) if previous != adapter => changes.push(format!("网卡 {} 的地址或DNS已变化", adapter.name)),
+            _ => {}
+        }
+    }
+    for adapter in old {
+        if !new.iter().any(|a| a.index == adapter.index) {
+            changes.push(format!("网卡已移除: {}", adapter.name));
+        }
+    }
+    changes
+}
+
+// 启动后台线程监视网络变化（首次枚举也在后台进行，避免阻塞界面）
+pub fn start_watcher(logger: Arc<Mutex<Logger>>) {
+    std::thread::spawn(move || {
+        let mut first = true;
+        loop {
+            let current = enumerate_adapters();
+            // 枚举失败时保留旧数据
+            if !current.is_empty() {
+                let changes = match CACHE.lock() {
+                    Ok(mut cache) => {
+                        let changes = describe_changes(&cache.adapters, &current);
+                        if !changes.is_empty() {
+                            cache.adapters = current;
+                            cache.generation += 1;
+                        }
+                        changes
+                    }
+                    Err(_) => Vec::new(),
+                };
+
+                // 首次枚举不记录为变化
+                if !first {
+                    if let Ok(mut logger) = logger.lock() {
+                        for change in changes {
+                            logger.info("网络", &change);
+                        }
+                    }
+                }
+                first = false;
+            }
+            std::thread::sleep(WATCH_INTERVAL);
+        }
+    });
+}
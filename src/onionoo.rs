@@ -0,0 +1,196 @@
+use eframe::egui::{self, Color32, RichText, Ui};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use anyhow::{Context, Result};
+use serde::Deserialize;
+
+use crate::utils;
+
+// Onionoo接口地址
+const ONIONOO_URL: &str = "https://onionoo.torproject.org/details";
+
+// 每次搜索最多返回的结果数
+const SEARCH_LIMIT: u32 = 50;
+
+// 中继或网桥的详细信息（只取需要显示的字段）
+#[derive(Clone, Debug, Default, Deserialize)]
+#[serde(default)]
+pub struct RelayDetails {
+    pub nickname: String,
+    pub fingerprint: String,
+    pub hashed_fingerprint: String, // 网桥只公开指纹的哈希
+    pub running: bool,
+    pub flags: Vec<String>,
+    pub country: String,
+    pub country_name: String,
+    pub or_addresses: Vec<String>,
+    pub observed_bandwidth: u64,
+    pub advertised_bandwidth: u64,
+    pub platform: String,
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(default)]
+struct DetailsResponse {
+    relays: Vec<RelayDetails>,
+    bridges: Vec<RelayDetails>,
+}
+
+// 搜索结果
+#[derive(Clone, Debug, Default)]
+pub struct SearchResult {
+    pub relays: Vec<RelayDetails>,
+    pub bridges: Vec<RelayDetails>,
+}
+
+// 按昵称、指纹或IP搜索（proxy_url可选，用于通过Tor查询）
+pub fn search(query: &str, proxy_url: Option<&str>) -> Result<SearchResult> {
+    let mut builder = reqwest::blocking::Client::builder().timeout(Duration::from_secs(30));
+    if let Some(proxy_url) = proxy_url {
+        builder = builder.proxy(reqwest::Proxy::all(proxy_url).context("Invalid proxy url")?);
+    }
+    let client = builder.build().context("Failed to build http client")?;
+    let limit = SEARCH_LIMIT.to_string();
+    let response: DetailsResponse = client
+        .get(ONIONOO_URL)
+        .query(&[("search", query.trim().trim_start_matches('$')), ("limit", limit.as_str())])
+        .send()
+        .context("Failed to reach Onionoo")?
+        .error_for_status()
+        .context("Onionoo rejected the query")?
+        .json()
+        .context("Failed to parse Onionoo response")?;
+    Ok(SearchResult {
+        relays: response.relays,
+        bridges: response.bridges,
+    })
+}
+
+#[derive(Clone, Debug)]
+enum SearchState {
+    Idle,
+    Searching,
+    Done(SearchResult),
+    Failed(String),
+}
+
+// 中继搜索面板
+pub struct RelaySearch {
+    query: String,
+    state: Arc<Mutex<SearchState>>,
+}
+
+impl Default for RelaySearch {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl RelaySearch {
+    pub fn new() -> Self {
+        Self {
+            query: String::new(),
+            state: Arc::new(Mutex::new(SearchState::Idle)),
+        }
+    }
+
+    fn start(&self, proxy_url: Option<String>) {
+        if let Ok(mut state) = self.state.lock() {
+            *state = SearchState::Searching;
+        }
+        let query = self.query.clone();
+        let state = Arc::clone(&self.state);
+        std::thread::spawn(move || {
+            let next = match search(&query, proxy_url.as_deref()) {
+                Ok(result) => SearchState::Done(result),
+                Err(e) => SearchState::Failed(format!("{:#}", e)),
+            };
+            if let Ok(mut state) = state.lock() {
+                *state = next;
+            }
+        });
+    }
+
+    // 渲染搜索面板，proxy_url为Tor已连接时的SOCKS地址
+    pub fn ui(&mut self, ui: &mut Ui, proxy_url: Option<String>) {
+        let state = self.state.lock().map(|s| s.clone()).unwrap_or(SearchState::Idle);
+        let searching = matches!(state, SearchState::Searching);
+
+        ui.horizontal(|ui| {
+            let response = ui.add(
+                egui::TextEdit::singleline(&mut self.query)
+                    .hint_text("昵称、指纹或IP")
+                    .desired_width(280.0),
+            );
+            let entered = response.lost_focus() && ui.input(|i| i.key_pressed(egui::Key::Enter));
+            let can_search = !searching && !self.query.trim().is_empty();
+            if ui.add_enabled(can_search, egui::Button::new("搜索")).clicked() || (entered && can_search) {
+                self.start(proxy_url.clone());
+            }
+            if searching {
+                ui.spinner();
+                ui.ctx().request_repaint_after(Duration::from_millis(200));
+            }
+        });
+        ui.label(RichText::new(if proxy_url.is_some() { "通过Tor查询" } else { "直接查询（Tor未连接）" }).weak());
+
+        match state {
+            SearchState::Idle | SearchState::Searching => {}
+            SearchState::Failed(error) => {
+                ui.label(RichText::new(format!("搜索失败: {}", error)).color(Color32::RED));
+            }
+            SearchState::Done(result) => {
+                if result.relays.is_empty() && result.bridges.is_empty() {
+                    ui.label("没有找到匹配的中继或网桥");
+                    return;
+                }
+                egui::ScrollArea::vertical().id_source("onionoo_results").max_height(300.0).show(ui, |ui| {
+                    if !result.relays.is_empty() {
+                        ui.label(RichText::new(format!("中继（{}）", result.relays.len())).strong());
+                        results_grid(ui, "onionoo_relays", &result.relays, false);
+                    }
+                    if !result.bridges.is_empty() {
+                        ui.label(RichText::new(format!("网桥（{}）", result.bridges.len())).strong());
+                        results_grid(ui, "onionoo_bridges", &result.bridges, true);
+                    }
+                });
+            }
+        }
+    }
+}
+
+// 结果表格
+fn results_grid(ui: &mut Ui, id: &str, entries: &[RelayDetails], bridges: bool) {
+    egui::Grid::new(id).striped(true).num_columns(6).show(ui, |ui| {
+        ui.strong("昵称");
+        ui.strong("指纹");
+        ui.strong("国家");
+        ui.strong("带宽");
+        ui.strong("标志");
+        ui.strong("状态");
+        ui.end_row();
+
+        for entry in entries {
+            ui.label(&entry.nickname);
+            let fingerprint = if bridges { &entry.hashed_fingerprint } else { &entry.fingerprint };
+            let response = ui.label(RichText::new(fingerprint).monospace().small());
+            if !bridges {
+                response.on_hover_text(format!("地址: {}\n平台: {}", entry.or_addresses.join(", "), entry.platform));
+            }
+            if entry.country.is_empty() {
+                ui.label("-");
+            } else {
+                ui.label(entry.country.to_uppercase()).on_hover_text(&entry.country_name);
+            }
+            let bandwidth = if bridges { entry.advertised_bandwidth } else { entry.observed_bandwidth };
+            ui.label(format!("{}/s", utils::format_bytes(bandwidth)));
+            ui.label(entry.flags.join(" "));
+            if entry.running {
+                ui.label(RichText::new("运行中").color(Color32::GREEN));
+            } else {
+                ui.label(RichText::new("离线").color(Color32::GRAY));
+            }
+            ui.end_row();
+        }
+    });
+}
@@ -0,0 +1,209 @@
+use eframe::egui::{self, Color32, RichText, Ui};
+use std::collections::{HashMap, HashSet};
+use std::net::{TcpListener, UdpSocket};
+use std::process::Command;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+use once_cell::sync::Lazy;
+
+// 检测结果的缓存时间
+const CHECK_TTL: Duration = Duration::from_secs(3);
+
+// 端口协议
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum Protocol {
+    Tcp,
+    Udp,
+}
+
+impl Protocol {
+    fn netstat_name(&self) -> &'static str {
+        match self {
+            Protocol::Tcp => "TCP",
+            Protocol::Udp => "UDP",
+        }
+    }
+}
+
+// 占用端口的进程
+#[derive(Clone, Debug, PartialEq)]
+pub struct PortOwner {
+    pub pid: u32,
+    pub process: String,
+}
+
+impl PortOwner {
+    pub fn describe(&self) -> String {
+        format!("{} (PID {})", self.process, self.pid)
+    }
+}
+
+// 端口检测状态
+#[derive(Clone, Debug, PartialEq)]
+pub enum PortStatus {
+    Checking,
+    Free,
+    InUse(Option<PortOwner>), // 被其他程序占用，尽量查出占用进程
+    Reserved(String),         // 与本软件的其他模块配置冲突
+}
+
+// 后台检测结果缓存：(地址, 端口, 协议) -> (检测时间, 状态)
+static CHECKS: Lazy<Mutex<HashMap<(String, u16, Protocol), (Instant, PortStatus)>>> = Lazy::new(|| Mutex::new(HashMap::new()));
+
+// 正在后台检测的端口
+static IN_FLIGHT: Lazy<Mutex<HashSet<(String, u16, Protocol)>>> = Lazy::new(|| Mutex::new(HashSet::new()));
+
+// 各模块配置使用的端口：模块名 -> 端口列表
+static RESERVED: Lazy<Mutex<HashMap<String, Vec<(u16, Protocol)>>>> = Lazy::new(|| Mutex::new(HashMap::new()));
+
+// 通过尝试绑定判断端口是否空闲（比连接探测更准确，可发现只监听特定地址的程序）
+pub fn is_port_free(host: &str, port: u16, protocol: Protocol) -> bool {
+    match protocol {
+        Protocol::Tcp => TcpListener::bind((host, port)).is_ok(),
+        Protocol::Udp => UdpSocket::bind((host, port)).is_ok(),
+    }
+}
+
+// 通过netstat查找占用端口的进程
+pub fn find_owner(port: u16, protocol: Protocol) -> Option<PortOwner> {
+    let output = Command::new("netstat")
+        .args(["-ano", "-p", protocol.netstat_name()])
+        .output()
+        .ok()?;
+    let pid = String::from_utf8_lossy(&output.stdout).lines().find_map(|line| {
+        let fields: Vec<&str> = line.split_whitespace().collect();
+        if fields.len() < 4 || !fields[0].eq_ignore_ascii_case(protocol.netstat_name()) {
+            return None;
+        }
+        // TCP只看监听状态的条目
+        if protocol == Protocol::Tcp && fields.get(3) != Some(&"LISTENING") {
+            return None;
+        }
+        let local_port = fields[1].rsplit(':').next()?.parse::<u16>().ok()?;
+        if local_port != port {
+            return None;
+        }
+        fields.last()?.parse::<u32>().ok()
+    })?;
+
+    let output = Command::new("tasklist")
+        .args(["/FI", &format!("PID eq {}", pid), "/FO", "CSV", "/NH"])
+        .output()
+        .ok()?;
+    let process = String::from_utf8_lossy(&output.stdout)
+        .lines()
+        .next()
+        .and_then(|line| line.split("\",\"").next())
+        .map(|name| name.trim_matches('"').to_string())
+        .filter(|name| !name.is_empty() && !name.starts_with("INFO:"))
+        .unwrap_or_else(|| "未知进程".to_string());
+    Some(PortOwner { pid, process })
+}
+
+// 登记模块配置使用的端口，替换该模块之前登记的端口
+pub fn reserve(module: &str, ports: Vec<(u16, Protocol)>) {
+    if let Ok(mut reserved) = RESERVED.lock() {
+        reserved.insert(module.to_string(), ports);
+    }
+}
+
+// 查找配置了该端口的其他模块
+fn reserved_by_other(module: &str, port: u16, protocol: Protocol) -> Option<String> {
+    let reserved = RESERVED.lock().ok()?;
+    reserved
+        .iter()
+        .find(|(name, ports)| name.as_str() != module && ports.contains(&(port, protocol)))
+        .map(|(name, _)| name.clone())
+}
+
+// 从start开始查找一个空闲且未被其他模块配置的端口
+pub fn suggest_free_port(module: &str, host: &str, start: u16, protocol: Protocol) -> Option<u16> {
+    (start.max(1024)..=u16::MAX)
+        .chain(1024..start.max(1024))
+        .find(|&port| reserved_by_other(module, port, protocol).is_none() && is_port_free(host, port, protocol))
+}
+
+// 立即检测端口状态（会阻塞调用线程，查找占用进程较慢）
+pub fn check_now(module: &str, host: &str, port: u16, protocol: Protocol) -> PortStatus {
+    if let Some(other) = reserved_by_other(module, port, protocol) {
+        return PortStatus::Reserved(other);
+    }
+    if is_port_free(host, port, protocol) {
+        PortStatus::Free
+    } else {
+        PortStatus::InUse(find_owner(port, protocol))
+    }
+}
+
+// 获取端口状态，不阻塞界面：结果过期时在后台重新检测，期间返回旧结果
+pub fn status(module: &str, host: &str, port: u16, protocol: Protocol) -> PortStatus {
+    let key = (host.to_string(), port, protocol);
+    let mut checks = match CHECKS.lock() {
+        Ok(checks) => checks,
+        Err(_) => return PortStatus::Checking,
+    };
+    let cached = checks.get(&key).map(|(checked_at, status)| (checked_at.elapsed() < CHECK_TTL, status.clone()));
+    if let Some((true, status)) = cached {
+        return status;
+    }
+    let in_flight = IN_FLIGHT.lock().map(|f| f.contains(&key)).unwrap_or(false);
+    if !in_flight {
+        if let Ok(mut in_flight) = IN_FLIGHT.lock() {
+            in_flight.insert(key.clone());
+        }
+        let module = module.to_string();
+        std::thread::spawn(move || {
+            let status = check_now(&module, &key.0, key.1, key.2);
+            if let Ok(mut checks) = CHECKS.lock() {
+                checks.insert(key.clone(), (Instant::now(), status));
+            }
+            if let Ok(mut in_flight) = IN_FLIGHT.lock() {
+                in_flight.remove(&key);
+            }
+        });
+    }
+    checks.retain(|_, (checked_at, _)| checked_at.elapsed() < CHECK_TTL * 20);
+    cached.map(|(_, status)| status).unwrap_or(PortStatus::Checking)
+}
+
+// 端口输入框：显示占用状态，冲突时提供"建议端口"按钮。listening表示本模块正在使用该端口
+pub fn port_field(ui: &mut Ui, module: &str, host: &str, port: &mut u16, protocol: Protocol, listening: bool) -> bool {
+    let mut changed = false;
+    ui.horizontal(|ui| {
+        changed |= ui.add(egui::DragValue::new(port).clamp_range(1..=65535)).changed();
+
+        if listening {
+            ui.label(RichText::new("使用中").color(Color32::GREEN));
+            return;
+        }
+        let conflict = match status(module, host, *port, protocol) {
+            PortStatus::Checking => {
+                ui.spinner();
+                false
+            }
+            PortStatus::Free => {
+                ui.label(RichText::new("端口可用").color(Color32::GREEN));
+                false
+            }
+            PortStatus::InUse(owner) => {
+                let text = match owner {
+                    Some(owner) => format!("已被 {} 占用", owner.describe()),
+                    None => "已被其他程序占用".to_string(),
+                };
+                ui.label(RichText::new(text).color(Color32::RED));
+                true
+            }
+            PortStatus::Reserved(other) => {
+                ui.label(RichText::new(format!("与{}的端口冲突", other)).color(Color32::RED));
+                true
+            }
+        };
+        if conflict && ui.button("建议端口").on_hover_text("自动选择一个空闲端口").clicked() {
+            if let Some(free) = suggest_free_port(module, host, *port, protocol) {
+                *port = free;
+                changed = true;
+            }
+        }
+    });
+    changed
+}
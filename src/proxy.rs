@@ -4,8 +4,15 @@ use serde::{Deserialize, Serialize};
 use arboard::Clipboard;
 
 use crate::logger::Logger;
+use crate::netif;
+use crate::ports::{self, Protocol};
+use crate::services::{self, NetworkProbe};
+use crate::traffic::{self, TrafficSource};
 use crate::app::SETTINGS_COLOR;
 
+// 登记端口时使用的模块名
+const MODULE_NAME: &str = "代理";
+
 // 代理协议类型
 #[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
 pub enum ProxyProtocol {
@@ -39,6 +46,28 @@ impl Default for ProxyConfig {
     }
 }
 
+// Tor提供的上游端口
+#[derive(Clone, Debug, PartialEq)]
+pub struct TorUpstream {
+    pub socks_port: u16,
+    pub dns_port: Option<u16>,
+    pub http_tunnel_port: Option<u16>,
+}
+
+impl TorUpstream {
+    // 显示用的描述
+    pub fn describe(&self) -> String {
+        let mut parts = vec![format!("SOCKS5 127.0.0.1:{}", self.socks_port)];
+        if let Some(port) = self.http_tunnel_port {
+            parts.push(format!("HTTP隧道 127.0.0.1:{}", port));
+        }
+        if let Some(port) = self.dns_port {
+            parts.push(format!("DNS 127.0.0.1:{}", port));
+        }
+        parts.join("，")
+    }
+}
+
 // 代理服务器特性
 pub trait ProxyServer {
     fn start(&self) -> Box<dyn ProxyServer>;
@@ -49,11 +78,12 @@ pub trait ProxyServer {
 pub struct HttpProxy {
     address: String,
     port: u16,
+    traffic: Arc<traffic::Counter>, // 与客户端之间转发的字节数
 }
 
 impl HttpProxy {
-    pub fn new(address: String, port: u16) -> Self {
-        Self { address, port }
+    pub fn new(address: String, port: u16, traffic: Arc<traffic::Counter>) -> Self {
+        Self { address, port, traffic }
     }
 }
 
@@ -64,6 +94,7 @@ impl ProxyServer for HttpProxy {
         Box::new(Self {
             address: self.address.clone(),
             port: self.port,
+            traffic: Arc::clone(&self.traffic),
         })
     }
     
@@ -77,11 +108,12 @@ impl ProxyServer for HttpProxy {
 pub struct Socks5Proxy {
     address: String,
     port: u16,
+    traffic: Arc<traffic::Counter>, // 与客户端之间转发的字节数
 }
 
 impl Socks5Proxy {
-    pub fn new(address: String, port: u16) -> Self {
-        Self { address, port }
+    pub fn new(address: String, port: u16, traffic: Arc<traffic::Counter>) -> Self {
+        Self { address, port, traffic }
     }
 }
 
@@ -92,6 +124,7 @@ impl ProxyServer for Socks5Proxy {
         Box::new(Self {
             address: self.address.clone(),
             port: self.port,
+            traffic: Arc::clone(&self.traffic),
         })
     }
     
@@ -106,21 +139,29 @@ pub struct ProxyModule {
     config: ProxyConfig,
     logger: Arc<Mutex<Logger>>,
     status: String,
-    port_conflict: bool,
-    port_checking: bool,
     proxy: Option<Box<dyn ProxyServer>>,
+    network: Arc<dyn NetworkProbe>,
+    tor_upstream: Option<TorUpstream>,
+    traffic: Arc<traffic::Counter>,
 }
 
 impl ProxyModule {
     pub fn new(logger: Arc<Mutex<Logger>>) -> Self {
+        Self::with_network(logger, services::network())
+    }
+    
+    // 使用指定的网络探测创建模块
+    pub fn with_network(logger: Arc<Mutex<Logger>>, network: Arc<dyn NetworkProbe>) -> Self {
         let module = Self {
             proxy: None,
+            network,
+            tor_upstream: None,
+            traffic: Arc::new(traffic::Counter::default()),
             config: ProxyConfig::default(),
             logger,
             status: "未启动".to_string(),
-            port_conflict: false,
-            port_checking: false,
         };
+        ports::reserve(MODULE_NAME, vec![(module.config.listen_port, Protocol::Tcp)]);
         
         // 记录模块初始化日志
         if let Ok(mut logger) = module.logger.lock() {
@@ -132,9 +173,13 @@ impl ProxyModule {
     
     // 启动代理服务
     fn start_proxy(&mut self) {
-        if self.port_conflict {
+        let port = self.config.listen_port;
+        if self.network.is_port_in_use(&self.config.listen_address, port, Protocol::Tcp) {
+            let owner = self.network.port_owner(port, Protocol::Tcp)
+                .map(|owner| format!("，占用进程: {}", owner.describe()))
+                .unwrap_or_default();
             if let Ok(mut logger) = self.logger.lock() {
-                logger.error("代理", "无法启动代理服务：端口冲突");
+                logger.error("代理", &format!("无法启动代理服务：端口 {} 已被占用{}", port, owner));
             }
             return;
         }
@@ -144,16 +189,19 @@ impl ProxyModule {
         
         if let Ok(mut logger) = self.logger.lock() {
             logger.info("代理", &format!("代理服务已启动 ({}:{})", self.config.listen_address, self.config.listen_port));
+            if let (true, Some(upstream)) = (self.config.tor_enabled, &self.tor_upstream) {
+                logger.info("代理", &format!("Tor上游: {}", upstream.describe()));
+            }
         }
         
         // 启动代理服务器
         let proxy = match self.config.protocol {
             ProxyProtocol::HTTP => {
-                let proxy = HttpProxy::new(self.config.listen_address.clone(), self.config.listen_port);
+                let proxy = HttpProxy::new(self.config.listen_address.clone(), self.config.listen_port, Arc::clone(&self.traffic));
                 proxy.start()
             }
             ProxyProtocol::SOCKS5 => {
-                let proxy = Socks5Proxy::new(self.config.listen_address.clone(), self.config.listen_port);
+                let proxy = Socks5Proxy::new(self.config.listen_address.clone(), self.config.listen_port, Arc::clone(&self.traffic));
                 proxy.start()
             }
         };
@@ -175,29 +223,76 @@ impl ProxyModule {
         }
     }
     
-    // 检查端口冲突
-    fn check_port_conflict(&mut self) {
-        self.port_checking = true;
-        
-        if let Ok(mut logger) = self.logger.lock() {
-            logger.info("代理", &format!("正在检查端口 {} 是否可用...", self.config.listen_port));
+    // 更改监听端口，运行中时重启服务
+    fn set_listen_port(&mut self, port: u16) {
+        if port == self.config.listen_port {
+            return;
         }
-        
-        // 使用端口扫描器检查端口是否被占用
-        let port_in_use = port_scanner::scan_port(self.config.listen_port);
-        
-        self.port_conflict = port_in_use;
-        self.port_checking = false;
-        
-        if let Ok(mut logger) = self.logger.lock() {
-            if self.port_conflict {
-                logger.warning("代理", &format!("端口 {} 已被占用", self.config.listen_port));
+        self.config.listen_port = port;
+        ports::reserve(MODULE_NAME, vec![(port, Protocol::Tcp)]);
+        if self.config.enabled {
+            self.stop_proxy();
+            self.start_proxy();
+        }
+    }
+    
+    // 更新Tor上游端口，Tor端口变化时记录
+    pub fn set_tor_upstream(&mut self, upstream: Option<TorUpstream>) {
+        if self.tor_upstream == upstream {
+            return;
+        }
+        if self.config.enabled && self.config.tor_enabled {
+            if let Ok(mut logger) = self.logger.lock() {
+                match &upstream {
+                    Some(upstream) => logger.info("代理", &format!("Tor上游已更新: {}", upstream.describe())),
+                    None => logger.warning("代理", "Tor已停止，代理暂时无法转发到Tor"),
+                }
+            }
+        }
+        self.tor_upstream = upstream;
+    }
+    
+    // 将转发的流量上报到流量统计
+    pub fn tick(&self) {
+        self.traffic.report(TrafficSource::Proxy);
+    }
+    
+    // 按指定状态启动/停止代理服务
+    pub fn set_enabled(&mut self, enabled: bool) {
+        if self.config.enabled != enabled {
+            if enabled {
+                self.start_proxy();
             } else {
-                logger.info("代理", &format!("端口 {} 可用", self.config.listen_port));
+                self.stop_proxy();
             }
         }
     }
     
+    // 代理服务是否正在运行
+    pub fn is_enabled(&self) -> bool {
+        self.config.enabled
+    }
+    
+    // 获取代理地址（仅在代理运行时可用）
+    pub fn proxy_url(&self) -> Option<String> {
+        if !self.config.enabled {
+            return None;
+        }
+        Some(match self.config.protocol {
+            ProxyProtocol::HTTP => format!("http://{}:{}", self.config.listen_address, self.config.listen_port),
+            ProxyProtocol::SOCKS5 => format!("socks5h://{}:{}", self.config.listen_address, self.config.listen_port),
+        })
+    }
+    
+    // 获取代理监听端口（仅在代理运行时可用）
+    pub fn listen_port(&self) -> Option<u16> {
+        if self.config.enabled {
+            Some(self.config.listen_port)
+        } else {
+            None
+        }
+    }
+    
     // 切换代理协议
     fn toggle_protocol(&mut self) {
         self.config.protocol = match self.config.protocol {
@@ -270,10 +365,31 @@ impl ProxyModule {
                 });
                 ui.end_row();
                 
-                // 监听地址
+                // 监听地址（可选择本机网卡地址以供局域网设备使用）
                 ui.label("监听地址:");
                 let mut listen_address = self.config.listen_address.clone();
-                if ui.text_edit_singleline(&mut listen_address).changed() {
+                let mut changed = false;
+                ui.horizontal(|ui| {
+                    changed |= ui.text_edit_singleline(&mut listen_address).changed();
+                    egui::ComboBox::from_id_source("proxy_listen_address")
+                        .selected_text("选择")
+                        .show_ui(ui, |ui| {
+                            let mut options = vec![
+                                ("127.0.0.1".to_string(), "仅本机".to_string()),
+                                ("0.0.0.0".to_string(), "所有网卡".to_string()),
+                            ];
+                            for adapter in netif::adapters().iter().filter(|a| a.is_up) {
+                                for address in adapter.ipv4_addresses() {
+                                    options.push((address.to_string(), adapter.name.clone()));
+                                }
+                            }
+                            for (address, name) in options {
+                                let text = format!("{} ({})", address, name);
+                                changed |= ui.selectable_value(&mut listen_address, address, text).changed();
+                            }
+                        });
+                });
+                if changed {
                     self.config.listen_address = listen_address;
                     if self.config.enabled {
                         // 如果代理正在运行，需要重启服务
@@ -285,36 +401,11 @@ impl ProxyModule {
                 
                 // 监听端口
                 ui.label("监听端口:");
-                ui.horizontal(|ui| {
-                    let mut port_str = self.config.listen_port.to_string();
-                    let response = ui.text_edit_singleline(&mut port_str);
-                    if response.changed() {
-                        if let Ok(port) = port_str.parse::<u16>() {
-                            if port != self.config.listen_port {
-                                self.config.listen_port = port;
-                                self.check_port_conflict();
-                                
-                                if self.config.enabled {
-                                    // 如果代理正在运行，需要重启服务
-                                    self.stop_proxy();
-                                    if !self.port_conflict {
-                                        self.start_proxy();
-                                    }
-                                }
-                            }
-                        }
-                    }
-                    
-                    if ui.button("检查端口").clicked() {
-                        self.check_port_conflict();
-                    }
-                    
-                    if self.port_conflict {
-                        ui.label(RichText::new("端口冲突！").color(Color32::RED));
-                    } else if !self.port_checking {
-                        ui.label(RichText::new("端口可用").color(Color32::GREEN));
-                    }
-                });
+                let mut listen_port = self.config.listen_port;
+                let host = self.config.listen_address.clone();
+                if ports::port_field(ui, MODULE_NAME, &host, &mut listen_port, Protocol::Tcp, self.config.enabled) {
+                    self.set_listen_port(listen_port);
+                }
                 ui.end_row();
             });
         
@@ -324,6 +415,14 @@ impl ProxyModule {
         ui.heading("代理服务选项");
         
         ui.checkbox(&mut self.config.tor_enabled, "通过代理启用Tor服务");
+        if self.config.tor_enabled {
+            ui.indent("proxy_tor_upstream", |ui| {
+                match &self.tor_upstream {
+                    Some(upstream) => ui.label(format!("Tor上游: {}", upstream.describe())),
+                    None => ui.label(RichText::new("Tor未运行").color(Color32::YELLOW)),
+                };
+            });
+        }
         ui.checkbox(&mut self.config.dnscrypt_enabled, "通过代理启用DNSCrypt服务");
         ui.checkbox(&mut self.config.i2p_enabled, "通过代理启用I2P服务");
         
@@ -354,4 +453,40 @@ impl ProxyModule {
             });
         }
     }
-}
\ No newline at end of file
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::services::mock::MockNetwork;
+
+    fn module(busy_ports: &[u16]) -> ProxyModule {
+        let logger = Arc::new(Mutex::new(Logger::new()));
+        ProxyModule::with_network(logger, Arc::new(MockNetwork::with_busy_ports(busy_ports)))
+    }
+
+    #[test]
+    fn start_and_stop() {
+        let mut proxy = module(&[]);
+        proxy.set_enabled(true);
+        assert!(proxy.is_enabled());
+        assert!(proxy.proxy.is_some());
+
+        proxy.set_enabled(false);
+        assert!(!proxy.is_enabled());
+        assert!(proxy.proxy.is_none());
+    }
+
+    #[test]
+    fn start_fails_when_port_in_use() {
+        let port = ProxyConfig::default().listen_port;
+        let mut proxy = module(&[port]);
+        proxy.set_enabled(true);
+        assert!(!proxy.is_enabled());
+        assert!(proxy.proxy.is_none());
+
+        let logger = proxy.logger.lock().unwrap();
+        let problem = logger.last_problem().expect("应记录错误");
+        assert!(problem.message.contains("已被占用"));
+    }
+}
@@ -0,0 +1,88 @@
+use std::time::Duration;
+use anyhow::{anyhow, Context, Result};
+
+use crate::dns_stamp::{self, DnsStamp};
+
+// 公共服务器列表的下载地址（任一可用即可）
+pub const PUBLIC_RESOLVERS_URLS: [&str; 2] = [
+    "https://raw.githubusercontent.com/DNSCrypt/dnscrypt-resolvers/master/v3/public-resolvers.md",
+    "https://download.dnscrypt.info/resolvers-list/v3/public-resolvers.md",
+];
+
+// 匿名DNS中继列表的下载地址
+pub const RELAYS_URLS: [&str; 2] = [
+    "https://raw.githubusercontent.com/DNSCrypt/dnscrypt-resolvers/master/v3/relays.md",
+    "https://download.dnscrypt.info/resolvers-list/v3/relays.md",
+];
+
+// 列表签名公钥（dnscrypt-proxy下载列表时用它校验）
+pub const LIST_MINISIGN_KEY: &str = "RWQf6LRCGA9i53mlYecO4IzT51TGPpvWucNSCh1CBM0QTaLn73Y7GFO3";
+
+// 列表中的一项
+#[derive(Clone, Debug)]
+pub struct ListEntry {
+    pub name: String,
+    pub description: String,
+    pub stamp: String,
+    pub decoded: DnsStamp,
+}
+
+// 解析列表文件：每项以"## 名称"开头，随后是描述和一个或多个sdns://行（取第一个可解码的）
+pub fn parse_list(contents: &str) -> Vec<ListEntry> {
+    let mut entries = Vec::new();
+    for section in contents.split("\n## ").skip(1) {
+        let mut lines = section.lines();
+        let name = lines.next().unwrap_or("").trim().to_string();
+        if name.is_empty() {
+            continue;
+        }
+        let mut description = Vec::new();
+        let mut stamp = None;
+        for line in lines.map(str::trim).filter(|l| !l.is_empty()) {
+            if line.starts_with("sdns://") {
+                if stamp.is_none() {
+                    if let Ok(decoded) = dns_stamp::parse(line) {
+                        stamp = Some((line.to_string(), decoded));
+                    }
+                }
+            } else {
+                description.push(line);
+            }
+        }
+        if let Some((stamp, decoded)) = stamp {
+            entries.push(ListEntry {
+                name,
+                description: description.join(" "),
+                stamp,
+                decoded,
+            });
+        }
+    }
+    entries
+}
+
+// 依次尝试各下载地址
+fn download(urls: &[&str]) -> Result<String> {
+    let client = reqwest::blocking::Client::builder()
+        .timeout(Duration::from_secs(30))
+        .build()
+        .context("Failed to build http client")?;
+    let mut last_error = anyhow!("No url to download");
+    for url in urls {
+        match client.get(*url).send().and_then(|r| r.error_for_status()).and_then(|r| r.text()) {
+            Ok(text) => return Ok(text),
+            Err(e) => last_error = anyhow!(e).context(format!("Failed to download {}", url)),
+        }
+    }
+    Err(last_error)
+}
+
+// 下载并解析公共服务器与中继列表（会阻塞调用线程）
+pub fn fetch() -> Result<(Vec<ListEntry>, Vec<ListEntry>)> {
+    let resolvers = parse_list(&download(&PUBLIC_RESOLVERS_URLS)?);
+    if resolvers.is_empty() {
+        return Err(anyhow!("服务器列表为空或格式无法识别"));
+    }
+    let relays = parse_list(&download(&RELAYS_URLS)?);
+    Ok((resolvers, relays))
+}
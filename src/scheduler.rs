@@ -0,0 +1,350 @@
+use eframe::egui::{self, Color32, RichText, Ui, Grid};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use serde::{Deserialize, Serialize};
+use chrono::{Datelike, Local, Timelike};
+
+use crate::firewall::FirewallProfile;
+use crate::logger::Logger;
+use crate::utils;
+
+// 定时任务配置文件名
+const SCHEDULER_CONFIG_FILE: &str = "scheduler.json";
+
+// 后台检查间隔
+const TICK_INTERVAL: Duration = Duration::from_secs(15);
+
+// 星期显示名称（周一到周日）
+const WEEKDAY_NAMES: [&str; 7] = ["一", "二", "三", "四", "五", "六", "日"];
+
+// 定时任务动作
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub enum ScheduledAction {
+    StartTor,
+    StopTor,
+    StartDnsCrypt,
+    StopDnsCrypt,
+    StartI2P,
+    StopI2P,
+    EnableFirewall,
+    DisableFirewall,
+    SetFirewallProfile(FirewallProfile),
+    UpdateSubscriptions,
+    RandomizeMac,
+}
+
+impl ScheduledAction {
+    // 所有可选动作
+    pub fn all() -> Vec<Self> {
+        vec![
+            ScheduledAction::StartTor,
+            ScheduledAction::StopTor,
+            ScheduledAction::StartDnsCrypt,
+            ScheduledAction::StopDnsCrypt,
+            ScheduledAction::StartI2P,
+            ScheduledAction::StopI2P,
+            ScheduledAction::EnableFirewall,
+            ScheduledAction::DisableFirewall,
+            ScheduledAction::SetFirewallProfile(FirewallProfile::Standard),
+            ScheduledAction::SetFirewallProfile(FirewallProfile::Strict),
+            ScheduledAction::UpdateSubscriptions,
+            ScheduledAction::RandomizeMac,
+        ]
+    }
+
+    // 动作的显示名称
+    pub fn label(&self) -> String {
+        match self {
+            ScheduledAction::StartTor => "启动Tor".to_string(),
+            ScheduledAction::StopTor => "停止Tor".to_string(),
+            ScheduledAction::StartDnsCrypt => "启动DNSCrypt".to_string(),
+            ScheduledAction::StopDnsCrypt => "停止DNSCrypt".to_string(),
+            ScheduledAction::StartI2P => "启动I2P".to_string(),
+            ScheduledAction::StopI2P => "停止I2P".to_string(),
+            ScheduledAction::EnableFirewall => "启用防火墙".to_string(),
+            ScheduledAction::DisableFirewall => "禁用防火墙".to_string(),
+            ScheduledAction::SetFirewallProfile(profile) => format!("切换防火墙配置为{}", profile.label()),
+            ScheduledAction::UpdateSubscriptions => "更新VPN订阅".to_string(),
+            ScheduledAction::RandomizeMac => "随机化MAC地址".to_string(),
+        }
+    }
+}
+
+// 定时任务规则
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct ScheduleRule {
+    pub id: usize,
+    pub name: String,
+    pub enabled: bool,
+    pub hour: u32,
+    pub minute: u32,
+    pub days: [bool; 7], // 周一到周日
+    pub action: ScheduledAction,
+    #[serde(skip)]
+    pub last_run: Option<String>, // 上次执行的时间标识，避免同一分钟内重复执行
+}
+
+impl ScheduleRule {
+    pub fn new(id: usize, name: &str, hour: u32, minute: u32, action: ScheduledAction) -> Self {
+        Self {
+            id,
+            name: name.to_string(),
+            enabled: true,
+            hour,
+            minute,
+            days: [true; 7],
+            action,
+            last_run: None,
+        }
+    }
+
+    // 星期描述
+    fn days_text(&self) -> String {
+        if self.days.iter().all(|d| *d) {
+            return "每天".to_string();
+        }
+        if self.days[..5].iter().all(|d| *d) && !self.days[5] && !self.days[6] {
+            return "工作日".to_string();
+        }
+        if !self.days[..5].iter().any(|d| *d) && self.days[5] && self.days[6] {
+            return "周末".to_string();
+        }
+        let days: Vec<&str> = WEEKDAY_NAMES.iter()
+            .zip(self.days.iter())
+            .filter(|(_, enabled)| **enabled)
+            .map(|(name, _)| *name)
+            .collect();
+        format!("周{}", days.join("、"))
+    }
+}
+
+// v1: 引入版本号，格式与之前相同
+impl utils::VersionedConfig for Vec<ScheduleRule> {
+    const VERSION: u32 = 1;
+}
+
+// 定时任务调度器
+pub struct Scheduler {
+    rules: Arc<Mutex<Vec<ScheduleRule>>>,
+    pending: Arc<Mutex<Vec<(String, ScheduledAction)>>>,
+    next_rule_id: usize,
+    logger: Arc<Mutex<Logger>>,
+    new_rule_name: String,
+    new_rule_hour: u32,
+    new_rule_minute: u32,
+    new_rule_days: [bool; 7],
+    new_rule_action: ScheduledAction,
+}
+
+impl Scheduler {
+    pub fn new(logger: Arc<Mutex<Logger>>) -> Self {
+        let rules: Vec<ScheduleRule> = utils::load_saved_config(SCHEDULER_CONFIG_FILE).unwrap_or_default();
+        let next_rule_id = rules.iter().map(|r| r.id).max().unwrap_or(0) + 1;
+
+        let scheduler = Self {
+            rules: Arc::new(Mutex::new(rules)),
+            pending: Arc::new(Mutex::new(Vec::new())),
+            next_rule_id,
+            logger,
+            new_rule_name: String::new(),
+            new_rule_hour: 22,
+            new_rule_minute: 0,
+            new_rule_days: [true; 7],
+            new_rule_action: ScheduledAction::StartTor,
+        };
+
+        scheduler.spawn_worker();
+
+        if let Ok(mut logger) = scheduler.logger.lock() {
+            logger.info("定时任务", "定时任务调度器已启动");
+        }
+
+        scheduler
+    }
+
+    // 启动后台线程，定期检查到期的规则
+    fn spawn_worker(&self) {
+        let rules = Arc::clone(&self.rules);
+        let pending = Arc::clone(&self.pending);
+        std::thread::spawn(move || loop {
+            let now = Local::now();
+            let run_key = now.format("%Y-%m-%d %H:%M").to_string();
+            let weekday = now.weekday().num_days_from_monday() as usize;
+
+            if let Ok(mut rules) = rules.lock() {
+                for rule in rules.iter_mut() {
+                    let due = rule.enabled
+                        && rule.days[weekday]
+                        && rule.hour == now.hour()
+                        && rule.minute == now.minute()
+                        && rule.last_run.as_deref() != Some(run_key.as_str());
+                    if due {
+                        rule.last_run = Some(run_key.clone());
+                        if let Ok(mut pending) = pending.lock() {
+                            pending.push((rule.name.clone(), rule.action.clone()));
+                        }
+                    }
+                }
+            }
+
+            std::thread::sleep(TICK_INTERVAL);
+        });
+    }
+
+    // 取出所有待执行的动作（由主线程执行）
+    pub fn take_pending(&self) -> Vec<(String, ScheduledAction)> {
+        match self.pending.lock() {
+            Ok(mut pending) => pending.drain(..).collect(),
+            Err(_) => Vec::new(),
+        }
+    }
+
+    // 保存规则到配置文件
+    fn save_rules(&self) {
+        let result = match self.rules.lock() {
+            Ok(rules) => utils::get_config_path(SCHEDULER_CONFIG_FILE)
+                .and_then(|path| utils::save_versioned_config(&*rules, &path)),
+            Err(_) => return,
+        };
+        if let Err(e) = result {
+            if let Ok(mut logger) = self.logger.lock() {
+                logger.error("定时任务", &format!("保存定时任务失败: {}", e));
+            }
+        }
+    }
+
+    // 添加规则
+    fn add_rule(&mut self, rule: ScheduleRule) {
+        if let Ok(mut logger) = self.logger.lock() {
+            logger.info("定时任务", &format!("添加定时任务: {}", rule.name));
+        }
+        if let Ok(mut rules) = self.rules.lock() {
+            rules.push(rule);
+        }
+        self.next_rule_id += 1;
+        self.save_rules();
+    }
+
+    // 删除规则
+    fn remove_rule(&mut self, id: usize) {
+        let removed = match self.rules.lock() {
+            Ok(mut rules) => rules.iter().position(|r| r.id == id).map(|index| rules.remove(index)),
+            Err(_) => None,
+        };
+        if let Some(rule) = removed {
+            if let Ok(mut logger) = self.logger.lock() {
+                logger.info("定时任务", &format!("删除定时任务: {}", rule.name));
+            }
+            self.save_rules();
+        }
+    }
+
+    // 启用/禁用规则
+    fn toggle_rule(&mut self, id: usize) {
+        let rule_info = match self.rules.lock() {
+            Ok(mut rules) => rules.iter_mut().find(|r| r.id == id).map(|rule| {
+                rule.enabled = !rule.enabled;
+                (rule.name.clone(), rule.enabled)
+            }),
+            Err(_) => None,
+        };
+        if let Some((name, enabled)) = rule_info {
+            if let Ok(mut logger) = self.logger.lock() {
+                logger.info("定时任务", &format!("定时任务 '{}' 已{}", name, if enabled { "启用" } else { "禁用" }));
+            }
+            self.save_rules();
+        }
+    }
+
+    // 渲染UI
+    pub fn ui(&mut self, ui: &mut Ui) {
+        ui.label("按时间自动执行操作，例如每晚22:00启动Tor、周末切换到严格防火墙配置、每晚更新订阅。");
+
+        let rules_clone = self.rules.lock().map(|r| r.clone()).unwrap_or_default();
+
+        if rules_clone.is_empty() {
+            ui.label(RichText::new("暂无定时任务").color(Color32::GRAY));
+        } else {
+            Grid::new("scheduler_rules_grid")
+                .num_columns(5)
+                .striped(true)
+                .spacing([10.0, 4.0])
+                .show(ui, |ui| {
+                    // 表头
+                    ui.label(RichText::new("启用").strong());
+                    ui.label(RichText::new("名称").strong());
+                    ui.label(RichText::new("时间").strong());
+                    ui.label(RichText::new("动作").strong());
+                    ui.label(RichText::new("操作").strong());
+                    ui.end_row();
+
+                    for rule in &rules_clone {
+                        let mut enabled = rule.enabled;
+                        let rule_id = rule.id;
+                        if ui.checkbox(&mut enabled, "").changed() {
+                            self.toggle_rule(rule_id);
+                        }
+                        ui.label(&rule.name);
+                        ui.label(format!("{} {:02}:{:02}", rule.days_text(), rule.hour, rule.minute));
+                        ui.label(rule.action.label());
+                        if ui.button("删除").clicked() {
+                            self.remove_rule(rule_id);
+                        }
+                        ui.end_row();
+                    }
+                });
+        }
+
+        ui.separator();
+
+        // 新建规则表单
+        ui.horizontal(|ui| {
+            ui.label("名称:");
+            ui.text_edit_singleline(&mut self.new_rule_name);
+        });
+
+        ui.horizontal(|ui| {
+            ui.label("时间:");
+            ui.add(egui::DragValue::new(&mut self.new_rule_hour).clamp_range(0..=23).suffix(" 时"));
+            ui.add(egui::DragValue::new(&mut self.new_rule_minute).clamp_range(0..=59).suffix(" 分"));
+        });
+
+        ui.horizontal(|ui| {
+            ui.label("星期:");
+            for (index, name) in WEEKDAY_NAMES.iter().enumerate() {
+                ui.checkbox(&mut self.new_rule_days[index], *name);
+            }
+        });
+
+        ui.horizontal(|ui| {
+            ui.label("动作:");
+            egui::ComboBox::from_id_source("schedule_action_combo")
+                .selected_text(self.new_rule_action.label())
+                .show_ui(ui, |ui| {
+                    for action in ScheduledAction::all() {
+                        let label = action.label();
+                        ui.selectable_value(&mut self.new_rule_action, action, label);
+                    }
+                });
+        });
+
+        if ui.button("添加定时任务").clicked() {
+            if self.new_rule_name.is_empty() || !self.new_rule_days.iter().any(|d| *d) {
+                if let Ok(mut logger) = self.logger.lock() {
+                    logger.warning("定时任务", "请填写任务名称并至少选择一天");
+                }
+            } else {
+                let mut rule = ScheduleRule::new(
+                    self.next_rule_id,
+                    &self.new_rule_name,
+                    self.new_rule_hour,
+                    self.new_rule_minute,
+                    self.new_rule_action.clone()
+                );
+                rule.days = self.new_rule_days;
+                self.add_rule(rule);
+                self.new_rule_name.clear();
+            }
+        }
+    }
+}
@@ -0,0 +1,226 @@
+use std::collections::BTreeSet;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+use once_cell::sync::Lazy;
+
+use crate::logger::Logger;
+use crate::ports::{self, PortOwner, Protocol};
+use crate::supervisor::{ProcessSpec, ProcessState, ProcessStatus, SupervisedProcess};
+
+// 模拟模式：不启动任何外部进程，也不探测真实端口，便于在没有组件的环境中调试界面
+static SIMULATED: Lazy<bool> = Lazy::new(|| std::env::args().any(|arg| arg == "--simulate"));
+
+// 是否以模拟模式运行
+pub fn is_simulated() -> bool {
+    *SIMULATED
+}
+
+// 本次运行中启动过的外部程序（含由tor启动的传输插件），断线保护据此放行
+static LAUNCHED: Lazy<Mutex<BTreeSet<PathBuf>>> = Lazy::new(|| Mutex::new(BTreeSet::new()));
+
+// 记录即将启动的外部程序
+pub fn record_launched(program: &Path) {
+    if let Ok(mut launched) = LAUNCHED.lock() {
+        launched.insert(program.to_path_buf());
+    }
+}
+
+// 本次运行中启动过的所有外部程序
+pub fn launched_programs() -> Vec<PathBuf> {
+    LAUNCHED.lock().map(|launched| launched.iter().cloned().collect()).unwrap_or_default()
+}
+
+// 运行中的外部进程
+pub trait ManagedProcess: Send {
+    fn status(&self) -> ProcessStatus;
+    fn stop(&self);
+}
+
+// 外部进程启动器，模块通过它启动tor、dnscrypt-proxy等组件
+pub trait ProcessLauncher: Send + Sync {
+    fn launch(&self, spec: ProcessSpec) -> Box<dyn ManagedProcess>;
+}
+
+// 网络探测，模块通过它检查本机端口状态
+pub trait NetworkProbe: Send + Sync {
+    fn is_port_in_use(&self, host: &str, port: u16, protocol: Protocol) -> bool;
+    fn port_owner(&self, port: u16, protocol: Protocol) -> Option<PortOwner>;
+}
+
+// 真实的进程启动器，由进程监控负责重启
+pub struct SystemLauncher {
+    logger: Arc<Mutex<Logger>>,
+}
+
+impl SystemLauncher {
+    pub fn new(logger: Arc<Mutex<Logger>>) -> Self {
+        Self { logger }
+    }
+}
+
+impl ManagedProcess for SupervisedProcess {
+    fn status(&self) -> ProcessStatus {
+        SupervisedProcess::status(self)
+    }
+
+    fn stop(&self) {
+        SupervisedProcess::stop(self)
+    }
+}
+
+impl ProcessLauncher for SystemLauncher {
+    fn launch(&self, spec: ProcessSpec) -> Box<dyn ManagedProcess> {
+        record_launched(&spec.program);
+        Box::new(SupervisedProcess::spawn(spec, Arc::clone(&self.logger)))
+    }
+}
+
+// 真实的网络探测
+pub struct SystemNetwork;
+
+impl NetworkProbe for SystemNetwork {
+    fn is_port_in_use(&self, host: &str, port: u16, protocol: Protocol) -> bool {
+        !ports::is_port_free(host, port, protocol)
+    }
+
+    fn port_owner(&self, port: u16, protocol: Protocol) -> Option<PortOwner> {
+        ports::find_owner(port, protocol)
+    }
+}
+
+// 模拟实现：不产生任何副作用，也用于测试模块的启动与失败处理
+pub mod mock {
+    use super::*;
+
+    // 模拟进程，始终处于运行（或指定的失败）状态直到被停止
+    pub struct MockProcess {
+        name: String,
+        failure: Option<String>,
+        stopped: Mutex<bool>,
+    }
+
+    impl ManagedProcess for MockProcess {
+        fn status(&self) -> ProcessStatus {
+            let stopped = self.stopped.lock().map(|s| *s).unwrap_or(true);
+            let state = match (stopped, &self.failure) {
+                (true, _) => ProcessState::Stopped,
+                (false, Some(reason)) => ProcessState::Failed(reason.clone()),
+                (false, None) => ProcessState::Running,
+            };
+            ProcessStatus {
+                name: self.name.clone(),
+                state,
+                pid: None,
+                restarts: 0,
+                uptime: None,
+            }
+        }
+
+        fn stop(&self) {
+            if let Ok(mut stopped) = self.stopped.lock() {
+                *stopped = true;
+            }
+        }
+    }
+
+    // 模拟启动器，只在日志中记录启动命令
+    pub struct MockLauncher {
+        logger: Arc<Mutex<Logger>>,
+        failure: Option<String>, // 设置时启动的进程均处于失败状态
+    }
+
+    impl MockLauncher {
+        pub fn new(logger: Arc<Mutex<Logger>>) -> Self {
+            Self { logger, failure: None }
+        }
+
+        // 启动的进程都以reason失败，模拟组件无法运行的情况
+        #[cfg(test)]
+        pub fn failing(logger: Arc<Mutex<Logger>>, reason: &str) -> Self {
+            Self { failure: Some(reason.to_string()), ..Self::new(logger) }
+        }
+    }
+
+    impl ProcessLauncher for MockLauncher {
+        fn launch(&self, spec: ProcessSpec) -> Box<dyn ManagedProcess> {
+            if let Ok(mut logger) = self.logger.lock() {
+                logger.info(&spec.name, &format!("[模拟] {} {}", spec.program.display(), spec.args.join(" ")));
+            }
+            Box::new(MockProcess {
+                name: spec.name,
+                failure: self.failure.clone(),
+                stopped: Mutex::new(false),
+            })
+        }
+    }
+
+    // 模拟网络，除指定端口外均视为可用
+    #[derive(Default)]
+    pub struct MockNetwork {
+        busy_ports: Vec<u16>,
+    }
+
+    impl MockNetwork {
+        #[cfg(test)]
+        pub fn with_busy_ports(busy_ports: &[u16]) -> Self {
+            Self { busy_ports: busy_ports.to_vec() }
+        }
+    }
+
+    impl NetworkProbe for MockNetwork {
+        fn is_port_in_use(&self, _host: &str, port: u16, _protocol: Protocol) -> bool {
+            self.busy_ports.contains(&port)
+        }
+
+        fn port_owner(&self, _port: u16, _protocol: Protocol) -> Option<PortOwner> {
+            None
+        }
+    }
+}
+
+// 按运行模式创建进程启动器
+pub fn launcher(logger: Arc<Mutex<Logger>>) -> Arc<dyn ProcessLauncher> {
+    if is_simulated() {
+        Arc::new(mock::MockLauncher::new(logger))
+    } else {
+        Arc::new(SystemLauncher::new(logger))
+    }
+}
+
+// 按运行模式创建网络探测
+pub fn network() -> Arc<dyn NetworkProbe> {
+    if is_simulated() {
+        Arc::new(mock::MockNetwork::default())
+    } else {
+        Arc::new(SystemNetwork)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::net::{TcpListener, UdpSocket};
+
+    #[test]
+    fn system_network_detects_bound_ports() {
+        let network = SystemNetwork;
+        let tcp = TcpListener::bind("127.0.0.1:0").unwrap();
+        let tcp_port = tcp.local_addr().unwrap().port();
+        assert!(network.is_port_in_use("127.0.0.1", tcp_port, Protocol::Tcp));
+        drop(tcp);
+        assert!(!network.is_port_in_use("127.0.0.1", tcp_port, Protocol::Tcp));
+
+        let udp = UdpSocket::bind("127.0.0.1:0").unwrap();
+        let udp_port = udp.local_addr().unwrap().port();
+        assert!(network.is_port_in_use("127.0.0.1", udp_port, Protocol::Udp));
+    }
+
+    #[test]
+    fn launched_programs_are_recorded_once() {
+        let program = Path::new("C:\\InviZible\\test\\obfs4proxy.exe");
+        record_launched(program);
+        record_launched(program);
+        let launched = launched_programs();
+        assert_eq!(launched.iter().filter(|path| path.as_path() == program).count(), 1);
+    }
+}
@@ -0,0 +1,98 @@
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+use anyhow::{anyhow, Result};
+
+use crate::components::{self, Executable};
+use crate::logger::Logger;
+use crate::services::{ManagedProcess, ProcessLauncher};
+use crate::supervisor::{ProcessSpec, ProcessState};
+
+// 统计日志的输出间隔
+const SUMMARY_INTERVAL: &str = "1m";
+
+// Snowflake代理程序不在Tor专家包中，作为单独的组件安装
+pub fn proxy_exe() -> Option<PathBuf> {
+    components::executable_path(Executable::SnowflakeProxy)
+}
+
+// 解析统计日志中的连接数，如"In the last 1m0s, there were 3 completed connections. Traffic Relayed ↓ 12 KB, ↑ 4 KB."
+fn parse_summary(line: &str) -> Option<u64> {
+    let (_, rest) = line.split_once("there were ")?;
+    rest.split_whitespace().next()?.parse().ok()
+}
+
+// 运行中的Snowflake代理，为受审查的用户转发流量
+pub struct SnowflakeProxy {
+    process: Box<dyn ManagedProcess>,
+    helped: Arc<AtomicU64>, // 尚未计入总数的已帮助用户数
+}
+
+impl SnowflakeProxy {
+    // capacity为同时服务的用户数上限，0表示不限制
+    pub fn start(launcher: &dyn ProcessLauncher, logger: Arc<Mutex<Logger>>, capacity: u32) -> Result<Self> {
+        let exe = proxy_exe().ok_or_else(|| anyhow!("未安装Snowflake代理，请在设置的组件管理中安装"))?;
+        let helped = Arc::new(AtomicU64::new(0));
+
+        let handler_helped = Arc::clone(&helped);
+        let handler_logger = Arc::clone(&logger);
+        let on_output = move |line: &str| {
+            let line = line.trim();
+            if line.is_empty() {
+                return;
+            }
+            match parse_summary(line) {
+                // 没有用户的统计不写入日志
+                Some(0) => {}
+                Some(count) => {
+                    handler_helped.fetch_add(count, Ordering::SeqCst);
+                    if let Ok(mut logger) = handler_logger.lock() {
+                        logger.info("Snowflake", &format!("最近1分钟帮助了 {} 位用户", count));
+                    }
+                }
+                None => {
+                    if let Ok(mut logger) = handler_logger.lock() {
+                        logger.info("Snowflake", line);
+                    }
+                }
+            }
+        };
+
+        let mut spec = ProcessSpec::new("Snowflake", &exe);
+        spec.args = vec![
+            "-capacity".to_string(),
+            capacity.to_string(),
+            "-summary-interval".to_string(),
+            SUMMARY_INTERVAL.to_string(),
+        ];
+        spec.working_dir = exe.parent().map(|p| p.to_path_buf());
+        spec.on_output = Some(Arc::new(on_output));
+
+        if let Ok(mut logger) = logger.lock() {
+            logger.info("Snowflake", &format!("正在启动Snowflake代理（用户上限 {}）", capacity));
+        }
+        Ok(Self {
+            process: launcher.launch(spec),
+            helped,
+        })
+    }
+
+    // 取出上次调用后新帮助的用户数
+    pub fn take_helped(&self) -> u64 {
+        self.helped.swap(0, Ordering::SeqCst)
+    }
+
+    pub fn state(&self) -> ProcessState {
+        self.process.status().state
+    }
+
+    pub fn stop(&self) {
+        self.process.stop();
+    }
+}
+
+impl Drop for SnowflakeProxy {
+    fn drop(&mut self) {
+        self.stop();
+    }
+}
@@ -0,0 +1,548 @@
+use eframe::egui::{Color32, Grid, RichText, Ui};
+use std::collections::VecDeque;
+use std::io::{BufRead, BufReader, Read};
+use std::net::{SocketAddr, TcpStream};
+use std::path::PathBuf;
+use std::process::{Child, Command, Stdio};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex, Weak};
+use std::time::{Duration, Instant};
+use chrono::{DateTime, Local};
+use once_cell::sync::Lazy;
+
+use crate::logger::Logger;
+
+// 进程状态检查间隔
+const CHECK_INTERVAL: Duration = Duration::from_secs(1);
+
+// 端口存活检测间隔
+const LIVENESS_INTERVAL: Duration = Duration::from_secs(5);
+
+// 启动后多久开始检测端口（给进程留出初始化时间）
+const STARTUP_GRACE: Duration = Duration::from_secs(30);
+
+// 连续多少次端口检测失败后判定为无响应
+const LIVENESS_FAILURES: u32 = 3;
+
+// 稳定运行超过该时间后重置重启计数
+const STABLE_AFTER: Duration = Duration::from_secs(300);
+
+// 保留的事件数量
+const MAX_EVENTS: usize = 100;
+
+// 子进程输出的处理函数（每行调用一次）
+pub type OutputHandler = Arc<dyn Fn(&str) + Send + Sync>;
+
+// 重启策略
+#[derive(Clone, Debug)]
+pub struct RestartPolicy {
+    pub max_restarts: Option<u32>,  // 连续重启次数上限，None表示不限
+    pub initial_backoff: Duration,
+    pub max_backoff: Duration,
+    pub restart_on_clean_exit: bool, // 退出码为0时是否也重启
+}
+
+impl Default for RestartPolicy {
+    fn default() -> Self {
+        Self {
+            max_restarts: Some(5),
+            initial_backoff: Duration::from_secs(1),
+            max_backoff: Duration::from_secs(60),
+            restart_on_clean_exit: false,
+        }
+    }
+}
+
+impl RestartPolicy {
+    // 第attempt次重启前的等待时间（指数退避）
+    fn backoff(&self, attempt: u32) -> Duration {
+        let factor = 2u32.saturating_pow(attempt.saturating_sub(1));
+        self.initial_backoff.saturating_mul(factor).min(self.max_backoff)
+    }
+}
+
+// 受监控进程的启动参数
+#[derive(Clone)]
+pub struct ProcessSpec {
+    pub name: String,
+    pub program: PathBuf,
+    pub args: Vec<String>,
+    pub working_dir: Option<PathBuf>,
+    pub liveness_port: Option<u16>,          // 进程应监听的本地端口
+    pub heartbeat_timeout: Option<Duration>, // 超过该时间未收到心跳视为卡死
+    pub policy: RestartPolicy,
+    pub on_output: Option<OutputHandler>,
+}
+
+impl ProcessSpec {
+    pub fn new(name: &str, program: impl Into<PathBuf>) -> Self {
+        Self {
+            name: name.to_string(),
+            program: program.into(),
+            args: Vec::new(),
+            working_dir: None,
+            liveness_port: None,
+            heartbeat_timeout: None,
+            policy: RestartPolicy::default(),
+            on_output: None,
+        }
+    }
+}
+
+// 进程状态
+#[derive(Clone, Debug, PartialEq)]
+pub enum ProcessState {
+    Starting,
+    Running,
+    Degraded(String),                          // 进程仍在运行但检测异常
+    Restarting { attempt: u32, delay: Duration },
+    Stopped,
+    Failed(String),                            // 已放弃重启
+}
+
+impl ProcessState {
+    pub fn label(&self) -> String {
+        match self {
+            ProcessState::Starting => "启动中".to_string(),
+            ProcessState::Running => "运行中".to_string(),
+            ProcessState::Degraded(reason) => format!("异常: {}", reason),
+            ProcessState::Restarting { attempt, delay } => format!("{}秒后第{}次重启", delay.as_secs(), attempt),
+            ProcessState::Stopped => "已停止".to_string(),
+            ProcessState::Failed(reason) => format!("失败: {}", reason),
+        }
+    }
+
+    fn color(&self) -> Color32 {
+        match self {
+            ProcessState::Running => Color32::GREEN,
+            ProcessState::Starting | ProcessState::Restarting { .. } => Color32::YELLOW,
+            ProcessState::Degraded(_) => Color32::from_rgb(255, 165, 0),
+            ProcessState::Stopped => Color32::GRAY,
+            ProcessState::Failed(_) => Color32::RED,
+        }
+    }
+}
+
+// 监控事件
+#[derive(Clone, Debug)]
+pub enum SupervisorEventKind {
+    Started { pid: u32 },
+    Exited { code: Option<i32> },
+    Unresponsive(String),
+    Recovered,
+    Restarting { attempt: u32, delay: Duration },
+    GaveUp(String),
+}
+
+#[derive(Clone, Debug)]
+pub struct SupervisorEvent {
+    pub process: String,
+    pub kind: SupervisorEventKind,
+    pub time: DateTime<Local>,
+}
+
+// 进程状态快照
+#[derive(Clone, Debug)]
+pub struct ProcessStatus {
+    pub name: String,
+    pub state: ProcessState,
+    pub pid: Option<u32>,
+    pub restarts: u32,
+    pub uptime: Option<Duration>,
+}
+
+// 监控线程与句柄共享的数据
+struct Shared {
+    spec: ProcessSpec,
+    logger: Arc<Mutex<Logger>>,
+    state: Mutex<ProcessState>,
+    pid: Mutex<Option<u32>>,
+    started_at: Mutex<Option<Instant>>,
+    restarts: Mutex<u32>,
+    last_heartbeat: Mutex<Instant>,
+    stop_requested: AtomicBool,
+    restart_requested: AtomicBool,
+}
+
+impl Shared {
+    fn status(&self) -> ProcessStatus {
+        ProcessStatus {
+            name: self.spec.name.clone(),
+            state: self.state.lock().map(|s| s.clone()).unwrap_or(ProcessState::Stopped),
+            pid: self.pid.lock().ok().and_then(|p| *p),
+            restarts: self.restarts.lock().map(|r| *r).unwrap_or(0),
+            uptime: self.started_at.lock().ok().and_then(|t| *t).map(|t| t.elapsed()),
+        }
+    }
+
+    fn set_state(&self, state: ProcessState) {
+        if let Ok(mut current) = self.state.lock() {
+            *current = state;
+        }
+    }
+
+    fn emit(&self, kind: SupervisorEventKind) {
+        let name = &self.spec.name;
+        if let Ok(mut logger) = self.logger.lock() {
+            match &kind {
+                SupervisorEventKind::Started { pid } => logger.info(name, &format!("进程已启动 (PID {})", pid)),
+                SupervisorEventKind::Exited { code: Some(code) } => logger.warning(name, &format!("进程已退出，退出码 {}", code)),
+                SupervisorEventKind::Exited { code: None } => logger.warning(name, "进程已被终止"),
+                SupervisorEventKind::Unresponsive(reason) => logger.warning(name, &format!("进程无响应: {}", reason)),
+                SupervisorEventKind::Recovered => logger.info(name, "进程已恢复正常"),
+                SupervisorEventKind::Restarting { attempt, delay } => {
+                    logger.warning(name, &format!("将在 {} 秒后进行第 {} 次重启", delay.as_secs(), attempt))
+                }
+                SupervisorEventKind::GaveUp(reason) => logger.error(name, &format!("已停止自动重启: {}", reason)),
+            }
+        }
+
+        if let Ok(mut events) = EVENTS.lock() {
+            events.push_back(SupervisorEvent {
+                process: name.clone(),
+                kind,
+                time: Local::now(),
+            });
+            while events.len() > MAX_EVENTS {
+                events.pop_front();
+            }
+        }
+    }
+
+    // 在等待期间响应停止请求，返回false表示已请求停止
+    fn sleep(&self, duration: Duration) -> bool {
+        let deadline = Instant::now() + duration;
+        while Instant::now() < deadline {
+            if self.stop_requested.load(Ordering::SeqCst) {
+                return false;
+            }
+            std::thread::sleep(CHECK_INTERVAL.min(deadline.saturating_duration_since(Instant::now())));
+        }
+        !self.stop_requested.load(Ordering::SeqCst)
+    }
+}
+
+// 请求重启；监控线程已结束（放弃重启或正常退出）时重新启动监控
+fn request_restart(shared: &Arc<Shared>) {
+    let finished = shared.state.lock()
+        .map(|s| matches!(*s, ProcessState::Failed(_) | ProcessState::Stopped))
+        .unwrap_or(false);
+    if finished && !shared.stop_requested.load(Ordering::SeqCst) {
+        if let Ok(mut restarts) = shared.restarts.lock() {
+            *restarts = 0;
+        }
+        shared.set_state(ProcessState::Starting);
+        let worker = Arc::clone(shared);
+        std::thread::spawn(move || supervise(worker));
+    } else {
+        shared.restart_requested.store(true, Ordering::SeqCst);
+    }
+}
+
+// 所有受监控进程（用于设置页面板）
+static PROCESSES: Lazy<Mutex<Vec<Weak<Shared>>>> = Lazy::new(|| Mutex::new(Vec::new()));
+
+// 尚未被消费的事件
+static EVENTS: Lazy<Mutex<VecDeque<SupervisorEvent>>> = Lazy::new(|| Mutex::new(VecDeque::new()));
+
+// 取出所有未处理的事件
+pub fn take_events() -> Vec<SupervisorEvent> {
+    EVENTS.lock().map(|mut e| e.drain(..).collect()).unwrap_or_default()
+}
+
+// 受监控进程的句柄，释放时停止进程
+pub struct SupervisedProcess {
+    shared: Arc<Shared>,
+}
+
+impl SupervisedProcess {
+    // 启动进程并开始监控
+    pub fn spawn(spec: ProcessSpec, logger: Arc<Mutex<Logger>>) -> Self {
+        let shared = Arc::new(Shared {
+            spec,
+            logger,
+            state: Mutex::new(ProcessState::Starting),
+            pid: Mutex::new(None),
+            started_at: Mutex::new(None),
+            restarts: Mutex::new(0),
+            last_heartbeat: Mutex::new(Instant::now()),
+            stop_requested: AtomicBool::new(false),
+            restart_requested: AtomicBool::new(false),
+        });
+        if let Ok(mut processes) = PROCESSES.lock() {
+            processes.retain(|p| p.strong_count() > 0);
+            processes.push(Arc::downgrade(&shared));
+        }
+
+        let worker = Arc::clone(&shared);
+        std::thread::spawn(move || supervise(worker));
+        Self { shared }
+    }
+
+    pub fn status(&self) -> ProcessStatus {
+        self.shared.status()
+    }
+
+    // 报告进程仍然正常工作（如控制端口收到响应）
+    pub fn heartbeat(&self) {
+        if let Ok(mut last) = self.shared.last_heartbeat.lock() {
+            *last = Instant::now();
+        }
+    }
+
+    // 立即重启进程（不计入重启次数）
+    pub fn restart(&self) {
+        request_restart(&self.shared);
+    }
+
+    // 停止进程，不再自动重启
+    pub fn stop(&self) {
+        self.shared.stop_requested.store(true, Ordering::SeqCst);
+    }
+}
+
+impl Drop for SupervisedProcess {
+    fn drop(&mut self) {
+        self.stop();
+    }
+}
+
+// 启动子进程并转发其输出
+fn launch(shared: &Shared) -> std::io::Result<Child> {
+    let spec = &shared.spec;
+    let mut command = Command::new(&spec.program);
+    command.args(&spec.args).stdin(Stdio::null());
+    if let Some(dir) = &spec.working_dir {
+        command.current_dir(dir);
+    }
+    if spec.on_output.is_some() {
+        command.stdout(Stdio::piped()).stderr(Stdio::piped());
+    } else {
+        command.stdout(Stdio::null()).stderr(Stdio::null());
+    }
+
+    // 不为子进程创建控制台窗口
+    #[cfg(target_os = "windows")]
+    {
+        use std::os::windows::process::CommandExt;
+        const CREATE_NO_WINDOW: u32 = 0x0800_0000;
+        command.creation_flags(CREATE_NO_WINDOW);
+    }
+
+    let mut child = command.spawn()?;
+    if let Some(handler) = &spec.on_output {
+        let stdout = child.stdout.take().map(|s| Box::new(s) as Box<dyn Read + Send>);
+        let stderr = child.stderr.take().map(|s| Box::new(s) as Box<dyn Read + Send>);
+        for stream in [stdout, stderr].into_iter().flatten() {
+            let handler = Arc::clone(handler);
+            std::thread::spawn(move || {
+                for line in BufReader::new(stream).lines().map_while(Result::ok) {
+                    handler(&line);
+                }
+            });
+        }
+    }
+    Ok(child)
+}
+
+// 进程结束的原因
+enum Outcome {
+    Exited(Option<i32>),
+    Unresponsive(String),
+    RestartRequested,
+    StopRequested,
+}
+
+// 监控一次运行中的进程，直到其退出或需要重启
+fn watch(shared: &Shared, child: &mut Child) -> Outcome {
+    let spec = &shared.spec;
+    let started = Instant::now();
+    let mut last_liveness = Instant::now();
+    let mut liveness_failures = 0;
+
+    loop {
+        std::thread::sleep(CHECK_INTERVAL);
+
+        if shared.stop_requested.load(Ordering::SeqCst) {
+            return Outcome::StopRequested;
+        }
+        if shared.restart_requested.swap(false, Ordering::SeqCst) {
+            return Outcome::RestartRequested;
+        }
+        match child.try_wait() {
+            Ok(Some(status)) => return Outcome::Exited(status.code()),
+            Ok(None) => {}
+            Err(e) => return Outcome::Unresponsive(format!("无法查询进程状态: {}", e)),
+        }
+
+        // 心跳检测
+        if let Some(timeout) = spec.heartbeat_timeout {
+            let silent = shared.last_heartbeat.lock().map(|t| t.elapsed()).unwrap_or_default();
+            if silent > timeout {
+                return Outcome::Unresponsive(format!("{} 秒未收到心跳", silent.as_secs()));
+            }
+        }
+
+        // 端口存活检测
+        if let Some(port) = spec.liveness_port {
+            if started.elapsed() >= STARTUP_GRACE && last_liveness.elapsed() >= LIVENESS_INTERVAL {
+                last_liveness = Instant::now();
+                let addr = SocketAddr::from(([127, 0, 0, 1], port));
+                if TcpStream::connect_timeout(&addr, Duration::from_secs(1)).is_ok() {
+                    if liveness_failures > 0 {
+                        shared.set_state(ProcessState::Running);
+                        shared.emit(SupervisorEventKind::Recovered);
+                    }
+                    liveness_failures = 0;
+                } else {
+                    liveness_failures += 1;
+                    if liveness_failures >= LIVENESS_FAILURES {
+                        return Outcome::Unresponsive(format!("端口 {} 无法连接", port));
+                    }
+                    shared.set_state(ProcessState::Degraded(format!("端口 {} 无法连接", port)));
+                }
+            }
+        }
+
+        // 稳定运行一段时间后重置重启计数
+        if started.elapsed() >= STABLE_AFTER {
+            if let Ok(mut restarts) = shared.restarts.lock() {
+                *restarts = 0;
+            }
+        }
+    }
+}
+
+// 监控线程：启动进程、检测异常并按策略重启
+fn supervise(shared: Arc<Shared>) {
+    let spec = shared.spec.clone();
+    loop {
+        shared.set_state(ProcessState::Starting);
+        if let Ok(mut last) = shared.last_heartbeat.lock() {
+            *last = Instant::now();
+        }
+
+        let outcome = match launch(&shared) {
+            Ok(mut child) => {
+                let pid = child.id();
+                if let Ok(mut current) = shared.pid.lock() {
+                    *current = Some(pid);
+                }
+                if let Ok(mut started_at) = shared.started_at.lock() {
+                    *started_at = Some(Instant::now());
+                }
+                shared.set_state(ProcessState::Running);
+                shared.emit(SupervisorEventKind::Started { pid });
+
+                let outcome = watch(&shared, &mut child);
+                if !matches!(outcome, Outcome::Exited(_)) {
+                    let _ = child.kill();
+                }
+                let _ = child.wait();
+
+                if let Ok(mut current) = shared.pid.lock() {
+                    *current = None;
+                }
+                if let Ok(mut started_at) = shared.started_at.lock() {
+                    *started_at = None;
+                }
+                outcome
+            }
+            Err(e) => {
+                if let Ok(mut logger) = shared.logger.lock() {
+                    logger.error(&spec.name, &format!("无法启动 {}: {}", spec.program.display(), e));
+                }
+                Outcome::Exited(None)
+            }
+        };
+
+        let reason = match outcome {
+            Outcome::StopRequested => break,
+            Outcome::RestartRequested => {
+                if let Ok(mut logger) = shared.logger.lock() {
+                    logger.info(&spec.name, "正在手动重启进程");
+                }
+                continue;
+            }
+            Outcome::Exited(Some(0)) if !spec.policy.restart_on_clean_exit => {
+                shared.emit(SupervisorEventKind::Exited { code: Some(0) });
+                break;
+            }
+            Outcome::Exited(code) => {
+                shared.emit(SupervisorEventKind::Exited { code });
+                match code {
+                    Some(code) => format!("退出码 {}", code),
+                    None => "进程异常终止".to_string(),
+                }
+            }
+            Outcome::Unresponsive(reason) => {
+                shared.emit(SupervisorEventKind::Unresponsive(reason.clone()));
+                reason
+            }
+        };
+
+        // 按退避策略重启
+        let attempt = match shared.restarts.lock() {
+            Ok(mut restarts) => {
+                *restarts += 1;
+                *restarts
+            }
+            Err(_) => 1,
+        };
+        if spec.policy.max_restarts.map_or(false, |max| attempt > max) {
+            let reason = format!("连续重启 {} 次仍失败（{}）", attempt - 1, reason);
+            shared.set_state(ProcessState::Failed(reason.clone()));
+            shared.emit(SupervisorEventKind::GaveUp(reason));
+            return;
+        }
+        let delay = spec.policy.backoff(attempt);
+        shared.set_state(ProcessState::Restarting { attempt, delay });
+        shared.emit(SupervisorEventKind::Restarting { attempt, delay });
+        if !shared.sleep(delay) {
+            break;
+        }
+    }
+    shared.set_state(ProcessState::Stopped);
+}
+
+// 设置页中的进程监控面板
+pub fn ui(ui: &mut Ui) {
+    let processes: Vec<Arc<Shared>> = PROCESSES
+        .lock()
+        .map(|p| p.iter().filter_map(|p| p.upgrade()).collect())
+        .unwrap_or_default();
+    if processes.is_empty() {
+        ui.label(RichText::new("当前没有受监控的进程").color(Color32::GRAY));
+        return;
+    }
+
+    Grid::new("supervisor_grid")
+        .num_columns(6)
+        .striped(true)
+        .spacing([10.0, 4.0])
+        .show(ui, |ui| {
+            ui.label(RichText::new("进程").strong());
+            ui.label(RichText::new("状态").strong());
+            ui.label(RichText::new("PID").strong());
+            ui.label(RichText::new("运行时间").strong());
+            ui.label(RichText::new("重启次数").strong());
+            ui.label("");
+            ui.end_row();
+
+            for process in processes {
+                let status = process.status();
+                ui.label(&status.name);
+                ui.label(RichText::new(status.state.label()).color(status.state.color()));
+                ui.label(status.pid.map_or("-".to_string(), |pid| pid.to_string()));
+                ui.label(status.uptime.map_or("-".to_string(), |t| {
+                    let secs = t.as_secs();
+                    format!("{:02}:{:02}:{:02}", secs / 3600, secs / 60 % 60, secs % 60)
+                }));
+                ui.label(status.restarts.to_string());
+                if ui.small_button("重启").clicked() {
+                    request_restart(&process);
+                }
+                ui.end_row();
+            }
+        });
+}
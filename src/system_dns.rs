@@ -0,0 +1,200 @@
+use std::fs;
+use std::net::IpAddr;
+use std::path::Path;
+use std::process::Command;
+use std::sync::{Arc, Mutex};
+use anyhow::{anyhow, Context, Result};
+use serde::{Deserialize, Serialize};
+
+use crate::logger::Logger;
+use crate::netif::{self, AdapterKind};
+use crate::utils;
+
+// 修改前的系统DNS备份，文件存在表示系统DNS当前指向本机
+const BACKUP_FILE: &str = "system_dns_backup.json";
+// 旧版本由Tor模块创建的备份文件
+const LEGACY_BACKUP_FILE: &str = "tor_dns_backup.json";
+
+// 接管系统DNS时设置的服务器。IPv6也必须指向本机，否则系统仍会通过网卡原有的IPv6 DNS明文解析；
+// Tor和DNSCrypt只监听127.0.0.1，发往::1的查询被拒绝后系统会改用127.0.0.1
+const LOCAL_SERVERS: [&str; 2] = ["127.0.0.1", "::1"];
+
+// 一个网卡原先的DNS设置
+#[derive(Clone, Debug, Serialize, Deserialize)]
+struct AdapterDns {
+    index: u32,
+    name: String,
+    servers: Vec<String>, // 手动设置的IPv4 DNS服务器，为空表示由DHCP分配
+    #[serde(default)]
+    servers_v6: Vec<String>, // 手动设置的IPv6 DNS服务器，为空表示自动获取
+}
+
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+struct DnsBackup {
+    adapters: Vec<AdapterDns>,
+}
+
+impl utils::VersionedConfig for DnsBackup {
+    const VERSION: u32 = 1;
+}
+
+fn backup_path() -> Result<String> {
+    let path = utils::get_config_path(BACKUP_FILE)?;
+    // 沿用旧版本留下的备份，否则升级后无法恢复原设置
+    if !Path::new(&path).exists() {
+        let legacy = utils::get_config_path(LEGACY_BACKUP_FILE)?;
+        if Path::new(&legacy).exists() {
+            fs::rename(&legacy, &path).context("Failed to migrate DNS backup")?;
+        }
+    }
+    Ok(path)
+}
+
+// 系统DNS是否已指向本机
+pub fn is_redirected() -> bool {
+    backup_path().map(|path| Path::new(&path).exists()).unwrap_or(false)
+}
+
+fn powershell(script: &str) -> Result<String> {
+    let mut command = Command::new("powershell");
+    command.args(["-NoProfile", "-Command", script]);
+    #[cfg(target_os = "windows")]
+    {
+        use std::os::windows::process::CommandExt;
+        const CREATE_NO_WINDOW: u32 = 0x08000000;
+        command.creation_flags(CREATE_NO_WINDOW);
+    }
+    let output = command.output().context("Failed to run powershell")?;
+    if !output.status.success() {
+        return Err(anyhow!("{}", String::from_utf8_lossy(&output.stderr).trim()));
+    }
+    Ok(String::from_utf8_lossy(&output.stdout).trim().to_string())
+}
+
+// 网卡手动设置的DNS服务器（注册表NameServer），DHCP分配的不包括在内。
+// service为Tcpip（IPv4）或Tcpip6（IPv6）
+fn static_dns_servers(index: u32, service: &str) -> Result<Vec<String>> {
+    let script = format!(
+        "$a = Get-NetAdapter -InterfaceIndex {}; (Get-ItemProperty \"HKLM:\\SYSTEM\\CurrentControlSet\\Services\\{}\\Parameters\\Interfaces\\$($a.InterfaceGuid)\" -ErrorAction SilentlyContinue).NameServer",
+        index, service
+    );
+    let output = powershell(&script)?;
+    Ok(output
+        .split([',', ' '])
+        .map(str::trim)
+        .filter(|server| server.parse::<IpAddr>().is_ok())
+        .map(str::to_string)
+        .collect())
+}
+
+// 设置网卡的DNS服务器。先恢复为自动获取，再设置手动指定的服务器，
+// servers中没有出现的地址族（IPv4/IPv6）保持自动获取
+fn set_dns_servers<S: AsRef<str>>(index: u32, servers: &[S]) -> Result<()> {
+    let mut script = format!("Set-DnsClientServerAddress -InterfaceIndex {} -ResetServerAddresses", index);
+    if !servers.is_empty() {
+        let list: Vec<String> = servers.iter().map(|s| format!("'{}'", s.as_ref())).collect();
+        script.push_str(&format!("; Set-DnsClientServerAddress -InterfaceIndex {} -ServerAddresses ({})", index, list.join(",")));
+    }
+    powershell(&script).map(|_| ())
+}
+
+// 将已联网的物理网卡的DNS指向本机（Tor DNSPort或DNSCrypt，需要监听127.0.0.1:53），返回修改的网卡数
+fn redirect() -> Result<usize> {
+    if is_redirected() {
+        return Ok(0);
+    }
+    let adapters: Vec<_> = netif::enumerate_adapters()
+        .into_iter()
+        .filter(|a| a.is_up && a.kind != AdapterKind::Tunnel && !a.gateways.is_empty())
+        .collect();
+    if adapters.is_empty() {
+        return Err(anyhow!("No connected network adapter found"));
+    }
+
+    // 先保存备份再修改，程序中途退出时下次启动可以恢复
+    let mut backup = DnsBackup::default();
+    for adapter in &adapters {
+        backup.adapters.push(AdapterDns {
+            index: adapter.index,
+            name: adapter.name.clone(),
+            servers: static_dns_servers(adapter.index, "Tcpip")
+                .with_context(|| format!("Failed to read DNS settings of {}", adapter.name))?,
+            servers_v6: static_dns_servers(adapter.index, "Tcpip6")
+                .with_context(|| format!("Failed to read IPv6 DNS settings of {}", adapter.name))?,
+        });
+    }
+    utils::save_versioned_config(&backup, &backup_path()?)?;
+
+    for adapter in &backup.adapters {
+        set_dns_servers(adapter.index, &LOCAL_SERVERS)
+            .with_context(|| format!("Failed to set DNS of {}", adapter.name))?;
+    }
+    Ok(backup.adapters.len())
+}
+
+// 恢复修改前的系统DNS设置
+fn restore() -> Result<()> {
+    let path = backup_path()?;
+    if !Path::new(&path).exists() {
+        return Ok(());
+    }
+    let backup: DnsBackup = utils::load_versioned_config(&path)?;
+    let mut errors = Vec::new();
+    for adapter in &backup.adapters {
+        let servers: Vec<&String> = adapter.servers.iter().chain(&adapter.servers_v6).collect();
+        if let Err(e) = set_dns_servers(adapter.index, &servers) {
+            errors.push(format!("{}: {:#}", adapter.name, e));
+        }
+    }
+    if !errors.is_empty() {
+        return Err(anyhow!("Failed to restore DNS settings ({})", errors.join("; ")));
+    }
+    fs::remove_file(&path).context("Failed to remove DNS backup")
+}
+
+// 系统DNS由Tor（DNSPort）和DNSCrypt共用：两者都监听127.0.0.1:53，使用同一份备份，
+// 同一时间只能有一个接管系统DNS
+pub struct SystemDns {
+    logger: Arc<Mutex<Logger>>,
+    target: Option<Option<&'static str>>, // 最近一次要求的接管者，避免失败后反复重试
+}
+
+impl SystemDns {
+    pub fn new(logger: Arc<Mutex<Logger>>) -> Self {
+        Self { logger, target: None }
+    }
+
+    // 根据接管者（"Tor"或"DNSCrypt"）将系统DNS指向本机，owner为None时恢复原设置
+    pub fn sync(&mut self, owner: Option<&'static str>) {
+        if self.target == Some(owner) {
+            return;
+        }
+        self.target = Some(owner);
+        if owner.is_some() == is_redirected() {
+            return;
+        }
+
+        let logger = Arc::clone(&self.logger);
+        std::thread::spawn(move || {
+            let result = match owner {
+                Some(owner) => redirect().map(|count| format!("已将 {} 个网卡的DNS指向{}", count, owner)),
+                None => restore().map(|_| "已恢复系统DNS设置".to_string()),
+            };
+            if let Ok(mut logger) = logger.lock() {
+                match result {
+                    Ok(message) => logger.info("系统DNS", &message),
+                    Err(e) => logger.error("系统DNS", &format!("修改系统DNS失败: {:#}", e)),
+                }
+            }
+        });
+    }
+
+    // 程序退出前恢复系统DNS（会阻塞调用线程）
+    pub fn restore(&self) {
+        if let Err(e) = restore() {
+            if let Ok(mut logger) = self.logger.lock() {
+                logger.error("系统DNS", &format!("恢复系统DNS失败: {:#}", e));
+            }
+        }
+    }
+}
@@ -1,11 +1,30 @@
 use eframe::egui::{self, Color32, RichText, Ui, Grid, ScrollArea};
+use std::fs;
+use std::path::{Path, PathBuf};
 use std::sync::{Arc, Mutex};
+use anyhow::Context;
 use serde::{Deserialize, Serialize};
-use std::process::Child;
-use torut::onion::TorClient;
-use tokio::runtime::Runtime;
 
+use crate::bridgedb::BridgeRequest;
+use crate::browser;
 use crate::logger::Logger;
+use crate::onionoo::RelaySearch;
+use crate::ports::{self, Protocol};
+use crate::proxy::TorUpstream;
+use crate::services::{self, ProcessLauncher};
+use crate::snowflake_proxy::{self, SnowflakeProxy};
+use crate::tor_accounting::{self, AccountingViewer};
+use crate::tor_bandwidth;
+use crate::tor_check::TorCheck;
+use crate::tor_circuits::CircuitViewer;
+use crate::supervisor::ProcessState;
+use crate::tor_config::{self, ConnectionPadding, ControlAuth, ExitRule, MapAddressRule, TorConfig};
+use crate::tor_control::ControlConnection;
+use crate::system_dns;
+use crate::tor_guards::GuardViewer;
+use crate::tor_process::{self, BootstrapState, TorProcess, TorSettings};
+use crate::transports::{self, PluggableTransport, TransportStatus};
+use crate::utils;
 use crate::app::TOR_COLOR;
 
 // Tor网桥类型
@@ -39,6 +58,116 @@ impl TorBridge {
     }
 }
 
+impl BridgeType {
+    pub fn label(&self) -> &'static str {
+        match self {
+            BridgeType::Vanilla => "Vanilla",
+            BridgeType::Obfs4 => "Obfs4",
+            BridgeType::Snowflake => "Snowflake",
+            BridgeType::Meek => "Meek",
+        }
+    }
+}
+
+// 滑块拖动结束或通过键盘修改后才应用，避免拖动过程中反复发送SETCONF
+fn slider_committed(response: &egui::Response) -> bool {
+    response.drag_released() || (response.changed() && !response.dragged())
+}
+
+// 解析一行网桥配置，如"obfs4 192.0.2.2:443 <指纹> cert=... iat-mode=0"，可带"Bridge "前缀
+pub fn parse_bridge_line(line: &str) -> Result<(BridgeType, String), String> {
+    let line = line.trim();
+    let line = line.strip_prefix("Bridge ").unwrap_or(line).trim();
+    let tokens: Vec<&str> = line.split_whitespace().collect();
+    let first = *tokens.first().ok_or("空行")?;
+
+    let (bridge_type, rest) = match first {
+        "obfs4" => (BridgeType::Obfs4, &tokens[1..]),
+        "snowflake" => (BridgeType::Snowflake, &tokens[1..]),
+        "meek" | "meek_lite" => (BridgeType::Meek, &tokens[1..]),
+        _ if first.contains(':') => (BridgeType::Vanilla, &tokens[..]),
+        other => return Err(format!("不支持的传输类型 \"{}\"", other)),
+    };
+
+    let address = *rest.first().ok_or("缺少地址")?;
+    address
+        .parse::<std::net::SocketAddr>()
+        .map_err(|_| format!("地址格式无效 \"{}\"", address))?;
+
+    // 指纹可省略，提供时必须为40位十六进制
+    let mut arguments = &rest[1..];
+    if let Some(fingerprint) = arguments.first().filter(|t| !t.contains('=')) {
+        if fingerprint.len() != 40 || !fingerprint.chars().all(|c| c.is_ascii_hexdigit()) {
+            return Err(format!("指纹格式无效 \"{}\"", fingerprint));
+        }
+        arguments = &arguments[1..];
+    }
+    if let Some(argument) = arguments.iter().find(|t| !t.contains('=')) {
+        return Err(format!("无法识别的参数 \"{}\"", argument));
+    }
+    let has = |key: &str| arguments.iter().any(|t| t.starts_with(&format!("{}=", key)));
+    match bridge_type {
+        BridgeType::Obfs4 if !has("cert") || !has("iat-mode") => return Err("obfs4网桥缺少cert或iat-mode参数".to_string()),
+        BridgeType::Meek if !has("url") => return Err("meek网桥缺少url参数".to_string()),
+        _ => {}
+    }
+    Ok((bridge_type, tokens.join(" ")))
+}
+
+// 导出网桥：.json文件保存完整信息，其他扩展名每行一个网桥
+fn export_bridges(path: &Path, bridges: &[TorBridge]) -> anyhow::Result<()> {
+    let is_json = path.extension().map(|e| e.eq_ignore_ascii_case("json")).unwrap_or(false);
+    let contents = if is_json {
+        serde_json::to_string_pretty(bridges).context("Failed to serialize bridges")?
+    } else {
+        bridges.iter().map(|b| format!("# {}\n{}\n", b.name, b.address)).collect()
+    };
+    fs::write(path, contents).context("Failed to write bridges file")
+}
+
+// 读取导出的网桥文件，JSON以外的文件按每行一个网桥处理
+fn read_bridges_file(path: &Path) -> anyhow::Result<Vec<TorBridge>> {
+    let contents = fs::read_to_string(path).context("Failed to read bridges file")?;
+    if contents.trim_start().starts_with('[') {
+        return serde_json::from_str(&contents).context("Failed to parse bridges file");
+    }
+    let mut name = None;
+    let mut bridges = Vec::new();
+    for line in contents.lines().map(str::trim).filter(|l| !l.is_empty()) {
+        match line.strip_prefix('#') {
+            Some(comment) => name = Some(comment.trim().to_string()),
+            None => bridges.push(TorBridge::new(0, &name.take().unwrap_or_default(), BridgeType::Vanilla, line)),
+        }
+    }
+    Ok(bridges)
+}
+
+// 批量导入网桥的对话框状态
+#[derive(Default)]
+struct BulkImport {
+    open: bool,
+    text: String,
+    report: Option<ImportReport>,
+}
+
+// 导入结果
+#[derive(Default)]
+struct ImportReport {
+    imported: usize,
+    duplicates: usize,
+    failures: Vec<String>, // "第N行: 原因"
+}
+
+// 自定义torrc的校验状态
+#[derive(Clone, Debug)]
+enum VerifyState {
+    Idle,
+    Verifying,
+    Passed(String), // 校验通过、等待应用的内容
+    Applied,
+    Failed(String),
+}
+
 // Tor节点类型
 #[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
 pub enum NodeType {
@@ -60,14 +189,41 @@ pub struct TorModule {
     run_as_node: bool,
     node_type: NodeType,
     connection_status: String,
-    bandwidth_limit: u32,  // KB/s
-    tor_process: Option<Child>
+    launcher: Arc<dyn ProcessLauncher>,
+    tor_process: Option<TorProcess>,
+    circuit_viewer: CircuitViewer,
+    config: TorConfig,
+    new_exclude_country: String,
+    new_exclude_fingerprint: String,
+    exclude_error: Option<String>,
+    bridge_request: BridgeRequest,
+    bulk_import: BulkImport,
+    tor_check: TorCheck,
+    relay_search: RelaySearch,
+    control_auth_error: Option<String>,
+    guard_viewer: GuardViewer,
+    new_entry_node: String,
+    entry_node_error: Option<String>,
+    tor_version: Arc<Mutex<Option<String>>>, // tor --version读取到的版本
+    bundle_versions: (Option<String>, Option<String>), // 已安装、最新的专家包版本
+    update_requested: bool,
+    snowflake_proxy: Option<SnowflakeProxy>,
+    snowflake_error: Option<String>,
+    extra_torrc_draft: String,
+    torrc_verify: Arc<Mutex<VerifyState>>,
+    accounting_viewer: AccountingViewer,
+    new_map_address: MapAddressRule,
+    map_address_error: Option<String>,
 }
 
 impl TorModule {
-    // ... existing code ...
-}
     pub fn new(logger: Arc<Mutex<Logger>>) -> Self {
+        let launcher = services::launcher(Arc::clone(&logger));
+        Self::with_launcher(logger, launcher)
+    }
+    
+    // 使用指定的进程启动器创建模块
+    pub fn with_launcher(logger: Arc<Mutex<Logger>>, launcher: Arc<dyn ProcessLauncher>) -> Self {
         let mut module = Self {
             enabled: false,
             bridges: Vec::new(),
@@ -81,12 +237,41 @@ impl TorModule {
             run_as_node: false,
             node_type: NodeType::Relay,
             connection_status: "未连接".to_string(),
-            bandwidth_limit: 1024,  // 默认1MB/s
+            launcher,
             tor_process: None,
+            circuit_viewer: CircuitViewer::new(),
+            config: TorConfig::load(),
+            new_exclude_country: String::new(),
+            new_exclude_fingerprint: String::new(),
+            exclude_error: None,
+            bridge_request: BridgeRequest::new(),
+            bulk_import: BulkImport::default(),
+            tor_check: TorCheck::new(),
+            relay_search: RelaySearch::new(),
+            control_auth_error: None,
+            guard_viewer: GuardViewer::new(),
+            new_entry_node: String::new(),
+            entry_node_error: None,
+            tor_version: Arc::new(Mutex::new(None)),
+            bundle_versions: (None, None),
+            update_requested: false,
+            snowflake_proxy: None,
+            snowflake_error: None,
+            extra_torrc_draft: String::new(),
+            torrc_verify: Arc::new(Mutex::new(VerifyState::Idle)),
+            accounting_viewer: AccountingViewer::new(),
+            new_map_address: MapAddressRule { from: String::new(), to: String::new() },
+            map_address_error: None,
         };
+        module.extra_torrc_draft = module.config.extra_torrc.clone();
         
         // 添加一些示例网桥
         module.add_example_bridges();
+        module.reserve_ports();
+        module.refresh_tor_version();
+        if module.config.snowflake_proxy.enabled {
+            module.start_snowflake_proxy();
+        }
         
         // 记录模块初始化日志
         if let Ok(mut logger) = module.logger.lock() {
@@ -96,6 +281,21 @@ impl TorModule {
         module
     }
     
+    // 登记本地监听端口
+    fn reserve_ports(&self) {
+        let mut reserved = vec![(self.config.socks.port, Protocol::Tcp), (self.config.control_port, Protocol::Tcp)];
+        if self.config.dns.enabled {
+            reserved.push((self.config.dns.port, Protocol::Udp));
+        }
+        if self.config.http_tunnel.enabled {
+            reserved.push((self.config.http_tunnel.port, Protocol::Tcp));
+        }
+        if self.run_as_node {
+            reserved.push((self.config.relay.or_port, Protocol::Tcp));
+        }
+        ports::reserve("Tor", reserved);
+    }
+    
     // 添加示例网桥
     fn add_example_bridges(&mut self) {
         // 添加一些示例网桥
@@ -136,6 +336,141 @@ impl TorModule {
         self.next_bridge_id += 1;
     }
     
+    // 解析并导入多行网桥配置，跳过空行、注释和已存在的网桥
+    fn import_bridge_lines<S: AsRef<str>>(&mut self, lines: &[S], source: &str) -> ImportReport {
+        let mut report = ImportReport::default();
+        for (index, line) in lines.iter().enumerate() {
+            let line = line.as_ref().trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            self.merge_bridge(line, None, true, source, index + 1, &mut report);
+        }
+        self.log_import(source, &report);
+        report
+    }
+    
+    // 合并导入的网桥，保留名称与启用状态，跳过已存在的网桥
+    fn import_bridges(&mut self, bridges: Vec<TorBridge>, source: &str) -> ImportReport {
+        let mut report = ImportReport::default();
+        for (index, bridge) in bridges.iter().enumerate() {
+            let name = Some(bridge.name.as_str()).filter(|n| !n.trim().is_empty());
+            self.merge_bridge(&bridge.address, name, bridge.enabled, source, index + 1, &mut report);
+        }
+        self.log_import(source, &report);
+        report
+    }
+    
+    // 校验一个网桥并在不重复时添加，number为在导入内容中的序号
+    fn merge_bridge(&mut self, line: &str, name: Option<&str>, enabled: bool, source: &str, number: usize, report: &mut ImportReport) {
+        match parse_bridge_line(line) {
+            Ok((bridge_type, address)) => {
+                if self.bridges.iter().any(|b| b.address == address) {
+                    report.duplicates += 1;
+                    return;
+                }
+                let name = name
+                    .map(str::to_string)
+                    .unwrap_or_else(|| format!("{} {} {}", source, bridge_type.label(), self.next_bridge_id));
+                let mut bridge = TorBridge::new(self.next_bridge_id, &name, bridge_type, &address);
+                bridge.enabled = enabled;
+                self.add_bridge(bridge);
+                report.imported += 1;
+            }
+            Err(reason) => report.failures.push(format!("第{}行: {}", number, reason)),
+        }
+    }
+    
+    fn log_import(&self, source: &str, report: &ImportReport) {
+        if let Ok(mut logger) = self.logger.lock() {
+            logger.info("Tor", &format!(
+                "从{}导入了 {} 个网桥，重复 {} 个，失败 {} 个",
+                source, report.imported, report.duplicates, report.failures.len()
+            ));
+        }
+    }
+    
+    // 将所有网桥导出到用户选择的文件
+    fn export_bridges_dialog(&self) {
+        let Some(path) = rfd::FileDialog::new()
+            .add_filter("JSON", &["json"])
+            .add_filter("文本", &["txt"])
+            .set_file_name("bridges.json")
+            .save_file()
+        else {
+            return;
+        };
+        let result = export_bridges(&path, &self.bridges);
+        if let Ok(mut logger) = self.logger.lock() {
+            match result {
+                Ok(()) => logger.info("Tor", &format!("已导出 {} 个网桥到 {}", self.bridges.len(), path.display())),
+                Err(e) => logger.error("Tor", &format!("导出网桥失败: {:#}", e)),
+            }
+        }
+    }
+    
+    // 从文件导入网桥，结果显示在批量导入窗口中
+    fn import_bridges_dialog(&mut self) {
+        let Some(path) = rfd::FileDialog::new()
+            .add_filter("网桥文件", &["json", "txt"])
+            .pick_file()
+        else {
+            return;
+        };
+        let report = match read_bridges_file(&path) {
+            Ok(bridges) => self.import_bridges(bridges, "文件"),
+            Err(e) => ImportReport {
+                failures: vec![format!("{:#}", e)],
+                ..Default::default()
+            },
+        };
+        self.bulk_import.open = true;
+        self.bulk_import.report = Some(report);
+    }
+    
+    // 批量导入对话框
+    fn render_bulk_import(&mut self, ctx: &egui::Context) {
+        if !self.bulk_import.open {
+            return;
+        }
+        let mut open = true;
+        let mut import = false;
+        egui::Window::new("批量导入网桥")
+            .open(&mut open)
+            .default_width(520.0)
+            .show(ctx, |ui| {
+                ui.label("每行一个网桥，支持Vanilla、obfs4、snowflake和meek格式，可带\"Bridge \"前缀");
+                ui.add(
+                    egui::TextEdit::multiline(&mut self.bulk_import.text)
+                        .desired_rows(8)
+                        .desired_width(f32::INFINITY)
+                        .code_editor()
+                        .hint_text("obfs4 192.0.2.2:443 <指纹> cert=... iat-mode=0"),
+                );
+                if ui.button("导入").clicked() {
+                    import = true;
+                }
+                if let Some(report) = &self.bulk_import.report {
+                    ui.separator();
+                    ui.label(format!("已导入 {} 个，跳过重复 {} 个", report.imported, report.duplicates));
+                    for failure in &report.failures {
+                        ui.label(RichText::new(failure).color(Color32::RED));
+                    }
+                }
+            });
+        if import {
+            let text = std::mem::take(&mut self.bulk_import.text);
+            let lines: Vec<&str> = text.lines().collect();
+            let report = self.import_bridge_lines(&lines, "导入");
+            // 有失败时保留原文以便修改后重新导入，已导入的行会作为重复跳过
+            if !report.failures.is_empty() {
+                self.bulk_import.text = text;
+            }
+            self.bulk_import.report = Some(report);
+        }
+        self.bulk_import.open = open;
+    }
+    
     // 删除网桥
     fn remove_bridge(&mut self, id: usize) {
         if let Some(index) = self.bridges.iter().position(|b| b.id == id) {
@@ -151,13 +486,19 @@ impl TorModule {
     }
     
     // 启用/禁用Tor
-    async fn connect_to_tor(&mut self) -> Result<(), Box<dyn std::error::Error>> {
-        let client = TorClient::connect().await?;
-        self.connection_status = "已连接".to_string();
-        Ok(())
+    // 启动Tor所需的设置
+    fn tor_settings(&self) -> TorSettings {
+        TorSettings {
+            config: self.config.clone(),
+            bridges: if self.config.use_bridges {
+                self.bridges.iter().filter(|b| b.enabled).cloned().collect()
+            } else {
+                Vec::new()
+            },
+            relay_mode: self.run_as_node.then(|| self.node_type.clone()),
+        }
     }
     
-    // 启用/禁用Tor
     fn toggle_tor(&mut self) -> Result<(), Box<dyn std::error::Error>> {
         // 先获取当前状态的副本，避免同时借用
         let new_enabled = !self.enabled;
@@ -175,36 +516,42 @@ impl TorModule {
         self.enabled = new_enabled;
         self.connection_status = if new_enabled { "正在连接..." } else { "未连接" }.to_string();
         
-        // 启动或停止Tor服务
-        let tor_process = if new_enabled {
-            Some(std::process::Command::new("tor")
-                .arg("--RunAsDaemon")
-                .arg("1")
-                .spawn().expect("无法启动Tor进程"))
-        } else {
-            if let Some(mut process) = self.tor_process.take() {
-                let _ = process.kill();
+        // 启动或停止Tor服务，进程由进程监控负责崩溃或SOCKS端口无响应时自动重启
+        if let Some(process) = self.tor_process.take() {
+            if let Err(e) = process.stop() {
+                if let Ok(mut logger) = self.logger.lock() {
+                    logger.error("Tor", &format!("保存流量记录失败: {}", e));
+                }
             }
-            None
-        };
-        self.tor_process = tor_process;
-        
-        // 模拟连接过程
+        }
         if new_enabled {
-            // 创建一个运行时来执行异步连接逻辑
-            let rt = Runtime::new().unwrap();
-            let result = rt.block_on(self.connect_to_tor());
-            if let Err(e) = result {
-                if let Ok(mut logger) = self.logger.lock() {
-                    logger.error("Tor", &format!("连接Tor失败: {}", e));
+            let settings = self.tor_settings();
+            match TorProcess::start(self.launcher.as_ref(), Arc::clone(&self.logger), &settings) {
+                Ok(process) => self.tor_process = Some(process),
+                Err(e) => {
+                    if let Ok(mut logger) = self.logger.lock() {
+                        logger.error("Tor", &format!("启动Tor失败: {:#}", e));
+                    }
+                    self.enabled = false;
+                    self.connection_status = "启动失败".to_string();
                 }
-                self.connection_status = "连接失败".to_string();
             }
         }
         
         Ok(())
     }
     
+    // 按指定状态启用/禁用Tor
+    pub fn set_enabled(&mut self, enabled: bool) {
+        if self.enabled != enabled {
+            if let Err(e) = self.toggle_tor() {
+                if let Ok(mut logger) = self.logger.lock() {
+                    logger.error("Tor", &format!("Tor操作失败: {}", e));
+                }
+            }
+        }
+    }
+    
     // 启用/禁用网桥
     fn toggle_bridge(&mut self, id: usize) {
         // 先查找网桥并获取必要信息，避免同时借用
@@ -237,6 +584,736 @@ impl TorModule {
         }
     }
     
+    fn save_config(&self) {
+        if let Err(e) = self.config.save() {
+            if let Ok(mut logger) = self.logger.lock() {
+                logger.error("Tor", &format!("保存Tor设置失败: {}", e));
+            }
+        }
+    }
+    
+    // 保存设置，Tor运行时提示重启后生效
+    fn save_restart_required(&self, what: &str) {
+        self.save_config();
+        if self.enabled {
+            if let Ok(mut logger) = self.logger.lock() {
+                logger.info("Tor", &format!("{}已更改，重启Tor后生效", what));
+            }
+        }
+    }
+    
+    // 本地监听端口设置
+    fn render_ports(&mut self, ui: &mut Ui) {
+        let mut changed = None;
+        Grid::new("tor_ports_grid").num_columns(4).spacing([10.0, 4.0]).show(ui, |ui| {
+            ui.label("");
+            ui.label("");
+            ui.label("");
+            ui.label(RichText::new("流隔离").strong())
+                .on_hover_text("按所选条件为不同连接使用不同线路，避免多个应用或网站的流量被关联");
+            ui.end_row();
+            
+            let enabled = self.enabled;
+            let listeners = [
+                ("SOCKS端口:", &mut self.config.socks, Protocol::Tcp, false),
+                ("DNS端口:", &mut self.config.dns, Protocol::Udp, true),
+                ("HTTP隧道端口:", &mut self.config.http_tunnel, Protocol::Tcp, true),
+            ];
+            for (label, listener, protocol, optional) in listeners {
+                ui.label(label);
+                // SOCKS端口始终启用
+                if optional {
+                    if ui.checkbox(&mut listener.enabled, "启用").changed() {
+                        changed = Some(label);
+                    }
+                } else {
+                    ui.label("");
+                }
+                let listening = enabled && listener.enabled;
+                if ports::port_field(ui, "Tor", "127.0.0.1", &mut listener.port, protocol, listening) {
+                    changed = Some(label);
+                }
+                ui.horizontal(|ui| {
+                    let mut isolation_changed = false;
+                    isolation_changed |= ui.checkbox(&mut listener.isolate_dest_addr, "目标地址")
+                        .on_hover_text("IsolateDestAddr：访问不同地址时使用不同线路").changed();
+                    isolation_changed |= ui.checkbox(&mut listener.isolate_dest_port, "目标端口")
+                        .on_hover_text("IsolateDestPort：访问不同端口时使用不同线路").changed();
+                    isolation_changed |= ui.checkbox(&mut listener.isolate_client_protocol, "客户端协议")
+                        .on_hover_text("IsolateClientProtocol：SOCKS4/SOCKS5/HTTP等不同协议使用不同线路").changed();
+                    if isolation_changed {
+                        changed = Some("流隔离设置");
+                    }
+                });
+                ui.end_row();
+            }
+            
+            ui.label("控制端口:");
+            ui.label("");
+            if ports::port_field(ui, "Tor", "127.0.0.1", &mut self.config.control_port, Protocol::Tcp, self.enabled) {
+                changed = Some("控制端口");
+            }
+            ui.end_row();
+        });
+        if let Some(what) = changed {
+            self.reserve_ports();
+            self.save_restart_required(what.trim_end_matches(':'));
+        }
+        
+        ui.add_space(5.0);
+        self.render_dns_over_tor(ui);
+        ui.add_space(5.0);
+        self.render_control_auth(ui);
+    }
+    
+    // 通过Tor解析系统DNS
+    fn render_dns_over_tor(&mut self, ui: &mut Ui) {
+        let is_admin = utils::is_running_as_admin();
+        let checkbox = ui.add_enabled(is_admin, egui::Checkbox::new(&mut self.config.dns_over_tor, "通过Tor解析系统DNS"))
+            .on_hover_text("DNSCrypt未运行时，将网卡的DNS服务器设为本机，由Tor的DNS端口经Tor网络解析域名")
+            .on_disabled_hover_text("修改系统DNS需要管理员权限");
+        if checkbox.changed() {
+            if self.config.dns_over_tor {
+                // 系统DNS只能使用53端口
+                self.config.dns.enabled = true;
+                self.config.dns.port = 53;
+                self.reserve_ports();
+            }
+            self.save_restart_required("DNS端口");
+        }
+        if self.config.dns_over_tor && (!self.config.dns.enabled || self.config.dns.port != 53) {
+            ui.label(RichText::new("系统DNS只能使用53端口，请启用DNS端口并设为53").color(Color32::YELLOW));
+        } else if system_dns::is_redirected() {
+            ui.label(RichText::new("系统DNS当前指向本机").color(Color32::GREEN));
+        }
+    }
+    
+    // 启动Snowflake志愿者代理（已运行时按新设置重启）
+    fn start_snowflake_proxy(&mut self) {
+        self.snowflake_proxy = None;
+        match SnowflakeProxy::start(self.launcher.as_ref(), Arc::clone(&self.logger), self.config.snowflake_proxy.capacity) {
+            Ok(proxy) => {
+                self.snowflake_proxy = Some(proxy);
+                self.snowflake_error = None;
+            }
+            Err(e) => {
+                if let Ok(mut logger) = self.logger.lock() {
+                    logger.error("Snowflake", &format!("启动Snowflake代理失败: {}", e));
+                }
+                self.snowflake_error = Some(e.to_string());
+            }
+        }
+    }
+    
+    // 停止Snowflake代理，不修改志愿者模式设置
+    pub fn stop_snowflake_proxy(&mut self) {
+        if self.snowflake_proxy.is_none() {
+            return;
+        }
+        self.sync_snowflake_counter();
+        self.snowflake_proxy = None;
+        if let Ok(mut logger) = self.logger.lock() {
+            logger.info("Snowflake", "Snowflake代理已停止");
+        }
+    }
+    
+    // Snowflake代理是否正在运行
+    pub fn is_snowflake_running(&self) -> bool {
+        self.snowflake_proxy.is_some()
+    }
+    
+    // 启用了志愿者模式时重新启动Snowflake代理（如组件更新后）
+    pub fn resume_snowflake_proxy(&mut self) {
+        if self.config.snowflake_proxy.enabled && self.snowflake_proxy.is_none() {
+            self.start_snowflake_proxy();
+        }
+    }
+    
+    // 将Snowflake代理新帮助的用户数计入累计值
+    pub fn sync_snowflake_counter(&mut self) {
+        let helped = self.snowflake_proxy.as_ref().map(|p| p.take_helped()).unwrap_or(0);
+        if helped == 0 {
+            return;
+        }
+        self.config.snowflake_proxy.helped_total += helped;
+        self.save_config();
+    }
+    
+    // Snowflake志愿者模式
+    fn render_snowflake_proxy(&mut self, ui: &mut Ui) {
+        ui.label(RichText::new("运行Snowflake代理，帮助受审查地区的用户连接Tor。本机不会成为出口，流量只转发到Snowflake网桥。").weak());
+        let installed = snowflake_proxy::proxy_exe().is_some();
+        let checkbox = ui.add_enabled(installed || self.config.snowflake_proxy.enabled, egui::Checkbox::new(&mut self.config.snowflake_proxy.enabled, "志愿者模式"))
+            .on_disabled_hover_text("未安装Snowflake代理，请在设置的组件管理中安装");
+        let mut restart = false;
+        if checkbox.changed() {
+            if self.config.snowflake_proxy.enabled {
+                restart = true;
+            } else {
+                self.stop_snowflake_proxy();
+            }
+            self.save_config();
+        }
+        
+        ui.horizontal(|ui| {
+            ui.label("同时服务的用户数上限:");
+            let response = ui.add(egui::Slider::new(&mut self.config.snowflake_proxy.capacity, 0..=100))
+                .on_hover_text("0表示不限制");
+            if slider_committed(&response) {
+                self.save_config();
+                restart = self.config.snowflake_proxy.enabled;
+            }
+        });
+        if restart {
+            self.start_snowflake_proxy();
+        }
+        
+        ui.horizontal(|ui| {
+            match self.snowflake_proxy.as_ref().map(|p| p.state()) {
+                Some(ProcessState::Running) => ui.label(RichText::new("运行中").color(Color32::GREEN)),
+                Some(state @ (ProcessState::Starting | ProcessState::Restarting { .. } | ProcessState::Degraded(_))) => {
+                    ui.label(RichText::new(state.label()).color(Color32::YELLOW))
+                }
+                Some(state) => ui.label(RichText::new(state.label()).color(Color32::RED)),
+                None => ui.label(RichText::new("未运行").color(Color32::GRAY)),
+            };
+            ui.label(format!("累计帮助 {} 位用户", self.config.snowflake_proxy.helped_total));
+        });
+        if let Some(error) = &self.snowflake_error {
+            ui.label(RichText::new(error).color(Color32::RED));
+        }
+    }
+    
+    // 是否要求将系统DNS指向Tor的DNSPort（DNSCrypt运行时由DNSCrypt接管）
+    pub fn wants_system_dns(&self) -> bool {
+        self.enabled && self.config.dns_over_tor && self.config.dns.enabled && self.config.dns.port == 53
+    }
+    
+    // 控制端口认证方式
+    fn render_control_auth(&mut self, ui: &mut Ui) {
+        ui.horizontal(|ui| {
+            ui.label("控制端口认证:");
+            let before = self.config.control_auth;
+            ui.radio_value(&mut self.config.control_auth, ControlAuth::Cookie, "Cookie")
+                .on_hover_text("使用Tor数据目录中的认证Cookie文件");
+            ui.radio_value(&mut self.config.control_auth, ControlAuth::Password, "密码")
+                .on_hover_text("使用HashedControlPassword，其他程序也可凭密码连接控制端口");
+            if self.config.control_auth != before {
+                self.save_restart_required("控制端口认证方式");
+            }
+        });
+        if self.config.control_auth != ControlAuth::Password {
+            return;
+        }
+        
+        ui.horizontal(|ui| {
+            ui.label("密码:");
+            let response = ui.add(egui::TextEdit::singleline(&mut self.config.control_password).password(true).desired_width(180.0));
+            if response.changed() {
+                // 密码修改后原哈希失效，需要重新生成
+                self.config.hashed_control_password.clear();
+                self.control_auth_error = None;
+            }
+            let can_generate = !self.config.control_password.is_empty();
+            if ui.add_enabled(can_generate, egui::Button::new("生成哈希")).clicked() {
+                match tor_process::hash_password(&self.config.control_password) {
+                    Ok(hash) => {
+                        self.config.hashed_control_password = hash;
+                        self.control_auth_error = None;
+                        self.save_restart_required("控制端口密码");
+                    }
+                    Err(e) => self.control_auth_error = Some(format!("生成哈希失败: {:#}", e)),
+                }
+            }
+        });
+        if let Some(error) = &self.control_auth_error {
+            ui.label(RichText::new(error).color(Color32::RED));
+        } else if self.config.hashed_control_password.is_empty() {
+            ui.label(RichText::new("请输入密码并生成哈希，否则无法启动Tor").color(Color32::YELLOW));
+        } else {
+            ui.label(RichText::new(format!("HashedControlPassword {}", self.config.hashed_control_password)).monospace().small().weak());
+        }
+    }
+    
+    // 中继节点设置表单
+    fn render_relay_form(&mut self, ui: &mut Ui) {
+        let mut changed = false;
+        let exit = self.node_type == NodeType::Exit;
+        let relay = &mut self.config.relay;
+        
+        Grid::new("tor_relay_grid").num_columns(2).spacing([10.0, 4.0]).show(ui, |ui| {
+            ui.label("昵称:");
+            changed |= ui.add(egui::TextEdit::singleline(&mut relay.nickname).hint_text("1-19位字母或数字")).changed();
+            ui.end_row();
+            
+            ui.label("联系方式:");
+            changed |= ui.add(egui::TextEdit::singleline(&mut relay.contact_info).hint_text("邮箱等，便于Tor项目联系运营者")).changed();
+            ui.end_row();
+            
+            ui.label("ORPort:");
+            changed |= ports::port_field(ui, "Tor", "0.0.0.0", &mut relay.or_port, Protocol::Tcp, self.enabled);
+            ui.end_row();
+            
+            ui.label("每周期流量上限:");
+            ui.horizontal(|ui| {
+                changed |= ui.add(egui::DragValue::new(&mut relay.accounting_max_gb).clamp_range(0..=100000).suffix(" GB")).changed();
+                ui.label("（0表示不限制，达到上限后休眠至下一周期）");
+            });
+            ui.end_row();
+        });
+        
+        if relay.accounting_max_gb > 0 {
+            ui.group(|ui| {
+                changed |= tor_accounting::schedule_editor(ui, relay);
+                let control = self.tor_process.as_ref().map(|p| p.control_endpoint());
+                self.accounting_viewer.ui(ui, control);
+            });
+        }
+        
+        // 出口策略，仅出口节点需要
+        if exit {
+            ui.label(RichText::new("出口策略（未列出的地址和端口一律拒绝）").strong());
+            let mut removed = None;
+            for (index, rule) in relay.exit_policy.iter_mut().enumerate() {
+                ui.horizontal(|ui| {
+                    egui::ComboBox::from_id_source(("exit_rule_action", index))
+                        .width(70.0)
+                        .selected_text(if rule.accept { "允许" } else { "拒绝" })
+                        .show_ui(ui, |ui| {
+                            changed |= ui.selectable_value(&mut rule.accept, true, "允许").changed();
+                            changed |= ui.selectable_value(&mut rule.accept, false, "拒绝").changed();
+                        });
+                    let color = if rule.is_valid() { ui.visuals().text_color() } else { Color32::RED };
+                    changed |= ui.add(egui::TextEdit::singleline(&mut rule.pattern).text_color(color).desired_width(200.0)).changed();
+                    if ui.small_button("✖").on_hover_text("删除").clicked() {
+                        removed = Some(index);
+                    }
+                });
+            }
+            if let Some(index) = removed {
+                relay.exit_policy.remove(index);
+                changed = true;
+            }
+            ui.horizontal(|ui| {
+                if ui.button("添加规则").clicked() {
+                    relay.exit_policy.push(ExitRule { accept: true, pattern: "*:".to_string() });
+                    changed = true;
+                }
+                if ui.button("仅Web端口").on_hover_text("只允许80和443端口").clicked() {
+                    relay.exit_policy = vec![
+                        ExitRule { accept: true, pattern: "*:80".to_string() },
+                        ExitRule { accept: true, pattern: "*:443".to_string() },
+                    ];
+                    changed = true;
+                }
+            });
+        }
+        
+        for error in relay.validate(exit) {
+            ui.label(RichText::new(error).color(Color32::RED));
+        }
+        if self.enabled {
+            ui.label(RichText::new("修改将在重启Tor后生效").weak());
+        }
+        
+        if changed {
+            self.reserve_ports();
+            self.save_config();
+        }
+    }
+    
+    // 通过控制端口修改运行中Tor的配置，Tor未运行时不做任何操作
+    fn apply_live_conf(&self, conf: Vec<(&'static str, Option<String>)>, what: &str) {
+        let Some((port, password)) = self.tor_process.as_ref().map(|p| p.control_endpoint()) else {
+            return;
+        };
+        let logger = Arc::clone(&self.logger);
+        let what = what.to_string();
+        std::thread::spawn(move || {
+            let result = ControlConnection::connect(port, password.as_deref()).and_then(|mut connection| {
+                connection.set_conf(&conf)
+            });
+            if let Ok(mut logger) = logger.lock() {
+                match result {
+                    Ok(_) => logger.info("Tor", &format!("{}已应用", what)),
+                    Err(e) => logger.error("Tor", &format!("应用{}失败: {:#}", what, e)),
+                }
+            }
+        });
+    }
+    
+    // 保存设置并立即应用到运行中的Tor
+    fn save_and_apply(&self, conf: Vec<(&'static str, Option<String>)>, what: &str) {
+        self.save_config();
+        self.apply_live_conf(conf, what);
+    }
+    
+    // 保存排除节点设置，Tor运行时通过控制端口立即生效
+    fn apply_exclude_nodes(&self) {
+        let exclude_nodes = self.config.exclude_nodes();
+        let strict_nodes = Some(if self.config.strict_nodes { "1" } else { "0" }.to_string());
+        self.save_and_apply(vec![("ExcludeNodes", exclude_nodes), ("StrictNodes", strict_nodes)], "排除节点设置");
+    }
+    
+    // 保存固定的入口守卫，Tor运行时通过控制端口立即生效
+    fn apply_entry_nodes(&self) {
+        self.save_and_apply(vec![("EntryNodes", self.config.entry_nodes())], "入口守卫设置");
+    }
+    
+    // 固定或取消固定入口守卫
+    fn toggle_entry_node(&mut self, fingerprint: String) {
+        match self.config.entry_nodes.iter().position(|fp| *fp == fingerprint) {
+            Some(index) => {
+                self.config.entry_nodes.remove(index);
+            }
+            None => self.config.entry_nodes.push(fingerprint),
+        }
+        self.apply_entry_nodes();
+    }
+    
+    // 入口守卫列表与固定的守卫
+    fn render_entry_guards(&mut self, ui: &mut Ui) {
+        let control = self.tor_process.as_ref().map(|p| p.control_endpoint());
+        if let Some(fingerprint) = self.guard_viewer.ui(ui, control, &self.config.entry_nodes) {
+            self.toggle_entry_node(fingerprint);
+        }
+        
+        ui.separator();
+        ui.label("固定的入口守卫（Tor只会从中选择入口节点）:");
+        if self.config.use_bridges {
+            ui.label(RichText::new("使用网桥时网桥即为入口节点，固定的守卫不会生效").color(Color32::YELLOW));
+        }
+        let mut removed = None;
+        for fingerprint in &self.config.entry_nodes {
+            ui.horizontal(|ui| {
+                ui.label(RichText::new(fingerprint).monospace());
+                if ui.small_button("✖").on_hover_text("取消固定").clicked() {
+                    removed = Some(fingerprint.clone());
+                }
+            });
+        }
+        if let Some(fingerprint) = removed {
+            self.toggle_entry_node(fingerprint);
+        }
+        ui.horizontal(|ui| {
+            ui.add(egui::TextEdit::singleline(&mut self.new_entry_node).hint_text("40位中继指纹").desired_width(320.0));
+            if ui.button("固定").clicked() {
+                match tor_config::normalize_fingerprint(&self.new_entry_node) {
+                    Some(fingerprint) if self.config.entry_nodes.contains(&fingerprint) => {
+                        self.entry_node_error = Some("该中继已固定".to_string());
+                    }
+                    Some(fingerprint) => {
+                        self.new_entry_node.clear();
+                        self.entry_node_error = None;
+                        self.toggle_entry_node(fingerprint);
+                    }
+                    None => self.entry_node_error = Some("指纹应为40位十六进制字符".to_string()),
+                }
+            }
+        });
+        if let Some(error) = &self.entry_node_error {
+            ui.label(RichText::new(error).color(Color32::RED));
+        }
+    }
+    
+    // 保存带宽限制，Tor运行时通过控制端口立即生效
+    fn apply_bandwidth(&self) {
+        self.save_and_apply(self.config.bandwidth_conf(self.run_as_node), "带宽限制");
+    }
+    
+    // 在后台启动浏览器，代理指向Tor的SOCKS端口
+    fn launch_browser(&self) {
+        let Some(socks_port) = self.socks_port() else {
+            return;
+        };
+        let chosen = Some(self.config.browser_path.clone()).filter(|p| !p.trim().is_empty()).map(PathBuf::from);
+        let logger = Arc::clone(&self.logger);
+        std::thread::spawn(move || {
+            let result = match chosen {
+                Some(path) => Ok(path),
+                None => browser::default_browser(),
+            }
+            .and_then(|path| browser::launch(&path, socks_port));
+            if let Ok(mut logger) = logger.lock() {
+                match result {
+                    Ok(command_line) => logger.info("Tor", &format!("已通过Tor启动浏览器: {}", command_line)),
+                    Err(e) => logger.error("Tor", &format!("启动浏览器失败: {:#}", e)),
+                }
+            }
+        });
+    }
+    
+    // 通过Tor打开浏览器
+    fn render_browser_launch(&mut self, ui: &mut Ui, ready: bool) {
+        ui.horizontal(|ui| {
+            let button = ui.add_enabled(ready, egui::Button::new("通过Tor打开浏览器"))
+                .on_hover_text("使用独立的浏览器配置启动，代理指向Tor的SOCKS端口")
+                .on_disabled_hover_text("请先启动Tor并等待连接完成");
+            if button.clicked() {
+                self.launch_browser();
+            }
+            
+            let label = if self.config.browser_path.is_empty() { "系统默认浏览器".to_string() } else { self.config.browser_path.clone() };
+            ui.label(RichText::new(label).weak());
+            if ui.small_button("选择...").clicked() {
+                if let Some(path) = rfd::FileDialog::new().add_filter("程序", &["exe"]).pick_file() {
+                    self.config.browser_path = path.display().to_string();
+                    self.save_config();
+                }
+            }
+            if !self.config.browser_path.is_empty() && ui.small_button("使用默认").clicked() {
+                self.config.browser_path.clear();
+                self.save_config();
+            }
+        });
+    }
+    
+    // 在后台用tor --verify-config校验包含草稿的完整配置
+    fn verify_extra_torrc(&self) {
+        if let Ok(mut state) = self.torrc_verify.lock() {
+            *state = VerifyState::Verifying;
+        }
+        let mut settings = self.tor_settings();
+        settings.config.extra_torrc = self.extra_torrc_draft.clone();
+        let state = Arc::clone(&self.torrc_verify);
+        std::thread::spawn(move || {
+            let next = match tor_process::verify_config(&settings) {
+                Ok(()) => VerifyState::Passed(settings.config.extra_torrc),
+                Err(e) => VerifyState::Failed(format!("{:#}", e)),
+            };
+            if let Ok(mut state) = state.lock() {
+                *state = next;
+            }
+        });
+    }
+    
+    // 自定义torrc编辑器，校验通过后才保存
+    fn render_extra_torrc(&mut self, ui: &mut Ui) {
+        ui.label(RichText::new("以下配置行会追加到自动生成的torrc末尾，与已有设置冲突时可能导致Tor无法启动").weak());
+        ui.add(
+            egui::TextEdit::multiline(&mut self.extra_torrc_draft)
+                .desired_rows(6)
+                .desired_width(f32::INFINITY)
+                .code_editor()
+                .hint_text("# 例如\nCircuitBuildTimeout 30\nNumEntryGuards 2"),
+        );
+        
+        let state = self.torrc_verify.lock().map(|s| s.clone()).unwrap_or(VerifyState::Idle);
+        if let VerifyState::Passed(text) = &state {
+            self.config.extra_torrc = text.clone();
+            self.save_restart_required("自定义torrc");
+            if let Ok(mut state) = self.torrc_verify.lock() {
+                *state = VerifyState::Applied;
+            }
+        }
+        let changed = self.extra_torrc_draft != self.config.extra_torrc;
+        ui.horizontal(|ui| {
+            let verifying = matches!(state, VerifyState::Verifying);
+            if ui.add_enabled(changed && !verifying, egui::Button::new("校验并应用")).clicked() {
+                self.verify_extra_torrc();
+            }
+            if ui.add_enabled(changed && !verifying, egui::Button::new("撤销修改")).clicked() {
+                self.extra_torrc_draft = self.config.extra_torrc.clone();
+                if let Ok(mut state) = self.torrc_verify.lock() {
+                    *state = VerifyState::Idle;
+                }
+            }
+            match &state {
+                VerifyState::Verifying => {
+                    ui.spinner();
+                    ui.label("正在运行tor --verify-config...");
+                    ui.ctx().request_repaint_after(std::time::Duration::from_millis(200));
+                }
+                VerifyState::Applied | VerifyState::Passed(_) if !changed => {
+                    let hint = if self.enabled { "校验通过，已保存，重启Tor后生效" } else { "校验通过，已保存" };
+                    ui.label(RichText::new(hint).color(Color32::GREEN));
+                }
+                _ => {}
+            }
+        });
+        if let VerifyState::Failed(error) = &state {
+            ui.label(RichText::new(format!("校验失败，未保存:\n{}", error)).color(Color32::RED));
+        }
+    }
+    
+    // 地址映射规则，修改后立即应用
+    fn render_map_addresses(&mut self, ui: &mut Ui) {
+        ui.label("通过Tor访问原地址时实际连接目标地址，例如将网站域名映射到它的.onion地址（需要应用使用Tor解析域名）");
+        let mut changed = false;
+        let mut removed = None;
+        for (index, rule) in self.config.map_addresses.iter().enumerate() {
+            ui.horizontal(|ui| {
+                ui.label(RichText::new(&rule.from).monospace());
+                ui.label("→");
+                ui.label(RichText::new(&rule.to).monospace());
+                if ui.small_button("✖").on_hover_text("删除").clicked() {
+                    removed = Some(index);
+                }
+            });
+        }
+        if let Some(index) = removed {
+            self.config.map_addresses.remove(index);
+            changed = true;
+        }
+        
+        ui.horizontal(|ui| {
+            ui.add(egui::TextEdit::singleline(&mut self.new_map_address.from).hint_text("example.com 或 *.example.com").desired_width(200.0));
+            ui.label("→");
+            ui.add(egui::TextEdit::singleline(&mut self.new_map_address.to).hint_text("xxxx.onion").desired_width(260.0));
+            if ui.button("添加").clicked() {
+                let rule = MapAddressRule {
+                    from: self.new_map_address.from.trim().to_lowercase(),
+                    to: self.new_map_address.to.trim().to_lowercase(),
+                };
+                match rule.validate() {
+                    Ok(()) if self.config.map_addresses.iter().any(|r| r.from == rule.from) => {
+                        self.map_address_error = Some(format!("{} 已有映射规则", rule.from));
+                    }
+                    Ok(()) => {
+                        self.config.map_addresses.push(rule);
+                        self.new_map_address = MapAddressRule { from: String::new(), to: String::new() };
+                        self.map_address_error = None;
+                        changed = true;
+                    }
+                    Err(reason) => self.map_address_error = Some(reason),
+                }
+            }
+        });
+        if let Some(error) = &self.map_address_error {
+            ui.label(RichText::new(error).color(Color32::RED));
+        }
+        
+        if changed {
+            self.save_and_apply(self.config.map_address_conf(), "地址映射");
+        }
+    }
+    
+    // 高级选项：连接填充与减少磁盘写入，修改后立即应用
+    fn render_advanced(&mut self, ui: &mut Ui) {
+        let mut apply = false;
+        ui.horizontal(|ui| {
+            ui.label("连接填充:");
+            let before = self.config.connection_padding;
+            ui.radio_value(&mut self.config.connection_padding, ConnectionPadding::Auto, "自动")
+                .on_hover_text("由Tor网络共识决定是否填充");
+            ui.radio_value(&mut self.config.connection_padding, ConnectionPadding::Enabled, "始终")
+                .on_hover_text("ConnectionPadding 1：即使中继未协商也发送填充");
+            ui.radio_value(&mut self.config.connection_padding, ConnectionPadding::Disabled, "关闭")
+                .on_hover_text("ConnectionPadding 0：不发送填充，会降低对流量分析的抵抗能力");
+            apply |= self.config.connection_padding != before;
+        });
+        apply |= ui.add_enabled(
+            self.config.connection_padding != ConnectionPadding::Disabled,
+            egui::Checkbox::new(&mut self.config.reduced_connection_padding, "减少连接填充"),
+        ).on_hover_text("ReducedConnectionPadding：更早关闭空闲连接并减少填充，节省流量和电量").changed();
+        apply |= ui.checkbox(&mut self.config.avoid_disk_writes, "减少磁盘写入")
+            .on_hover_text("AvoidDiskWrites：降低写入数据目录的频率，适合U盘或SSD")
+            .changed();
+        if apply {
+            self.save_and_apply(self.config.advanced_conf(), "高级选项");
+        }
+    }
+    
+    // 客户端带宽限制
+    fn render_client_bandwidth(&mut self, ui: &mut Ui) {
+        let mut limited = self.config.bandwidth_limit_kb > 0;
+        let mut apply = false;
+        ui.horizontal(|ui| {
+            if ui.checkbox(&mut limited, "限制Tor带宽").changed() {
+                self.config.bandwidth_limit_kb = if limited { 1024 } else { 0 };
+                apply = true;
+            }
+            if limited {
+                let response = ui.add(egui::Slider::new(&mut self.config.bandwidth_limit_kb, 100..=10240).suffix(" KB/s"));
+                apply |= slider_committed(&response);
+            }
+        });
+        ui.label(RichText::new("BandwidthRate：限制Tor的平均传输速率，运行中修改会立即生效").weak());
+        if apply {
+            self.apply_bandwidth();
+        }
+    }
+    
+    // 排除节点编辑器
+    fn render_exclude_nodes(&mut self, ui: &mut Ui) {
+        let mut changed = false;
+        
+        ui.label("不使用以下国家或中继作为线路中的任何节点（按国家排除需要GeoIP数据）");
+        ui.horizontal_wrapped(|ui| {
+            ui.label("国家:");
+            let mut removed = None;
+            for (index, code) in self.config.exclude_countries.iter().enumerate() {
+                if ui.button(format!("{} ✖", code)).on_hover_text("移除").clicked() {
+                    removed = Some(index);
+                }
+            }
+            if let Some(index) = removed {
+                self.config.exclude_countries.remove(index);
+                changed = true;
+            }
+        });
+        ui.horizontal(|ui| {
+            ui.add(egui::TextEdit::singleline(&mut self.new_exclude_country).hint_text("国家代码，如 RU").desired_width(120.0));
+            if ui.button("添加国家").clicked() {
+                match tor_config::normalize_country(&self.new_exclude_country) {
+                    Some(code) => {
+                        if !self.config.exclude_countries.contains(&code) {
+                            self.config.exclude_countries.push(code);
+                            changed = true;
+                        }
+                        self.new_exclude_country.clear();
+                        self.exclude_error = None;
+                    }
+                    None => self.exclude_error = Some("国家代码应为两位字母".to_string()),
+                }
+            }
+        });
+        
+        ui.label("中继指纹:");
+        let mut removed = None;
+        for (index, fingerprint) in self.config.exclude_fingerprints.iter().enumerate() {
+            ui.horizontal(|ui| {
+                ui.label(RichText::new(fingerprint).monospace());
+                if ui.small_button("✖").on_hover_text("移除").clicked() {
+                    removed = Some(index);
+                }
+            });
+        }
+        if let Some(index) = removed {
+            self.config.exclude_fingerprints.remove(index);
+            changed = true;
+        }
+        ui.horizontal(|ui| {
+            ui.add(egui::TextEdit::singleline(&mut self.new_exclude_fingerprint).hint_text("40位十六进制指纹").desired_width(320.0));
+            if ui.button("添加指纹").clicked() {
+                match tor_config::normalize_fingerprint(&self.new_exclude_fingerprint) {
+                    Some(fingerprint) => {
+                        if !self.config.exclude_fingerprints.contains(&fingerprint) {
+                            self.config.exclude_fingerprints.push(fingerprint);
+                            changed = true;
+                        }
+                        self.new_exclude_fingerprint.clear();
+                        self.exclude_error = None;
+                    }
+                    None => self.exclude_error = Some("指纹应为40位十六进制字符".to_string()),
+                }
+            }
+        });
+        
+        if let Some(error) = &self.exclude_error {
+            ui.label(RichText::new(error).color(Color32::RED));
+        }
+        
+        changed |= ui
+            .checkbox(&mut self.config.strict_nodes, "严格排除")
+            .on_hover_text("无法避开排除的节点时宁可连接失败（可能导致部分功能不可用）")
+            .changed();
+        
+        if changed {
+            self.apply_exclude_nodes();
+        }
+    }
+    
     // 打开Tor项目捐赠页面
     fn open_donation_page(&self) {
         if let Ok(mut logger) = self.logger.lock() {
@@ -252,23 +1329,127 @@ impl TorModule {
     }
     
     // 获取当前连接状态的副本
-    fn get_connection_status(&self) -> String {
+    pub fn get_connection_status(&self) -> String {
         self.connection_status.clone()
     }
     
+    // Tor是否已启用
+    pub fn is_enabled(&self) -> bool {
+        self.enabled
+    }
+    
+    // Tor是否已完成引导，可以建立线路
+    pub fn is_connected(&self) -> bool {
+        self.tor_process.as_ref().map_or(false, |p| matches!(p.bootstrap_state(), BootstrapState::Ready))
+    }
+    
+    // 获取Tor SOCKS端口（仅在Tor启用时可用）
+    pub fn socks_port(&self) -> Option<u16> {
+        if self.enabled {
+            Some(self.config.socks.port)
+        } else {
+            None
+        }
+    }
+    
+    // 提供给代理模块的Tor上游（仅在Tor启用时可用）
+    pub fn upstream(&self) -> Option<TorUpstream> {
+        self.socks_port().map(|socks_port| TorUpstream {
+            socks_port,
+            dns_port: self.config.dns.enabled.then_some(self.config.dns.port),
+            http_tunnel_port: self.config.http_tunnel.enabled.then_some(self.config.http_tunnel.port),
+        })
+    }
+    
+    // Tor当前的本地监听端口（仅在Tor启用时非空），供防火墙放行仅限Tor的应用
+    pub fn listener_ports(&self) -> Vec<u16> {
+        if !self.enabled {
+            return Vec::new();
+        }
+        [&self.config.socks, &self.config.dns, &self.config.http_tunnel]
+            .into_iter()
+            .filter(|listener| listener.enabled)
+            .map(|listener| listener.port)
+            .collect()
+    }
+    
+    // 获取Tor SOCKS代理地址（仅在Tor启用时可用）
+    pub fn socks_proxy_url(&self) -> Option<String> {
+        self.socks_port().map(|port| format!("socks5h://127.0.0.1:{}", port))
+    }
+    
+    // 在后台读取tor.exe的版本号（安装或更新后需重新读取）
+    pub fn refresh_tor_version(&self) {
+        let tor_version = Arc::clone(&self.tor_version);
+        std::thread::spawn(move || {
+            let version = tor_process::tor_version().ok();
+            if let Ok(mut tor_version) = tor_version.lock() {
+                *tor_version = version;
+            }
+        });
+    }
+    
+    // 由组件管理器提供已安装与最新的专家包版本
+    pub fn set_bundle_versions(&mut self, installed: Option<String>, latest: Option<String>) {
+        self.bundle_versions = (installed, latest);
+    }
+    
+    // 取出用户在Tor页面发起的更新请求
+    pub fn take_update_request(&mut self) -> bool {
+        std::mem::take(&mut self.update_requested)
+    }
+    
+    // 版本信息与更新提示
+    fn render_version(&mut self, ui: &mut Ui) {
+        let tor_version = self.tor_version.lock().ok().and_then(|v| v.clone());
+        let (installed, latest) = &self.bundle_versions;
+        ui.horizontal(|ui| {
+            match (&tor_version, installed) {
+                (Some(version), Some(bundle)) => ui.label(format!("Tor {}（专家包 {}）", version, bundle)),
+                (None, Some(bundle)) => ui.label(format!("专家包 {}", bundle)),
+                _ => ui.label(RichText::new("未安装tor.exe").color(Color32::GRAY)),
+            };
+            match (installed, latest) {
+                (Some(installed), Some(latest)) if installed != latest => {
+                    ui.label(RichText::new(format!("有新版本 {}", latest)).color(Color32::YELLOW));
+                    let hint = if self.enabled { "将先停止Tor，更新完成后自动重新启动" } else { "下载并校验SHA-256后替换" };
+                    if ui.button("更新").on_hover_text(hint).clicked() {
+                        self.update_requested = true;
+                    }
+                }
+                (Some(_), Some(_)) => {
+                    ui.label(RichText::new("已是最新版本").weak());
+                }
+                _ => {}
+            }
+        });
+    }
+    
     // 渲染UI
     pub fn ui(&mut self, ui: &mut Ui) {
-        // ... existing code ...
-    }
+        // 根据引导进度更新连接状态
+        let bootstrap = self.tor_process.as_ref().map(|p| p.bootstrap_state());
+        if let Some(state) = &bootstrap {
+            self.connection_status = match state {
+                BootstrapState::Starting => "正在连接...".to_string(),
+                BootstrapState::Bootstrapping { progress, .. } => format!("正在连接 {}%", progress),
+                BootstrapState::Ready => "已连接".to_string(),
+                BootstrapState::Restarting { .. } => "正在重启...".to_string(),
+                BootstrapState::Failed(_) => "连接失败".to_string(),
+            };
+        }
+        
         ui.horizontal(|ui| {
             ui.heading(RichText::new("Tor洋葱网络").color(TOR_COLOR).strong());
             ui.add_space(10.0);
             
             let status_text = &self.connection_status;
-            let status_color = match status_text.as_str() {
-                "已连接" => Color32::GREEN,
-                "正在连接..." => Color32::YELLOW,
-                _ => Color32::RED,
+            let status_color = if status_text == "已连接" {
+                Color32::GREEN
+            } else if status_text.starts_with("正在连接") || status_text.starts_with("正在重启") {
+                Color32::YELLOW
+            } else {
+                Color32::RED
             };
             ui.label(RichText::new(status_text).color(status_color).strong());
             
@@ -280,14 +1461,37 @@ impl TorModule {
                         }
                     }
                 }
+                if ui.checkbox(&mut self.config.auto_restart, "意外退出时自动重启").changed() {
+                    self.save_restart_required("自动重启设置");
+                }
             });
         });
         
         ui.separator();
         
+        // 引导进度或失败原因
+        match &bootstrap {
+            Some(BootstrapState::Bootstrapping { progress, summary }) => {
+                ui.add(egui::ProgressBar::new(*progress as f32 / 100.0).text(format!("引导 {}% {}", progress, summary)));
+            }
+            Some(BootstrapState::Restarting { attempt, delay }) => {
+                ui.label(RichText::new(format!("Tor进程意外退出，{}秒后进行第{}次重启", delay.as_secs(), attempt)).color(Color32::YELLOW));
+                ui.ctx().request_repaint_after(std::time::Duration::from_secs(1));
+            }
+            Some(BootstrapState::Failed(reason)) => {
+                ui.label(RichText::new(reason).color(Color32::RED));
+            }
+            _ => {}
+        }
+        self.render_version(ui);
+        
+        // 连接测试，需要引导完成后才能通过SOCKS端口访问
+        let proxy_url = if bootstrap == Some(BootstrapState::Ready) { self.socks_proxy_url() } else { None };
+        self.tor_check.ui(ui, proxy_url);
+        self.render_browser_launch(ui, bootstrap == Some(BootstrapState::Ready));
+        
         // Tor简介
         ui.collapsing("关于Tor", |ui| {
-            // ... existing code ...
             ui.label("Tor是一个匿名通信网络，可以帮助您保护隐私和规避网络审查。");
             ui.label("通过Tor，您的网络流量会经过多个中继节点加密传输，使得第三方难以追踪您的真实位置和活动。");
             ui.label("官方网站: https://www.torproject.org/");
@@ -296,11 +1500,74 @@ impl TorModule {
                 if ui.button("赞助Tor项目").clicked() {
                     self.open_donation_page();
                 }
-                    self.open_donation_page();
-                }
                 
-                ui.checkbox(&mut self.run_as_node, "运行节点服务来支持Tor");
-            );
+                if ui.checkbox(&mut self.run_as_node, "运行节点服务来支持Tor").changed() {
+                    self.reserve_ports();
+                }
+            });
+        });
+        
+        // 本地端口
+        ui.collapsing("端口", |ui| {
+            self.render_ports(ui);
+        });
+        
+        // 当前线路
+        ui.collapsing("线路", |ui| {
+            let control = self.tor_process.as_ref().map(|p| p.control_endpoint());
+            self.circuit_viewer.ui(ui, control);
+        });
+        
+        ui.collapsing("流量", |ui| {
+            match &self.tor_process {
+                Some(process) => process.bandwidth().ui(ui),
+                None => {
+                    ui.label("Tor未运行");
+                }
+            }
+            ui.collapsing("历史会话", |ui| {
+                tor_bandwidth::sessions_ui(ui);
+            });
+            ui.collapsing("每日流量", |ui| {
+                tor_bandwidth::daily_ui(ui);
+            });
+        });
+        
+        ui.collapsing("入口守卫", |ui| {
+            self.render_entry_guards(ui);
+        });
+        
+        ui.collapsing("带宽限制", |ui| {
+            if self.run_as_node {
+                ui.label("作为中继运行时，请在节点设置中限制转发带宽");
+            } else {
+                self.render_client_bandwidth(ui);
+            }
+        });
+        
+        ui.collapsing("排除节点", |ui| {
+            self.render_exclude_nodes(ui);
+        });
+        
+        ui.collapsing("地址映射", |ui| {
+            self.render_map_addresses(ui);
+        });
+        
+        ui.collapsing("高级", |ui| {
+            self.render_advanced(ui);
+            ui.separator();
+            ui.label(RichText::new("自定义torrc").strong());
+            self.render_extra_torrc(ui);
+        });
+        
+        ui.collapsing("中继搜索", |ui| {
+            let ready = self.tor_process.as_ref().map(|p| p.bootstrap_state()) == Some(BootstrapState::Ready);
+            let proxy_url = if ready { self.socks_proxy_url() } else { None };
+            self.relay_search.ui(ui, proxy_url);
+        });
+        
+        ui.collapsing("Snowflake志愿者", |ui| {
+            self.render_snowflake_proxy(ui);
         });
         
         // 节点服务设置部分修复
@@ -332,7 +1599,7 @@ impl TorModule {
                             
                             if let Some(response) = response {
                                 if let Some(inner) = response.inner {
-                                    if inner {
+                                    if inner.inner {
                                         self.toggle_node_type();
                                     }
                                 }
@@ -343,10 +1610,28 @@ impl TorModule {
                     }
                 });
                 
-                ui.horizontal(|ui| {
+                let mut apply = false;
+                let relay = &mut self.config.relay;
+                Grid::new("tor_relay_bandwidth_grid").num_columns(2).spacing([10.0, 4.0]).show(ui, |ui| {
                     ui.label("带宽限制:");
-                    ui.add(egui::Slider::new(&mut self.bandwidth_limit, 100..=10240).suffix(" KB/s"));
+                    let response = ui.add(egui::Slider::new(&mut relay.bandwidth_rate_kb, 100..=10240).suffix(" KB/s"))
+                        .on_hover_text("RelayBandwidthRate：为其他用户转发流量的平均速率上限");
+                    apply |= slider_committed(&response);
+                    ui.end_row();
+                    
+                    ui.label("突发上限:");
+                    let response = ui.add(egui::Slider::new(&mut relay.bandwidth_burst_kb, 100..=20480).suffix(" KB/s"))
+                        .on_hover_text("RelayBandwidthBurst：短时间内允许的最高速率，不低于平均速率");
+                    apply |= slider_committed(&response);
+                    ui.end_row();
                 });
+                if apply {
+                    relay.bandwidth_burst_kb = relay.bandwidth_burst_kb.max(relay.bandwidth_rate_kb);
+                    self.apply_bandwidth();
+                }
+                
+                ui.separator();
+                self.render_relay_form(ui);
             });
         }
         
@@ -355,17 +1640,42 @@ impl TorModule {
         // 网桥管理区域
         ui.horizontal(|ui| {
             ui.heading("Tor网桥");
+            if ui.checkbox(&mut self.config.use_bridges, "使用网桥").changed() {
+                self.save_restart_required("网桥设置");
+            }
             ui.with_layout(egui::Layout::right_to_left(egui::Align::Center), |ui| {
                 if ui.button("添加网桥").clicked() {
                     self.edit_mode = true;
                 }
+                if ui.button("批量导入").clicked() {
+                    self.bulk_import.open = true;
+                    self.bulk_import.report = None;
+                }
+                if ui.button("从文件导入").on_hover_text("导入JSON或每行一个网桥的文本文件，跳过已存在的网桥").clicked() {
+                    self.import_bridges_dialog();
+                }
+                if ui.add_enabled(!self.bridges.is_empty(), egui::Button::new("导出")).on_hover_text("保存为JSON（含名称与启用状态）或纯文本").clicked() {
+                    self.export_bridges_dialog();
+                }
+                if ui.button("请求网桥").on_hover_text("从BridgeDB获取obfs4网桥（Tor已连接时通过Tor请求）").clicked() {
+                    // Tor引导完成前SOCKS端口不可用，此时直接连接
+                    let ready = self.tor_process.as_ref().map(|p| p.bootstrap_state()) == Some(BootstrapState::Ready);
+                    let proxy_url = if ready { self.socks_proxy_url() } else { None };
+                    self.bridge_request.open(proxy_url);
+                }
             });
         });
         
+        let received = self.bridge_request.ui(ui.ctx());
+        if !received.is_empty() {
+            self.import_bridge_lines(&received, "BridgeDB");
+        }
+        self.render_bulk_import(ui.ctx());
+        
         // 网桥列表
         ScrollArea::vertical().show(ui, |ui| {
             Grid::new("tor_bridges_grid")
-                .num_columns(4)
+                .num_columns(5)
                 .striped(true)
                 .spacing([10.0, 4.0])
                 .show(ui, |ui| {
@@ -373,6 +1683,7 @@ impl TorModule {
                     ui.label(RichText::new("启用").strong());
                     ui.label(RichText::new("名称").strong());
                     ui.label(RichText::new("类型").strong());
+                    ui.label(RichText::new("传输插件").strong());
                     ui.label(RichText::new("操作").strong());
                     ui.end_row();
                     
@@ -402,6 +1713,28 @@ impl TorModule {
                         };
                         ui.label(type_text);
                         
+                        // 所需传输插件的状态
+                        match PluggableTransport::for_bridge(&bridge.bridge_type) {
+                            None => {
+                                ui.label("不需要");
+                            }
+                            Some(transport) => match transports::status(transport) {
+                                TransportStatus::Checking => {
+                                    ui.spinner();
+                                }
+                                TransportStatus::Ready(version) => {
+                                    ui.label(RichText::new(transport.name()).color(Color32::GREEN)).on_hover_text(version);
+                                }
+                                TransportStatus::Missing => {
+                                    ui.label(RichText::new("未安装").color(Color32::RED))
+                                        .on_hover_text(format!("缺少{}，请在 设置 → 组件管理 中安装Tor", transport.name()));
+                                }
+                                TransportStatus::Broken(reason) => {
+                                    ui.label(RichText::new("无法运行").color(Color32::RED)).on_hover_text(reason);
+                                }
+                            },
+                        }
+                        
                         // 操作按钮
                         let bridge_id = bridge.id; // 再次获取ID避免闭包中的借用冲突
                         ui.horizontal(|ui| {
@@ -418,6 +1751,7 @@ impl TorModule {
                         ui.end_row();
                     }
                 });
+        });
         
         // 网桥详情区域
         if let Some(bridge_id) = self.selected_bridge {
@@ -494,7 +1828,7 @@ impl TorModule {
 
             if let Some(response) = response {
                 if let Some(inner) = response.inner {
-                    if inner && !self.new_bridge_name.is_empty() && !self.new_bridge_address.is_empty() {
+                    if inner.inner && !self.new_bridge_name.is_empty() && !self.new_bridge_address.is_empty() {
                         let new_bridge = TorBridge::new(
                             self.next_bridge_id,
                             &self.new_bridge_name,
@@ -509,6 +1843,8 @@ impl TorModule {
                 }
             }
         }
+    }
+}
 
 // 响应类型定义
 #[derive(Debug)]
@@ -524,8 +1860,6 @@ impl<T> InnerResponse<T> {
 
 // 修改响应处理逻辑
 impl TorModule {
-    // ... existing code ...
-}
     fn handle_response(&self, response: InnerResponse<bool>) -> bool {
         response.inner
     }
@@ -533,4 +1867,4 @@ impl TorModule {
     fn handle_optional_response(&self, response: Option<InnerResponse<bool>>) -> bool {
         response.map(|r| r.inner).unwrap_or(false)
     }
-})
\ No newline at end of file
+}
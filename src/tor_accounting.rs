@@ -0,0 +1,212 @@
+use eframe::egui::{self, Color32, RichText, Ui};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+use anyhow::Result;
+use chrono::{Datelike, Duration, Local, Months, NaiveDateTime, NaiveTime};
+
+use crate::tor_config::{AccountingPeriod, RelayConfig};
+use crate::tor_control::ControlConnection;
+use crate::utils;
+
+// 星期的显示名称，与AccountingStart中的1（周一）到7对应
+const WEEKDAYS: [&str; 7] = ["一", "二", "三", "四", "五", "六", "日"];
+
+// 按设置计算下一次开始新统计周期的时间
+pub fn next_reset(relay: &RelayConfig, now: NaiveDateTime) -> Option<NaiveDateTime> {
+    let time = NaiveTime::from_hms_opt(relay.accounting_start_hour as u32, relay.accounting_start_minute as u32, 0)?;
+    let today = now.date();
+    let candidate = match relay.accounting_period {
+        AccountingPeriod::Day => today.and_time(time),
+        AccountingPeriod::Week => {
+            let offset = relay.accounting_start_day as i64 - today.weekday().number_from_monday() as i64;
+            (today + Duration::days(offset)).and_time(time)
+        }
+        AccountingPeriod::Month => today.with_day(relay.accounting_start_day as u32)?.and_time(time),
+    };
+    if candidate > now {
+        return Some(candidate);
+    }
+    match relay.accounting_period {
+        AccountingPeriod::Day => Some(candidate + Duration::days(1)),
+        AccountingPeriod::Week => Some(candidate + Duration::weeks(1)),
+        AccountingPeriod::Month => candidate.checked_add_months(Months::new(1)),
+    }
+}
+
+// 统计周期编辑器：周期、起始日（日历式选择）与起始时间，返回是否有修改
+pub fn schedule_editor(ui: &mut Ui, relay: &mut RelayConfig) -> bool {
+    let mut changed = false;
+    ui.horizontal(|ui| {
+        ui.label("统计周期:");
+        for period in [AccountingPeriod::Day, AccountingPeriod::Week, AccountingPeriod::Month] {
+            if ui.radio_value(&mut relay.accounting_period, period, period.label()).changed() {
+                relay.accounting_start_day = 1;
+                changed = true;
+            }
+        }
+    });
+
+    match relay.accounting_period {
+        AccountingPeriod::Day => {}
+        AccountingPeriod::Week => {
+            ui.horizontal(|ui| {
+                ui.label("起始日:");
+                for (index, name) in WEEKDAYS.iter().enumerate() {
+                    let day = index as u8 + 1;
+                    changed |= ui.selectable_value(&mut relay.accounting_start_day, day, format!("周{}", name)).changed();
+                }
+            });
+        }
+        AccountingPeriod::Month => {
+            ui.label("起始日（每月）:");
+            egui::Grid::new("tor_accounting_days").spacing([4.0, 4.0]).show(ui, |ui| {
+                for day in 1..=AccountingPeriod::Month.max_day() {
+                    let button = egui::SelectableLabel::new(relay.accounting_start_day == day, format!("{:>2}", day));
+                    if ui.add_sized([28.0, 20.0], button).clicked() && relay.accounting_start_day != day {
+                        relay.accounting_start_day = day;
+                        changed = true;
+                    }
+                    if day % 7 == 0 {
+                        ui.end_row();
+                    }
+                }
+            });
+        }
+    }
+
+    ui.horizontal(|ui| {
+        ui.label("起始时间:");
+        changed |= ui.add(egui::DragValue::new(&mut relay.accounting_start_hour).clamp_range(0..=23).custom_formatter(|v, _| format!("{:02}", v))).changed();
+        ui.label(":");
+        changed |= ui.add(egui::DragValue::new(&mut relay.accounting_start_minute).clamp_range(0..=59).custom_formatter(|v, _| format!("{:02}", v))).changed();
+    });
+
+    if let Some(next) = next_reset(relay, Local::now().naive_local()) {
+        ui.label(RichText::new(format!("下一个周期开始于 {}（AccountingStart {}）", next.format("%Y-%m-%d %H:%M"), relay.accounting_start())).weak());
+    }
+    changed
+}
+
+// 运行中Tor的流量统计状态
+#[derive(Clone, Debug)]
+struct AccountingStatus {
+    enabled: bool,
+    hibernating: String, // awake、soft或hard
+    read: u64,
+    written: u64,
+    read_left: u64,
+    written_left: u64,
+    interval_end: String,
+}
+
+// 两个数字组成的值，如"1024 2048"
+fn parse_pair(value: &str) -> (u64, u64) {
+    let mut numbers = value.split_whitespace().map(|n| n.parse().unwrap_or(0));
+    (numbers.next().unwrap_or(0), numbers.next().unwrap_or(0))
+}
+
+fn fetch(port: u16, password: Option<&str>) -> Result<AccountingStatus> {
+    let mut connection = ControlConnection::connect(port, password)?;
+    let enabled = connection.get_info("accounting/enabled")?.trim() == "1";
+    if !enabled {
+        return Ok(AccountingStatus {
+            enabled,
+            hibernating: String::new(),
+            read: 0,
+            written: 0,
+            read_left: 0,
+            written_left: 0,
+            interval_end: String::new(),
+        });
+    }
+    let (read, written) = parse_pair(&connection.get_info("accounting/bytes")?);
+    let (read_left, written_left) = parse_pair(&connection.get_info("accounting/bytes-left")?);
+    Ok(AccountingStatus {
+        enabled,
+        hibernating: connection.get_info("accounting/hibernating")?.trim().to_string(),
+        read,
+        written,
+        read_left,
+        written_left,
+        interval_end: connection.get_info("accounting/interval-end")?.trim().to_string(),
+    })
+}
+
+// 流量统计与休眠状态
+pub struct AccountingViewer {
+    status: Arc<Mutex<Option<Result<AccountingStatus, String>>>>,
+    refreshing: Arc<AtomicBool>,
+}
+
+impl Default for AccountingViewer {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl AccountingViewer {
+    pub fn new() -> Self {
+        Self {
+            status: Arc::new(Mutex::new(None)),
+            refreshing: Arc::new(AtomicBool::new(false)),
+        }
+    }
+
+    fn refresh(&self, port: u16, password: Option<String>) {
+        if self.refreshing.swap(true, Ordering::SeqCst) {
+            return;
+        }
+        let status = Arc::clone(&self.status);
+        let refreshing = Arc::clone(&self.refreshing);
+        std::thread::spawn(move || {
+            let result = fetch(port, password.as_deref()).map_err(|e| format!("{:#}", e));
+            if let Ok(mut status) = status.lock() {
+                *status = Some(result);
+            }
+            refreshing.store(false, Ordering::SeqCst);
+        });
+    }
+
+    // 渲染状态，control为控制端口与密码（Tor未运行时为None）
+    pub fn ui(&mut self, ui: &mut Ui, control: Option<(u16, Option<String>)>) {
+        let Some((port, password)) = control else {
+            return;
+        };
+        ui.horizontal(|ui| {
+            ui.label("当前状态:");
+            if ui.small_button("查询").clicked() {
+                self.refresh(port, password);
+            }
+            if self.refreshing.load(Ordering::SeqCst) {
+                ui.spinner();
+                ui.ctx().request_repaint_after(std::time::Duration::from_millis(200));
+            }
+        });
+        match self.status.lock().ok().and_then(|s| s.clone()) {
+            None => {}
+            Some(Err(error)) => {
+                ui.label(RichText::new(format!("查询失败: {}", error)).color(Color32::RED));
+            }
+            Some(Ok(status)) if !status.enabled => {
+                ui.label("Tor未启用流量统计");
+            }
+            Some(Ok(status)) => {
+                let (text, color) = match status.hibernating.as_str() {
+                    "awake" => ("正常转发".to_string(), Color32::GREEN),
+                    "soft" => ("即将达到上限，停止接受新连接".to_string(), Color32::YELLOW),
+                    "hard" => ("已达到上限，休眠中".to_string(), Color32::RED),
+                    other => (other.to_string(), Color32::GRAY),
+                };
+                ui.label(RichText::new(text).color(color));
+                ui.label(format!(
+                    "本周期已读取 {}（剩余 {}），已写入 {}（剩余 {}）",
+                    utils::format_bytes(status.read),
+                    utils::format_bytes(status.read_left),
+                    utils::format_bytes(status.written),
+                    utils::format_bytes(status.written_left)
+                ));
+                ui.label(format!("本周期结束于 {}", status.interval_end));
+            }
+        }
+    }
+}
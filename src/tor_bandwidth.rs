@@ -0,0 +1,218 @@
+use eframe::egui::plot::{Bar, BarChart, Legend, Line, Plot, PlotPoints};
+use eframe::egui::{Color32, Grid, RichText, Ui};
+use std::collections::VecDeque;
+use std::sync::Mutex;
+use chrono::Local;
+use once_cell::sync::Lazy;
+use serde::{Deserialize, Serialize};
+
+use crate::traffic::{self, TrafficSource};
+use crate::utils;
+
+// 会话记录文件名
+const SESSIONS_FILE: &str = "tor_sessions.json";
+
+// 历史图表与表格显示的天数
+const DAILY_DISPLAY_DAYS: usize = 30;
+
+// 曲线保留的采样数（BW事件每秒一次）
+const HISTORY_LEN: usize = 300;
+
+// 最多保留的会话记录数
+const MAX_SESSIONS: usize = 30;
+
+// 一次Tor运行期间的流量合计
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct TorSession {
+    pub started: String,
+    pub ended: String,
+    pub read: u64,
+    pub written: u64,
+}
+
+impl utils::VersionedConfig for Vec<TorSession> {
+    const VERSION: u32 = 1;
+}
+
+// 已保存的会话记录，首次使用时从文件读取
+static SESSIONS: Lazy<Mutex<Vec<TorSession>>> = Lazy::new(|| {
+    let sessions = utils::load_saved_config(SESSIONS_FILE).unwrap_or_default();
+    Mutex::new(sessions)
+});
+
+struct MeterState {
+    history: VecDeque<(u64, u64)>, // 每秒读取、写入字节数
+    session: TorSession,
+}
+
+// 根据控制端口BW事件统计的Tor流量
+pub struct BandwidthMeter {
+    state: Mutex<MeterState>,
+}
+
+impl Default for BandwidthMeter {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl BandwidthMeter {
+    pub fn new() -> Self {
+        let now = Local::now().format("%Y-%m-%d %H:%M:%S").to_string();
+        Self {
+            state: Mutex::new(MeterState {
+                history: VecDeque::with_capacity(HISTORY_LEN),
+                session: TorSession {
+                    started: now.clone(),
+                    ended: now,
+                    read: 0,
+                    written: 0,
+                },
+            }),
+        }
+    }
+
+    // 记录一次BW事件
+    pub fn record(&self, read: u64, written: u64) {
+        if let Ok(mut state) = self.state.lock() {
+            if state.history.len() >= HISTORY_LEN {
+                state.history.pop_front();
+            }
+            state.history.push_back((read, written));
+            state.session.read += read;
+            state.session.written += written;
+        }
+        // 包括作为中继转发的流量
+        traffic::report(TrafficSource::Tor, read, written);
+    }
+
+    // 保存本次会话的合计（同一会话重复保存时覆盖之前的记录）
+    pub fn save(&self) -> anyhow::Result<()> {
+        traffic::flush();
+        let session = match self.state.lock() {
+            Ok(mut state) => {
+                state.session.ended = Local::now().format("%Y-%m-%d %H:%M:%S").to_string();
+                state.session.clone()
+            }
+            Err(_) => return Ok(()),
+        };
+        let mut sessions = match SESSIONS.lock() {
+            Ok(sessions) => sessions,
+            Err(_) => return Ok(()),
+        };
+        match sessions.iter_mut().find(|s| s.started == session.started) {
+            Some(existing) => *existing = session,
+            None => sessions.push(session),
+        }
+        let excess = sessions.len().saturating_sub(MAX_SESSIONS);
+        sessions.drain(..excess);
+        let path = utils::get_config_path(SESSIONS_FILE)?;
+        utils::save_versioned_config(&*sessions, &path)
+    }
+
+    // 绘制流量曲线与本次会话合计
+    pub fn ui(&self, ui: &mut Ui) {
+        let (history, session) = match self.state.lock() {
+            Ok(state) => (state.history.clone(), state.session.clone()),
+            Err(_) => return,
+        };
+        ui.label(format!(
+            "本次会话（{} 起）: 下载 {}，上传 {}",
+            session.started,
+            utils::format_bytes(session.read),
+            utils::format_bytes(session.written)
+        ));
+
+        let read: Vec<[f64; 2]> = history.iter().enumerate().map(|(i, (r, _))| [i as f64, *r as f64 / 1024.0]).collect();
+        let written: Vec<[f64; 2]> = history.iter().enumerate().map(|(i, (_, w))| [i as f64, *w as f64 / 1024.0]).collect();
+        Plot::new("tor_bandwidth_plot")
+            .height(140.0)
+            .legend(Legend::default())
+            .allow_drag(false)
+            .allow_zoom(false)
+            .allow_scroll(false)
+            .include_y(0.0)
+            .y_axis_formatter(|value, _| format!("{:.0} KB/s", value))
+            .show(ui, |plot_ui| {
+                plot_ui.line(Line::new(PlotPoints::from(read)).name("读取").color(Color32::GREEN));
+                plot_ui.line(Line::new(PlotPoints::from(written)).name("写入").color(Color32::LIGHT_BLUE));
+            });
+        ui.ctx().request_repaint_after(std::time::Duration::from_secs(1));
+    }
+}
+
+// 历史会话表格
+pub fn sessions_ui(ui: &mut Ui) {
+    let sessions = SESSIONS.lock().map(|s| s.clone()).unwrap_or_default();
+    if sessions.is_empty() {
+        ui.label("暂无记录");
+        return;
+    }
+    Grid::new("tor_sessions_grid").striped(true).num_columns(4).show(ui, |ui| {
+        ui.strong("开始");
+        ui.strong("结束");
+        ui.strong("读取");
+        ui.strong("写入");
+        ui.end_row();
+        for session in sessions.iter().rev() {
+            ui.label(&session.started);
+            ui.label(&session.ended);
+            ui.label(utils::format_bytes(session.read));
+            ui.label(utils::format_bytes(session.written));
+            ui.end_row();
+        }
+    });
+}
+
+// 每日流量图表与表格（最近若干天）
+pub fn daily_ui(ui: &mut Ui) {
+    let totals = traffic::daily_totals(TrafficSource::Tor);
+    if totals.is_empty() {
+        ui.label("暂无记录");
+        return;
+    }
+    let days: Vec<(String, (u64, u64))> = totals.into_iter().rev().take(DAILY_DISPLAY_DAYS).collect();
+    let (read_sum, written_sum) = days.iter().fold((0, 0), |acc, (_, (r, w))| (acc.0 + r, acc.1 + w));
+    ui.label(format!(
+        "最近{}天: 读取 {}，写入 {}",
+        days.len(),
+        utils::format_bytes(read_sum),
+        utils::format_bytes(written_sum)
+    ));
+
+    // 按时间从左到右排列，单位MB
+    let chronological: Vec<&(String, (u64, u64))> = days.iter().rev().collect();
+    let mb = |bytes: u64| bytes as f64 / 1024.0 / 1024.0;
+    let read_bars: Vec<Bar> = chronological.iter().enumerate()
+        .map(|(i, (date, (r, _)))| Bar::new(i as f64 - 0.2, mb(*r)).width(0.4).name(date))
+        .collect();
+    let written_bars: Vec<Bar> = chronological.iter().enumerate()
+        .map(|(i, (date, (_, w)))| Bar::new(i as f64 + 0.2, mb(*w)).width(0.4).name(date))
+        .collect();
+    Plot::new("tor_daily_plot")
+        .height(140.0)
+        .legend(Legend::default())
+        .allow_drag(false)
+        .allow_zoom(false)
+        .allow_scroll(false)
+        .include_y(0.0)
+        .y_axis_formatter(|value, _| format!("{:.0} MB", value))
+        .show(ui, |plot_ui| {
+            plot_ui.bar_chart(BarChart::new(read_bars).name("读取").color(Color32::GREEN));
+            plot_ui.bar_chart(BarChart::new(written_bars).name("写入").color(Color32::LIGHT_BLUE));
+        });
+
+    Grid::new("tor_daily_grid").striped(true).num_columns(3).show(ui, |ui| {
+        ui.strong("日期");
+        ui.strong("读取");
+        ui.strong("写入");
+        ui.end_row();
+        for (date, (read, written)) in &days {
+            ui.label(date);
+            ui.label(utils::format_bytes(*read));
+            ui.label(utils::format_bytes(*written));
+            ui.end_row();
+        }
+    });
+    ui.label(RichText::new("作为中继运行时，统计中包括为他人转发的流量").weak());
+}
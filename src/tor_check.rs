@@ -0,0 +1,120 @@
+use eframe::egui::{Button, Color32, RichText, Ui};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use anyhow::{Context, Result};
+use chrono::Local;
+use serde::Deserialize;
+
+use crate::geoip;
+
+// Tor项目提供的检测接口，返回请求来源IP及其是否为Tor出口
+const CHECK_URL: &str = "https://check.torproject.org/api/ip";
+
+// 检测接口的响应
+#[derive(Clone, Debug, Deserialize)]
+pub struct TorCheckResult {
+    #[serde(rename = "IsTor")]
+    pub is_tor: bool,
+    #[serde(rename = "IP")]
+    pub ip: String,
+}
+
+// 通过Tor的SOCKS端口请求检测接口
+pub fn check(proxy_url: &str) -> Result<TorCheckResult> {
+    let client = reqwest::blocking::Client::builder()
+        .timeout(Duration::from_secs(30))
+        .proxy(reqwest::Proxy::all(proxy_url).context("Invalid proxy url")?)
+        .build()
+        .context("Failed to build http client")?;
+    client
+        .get(CHECK_URL)
+        .send()
+        .context("Failed to reach check.torproject.org")?
+        .json()
+        .context("Failed to parse check.torproject.org response")
+}
+
+#[derive(Clone, Debug)]
+enum CheckState {
+    Idle,
+    Running,
+    Done { result: TorCheckResult, country: Option<String>, time: String },
+    Failed(String),
+}
+
+// "Tor连接测试"
+pub struct TorCheck {
+    state: Arc<Mutex<CheckState>>,
+}
+
+impl Default for TorCheck {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl TorCheck {
+    pub fn new() -> Self {
+        Self {
+            state: Arc::new(Mutex::new(CheckState::Idle)),
+        }
+    }
+
+    // 在后台线程中执行检测
+    fn start(&self, proxy_url: String) {
+        if let Ok(mut state) = self.state.lock() {
+            *state = CheckState::Running;
+        }
+        let state = Arc::clone(&self.state);
+        std::thread::spawn(move || {
+            let next = match check(&proxy_url) {
+                Ok(result) => CheckState::Done {
+                    country: result.ip.parse().ok().and_then(geoip::describe),
+                    result,
+                    time: Local::now().format("%H:%M:%S").to_string(),
+                },
+                Err(e) => CheckState::Failed(format!("{:#}", e)),
+            };
+            if let Ok(mut state) = state.lock() {
+                *state = next;
+            }
+        });
+    }
+
+    // 渲染测试按钮与结果，proxy_url为Tor的SOCKS地址（Tor未运行时为None）
+    pub fn ui(&mut self, ui: &mut Ui, proxy_url: Option<String>) {
+        let state = self.state.lock().map(|s| s.clone()).unwrap_or(CheckState::Idle);
+        ui.horizontal(|ui| {
+            let running = matches!(state, CheckState::Running);
+            let button = ui.add_enabled(!running && proxy_url.is_some(), Button::new("检测Tor连接"));
+            if button.on_disabled_hover_text("请先启动Tor并等待连接完成").clicked() {
+                if let Some(proxy_url) = proxy_url {
+                    self.start(proxy_url);
+                }
+            }
+
+            match &state {
+                CheckState::Idle => {}
+                CheckState::Running => {
+                    ui.spinner();
+                    ui.label("正在通过Tor访问check.torproject.org...");
+                    ui.ctx().request_repaint_after(Duration::from_millis(200));
+                }
+                CheckState::Done { result, country, time } => {
+                    if result.is_tor {
+                        ui.label(RichText::new("✔ 已确认通过Tor访问").color(Color32::GREEN));
+                    } else {
+                        ui.label(RichText::new("✖ 流量未经过Tor").color(Color32::RED));
+                    }
+                    match country {
+                        Some(country) => ui.label(format!("出口IP: {} {}（{}）", result.ip, country, time)),
+                        None => ui.label(format!("出口IP: {}（{}）", result.ip, time)),
+                    };
+                }
+                CheckState::Failed(error) => {
+                    ui.label(RichText::new(format!("检测失败: {}", error)).color(Color32::RED));
+                }
+            }
+        });
+    }
+}
@@ -0,0 +1,285 @@
+use eframe::egui::{self, Color32, RichText, Ui};
+use std::collections::{HashMap, HashSet};
+use std::net::IpAddr;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+use anyhow::Result;
+
+use crate::geoip;
+use crate::tor_control::{self, ControlConnection};
+
+// 自动刷新间隔
+const AUTO_REFRESH_INTERVAL: Duration = Duration::from_secs(5);
+
+// 线路中的一个中继
+#[derive(Clone, Debug)]
+pub struct Hop {
+    pub fingerprint: String,
+    pub nickname: String,
+    pub address: Option<IpAddr>,
+}
+
+impl Hop {
+    pub fn country(&self) -> Option<String> {
+        self.address.and_then(geoip::lookup)
+    }
+}
+
+// 一条Tor线路
+#[derive(Clone, Debug)]
+pub struct Circuit {
+    pub id: String,
+    pub status: String,
+    pub purpose: String,
+    pub hops: Vec<Hop>,
+}
+
+// 解析circuit-status中的一行，如"12 BUILT $AAAA~relay1,$BBBB~relay2 BUILD_FLAGS=NEED_CAPACITY PURPOSE=GENERAL"
+fn parse_circuit(line: &str) -> Option<Circuit> {
+    let mut fields = line.splitn(3, ' ');
+    let id = fields.next()?.to_string();
+    let status = fields.next()?.to_string();
+    let rest = fields.next().unwrap_or("");
+
+    // 刚开始建立的线路可能还没有路径
+    let (path, keywords) = match rest.split_once(' ') {
+        Some((first, remaining)) if !first.contains('=') => (first, remaining),
+        _ if !rest.contains('=') => (rest, ""),
+        _ => ("", rest),
+    };
+    let hops = path
+        .split(',')
+        .filter(|hop| !hop.is_empty())
+        .map(|hop| {
+            let hop = hop.trim_start_matches('$');
+            let (fingerprint, nickname) = hop.split_once(['~', '=']).unwrap_or((hop, ""));
+            Hop {
+                fingerprint: fingerprint.to_string(),
+                nickname: nickname.to_string(),
+                address: None,
+            }
+        })
+        .collect();
+    let purpose = tor_control::parse_keywords(keywords).remove("PURPOSE").unwrap_or_default();
+    Some(Circuit { id, status, purpose, hops })
+}
+
+// 从共识中查询中继的IP地址，如"r nickname identity digest 2024-01-01 00:00:00 1.2.3.4 9001 0"
+pub fn relay_address(connection: &mut ControlConnection, fingerprint: &str) -> Option<IpAddr> {
+    let entry = connection.get_info(&format!("ns/id/{}", fingerprint)).ok()?;
+    let router = entry.lines().find(|line| line.starts_with("r "))?;
+    router.split_whitespace().nth(6)?.parse().ok()
+}
+
+// 获取当前所有线路，中继地址查询结果缓存在addresses中
+fn fetch(port: u16, password: Option<&str>, addresses: &mut HashMap<String, Option<IpAddr>>) -> Result<Vec<Circuit>> {
+    let mut connection = ControlConnection::connect(port, password)?;
+    let status = connection.get_info("circuit-status")?;
+    let mut circuits: Vec<Circuit> = status.lines().filter_map(parse_circuit).collect();
+    for circuit in &mut circuits {
+        for hop in &mut circuit.hops {
+            hop.address = *addresses
+                .entry(hop.fingerprint.clone())
+                .or_insert_with(|| relay_address(&mut connection, &hop.fingerprint));
+        }
+    }
+    Ok(circuits)
+}
+
+// 关闭指定线路，Tor会在需要时建立新线路
+fn close_circuit(port: u16, password: Option<&str>, id: &str) -> Result<()> {
+    let mut connection = ControlConnection::connect(port, password)?;
+    connection.command(&format!("CLOSECIRCUIT {}", id))?;
+    Ok(())
+}
+
+// 最近一次关闭线路的结果
+#[derive(Clone, Debug)]
+enum CloseState {
+    Closing(String),
+    Closed { id: String, existing: HashSet<String> }, // 关闭时已存在的线路，之后出现的为新建线路
+    Failed { id: String, error: String },
+}
+
+// Tor线路查看器
+pub struct CircuitViewer {
+    circuits: Arc<Mutex<Vec<Circuit>>>,
+    error: Arc<Mutex<Option<String>>>,
+    addresses: Arc<Mutex<HashMap<String, Option<IpAddr>>>>,
+    refreshing: Arc<AtomicBool>,
+    last_refresh: Option<Instant>,
+    auto_refresh: bool,
+    close_state: Arc<Mutex<Option<CloseState>>>,
+}
+
+impl Default for CircuitViewer {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl CircuitViewer {
+    pub fn new() -> Self {
+        Self {
+            circuits: Arc::new(Mutex::new(Vec::new())),
+            error: Arc::new(Mutex::new(None)),
+            addresses: Arc::new(Mutex::new(HashMap::new())),
+            refreshing: Arc::new(AtomicBool::new(false)),
+            last_refresh: None,
+            auto_refresh: true,
+            close_state: Arc::new(Mutex::new(None)),
+        }
+    }
+
+    // 在后台线程中通过控制端口刷新线路列表
+    fn refresh(&mut self, port: u16, password: Option<String>) {
+        if self.refreshing.swap(true, Ordering::SeqCst) {
+            return;
+        }
+        self.last_refresh = Some(Instant::now());
+        let circuits = Arc::clone(&self.circuits);
+        let error = Arc::clone(&self.error);
+        let addresses = Arc::clone(&self.addresses);
+        let refreshing = Arc::clone(&self.refreshing);
+        std::thread::spawn(move || {
+            // 查询地址期间不持有缓存锁，避免阻塞界面
+            let mut cache = addresses.lock().map(|a| a.clone()).unwrap_or_default();
+            let result = fetch(port, password.as_deref(), &mut cache);
+            if let Ok(mut addresses) = addresses.lock() {
+                *addresses = cache;
+            }
+            match result {
+                Ok(list) => {
+                    if let Ok(mut circuits) = circuits.lock() {
+                        *circuits = list;
+                    }
+                    if let Ok(mut error) = error.lock() {
+                        *error = None;
+                    }
+                }
+                Err(e) => {
+                    if let Ok(mut error) = error.lock() {
+                        *error = Some(format!("{:#}", e));
+                    }
+                }
+            }
+            refreshing.store(false, Ordering::SeqCst);
+        });
+    }
+
+    // 在后台关闭线路，完成后立即刷新列表
+    fn close(&mut self, port: u16, password: Option<String>, id: String) {
+        if let Ok(mut state) = self.close_state.lock() {
+            *state = Some(CloseState::Closing(id.clone()));
+        }
+        let circuits = Arc::clone(&self.circuits);
+        let close_state = Arc::clone(&self.close_state);
+        std::thread::spawn(move || {
+            let next = match close_circuit(port, password.as_deref(), &id) {
+                Ok(()) => {
+                    let existing = circuits.lock().map(|c| c.iter().map(|c| c.id.clone()).collect()).unwrap_or_default();
+                    CloseState::Closed { id, existing }
+                }
+                Err(e) => CloseState::Failed { id, error: format!("{:#}", e) },
+            };
+            if let Ok(mut state) = close_state.lock() {
+                *state = Some(next);
+            }
+        });
+        self.last_refresh = None;
+    }
+
+    // 渲染线路列表，control为控制端口与密码（Tor未运行时为None）
+    pub fn ui(&mut self, ui: &mut Ui, control: Option<(u16, Option<String>)>) {
+        let Some((port, password)) = control else {
+            ui.label("Tor未运行");
+            return;
+        };
+
+        let due = self.last_refresh.map(|t| t.elapsed() >= AUTO_REFRESH_INTERVAL).unwrap_or(true);
+        ui.horizontal(|ui| {
+            if ui.button("刷新").clicked() || (self.auto_refresh && due) {
+                self.refresh(port, password.clone());
+            }
+            ui.checkbox(&mut self.auto_refresh, format!("每{}秒自动刷新", AUTO_REFRESH_INTERVAL.as_secs()));
+            if self.refreshing.load(Ordering::SeqCst) {
+                ui.spinner();
+            }
+        });
+        if self.auto_refresh {
+            ui.ctx().request_repaint_after(AUTO_REFRESH_INTERVAL);
+        }
+
+        if let Some(error) = self.error.lock().ok().and_then(|e| e.clone()) {
+            ui.label(RichText::new(format!("获取线路失败: {}", error)).color(Color32::RED));
+        }
+
+        let close_state = self.close_state.lock().ok().and_then(|s| s.clone());
+        let mut existing_ids = HashSet::new();
+        match &close_state {
+            Some(CloseState::Closing(id)) => {
+                ui.label(format!("正在关闭线路 {}...", id));
+            }
+            Some(CloseState::Closed { id, existing }) => {
+                ui.label(RichText::new(format!("已关闭线路 {}，Tor会按需建立新线路（标记为“新”）", id)).color(Color32::GREEN));
+                existing_ids = existing.clone();
+            }
+            Some(CloseState::Failed { id, error }) => {
+                ui.label(RichText::new(format!("关闭线路 {} 失败: {}", id, error)).color(Color32::RED));
+            }
+            None => {}
+        }
+
+        let circuits = self.circuits.lock().map(|c| c.clone()).unwrap_or_default();
+        if circuits.is_empty() {
+            ui.label("暂无线路");
+            return;
+        }
+
+        egui::ScrollArea::vertical().id_source("tor_circuits").max_height(300.0).show(ui, |ui| {
+            egui::Grid::new("tor_circuit_grid").striped(true).num_columns(4).show(ui, |ui| {
+                ui.strong("ID");
+                ui.strong("状态");
+                ui.strong("用途");
+                ui.strong("路径（入口 → 出口）");
+                ui.end_row();
+
+                let mut close_request = None;
+                for circuit in &circuits {
+                    ui.horizontal(|ui| {
+                        ui.add(egui::Label::new(&circuit.id).sense(egui::Sense::click()))
+                            .on_hover_text("右键可关闭此线路")
+                            .context_menu(|ui| {
+                                if ui.button("关闭线路").clicked() {
+                                    close_request = Some(circuit.id.clone());
+                                    ui.close_menu();
+                                }
+                            });
+                        if !existing_ids.is_empty() && !existing_ids.contains(&circuit.id) {
+                            ui.label(RichText::new("新").color(Color32::LIGHT_BLUE));
+                        }
+                    });
+                    let color = if circuit.status == "BUILT" { Color32::GREEN } else { Color32::YELLOW };
+                    ui.label(RichText::new(&circuit.status).color(color));
+                    ui.label(&circuit.purpose);
+                    ui.vertical(|ui| {
+                        for hop in &circuit.hops {
+                            let country = hop.country().map(|code| geoip::country_label(&code)).unwrap_or_else(|| "未知".to_string());
+                            let address = hop.address.map(|a| a.to_string()).unwrap_or_else(|| "-".to_string());
+                            ui.horizontal(|ui| {
+                                ui.label(format!("[{}]", country));
+                                ui.label(&hop.nickname).on_hover_text(format!("地址: {}", address));
+                                ui.label(RichText::new(&hop.fingerprint).monospace().small());
+                            });
+                        }
+                    });
+                    ui.end_row();
+                }
+                if let Some(id) = close_request {
+                    self.close(port, password.clone(), id);
+                }
+            });
+        });
+    }
+}
@@ -0,0 +1,515 @@
+use serde::{Deserialize, Serialize};
+
+use crate::utils;
+
+// Tor设置配置文件名
+const TOR_CONFIG_FILE: &str = "tor.json";
+
+// Tor的本地监听端口
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct TorListener {
+    pub enabled: bool,
+    pub port: u16,
+    // 流隔离：满足条件的连接使用不同线路
+    #[serde(default)]
+    pub isolate_dest_addr: bool,
+    #[serde(default)]
+    pub isolate_dest_port: bool,
+    #[serde(default)]
+    pub isolate_client_protocol: bool,
+}
+
+impl TorListener {
+    fn new(enabled: bool, port: u16) -> Self {
+        Self {
+            enabled,
+            port,
+            isolate_dest_addr: false,
+            isolate_dest_port: false,
+            isolate_client_protocol: false,
+        }
+    }
+
+    // torrc中的端口配置，如"127.0.0.1:9050 IsolateDestAddr"
+    fn torrc_value(&self) -> String {
+        let mut value = format!("127.0.0.1:{}", self.port);
+        for (enabled, flag) in [
+            (self.isolate_dest_addr, "IsolateDestAddr"),
+            (self.isolate_dest_port, "IsolateDestPort"),
+            (self.isolate_client_protocol, "IsolateClientProtocol"),
+        ] {
+            if enabled {
+                value.push(' ');
+                value.push_str(flag);
+            }
+        }
+        value
+    }
+}
+
+// 出口策略中的一条规则
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct ExitRule {
+    pub accept: bool,
+    pub pattern: String, // 地址:端口，如"*:443"、"192.0.2.0/24:*"
+}
+
+// 流量统计周期（AccountingStart）
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+pub enum AccountingPeriod {
+    Day,
+    Week,
+    Month,
+}
+
+impl AccountingPeriod {
+    pub fn label(&self) -> &'static str {
+        match self {
+            AccountingPeriod::Day => "每天",
+            AccountingPeriod::Week => "每周",
+            AccountingPeriod::Month => "每月",
+        }
+    }
+
+    // 起始日的取值范围：每周为1（周一）到7，每月为1到28
+    pub fn max_day(&self) -> u8 {
+        match self {
+            AccountingPeriod::Day => 1,
+            AccountingPeriod::Week => 7,
+            AccountingPeriod::Month => 28,
+        }
+    }
+}
+
+// 中继节点设置
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(default)]
+pub struct RelayConfig {
+    pub nickname: String,
+    pub contact_info: String,
+    pub or_port: u16,
+    pub accounting_max_gb: u32, // 每个统计周期的流量上限，0表示不限制
+    pub accounting_period: AccountingPeriod,
+    pub accounting_start_day: u8,    // 周期起始日，含义取决于统计周期
+    pub accounting_start_hour: u8,
+    pub accounting_start_minute: u8,
+    pub bandwidth_rate_kb: u32,  // 转发流量的平均速率上限（KB/s）
+    pub bandwidth_burst_kb: u32, // 允许的突发速率（KB/s），不低于平均速率
+    pub exit_policy: Vec<ExitRule>,
+}
+
+impl Default for RelayConfig {
+    fn default() -> Self {
+        Self {
+            nickname: String::new(),
+            contact_info: String::new(),
+            or_port: 9001,
+            accounting_max_gb: 0,
+            accounting_period: AccountingPeriod::Month,
+            accounting_start_day: 1,
+            accounting_start_hour: 0,
+            accounting_start_minute: 0,
+            bandwidth_rate_kb: 1024,
+            bandwidth_burst_kb: 2048,
+            exit_policy: vec![
+                ExitRule { accept: true, pattern: "*:80".to_string() },
+                ExitRule { accept: true, pattern: "*:443".to_string() },
+            ],
+        }
+    }
+}
+
+// 校验出口策略中的端口部分："*"、"80"或"1000-2000"
+fn valid_port_spec(spec: &str) -> bool {
+    if spec == "*" {
+        return true;
+    }
+    let parse = |p: &str| p.parse::<u16>().ok().filter(|&p| p > 0);
+    match spec.split_once('-') {
+        Some((start, end)) => matches!((parse(start), parse(end)), (Some(start), Some(end)) if start <= end),
+        None => parse(spec).is_some(),
+    }
+}
+
+// 校验出口策略中的地址部分："*"、IP或IP/掩码位数
+fn valid_address_spec(spec: &str) -> bool {
+    if matches!(spec, "*" | "*4" | "*6") {
+        return true;
+    }
+    let (address, mask) = match spec.split_once('/') {
+        Some((address, mask)) => (address, Some(mask)),
+        None => (spec, None),
+    };
+    let address = address.trim_start_matches('[').trim_end_matches(']');
+    let max_mask = match address.parse::<std::net::IpAddr>() {
+        Ok(std::net::IpAddr::V4(_)) => 32,
+        Ok(std::net::IpAddr::V6(_)) => 128,
+        Err(_) => return false,
+    };
+    mask.map(|m| m.parse::<u8>().map(|m| m <= max_mask).unwrap_or(false)).unwrap_or(true)
+}
+
+impl ExitRule {
+    pub fn is_valid(&self) -> bool {
+        match self.pattern.trim().rsplit_once(':') {
+            Some((address, port)) => valid_address_spec(address) && valid_port_spec(port),
+            None => false,
+        }
+    }
+}
+
+impl RelayConfig {
+    // 校验设置，返回所有错误
+    pub fn validate(&self, exit: bool) -> Vec<String> {
+        let mut errors = Vec::new();
+        let nickname = self.nickname.trim();
+        if nickname.is_empty() || nickname.len() > 19 || !nickname.chars().all(|c| c.is_ascii_alphanumeric()) {
+            errors.push("昵称须为1到19位英文字母或数字".to_string());
+        }
+        if self.contact_info.contains(['\n', '\r']) {
+            errors.push("联系方式不能包含换行".to_string());
+        }
+        if self.or_port == 0 {
+            errors.push("ORPort无效".to_string());
+        }
+        if self.accounting_max_gb > 0 {
+            let max_day = self.accounting_period.max_day();
+            if self.accounting_period != AccountingPeriod::Day && !(1..=max_day).contains(&self.accounting_start_day) {
+                errors.push(format!("统计周期起始日须在1到{}之间", max_day));
+            }
+            if self.accounting_start_hour > 23 || self.accounting_start_minute > 59 {
+                errors.push("统计周期起始时间无效".to_string());
+            }
+        }
+        if exit {
+            for rule in self.exit_policy.iter().filter(|r| !r.is_valid()) {
+                errors.push(format!("出口规则格式无效: {}", rule.pattern));
+            }
+        }
+        errors
+    }
+
+    // AccountingStart的值，如"month 1 00:00"、"week 1 08:30"、"day 00:00"
+    pub fn accounting_start(&self) -> String {
+        let time = format!("{:02}:{:02}", self.accounting_start_hour, self.accounting_start_minute);
+        match self.accounting_period {
+            AccountingPeriod::Day => format!("day {}", time),
+            AccountingPeriod::Week => format!("week {} {}", self.accounting_start_day, time),
+            AccountingPeriod::Month => format!("month {} {}", self.accounting_start_day, time),
+        }
+    }
+
+    // 写入torrc的配置行，exit为true时作为出口节点运行
+    pub fn torrc_lines(&self, exit: bool) -> Vec<String> {
+        let mut lines = vec![
+            format!("Nickname {}", self.nickname.trim()),
+            format!("ORPort {}", self.or_port),
+        ];
+        if !self.contact_info.trim().is_empty() {
+            lines.push(format!("ContactInfo {}", self.contact_info.trim()));
+        }
+        if self.accounting_max_gb > 0 {
+            lines.push(format!("AccountingMax {} GBytes", self.accounting_max_gb));
+            lines.push(format!("AccountingStart {}", self.accounting_start()));
+        }
+        if exit {
+            lines.push("ExitRelay 1".to_string());
+            let mut policy: Vec<String> = self
+                .exit_policy
+                .iter()
+                .map(|rule| format!("{} {}", if rule.accept { "accept" } else { "reject" }, rule.pattern.trim()))
+                .collect();
+            // 未列出的一律拒绝
+            policy.push("reject *:*".to_string());
+            lines.push(format!("ExitPolicy {}", policy.join(", ")));
+        } else {
+            lines.push("ExitRelay 0".to_string());
+            lines.push("ExitPolicy reject *:*".to_string());
+        }
+        lines
+    }
+}
+
+// 地址映射规则（MapAddress），如将example.com映射到某个.onion地址
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct MapAddressRule {
+    pub from: String,
+    pub to: String,
+}
+
+impl MapAddressRule {
+    // 校验规则，返回错误原因
+    pub fn validate(&self) -> Result<(), String> {
+        let valid_host = |host: &str| {
+            let host = host.strip_prefix("*.").unwrap_or(host);
+            !host.is_empty()
+                && !host.starts_with('.')
+                && host.chars().all(|c| c.is_ascii_alphanumeric() || matches!(c, '.' | '-' | ':' | '[' | ']'))
+        };
+        if !valid_host(&self.from) {
+            return Err(format!("原地址格式无效 \"{}\"", self.from));
+        }
+        if !valid_host(&self.to) {
+            return Err(format!("目标地址格式无效 \"{}\"", self.to));
+        }
+        // 通配规则两边都必须以"*."开头
+        if self.from.starts_with("*.") != self.to.starts_with("*.") {
+            return Err("通配规则的原地址和目标地址都须以\"*.\"开头".to_string());
+        }
+        Ok(())
+    }
+}
+
+// 控制端口认证方式
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+pub enum ControlAuth {
+    Cookie,   // 读取数据目录中的control_auth_cookie
+    Password, // HashedControlPassword
+}
+
+// 连接填充（ConnectionPadding），用于对抗流量分析
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+pub enum ConnectionPadding {
+    Auto,     // 由共识参数决定
+    Enabled,  // 即使中继不支持也强制填充
+    Disabled, // 完全关闭填充
+}
+
+impl ConnectionPadding {
+    fn torrc_value(&self) -> &'static str {
+        match self {
+            ConnectionPadding::Auto => "auto",
+            ConnectionPadding::Enabled => "1",
+            ConnectionPadding::Disabled => "0",
+        }
+    }
+}
+
+// Snowflake志愿者代理设置
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(default)]
+pub struct SnowflakeProxyConfig {
+    pub enabled: bool,
+    pub capacity: u32,     // 同时服务的用户数上限，0表示不限制
+    pub helped_total: u64, // 累计帮助的用户数
+}
+
+impl Default for SnowflakeProxyConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            capacity: 10,
+            helped_total: 0,
+        }
+    }
+}
+
+// 需要保存的Tor设置，启动时写入torrc
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(default)]
+pub struct TorConfig {
+    pub socks: TorListener, // SOCKS端口始终启用
+    pub dns: TorListener,
+    pub http_tunnel: TorListener,
+    pub control_port: u16,
+    pub control_auth: ControlAuth,
+    #[serde(with = "crate::utils::secret_string")]
+    pub control_password: String,        // 本程序连接控制端口时使用的密码
+    pub hashed_control_password: String, // 由tor --hash-password生成，写入torrc
+    pub use_bridges: bool,
+    pub exclude_countries: Vec<String>,    // 国家代码，如"RU"
+    pub exclude_fingerprints: Vec<String>, // 40位十六进制中继指纹
+    pub strict_nodes: bool,                // 无法避开排除节点时宁可连接失败
+    pub entry_nodes: Vec<String>,          // 固定使用的入口守卫指纹
+    pub relay: RelayConfig,
+    pub auto_restart: bool, // tor.exe意外退出时按退避策略自动重启
+    pub bandwidth_limit_kb: u32, // 作为客户端时的速率上限（KB/s），0表示不限制
+    pub dns_over_tor: bool,      // DNSCrypt未运行时将系统DNS指向Tor的DNSPort
+    pub snowflake_proxy: SnowflakeProxyConfig,
+    pub connection_padding: ConnectionPadding,
+    pub reduced_connection_padding: bool, // 减少填充以节省流量（适合移动网络）
+    pub avoid_disk_writes: bool,          // 尽量减少写入数据目录的次数
+    pub browser_path: String,             // 通过Tor打开的浏览器，为空时使用系统默认浏览器
+    pub extra_torrc: String,              // 追加到生成的torrc末尾的自定义配置行
+    pub map_addresses: Vec<MapAddressRule>,
+}
+
+impl Default for TorConfig {
+    fn default() -> Self {
+        Self {
+            socks: TorListener::new(true, 9050),
+            dns: TorListener::new(false, 5400),
+            http_tunnel: TorListener::new(false, 8118),
+            control_port: 9051,
+            control_auth: ControlAuth::Cookie,
+            control_password: String::new(),
+            hashed_control_password: String::new(),
+            use_bridges: false,
+            exclude_countries: Vec::new(),
+            exclude_fingerprints: Vec::new(),
+            strict_nodes: false,
+            entry_nodes: Vec::new(),
+            relay: RelayConfig::default(),
+            auto_restart: true,
+            bandwidth_limit_kb: 0,
+            dns_over_tor: false,
+            snowflake_proxy: SnowflakeProxyConfig::default(),
+            connection_padding: ConnectionPadding::Auto,
+            reduced_connection_padding: false,
+            avoid_disk_writes: false,
+            browser_path: String::new(),
+            extra_torrc: String::new(),
+            map_addresses: Vec::new(),
+        }
+    }
+}
+
+impl utils::VersionedConfig for TorConfig {
+    const VERSION: u32 = 1;
+}
+
+impl TorConfig {
+    // 读取保存的设置，文件不存在或损坏时使用默认值（损坏的文件会先备份）
+    pub fn load() -> Self {
+        utils::load_saved_config(TOR_CONFIG_FILE).unwrap_or_default()
+    }
+
+    pub fn save(&self) -> anyhow::Result<()> {
+        let path = utils::get_config_path(TOR_CONFIG_FILE)?;
+        utils::save_versioned_config(self, &path)
+    }
+
+    // ExcludeNodes的值，如"{ru},{by},$AAAA..."；没有排除项时为None
+    pub fn exclude_nodes(&self) -> Option<String> {
+        let nodes: Vec<String> = self
+            .exclude_countries
+            .iter()
+            .map(|code| format!("{{{}}}", code.to_lowercase()))
+            .chain(self.exclude_fingerprints.iter().map(|fp| format!("${}", fp)))
+            .collect();
+        if nodes.is_empty() {
+            None
+        } else {
+            Some(nodes.join(","))
+        }
+    }
+
+    // 连接控制端口时使用的密码，Cookie认证时为None
+    pub fn control_password(&self) -> Option<String> {
+        match self.control_auth {
+            ControlAuth::Cookie => None,
+            ControlAuth::Password => Some(self.control_password.clone()),
+        }
+    }
+
+    // EntryNodes的值；使用网桥时网桥即为入口，固定的守卫不生效
+    pub fn entry_nodes(&self) -> Option<String> {
+        if self.use_bridges || self.entry_nodes.is_empty() {
+            return None;
+        }
+        Some(self.entry_nodes.iter().map(|fp| format!("${}", fp)).collect::<Vec<_>>().join(","))
+    }
+
+    // 带宽限制配置项，值为None表示恢复Tor的默认值；relay为true时只限制中继转发的流量
+    pub fn bandwidth_conf(&self, relay: bool) -> Vec<(&'static str, Option<String>)> {
+        let kbytes = |kb: u32| (kb > 0).then(|| format!("{} KBytes", kb));
+        if relay {
+            vec![
+                ("BandwidthRate", None),
+                ("RelayBandwidthRate", kbytes(self.relay.bandwidth_rate_kb)),
+                ("RelayBandwidthBurst", kbytes(self.relay.bandwidth_burst_kb.max(self.relay.bandwidth_rate_kb))),
+            ]
+        } else {
+            vec![
+                ("BandwidthRate", kbytes(self.bandwidth_limit_kb)),
+                ("RelayBandwidthRate", None),
+                ("RelayBandwidthBurst", None),
+            ]
+        }
+    }
+
+    // 写入torrc的带宽限制配置行
+    pub fn bandwidth_lines(&self, relay: bool) -> Vec<String> {
+        conf_lines(self.bandwidth_conf(relay))
+    }
+
+    // 高级选项（连接填充、减少磁盘写入），值为None表示恢复Tor的默认值
+    pub fn advanced_conf(&self) -> Vec<(&'static str, Option<String>)> {
+        let flag = |enabled: bool| enabled.then(|| "1".to_string());
+        let padding = (self.connection_padding != ConnectionPadding::Auto).then(|| self.connection_padding.torrc_value().to_string());
+        vec![
+            ("ConnectionPadding", padding),
+            ("ReducedConnectionPadding", flag(self.reduced_connection_padding)),
+            ("AvoidDiskWrites", flag(self.avoid_disk_writes)),
+        ]
+    }
+
+    // 地址映射配置项，没有规则时清除所有映射
+    pub fn map_address_conf(&self) -> Vec<(&'static str, Option<String>)> {
+        if self.map_addresses.is_empty() {
+            return vec![("MapAddress", None)];
+        }
+        self.map_addresses
+            .iter()
+            .map(|rule| ("MapAddress", Some(format!("{} {}", rule.from, rule.to))))
+            .collect()
+    }
+
+    // 写入torrc的配置行
+    pub fn torrc_lines(&self) -> Vec<String> {
+        let mut lines = vec![format!("SocksPort {}", self.socks.torrc_value())];
+        if self.dns.enabled {
+            lines.push(format!("DNSPort {}", self.dns.torrc_value()));
+        }
+        if self.http_tunnel.enabled {
+            lines.push(format!("HTTPTunnelPort {}", self.http_tunnel.torrc_value()));
+        }
+        lines.push(format!("ControlPort 127.0.0.1:{}", self.control_port));
+        match self.control_auth {
+            ControlAuth::Cookie => lines.push("CookieAuthentication 1".to_string()),
+            ControlAuth::Password => {
+                lines.push("CookieAuthentication 0".to_string());
+                lines.push(format!("HashedControlPassword {}", self.hashed_control_password));
+            }
+        }
+        if let Some(nodes) = self.entry_nodes() {
+            lines.push(format!("EntryNodes {}", nodes));
+        }
+        if let Some(nodes) = self.exclude_nodes() {
+            lines.push(format!("ExcludeNodes {}", nodes));
+            if self.strict_nodes {
+                lines.push("StrictNodes 1".to_string());
+            }
+        }
+        lines.extend(conf_lines(self.map_address_conf()));
+        lines.extend(conf_lines(self.advanced_conf()));
+        lines
+    }
+}
+
+// 将配置项转为torrc行，跳过使用默认值的项
+fn conf_lines(conf: Vec<(&'static str, Option<String>)>) -> Vec<String> {
+    conf.into_iter()
+        .filter_map(|(option, value)| value.map(|value| format!("{} {}", option, value)))
+        .collect()
+}
+
+// 校验并规范化国家代码（两位字母，转为大写）
+pub fn normalize_country(input: &str) -> Option<String> {
+    let code = input.trim().trim_matches(|c| c == '{' || c == '}');
+    if code.len() == 2 && code.chars().all(|c| c.is_ascii_alphabetic()) {
+        Some(code.to_uppercase())
+    } else {
+        None
+    }
+}
+
+// 校验并规范化中继指纹（可带$前缀，转为大写）
+pub fn normalize_fingerprint(input: &str) -> Option<String> {
+    let fingerprint = input.trim().trim_start_matches('$');
+    if fingerprint.len() == 40 && fingerprint.chars().all(|c| c.is_ascii_hexdigit()) {
+        Some(fingerprint.to_uppercase())
+    } else {
+        None
+    }
+}
@@ -0,0 +1,225 @@
+use std::collections::HashMap;
+use std::fs;
+use std::io::{BufRead, BufReader, Write};
+use std::net::{SocketAddr, TcpStream};
+use std::time::Duration;
+use anyhow::{anyhow, Context, Result};
+
+// 连接控制端口的超时时间
+const CONNECT_TIMEOUT: Duration = Duration::from_secs(5);
+
+// 命令响应的超时时间
+const REPLY_TIMEOUT: Duration = Duration::from_secs(15);
+
+// 控制协议的一条完整响应（多行响应的每一行，数据块合并为一项）
+#[derive(Debug)]
+pub struct Reply {
+    pub code: u16,
+    pub lines: Vec<String>,
+}
+
+// 解析带引号的字符串，处理转义字符；返回内容与剩余部分
+fn take_quoted(input: &str) -> (String, &str) {
+    let mut value = String::new();
+    let mut chars = input.char_indices().skip(1);
+    while let Some((index, c)) = chars.next() {
+        match c {
+            '\\' => {
+                if let Some((_, escaped)) = chars.next() {
+                    value.push(match escaped {
+                        'n' => '\n',
+                        't' => '\t',
+                        'r' => '\r',
+                        other => other,
+                    });
+                }
+            }
+            '"' => return (value, &input[index + 1..]),
+            _ => value.push(c),
+        }
+    }
+    (value, "")
+}
+
+// 解析"KEY=VALUE KEY2="quoted value""形式的参数
+pub fn parse_keywords(input: &str) -> HashMap<String, String> {
+    let mut result = HashMap::new();
+    let mut rest = input.trim_start();
+    while !rest.is_empty() {
+        let key_end = rest.find(|c: char| c == '=' || c == ' ').unwrap_or(rest.len());
+        let key = &rest[..key_end];
+        rest = &rest[key_end..];
+        if let Some(after_eq) = rest.strip_prefix('=') {
+            let value;
+            if after_eq.starts_with('"') {
+                let (quoted, remaining) = take_quoted(after_eq);
+                value = quoted;
+                rest = remaining;
+            } else {
+                let end = after_eq.find(' ').unwrap_or(after_eq.len());
+                value = after_eq[..end].to_string();
+                rest = &after_eq[end..];
+            }
+            result.insert(key.to_string(), value);
+        } else if !key.is_empty() {
+            result.insert(key.to_string(), String::new());
+        }
+        rest = rest.trim_start();
+    }
+    result
+}
+
+// 将字符串编码为控制协议的带引号字符串
+pub fn quote(value: &str) -> String {
+    format!("\"{}\"", value.replace('\\', "\\\\").replace('"', "\\\""))
+}
+
+// Tor控制端口连接
+pub struct ControlConnection {
+    reader: BufReader<TcpStream>,
+    stream: TcpStream,
+}
+
+impl ControlConnection {
+    // 连接控制端口并认证（自动选择Cookie、密码或无认证）
+    pub fn connect(port: u16, password: Option<&str>) -> Result<Self> {
+        let addr = SocketAddr::from(([127, 0, 0, 1], port));
+        let stream = TcpStream::connect_timeout(&addr, CONNECT_TIMEOUT).context("Failed to connect to tor control port")?;
+        stream.set_read_timeout(Some(REPLY_TIMEOUT)).context("Failed to configure control connection")?;
+        let reader = BufReader::new(stream.try_clone().context("Failed to clone control connection")?);
+        let mut connection = Self { reader, stream };
+        connection.authenticate(password)?;
+        Ok(connection)
+    }
+
+    // 获取底层连接的副本，可在其他线程中关闭以中断阻塞的读取
+    pub fn try_clone_stream(&self) -> Result<TcpStream> {
+        self.stream.try_clone().context("Failed to clone control connection")
+    }
+
+    fn authenticate(&mut self, password: Option<&str>) -> Result<()> {
+        let info = self.command("PROTOCOLINFO 1")?;
+        let auth = info
+            .iter()
+            .find_map(|line| line.strip_prefix("AUTH "))
+            .map(parse_keywords)
+            .unwrap_or_default();
+        let methods: Vec<&str> = auth.get("METHODS").map(|m| m.split(',').collect()).unwrap_or_default();
+
+        let command = if let (Some(password), true) = (password, methods.contains(&"HASHEDPASSWORD")) {
+            format!("AUTHENTICATE {}", quote(password))
+        } else if methods.contains(&"COOKIE") {
+            let cookie_file = auth.get("COOKIEFILE").ok_or_else(|| anyhow!("Tor did not report a cookie file"))?;
+            let cookie = fs::read(cookie_file).context("Failed to read tor control cookie")?;
+            let hex: String = cookie.iter().map(|b| format!("{:02X}", b)).collect();
+            format!("AUTHENTICATE {}", hex)
+        } else if methods.contains(&"NULL") {
+            "AUTHENTICATE".to_string()
+        } else if methods.contains(&"HASHEDPASSWORD") {
+            return Err(anyhow!("Tor control port requires a password"));
+        } else {
+            return Err(anyhow!("No supported control port authentication method"));
+        };
+        self.command(&command).context("Tor control port authentication failed")?;
+        Ok(())
+    }
+
+    // 读取一条完整响应
+    fn read_reply(&mut self) -> Result<Reply> {
+        let mut lines = Vec::new();
+        loop {
+            let line = self.read_line()?;
+            if line.len() < 4 {
+                return Err(anyhow!("Malformed control reply: {}", line));
+            }
+            let code: u16 = line[..3].parse().map_err(|_| anyhow!("Malformed control reply: {}", line))?;
+            let text = line[4..].to_string();
+            match &line[3..4] {
+                " " => {
+                    lines.push(text);
+                    return Ok(Reply { code, lines });
+                }
+                "-" => lines.push(text),
+                "+" => {
+                    // 数据块以单独一行"."结束
+                    let mut data = Vec::new();
+                    loop {
+                        let data_line = self.read_line()?;
+                        if data_line == "." {
+                            break;
+                        }
+                        data.push(data_line.strip_prefix('.').map(str::to_string).unwrap_or(data_line));
+                    }
+                    lines.push(format!("{}\n{}", text, data.join("\n")));
+                }
+                _ => return Err(anyhow!("Malformed control reply: {}", line)),
+            }
+        }
+    }
+
+    fn read_line(&mut self) -> Result<String> {
+        let mut buffer = Vec::new();
+        let read = self.reader.read_until(b'\n', &mut buffer).context("Failed to read from tor control port")?;
+        if read == 0 {
+            return Err(anyhow!("Tor control connection closed"));
+        }
+        Ok(String::from_utf8_lossy(&buffer).trim_end_matches(['\r', '\n']).to_string())
+    }
+
+    // 发送命令并返回响应行，非250响应视为错误
+    pub fn command(&mut self, command: &str) -> Result<Vec<String>> {
+        self.stream
+            .write_all(format!("{}\r\n", command).as_bytes())
+            .context("Failed to write to tor control port")?;
+        loop {
+            let reply = self.read_reply()?;
+            // 跳过异步事件
+            if reply.code == 650 {
+                continue;
+            }
+            if reply.code != 250 {
+                return Err(anyhow!("{} {}", reply.code, reply.lines.join(" ")));
+            }
+            return Ok(reply.lines);
+        }
+    }
+
+    // GETINFO单个键
+    pub fn get_info(&mut self, key: &str) -> Result<String> {
+        let lines = self.command(&format!("GETINFO {}", key))?;
+        let prefix = format!("{}=", key);
+        lines
+            .iter()
+            .find_map(|line| line.strip_prefix(&prefix))
+            .map(|value| value.trim_start_matches('\n').to_string())
+            .ok_or_else(|| anyhow!("Tor did not return {}", key))
+    }
+
+    // 修改运行中的配置，值为None时恢复默认值
+    pub fn set_conf(&mut self, options: &[(&str, Option<String>)]) -> Result<()> {
+        let arguments: Vec<String> = options
+            .iter()
+            .map(|(key, value)| match value {
+                Some(value) => format!("{}={}", key, quote(value)),
+                None => key.to_string(),
+            })
+            .collect();
+        self.command(&format!("SETCONF {}", arguments.join(" "))).map(|_| ())
+    }
+
+    // 订阅异步事件
+    pub fn set_events(&mut self, events: &[&str]) -> Result<()> {
+        self.command(&format!("SETEVENTS {}", events.join(" "))).map(|_| ())
+    }
+
+    // 阻塞等待下一个异步事件，返回事件内容（不含"650 "前缀）
+    pub fn read_event(&mut self) -> Result<Vec<String>> {
+        self.stream.set_read_timeout(None).context("Failed to configure control connection")?;
+        loop {
+            let reply = self.read_reply()?;
+            if reply.code == 650 {
+                return Ok(reply.lines);
+            }
+        }
+    }
+}
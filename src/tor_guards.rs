@@ -0,0 +1,152 @@
+use eframe::egui::{self, Color32, RichText, Ui};
+use std::net::IpAddr;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+use anyhow::Result;
+
+use crate::geoip;
+use crate::tor_circuits;
+use crate::tor_control::ControlConnection;
+
+// 一个入口守卫
+#[derive(Clone, Debug)]
+pub struct Guard {
+    pub fingerprint: String,
+    pub nickname: String,
+    pub status: String, // up、down、never-connected、unusable等
+    pub address: Option<IpAddr>,
+}
+
+// 解析entry-guards中的一行，如"$AAAA~relay1 up"
+fn parse_guard(line: &str) -> Option<Guard> {
+    let mut fields = line.split_whitespace();
+    let relay = fields.next()?.strip_prefix('$')?;
+    let status = fields.next().unwrap_or("").to_string();
+    let (fingerprint, nickname) = relay.split_once(['~', '=']).unwrap_or((relay, ""));
+    Some(Guard {
+        fingerprint: fingerprint.to_uppercase(),
+        nickname: nickname.to_string(),
+        status,
+        address: None,
+    })
+}
+
+// 获取当前的入口守卫
+fn fetch(port: u16, password: Option<&str>) -> Result<Vec<Guard>> {
+    let mut connection = ControlConnection::connect(port, password)?;
+    let entries = connection.get_info("entry-guards")?;
+    let mut guards: Vec<Guard> = entries.lines().filter_map(parse_guard).collect();
+    for guard in &mut guards {
+        guard.address = tor_circuits::relay_address(&mut connection, &guard.fingerprint);
+    }
+    Ok(guards)
+}
+
+// 入口守卫列表
+pub struct GuardViewer {
+    guards: Arc<Mutex<Vec<Guard>>>,
+    error: Arc<Mutex<Option<String>>>,
+    refreshing: Arc<AtomicBool>,
+    loaded: bool,
+}
+
+impl Default for GuardViewer {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl GuardViewer {
+    pub fn new() -> Self {
+        Self {
+            guards: Arc::new(Mutex::new(Vec::new())),
+            error: Arc::new(Mutex::new(None)),
+            refreshing: Arc::new(AtomicBool::new(false)),
+            loaded: false,
+        }
+    }
+
+    // 在后台线程中通过控制端口刷新守卫列表
+    fn refresh(&mut self, port: u16, password: Option<String>) {
+        if self.refreshing.swap(true, Ordering::SeqCst) {
+            return;
+        }
+        self.loaded = true;
+        let guards = Arc::clone(&self.guards);
+        let error = Arc::clone(&self.error);
+        let refreshing = Arc::clone(&self.refreshing);
+        std::thread::spawn(move || {
+            match fetch(port, password.as_deref()) {
+                Ok(list) => {
+                    if let Ok(mut guards) = guards.lock() {
+                        *guards = list;
+                    }
+                    if let Ok(mut error) = error.lock() {
+                        *error = None;
+                    }
+                }
+                Err(e) => {
+                    if let Ok(mut error) = error.lock() {
+                        *error = Some(format!("{:#}", e));
+                    }
+                }
+            }
+            refreshing.store(false, Ordering::SeqCst);
+        });
+    }
+
+    // 渲染守卫列表，pinned为已固定的指纹；返回被点击"固定"或"取消固定"的指纹
+    pub fn ui(&mut self, ui: &mut Ui, control: Option<(u16, Option<String>)>, pinned: &[String]) -> Option<String> {
+        let Some((port, password)) = control else {
+            ui.label("Tor未运行");
+            self.loaded = false;
+            return None;
+        };
+
+        ui.horizontal(|ui| {
+            if ui.button("刷新").clicked() || !self.loaded {
+                self.refresh(port, password.clone());
+            }
+            if self.refreshing.load(Ordering::SeqCst) {
+                ui.spinner();
+                ui.ctx().request_repaint_after(std::time::Duration::from_millis(200));
+            }
+        });
+
+        if let Some(error) = self.error.lock().ok().and_then(|e| e.clone()) {
+            ui.label(RichText::new(format!("获取入口守卫失败: {}", error)).color(Color32::RED));
+        }
+
+        let guards = self.guards.lock().map(|g| g.clone()).unwrap_or_default();
+        if guards.is_empty() {
+            ui.label("暂无入口守卫");
+            return None;
+        }
+
+        let mut toggled = None;
+        egui::Grid::new("tor_guard_grid").striped(true).num_columns(5).show(ui, |ui| {
+            ui.strong("昵称");
+            ui.strong("指纹");
+            ui.strong("国家");
+            ui.strong("状态");
+            ui.label("");
+            ui.end_row();
+
+            for guard in &guards {
+                let address = guard.address.map(|a| a.to_string()).unwrap_or_else(|| "-".to_string());
+                ui.label(&guard.nickname).on_hover_text(format!("地址: {}", address));
+                ui.label(RichText::new(&guard.fingerprint).monospace().small());
+                let country = guard.address.and_then(geoip::describe).unwrap_or_else(|| "未知".to_string());
+                ui.label(country);
+                let color = if guard.status == "up" { Color32::GREEN } else { Color32::GRAY };
+                ui.label(RichText::new(&guard.status).color(color));
+                let is_pinned = pinned.contains(&guard.fingerprint);
+                if ui.small_button(if is_pinned { "取消固定" } else { "固定" }).clicked() {
+                    toggled = Some(guard.fingerprint.clone());
+                }
+                ui.end_row();
+            }
+        });
+        toggled
+    }
+}
@@ -0,0 +1,513 @@
+use std::fs;
+use std::net::{Shutdown, TcpStream};
+use std::path::{Path, PathBuf};
+use std::process::Command;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+use anyhow::{anyhow, Context, Result};
+
+use crate::components::{self, Executable};
+use crate::logger::{LogLevel, Logger};
+use crate::services::{ManagedProcess, ProcessLauncher};
+use crate::supervisor::{ProcessSpec, ProcessState};
+use crate::tor_bandwidth::BandwidthMeter;
+use crate::transports;
+use crate::tor::{BridgeType, NodeType, TorBridge};
+use crate::tor_config::{ControlAuth, TorConfig};
+use crate::tor_control::{self, ControlConnection};
+use crate::utils;
+
+// 引导超时时间，超时后向界面报告失败（Tor仍会继续尝试）
+const BOOTSTRAP_TIMEOUT: Duration = Duration::from_secs(180);
+
+// Tor引导状态
+#[derive(Clone, Debug, PartialEq)]
+pub enum BootstrapState {
+    Starting,
+    Bootstrapping { progress: u8, summary: String },
+    Ready,
+    Restarting { attempt: u32, delay: Duration }, // 进程意外退出，等待自动重启
+    Failed(String),
+}
+
+// 生成torrc所需的设置
+#[derive(Clone, Debug)]
+pub struct TorSettings {
+    pub config: TorConfig,
+    pub bridges: Vec<TorBridge>, // 启用的网桥，为空时直接连接
+    pub relay_mode: Option<NodeType>, // 作为中继运行时的节点类型，None表示仅作为客户端
+}
+
+// Tor工作目录（torrc与数据目录所在位置）
+fn tor_home() -> Result<PathBuf> {
+    let home = Path::new(&utils::get_app_data_dir()?).join("tor");
+    fs::create_dir_all(home.join("data")).context("Failed to create tor data directory")?;
+    Ok(home)
+}
+
+// torrc中的路径需要加引号并转义反斜杠
+pub fn torrc_path(path: &Path) -> String {
+    format!("\"{}\"", path.display().to_string().replace('\\', "\\\\"))
+}
+
+// 网桥配置行，lyrebird只支持meek_lite
+fn bridge_line(bridge: &TorBridge) -> String {
+    let line = bridge.address.trim();
+    match (&bridge.bridge_type, line.strip_prefix("meek ")) {
+        (BridgeType::Meek, Some(rest)) => format!("meek_lite {}", rest),
+        _ => line.to_string(),
+    }
+}
+
+// 生成torrc内容
+fn generate_torrc(settings: &TorSettings, home: &Path, tor_exe: &Path, transport_lines: Vec<String>) -> String {
+    let mut lines = vec![
+        "# 由InviZible Pro自动生成，修改将在下次启动时被覆盖".to_string(),
+        format!("DataDirectory {}", torrc_path(&home.join("data"))),
+        "Log notice stdout".to_string(),
+    ];
+    lines.extend(settings.config.torrc_lines());
+    lines.extend(settings.config.bandwidth_lines(settings.relay_mode.is_some()));
+    if let Some(node_type) = &settings.relay_mode {
+        lines.extend(settings.config.relay.torrc_lines(*node_type == NodeType::Exit));
+    }
+    if !settings.bridges.is_empty() {
+        lines.push("UseBridges 1".to_string());
+        lines.extend(transport_lines);
+        lines.extend(settings.bridges.iter().map(|bridge| format!("Bridge {}", bridge_line(bridge))));
+    }
+
+    // 专家包中的GeoIP文件位于tor.exe上级目录的data目录下
+    if let Some(bundle_dir) = tor_exe.parent().and_then(|p| p.parent()) {
+        for (option, file) in [("GeoIPFile", "geoip"), ("GeoIPv6File", "geoip6")] {
+            let path = bundle_dir.join("data").join(file);
+            if path.exists() {
+                lines.push(format!("{} {}", option, torrc_path(&path)));
+            }
+        }
+    }
+
+    // 用户自定义的配置行放在最后
+    let extra = settings.config.extra_torrc.trim();
+    if !extra.is_empty() {
+        lines.push("# 自定义配置".to_string());
+        lines.push(extra.to_string());
+    }
+    lines.join("\n") + "\n"
+}
+
+// 校验设置并生成torrc，返回tor.exe与torrc的路径
+fn write_torrc(settings: &TorSettings, file_name: &str) -> Result<(PathBuf, PathBuf)> {
+    let tor_exe = components::executable_path(Executable::Tor)
+        .ok_or_else(|| anyhow!("未找到tor.exe，请在 设置 → 组件管理 中安装Tor"))?;
+    if settings.config.control_auth == ControlAuth::Password && settings.config.hashed_control_password.is_empty() {
+        return Err(anyhow!("已选择密码认证，但尚未生成控制端口密码哈希"));
+    }
+    if let Some(node_type) = &settings.relay_mode {
+        let errors = settings.config.relay.validate(*node_type == NodeType::Exit);
+        if !errors.is_empty() {
+            return Err(anyhow!("中继设置有误: {}", errors.join("；")));
+        }
+    }
+    let transport_lines = transports::torrc_lines(settings.bridges.iter().map(|b| &b.bridge_type))?;
+    let home = tor_home()?;
+    let torrc = home.join(file_name);
+    fs::write(&torrc, generate_torrc(settings, &home, &tor_exe, transport_lines)).context("Failed to write torrc")?;
+    Ok((tor_exe, torrc))
+}
+
+// 用tor --verify-config检查按settings生成的完整torrc（会阻塞调用线程）
+pub fn verify_config(settings: &TorSettings) -> Result<()> {
+    let (tor_exe, torrc) = write_torrc(settings, "torrc.verify")?;
+    let mut command = Command::new(&tor_exe);
+    command.args(["--verify-config", "-f"]).arg(&torrc);
+    #[cfg(target_os = "windows")]
+    {
+        use std::os::windows::process::CommandExt;
+        const CREATE_NO_WINDOW: u32 = 0x08000000;
+        command.creation_flags(CREATE_NO_WINDOW);
+    }
+    let output = command.output().context("Failed to run tor --verify-config");
+    let _ = fs::remove_file(&torrc);
+    let output = output?;
+    if output.status.success() {
+        return Ok(());
+    }
+    // 只保留警告与错误，如"[warn] Failed to parse/validate config: Unknown option 'Foo'."
+    let problems: Vec<String> = String::from_utf8_lossy(&output.stdout)
+        .lines()
+        .map(parse_log_line)
+        .filter(|(level, _)| matches!(level, LogLevel::Warning | LogLevel::Error))
+        .map(|(_, message)| message)
+        .collect();
+    if problems.is_empty() {
+        Err(anyhow!("配置校验失败: {}", String::from_utf8_lossy(&output.stderr).trim()))
+    } else {
+        Err(anyhow!("{}", problems.join("\n")))
+    }
+}
+
+// 解析引导状态，如"NOTICE BOOTSTRAP PROGRESS=45 TAG=requesting_descriptors SUMMARY="Asking for relay descriptors""
+fn parse_bootstrap(line: &str) -> Option<(u8, String)> {
+    let rest = &line[line.find("BOOTSTRAP ")? + "BOOTSTRAP ".len()..];
+    let keywords = tor_control::parse_keywords(rest);
+    let progress = keywords.get("PROGRESS")?.parse().ok()?;
+    let mut summary = keywords.get("SUMMARY").cloned().unwrap_or_default();
+    // 引导遇到问题时Tor会附带警告原因
+    if keywords.get("RECOMMENDATION").map(String::as_str) == Some("warn") {
+        if let Some(warning) = keywords.get("WARNING") {
+            summary = format!("{}（{}）", summary, warning);
+        }
+    }
+    Some((progress, summary))
+}
+
+// 解析Tor日志行，如"Oct 16 12:00:00.000 [warn] Problem bootstrapping..."，返回日志级别与消息
+fn parse_log_line(line: &str) -> (LogLevel, String) {
+    let severity = line.find('[').and_then(|start| {
+        let end = start + line[start..].find(']')?;
+        Some((&line[start + 1..end], line[end + 1..].trim()))
+    });
+    match severity {
+        Some(("err", message)) => (LogLevel::Error, message.to_string()),
+        Some(("warn", message)) => (LogLevel::Warning, message.to_string()),
+        Some(("notice", message)) => (LogLevel::Info, message.to_string()),
+        Some(("info" | "debug", message)) => (LogLevel::Debug, message.to_string()),
+        // 没有级别标记的输出（如启动参数错误）按普通信息记录
+        _ => (LogLevel::Info, line.trim().to_string()),
+    }
+}
+
+// 控制端口事件更新的共享状态
+struct TorState {
+    bootstrap: Mutex<BootstrapState>,
+    bootstrap_started: Mutex<Instant>,
+    pid: Mutex<Option<u32>>, // 最近一次看到的进程ID，变化说明进程已被重启
+    bandwidth: BandwidthMeter,
+}
+
+impl TorState {
+    // 更新引导状态，进度为0时重新开始计算超时
+    fn update_bootstrap(&self, progress: u8, summary: String) {
+        if let Ok(mut state) = self.bootstrap.lock() {
+            *state = if progress >= 100 {
+                BootstrapState::Ready
+            } else {
+                BootstrapState::Bootstrapping { progress, summary }
+            };
+        }
+        // 进程被监控重启后会从0%重新引导
+        if progress == 0 {
+            if let Ok(mut started) = self.bootstrap_started.lock() {
+                *started = Instant::now();
+            }
+        }
+    }
+
+    // 进程被重启后丢弃上一次运行的引导状态，等待控制端口重新报告
+    fn reset_on_restart(&self, pid: Option<u32>) {
+        let restarted = match self.pid.lock() {
+            Ok(mut last) => {
+                let restarted = pid.is_some() && last.is_some() && *last != pid;
+                if pid.is_some() {
+                    *last = pid;
+                }
+                restarted
+            }
+            Err(_) => false,
+        };
+        if restarted {
+            if let Ok(mut state) = self.bootstrap.lock() {
+                *state = BootstrapState::Starting;
+            }
+            if let Ok(mut started) = self.bootstrap_started.lock() {
+                *started = Instant::now();
+            }
+        }
+    }
+
+    // 处理一条异步事件
+    fn handle_event(&self, line: &str) {
+        if let Some(rest) = line.strip_prefix("BW ") {
+            // "BW <读取字节数> <写入字节数>"，每秒一次
+            let mut fields = rest.split_whitespace().map(|f| f.parse::<u64>().unwrap_or(0));
+            self.bandwidth.record(fields.next().unwrap_or(0), fields.next().unwrap_or(0));
+        } else if let Some((progress, summary)) = parse_bootstrap(line) {
+            self.update_bootstrap(progress, summary);
+        }
+    }
+}
+
+// 每隔多少次BW事件保存一次会话流量
+const BANDWIDTH_SAVE_INTERVAL: u32 = 60;
+
+// 通过控制端口订阅引导与流量事件，连接断开（如进程重启）后自动重连
+struct ControlWatcher {
+    stop: Arc<AtomicBool>,
+    stream: Arc<Mutex<Option<TcpStream>>>,
+}
+
+impl ControlWatcher {
+    fn spawn(port: u16, password: Option<String>, state: Arc<TorState>, logger: Arc<Mutex<Logger>>) -> Self {
+        let stop = Arc::new(AtomicBool::new(false));
+        let stream = Arc::new(Mutex::new(None));
+        let watcher = Self { stop: Arc::clone(&stop), stream: Arc::clone(&stream) };
+
+        std::thread::spawn(move || {
+            // Tor启动初期控制端口尚未监听，连接被拒绝属于正常情况
+            let mut last_error = "Failed to connect to tor control port".to_string();
+            let mut events_since_save = 0;
+            while !stop.load(Ordering::SeqCst) {
+                let result = ControlConnection::connect(port, password.as_deref()).and_then(|mut connection| -> Result<()> {
+                    if let Ok(mut current) = stream.lock() {
+                        *current = connection.try_clone_stream().ok();
+                    }
+                    if let Ok(mut logger) = logger.lock() {
+                        logger.info("Tor", &format!("已连接控制端口 {}", port));
+                    }
+                    last_error.clear();
+
+                    // 先读取当前进度，再订阅后续变化
+                    let phase = connection.get_info("status/bootstrap-phase")?;
+                    if let Some((progress, summary)) = parse_bootstrap(&phase) {
+                        state.update_bootstrap(progress, summary);
+                    }
+                    connection.set_events(&["STATUS_CLIENT", "BW"])?;
+                    loop {
+                        for line in connection.read_event()? {
+                            state.handle_event(&line);
+                            if line.starts_with("BW ") {
+                                events_since_save += 1;
+                            }
+                        }
+                        if events_since_save >= BANDWIDTH_SAVE_INTERVAL {
+                            events_since_save = 0;
+                            if let Err(e) = state.bandwidth.save() {
+                                if let Ok(mut logger) = logger.lock() {
+                                    logger.error("Tor", &format!("保存流量记录失败: {}", e));
+                                }
+                            }
+                        }
+                    }
+                });
+
+                if let Err(e) = result {
+                    // 只在错误变化时记录，避免重连期间刷屏
+                    let message = e.to_string();
+                    if message != last_error && !stop.load(Ordering::SeqCst) {
+                        if let Ok(mut logger) = logger.lock() {
+                            logger.warning("Tor", &format!("控制端口连接中断: {:#}", e));
+                        }
+                        last_error = message;
+                    }
+                }
+                std::thread::sleep(Duration::from_secs(1));
+            }
+        });
+        watcher
+    }
+
+    fn stop(&self) {
+        self.stop.store(true, Ordering::SeqCst);
+        if let Ok(mut stream) = self.stream.lock() {
+            if let Some(stream) = stream.take() {
+                let _ = stream.shutdown(Shutdown::Both);
+            }
+        }
+    }
+}
+
+impl Drop for ControlWatcher {
+    fn drop(&mut self) {
+        self.stop();
+    }
+}
+
+// 调用tor --hash-password生成HashedControlPassword的值，如"16:..."（会阻塞调用线程）
+pub fn hash_password(password: &str) -> Result<String> {
+    let tor_exe = components::executable_path(Executable::Tor)
+        .ok_or_else(|| anyhow!("未找到tor.exe，请在 设置 → 组件管理 中安装Tor"))?;
+    let mut command = Command::new(&tor_exe);
+    command.args(["--quiet", "--hash-password", password]);
+    #[cfg(target_os = "windows")]
+    {
+        use std::os::windows::process::CommandExt;
+        const CREATE_NO_WINDOW: u32 = 0x08000000;
+        command.creation_flags(CREATE_NO_WINDOW);
+    }
+    let output = command.output().context("Failed to run tor --hash-password")?;
+    String::from_utf8_lossy(&output.stdout)
+        .lines()
+        .map(str::trim)
+        .find(|line| line.starts_with("16:"))
+        .map(str::to_string)
+        .ok_or_else(|| anyhow!("tor未输出密码哈希: {}", String::from_utf8_lossy(&output.stderr).trim()))
+}
+
+// 调用tor --version读取已安装tor.exe的版本号，如"0.4.8.13"（会阻塞调用线程）
+pub fn tor_version() -> Result<String> {
+    let tor_exe = components::executable_path(Executable::Tor)
+        .ok_or_else(|| anyhow!("未找到tor.exe"))?;
+    let mut command = Command::new(&tor_exe);
+    command.arg("--version");
+    #[cfg(target_os = "windows")]
+    {
+        use std::os::windows::process::CommandExt;
+        const CREATE_NO_WINDOW: u32 = 0x08000000;
+        command.creation_flags(CREATE_NO_WINDOW);
+    }
+    let output = command.output().context("Failed to run tor --version")?;
+    // 输出形如 "Tor version 0.4.8.13."
+    String::from_utf8_lossy(&output.stdout)
+        .lines()
+        .find_map(|line| line.trim().strip_prefix("Tor version "))
+        .map(|version| version.trim_end_matches('.').split_whitespace().next().unwrap_or("").to_string())
+        .filter(|version| !version.is_empty())
+        .ok_or_else(|| anyhow!("无法识别tor --version的输出"))
+}
+
+// 运行中的tor.exe
+pub struct TorProcess {
+    process: Box<dyn ManagedProcess>,
+    state: Arc<TorState>,
+    watcher: ControlWatcher,
+    control_port: u16,
+    control_password: Option<String>,
+}
+
+impl TorProcess {
+    // 查找已安装的tor.exe，生成torrc后启动
+    pub fn start(launcher: &dyn ProcessLauncher, logger: Arc<Mutex<Logger>>, settings: &TorSettings) -> Result<Self> {
+        let (tor_exe, torrc) = write_torrc(settings, "torrc")?;
+
+        let state = Arc::new(TorState {
+            bootstrap: Mutex::new(BootstrapState::Starting),
+            bootstrap_started: Mutex::new(Instant::now()),
+            pid: Mutex::new(None),
+            bandwidth: BandwidthMeter::new(),
+        });
+
+        // Tor的输出按级别写入日志，引导进度通过控制端口获取
+        let handler_state = Arc::clone(&state);
+        let handler_logger = Arc::clone(&logger);
+        let on_output = move |line: &str| {
+            if line.trim().is_empty() {
+                return;
+            }
+            let (level, message) = parse_log_line(line);
+            if let Ok(mut logger) = handler_logger.lock() {
+                logger.log(level, "Tor", &message);
+            }
+            if level == LogLevel::Error {
+                if let Ok(mut state) = handler_state.bootstrap.lock() {
+                    if *state != BootstrapState::Ready {
+                        *state = BootstrapState::Failed(message);
+                    }
+                }
+            }
+        };
+
+        let mut spec = ProcessSpec::new("Tor", &tor_exe);
+        spec.args = vec!["-f".to_string(), torrc.display().to_string()];
+        spec.working_dir = tor_exe.parent().map(Path::to_path_buf);
+        spec.liveness_port = Some(settings.config.socks.port);
+        spec.on_output = Some(Arc::new(on_output));
+        if !settings.config.auto_restart {
+            spec.policy.max_restarts = Some(0);
+        }
+
+        if let Ok(mut logger) = logger.lock() {
+            logger.info("Tor", &format!("正在启动 {}", tor_exe.display()));
+        }
+        let process = launcher.launch(spec);
+        let control_password = settings.config.control_password();
+        let watcher = ControlWatcher::spawn(settings.config.control_port, control_password.clone(), Arc::clone(&state), logger);
+        Ok(Self {
+            process,
+            state,
+            watcher,
+            control_port: settings.config.control_port,
+            control_password,
+        })
+    }
+
+    // 当前引导状态（包含进程退出、自动重启与引导超时）
+    pub fn bootstrap_state(&self) -> BootstrapState {
+        let status = self.process.status();
+        match status.state {
+            ProcessState::Failed(reason) => return BootstrapState::Failed(format!("Tor进程已退出: {}", reason)),
+            ProcessState::Stopped => return BootstrapState::Failed("Tor进程已退出".to_string()),
+            ProcessState::Restarting { attempt, delay } => return BootstrapState::Restarting { attempt, delay },
+            _ => {}
+        }
+        self.state.reset_on_restart(status.pid);
+        let state = self.state.bootstrap.lock().map(|s| s.clone()).unwrap_or(BootstrapState::Starting);
+        let elapsed = self.state.bootstrap_started.lock().map(|t| t.elapsed()).unwrap_or_default();
+        match state {
+            BootstrapState::Starting | BootstrapState::Bootstrapping { .. } if elapsed > BOOTSTRAP_TIMEOUT => {
+                BootstrapState::Failed("引导超时，请检查网络连接或配置网桥".to_string())
+            }
+            state => state,
+        }
+    }
+
+    // 本次运行的流量统计
+    pub fn bandwidth(&self) -> &BandwidthMeter {
+        &self.state.bandwidth
+    }
+
+    // 控制端口与认证密码，供线路查看等功能建立新连接
+    pub fn control_endpoint(&self) -> (u16, Option<String>) {
+        (self.control_port, self.control_password.clone())
+    }
+
+    // 停止进程并保存本次会话的流量合计
+    pub fn stop(&self) -> Result<()> {
+        self.watcher.stop();
+        self.process.stop();
+        self.state.bandwidth.save()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn settings(bridges: Vec<TorBridge>) -> TorSettings {
+        TorSettings { config: TorConfig::default(), bridges, relay_mode: None }
+    }
+
+    #[test]
+    fn torrc_without_bridges() {
+        let torrc = generate_torrc(&settings(Vec::new()), Path::new("tor"), Path::new("tor/tor.exe"), Vec::new());
+        assert!(torrc.starts_with("# 由InviZible Pro自动生成"));
+        assert!(torrc.contains("Log notice stdout"));
+        assert!(!torrc.contains("UseBridges"));
+    }
+
+    #[test]
+    fn torrc_with_bridges() {
+        let bridges = vec![
+            TorBridge::new(1, "meek", BridgeType::Meek, "meek 0.0.2.0:2 url=https://meek.example/"),
+            TorBridge::new(2, "obfs4", BridgeType::Obfs4, " obfs4 192.0.2.1:443 CERT iat-mode=0 "),
+        ];
+        let transport = vec!["ClientTransportPlugin meek_lite,obfs4 exec lyrebird.exe".to_string()];
+        let torrc = generate_torrc(&settings(bridges), Path::new("tor"), Path::new("tor/tor.exe"), transport);
+        assert!(torrc.contains("UseBridges 1\nClientTransportPlugin meek_lite,obfs4 exec lyrebird.exe\n"));
+        assert!(torrc.contains("Bridge meek_lite 0.0.2.0:2 url=https://meek.example/\n"));
+        assert!(torrc.contains("Bridge obfs4 192.0.2.1:443 CERT iat-mode=0\n"));
+    }
+
+    #[test]
+    fn parses_bootstrap_and_log_lines() {
+        let line = "650 STATUS_CLIENT NOTICE BOOTSTRAP PROGRESS=45 TAG=requesting_descriptors SUMMARY=\"Asking for relay descriptors\"";
+        assert_eq!(parse_bootstrap(line), Some((45, "Asking for relay descriptors".to_string())));
+        assert_eq!(parse_bootstrap("650 STATUS_CLIENT NOTICE CIRCUIT_ESTABLISHED"), None);
+
+        let (level, message) = parse_log_line("Oct 16 12:00:00.000 [warn] Problem bootstrapping.");
+        assert_eq!(level, LogLevel::Warning);
+        assert_eq!(message, "Problem bootstrapping.");
+        assert_eq!(parse_log_line("Invalid command-line option").0, LogLevel::Info);
+    }
+}
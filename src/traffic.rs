@@ -0,0 +1,222 @@
+use eframe::egui::{Color32, RichText, Ui, Grid};
+use std::collections::{BTreeMap, VecDeque};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+use serde::{Deserialize, Serialize};
+use chrono::Local;
+use once_cell::sync::Lazy;
+
+use crate::logger::Logger;
+use crate::utils;
+
+// 流量历史文件名
+const TRAFFIC_FILE: &str = "traffic.json";
+
+// 计算实时速率的时间窗口
+const RATE_WINDOW: Duration = Duration::from_secs(6);
+
+// 历史数据写入磁盘的间隔
+const FLUSH_INTERVAL: Duration = Duration::from_secs(60);
+
+// 保留的历史天数
+const HISTORY_DAYS: usize = 90;
+
+// 流量来源
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, PartialOrd, Ord, Serialize, Deserialize)]
+pub enum TrafficSource {
+    Tor,
+    Vpn,
+    I2P,
+    Proxy,
+    Direct,
+    Unclassified, // 无法按连接区分时的系统总流量
+}
+
+impl TrafficSource {
+    pub fn all() -> [TrafficSource; 6] {
+        [
+            TrafficSource::Tor,
+            TrafficSource::Vpn,
+            TrafficSource::I2P,
+            TrafficSource::Proxy,
+            TrafficSource::Direct,
+            TrafficSource::Unclassified,
+        ]
+    }
+
+    pub fn label(&self) -> &'static str {
+        match self {
+            TrafficSource::Tor => "Tor",
+            TrafficSource::Vpn => "VPN",
+            TrafficSource::I2P => "I2P",
+            TrafficSource::Proxy => "代理",
+            TrafficSource::Direct => "直连",
+            TrafficSource::Unclassified => "未区分",
+        }
+    }
+}
+
+// 每天各来源的收发字节数，键为日期(YYYY-MM-DD)
+type DailyTraffic = BTreeMap<String, BTreeMap<TrafficSource, (u64, u64)>>;
+
+impl utils::VersionedConfig for DailyTraffic {
+    const VERSION: u32 = 1;
+}
+
+// 流量统计状态
+struct Accounting {
+    recent: VecDeque<(Instant, TrafficSource, u64, u64)>,
+    daily: DailyTraffic,
+    dirty: bool,
+}
+
+static ACCOUNTING: Lazy<Mutex<Accounting>> = Lazy::new(|| {
+    let daily = utils::load_saved_config(TRAFFIC_FILE).unwrap_or_default();
+    Mutex::new(Accounting {
+        recent: VecDeque::new(),
+        daily,
+        dirty: false,
+    })
+});
+
+// 上报一段时间内新增的收发字节数
+pub fn report(source: TrafficSource, rx_bytes: u64, tx_bytes: u64) {
+    if rx_bytes == 0 && tx_bytes == 0 {
+        return;
+    }
+    if let Ok(mut accounting) = ACCOUNTING.lock() {
+        let now = Instant::now();
+        accounting.recent.push_back((now, source, rx_bytes, tx_bytes));
+        while accounting.recent.front().map_or(false, |(t, ..)| now.duration_since(*t) > RATE_WINDOW) {
+            accounting.recent.pop_front();
+        }
+
+        let today = Local::now().format("%Y-%m-%d").to_string();
+        let totals = accounting.daily.entry(today).or_default().entry(source).or_insert((0, 0));
+        totals.0 += rx_bytes;
+        totals.1 += tx_bytes;
+        accounting.dirty = true;
+    }
+}
+
+// 模块数据通道累计的收发字节数：转发数据的线程调用add，模块定期调用report上报增量
+#[derive(Default)]
+pub struct Counter {
+    rx: AtomicU64,
+    tx: AtomicU64,
+}
+
+impl Counter {
+    pub fn add(&self, rx_bytes: u64, tx_bytes: u64) {
+        self.rx.fetch_add(rx_bytes, Ordering::Relaxed);
+        self.tx.fetch_add(tx_bytes, Ordering::Relaxed);
+    }
+
+    // 上报自上次调用以来新增的字节数
+    pub fn report(&self, source: TrafficSource) {
+        report(source, self.rx.swap(0, Ordering::Relaxed), self.tx.swap(0, Ordering::Relaxed));
+    }
+}
+
+// 各来源的实时速率（字节/秒）
+pub fn current_rates() -> BTreeMap<TrafficSource, (f64, f64)> {
+    let mut rates = BTreeMap::new();
+    if let Ok(accounting) = ACCOUNTING.lock() {
+        let window = RATE_WINDOW.as_secs_f64();
+        for (time, source, rx, tx) in &accounting.recent {
+            if time.elapsed() <= RATE_WINDOW {
+                let rate = rates.entry(*source).or_insert((0.0, 0.0));
+                rate.0 += *rx as f64 / window;
+                rate.1 += *tx as f64 / window;
+            }
+        }
+    }
+    rates
+}
+
+// 某一来源每天的收发字节数，键为日期(YYYY-MM-DD)
+pub fn daily_totals(source: TrafficSource) -> BTreeMap<String, (u64, u64)> {
+    ACCOUNTING.lock()
+        .map(|accounting| {
+            accounting.daily.iter()
+                .filter_map(|(date, sources)| sources.get(&source).map(|totals| (date.clone(), *totals)))
+                .collect()
+        })
+        .unwrap_or_default()
+}
+
+// 总的实时速率（字节/秒）
+pub fn total_rate() -> (f64, f64) {
+    current_rates().values().fold((0.0, 0.0), |acc, rate| (acc.0 + rate.0, acc.1 + rate.1))
+}
+
+// 将历史数据写入磁盘
+pub fn flush() {
+    let daily = match ACCOUNTING.lock() {
+        Ok(mut accounting) if accounting.dirty => {
+            // 只保留最近的历史
+            while accounting.daily.len() > HISTORY_DAYS {
+                let oldest = accounting.daily.keys().next().cloned();
+                if let Some(oldest) = oldest {
+                    accounting.daily.remove(&oldest);
+                }
+            }
+            accounting.dirty = false;
+            accounting.daily.clone()
+        }
+        _ => return,
+    };
+    if let Err(e) = utils::get_config_path(TRAFFIC_FILE).and_then(|path| utils::save_versioned_config(&daily, &path)) {
+        log::warn!("Failed to save traffic history: {}", e);
+    }
+}
+
+// 启动定期保存历史数据的后台线程
+pub fn start_flusher(logger: Arc<Mutex<Logger>>) {
+    if let Ok(mut logger) = logger.lock() {
+        logger.info("流量统计", "流量统计服务已启动");
+    }
+    std::thread::spawn(|| loop {
+        std::thread::sleep(FLUSH_INTERVAL);
+        flush();
+    });
+}
+
+// 渲染按天统计的流量表
+pub fn ui(ui: &mut Ui) {
+    let daily = ACCOUNTING.lock().map(|a| a.daily.clone()).unwrap_or_default();
+    if daily.is_empty() {
+        ui.label(RichText::new("暂无流量记录").color(Color32::GRAY));
+        return;
+    }
+
+    Grid::new("traffic_daily_grid")
+        .num_columns(TrafficSource::all().len() + 2)
+        .striped(true)
+        .spacing([10.0, 4.0])
+        .show(ui, |ui| {
+            ui.label(RichText::new("日期").strong());
+            for source in TrafficSource::all() {
+                ui.label(RichText::new(source.label()).strong());
+            }
+            ui.label(RichText::new("合计").strong());
+            ui.end_row();
+
+            for (date, sources) in daily.iter().rev().take(14) {
+                ui.label(date);
+                let mut total = (0, 0);
+                for source in TrafficSource::all() {
+                    let (rx, tx) = sources.get(&source).copied().unwrap_or((0, 0));
+                    total = (total.0 + rx, total.1 + tx);
+                    if rx + tx == 0 {
+                        ui.label(RichText::new("-").color(Color32::GRAY));
+                    } else {
+                        ui.label(format!("↓{} ↑{}", utils::format_bytes(rx), utils::format_bytes(tx)));
+                    }
+                }
+                ui.label(format!("↓{} ↑{}", utils::format_bytes(total.0), utils::format_bytes(total.1)));
+                ui.end_row();
+            }
+        });
+}
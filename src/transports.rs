@@ -0,0 +1,157 @@
+use std::collections::{HashMap, HashSet};
+use std::path::Path;
+use std::process::Command;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+use anyhow::{anyhow, Result};
+use once_cell::sync::Lazy;
+
+use crate::components::{self, Executable};
+use crate::services;
+use crate::tor::BridgeType;
+use crate::tor_process::torrc_path;
+
+// 检测结果的缓存时间
+const CHECK_TTL: Duration = Duration::from_secs(30);
+
+// Tor的可插拔传输插件
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum PluggableTransport {
+    Lyrebird,  // obfs4/meek_lite/webtunnel（原obfs4proxy）
+    Snowflake,
+}
+
+impl PluggableTransport {
+    // 网桥类型所需的传输插件，普通网桥不需要
+    pub fn for_bridge(bridge_type: &BridgeType) -> Option<Self> {
+        match bridge_type {
+            BridgeType::Vanilla => None,
+            BridgeType::Obfs4 | BridgeType::Meek => Some(PluggableTransport::Lyrebird),
+            BridgeType::Snowflake => Some(PluggableTransport::Snowflake),
+        }
+    }
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            PluggableTransport::Lyrebird => "lyrebird",
+            PluggableTransport::Snowflake => "snowflake-client",
+        }
+    }
+
+    fn executable(&self) -> Executable {
+        match self {
+            PluggableTransport::Lyrebird => Executable::Lyrebird,
+            PluggableTransport::Snowflake => Executable::Snowflake,
+        }
+    }
+
+    // 插件提供的传输方式，写入ClientTransportPlugin
+    fn methods(&self) -> &'static str {
+        match self {
+            PluggableTransport::Lyrebird => "meek_lite,obfs2,obfs3,obfs4,scramblesuit,webtunnel",
+            PluggableTransport::Snowflake => "snowflake",
+        }
+    }
+}
+
+// 传输插件状态
+#[derive(Clone, Debug, PartialEq)]
+pub enum TransportStatus {
+    Checking,
+    Ready(String), // 版本信息
+    Missing,
+    Broken(String),
+}
+
+// 后台检测结果缓存
+static CHECKS: Lazy<Mutex<HashMap<PluggableTransport, (Instant, TransportStatus)>>> = Lazy::new(|| Mutex::new(HashMap::new()));
+
+// 正在后台检测的插件
+static IN_FLIGHT: Lazy<Mutex<HashSet<PluggableTransport>>> = Lazy::new(|| Mutex::new(HashSet::new()));
+
+// 运行插件的-version确认可执行文件完好（未损坏、未被安全软件拦截）
+fn run_version(path: &Path) -> Result<String> {
+    let mut command = Command::new(path);
+    command.arg("-version");
+    #[cfg(target_os = "windows")]
+    {
+        use std::os::windows::process::CommandExt;
+        const CREATE_NO_WINDOW: u32 = 0x08000000;
+        command.creation_flags(CREATE_NO_WINDOW);
+    }
+    let output = command.output().map_err(|e| anyhow!("无法运行: {}", e))?;
+    let version = String::from_utf8_lossy(&output.stdout)
+        .lines()
+        .chain(String::from_utf8_lossy(&output.stderr).lines())
+        .map(str::trim)
+        .find(|line| !line.is_empty())
+        .map(str::to_string)
+        .unwrap_or_else(|| "未知版本".to_string());
+    Ok(version)
+}
+
+// 查找并校验插件（会阻塞调用线程）
+pub fn verify(transport: PluggableTransport) -> TransportStatus {
+    let status = match components::executable_path(transport.executable()) {
+        None => TransportStatus::Missing,
+        Some(path) => match run_version(&path) {
+            Ok(version) => TransportStatus::Ready(version),
+            Err(e) => TransportStatus::Broken(e.to_string()),
+        },
+    };
+    if let Ok(mut checks) = CHECKS.lock() {
+        checks.insert(transport, (Instant::now(), status.clone()));
+    }
+    status
+}
+
+// 获取插件状态，不阻塞界面：结果过期时在后台重新检测
+pub fn status(transport: PluggableTransport) -> TransportStatus {
+    let cached = CHECKS
+        .lock()
+        .ok()
+        .and_then(|checks| checks.get(&transport).cloned())
+        .map(|(checked_at, status)| (checked_at.elapsed() < CHECK_TTL, status));
+    if let Some((true, status)) = cached {
+        return status;
+    }
+    let started = IN_FLIGHT.lock().map(|mut f| f.insert(transport)).unwrap_or(false);
+    if started {
+        std::thread::spawn(move || {
+            verify(transport);
+            if let Ok(mut in_flight) = IN_FLIGHT.lock() {
+                in_flight.remove(&transport);
+            }
+        });
+    }
+    cached.map(|(_, status)| status).unwrap_or(TransportStatus::Checking)
+}
+
+// 为启用的网桥生成ClientTransportPlugin配置行，缺少插件时返回错误
+pub fn torrc_lines<'a>(bridge_types: impl Iterator<Item = &'a BridgeType>) -> Result<Vec<String>> {
+    let mut needed: Vec<PluggableTransport> = Vec::new();
+    for transport in bridge_types.filter_map(PluggableTransport::for_bridge) {
+        if !needed.contains(&transport) {
+            needed.push(transport);
+        }
+    }
+
+    let mut lines = Vec::new();
+    for transport in needed {
+        match verify(transport) {
+            TransportStatus::Ready(_) => {}
+            TransportStatus::Broken(reason) => {
+                return Err(anyhow!("传输插件{}无法运行: {}", transport.name(), reason));
+            }
+            _ => {
+                return Err(anyhow!("缺少传输插件{}，请在 设置 → 组件管理 中安装Tor", transport.name()));
+            }
+        }
+        if let Some(path) = components::executable_path(transport.executable()) {
+            // 插件由tor.exe启动，同样需要在断线保护中放行
+            services::record_launched(&path);
+            lines.push(format!("ClientTransportPlugin {} exec {}", transport.methods(), torrc_path(&path)));
+        }
+    }
+    Ok(lines)
+}
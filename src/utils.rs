@@ -1,30 +1,14 @@
-use std::net::{SocketAddr, TcpStream};
 use std::time::Duration;
 use std::fs::{self, File};
 use std::io::{Read, Write};
-use std::path::Path;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use once_cell::sync::Lazy;
 use serde::{Serialize, Deserialize};
-use anyhow::{Result, Context};
+use anyhow::{anyhow, Result, Context};
+use base64::{Engine as _, engine::general_purpose};
 use log::info;
 
-// 检查端口是否被占用
-pub fn is_port_in_use(host: &str, port: u16) -> bool {
-    match format!("{host}:{port}").parse::<SocketAddr>() {
-        Ok(addr) => TcpStream::connect_timeout(&addr, Duration::from_millis(100)).is_ok(),
-        Err(_) => false,
-    }
-}
-
-// 查找可用端口
-pub fn find_available_port(host: &str, start_port: u16) -> Option<u16> {
-    for port in start_port..65535 {
-        if !is_port_in_use(host, port) {
-            return Some(port);
-        }
-    }
-    None
-}
-
 // 保存配置到文件
 pub fn save_config<T: Serialize>(config: &T, file_path: &str) -> Result<()> {
     let config_dir = Path::new(file_path).parent().unwrap_or(Path::new(""));
@@ -51,10 +35,135 @@ pub fn load_config<T: for<'de> Deserialize<'de>>(file_path: &str) -> Result<T> {
     Ok(config)
 }
 
+// 加载配置时遇到的问题（如无法解密的字段），由主界面取出后写入日志
+static CONFIG_PROBLEMS: Lazy<Mutex<Vec<String>>> = Lazy::new(|| Mutex::new(Vec::new()));
+
+// 记录加载配置时遇到的问题
+pub fn report_config_problem(message: String) {
+    if let Ok(mut problems) = CONFIG_PROBLEMS.lock() {
+        problems.push(message);
+    }
+}
+
+// 取出尚未写入日志的配置问题
+pub fn take_config_problems() -> Vec<String> {
+    CONFIG_PROBLEMS.lock().map(|mut problems| problems.drain(..).collect()).unwrap_or_default()
+}
+
+// 带版本号的配置结构，由save_versioned_config/load_versioned_config使用
+pub trait VersionedConfig: Serialize + for<'de> Deserialize<'de> {
+    // 当前配置格式的版本号，字段增加、重命名时递增
+    const VERSION: u32;
+    
+    // 将配置从from_version升级到from_version + 1，默认不做修改
+    // 版本0表示引入版本号之前保存的配置
+    fn migrate(from_version: u32, data: serde_json::Value) -> Result<serde_json::Value> {
+        let _ = from_version;
+        Ok(data)
+    }
+}
+
+// 配置文件的外层格式
+#[derive(Serialize, Deserialize)]
+struct ConfigEnvelope<T> {
+    version: u32,
+    data: T,
+}
+
+// 保存带版本号的配置
+pub fn save_versioned_config<T: VersionedConfig>(config: &T, file_path: &str) -> Result<()> {
+    save_config(&ConfigEnvelope { version: T::VERSION, data: config }, file_path)
+}
+
+// 加载带版本号的配置，旧版本会先备份原文件再逐级升级
+pub fn load_versioned_config<T: VersionedConfig>(file_path: &str) -> Result<T> {
+    let value: serde_json::Value = load_config(file_path)?;
+    
+    // 没有外层结构的文件来自引入版本号之前，视为版本0
+    let (version, mut data) = match value {
+        serde_json::Value::Object(mut map) if map.contains_key("version") && map.contains_key("data") => {
+            let version = map.get("version")
+                .and_then(|v| v.as_u64())
+                .context("Invalid config version")? as u32;
+            (version, map.remove("data").unwrap_or_default())
+        }
+        other => (0, other),
+    };
+    
+    if version > T::VERSION {
+        return Err(anyhow!(
+            "Config file {} was written by a newer version (v{} > v{})",
+            file_path, version, T::VERSION
+        ));
+    }
+    
+    if version < T::VERSION {
+        let backup_path = format!("{}.v{}.bak", file_path, version);
+        fs::copy(file_path, &backup_path).context("Failed to back up config file")?;
+        info!("Backed up {} to {} before migration", file_path, backup_path);
+        
+        for from_version in version..T::VERSION {
+            data = T::migrate(from_version, data)
+                .with_context(|| format!("Failed to migrate config from v{}", from_version))?;
+        }
+    }
+    
+    let config: T = serde_json::from_value(data).context("Failed to parse config file")?;
+    if version < T::VERSION {
+        save_versioned_config(&config, file_path)?;
+        info!("Migrated {} from v{} to v{}", file_path, version, T::VERSION);
+    }
+    Ok(config)
+}
+
+// 加载应用数据目录下的配置文件，文件不存在时返回None。
+// 无法读取时先备份原文件（调用方随后使用默认值，下次保存会覆盖原文件），并提示用户
+pub fn load_saved_config<T: VersionedConfig>(file_name: &str) -> Option<T> {
+    let path = get_config_path(file_name).ok()?;
+    if !Path::new(&path).exists() {
+        return None;
+    }
+    match load_versioned_config(&path) {
+        Ok(config) => Some(config),
+        Err(e) => {
+            let backup_path = format!("{}.{}.unreadable.bak", path, chrono::Local::now().format("%Y%m%d-%H%M%S"));
+            let backup = match fs::copy(&path, &backup_path) {
+                Ok(_) => format!("原文件已备份为 {}", backup_path),
+                Err(copy_error) => format!("备份原文件失败: {}", copy_error),
+            };
+            report_config_problem(format!("无法读取配置文件 {}，已改用默认设置，{}: {:#}", file_name, backup, e));
+            None
+        }
+    }
+}
+
+// 便携模式标记文件，放在可执行文件旁边
+const PORTABLE_FLAG_FILE: &str = "portable.flag";
+
+// 便携模式下的数据根目录（可执行文件所在目录），非便携模式为None
+static PORTABLE_ROOT: Lazy<Option<PathBuf>> = Lazy::new(|| {
+    let exe_dir = std::env::current_exe().ok()?.parent()?.to_path_buf();
+    let requested = std::env::args().any(|arg| arg == "--portable");
+    if requested || exe_dir.join(PORTABLE_FLAG_FILE).exists() {
+        info!("Portable mode enabled, data root: {}", exe_dir.display());
+        Some(exe_dir)
+    } else {
+        None
+    }
+});
+
+// 是否以便携模式运行
+pub fn is_portable() -> bool {
+    PORTABLE_ROOT.is_some()
+}
+
 // 获取应用程序数据目录
+// 便携模式下位于可执行文件旁的data目录，否则位于用户目录
 pub fn get_app_data_dir() -> Result<String> {
-    let home_dir = dirs::home_dir().context("Failed to get home directory")?;
-    let app_dir = home_dir.join(".invizible-pro");
+    let app_dir = match PORTABLE_ROOT.as_ref() {
+        Some(root) => root.join("data"),
+        None => dirs::home_dir().context("Failed to get home directory")?.join(".invizible-pro"),
+    };
     
     if !app_dir.exists() {
         fs::create_dir_all(&app_dir).context("Failed to create app data directory")?;
@@ -63,8 +172,101 @@ pub fn get_app_data_dir() -> Result<String> {
     Ok(app_dir.to_string_lossy().to_string())
 }
 
+// 获取存放外部程序（tor、dnscrypt-proxy、i2pd等）的目录
+pub fn get_bin_dir() -> Result<String> {
+    let bin_dir = match PORTABLE_ROOT.as_ref() {
+        Some(root) => root.join("bin"),
+        None => Path::new(&get_app_data_dir()?).join("bin"),
+    };
+    
+    if !bin_dir.exists() {
+        fs::create_dir_all(&bin_dir).context("Failed to create binary directory")?;
+    }
+    
+    Ok(bin_dir.to_string_lossy().to_string())
+}
+
+// 获取应用数据目录下指定配置文件的路径
+pub fn get_config_path(file_name: &str) -> Result<String> {
+    let app_dir = get_app_data_dir()?;
+    Ok(Path::new(&app_dir).join(file_name).to_string_lossy().to_string())
+}
+
+// 管理员权限在进程生命周期内不会变化，只需检查一次
+static IS_ADMIN: Lazy<bool> = Lazy::new(check_admin);
+
 // 检查应用程序是否以管理员权限运行
 pub fn is_running_as_admin() -> bool {
+    *IS_ADMIN
+}
+
+// 按CommandLineToArgvW的规则为参数加引号：引号前的反斜杠加倍后再转义引号，
+// 结尾的反斜杠加倍以免吞掉收尾的引号，空参数与含空白的参数用引号包围
+fn quote_arg(arg: &str) -> String {
+    if !arg.is_empty() && !arg.contains([' ', '\t', '\n', '\x0b', '"']) {
+        return arg.to_string();
+    }
+    let mut quoted = String::from("\"");
+    let mut backslashes = 0;
+    for c in arg.chars() {
+        match c {
+            '\\' => backslashes += 1,
+            '"' => {
+                quoted.push_str(&"\\".repeat(backslashes * 2 + 1));
+                quoted.push('"');
+                backslashes = 0;
+            }
+            _ => {
+                quoted.push_str(&"\\".repeat(backslashes));
+                quoted.push(c);
+                backslashes = 0;
+            }
+        }
+    }
+    quoted.push_str(&"\\".repeat(backslashes * 2));
+    quoted.push('"');
+    quoted
+}
+
+// 以管理员身份重新启动本程序（会弹出UAC提示），成功后退出当前进程
+pub fn relaunch_as_admin() -> Result<()> {
+    let exe = std::env::current_exe().context("Failed to get executable path")?;
+    // 保留原有启动参数（如--portable）
+    let args: Vec<String> = std::env::args().skip(1).map(|arg| quote_arg(&arg)).collect();
+    
+    #[cfg(target_os = "windows")]
+    {
+        use std::ffi::OsStr;
+        use std::os::windows::ffi::OsStrExt;
+        use std::ptr::{null, null_mut};
+        use winapi::um::shellapi::ShellExecuteW;
+        use winapi::um::winuser::SW_SHOWNORMAL;
+        
+        let wide = |s: &OsStr| s.encode_wide().chain(std::iter::once(0)).collect::<Vec<u16>>();
+        let verb = wide(OsStr::new("runas"));
+        let file = wide(exe.as_os_str());
+        let params = wide(OsStr::new(&args.join(" ")));
+        
+        let result = unsafe {
+            ShellExecuteW(null_mut(), verb.as_ptr(), file.as_ptr(), params.as_ptr(), null(), SW_SHOWNORMAL)
+        };
+        // 返回值小于等于32表示失败（包括用户在UAC提示中选择了"否"）
+        if result as usize <= 32 {
+            return Err(anyhow!("Elevation was cancelled or failed (code {})", result as usize));
+        }
+        
+        info!("Relaunched {} as administrator", exe.display());
+        std::process::exit(0);
+    }
+    
+    #[cfg(not(target_os = "windows"))]
+    {
+        let _ = (exe, args);
+        Err(anyhow!("Elevation is only supported on Windows"))
+    }
+}
+
+fn check_admin() -> bool {
     #[cfg(target_os = "windows")]
     {
         use winapi::um::winnt::{SECURITY_BUILTIN_DOMAIN_RID, DOMAIN_ALIAS_RID_ADMINS};
@@ -117,6 +319,189 @@ pub fn is_running_as_admin() -> bool {
     }
 }
 
+// 已加密敏感信息的前缀，用于区分旧版本保存的明文
+const SECRET_PREFIX: &str = "dpapi:";
+
+// 使用DPAPI加密敏感信息（仅当前Windows用户可以解密）
+pub fn protect_secret(plain: &str) -> Result<String> {
+    if plain.is_empty() {
+        return Ok(String::new());
+    }
+    
+    #[cfg(target_os = "windows")]
+    {
+        let encrypted = dpapi_transform(plain.as_bytes(), true)?;
+        Ok(format!("{}{}", SECRET_PREFIX, general_purpose::STANDARD.encode(encrypted)))
+    }
+    
+    #[cfg(not(target_os = "windows"))]
+    {
+        Ok(plain.to_string())
+    }
+}
+
+// 解密敏感信息，没有前缀的值视为旧版本保存的明文
+pub fn unprotect_secret(stored: &str) -> Result<String> {
+    let encoded = match stored.strip_prefix(SECRET_PREFIX) {
+        Some(encoded) => encoded,
+        None => return Ok(stored.to_string()),
+    };
+    let data = general_purpose::STANDARD.decode(encoded).context("Invalid encrypted secret")?;
+    
+    #[cfg(target_os = "windows")]
+    {
+        let decrypted = dpapi_transform(&data, false)?;
+        String::from_utf8(decrypted).context("Decrypted secret is not valid UTF-8")
+    }
+    
+    #[cfg(not(target_os = "windows"))]
+    {
+        let _ = data;
+        Err(anyhow!("DPAPI is only available on Windows"))
+    }
+}
+
+// 调用CryptProtectData/CryptUnprotectData
+#[cfg(target_os = "windows")]
+fn dpapi_transform(data: &[u8], encrypt: bool) -> Result<Vec<u8>> {
+    use winapi::um::dpapi::{CryptProtectData, CryptUnprotectData, CRYPTPROTECT_UI_FORBIDDEN};
+    use winapi::um::wincrypt::DATA_BLOB;
+    use winapi::um::winbase::LocalFree;
+    use std::ptr::null_mut;
+    
+    let mut input = data.to_vec();
+    let mut in_blob = DATA_BLOB { cbData: input.len() as u32, pbData: input.as_mut_ptr() };
+    let mut out_blob = DATA_BLOB { cbData: 0, pbData: null_mut() };
+    
+    unsafe {
+        let result = if encrypt {
+            CryptProtectData(&mut in_blob, null_mut(), null_mut(), null_mut(), null_mut(), CRYPTPROTECT_UI_FORBIDDEN, &mut out_blob)
+        } else {
+            CryptUnprotectData(&mut in_blob, null_mut(), null_mut(), null_mut(), null_mut(), CRYPTPROTECT_UI_FORBIDDEN, &mut out_blob)
+        };
+        if result == 0 {
+            return Err(anyhow!(std::io::Error::last_os_error())).context("DPAPI operation failed");
+        }
+        
+        // 输出缓冲区由系统分配，需要用LocalFree释放
+        let output = std::slice::from_raw_parts(out_blob.pbData, out_blob.cbData as usize).to_vec();
+        LocalFree(out_blob.pbData as *mut _);
+        Ok(output)
+    }
+}
+
+// 敏感字段的serde适配，保存时加密、加载时解密
+// 用法: #[serde(with = "crate::utils::secret_string")]
+pub mod secret_string {
+    use serde::{Deserialize, Deserializer, Serializer};
+    
+    pub fn serialize<S: Serializer>(value: &str, serializer: S) -> Result<S::Ok, S::Error> {
+        let protected = super::protect_secret(value).map_err(serde::ser::Error::custom)?;
+        serializer.serialize_str(&protected)
+    }
+    
+    // 无法解密时（如配置来自其他电脑或其他Windows用户）只清空该字段，不影响配置中的其他设置
+    pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<String, D::Error> {
+        let stored = String::deserialize(deserializer)?;
+        match super::unprotect_secret(&stored) {
+            Ok(plain) => Ok(plain),
+            Err(e) => {
+                super::report_config_problem(format!("无法解密已保存的密码或令牌，已清空该项，请重新填写: {:#}", e));
+                Ok(String::new())
+            }
+        }
+    }
+}
+
+// 转义PowerShell单引号字符串
+pub fn ps_quote(value: &str) -> String {
+    format!("'{}'", value.replace('\'', "''"))
+}
+
+// 超过该长度的PowerShell脚本通过临时文件执行
+const MAX_INLINE_SCRIPT: usize = 8000;
+
+// 执行PowerShell脚本，失败时返回错误输出
+pub fn run_powershell(script: &str) -> Result<String> {
+    // 命令行长度有限（约32K字符），较长的脚本写入临时文件执行
+    let script_file = if script.len() > MAX_INLINE_SCRIPT {
+        static NEXT_SCRIPT: std::sync::atomic::AtomicUsize = std::sync::atomic::AtomicUsize::new(0);
+        let index = NEXT_SCRIPT.fetch_add(1, std::sync::atomic::Ordering::SeqCst);
+        let path = std::env::temp_dir().join(format!("invizible_{}_{}.ps1", std::process::id(), index));
+        // 带BOM的UTF-8，Windows PowerShell才能正确读取中文
+        fs::write(&path, format!("\u{feff}{}", script)).context("Failed to write powershell script")?;
+        Some(path)
+    } else {
+        None
+    };
+    let mut command = std::process::Command::new("powershell");
+    match &script_file {
+        Some(path) => command.args(["-NoProfile", "-ExecutionPolicy", "Bypass", "-File"]).arg(path),
+        None => command.args(["-NoProfile", "-Command", script]),
+    };
+    #[cfg(target_os = "windows")]
+    {
+        use std::os::windows::process::CommandExt;
+        const CREATE_NO_WINDOW: u32 = 0x08000000;
+        command.creation_flags(CREATE_NO_WINDOW);
+    }
+    let output = command.output();
+    if let Some(path) = &script_file {
+        let _ = fs::remove_file(path);
+    }
+    let output = output.context("Failed to run powershell")?;
+    if !output.status.success() {
+        return Err(anyhow!("{}", String::from_utf8_lossy(&output.stderr).trim()));
+    }
+    Ok(String::from_utf8_lossy(&output.stdout).to_string())
+}
+
+// 显示Windows通知（借用PowerShell的通知身份），在后台线程中执行，失败时忽略
+pub fn show_toast(title: &str, body: &str) {
+    let script = format!(
+        "[Windows.UI.Notifications.ToastNotificationManager, Windows.UI.Notifications, ContentType = WindowsRuntime] | Out-Null; \
+         $xml = [Windows.UI.Notifications.ToastNotificationManager]::GetTemplateContent([Windows.UI.Notifications.ToastTemplateType]::ToastText02); \
+         $text = $xml.GetElementsByTagName('text'); \
+         $text.Item(0).AppendChild($xml.CreateTextNode({})) | Out-Null; \
+         $text.Item(1).AppendChild($xml.CreateTextNode({})) | Out-Null; \
+         [Windows.UI.Notifications.ToastNotificationManager]::CreateToastNotifier('{{1AC14E77-02E7-4E5D-B744-2EB1AE5198B7}}\\WindowsPowerShell\\v1.0\\powershell.exe').Show([Windows.UI.Notifications.ToastNotification]::new($xml))",
+        ps_quote(title),
+        ps_quote(body)
+    );
+    std::thread::spawn(move || {
+        let _ = run_powershell(&script);
+    });
+}
+
+// 获取进程可执行文件的完整路径，进程已退出或无权访问时返回None
+#[cfg(target_os = "windows")]
+pub fn process_path(pid: u32) -> Option<String> {
+    use winapi::um::handleapi::CloseHandle;
+    use winapi::um::processthreadsapi::OpenProcess;
+    use winapi::um::winbase::QueryFullProcessImageNameW;
+    use winapi::um::winnt::PROCESS_QUERY_LIMITED_INFORMATION;
+
+    unsafe {
+        let process = OpenProcess(PROCESS_QUERY_LIMITED_INFORMATION, 0, pid);
+        if process.is_null() {
+            return None;
+        }
+        let mut buffer = [0u16; 1024];
+        let mut size = buffer.len() as u32;
+        let result = QueryFullProcessImageNameW(process, 0, buffer.as_mut_ptr(), &mut size);
+        CloseHandle(process);
+        if result == 0 {
+            return None;
+        }
+        Some(String::from_utf16_lossy(&buffer[..size as usize]))
+    }
+}
+
+#[cfg(not(target_os = "windows"))]
+pub fn process_path(_pid: u32) -> Option<String> {
+    None
+}
+
 // 格式化字节大小为人类可读的形式
 pub fn format_bytes(bytes: u64) -> String {
     const UNITS: [&str; 5] = ["B", "KB", "MB", "GB", "TB"];
@@ -129,4 +514,47 @@ pub fn format_bytes(bytes: u64) -> String {
     }
     
     format!("{:.2} {}", size, UNITS[unit_index])
-}
\ No newline at end of file
+}
+
+// 公网IP信息
+#[derive(Clone, Debug, Deserialize)]
+pub struct PublicIpInfo {
+    pub ip: String,
+    #[serde(default)]
+    pub country: String,
+}
+
+// 查询当前公网IP及所在国家（可通过代理查询，以反映隧道出口）
+pub fn fetch_public_ip(proxy_url: Option<&str>) -> Result<PublicIpInfo> {
+    let mut builder = reqwest::blocking::Client::builder().timeout(Duration::from_secs(15));
+    if let Some(proxy_url) = proxy_url {
+        let proxy = reqwest::Proxy::all(proxy_url).context("Invalid proxy url")?;
+        builder = builder.proxy(proxy);
+    }
+    let client = builder.build().context("Failed to build http client")?;
+    
+    let info: PublicIpInfo = client
+        .get("https://ipinfo.io/json")
+        .send()
+        .context("Failed to query public ip")?
+        .json()
+        .context("Failed to parse public ip response")?;
+    Ok(info)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn quotes_args_like_command_line_to_argv() {
+        assert_eq!(quote_arg("--portable"), "--portable");
+        assert_eq!(quote_arg("C:\\dir\\file"), "C:\\dir\\file");
+        assert_eq!(quote_arg(""), "\"\"");
+        assert_eq!(quote_arg("a b"), "\"a b\"");
+        assert_eq!(quote_arg("a\tb"), "\"a\tb\"");
+        assert_eq!(quote_arg("say \"hi\""), "\"say \\\"hi\\\"\"");
+        assert_eq!(quote_arg("a\\\"b"), "\"a\\\\\\\"b\"");
+        assert_eq!(quote_arg("C:\\my dir\\"), "\"C:\\my dir\\\\\"");
+    }
+}
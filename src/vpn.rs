@@ -1,15 +1,21 @@
 use eframe::egui::{self, Color32, RichText, Ui};
 use std::sync::{Arc, Mutex};
+use std::net::{TcpStream, ToSocketAddrs};
+use std::time::{Duration, Instant};
 use serde::{Deserialize, Serialize};
 use reqwest::blocking::Client;
-use base64::{Engine as _, engine::general_purpose};
-use yaml_rust::{YamlLoader, Yaml};
 use chrono;
 
 use crate::logger::Logger;
+use crate::traffic::{self, TrafficSource};
+use crate::utils;
+use crate::vpn_url;
 
 use crate::app::VPN_COLOR;
 
+// VPN配置文件名
+const CONFIGS_FILE: &str = "vpn.json";
+
 // VPN协议类型
 #[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
 pub enum VpnProtocol {
@@ -28,7 +34,8 @@ pub struct VpnConfig {
     pub protocol: VpnProtocol,
     pub server: String,
     pub port: u16,
-    pub uuid: String,
+    #[serde(with = "crate::utils::secret_string")]
+    pub uuid: String, // 密码或UUID，加密保存
     pub encryption: String,
     pub enabled: bool,
 }
@@ -53,7 +60,8 @@ impl VpnConfig {
 pub struct ClashSubscription {
     pub id: usize,
     pub name: String,
-    pub url: String,
+    #[serde(with = "crate::utils::secret_string")]
+    pub url: String, // 订阅链接通常包含访问令牌，加密保存
     pub last_updated: String,
     pub configs: Vec<VpnConfig>,
 }
@@ -70,6 +78,17 @@ impl ClashSubscription {
     }
 }
 
+// 保存的VPN配置与订阅，密码/UUID与订阅链接加密保存
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+struct SavedConfigs {
+    configs: Vec<VpnConfig>,
+    subscriptions: Vec<ClashSubscription>,
+}
+
+impl utils::VersionedConfig for SavedConfigs {
+    const VERSION: u32 = 1;
+}
+
 // VPN模块结构
 pub struct VpnModule {
     enabled: bool,
@@ -89,8 +108,11 @@ pub struct VpnModule {
     new_subscription_name: String,
     new_subscription_url: String,
     edit_mode: bool,
+    adding_subscription: bool, // 对话框用于添加订阅而不是配置
+    import_url: String,
     connection_status: String,
     show_subscription_warning: bool,
+    traffic: Arc<traffic::Counter>,
 }
 
 // 修复VpnModule的闭合问题
@@ -114,12 +136,27 @@ impl VpnModule {
             new_subscription_name: String::new(),
             new_subscription_url: String::new(),
             edit_mode: false,
+            adding_subscription: false,
+            import_url: String::new(),
             connection_status: "未连接".to_string(),
             show_subscription_warning: false,
+            traffic: Arc::new(traffic::Counter::default()),
         };
         
-        // 添加一些示例配置
-        module.add_example_configs();
+        // 读取保存的配置，从未保存过时添加一些示例配置
+        match module.load_configs() {
+            Some(saved) => {
+                module.next_config_id = saved.configs.iter()
+                    .chain(saved.subscriptions.iter().flat_map(|s| s.configs.iter()))
+                    .map(|c| c.id + 1)
+                    .max()
+                    .unwrap_or(1);
+                module.next_subscription_id = saved.subscriptions.iter().map(|s| s.id + 1).max().unwrap_or(1);
+                module.configs = saved.configs;
+                module.subscriptions = saved.subscriptions;
+            }
+            None => module.add_example_configs(),
+        }
         
         // 记录模块初始化日志
         if let Ok(mut logger) = module.logger.lock() {
@@ -129,6 +166,11 @@ impl VpnModule {
         module
     }
     
+    // 将经隧道收发的流量上报到流量统计
+    pub fn tick(&self) {
+        self.traffic.report(TrafficSource::Vpn);
+    }
+    
     // 添加示例配置
     fn add_example_configs(&mut self) {
         // 添加一些示例VPN配置
@@ -157,6 +199,24 @@ impl VpnModule {
         self.next_config_id += 1;
     }
     
+    fn load_configs(&self) -> Option<SavedConfigs> {
+        utils::load_saved_config(CONFIGS_FILE)
+    }
+    
+    // 保存配置与订阅
+    fn save_configs(&self) {
+        let saved = SavedConfigs {
+            configs: self.configs.clone(),
+            subscriptions: self.subscriptions.clone(),
+        };
+        let result = utils::get_config_path(CONFIGS_FILE).and_then(|path| utils::save_versioned_config(&saved, &path));
+        if let Err(e) = result {
+            if let Ok(mut logger) = self.logger.lock() {
+                logger.error("VPN", &format!("保存VPN配置失败: {}", e));
+            }
+        }
+    }
+    
     // 添加新配置
     fn add_config(&mut self, config: VpnConfig) {
         if let Ok(mut logger) = self.logger.lock() {
@@ -164,6 +224,7 @@ impl VpnModule {
         }
         self.configs.push(config);
         self.next_config_id += 1;
+        self.save_configs();
     }
     
     // 删除配置
@@ -177,6 +238,7 @@ impl VpnModule {
             if self.selected_config == Some(id) {
                 self.selected_config = None;
             }
+            self.save_configs();
         }
     }
     
@@ -187,6 +249,7 @@ impl VpnModule {
         }
         self.subscriptions.push(subscription);
         self.next_subscription_id += 1;
+        self.save_configs();
     }
     
     // 删除订阅
@@ -200,49 +263,58 @@ impl VpnModule {
             if self.selected_subscription == Some(id) {
                 self.selected_subscription = None;
             }
+            self.save_configs();
         }
     }
     
     // 更新订阅
     fn update_subscription(&mut self, id: usize) {
-        if let Some(subscription) = self.subscriptions.iter_mut().find(|s| s.id == id) {
-            {
-                if let Ok(mut logger) = self.logger.lock() {
-                    logger.info("VPN", &format!("正在更新Clash订阅: {}", subscription.name));
-                }
-            }
+        let (name, url) = match self.subscriptions.iter().find(|s| s.id == id) {
+            Some(subscription) => (subscription.name.clone(), subscription.url.clone()),
+            None => return,
+        };
+        if let Ok(mut logger) = self.logger.lock() {
+            logger.info("VPN", &format!("正在更新Clash订阅: {}", name));
+        }
 
-            let url = subscription.url.clone();
-            match self.download_and_parse_clash_config(&url) {
-                Ok(configs) => {
-                    let now = chrono::Local::now();
-                    subscription.last_updated = now.format("%Y-%m-%d %H:%M:%S").to_string();
-                    
-                    let mut current_id = self.next_config_id;
-                    let new_configs: Vec<VpnConfig> = configs.into_iter()
-                        .map(|mut config| {
-                            config.id = current_id;
-                            current_id += 1;
-                            config
-                        })
-                        .collect();
-                    
+        match self.download_and_parse_clash_config(&url) {
+            Ok(configs) => {
+                let mut current_id = self.next_config_id;
+                let new_configs: Vec<VpnConfig> = configs.into_iter()
+                    .map(|mut config| {
+                        config.id = current_id;
+                        current_id += 1;
+                        config
+                    })
+                    .collect();
+                self.next_config_id = current_id;
+                
+                let count = new_configs.len();
+                if let Some(subscription) = self.subscriptions.iter_mut().find(|s| s.id == id) {
+                    subscription.last_updated = chrono::Local::now().format("%Y-%m-%d %H:%M:%S").to_string();
                     subscription.configs = new_configs;
-                    self.next_config_id = current_id;
-                    
-                    if let Ok(mut logger) = self.logger.lock() {
-                        logger.info("VPN", &format!("Clash订阅 {} 已更新，添加了 {} 个配置", 
-                                                  subscription.name, subscription.configs.len()));
-                    }
-                },
-                Err(err) => {
-                    if let Ok(mut logger) = self.logger.lock() {
-                        logger.error("VPN", &format!("更新Clash订阅失败: {}", err));
-                    }
+                }
+                
+                if let Ok(mut logger) = self.logger.lock() {
+                    logger.info("VPN", &format!("Clash订阅 {} 已更新，添加了 {} 个配置", name, count));
+                }
+            },
+            Err(err) => {
+                if let Ok(mut logger) = self.logger.lock() {
+                    logger.error("VPN", &format!("更新Clash订阅失败: {}", err));
                 }
             }
-        }  // 结束if let块
-    }  // 正确闭合update_subscription方法
+        }
+        self.save_configs();
+    }
+    
+    // 更新所有订阅
+    pub fn update_all_subscriptions(&mut self) {
+        let ids: Vec<usize> = self.subscriptions.iter().map(|s| s.id).collect();
+        for id in ids {
+            self.update_subscription(id);
+        }
+    }
     
     // 下载并解析Clash配置
     fn download_and_parse_clash_config(&self, url: &str) -> Result<Vec<VpnConfig>, String> {
@@ -266,29 +338,7 @@ impl VpnModule {
             Err(e) => return Err(format!("读取响应内容失败: {}", e)),
         };
         
-        // 解析YAML
-        let docs = match YamlLoader::load_from_str(&content) {
-            Ok(docs) => docs,
-            Err(e) => return Err(format!("解析YAML失败: {}", e)),
-        };
-        
-        if docs.is_empty() {
-            return Err("YAML文档为空".to_string());
-        }
-        
-        let doc = &docs[0];
-        
-        // 解析代理配置
-        let mut configs = Vec::new();
-        
-        // 尝试获取proxies字段
-        if let Some(proxies) = doc["proxies"].as_vec() {
-            for (i, proxy) in proxies.iter().enumerate() {
-                if let Some(config) = self.parse_clash_proxy(proxy, i) {
-                    configs.push(config);
-                }
-            }
-        }
+        let configs = vpn_url::parse_clash(&content)?;
         
         if let Ok(mut logger) = self.logger.lock() {
             logger.info("VPN", &format!("成功解析 {} 个VPN配置", configs.len()));
@@ -297,427 +347,31 @@ impl VpnModule {
         Ok(configs)
     }
     
-    // 解析单个Clash代理配置
-    fn parse_clash_proxy(&self, proxy: &Yaml, index: usize) -> Option<VpnConfig> {
-        // 处理名称，确保使用String而不是&str
-        let name_str = match proxy["name"].as_str() {
-            Some(s) => s.to_string(),
-            _ => format!("未命名代理{}", index)
-        };
-        
-        // 使用to_string()确保proxy_type是String类型
-        let proxy_type = proxy["type"].as_str().unwrap_or("unknown").to_string();
-        
-        match proxy_type.to_lowercase().as_str() {
-            "vmess" => {
-                let server = proxy["server"].as_str().unwrap_or("unknown").to_string();
-                let port = proxy["port"].as_i64().unwrap_or(443) as u16;
-                let uuid = proxy["uuid"].as_str().unwrap_or("").to_string();
-                let encryption = proxy["cipher"].as_str().unwrap_or("auto").to_string();
-                
-                Some(VpnConfig::new(
-                    0, // 临时ID，会在调用方重新分配
-                    &name_str,
-                    VpnProtocol::Vmess,
-                    &server,
-                    port,
-                    &uuid,
-                    &encryption
-                ))
-            },
-            "ss" | "shadowsocks" => {
-                let server = proxy["server"].as_str().unwrap_or("unknown").to_string();
-                let port = proxy["port"].as_i64().unwrap_or(8388) as u16;
-                let password = proxy["password"].as_str().unwrap_or("").to_string();
-                let encryption = proxy["cipher"].as_str().unwrap_or("aes-256-gcm").to_string();
-                
-                Some(VpnConfig::new(
-                    0, // 临时ID，会在调用方重新分配
-                    &name_str,
-                    VpnProtocol::Shadowsocks,
-                    &server,
-                    port,
-                    &password,
-                    &encryption
-                ))
-            },
-            "trojan" => {
-                let server = proxy["server"].as_str().unwrap_or("unknown").to_string();
-                let port = proxy["port"].as_i64().unwrap_or(443) as u16;
-                let password = proxy["password"].as_str().unwrap_or("").to_string();
-                
-                Some(VpnConfig::new(
-                    0, // 临时ID，会在调用方重新分配
-                    &name_str,
-                    VpnProtocol::Trojan,
-                    &server,
-                    port,
-                    &password,
-                    "auto"
-                ))
-            },
-            _ => None
-        }
-    }
-    
-    // 从Base64编码的URL解析Vmess配置
-    fn parse_vmess_url(&self, vmess_url: &str) -> Result<VpnConfig, String> {
-        // vmess://base64(json)
-        if !vmess_url.starts_with("vmess://") {
-            return Err("不是有效的Vmess URL".to_string());
-        }
-        
-        let base64_str = &vmess_url[8..]; // 去掉 "vmess://"
-        
-        // 解码Base64
-        let decoded = match general_purpose::STANDARD.decode(base64_str) {
-            Ok(bytes) => bytes,
-            Err(_) => return Err("Base64解码失败".to_string()),
-        };
-        
-        // 解析JSON
-        let json_str = match String::from_utf8(decoded) {
-            Ok(s) => s,
-            Err(_) => return Err("UTF-8解码失败".to_string()),
-        };
-        
-        // 解析JSON
-        let json: serde_json::Value = match serde_json::from_str(&json_str) {
-            Ok(v) => v,
-            Err(e) => return Err(format!("JSON解析失败: {}", e)),
-        };
+    // 导入VPN配置URL
+    fn import_vpn_url(&mut self, url_str: &str) -> Result<(), String> {
+        let config = vpn_url::parse(url_str)?;
         
-        // 提取配置信息
-        let name = json["ps"].as_str().unwrap_or("从URL导入的Vmess");
-        let server = json["add"].as_str().unwrap_or("unknown");
-        let port_str = json["port"].as_str().unwrap_or("443");
-        let port = port_str.parse::<u16>().unwrap_or(443);
-        let uuid = json["id"].as_str().unwrap_or("");
-        let encryption = json["scy"].as_str().unwrap_or("auto");
+        // 获取下一个ID并递增
+        let next_id = self.next_config_id;
+        self.next_config_id += 1;
         
-        let config = VpnConfig::new(
-            0, // 临时ID，会在调用方重新分配
-            name,
-            VpnProtocol::Vmess,
-            server,
-            port,
-            uuid,
-            encryption
+        let config_with_id = VpnConfig::new(
+            next_id,
+            &config.name,
+            config.protocol,
+            &config.server,
+            config.port,
+            &config.uuid,
+            &config.encryption
         );
         
-        Ok(config)
-    }
-    
-    // 从Base64编码的URL解析Shadowsocks配置
-    fn parse_shadowsocks_url(&self, ss_url: &str) -> Result<VpnConfig, String> {
-        // ss://base64(method:password@host:port)#tag
-        if !ss_url.starts_with("ss://") {
-            return Err("不是有效的Shadowsocks URL".to_string());
-        }
-        
-        let mut parts = ss_url[5..].split('#');
-        let main_part = parts.next().unwrap_or("");
-        let tag = parts.next().unwrap_or("从URL导入的Shadowsocks");
-        
-        // 解码Base64
-        let decoded = match general_purpose::STANDARD.decode(main_part) {
-            Ok(bytes) => bytes,
-            Err(_) => {
-                // 尝试新格式: ss://method:password@server:port
-                let without_prefix = &ss_url[5..];
-                let parts: Vec<&str> = without_prefix.split('#').collect();
-                let main_part = parts[0];
-                
-                // 解析主要部分
-                if let Some(at_pos) = main_part.find('@') {
-                    let method_pass = &main_part[..at_pos];
-                    let server_port = &main_part[at_pos+1..];
-                    
-                    if let Some(colon_pos) = method_pass.find(':') {
-                        let method = &method_pass[..colon_pos];
-                        let password = &method_pass[colon_pos+1..];
-                        
-                        if let Some(colon_pos) = server_port.find(':') {
-                            let server = &server_port[..colon_pos];
-                            let port_str = &server_port[colon_pos+1..];
-                            
-                            if let Ok(port) = port_str.parse::<u16>() {
-                                let config = VpnConfig::new(
-                                    0,
-                                    tag,
-                                    VpnProtocol::Shadowsocks,
-                                    server,
-                                    port,
-                                    password,
-                                    method
-                                );
-                                return Ok(config);
-                            }
-                        }
-                    }
-                }
-                
-                return Err("无法解析Shadowsocks URL".to_string());
-            }
-        };
-        
-        let decoded_str = match String::from_utf8(decoded) {
-            Ok(s) => s,
-            Err(_) => return Err("UTF-8解码失败".to_string()),
-        };
-        
-        // 解析格式: method:password@server:port
-        if let Some(at_pos) = decoded_str.find('@') {
-            let method_pass = &decoded_str[..at_pos];
-            let server_port = &decoded_str[at_pos+1..];
-            
-            if let Some(colon_pos) = method_pass.find(':') {
-                let method = &method_pass[..colon_pos];
-                let password = &method_pass[colon_pos+1..];
-                
-                if let Some(colon_pos) = server_port.find(':') {
-                    let server = &server_port[..colon_pos];
-                    let port_str = &server_port[colon_pos+1..];
-                    
-                    if let Ok(port) = port_str.parse::<u16>() {
-                        let config = VpnConfig::new(
-                            0,
-                            tag,
-                            VpnProtocol::Shadowsocks,
-                            server,
-                            port,
-                            password,
-                            method
-                        );
-                        return Ok(config);
-                    }
-                }
-            }
-        }
-        
-        Err("无法解析Shadowsocks URL格式".to_string())
-    }
-    
-    // 从URL解析Trojan配置
-    fn parse_trojan_url(&self, trojan_url: &str) -> Result<VpnConfig, String> {
-        // trojan://password@server:port?allowInsecure=1#tag
-        if !trojan_url.starts_with("trojan://") {
-            return Err("不是有效的Trojan URL".to_string());
-        }
-        
-        let without_prefix = &trojan_url[9..];
-        let parts: Vec<&str> = without_prefix.split('#').collect();
-        let main_part = parts[0];
-        let tag = if parts.len() > 1 { parts[1] } else { "从URL导入的Trojan" };
-        
-        // 解析主要部分
-        if let Some(at_pos) = main_part.find('@') {
-            let password = &main_part[..at_pos];
-            let server_port_params = &main_part[at_pos+1..];
-            
-            // 处理可能的查询参数
-            let server_port = if let Some(q_pos) = server_port_params.find('?') {
-                &server_port_params[..q_pos]
-            } else {
-                server_port_params
-            };
-            
-            if let Some(colon_pos) = server_port.find(':') {
-                let server = &server_port[..colon_pos];
-                let port_str = &server_port[colon_pos+1..];
-                
-                if let Ok(port) = port_str.parse::<u16>() {
-                    let config = VpnConfig::new(
-                        0,
-                        tag,
-                        VpnProtocol::Trojan,
-                        server,
-                        port,
-                        password,
-                        "auto"
-                    );
-                    return Ok(config);
-                }
-            }
+        if let Ok(mut logger) = self.logger.lock() {
+            logger.info("VPN", &format!("添加新VPN配置: {}", config_with_id.name));
         }
         
-        Err("无法解析Trojan URL格式".to_string())
-    }
-    
-    // 导入VPN配置URL
-    fn parse_shadowsocks_url(&self, url: &str) -> Result<VpnConfig, String> {
-        let decoded = general_purpose::STANDARD.decode(url.replace("ss://", ""))
-            .map_err(|_| "Base64解码失败")?;
-        let parts = String::from_utf8(decoded)
-            .map_err(|_| "UTF-8解码失败")?
-            .splitn(2, '@').collect::<Vec<_>>();
-        
-        let (method_password, server_port) = match parts.as_slice() {
-            &[mp, sp] => (mp, sp),
-            _ => return Err("无效的Shadowsocks格式".into())
-        };
-        
-        let method_password = method_password.splitn(2, ':').collect::<Vec<_>>();
-        let (method, password) = match method_password.as_slice() {
-            &[m, p] => (m, p),
-            _ => return Err("无效的加密方法格式".into())
-        };
-        
-        let server_port = server_port.splitn(2, ':').collect::<Vec<_>>();
-        let (server, port) = match server_port.as_slice() {
-            &[s, p] => (s, p.parse().unwrap_or(8388)),
-            _ => return Err("无效的服务器地址格式".into())
-        };
-        
-        Ok(VpnConfig::new(
-            0,
-            "从URL导入的Shadowsocks",
-            VpnProtocol::Shadowsocks,
-            server,
-            port,
-            password,
-            method
-        ))
-    }
-    
-    fn parse_trojan_url(&self, url: &str) -> Result<VpnConfig, String> {
-        let uri = url.replace("trojan://", "");
-        let parts = uri.splitn(2, '@').collect::<Vec<_>>();
-        
-        let (password_server, remainder) = match parts.as_slice() {
-            &[ps, r] => (ps, r),
-            _ => return Err("无效的Trojan格式".into())
-        };
-        
-        let password_server = password_server.splitn(2, ':').collect::<Vec<_>>();
-        let (password, server_port) = match password_server.as_slice() {
-            &[p, sp] => (p, sp),
-            _ => return Err("无效的密码格式".into())
-        };
-        
-        let server_port = server_port.splitn(2, ':').collect::<Vec<_>>();
-        let (server, port) = match server_port.as_slice() {
-            &[s, p] => (s, p.parse().unwrap_or(443)),
-            _ => return Err("无效的服务器地址格式".into())
-        };
-        
-        Ok(VpnConfig::new(
-            0,
-            "从URL导入的Trojan",
-            VpnProtocol::Trojan,
-            server,
-            port,
-            password,
-            "auto"
-        ))
-    }
-    
-    fn import_vpn_url(&mut self, url_str: &str) -> Result<(), String> {
-        if url_str.starts_with("vmess://") {
-            // 先解析URL，避免同时借用self
-            let config_result = self.parse_vmess_url(url_str);
-            
-            match config_result {
-                Ok(config) => {
-                    // 获取下一个ID并递增
-                    let next_id = self.next_config_id;
-                    self.next_config_id += 1;
-                    
-                    let config_with_id = VpnConfig::new(
-                        next_id,
-                        &config.name,
-                        config.protocol,
-                        &config.server,
-                        config.port,
-                        &config.uuid,
-                        &config.encryption
-                    );
-                    
-                    let logger_clone = self.logger.clone();
-                    // 记录日志
-                    {
-                        if let Ok(mut logger) = logger_clone.lock() {
-                            logger.info("VPN", &format!("添加新VPN配置: {}", config_with_id.name));
-                        }
-                    }
-                    
-                    // 添加配置
-                    self.configs.push(config_with_id);
-                    Ok(())
-                },
-                Err(e) => Err(e)
-            }
-        } else if url_str.starts_with("ss://") {
-            // 解析Shadowsocks URL
-            // 实现类似parse_vmess_url的功能
-            let parse_result = self.parse_shadowsocks_url(url_str);
-            match parse_result {
-                Ok(config) => {
-                    // 获取下一个ID并递增
-                    let next_id = self.next_config_id;
-                    self.next_config_id += 1;
-                    
-                    let config_with_id = VpnConfig::new(
-                        next_id,
-                        &config.name,
-                        config.protocol,
-                        &config.server,
-                        config.port,
-                        &config.uuid,
-                        &config.encryption
-                    );
-                    
-                    let logger_clone = self.logger.clone();
-                    // 记录日志
-                    {
-                        if let Ok(mut logger) = logger_clone.lock() {
-                            logger.info("VPN", &format!("添加新VPN配置: {}", config_with_id.name));
-                        }
-                    }
-                    
-                    // 添加配置
-                    self.configs.push(config_with_id);
-                    Ok(())
-                },
-                Err(e) => Err(e)
-            }
-        } else if url_str.starts_with("trojan://") {
-            // 解析Trojan URL
-            // 实现类似parse_vmess_url的功能
-            let parse_result = self.parse_trojan_url(url_str);
-            match parse_result {
-                Ok(config) => {
-                    // 获取下一个ID并递增
-                    let next_id = self.next_config_id;
-                    self.next_config_id += 1;
-                    
-                    let config_with_id = VpnConfig::new(
-                        next_id,
-                        &config.name,
-                        config.protocol,
-                        &config.server,
-                        config.port,
-                        &config.uuid,
-                        &config.encryption
-                    );
-                    
-                    let logger_clone = self.logger.clone();
-                    // 记录日志
-                    {
-                        if let Ok(mut logger) = logger_clone.lock() {
-                            logger.info("VPN", &format!("添加新VPN配置: {}", config_with_id.name));
-                        }
-                    }
-                    
-                    // 添加配置
-                    self.configs.push(config_with_id);
-                    Ok(())
-                },
-                Err(e) => Err(e)
-            }
-        } else {
-            Err("不支持的URL格式".to_string())
-        }
+        self.configs.push(config_with_id);
+        self.save_configs();
+        Ok(())
     }
     
     // 启用/禁用VPN
@@ -739,6 +393,46 @@ impl VpnModule {
         self.connection_status = if new_enabled { "正在连接..." } else { "未连接" }.to_string();
     }
     
+    // 获取当前连接状态的副本
+    pub fn get_connection_status(&self) -> String {
+        self.connection_status.clone()
+    }
+    
+    // VPN是否已启用
+    pub fn is_enabled(&self) -> bool {
+        self.enabled
+    }
+    
+    // VPN是否已连接
+    pub fn is_connected(&self) -> bool {
+        self.connection_status == "已连接"
+    }
+    
+    // 在后台测试所有配置的连接延迟（TCP握手耗时），结果写入日志
+    pub fn test_latency(&self) {
+        let targets: Vec<(String, String, u16)> = self.configs.iter()
+            .map(|c| (c.name.clone(), c.server.clone(), c.port))
+            .collect();
+        let logger = Arc::clone(&self.logger);
+        
+        std::thread::spawn(move || {
+            for (name, server, port) in targets {
+                let start = Instant::now();
+                let result = (server.as_str(), port).to_socket_addrs()
+                    .map_err(|e| e.to_string())
+                    .and_then(|mut addrs| addrs.next().ok_or_else(|| "无法解析服务器地址".to_string()))
+                    .and_then(|addr| TcpStream::connect_timeout(&addr, Duration::from_secs(5)).map_err(|e| e.to_string()));
+                
+                if let Ok(mut logger) = logger.lock() {
+                    match result {
+                        Ok(_) => logger.info("VPN", &format!("'{}' 延迟: {} ms", name, start.elapsed().as_millis())),
+                        Err(e) => logger.warning("VPN", &format!("'{}' 延迟测试失败: {}", name, e)),
+                    }
+                }
+            }
+        });
+    }
+    
     // 启动Vmess客户端
     fn start_vmess_client(&mut self, config: &VpnConfig) {
         // 克隆必要变量避免借用冲突
@@ -757,8 +451,8 @@ impl VpnModule {
             logger.info("VPN", &format!("正在启动Vmess客户端: {}", config.name));
         }
         
-        let client = VmessClient::new(config.server.clone(), config.port, config.uuid.clone(), config.encryption.clone());
-        match client.connect().await {
+        let client = VmessClient::new(config.server.clone(), config.port, config.uuid.clone(), config.encryption.clone(), Arc::clone(&self.traffic));
+        match client.connect() {
             Ok(_) => {
                 if let Ok(mut logger) = self.logger.lock() {
                     logger.info("VPN", "Vmess客户端启动成功");
@@ -772,7 +466,6 @@ impl VpnModule {
         }
     }
     
-    // 启动Shadowsocks客户端
     // 启动Shadowsocks客户端
     fn start_shadowsocks_client(&mut self, config: &VpnConfig) {
         // 克隆必要变量避免借用冲突
@@ -791,8 +484,8 @@ impl VpnModule {
             logger.info("VPN", &format!("正在启动Shadowsocks客户端: {}", config.name));
         }
         
-        let client = ShadowsocksClient::new(config.server.clone(), config.port, config.uuid.clone(), config.encryption.clone());
-        match client.connect().await {
+        let client = ShadowsocksClient::new(config.server.clone(), config.port, config.uuid.clone(), config.encryption.clone(), Arc::clone(&self.traffic));
+        match client.connect() {
             Ok(_) => {
                 if let Ok(mut logger) = self.logger.lock() {
                     logger.info("VPN", "Shadowsocks客户端启动成功");
@@ -806,8 +499,8 @@ impl VpnModule {
         }
     }
     
-    // 启动Shadowsocks客户端
-    fn start_shadowsocks_client(&mut self, config: &VpnConfig) {
+    // 启动Trojan客户端
+    fn start_trojan_client(&mut self, config: &VpnConfig) {
         // 克隆必要变量避免借用冲突
         let client_name = config.name.clone();
         let logger_clone = self.logger.clone();
@@ -815,52 +508,26 @@ impl VpnModule {
         // 在单独作用域中使用克隆的logger
         {
             if let Ok(mut logger) = logger_clone.lock() {
-                logger.info("VPN", &format!("启动Shadowsocks客户端: {}", client_name));
+                logger.info("VPN", &format!("启动Trojan客户端: {}", client_name));
             }
         }
         
-        // 启动Shadowsocks客户端
-        if let Ok(mut logger) = self.logger.lock() {
-            logger.info("VPN", &format!("正在启动Shadowsocks客户端: {}", config.name));
-        }
-        let client = ShadowsocksClient::new(config.server.clone(), config.port, config.uuid.clone(), config.encryption.clone());
-        match client.connect().await {
-            Ok(connection) => {
-                // 处理连接成功的情况
-            },
-            Err(e) => {
-                // 处理连接失败的情况
-            }
-        }
+        // 启动Trojan客户端
+        let client = TrojanClient::new(config.server.clone(), config.port, config.uuid.clone(), Arc::clone(&self.traffic));
+        match client.connect() {
             Ok(_) => {
                 if let Ok(mut logger) = self.logger.lock() {
-                    logger.info("VPN", "Shadowsocks客户端启动成功");
+                    logger.info("VPN", "Trojan客户端启动成功");
                 }
             }
             Err(e) => {
                 if let Ok(mut logger) = self.logger.lock() {
-                    logger.error("VPN", &format!("Shadowsocks客户端启动失败: {}", e));
+                    logger.error("VPN", &format!("Trojan客户端启动失败: {}", e));
                 }
             }
         }
     }
     
-    // 启动Trojan客户端
-    fn start_trojan_client(&mut self, config: &VpnConfig) {
-        // 克隆必要变量避免借用冲突
-        let client_name = config.name.clone();
-        let logger_clone = self.logger.clone();
-        
-        // 在单独作用域中使用克隆的logger
-        {
-            if let Ok(mut logger) = logger_clone.lock() {
-                logger.info("VPN", &format!("启动Trojan客户端: {}", client_name));
-            }
-        }
-        
-        // 在实际应用中，这里会启动Trojan客户端
-    }
-    
     // 启动Wireguard客户端
     fn start_wireguard_client(&mut self, config: &VpnConfig) {
         // 克隆必要变量避免借用冲突
@@ -878,7 +545,7 @@ impl VpnModule {
         if let Ok(mut logger) = self.logger.lock() {
             logger.info("VPN", &format!("正在启动Wireguard客户端: {}", config.name));
         }
-        let client = WireguardClient::new(config.server.clone(), config.port, config.uuid.clone());
+        let client = WireguardClient::new(config.server.clone(), config.port, config.uuid.clone(), Arc::clone(&self.traffic));
         match client.connect() {
             Ok(_) => {
                 if let Ok(mut logger) = self.logger.lock() {
@@ -910,7 +577,7 @@ impl VpnModule {
         if let Ok(mut logger) = self.logger.lock() {
             logger.info("VPN", &format!("正在启动OpenVPN客户端: {}", config.name));
         }
-        let client = OpenVPNClient::new(config.server.clone(), config.port, config.uuid.clone());
+        let client = OpenVPNClient::new(config.server.clone(), config.port, config.uuid.clone(), Arc::clone(&self.traffic));
         match client.connect() {
             Ok(_) => {
                 if let Ok(mut logger) = self.logger.lock() {
@@ -966,6 +633,7 @@ impl VpnModule {
             if let Ok(mut logger) = self.logger.lock() {
                 logger.info("VPN", &format!("VPN配置 '{}' 已{}", name, if enabled { "启用" } else { "禁用" }));
             }
+            self.save_configs();
         }
     }
     
@@ -1051,7 +719,7 @@ impl VpnModule {
             ui.with_layout(egui::Layout::right_to_left(egui::Align::Center), |ui| {
                 if ui.button("添加订阅").clicked() {
                     self.edit_mode = true;
-                    self.selected_subscription = None;
+                    self.adding_subscription = true;
                 }
             });
         });
@@ -1059,28 +727,35 @@ impl VpnModule {
         ui.separator();
         
         // 根据选择的标签页显示内容
-        if let Some(subscription_id) = self.selected_subscription {
+        let selected = self.selected_subscription
+            .and_then(|id| self.subscriptions.iter().find(|s| s.id == id).cloned());
+        if let Some(subscription) = selected {
             // 显示订阅内容
-            if let Some(subscription) = self.subscriptions.iter().find(|s| s.id == subscription_id) {
-                ui.horizontal(|ui| {
-                    ui.heading(&subscription.name);
-                    ui.label(format!("(上次更新: {})", subscription.last_updated));
-                    
-                    ui.with_layout(egui::Layout::right_to_left(egui::Align::Center), |ui| {
-                        if ui.button("更新").clicked() {
-                            self.update_subscription(subscription_id);
-                        }
-                        if ui.button("删除").clicked() {
-                            self.remove_subscription(subscription_id);
-                        }
-                    });
-                });
-                
-                ui.label(format!("URL: {}", subscription.url));
-                ui.label(format!("配置数量: {}", subscription.configs.len()));
+            let mut update = false;
+            let mut remove = false;
+            ui.horizontal(|ui| {
+                ui.heading(&subscription.name);
+                ui.label(format!("(上次更新: {})", subscription.last_updated));
                 
-                // 显示订阅中的配置列表
-                self.add_config(subscription.configs.clone());
+                ui.with_layout(egui::Layout::right_to_left(egui::Align::Center), |ui| {
+                    update = ui.button("更新").clicked();
+                    remove = ui.button("删除").clicked();
+                });
+            });
+            
+            ui.label(format!("URL: {}", subscription.url));
+            ui.label(format!("配置数量: {}", subscription.configs.len()));
+            
+            // 显示订阅中的配置列表，订阅配置随订阅更新，不能单独修改
+            for config in &subscription.configs {
+                Self::config_row(ui, config);
+            }
+            
+            if update {
+                self.update_subscription(subscription.id);
+            }
+            if remove {
+                self.remove_subscription(subscription.id);
             }
         } else {
             // 显示手动添加的配置
@@ -1089,17 +764,54 @@ impl VpnModule {
                 ui.with_layout(egui::Layout::right_to_left(egui::Align::Center), |ui| {
                     if ui.button("添加配置").clicked() {
                         self.edit_mode = true;
+                        self.adding_subscription = false;
                     }
                 });
             });
             
+            // 从分享链接导入
+            ui.horizontal(|ui| {
+                ui.label("分享链接:");
+                ui.text_edit_singleline(&mut self.import_url);
+                if ui.button("导入").clicked() {
+                    let url = self.import_url.trim().to_string();
+                    match self.import_vpn_url(&url) {
+                        Ok(()) => self.import_url.clear(),
+                        Err(e) => {
+                            if let Ok(mut logger) = self.logger.lock() {
+                                logger.error("VPN", &format!("导入VPN链接失败: {}", e));
+                            }
+                        }
+                    }
+                }
+            });
+            
             // 显示配置列表
-            self.add_config(self.configs.clone());
+            let mut toggled = None;
+            let mut removed = None;
+            for config in &self.configs {
+                ui.horizontal(|ui| {
+                    let mut enabled = config.enabled;
+                    if ui.checkbox(&mut enabled, "").changed() {
+                        toggled = Some(config.id);
+                    }
+                    Self::config_row(ui, config);
+                    if ui.small_button("删除").clicked() {
+                        removed = Some(config.id);
+                    }
+                });
+            }
+            if let Some(id) = toggled {
+                self.toggle_config(id);
+            }
+            if let Some(id) = removed {
+                self.remove_config(id);
+            }
         }
 
         // 添加/编辑配置对话框
         if self.edit_mode {
-            let title = if self.selected_subscription.is_some() {
+            let title = if self.adding_subscription {
                 "添加Clash订阅"
             } else if self.selected_config.is_some() {
                 "编辑VPN配置"
@@ -1107,10 +819,13 @@ impl VpnModule {
                 "添加VPN配置"
             };
             
-            let response = egui::Window::new(title)
-                .open(&mut self.edit_mode)
+            let mut open = true;
+            let mut cancelled = false;
+            let mut confirmed = false;
+            egui::Window::new(title)
+                .open(&mut open)
                 .show(ui.ctx(), |ui| {
-                    if self.selected_subscription.is_some() {
+                    if self.adding_subscription {
                         // 添加Clash订阅表单
                         ui.horizontal(|ui| {
                             ui.label("订阅名称:");
@@ -1128,23 +843,9 @@ impl VpnModule {
                         ui.checkbox(&mut self.show_subscription_warning, "我了解添加订阅的风险");
                         
                         ui.horizontal(|ui| {
-                            if ui.button("取消").clicked() {
-                                false
-                            } else if ui.button("添加").clicked() && self.show_subscription_warning {
-                                true
-                            } else {
-                                false
-                            }
+                            cancelled = ui.button("取消").clicked();
+                            confirmed = ui.button("添加").clicked() && self.show_subscription_warning;
                         });
-                        if let Some(inner_response) = response {
-                            if let Some(inner) = inner_response.inner {
-                                inner
-                            } else {
-                                false
-                            }
-                        } else {
-                            false
-                        }
                     } else {
                         // 添加/编辑VPN配置表单
                         ui.horizontal(|ui| {
@@ -1199,65 +900,60 @@ impl VpnModule {
                         }
                         
                         ui.horizontal(|ui| {
-                            if ui.button("取消").clicked() {
-                                false
-                            } else if ui.button("保存").clicked() {
-                                true
-                            } else {
-                                false
-                            }
+                            cancelled = ui.button("取消").clicked();
+                            confirmed = ui.button("保存").clicked();
                         });
-                        if let Some(inner_response) = response {
-                            if let Some(inner) = inner_response.inner {
-                                inner
-                            } else {
-                                false
-                            }
-                        } else {
-                            false
-                        }
                     }
                 });
             
-            if let Some(inner_response) = response {
-                if let Some(true) = inner_response.inner {
-                    if self.selected_subscription.is_some() {
-                        // 添加新订阅
-                        if !self.new_subscription_name.is_empty() && !self.new_subscription_url.is_empty() {
-                            let new_subscription = ClashSubscription::new(
-                                self.next_subscription_id,
-                                &self.new_subscription_name,
-                                &self.new_subscription_url
-                            );
-                            self.add_subscription(new_subscription);
-                            self.new_subscription_name.clear();
-                            self.new_subscription_url.clear();
-                        }
-                    } else {
-                        // 添加/编辑VPN配置
-                        if !self.new_config_name.is_empty() && !self.new_config_server.is_empty() && !self.new_config_uuid.is_empty() {
-                            let new_config = VpnConfig::new(
-                                self.next_config_id,
-                                &self.new_config_name,
-                                self.new_config_protocol.clone(),
-                                &self.new_config_server,
-                                self.new_config_port,
-                                &self.new_config_uuid,
-                                &self.new_config_encryption
-                            );
-                            self.add_config(new_config);
-                            self.new_config_name.clear();
-                            self.new_config_server.clear();
-                            self.new_config_uuid.clear();
-                            self.new_config_encryption.clear();
-                            self.new_config_port = 443;
-                            self.edit_mode = false;
-                        }
+            if !open || cancelled {
+                self.edit_mode = false;
+            } else if confirmed {
+                if self.adding_subscription {
+                    // 添加新订阅
+                    if !self.new_subscription_name.is_empty() && !self.new_subscription_url.is_empty() {
+                        let new_subscription = ClashSubscription::new(
+                            self.next_subscription_id,
+                            &self.new_subscription_name,
+                            &self.new_subscription_url
+                        );
+                        self.add_subscription(new_subscription);
+                        self.new_subscription_name.clear();
+                        self.new_subscription_url.clear();
+                        self.show_subscription_warning = false;
+                        self.edit_mode = false;
+                    }
+                } else {
+                    // 添加/编辑VPN配置
+                    if !self.new_config_name.is_empty() && !self.new_config_server.is_empty() && !self.new_config_uuid.is_empty() {
+                        let new_config = VpnConfig::new(
+                            self.next_config_id,
+                            &self.new_config_name,
+                            self.new_config_protocol.clone(),
+                            &self.new_config_server,
+                            self.new_config_port,
+                            &self.new_config_uuid,
+                            &self.new_config_encryption
+                        );
+                        self.add_config(new_config);
+                        self.new_config_name.clear();
+                        self.new_config_server.clear();
+                        self.new_config_uuid.clear();
+                        self.new_config_encryption.clear();
+                        self.new_config_port = 443;
+                        self.edit_mode = false;
                     }
                 }
             }
         }
     }
+    
+    // 配置列表中的一行：名称、协议与服务器地址
+    fn config_row(ui: &mut Ui, config: &VpnConfig) {
+        ui.label(RichText::new(&config.name).strong());
+        ui.label(format!("{:?}", config.protocol));
+        ui.label(format!("{}:{}", config.server, config.port));
+    }
 }
 
 // VPN客户端结构体
@@ -1265,15 +961,16 @@ pub struct VmessClient {
     server: String,
     port: u16,
     uuid: String,
-    encryption: String
+    encryption: String,
+    traffic: Arc<traffic::Counter>, // 经隧道收发的字节数
 }
 
 impl VmessClient {
-    pub fn new(server: String, port: u16, uuid: String, encryption: String) -> Self {
-        Self { server, port, uuid, encryption }
+    pub fn new(server: String, port: u16, uuid: String, encryption: String, traffic: Arc<traffic::Counter>) -> Self {
+        Self { server, port, uuid, encryption, traffic }
     }
 
-    pub async fn connect(&self) -> Result<(), Box<dyn std::error::Error>> {
+    pub fn connect(&self) -> Result<(), Box<dyn std::error::Error>> {
         // 实现Vmess连接逻辑
         Ok(())
     }
@@ -1283,15 +980,16 @@ pub struct ShadowsocksClient {
     server: String,
     port: u16,
     password: String,
-    encryption: String
+    encryption: String,
+    traffic: Arc<traffic::Counter>, // 经隧道收发的字节数
 }
 
 impl ShadowsocksClient {
-    pub fn new(server: String, port: u16, password: String, encryption: String) -> Self {
-        Self { server, port, password, encryption }
+    pub fn new(server: String, port: u16, password: String, encryption: String, traffic: Arc<traffic::Counter>) -> Self {
+        Self { server, port, password, encryption, traffic }
     }
 
-    pub async fn connect(&self) -> Result<(), Box<dyn std::error::Error>> {
+    pub fn connect(&self) -> Result<(), Box<dyn std::error::Error>> {
         // 实现Shadowsocks连接逻辑
         Ok(())
     }
@@ -1300,12 +998,13 @@ impl ShadowsocksClient {
 pub struct TrojanClient {
     server: String,
     port: u16,
-    password: String
+    password: String,
+    traffic: Arc<traffic::Counter>, // 经隧道收发的字节数
 }
 
 impl TrojanClient {
-    pub fn new(server: String, port: u16, password: String) -> Self {
-        Self { server, port, password }
+    pub fn new(server: String, port: u16, password: String, traffic: Arc<traffic::Counter>) -> Self {
+        Self { server, port, password, traffic }
     }
     
     pub fn connect(&self) -> Result<(), Box<dyn std::error::Error>> {
@@ -1317,12 +1016,13 @@ impl TrojanClient {
 pub struct WireguardClient {
     server: String,
     port: u16,
-    key: String
+    key: String,
+    traffic: Arc<traffic::Counter>, // 经隧道收发的字节数
 }
 
 impl WireguardClient {
-    pub fn new(server: String, port: u16, key: String) -> Self {
-        Self { server, port, key }
+    pub fn new(server: String, port: u16, key: String, traffic: Arc<traffic::Counter>) -> Self {
+        Self { server, port, key, traffic }
     }
 
     pub fn connect(&self) -> Result<(), String> {
@@ -1334,12 +1034,13 @@ impl WireguardClient {
 pub struct OpenVPNClient {
     server: String,
     port: u16,
-    config: String
+    config: String,
+    traffic: Arc<traffic::Counter>, // 经隧道收发的字节数
 }
 
 impl OpenVPNClient {
-    pub fn new(server: String, port: u16, config: String) -> Self {
-        Self { server, port, config }
+    pub fn new(server: String, port: u16, config: String, traffic: Arc<traffic::Counter>) -> Self {
+        Self { server, port, config, traffic }
     }
 
     pub fn connect(&self) -> Result<(), String> {
@@ -0,0 +1,220 @@
+use base64::{Engine as _, engine::general_purpose};
+use yaml_rust::{Yaml, YamlLoader};
+
+use crate::vpn::{VpnConfig, VpnProtocol};
+
+// 解析分享链接（vmess://、ss://、trojan://），返回的配置ID为0，由调用方重新分配
+pub fn parse(url: &str) -> Result<VpnConfig, String> {
+    if url.starts_with("vmess://") {
+        parse_vmess(url)
+    } else if url.starts_with("ss://") {
+        parse_shadowsocks(url)
+    } else if url.starts_with("trojan://") {
+        parse_trojan(url)
+    } else {
+        Err("不支持的URL格式".to_string())
+    }
+}
+
+// 解析Clash配置文件中的proxies列表，不支持的代理类型会被跳过
+pub fn parse_clash(content: &str) -> Result<Vec<VpnConfig>, String> {
+    let docs = YamlLoader::load_from_str(content).map_err(|e| format!("解析YAML失败: {}", e))?;
+    let doc = docs.first().ok_or_else(|| "YAML文档为空".to_string())?;
+
+    let mut configs = Vec::new();
+    if let Some(proxies) = doc["proxies"].as_vec() {
+        for (index, proxy) in proxies.iter().enumerate() {
+            if let Some(config) = parse_clash_proxy(proxy, index) {
+                configs.push(config);
+            }
+        }
+    }
+    Ok(configs)
+}
+
+// 解析单个Clash代理配置
+fn parse_clash_proxy(proxy: &Yaml, index: usize) -> Option<VpnConfig> {
+    let name = match proxy["name"].as_str() {
+        Some(name) => name.to_string(),
+        None => format!("未命名代理{}", index),
+    };
+    let server = proxy["server"].as_str().unwrap_or("unknown");
+
+    match proxy["type"].as_str().unwrap_or("unknown").to_lowercase().as_str() {
+        "vmess" => {
+            let port = proxy["port"].as_i64().unwrap_or(443) as u16;
+            let uuid = proxy["uuid"].as_str().unwrap_or("");
+            let encryption = proxy["cipher"].as_str().unwrap_or("auto");
+            Some(VpnConfig::new(0, &name, VpnProtocol::Vmess, server, port, uuid, encryption))
+        }
+        "ss" | "shadowsocks" => {
+            let port = proxy["port"].as_i64().unwrap_or(8388) as u16;
+            let password = proxy["password"].as_str().unwrap_or("");
+            let encryption = proxy["cipher"].as_str().unwrap_or("aes-256-gcm");
+            Some(VpnConfig::new(0, &name, VpnProtocol::Shadowsocks, server, port, password, encryption))
+        }
+        "trojan" => {
+            let port = proxy["port"].as_i64().unwrap_or(443) as u16;
+            let password = proxy["password"].as_str().unwrap_or("");
+            Some(VpnConfig::new(0, &name, VpnProtocol::Trojan, server, port, password, "auto"))
+        }
+        _ => None,
+    }
+}
+
+// vmess://base64(json)
+fn parse_vmess(url: &str) -> Result<VpnConfig, String> {
+    let encoded = url.strip_prefix("vmess://").ok_or_else(|| "不是有效的Vmess URL".to_string())?;
+    let decoded = general_purpose::STANDARD.decode(encoded.trim()).map_err(|_| "Base64解码失败".to_string())?;
+    let json_str = String::from_utf8(decoded).map_err(|_| "UTF-8解码失败".to_string())?;
+    let json: serde_json::Value = serde_json::from_str(&json_str).map_err(|e| format!("JSON解析失败: {}", e))?;
+
+    // 端口在不同客户端导出的链接中可能是字符串或数字
+    let port = match &json["port"] {
+        serde_json::Value::Number(port) => port.as_u64().and_then(|p| u16::try_from(p).ok()),
+        serde_json::Value::String(port) => port.parse().ok(),
+        _ => None,
+    }
+    .unwrap_or(443);
+
+    Ok(VpnConfig::new(
+        0,
+        json["ps"].as_str().unwrap_or("从URL导入的Vmess"),
+        VpnProtocol::Vmess,
+        json["add"].as_str().unwrap_or("unknown"),
+        port,
+        json["id"].as_str().unwrap_or(""),
+        json["scy"].as_str().unwrap_or("auto"),
+    ))
+}
+
+// 拆分"method:password@server:port"形式的Shadowsocks用户信息与地址
+fn split_shadowsocks(main: &str) -> Option<(&str, &str, &str, u16)> {
+    let (method_password, server_port) = main.rsplit_once('@')?;
+    let (method, password) = method_password.split_once(':')?;
+    let (server, port) = server_port.rsplit_once(':')?;
+    Some((method, password, server, port.parse().ok()?))
+}
+
+// ss://base64(method:password@server:port)#tag 或 ss://method:password@server:port#tag
+fn parse_shadowsocks(url: &str) -> Result<VpnConfig, String> {
+    let rest = url.strip_prefix("ss://").ok_or_else(|| "不是有效的Shadowsocks URL".to_string())?;
+    let (main, tag) = match rest.split_once('#') {
+        Some((main, tag)) => (main, tag),
+        None => (rest, "从URL导入的Shadowsocks"),
+    };
+
+    let decoded = general_purpose::STANDARD.decode(main).ok().and_then(|bytes| String::from_utf8(bytes).ok());
+    let parsed = match &decoded {
+        Some(decoded) => split_shadowsocks(decoded),
+        None => split_shadowsocks(main),
+    };
+    let (method, password, server, port) = parsed.ok_or_else(|| "无法解析Shadowsocks URL格式".to_string())?;
+    Ok(VpnConfig::new(0, tag, VpnProtocol::Shadowsocks, server, port, password, method))
+}
+
+// trojan://password@server:port?allowInsecure=1#tag
+fn parse_trojan(url: &str) -> Result<VpnConfig, String> {
+    let rest = url.strip_prefix("trojan://").ok_or_else(|| "不是有效的Trojan URL".to_string())?;
+    let (main, tag) = match rest.split_once('#') {
+        Some((main, tag)) => (main, tag),
+        None => (rest, "从URL导入的Trojan"),
+    };
+    // 去掉查询参数
+    let main = main.split('?').next().unwrap_or(main);
+
+    let parsed = main.rsplit_once('@').and_then(|(password, server_port)| {
+        let (server, port) = server_port.rsplit_once(':')?;
+        Some((password, server, port.parse::<u16>().ok()?))
+    });
+    let (password, server, port) = parsed.ok_or_else(|| "无法解析Trojan URL格式".to_string())?;
+    Ok(VpnConfig::new(0, tag, VpnProtocol::Trojan, server, port, password, "auto"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_vmess() {
+        let json = r#"{"ps":"节点","add":"vmess.example","port":8443,"id":"b831381d-6324-4d53-ad4f-8cda48b30811","scy":"aes-128-gcm"}"#;
+        let config = parse(&format!("vmess://{}", general_purpose::STANDARD.encode(json))).unwrap();
+        assert_eq!(config.protocol, VpnProtocol::Vmess);
+        assert_eq!(config.name, "节点");
+        assert_eq!(config.server, "vmess.example");
+        assert_eq!(config.port, 8443);
+        assert_eq!(config.uuid, "b831381d-6324-4d53-ad4f-8cda48b30811");
+        assert_eq!(config.encryption, "aes-128-gcm");
+
+        // 端口为字符串
+        let json = r#"{"add":"vmess.example","port":"443","id":"id"}"#;
+        let config = parse(&format!("vmess://{}", general_purpose::STANDARD.encode(json))).unwrap();
+        assert_eq!(config.port, 443);
+        assert_eq!(config.encryption, "auto");
+
+        assert!(parse("vmess://不是base64").is_err());
+    }
+
+    #[test]
+    fn parses_shadowsocks() {
+        let encoded = general_purpose::STANDARD.encode("aes-256-gcm:pa:ss@ss.example:8388");
+        let config = parse(&format!("ss://{}#SS", encoded)).unwrap();
+        assert_eq!(config.protocol, VpnProtocol::Shadowsocks);
+        assert_eq!(config.name, "SS");
+        assert_eq!((config.server.as_str(), config.port), ("ss.example", 8388));
+        assert_eq!((config.uuid.as_str(), config.encryption.as_str()), ("pa:ss", "aes-256-gcm"));
+
+        let config = parse("ss://chacha20-ietf-poly1305:secret@192.0.2.1:443").unwrap();
+        assert_eq!(config.name, "从URL导入的Shadowsocks");
+        assert_eq!(config.encryption, "chacha20-ietf-poly1305");
+        assert_eq!(config.port, 443);
+
+        assert!(parse("ss://secret@192.0.2.1").is_err());
+    }
+
+    #[test]
+    fn parses_trojan() {
+        let config = parse("trojan://secret@trojan.example:443?allowInsecure=1#Trojan").unwrap();
+        assert_eq!(config.protocol, VpnProtocol::Trojan);
+        assert_eq!(config.name, "Trojan");
+        assert_eq!((config.server.as_str(), config.port), ("trojan.example", 443));
+        assert_eq!(config.uuid, "secret");
+
+        assert!(parse("trojan://trojan.example:443").is_err());
+        assert!(parse("wireguard://example").is_err());
+    }
+
+    #[test]
+    fn parses_clash_proxies() {
+        let yaml = "
+proxies:
+  - name: A
+    type: vmess
+    server: a.example
+    port: 443
+    uuid: id
+    cipher: auto
+  - name: B
+    type: ss
+    server: b.example
+    port: 8388
+    cipher: aes-256-gcm
+    password: secret
+  - name: C
+    type: trojan
+    server: c.example
+    password: secret
+  - name: D
+    type: http
+    server: d.example
+";
+        let configs = parse_clash(yaml).unwrap();
+        let protocols: Vec<VpnProtocol> = configs.iter().map(|c| c.protocol.clone()).collect();
+        assert_eq!(protocols, [VpnProtocol::Vmess, VpnProtocol::Shadowsocks, VpnProtocol::Trojan]);
+        assert_eq!(configs[1].uuid, "secret");
+        assert_eq!(configs[2].port, 443);
+
+        assert!(parse_clash("").is_err());
+        assert!(parse_clash("proxies: [").is_err());
+    }
+}
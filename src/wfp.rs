@@ -0,0 +1,839 @@
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr};
+use anyhow::{anyhow, Result};
+use serde::{Deserialize, Serialize};
+
+use crate::firewall::{FirewallProfile, FirewallRule, Protocol, Remote, RuleAction};
+use crate::geoip;
+use crate::utils;
+
+const IPPROTO_TCP: u8 = 6;
+const IPPROTO_UDP: u8 = 17;
+
+// DHCP服务器端口（IPv4与IPv6），断线保护期间仍需续租地址
+const DHCP_SERVER_PORT: u16 = 67;
+const DHCPV6_SERVER_PORT: u16 = 547;
+
+// 规则过滤器的权重从此开始，越靠前的规则权重越高
+const RULE_WEIGHT_BASE: u64 = 100;
+// 严格模式下放行回环连接与默认阻止的权重，低于所有规则
+const LOOPBACK_WEIGHT: u64 = 2;
+const DEFAULT_BLOCK_WEIGHT: u64 = 1;
+
+// 国家规则中每个过滤器包含的地址段数
+const RANGES_PER_FILTER: usize = 1000;
+
+// 开启阻止事件记录前的系统设置备份，文件存在表示设置尚未恢复。
+// release构建panic时直接终止，来不及在Drop中恢复，下次启动时按备份恢复
+#[cfg_attr(not(target_os = "windows"), allow(dead_code))]
+const EVENT_BACKUP_FILE: &str = "wfp_event_backup.json";
+
+#[derive(Clone, Copy, Debug, Serialize, Deserialize)]
+#[cfg_attr(not(target_os = "windows"), allow(dead_code))]
+struct EventSettings {
+    collect_net_events: u32, // WFP引擎的事件收集开关
+    packet_drop_audit: u32,  // "筛选平台数据包丢弃"审核策略
+}
+
+impl utils::VersionedConfig for EventSettings {
+    const VERSION: u32 = 1;
+}
+
+// 本程序使用的子层，每个会话一个。不同子层分别裁决，任一子层阻止即被阻止
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Sublayer {
+    Rules,      // 防火墙规则
+    KillSwitch, // 断线保护
+}
+
+// 过滤器所在的ALE层：出站连接与入站接受，分IPv4和IPv6
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Layer {
+    ConnectV4,
+    ConnectV6,
+    AcceptV4,
+    AcceptV6,
+}
+
+impl Layer {
+    const ALL: [Layer; 4] = [Self::ConnectV4, Self::ConnectV6, Self::AcceptV4, Self::AcceptV6];
+
+    fn is_v4(&self) -> bool {
+        matches!(self, Self::ConnectV4 | Self::AcceptV4)
+    }
+
+    fn is_inbound(&self) -> bool {
+        matches!(self, Self::AcceptV4 | Self::AcceptV6)
+    }
+}
+
+// 过滤条件。同一过滤器中不同字段的条件需全部满足，同一字段的多个条件满足其一即可
+#[derive(Clone, Debug, PartialEq)]
+#[cfg_attr(not(target_os = "windows"), allow(dead_code))]
+pub enum Condition {
+    App(String),            // 应用程序完整路径
+    Protocol(u8),
+    LocalPort(u16),
+    RemotePort(u16),
+    RemoteV4(Ipv4Addr, u8), // 地址与前缀长度
+    RemoteV6(Ipv6Addr, u8),
+    RemoteRangeV4(Ipv4Addr, Ipv4Addr), // 起始与结束地址（含）
+    RemoteRangeV6(Ipv6Addr, Ipv6Addr),
+    Loopback,
+}
+
+// 待添加的过滤器
+#[derive(Clone, Debug)]
+#[cfg_attr(not(target_os = "windows"), allow(dead_code))]
+pub struct FilterSpec {
+    name: String,
+    layer: Layer,
+    conditions: Vec<Condition>,
+    permit: bool,
+    weight: u64,
+    hit: Option<(usize, String)>, // 命中统计的归属：规则ID与国家代码
+}
+
+// 解析地址规则，支持单个地址或CIDR（如 10.0.0.0/8、fd00::/8）
+pub fn parse_address(value: &str) -> Option<(IpAddr, u8)> {
+    let value = value.trim();
+    let (address, prefix) = match value.split_once('/') {
+        Some((address, prefix)) => (address, Some(prefix.parse::<u8>().ok()?)),
+        None => (value, None),
+    };
+    let address: IpAddr = address.parse().ok()?;
+    let max = if address.is_ipv4() { 32 } else { 128 };
+    match prefix {
+        Some(prefix) if prefix > max => None,
+        Some(prefix) => Some((address, prefix)),
+        None => Some((address, max)),
+    }
+}
+
+fn protocol_number(protocol: Protocol) -> u8 {
+    match protocol {
+        Protocol::Tcp => IPPROTO_TCP,
+        Protocol::Udp => IPPROTO_UDP,
+    }
+}
+
+// 把一条规则的匹配条件翻译为各层的过滤条件，国家规则的过滤器附带命中统计所属的国家
+fn rule_conditions(rule: &FirewallRule) -> Result<Vec<(Layer, Vec<Condition>, Option<String>)>, String> {
+    let matcher = rule.matcher()?;
+    let mut layers = Vec::new();
+    for layer in Layer::ALL.iter().filter(|layer| matcher.direction.includes(layer.is_inbound())) {
+        let mut conditions = Vec::new();
+        if let Some(path) = matcher.app {
+            conditions.push(Condition::App(path.to_string()));
+        }
+        if let Some(protocol) = matcher.protocol {
+            conditions.push(Condition::Protocol(protocol_number(protocol)));
+        }
+        // 出站连接匹配远程端口，入站连接匹配本地端口（如远程桌面的3389）
+        if let Some(port) = matcher.port {
+            conditions.push(if layer.is_inbound() { Condition::LocalPort(port) } else { Condition::RemotePort(port) });
+        }
+        match &matcher.remote {
+            Remote::Any => layers.push((*layer, conditions, None)),
+            // 每个网段一个过滤器，只加入与地址族相同的层
+            Remote::Networks(networks) => {
+                for &(address, prefix) in networks.iter().filter(|(address, _)| address.is_ipv4() == layer.is_v4()) {
+                    let mut conditions = conditions.clone();
+                    conditions.push(match address {
+                        IpAddr::V4(ip) => Condition::RemoteV4(ip, prefix),
+                        IpAddr::V6(ip) => Condition::RemoteV6(ip, prefix),
+                    });
+                    layers.push((*layer, conditions, None));
+                }
+            }
+            // 每个国家的地址段每RANGES_PER_FILTER段一个过滤器
+            Remote::Countries(countries) => {
+                for code in countries.iter() {
+                    let ranges: Vec<Condition> = geoip::country_ranges(code).ok_or("GeoIP数据库未加载")?
+                        .into_iter()
+                        .filter_map(|range| match range {
+                            (IpAddr::V4(start), IpAddr::V4(end)) if layer.is_v4() => Some(Condition::RemoteRangeV4(start, end)),
+                            (IpAddr::V6(start), IpAddr::V6(end)) if !layer.is_v4() => Some(Condition::RemoteRangeV6(start, end)),
+                            _ => None,
+                        })
+                        .collect();
+                    for chunk in ranges.chunks(RANGES_PER_FILTER) {
+                        let mut conditions = conditions.clone();
+                        conditions.extend_from_slice(chunk);
+                        layers.push((*layer, conditions, Some(code.clone())));
+                    }
+                }
+            }
+        }
+    }
+    Ok(layers)
+}
+
+// 断线保护：只允许指定的程序、本机回环连接与DHCP发起出站连接，其余一律阻止
+pub fn kill_switch_plan(apps: &[String]) -> Vec<FilterSpec> {
+    let mut filters = Vec::new();
+    for layer in [Layer::ConnectV4, Layer::ConnectV6] {
+        for app in apps {
+            let file_name = app.rsplit(['\\', '/']).next().unwrap_or(app);
+            filters.push(FilterSpec {
+                name: format!("InviZible: 断线保护允许 {}", file_name),
+                layer,
+                conditions: vec![Condition::App(app.clone())],
+                permit: true,
+                weight: RULE_WEIGHT_BASE,
+                hit: None,
+            });
+        }
+        let dhcp_port = if layer.is_v4() { DHCP_SERVER_PORT } else { DHCPV6_SERVER_PORT };
+        filters.push(FilterSpec {
+            name: "InviZible: 断线保护允许DHCP".to_string(),
+            layer,
+            conditions: vec![Condition::Protocol(IPPROTO_UDP), Condition::RemotePort(dhcp_port)],
+            permit: true,
+            weight: RULE_WEIGHT_BASE,
+            hit: None,
+        });
+        filters.push(FilterSpec {
+            name: "InviZible: 断线保护允许回环连接".to_string(),
+            layer,
+            conditions: vec![Condition::Loopback],
+            permit: true,
+            weight: LOOPBACK_WEIGHT,
+            hit: None,
+        });
+        filters.push(FilterSpec {
+            name: "InviZible: 断线保护阻止".to_string(),
+            layer,
+            conditions: Vec::new(),
+            permit: false,
+            weight: DEFAULT_BLOCK_WEIGHT,
+            hit: None,
+        });
+    }
+    filters
+}
+
+// 按规则与配置方案生成过滤器，返回过滤器与无法翻译的规则说明。
+// 过滤条件由FirewallRule::matcher生成，与程序内评估的匹配条件相同；
+// 第一条匹配的规则生效，由权重体现：越靠前权重越高
+pub fn plan(rules: &[FirewallRule], profile: &FirewallProfile) -> (Vec<FilterSpec>, Vec<String>) {
+    let enabled: Vec<&FirewallRule> = rules.iter().filter(|r| r.enabled).collect();
+    let mut filters = Vec::new();
+    let mut skipped = Vec::new();
+    for (index, rule) in enabled.iter().enumerate() {
+        let layers = match rule_conditions(rule) {
+            Ok(layers) => layers,
+            Err(e) => {
+                skipped.push(format!("{}: {}", rule.name, e));
+                continue;
+            }
+        };
+        let weight = RULE_WEIGHT_BASE + (enabled.len() - index) as u64;
+        for (layer, conditions, country) in layers {
+            filters.push(FilterSpec {
+                name: match &country {
+                    Some(code) => format!("InviZible: {} ({})", rule.name, code),
+                    None => format!("InviZible: {}", rule.name),
+                },
+                layer,
+                conditions,
+                permit: rule.action == RuleAction::Allow,
+                weight,
+                hit: country.map(|code| (rule.id, code)),
+            });
+        }
+    }
+
+    // 严格模式：未被规则允许的连接一律阻止，但保留本机回环连接，否则代理端口也无法使用
+    if *profile == FirewallProfile::Strict {
+        for layer in Layer::ALL {
+            filters.push(FilterSpec {
+                name: "InviZible: 允许回环连接".to_string(),
+                layer,
+                conditions: vec![Condition::Loopback],
+                permit: true,
+                weight: LOOPBACK_WEIGHT,
+                hit: None,
+            });
+            filters.push(FilterSpec {
+                name: "InviZible: 严格模式默认阻止".to_string(),
+                layer,
+                conditions: Vec::new(),
+                permit: false,
+                weight: DEFAULT_BLOCK_WEIGHT,
+                hit: None,
+            });
+        }
+    }
+    (filters, skipped)
+}
+
+// WFP引擎会话。会话为动态会话：关闭句柄（包括程序退出或崩溃）后，其中添加的过滤器由系统自动删除
+pub struct WfpEngine {
+    #[cfg(target_os = "windows")]
+    handle: windows_sys::Win32::Foundation::HANDLE,
+    #[cfg(target_os = "windows")]
+    sublayer: Sublayer,
+    filter_ids: Vec<u64>,
+    #[cfg(target_os = "windows")]
+    hit_filters: std::collections::HashMap<u64, (usize, String)>, // 需要统计命中的过滤器
+    #[cfg(target_os = "windows")]
+    events_since: Option<u64>, // 上次读取阻止事件的时间（FILETIME）
+    #[cfg(target_os = "windows")]
+    event_settings: Option<EventSettings>, // 开启事件记录前的系统设置，停止统计或关闭会话时恢复
+}
+
+impl WfpEngine {
+    // 当前生效的过滤器数量
+    pub fn filter_count(&self) -> usize {
+        self.filter_ids.len()
+    }
+}
+
+#[cfg(target_os = "windows")]
+mod sys {
+    use std::collections::{HashMap, HashSet};
+    use std::ffi::c_void;
+    use std::fs;
+    use std::path::Path;
+    use std::ptr;
+    use std::time::{SystemTime, UNIX_EPOCH};
+    use windows_sys::core::GUID;
+    use windows_sys::Win32::Foundation::{CloseHandle, FILETIME, HANDLE, LUID};
+    use windows_sys::Win32::NetworkManagement::WindowsFilteringPlatform::*;
+    use windows_sys::Win32::Security::Authentication::Identity::{
+        AuditFree, AuditQuerySystemPolicy, AuditSetSystemPolicy, AUDIT_POLICY_INFORMATION, POLICY_AUDIT_EVENT_FAILURE,
+    };
+    use windows_sys::Win32::Security::{
+        AdjustTokenPrivileges, LookupPrivilegeValueW, LUID_AND_ATTRIBUTES, SE_PRIVILEGE_ENABLED, SE_SECURITY_NAME,
+        TOKEN_ADJUST_PRIVILEGES, TOKEN_PRIVILEGES,
+    };
+    use windows_sys::Win32::System::Threading::{GetCurrentProcess, OpenProcessToken};
+
+    use anyhow::Context;
+
+    use super::{anyhow, utils, Condition, EventSettings, FilterSpec, Layer, Result, Sublayer, WfpEngine, EVENT_BACKUP_FILE};
+
+    const RPC_C_AUTHN_WINNT: u32 = 10;
+    // 1601-01-01到1970-01-01之间的100纳秒数
+    const FILETIME_UNIX_EPOCH: u64 = 116_444_736_000_000_000;
+    // 每次读取的阻止事件数
+    const EVENTS_PER_CALL: u32 = 1000;
+    // "筛选平台数据包丢弃"审核子类别。系统只在开启事件收集且该子类别审核失败事件时记录被过滤器阻止的连接
+    const AUDIT_PACKET_DROP: GUID = GUID::from_u128(0x0cce9225_69ae_11d9_bed3_505054503030);
+
+    fn wide(value: &str) -> Vec<u16> {
+        value.encode_utf16().chain(std::iter::once(0)).collect()
+    }
+
+    fn check(code: u32, call: &str) -> Result<()> {
+        if code == 0 {
+            Ok(())
+        } else {
+            Err(anyhow!("{} failed: 0x{:08X}", call, code))
+        }
+    }
+
+    fn filetime_now() -> u64 {
+        let since_epoch = SystemTime::now().duration_since(UNIX_EPOCH).map(|d| d.as_nanos() / 100).unwrap_or(0);
+        FILETIME_UNIX_EPOCH + since_epoch as u64
+    }
+
+    fn filetime(value: u64) -> FILETIME {
+        FILETIME { dwLowDateTime: value as u32, dwHighDateTime: (value >> 32) as u32 }
+    }
+
+    impl Sublayer {
+        // 本程序子层的标识
+        fn key(&self) -> GUID {
+            match self {
+                Sublayer::Rules => GUID::from_u128(0x6c1f3e52_8a4b_4d7e_9f21_3b5c7d9e0a14),
+                Sublayer::KillSwitch => GUID::from_u128(0x6c1f3e52_8a4b_4d7e_9f21_3b5c7d9e0a15),
+            }
+        }
+
+        // 会话、子层与过滤器在"高级安全Windows Defender防火墙"的WFP状态中显示的名称
+        fn display_name(&self) -> &'static str {
+            match self {
+                Sublayer::Rules => "InviZible Pro",
+                Sublayer::KillSwitch => "InviZible Pro 断线保护",
+            }
+        }
+    }
+
+    impl Layer {
+        fn key(&self) -> GUID {
+            match self {
+                Layer::ConnectV4 => FWPM_LAYER_ALE_AUTH_CONNECT_V4,
+                Layer::ConnectV6 => FWPM_LAYER_ALE_AUTH_CONNECT_V6,
+                Layer::AcceptV4 => FWPM_LAYER_ALE_AUTH_RECV_ACCEPT_V4,
+                Layer::AcceptV6 => FWPM_LAYER_ALE_AUTH_RECV_ACCEPT_V6,
+            }
+        }
+    }
+
+    // 由系统分配的应用程序标识，用完后释放
+    struct AppId(*mut FWP_BYTE_BLOB);
+
+    impl AppId {
+        fn from_path(path: &str) -> Result<Self> {
+            let path = wide(path);
+            let mut blob = ptr::null_mut();
+            check(unsafe { FwpmGetAppIdFromFileName0(path.as_ptr(), &mut blob) }, "FwpmGetAppIdFromFileName0")?;
+            Ok(Self(blob))
+        }
+    }
+
+    impl Drop for AppId {
+        fn drop(&mut self) {
+            let mut memory = self.0 as *mut c_void;
+            unsafe { FwpmFreeMemory0(&mut memory) };
+        }
+    }
+
+    impl WfpEngine {
+        // 打开动态会话并添加本程序的子层，需要管理员权限
+        pub fn open(sublayer: Sublayer) -> Result<Self> {
+            let mut name = wide(sublayer.display_name());
+            let mut handle = 0;
+            unsafe {
+                let mut session: FWPM_SESSION0 = std::mem::zeroed();
+                session.displayData.name = name.as_mut_ptr();
+                session.flags = FWPM_SESSION_FLAG_DYNAMIC;
+                check(
+                    FwpmEngineOpen0(ptr::null(), RPC_C_AUTHN_WINNT, ptr::null(), &session, &mut handle),
+                    "FwpmEngineOpen0",
+                )?;
+            }
+            let engine = Self { handle, sublayer, filter_ids: Vec::new(), hit_filters: HashMap::new(), events_since: None, event_settings: None };
+            unsafe {
+                let mut layer: FWPM_SUBLAYER0 = std::mem::zeroed();
+                layer.subLayerKey = sublayer.key();
+                layer.displayData.name = name.as_mut_ptr();
+                layer.weight = 0x8000;
+                check(FwpmSubLayerAdd0(engine.handle, &layer, ptr::null_mut()), "FwpmSubLayerAdd0")?;
+            }
+            Ok(engine)
+        }
+
+        // 在一个事务中用新的过滤器替换当前的过滤器，失败时保持原样。返回因应用程序不存在等原因跳过的过滤器说明
+        pub fn apply(&mut self, filters: &[FilterSpec]) -> Result<Vec<String>> {
+            check(unsafe { FwpmTransactionBegin0(self.handle, 0) }, "FwpmTransactionBegin0")?;
+            match self.replace_filters(filters) {
+                Ok((ids, skipped)) => {
+                    check(unsafe { FwpmTransactionCommit0(self.handle) }, "FwpmTransactionCommit0")?;
+                    self.filter_ids = ids.iter().map(|(id, _)| *id).collect();
+                    self.hit_filters = ids.into_iter().filter_map(|(id, hit)| hit.map(|hit| (id, hit))).collect();
+                    Ok(skipped)
+                }
+                Err(e) => {
+                    unsafe { FwpmTransactionAbort0(self.handle) };
+                    Err(e)
+                }
+            }
+        }
+
+        // 返回新过滤器的ID及其命中统计归属
+        fn replace_filters(&self, filters: &[FilterSpec]) -> Result<(Vec<(u64, Option<(usize, String)>)>, Vec<String>)> {
+            for id in &self.filter_ids {
+                check(unsafe { FwpmFilterDeleteById0(self.handle, *id) }, "FwpmFilterDeleteById0")?;
+            }
+
+            // 应用程序标识要求文件存在，无法获取时跳过该过滤器
+            let mut app_ids: HashMap<String, AppId> = HashMap::new();
+            let mut failed = HashSet::new();
+            let mut skipped = Vec::new();
+            for spec in filters {
+                for condition in &spec.conditions {
+                    if let Condition::App(path) = condition {
+                        if app_ids.contains_key(path) || failed.contains(path) {
+                            continue;
+                        }
+                        match AppId::from_path(path) {
+                            Ok(app_id) => {
+                                app_ids.insert(path.clone(), app_id);
+                            }
+                            Err(e) => {
+                                skipped.push(format!("{}: {:#}", path, e));
+                                failed.insert(path.clone());
+                            }
+                        }
+                    }
+                }
+            }
+
+            let mut ids = Vec::new();
+            for spec in filters {
+                let resolved = spec.conditions.iter().all(|c| match c {
+                    Condition::App(path) => app_ids.contains_key(path),
+                    _ => true,
+                });
+                if resolved {
+                    ids.push((self.add_filter(spec, &app_ids)?, spec.hit.clone()));
+                }
+            }
+            Ok((ids, skipped))
+        }
+
+        fn add_filter(&self, spec: &FilterSpec, app_ids: &HashMap<String, AppId>) -> Result<u64> {
+            let mut name = wide(&spec.name);
+            let mut weight = spec.weight;
+            // 条件值通过指针引用地址数据，需在FwpmFilterAdd0返回前保持有效
+            let mut v4_masks: Vec<Box<FWP_V4_ADDR_AND_MASK>> = Vec::new();
+            let mut v6_masks: Vec<Box<FWP_V6_ADDR_AND_MASK>> = Vec::new();
+            let mut ranges: Vec<Box<FWP_RANGE0>> = Vec::new();
+            let mut v6_bounds: Vec<Box<FWP_BYTE_ARRAY16>> = Vec::new();
+            let mut conditions = Vec::with_capacity(spec.conditions.len());
+            unsafe {
+                for condition in &spec.conditions {
+                    let mut value: FWPM_FILTER_CONDITION0 = std::mem::zeroed();
+                    value.matchType = FWP_MATCH_EQUAL;
+                    match condition {
+                        Condition::App(path) => {
+                            value.fieldKey = FWPM_CONDITION_ALE_APP_ID;
+                            value.conditionValue.r#type = FWP_BYTE_BLOB_TYPE;
+                            value.conditionValue.Anonymous.byteBlob = app_ids[path].0;
+                        }
+                        Condition::Protocol(protocol) => {
+                            value.fieldKey = FWPM_CONDITION_IP_PROTOCOL;
+                            value.conditionValue.r#type = FWP_UINT8;
+                            value.conditionValue.Anonymous.uint8 = *protocol;
+                        }
+                        Condition::LocalPort(port) => {
+                            value.fieldKey = FWPM_CONDITION_IP_LOCAL_PORT;
+                            value.conditionValue.r#type = FWP_UINT16;
+                            value.conditionValue.Anonymous.uint16 = *port;
+                        }
+                        Condition::RemotePort(port) => {
+                            value.fieldKey = FWPM_CONDITION_IP_REMOTE_PORT;
+                            value.conditionValue.r#type = FWP_UINT16;
+                            value.conditionValue.Anonymous.uint16 = *port;
+                        }
+                        Condition::RemoteV4(address, prefix) => {
+                            // WFP中的IPv4地址与掩码为主机字节序
+                            let mask = if *prefix == 0 { 0 } else { u32::MAX << (32 - *prefix as u32) };
+                            let mut addr_mask = Box::new(FWP_V4_ADDR_AND_MASK { addr: u32::from(*address), mask });
+                            value.fieldKey = FWPM_CONDITION_IP_REMOTE_ADDRESS;
+                            value.conditionValue.r#type = FWP_V4_ADDR_MASK;
+                            value.conditionValue.Anonymous.v4AddrMask = &mut *addr_mask;
+                            v4_masks.push(addr_mask);
+                        }
+                        Condition::RemoteV6(address, prefix) => {
+                            let mut addr_mask = Box::new(FWP_V6_ADDR_AND_MASK { addr: address.octets(), prefixLength: *prefix });
+                            value.fieldKey = FWPM_CONDITION_IP_REMOTE_ADDRESS;
+                            value.conditionValue.r#type = FWP_V6_ADDR_MASK;
+                            value.conditionValue.Anonymous.v6AddrMask = &mut *addr_mask;
+                            v6_masks.push(addr_mask);
+                        }
+                        Condition::RemoteRangeV4(start, end) => {
+                            let mut range: Box<FWP_RANGE0> = Box::new(std::mem::zeroed());
+                            range.valueLow.r#type = FWP_UINT32;
+                            range.valueLow.Anonymous.uint32 = u32::from(*start);
+                            range.valueHigh.r#type = FWP_UINT32;
+                            range.valueHigh.Anonymous.uint32 = u32::from(*end);
+                            value.fieldKey = FWPM_CONDITION_IP_REMOTE_ADDRESS;
+                            value.matchType = FWP_MATCH_RANGE;
+                            value.conditionValue.r#type = FWP_RANGE_TYPE;
+                            value.conditionValue.Anonymous.rangeValue = &mut *range;
+                            ranges.push(range);
+                        }
+                        Condition::RemoteRangeV6(start, end) => {
+                            let mut low = Box::new(FWP_BYTE_ARRAY16 { byteArray16: start.octets() });
+                            let mut high = Box::new(FWP_BYTE_ARRAY16 { byteArray16: end.octets() });
+                            let mut range: Box<FWP_RANGE0> = Box::new(std::mem::zeroed());
+                            range.valueLow.r#type = FWP_BYTE_ARRAY16_TYPE;
+                            range.valueLow.Anonymous.byteArray16 = &mut *low;
+                            range.valueHigh.r#type = FWP_BYTE_ARRAY16_TYPE;
+                            range.valueHigh.Anonymous.byteArray16 = &mut *high;
+                            value.fieldKey = FWPM_CONDITION_IP_REMOTE_ADDRESS;
+                            value.matchType = FWP_MATCH_RANGE;
+                            value.conditionValue.r#type = FWP_RANGE_TYPE;
+                            value.conditionValue.Anonymous.rangeValue = &mut *range;
+                            v6_bounds.push(low);
+                            v6_bounds.push(high);
+                            ranges.push(range);
+                        }
+                        Condition::Loopback => {
+                            value.fieldKey = FWPM_CONDITION_FLAGS;
+                            value.matchType = FWP_MATCH_FLAGS_ALL_SET;
+                            value.conditionValue.r#type = FWP_UINT32;
+                            value.conditionValue.Anonymous.uint32 = FWP_CONDITION_FLAG_IS_LOOPBACK;
+                        }
+                    }
+                    conditions.push(value);
+                }
+
+                let mut filter: FWPM_FILTER0 = std::mem::zeroed();
+                filter.displayData.name = name.as_mut_ptr();
+                filter.layerKey = spec.layer.key();
+                filter.subLayerKey = self.sublayer.key();
+                filter.weight.r#type = FWP_UINT64;
+                filter.weight.Anonymous.uint64 = &mut weight;
+                filter.numFilterConditions = conditions.len() as u32;
+                if !conditions.is_empty() {
+                    filter.filterCondition = conditions.as_mut_ptr();
+                }
+                filter.action.r#type = if spec.permit { FWP_ACTION_PERMIT } else { FWP_ACTION_BLOCK };
+
+                let mut id = 0;
+                check(FwpmFilterAdd0(self.handle, &filter, ptr::null_mut(), &mut id), "FwpmFilterAdd0")?;
+                Ok(id)
+            }
+        }
+    }
+
+    // 读写审核策略需要在进程令牌中启用SeSecurityPrivilege（管理员默认拥有但未启用）
+    fn enable_security_privilege() -> Result<()> {
+        unsafe {
+            let mut token = 0;
+            if OpenProcessToken(GetCurrentProcess(), TOKEN_ADJUST_PRIVILEGES, &mut token) == 0 {
+                return Err(anyhow!("OpenProcessToken failed: {}", std::io::Error::last_os_error()));
+            }
+            let mut luid: LUID = std::mem::zeroed();
+            let mut ok = LookupPrivilegeValueW(ptr::null(), SE_SECURITY_NAME, &mut luid) != 0;
+            if ok {
+                let privileges = TOKEN_PRIVILEGES {
+                    PrivilegeCount: 1,
+                    Privileges: [LUID_AND_ATTRIBUTES { Luid: luid, Attributes: SE_PRIVILEGE_ENABLED }],
+                };
+                ok = AdjustTokenPrivileges(token, 0, &privileges, 0, ptr::null_mut(), ptr::null_mut()) != 0;
+            }
+            let error = std::io::Error::last_os_error();
+            CloseHandle(token);
+            if ok {
+                Ok(())
+            } else {
+                Err(anyhow!("Failed to enable SeSecurityPrivilege: {}", error))
+            }
+        }
+    }
+
+    fn packet_drop_audit() -> Result<u32> {
+        unsafe {
+            let mut policy: *mut AUDIT_POLICY_INFORMATION = ptr::null_mut();
+            if AuditQuerySystemPolicy(&AUDIT_PACKET_DROP, 1, &mut policy) == 0 {
+                return Err(anyhow!("AuditQuerySystemPolicy failed: {}", std::io::Error::last_os_error()));
+            }
+            let auditing = (*policy).AuditingInformation;
+            AuditFree(policy as *const c_void);
+            Ok(auditing)
+        }
+    }
+
+    fn set_packet_drop_audit(auditing: u32) -> Result<()> {
+        unsafe {
+            let policy = AUDIT_POLICY_INFORMATION {
+                AuditSubCategoryGuid: AUDIT_PACKET_DROP,
+                AuditingInformation: auditing,
+                AuditCategoryGuid: std::mem::zeroed(),
+            };
+            if AuditSetSystemPolicy(&policy, 1) == 0 {
+                return Err(anyhow!("AuditSetSystemPolicy failed: {}", std::io::Error::last_os_error()));
+            }
+        }
+        Ok(())
+    }
+
+    fn set_collect_net_events(handle: HANDLE, enabled: u32) -> Result<()> {
+        unsafe {
+            let mut value: FWP_VALUE0 = std::mem::zeroed();
+            value.r#type = FWP_UINT32;
+            value.Anonymous.uint32 = enabled;
+            check(FwpmEngineSetOption0(handle, FWPM_ENGINE_COLLECT_NET_EVENTS, &value), "FwpmEngineSetOption0")
+        }
+    }
+
+    impl WfpEngine {
+        fn collect_net_events(&self) -> Result<u32> {
+            unsafe {
+                let mut value: *mut FWP_VALUE0 = ptr::null_mut();
+                check(FwpmEngineGetOption0(self.handle, FWPM_ENGINE_COLLECT_NET_EVENTS, &mut value), "FwpmEngineGetOption0")?;
+                let enabled = (*value).Anonymous.uint32;
+                let mut memory = value as *mut c_void;
+                FwpmFreeMemory0(&mut memory);
+                Ok(enabled)
+            }
+        }
+
+        // 开启阻止事件的记录。原来的设置先写入备份文件再修改；
+        // 备份已存在时说明上次未能恢复，其中才是原来的设置
+        fn enable_event_collection(&mut self) -> Result<()> {
+            if self.event_settings.is_some() {
+                return Ok(());
+            }
+            enable_security_privilege()?;
+            let path = utils::get_config_path(EVENT_BACKUP_FILE)?;
+            let original: EventSettings = if Path::new(&path).exists() {
+                utils::load_versioned_config(&path)?
+            } else {
+                let original = EventSettings {
+                    collect_net_events: self.collect_net_events()?,
+                    packet_drop_audit: packet_drop_audit()?,
+                };
+                utils::save_versioned_config(&original, &path)?;
+                original
+            };
+            self.event_settings = Some(original);
+            let result = set_collect_net_events(self.handle, 1)
+                .and_then(|_| set_packet_drop_audit(original.packet_drop_audit | POLICY_AUDIT_EVENT_FAILURE as u32));
+            if result.is_err() {
+                let _ = self.restore_event_collection();
+            }
+            result
+        }
+
+        // 恢复开启事件记录前的设置，全部恢复后删除备份
+        fn restore_event_collection(&mut self) -> Result<()> {
+            let original = match self.event_settings.take() {
+                Some(original) => original,
+                None => return Ok(()),
+            };
+            self.events_since = None;
+            restore_event_settings(self.handle, &original)
+        }
+
+        // 停止统计命中次数并恢复系统设置
+        pub fn stop_hit_collection(&mut self) -> Result<()> {
+            self.restore_event_collection()
+        }
+    }
+
+    fn restore_event_settings(handle: HANDLE, original: &EventSettings) -> Result<()> {
+        set_collect_net_events(handle, original.collect_net_events)?;
+        enable_security_privilege()?;
+        set_packet_drop_audit(original.packet_drop_audit)?;
+        let path = utils::get_config_path(EVENT_BACKUP_FILE)?;
+        fs::remove_file(&path).context("Failed to remove WFP event backup")
+    }
+
+    // 程序启动时按备份恢复上次未能恢复的设置，返回是否进行了恢复
+    pub fn restore_leftover_event_settings() -> Result<bool> {
+        let path = utils::get_config_path(EVENT_BACKUP_FILE)?;
+        if !Path::new(&path).exists() {
+            return Ok(false);
+        }
+        let original: EventSettings = utils::load_versioned_config(&path)?;
+        let mut handle = 0;
+        check(
+            unsafe { FwpmEngineOpen0(ptr::null(), RPC_C_AUTHN_WINNT, ptr::null(), ptr::null(), &mut handle) },
+            "FwpmEngineOpen0",
+        )?;
+        let result = restore_event_settings(handle, &original);
+        unsafe { FwpmEngineClose0(handle) };
+        result.map(|_| true)
+    }
+
+    impl WfpEngine {
+        // 取出上次调用后被需要统计的过滤器阻止的连接，每次阻止返回一条归属（规则ID与国家代码）。
+        // 首次调用时开启系统的阻止事件记录并记下开始时间
+        pub fn take_hits(&mut self) -> Result<Vec<(usize, String)>> {
+            self.enable_event_collection()?;
+            let now = filetime_now();
+            let since = match self.events_since.replace(now) {
+                Some(since) => since,
+                None => return Ok(Vec::new()),
+            };
+            if self.hit_filters.is_empty() {
+                return Ok(Vec::new());
+            }
+
+            let mut enum_handle = 0;
+            unsafe {
+                let mut template: FWPM_NET_EVENT_ENUM_TEMPLATE0 = std::mem::zeroed();
+                template.startTime = filetime(since);
+                template.endTime = filetime(now);
+                check(FwpmNetEventCreateEnumHandle0(self.handle, &template, &mut enum_handle), "FwpmNetEventCreateEnumHandle0")?;
+            }
+            let mut hits = Vec::new();
+            let result = loop {
+                let mut entries: *mut *mut FWPM_NET_EVENT0 = ptr::null_mut();
+                let mut count = 0;
+                let code = unsafe { FwpmNetEventEnum0(self.handle, enum_handle, EVENTS_PER_CALL, &mut entries, &mut count) };
+                if let Err(e) = check(code, "FwpmNetEventEnum0") {
+                    break Err(e);
+                }
+                unsafe {
+                    for index in 0..count as usize {
+                        let event = &**entries.add(index);
+                        if event.r#type != FWPM_NET_EVENT_TYPE_CLASSIFY_DROP || event.Anonymous.classifyDrop.is_null() {
+                            continue;
+                        }
+                        if let Some(hit) = self.hit_filters.get(&(*event.Anonymous.classifyDrop).filterId) {
+                            hits.push(hit.clone());
+                        }
+                    }
+                    if !entries.is_null() {
+                        let mut memory = entries as *mut c_void;
+                        FwpmFreeMemory0(&mut memory);
+                    }
+                }
+                if count < EVENTS_PER_CALL {
+                    break Ok(hits);
+                }
+            };
+            unsafe { FwpmNetEventDestroyEnumHandle0(self.handle, enum_handle) };
+            result
+        }
+    }
+
+    impl Drop for WfpEngine {
+        fn drop(&mut self) {
+            let _ = self.restore_event_collection();
+            unsafe { FwpmEngineClose0(self.handle) };
+        }
+    }
+}
+
+#[cfg(not(target_os = "windows"))]
+impl WfpEngine {
+    pub fn open(_sublayer: Sublayer) -> Result<Self> {
+        Err(anyhow!("Windows Filtering Platform is only available on Windows"))
+    }
+
+    pub fn apply(&mut self, _filters: &[FilterSpec]) -> Result<Vec<String>> {
+        Err(anyhow!("Windows Filtering Platform is only available on Windows"))
+    }
+
+    pub fn take_hits(&mut self) -> Result<Vec<(usize, String)>> {
+        Err(anyhow!("Windows Filtering Platform is only available on Windows"))
+    }
+
+    pub fn stop_hit_collection(&mut self) -> Result<()> {
+        Ok(())
+    }
+}
+
+#[cfg(target_os = "windows")]
+pub use sys::restore_leftover_event_settings;
+
+#[cfg(not(target_os = "windows"))]
+pub fn restore_leftover_event_settings() -> Result<bool> {
+    Ok(false)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::firewall::{RuleDirection, RuleType};
+
+    #[test]
+    fn plans_prefix_protocol_and_direction() {
+        let mut address = FirewallRule::new(1, "阻止内网", RuleType::Address);
+        address.address = Some("10.0.0.0/8".to_string());
+        let mut port = FirewallRule::new(2, "阻止DNS", RuleType::Port);
+        port.port = Some(53);
+        port.protocol = Some("UDP".to_string());
+        port.direction = RuleDirection::Outbound;
+
+        let (filters, skipped) = plan(&[address, port], &FirewallProfile::Standard);
+        assert!(skipped.is_empty());
+        let planned: Vec<(Layer, Vec<Condition>)> = filters.into_iter().map(|f| (f.layer, f.conditions)).collect();
+        let cidr = vec![Condition::RemoteV4(Ipv4Addr::new(10, 0, 0, 0), 8)];
+        let udp = vec![Condition::Protocol(IPPROTO_UDP), Condition::RemotePort(53)];
+        assert_eq!(planned, [
+            (Layer::ConnectV4, cidr.clone()),
+            (Layer::AcceptV4, cidr),
+            (Layer::ConnectV4, udp.clone()),
+            (Layer::ConnectV6, udp),
+        ]);
+    }
+}